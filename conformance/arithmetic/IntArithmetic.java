@@ -0,0 +1,16 @@
+public class IntArithmetic {
+    static int[] values = { 0, 1, -1, 7, -7, 100, Integer.MAX_VALUE, Integer.MIN_VALUE };
+
+    public static void main(String[] args) {
+        for (int a : values) {
+            for (int b : values) {
+                System.out.println(a + " " + b + ": " + (a + b) + " " + (a - b) + " " + (a * b));
+                if (b != 0) {
+                    System.out.println("  " + (a / b) + " " + (a % b));
+                }
+                System.out.println("  " + (a & b) + " " + (a | b) + " " + (a ^ b) + " " + (a << (b & 31)) + " " + (a >> (b & 31)) + " " + (a >>> (b & 31)));
+            }
+            System.out.println((byte) a + " " + (short) a + " " + (int) (char) a + " " + (-a) + " " + (~a));
+        }
+    }
+}
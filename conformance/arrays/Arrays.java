@@ -0,0 +1,38 @@
+public class Arrays {
+    static void sort(int[] values) {
+        for (int i = 1; i < values.length; i++) {
+            int value = values[i];
+            int j = i - 1;
+            while (j >= 0 && values[j] > value) {
+                values[j + 1] = values[j];
+                j--;
+            }
+            values[j + 1] = value;
+        }
+    }
+
+    public static void main(String[] args) {
+        int[] values = { 5, -2, 9, 0, 3, 3, -7 };
+        sort(values);
+        for (int value : values) {
+            System.out.print(value + " ");
+        }
+        System.out.println();
+
+        int[][] grid = new int[3][];
+        for (int i = 0; i < grid.length; i++) {
+            grid[i] = new int[4];
+            for (int j = 0; j < grid[i].length; j++) {
+                grid[i][j] = i * j;
+            }
+        }
+        System.out.println(grid[2][3] + grid[1][2]);
+
+        char[] letters = { 'j', 'v', 'm' };
+        System.out.println(letters);
+        String[] words = new String[2];
+        System.out.println(words[0] + " " + words.length);
+        byte[] bytes = { (byte) 200, 100 };
+        System.out.println(bytes[0] + bytes[1]);
+    }
+}
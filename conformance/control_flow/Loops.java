@@ -0,0 +1,57 @@
+public class Loops {
+    static String dense(int value) {
+        switch (value) {
+            case 0: return "zero";
+            case 1: return "one";
+            case 2: return "two";
+            case 3: return "three";
+            default: return "many";
+        }
+    }
+
+    static int sparse(int value) {
+        switch (value) {
+            case -1000: return 1;
+            case 7: return 2;
+            case 123456: return 3;
+            default: return 0;
+        }
+    }
+
+    public static void main(String[] args) {
+        int sum = 0;
+        for (int i = 0; i < 10; i++) {
+            if (i % 3 == 0) {
+                continue;
+            }
+            sum += i;
+        }
+        System.out.println(sum);
+
+        int n = 27;
+        int steps = 0;
+        while (n != 1) {
+            n = n % 2 == 0 ? n / 2 : 3 * n + 1;
+            steps++;
+        }
+        System.out.println(steps);
+
+        outer:
+        for (int i = 0; i < 5; i++) {
+            for (int j = 0; j < 5; j++) {
+                if (i * j > 6) {
+                    System.out.println("break at " + i + "," + j);
+                    break outer;
+                }
+            }
+        }
+
+        int k = 0;
+        do {
+            System.out.print(dense(k) + " ");
+            k++;
+        } while (k < 5);
+        System.out.println();
+        System.out.println(sparse(-1000) + sparse(7) + sparse(123456) + sparse(8));
+    }
+}
@@ -0,0 +1,50 @@
+class ValidationException extends Exception {
+    ValidationException(String message) {
+        super(message);
+    }
+}
+
+public class Exceptions {
+    static int check(int value) throws ValidationException {
+        if (value < 0) {
+            throw new ValidationException("negative: " + value);
+        }
+        return value;
+    }
+
+    static int divide(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            System.out.println("caught " + e.getMessage());
+            return 0;
+        } finally {
+            System.out.println("finally " + a);
+        }
+    }
+
+    public static void main(String[] args) {
+        System.out.println(divide(10, 2));
+        System.out.println(divide(1, 0));
+        try {
+            check(5);
+            check(-3);
+            System.out.println("not reached");
+        } catch (ValidationException e) {
+            System.out.println(e.getMessage());
+        }
+        int[] values = new int[2];
+        try {
+            values[2] = 1;
+        } catch (ArrayIndexOutOfBoundsException e) {
+            System.out.println("index");
+        }
+        Object text = "text";
+        try {
+            Integer number = (Integer) text;
+            System.out.println(number);
+        } catch (ClassCastException e) {
+            System.out.println("cast");
+        }
+    }
+}
@@ -0,0 +1,6 @@
+public class Uncaught {
+    public static void main(String[] args) {
+        System.out.println("before");
+        throw new IllegalStateException("boom");
+    }
+}
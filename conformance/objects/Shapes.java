@@ -0,0 +1,82 @@
+interface Shape {
+    int area();
+
+    default String describe() {
+        return name() + " with area " + area();
+    }
+
+    String name();
+}
+
+abstract class Polygon implements Shape {
+    static int created;
+
+    Polygon() {
+        created++;
+    }
+
+    public String name() {
+        return "polygon";
+    }
+}
+
+class Rectangle extends Polygon {
+    private final int width;
+    private final int height;
+
+    Rectangle(int width, int height) {
+        this.width = width;
+        this.height = height;
+    }
+
+    public int area() {
+        return width * height;
+    }
+
+    public String name() {
+        return "rectangle";
+    }
+}
+
+class Square extends Rectangle {
+    Square(int side) {
+        super(side, side);
+    }
+
+    public String name() {
+        return "square, a " + super.name();
+    }
+}
+
+class Triangle extends Polygon {
+    private final int base;
+    private final int height;
+
+    Triangle(int base, int height) {
+        this.base = base;
+        this.height = height;
+    }
+
+    public int area() {
+        return base * height / 2;
+    }
+
+    public String toString() {
+        return "Triangle(" + base + ", " + height + ")";
+    }
+}
+
+public class Shapes {
+    public static void main(String[] args) {
+        Shape[] shapes = { new Rectangle(2, 3), new Square(4), new Triangle(5, 3) };
+        int total = 0;
+        for (Shape shape : shapes) {
+            System.out.println(shape.describe());
+            total += shape.area();
+        }
+        System.out.println(total + " in " + Polygon.created + " shapes");
+        System.out.println(shapes[2]);
+        System.out.println(shapes[1] instanceof Rectangle);
+        System.out.println(shapes[2] instanceof Rectangle);
+    }
+}
@@ -0,0 +1,19 @@
+public class Concatenation {
+    public static void main(String[] args) {
+        String name = "world";
+        int count = 3;
+        char mark = '!';
+        boolean flag = false;
+        Object nothing = null;
+        System.out.println("hello " + name + mark);
+        System.out.println(count + count + " items, " + count + count);
+        System.out.println("flag=" + flag + ", nothing=" + nothing);
+        String built = "";
+        for (int i = 0; i < 5; i++) {
+            built = built + i;
+        }
+        System.out.println(built);
+        System.out.println('a' + 1);
+        System.out.println("" + 'a' + 1);
+    }
+}
@@ -0,0 +1,5 @@
+//! exports the C interface of rjvm, declared in `include/rjvm.h`, from a shared library.
+
+extern crate rjvm;
+
+pub use rjvm::ffi::*;
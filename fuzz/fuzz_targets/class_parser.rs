@@ -0,0 +1,23 @@
+//! parses arbitrary bytes as a class file and reads what the runtime reads from every class it
+//! loads. seed the corpus with the classes of `sample/`:
+//!
+//! ```text
+//! mkdir -p fuzz/corpus/class_parser && cp sample/*.class fuzz/corpus/class_parser
+//! cargo +nightly fuzz run class_parser
+//! ```
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rjvm::java::class_file::ClassFile;
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(class) = ClassFile::parse(bytes) {
+        let _ = (class.get_class_name(), class.get_super_class_name(), class.get_source_file(), class.get_module());
+        let _ = class.field_signatures();
+        for method in class.methods.iter() {
+            let _ = method.get_signature();
+            if let Some(code) = method.get_code() {
+                let _ = (code.instructions_with_pc(), code.get_line_numbers());
+            }
+        }
+    }
+});
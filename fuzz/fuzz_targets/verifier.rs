@@ -0,0 +1,16 @@
+//! decodes and verifies the methods of arbitrary class files like linking does, and builds their
+//! control flow graphs. seeded like `class_parser`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rjvm::java::analysis::ControlFlowGraph;
+use rjvm::java::class_file::ClassFile;
+use rjvm::java::runtime::DecodedMethod;
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(class) = ClassFile::parse(bytes) {
+        for method in class.methods.iter() {
+            let _ = DecodedMethod::decode(&class, method);
+            let _ = ControlFlowGraph::build(method);
+        }
+    }
+});
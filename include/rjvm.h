@@ -0,0 +1,120 @@
+/* generated from src/ffi.rs, do not edit. */
+#ifndef RJVM_H
+#define RJVM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct rjvm_runtime rjvm_runtime;
+
+typedef struct rjvm_value {
+    int32_t kind;
+    int64_t value;
+} rjvm_value;
+
+typedef int32_t (*rjvm_native)(void *user_data, const rjvm_value *arguments, size_t count, rjvm_value *result);
+
+#define RJVM_OK 0
+
+/* the java code threw an exception, the result holds the throwable. */
+#define RJVM_EXCEPTION 1
+
+/* the vm failed, e.g. a class or method was not found. */
+#define RJVM_ERROR 2
+
+/* a null pointer, a string which is not utf-8 or a value of an unknown kind was passed. */
+#define RJVM_INVALID_ARGUMENT 3
+
+/* the kinds of an `rjvm_value`. */
+#define RJVM_VOID 0
+
+#define RJVM_NULL 1
+
+/* an int, boolean, byte, char or short, in the lower 32 bits. */
+#define RJVM_INT 2
+
+#define RJVM_LONG 3
+
+/* an object, the value is its handle. the handle of a collected object stays invalid, it never
+   refers to an object allocated later. */
+#define RJVM_REFERENCE 4
+
+/* creates a runtime for the class file in `bytes`. the bytes are copied and freed with the runtime.
+   returns null if they are not a valid class file.
+
+   # Safety
+
+   `bytes` must point to `length` readable bytes. */
+rjvm_runtime *rjvm_runtime_new(const uint8_t *bytes, size_t length);
+
+/* destroys a runtime. does nothing for null.
+
+   # Safety
+
+   `runtime` must be null or come from `rjvm_runtime_new` and not be freed yet. it must not be
+   used afterwards. */
+void rjvm_runtime_free(rjvm_runtime *runtime);
+
+/* loads the classes in the directory `path` and its subdirectories and adds it to the classpath.
+   returns the number of classes loaded, or -1 for an invalid path.
+
+   # Safety
+
+   `runtime` must be null or a live runtime not used by another thread. `path` must be null or a
+   nul-terminated string. */
+int64_t rjvm_add_classpath(rjvm_runtime *runtime, const char *path);
+
+/* implements a static method with a function of the host, which gets `user_data` with every call.
+   natives take precedence over the bytecode of loaded classes.
+
+   # Safety
+
+   `runtime` must be null or a live runtime not used by another thread. the names must be null or
+   nul-terminated strings. `native` is called with `user_data` as long as the runtime lives, so
+   both must stay valid until it is freed. */
+int32_t rjvm_register_native(rjvm_runtime *runtime, const char *class_name, const char *method_name, const char *descriptor, rjvm_native native, void *user_data);
+
+/* runs a static method with `count` arguments and stores its return value in `result`, or the
+   throwable for `RJVM_EXCEPTION`. references in results stay valid until the next call into
+   java, which may collect them.
+
+   # Safety
+
+   `runtime` must be null or a live runtime not used by another thread. the names must be null or
+   nul-terminated strings. `arguments` must be null or point to `count` values, `result` must be null or
+   writable. */
+int32_t rjvm_invoke_static(rjvm_runtime *runtime, const char *class_name, const char *method_name, const char *descriptor, const rjvm_value *arguments, size_t count, rjvm_value *result);
+
+/* stores the interned string with the utf-8 `value` in `result`. interned strings are never collected.
+
+   # Safety
+
+   `runtime` must be null or a live runtime not used by another thread. `value` must be null or a
+   nul-terminated string, `result` must be null or writable. */
+int32_t rjvm_string_new(rjvm_runtime *runtime, const char *value, rjvm_value *result);
+
+/* the value of a `java.lang.String` as utf-8, or null if `string` is not a string.
+   the text belongs to the runtime and stays valid until the next call of this function.
+
+   # Safety
+
+   `runtime` must be null or a live runtime not used by another thread. */
+const char *rjvm_string_value(rjvm_runtime *runtime, rjvm_value string);
+
+/* the message of the last failed call, the stack trace for an exception. null if the last call
+   succeeded. belongs to the runtime and stays valid until the next call.
+
+   # Safety
+
+   `runtime` must be null or a live runtime not used by another thread. */
+const char *rjvm_last_error(const rjvm_runtime *runtime);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,10 @@
+class Asserts {
+    static int check(int x) {
+        assert x > 0 : "x must be positive";
+        return x;
+    }
+
+    static int sameClass() {
+        return Asserts.class == Asserts.class ? 1 : 0;
+    }
+}
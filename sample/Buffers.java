@@ -0,0 +1,75 @@
+import java.io.RandomAccessFile;
+import java.nio.ByteBuffer;
+import java.nio.ByteOrder;
+import java.nio.MappedByteBuffer;
+import java.nio.channels.FileChannel;
+import sun.misc.Unsafe;
+
+// direct byte buffers, memory mapped files and raw memory
+class Buffers {
+    static int[] direct() {
+        ByteBuffer buffer = ByteBuffer.allocateDirect(16);
+        buffer.putInt(0x01020304);
+        buffer.order(ByteOrder.LITTLE_ENDIAN).putShort((short) 0x0506);
+        buffer.put((byte) -1);
+        buffer.flip();
+        ByteBuffer slice = buffer.position(4).slice();
+        return new int[] {
+            buffer.isDirect() ? 1 : 0, buffer.limit(), buffer.getInt(0), buffer.get(0),
+            slice.get(0), slice.get(1), slice.get(2), slice.remaining(), slice.order() == ByteOrder.BIG_ENDIAN ? 1 : 0,
+        };
+    }
+
+    static long longs(boolean direct, long value) {
+        ByteBuffer buffer = direct ? ByteBuffer.allocateDirect(12) : ByteBuffer.allocate(12);
+        buffer.putInt(7).putLong(value);
+        buffer.rewind();
+        buffer.getInt();
+        return buffer.getLong();
+    }
+
+    static ByteBuffer allocate(int capacity) {
+        return ByteBuffer.allocateDirect(capacity);
+    }
+
+    static void overflow() {
+        ByteBuffer.allocateDirect(2).putInt(1);
+    }
+
+    static void readOnly() {
+        ByteBuffer.allocateDirect(2).asReadOnlyBuffer().put((byte) 1);
+    }
+
+    static String read(String path) throws Exception {
+        RandomAccessFile file = new RandomAccessFile(path, "r");
+        FileChannel channel = file.getChannel();
+        MappedByteBuffer buffer = channel.map(FileChannel.MapMode.READ_ONLY, 0, channel.size());
+        file.close();
+        byte[] bytes = new byte[buffer.remaining()];
+        buffer.get(bytes);
+        return new String(bytes);
+    }
+
+    static int write(String path, long size) throws Exception {
+        RandomAccessFile file = new RandomAccessFile(path, "rw");
+        MappedByteBuffer buffer = file.getChannel().map(FileChannel.MapMode.READ_WRITE, 0, size);
+        int first = buffer.getInt();
+        buffer.putInt(first);
+        buffer.force();
+        file.close();
+        return first;
+    }
+
+    static int unsafe(long size) {
+        Unsafe unsafe = Unsafe.getUnsafe();
+        long address = unsafe.allocateMemory(size);
+        unsafe.putInt(address, 42);
+        int value = unsafe.getInt(address);
+        unsafe.freeMemory(address);
+        return value;
+    }
+
+    static int peek(long address) {
+        return Unsafe.getUnsafe().getByte(address);
+    }
+}
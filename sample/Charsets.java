@@ -0,0 +1,35 @@
+import java.io.UnsupportedEncodingException;
+import java.nio.charset.Charset;
+import java.nio.charset.StandardCharsets;
+
+// String.getBytes and new String(bytes) with the standard charsets
+class Charsets {
+    static final String TEXT = "grüß";
+
+    static byte[] encode() throws UnsupportedEncodingException {
+        byte[][] parts = { TEXT.getBytes(), TEXT.getBytes("ISO-8859-1"), TEXT.getBytes(StandardCharsets.UTF_16) };
+        byte[] all = new byte[parts[0].length + parts[1].length + parts[2].length];
+        int next = 0;
+        for (byte[] part : parts) {
+            for (byte b : part) {
+                all[next++] = b;
+            }
+        }
+        return all;
+    }
+
+    static String decode() throws UnsupportedEncodingException {
+        String utf8 = new String(new byte[] { 103, 114, -61, -68, -61, -97 });
+        String latin1 = new String(new byte[] { 103, 114, -4, -33 }, "latin1");
+        String utf16 = new String(TEXT.getBytes(StandardCharsets.UTF_16LE), Charset.forName("UTF-16LE"));
+        return utf8 + " " + latin1 + " " + utf16 + " " + StandardCharsets.UTF_16LE.name();
+    }
+
+    static byte[] byName(String name) throws UnsupportedEncodingException {
+        return TEXT.getBytes(name);
+    }
+
+    static byte[] forName(String name) {
+        return TEXT.getBytes(Charset.forName(name));
+    }
+}
@@ -0,0 +1,55 @@
+import java.util.function.IntUnaryOperator;
+
+class CheckpointNode {
+    int value;
+    CheckpointNode next;
+
+    CheckpointNode(int value, CheckpointNode next) {
+        this.value = value;
+        this.next = next;
+    }
+}
+
+public class Checkpoint {
+    static int initialized;
+    static int[] counts = new int[4];
+    static String name = "checkpoint";
+    static CheckpointNode head;
+    static IntUnaryOperator scale;
+
+    static {
+        initialized++;
+    }
+
+    public static int initialized() {
+        return initialized;
+    }
+
+    public static int add(int value) {
+        head = new CheckpointNode(value, head);
+        int remainder = value % 4;
+        counts[remainder] = counts[remainder] + 1;
+        return sum();
+    }
+
+    public static int sum() {
+        int sum = 0;
+        for (CheckpointNode node = head; node != null; node = node.next) {
+            sum += node.value;
+        }
+        return sum;
+    }
+
+    public static int count(int remainder) {
+        return counts[remainder];
+    }
+
+    public static int capture(int factor) {
+        scale = x -> x * factor;
+        return scale.applyAsInt(sum());
+    }
+
+    public static int scaled(int x) {
+        return scale.applyAsInt(x);
+    }
+}
@@ -0,0 +1,52 @@
+import java.lang.ref.Cleaner;
+
+public class Cleanups {
+    static final Cleaner CLEANER = Cleaner.create();
+    static int cleaned;
+    static int finalized;
+
+    static class Finalized {
+        protected void finalize() {
+            finalized++;
+        }
+    }
+
+    static int cleanedSoFar() {
+        return cleaned;
+    }
+
+    static int finalizedSoFar() {
+        return finalized;
+    }
+
+    static int unreachable(int n) {
+        for (int i = 0; i < n; i++) {
+            CLEANER.register(new Object(), () -> cleaned++);
+        }
+        System.gc();
+        return cleanedSoFar();
+    }
+
+    static int reachable(int n) {
+        Object resource = new Object();
+        CLEANER.register(resource, () -> cleaned++);
+        System.gc();
+        int count = cleanedSoFar();
+        return resource == null ? -1 : count;
+    }
+
+    static int explicit(int n) {
+        Cleaner.Cleanable cleanable = CLEANER.register(new Object(), () -> cleaned += 10);
+        cleanable.clean();
+        cleanable.clean();
+        return cleaned;
+    }
+
+    static int finalizers(int n) {
+        for (int i = 0; i < n; i++) {
+            new Finalized();
+        }
+        System.gc();
+        return finalizedSoFar();
+    }
+}
@@ -0,0 +1,10 @@
+// the clocks of System
+class Clocks {
+    static long millis() {
+        return System.currentTimeMillis();
+    }
+
+    static long nanos() {
+        return System.nanoTime();
+    }
+}
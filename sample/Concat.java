@@ -0,0 +1,36 @@
+class ConcatPoint {
+    int x;
+    int y;
+
+    ConcatPoint(int x, int y) {
+        this.x = x;
+        this.y = y;
+    }
+
+    public String toString() {
+        return "(" + x + ", " + y + ")";
+    }
+}
+
+public class Concat {
+    static String greet(String name) {
+        return "Hello, " + name + "!";
+    }
+
+    static String primitives(int i, long l, char c, boolean z, byte b, short s) {
+        return i + "/" + l + "/" + c + "/" + z + "/" + b + "/" + s;
+    }
+
+    static String nothing() {
+        String missing = null;
+        return "value: " + missing;
+    }
+
+    static String point(int x) {
+        return "point " + new ConcatPoint(x, x + 1);
+    }
+
+    static String tagged(int x) {
+        return "\u0001" + x + "\u0002";
+    }
+}
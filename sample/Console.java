@@ -0,0 +1,28 @@
+class Point {
+    private final int x;
+    private final int y;
+
+    Point(int x, int y) {
+        this.x = x;
+        this.y = y;
+    }
+
+    public String toString() {
+        return "Point[" + x + ", " + y + "]";
+    }
+}
+
+public class Console {
+    public static void main(String[] args) {
+        System.out.print("hello ");
+        System.out.println(42);
+        System.out.print(true);
+        System.out.print(' ');
+        System.out.print('x');
+        System.out.print(-7);
+        System.out.print(' ');
+        System.out.println((Object) null);
+        System.out.println(new Point(1, 2));
+        System.err.println("oops");
+    }
+}
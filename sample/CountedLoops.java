@@ -0,0 +1,63 @@
+public class CountedLoops {
+    static int[] filled(int n) {
+        int[] values = new int[n];
+        for (int i = 0; i < values.length; i++) {
+            values[i] = i;
+        }
+        return values;
+    }
+
+    static int sum(int n) {
+        int[] values = filled(n);
+        int sum = 0;
+        for (int i = 0; i < values.length; i++) {
+            sum += values[i];
+        }
+        return sum;
+    }
+
+    static int strided(int n) {
+        int[] values = filled(n);
+        int sum = 0;
+        for (int i = 0; i < values.length; i += 2) {
+            sum += values[i];
+        }
+        return sum;
+    }
+
+    static int shifted(int n) {
+        int[] values = filled(4);
+        int sum = 0;
+        for (int i = n; i < values.length; i++) {
+            sum += values[i];
+        }
+        return sum;
+    }
+
+    static int incrementedFirst(int n) {
+        int[] values = filled(n);
+        int sum = 0;
+        for (int i = 0; i < values.length; i++) {
+            if (values[i] % 2 == 0) {
+                i++;
+            }
+            sum += values[i];
+        }
+        return sum;
+    }
+
+    static int nested(int n) {
+        int[][] rows = new int[n][];
+        for (int i = 0; i < rows.length; i++) {
+            rows[i] = filled(i);
+        }
+        int sum = 0;
+        for (int i = 0; i < rows.length; i++) {
+            int[] row = rows[i];
+            for (int j = 0; j < row.length; j++) {
+                sum += row[j];
+            }
+        }
+        return sum;
+    }
+}
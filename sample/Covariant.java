@@ -0,0 +1,51 @@
+interface Shape {
+}
+
+class Square implements Shape {
+}
+
+class Circle implements Shape {
+}
+
+class Covariant {
+    static int store(int which) {
+        Object[] shapes = new Square[1];
+        if (which == 0) {
+            shapes[0] = new Square();
+        } else if (which == 1) {
+            shapes[0] = null;
+        } else {
+            shapes[0] = new Circle();
+        }
+        return shapes.length;
+    }
+
+    static int storeCaught() {
+        try {
+            return store(2);
+        } catch (ArrayStoreException e) {
+            return -1;
+        }
+    }
+
+    static int interfaces() {
+        Shape[] shapes = new Shape[2];
+        shapes[0] = new Square();
+        shapes[1] = new Circle();
+        return shapes[1] != null ? 2 : 0;
+    }
+
+    static int nested(int which) {
+        Object[] arrays = which == 0 ? new int[1][] : new Object[1];
+        arrays[0] = new int[3];
+        int[][] ints = new int[1][];
+        ints[0] = new int[4];
+        return ints[0].length;
+    }
+
+    static int nestedWrong() {
+        Object[] arrays = new int[1][];
+        arrays[0] = new byte[3];
+        return 0;
+    }
+}
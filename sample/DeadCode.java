@@ -0,0 +1,61 @@
+// the fixture of the dead code analysis: a handler whose range cannot throw, a handler an earlier
+// one shadows, methods nothing calls and overrides reached through virtual calls.
+class DeadCode {
+    interface Action {
+        int act();
+    }
+
+    static class Twice implements Action {
+        public int act() {
+            return 2;
+        }
+    }
+
+    static class Never implements Action {
+        public int act() {
+            return 3;
+        }
+
+        public String toString() {
+            return "never";
+        }
+    }
+
+    static int quiet() {
+        int x;
+        try {
+            x = 1;
+        } catch (RuntimeException e) {
+            x = 2;
+        }
+        return x;
+    }
+
+    static int nested(int[] values) {
+        try {
+            try {
+                return values[0];
+            } catch (Exception e) {
+                return -1;
+            }
+        } catch (RuntimeException e) {
+            return -2;
+        }
+    }
+
+    static int run(Action action) {
+        return action.act() + quiet() + nested(new int[] { 1 });
+    }
+
+    static int unused() {
+        return unusedHelper();
+    }
+
+    static int unusedHelper() {
+        return 4;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(run(new Twice()));
+    }
+}
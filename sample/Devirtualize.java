@@ -0,0 +1,54 @@
+interface Greeter {
+    int greet(int x);
+}
+
+class PlainGreeter implements Greeter {
+    public int greet(int x) {
+        return x + 1;
+    }
+}
+
+class Counted {
+    int value() {
+        return 1;
+    }
+}
+
+class CountedLeaf extends Counted {
+}
+
+class CountedOverride extends Counted {
+    int value() {
+        return 10;
+    }
+}
+
+public class Devirtualize {
+    static int value(Counted counted) {
+        return counted.value();
+    }
+
+    static int greet(Greeter greeter, int x) {
+        return greeter.greet(x);
+    }
+
+    public static int leaf() {
+        return value(new CountedLeaf()) + value(new Counted());
+    }
+
+    public static int overridden() {
+        return value(new CountedOverride());
+    }
+
+    public static int plain(int x) {
+        return greet(new PlainGreeter(), x);
+    }
+
+    public static int lambda(int x) {
+        return greet(y -> y * 2, x);
+    }
+
+    public static int nothing() {
+        return value(null);
+    }
+}
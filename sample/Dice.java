@@ -0,0 +1,38 @@
+import java.security.SecureRandom;
+import java.util.Random;
+
+// java.util.Random and java.security.SecureRandom
+class Dice {
+    static int[] ints(long seed) {
+        Random random = new Random(seed);
+        return new int[] { random.nextInt(), random.nextInt(6), random.nextInt(1000), random.nextBoolean() ? 1 : 0 };
+    }
+
+    static long nextLong(long seed) {
+        Random random = new Random(0);
+        random.setSeed(seed);
+        return random.nextLong();
+    }
+
+    static byte[] bytes(long seed, int count) {
+        byte[] bytes = new byte[count];
+        new Random(seed).nextBytes(bytes);
+        return bytes;
+    }
+
+    static long unseeded() {
+        return new Random().nextLong();
+    }
+
+    static long secure() {
+        return new SecureRandom().nextLong();
+    }
+
+    static byte[] secureSeed(int count) {
+        return new SecureRandom().generateSeed(count);
+    }
+
+    static int bounded(int bound) {
+        return new Random().nextInt(bound);
+    }
+}
@@ -0,0 +1,27 @@
+interface Valued {
+    int value();
+}
+
+class Dispatch implements Valued {
+    public int value() {
+        return 1;
+    }
+
+    static int twice(Dispatch d) {
+        return d.value();
+    }
+
+    static int viaInterface(Valued v) {
+        return v.value();
+    }
+
+    static int run() {
+        return twice(new Dispatch()) + twice(new Sub()) + viaInterface(new Sub());
+    }
+}
+
+class Sub extends Dispatch {
+    public int value() {
+        return 20;
+    }
+}
@@ -0,0 +1,43 @@
+class Embedded {
+    static final String GREETING = "hello from a microcontroller";
+    static int counter = 40;
+
+    static int fib(int n) {
+        return n < 2 ? n : fib(n - 1) + fib(n - 2);
+    }
+
+    static int sum(int n) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += i;
+        }
+        return sum;
+    }
+
+    static long scale(int factor, long value) {
+        return factor * value + 1;
+    }
+
+    static int bump() {
+        counter += 2;
+        return counter;
+    }
+
+    static int divide(int a, int b) {
+        return a / b;
+    }
+
+    static String greeting() {
+        return GREETING;
+    }
+
+    static void report(int n) {
+        System.out.println(GREETING);
+        System.out.println(fib(n));
+        System.err.println("done");
+    }
+
+    static int recurse(int n) {
+        return recurse(n + 1);
+    }
+}
@@ -0,0 +1,99 @@
+enum Planet {
+    MERCURY(3, "hot"), VENUS(6, "cloudy"), EARTH(6, "home"), MARS(3, "red");
+
+    private final int radius;
+    private final String nickname;
+
+    Planet(int radius, String nickname) {
+        this.radius = radius;
+        this.nickname = nickname;
+    }
+
+    int radius() {
+        return radius;
+    }
+
+    String nickname() {
+        return nickname;
+    }
+}
+
+enum Op {
+    PLUS {
+        int apply(int a, int b) { return a + b; }
+    },
+    TIMES {
+        int apply(int a, int b) { return a * b; }
+    };
+
+    abstract int apply(int a, int b);
+}
+
+public class Enums {
+    static int describe(Planet planet) {
+        switch (planet) {
+            case MERCURY: return 1;
+            case EARTH: return 3;
+            case MARS: return 4;
+            default: return 0;
+        }
+    }
+
+    static int code(int value) {
+        switch (value) {
+            case -100: return 1;
+            case 7: return 2;
+            case 100000: return 3;
+            default: return 0;
+        }
+    }
+
+    public static int sumRadii() {
+        int sum = 0;
+        for (Planet planet : Planet.values()) {
+            sum += planet.radius() * (planet.ordinal() + 1);
+        }
+        return sum;
+    }
+
+    public static int switches() {
+        int result = 0;
+        for (Planet planet : Planet.values()) {
+            result = result * 10 + describe(planet);
+        }
+        return result * 10 + code(-100) + code(7) + code(100000) + code(8);
+    }
+
+    public static boolean valuesAreCopies() {
+        Planet[] first = Planet.values();
+        first[0] = Planet.MARS;
+        return Planet.values()[0] == Planet.MERCURY && first.length == 4;
+    }
+
+    public static String lookup(String name) {
+        Planet planet = Planet.valueOf(name);
+        return planet.name() + ":" + planet.nickname() + ":" + planet;
+    }
+
+    public static String missing() {
+        try {
+            Planet.valueOf("PLUTO");
+            return "found";
+        } catch (IllegalArgumentException e) {
+            return e.getMessage();
+        }
+    }
+
+    public static int compare() {
+        return Planet.MARS.compareTo(Planet.VENUS) + (Planet.EARTH.equals(Planet.EARTH) ? 10 : 0)
+            + (Op.valueOf("TIMES").getDeclaringClass() == Op.class ? 100 : 0);
+    }
+
+    public static int apply() {
+        int result = 0;
+        for (Op op : Op.values()) {
+            result = result * 100 + op.apply(6, 7);
+        }
+        return result;
+    }
+}
@@ -0,0 +1,78 @@
+class Vec2 {
+    int x;
+    int y;
+
+    Vec2(int x, int y) {
+        this.x = x;
+        this.y = y;
+    }
+}
+
+class Tracked {
+    static Tracked last;
+    int value;
+
+    Tracked(int value) {
+        this.value = value;
+        last = this;
+    }
+}
+
+public class Escapes {
+    static Vec2 kept;
+
+    public static int temporaries(int n) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            Vec2 v = new Vec2(i, i + 1);
+            sum += v.x * v.y;
+        }
+        return sum;
+    }
+
+    public static int pairs(int n) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            Vec2 a = new Vec2(i, 1);
+            Vec2 b = new Vec2(2, i);
+            sum += a.x * b.y + a.y;
+        }
+        return sum;
+    }
+
+    static Vec2 make(int x) {
+        return new Vec2(x, x);
+    }
+
+    public static int stored(int n) {
+        kept = new Vec2(n, n);
+        return kept.x;
+    }
+
+    static int sumOf(Vec2 v) {
+        return v.x + v.y;
+    }
+
+    public static int passed(int n) {
+        Vec2 v = new Vec2(n, 2);
+        return sumOf(v);
+    }
+
+    public static int tracked(int n) {
+        return new Tracked(n).value;
+    }
+
+    public static int collected(int n) {
+        Vec2 v = new Vec2(n, 1);
+        int x = v.x;
+        v = null;
+        System.gc();
+        kept = new Vec2(7, 7);
+        return x;
+    }
+
+    public static int either(int n) {
+        Vec2 v = n > 0 ? new Vec2(n, 0) : new Vec2(0, n);
+        return v.x + v.y;
+    }
+}
@@ -0,0 +1,17 @@
+class ExceptionExample {
+    static int thrower() {
+        throw new RuntimeException("boom");
+    }
+
+    static int caught() {
+        try {
+            return thrower();
+        } catch (RuntimeException e) {
+            return 42;
+        }
+    }
+
+    public static void main(String[] argv) {
+        thrower();
+    }
+}
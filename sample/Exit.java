@@ -0,0 +1,12 @@
+public class Exit {
+    public static void main(String[] args) {
+        try {
+            System.out.println("exiting");
+            System.exit(3);
+        } catch (Throwable e) {
+            System.out.println("caught");
+        } finally {
+            System.out.println("finally");
+        }
+    }
+}
@@ -0,0 +1,12 @@
+class FieldExample {
+    static int counter;
+    int value;
+
+    static int run() {
+        FieldExample example = new FieldExample();
+        example.value = 5;
+        example.value = example.value + 2;
+        counter = example.value;
+        return counter;
+    }
+}
@@ -0,0 +1,26 @@
+interface Figure {
+    int area();
+}
+
+interface Labeled {
+    String label();
+}
+
+abstract class Polygon implements Figure {
+}
+
+final class Triangle extends Polygon {
+    public int area() {
+        return 4;
+    }
+}
+
+class Pentagon extends Polygon implements Labeled {
+    public int area() {
+        return 6;
+    }
+
+    public String label() {
+        return "pentagon";
+    }
+}
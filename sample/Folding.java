@@ -0,0 +1,38 @@
+public class Folding {
+    public static int scaled(int n) {
+        int sum = 0;
+        int step = 3;
+        for (int i = 0; i < n; i++) {
+            int k = step * 2 + 1;
+            sum += k;
+        }
+        return sum;
+    }
+
+    public static int branches(int n) {
+        int mode = 2;
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            if (mode > 1) {
+                total += i;
+            } else {
+                total -= i;
+            }
+        }
+        return total;
+    }
+
+    static int id(int x) {
+        return x;
+    }
+
+    public static int unused(int n) {
+        int tripled = n * 3;
+        id(n);
+        return n + 1;
+    }
+
+    public static int length(String s) {
+        return s == null ? 0 : 1;
+    }
+}
@@ -0,0 +1,19 @@
+public class Handlers {
+    static int nested(int n) {
+        int result = 0;
+        try {
+            try {
+                result = 10 / n;
+            } catch (ArithmeticException e) {
+                result = -1;
+                int[] values = new int[1];
+                values[n + 2] = 1;
+            }
+        } catch (IndexOutOfBoundsException e) {
+            result = -2;
+        } finally {
+            result += 100;
+        }
+        return result;
+    }
+}
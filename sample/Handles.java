@@ -0,0 +1,90 @@
+import java.lang.invoke.MethodHandle;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
+
+class Counter {
+    int count;
+
+    Counter(int count) {
+        this.count = count;
+    }
+
+    int add(int amount) {
+        count += amount;
+        return count;
+    }
+}
+
+class LoudCounter extends Counter {
+    LoudCounter(int count) {
+        super(count);
+    }
+
+    int add(int amount) {
+        return super.add(amount * 10);
+    }
+}
+
+public class Handles {
+    static int multiply(int a, int b) {
+        return a * b;
+    }
+
+    public static int callStatic(int a, int b) throws Throwable {
+        MethodHandle handle = MethodHandles.lookup().findStatic(Handles.class, "multiply", MethodType.methodType(int.class, int.class, int.class));
+        return (int) handle.invokeExact(a, b);
+    }
+
+    public static int callVirtual(boolean loud) throws Throwable {
+        MethodHandle add = MethodHandles.lookup().findVirtual(Counter.class, "add", MethodType.methodType(int.class, int.class));
+        Counter counter = loud ? new LoudCounter(1) : new Counter(1);
+        return (int) add.invokeExact(counter, 2);
+    }
+
+    public static int bound() throws Throwable {
+        Counter counter = new Counter(5);
+        MethodHandle add = MethodHandles.lookup().findVirtual(Counter.class, "add", MethodType.methodType(int.class, int.class)).bindTo(counter);
+        int ignored = (int) add.invokeExact(1);
+        return (int) add.invokeExact(1);
+    }
+
+    public static int fields() throws Throwable {
+        MethodHandles.Lookup lookup = MethodHandles.lookup();
+        MethodHandle getter = lookup.findGetter(Counter.class, "count", int.class);
+        MethodHandle setter = lookup.findSetter(Counter.class, "count", int.class);
+        Counter counter = new Counter(3);
+        setter.invokeExact(counter, 40);
+        return (int) getter.invokeExact(counter) + 2;
+    }
+
+    public static String types() throws Throwable {
+        MethodHandle handle = MethodHandles.lookup().findStatic(Handles.class, "multiply", MethodType.methodType(int.class, int.class, int.class));
+        MethodHandle converted = handle.asType(MethodType.methodType(long.class, int.class, int.class));
+        return handle.type().toMethodDescriptorString() + " " + converted.type().toMethodDescriptorString();
+    }
+
+    public static long widened() throws Throwable {
+        MethodHandle handle = MethodHandles.lookup().findStatic(Handles.class, "multiply", MethodType.methodType(int.class, int.class, int.class))
+            .asType(MethodType.methodType(long.class, int.class, int.class));
+        return (long) handle.invokeExact(6, 7);
+    }
+
+    public static boolean wrongType() throws Throwable {
+        MethodHandle handle = MethodHandles.lookup().findStatic(Handles.class, "multiply", MethodType.methodType(int.class, int.class, int.class));
+        try {
+            long result = (long) handle.invokeExact(1, 2);
+            return false;
+        } catch (java.lang.invoke.WrongMethodTypeException e) {
+            return true;
+        }
+    }
+
+    public static boolean missing() throws Throwable {
+        try {
+            MethodHandles.lookup().findStatic(Handles.class, "divide", MethodType.methodType(int.class, int.class, int.class));
+            return false;
+        } catch (NoSuchMethodException e) {
+            return true;
+        }
+    }
+}
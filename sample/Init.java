@@ -0,0 +1,74 @@
+class InitBase {
+    static int order;
+
+    static {
+        order = order * 10 + 1;
+    }
+}
+
+class InitDerived extends InitBase {
+    static int value;
+
+    static {
+        order = order * 10 + 2;
+        value = order;
+    }
+}
+
+class InitCycle {
+    static int seen = peek() + 5;
+    static int late = 7;
+
+    static int peek() {
+        return late;
+    }
+}
+
+class InitFailing {
+    static int value = 1 / zero();
+
+    static int zero() {
+        return 0;
+    }
+}
+
+class Init {
+    static int derived() {
+        return InitDerived.value;
+    }
+
+    static int created() {
+        new InitBase();
+        return InitBase.order;
+    }
+
+    static int cycle() {
+        return InitCycle.seen;
+    }
+
+    static int failing() {
+        return InitFailing.value;
+    }
+
+    static int failingCaught() {
+        try {
+            return InitFailing.value;
+        } catch (NoClassDefFoundError e) {
+            return -1;
+        }
+    }
+
+    static int forName() throws Exception {
+        Class.forName("InitBase");
+        return InitBase.order;
+    }
+
+    static int forMissing() {
+        try {
+            Class.forName("Missing");
+            return 0;
+        } catch (ClassNotFoundException e) {
+            return -1;
+        }
+    }
+}
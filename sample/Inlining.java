@@ -0,0 +1,64 @@
+class Cell {
+    int x;
+    int y;
+    Cell next;
+
+    Cell(int x, int y) {
+        this.x = x;
+        this.y = y;
+    }
+
+    int getX() {
+        return x;
+    }
+
+    Cell getNext() {
+        return next;
+    }
+
+    private int scaled(int factor) {
+        return y * factor;
+    }
+
+    int scaledY(int factor) {
+        return scaled(factor);
+    }
+}
+
+public class Inlining {
+    static int mix(int a, int b) {
+        return (a << 3) ^ b - 7;
+    }
+
+    static int quotient(int a, int b) {
+        return a / b;
+    }
+
+    public static int sum(int n) {
+        Cell cell = new Cell(3, 4);
+        cell.next = cell;
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += cell.getX() + mix(i, sum) + cell.getNext().scaledY(2);
+        }
+        return sum;
+    }
+
+    public static int divide(int n, int b) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += quotient(i, b);
+        }
+        return sum;
+    }
+
+    public static int nullCell(int n) {
+        Cell cell = new Cell(1, 2);
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += cell.getX();
+        }
+        cell = cell.getNext();
+        return sum + cell.getX();
+    }
+}
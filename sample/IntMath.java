@@ -0,0 +1,54 @@
+class IntMath {
+    static int add(int a, int b) {
+        return a + b;
+    }
+
+    static int sub(int a, int b) {
+        return a - b;
+    }
+
+    static int mul(int a, int b) {
+        return a * b;
+    }
+
+    static int div(int a, int b) {
+        return a / b;
+    }
+
+    static int rem(int a, int b) {
+        return a % b;
+    }
+
+    static int neg(int a) {
+        return -a;
+    }
+
+    static int shl(int a, int b) {
+        return a << b;
+    }
+
+    static int shr(int a, int b) {
+        return a >> b;
+    }
+
+    static int ushr(int a, int b) {
+        return a >>> b;
+    }
+
+    static int bits(int a, int b) {
+        return (a & b) ^ (a | b);
+    }
+
+    static int inc(int a) {
+        a += 100;
+        return a;
+    }
+
+    static int safeDiv(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            return -1;
+        }
+    }
+}
@@ -0,0 +1,55 @@
+interface Sized {
+    int size();
+
+    static int twice(int value) {
+        return helper(value) * 2;
+    }
+
+    private static int helper(int value) {
+        return value + 1;
+    }
+
+    private int doubled() {
+        return size() * 2;
+    }
+
+    default int score() {
+        return doubled() + twice(1);
+    }
+}
+
+interface Weighted extends Sized {
+    default int score() {
+        return Sized.super.score() + 100;
+    }
+}
+
+class Box implements Sized {
+    public int size() {
+        return 3;
+    }
+}
+
+class Crate implements Weighted {
+    public int size() {
+        return 5;
+    }
+
+    public int score() {
+        return Weighted.super.score() * 10;
+    }
+}
+
+class Parcel extends Box implements Weighted {
+}
+
+public class Interfaces {
+    public static int helper(int value) {
+        return Sized.twice(value);
+    }
+
+    public static int score(int kind) {
+        Sized sized = kind == 0 ? new Box() : kind == 1 ? new Crate() : new Parcel();
+        return sized.score();
+    }
+}
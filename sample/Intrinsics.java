@@ -0,0 +1,48 @@
+import java.util.Objects;
+
+public class Intrinsics {
+    public static int clamp(int n) {
+        return Math.max(-5, Math.min(Math.abs(n), 10));
+    }
+
+    public static long larger(long a, long b) {
+        return Math.max(Math.abs(a), Math.min(a, b));
+    }
+
+    public static int copy(int n) {
+        int[] source = {1, 2, 3, 4, 5};
+        int[] destination = new int[5];
+        System.arraycopy(source, 1, destination, 0, n);
+        int sum = 0;
+        for (int i = 0; i < destination.length; i++) {
+            sum = sum * 10 + destination[i];
+        }
+        return sum;
+    }
+
+    public static int mixed(int n) {
+        Object[] source = {"a", "b", new Object(), "c"};
+        String[] destination = new String[4];
+        try {
+            System.arraycopy(source, 0, destination, 0, 4);
+        } catch (ArrayStoreException e) {
+            return destination[1] != null && destination[2] == null ? 1 : 2;
+        }
+        return 0;
+    }
+
+    public static int strings(int n) {
+        String a = "hello";
+        String b = n > 0 ? "hello" : "world";
+        return (a.equals(b) ? 1000 : 0) + (a.equals(null) ? 100 : 0) + a.hashCode() % 1000;
+    }
+
+    public static int required(int n) {
+        Object value = n > 0 ? "value" : null;
+        try {
+            return Objects.requireNonNull(value, "missing") == value ? 1 : 0;
+        } catch (NullPointerException e) {
+            return -1;
+        }
+    }
+}
@@ -0,0 +1,78 @@
+import java.util.function.IntUnaryOperator;
+import java.util.function.ToIntFunction;
+
+interface IntOp {
+    int apply(int a, int b);
+}
+
+interface LambdasFactory {
+    Lambdas create(int base);
+}
+
+public class Lambdas {
+    int base;
+
+    Lambdas(int base) {
+        this.base = base;
+    }
+
+    static int twice(int x) {
+        return x * 2;
+    }
+
+    int plusBase(int x) {
+        return x + base;
+    }
+
+    int getBase() {
+        return base;
+    }
+
+    static int apply(IntOp op, int a, int b) {
+        return op.apply(a, b);
+    }
+
+    static int nonCapturing() {
+        return apply((a, b) -> a * b, 6, 7);
+    }
+
+    static int capturing(int offset) {
+        IntUnaryOperator f = x -> x + offset;
+        return f.applyAsInt(10);
+    }
+
+    static int staticReference() {
+        IntUnaryOperator f = Lambdas::twice;
+        return f.applyAsInt(21);
+    }
+
+    static int boundReference() {
+        IntUnaryOperator f = new Lambdas(5)::plusBase;
+        return f.applyAsInt(1);
+    }
+
+    static int unboundReference() {
+        ToIntFunction<Lambdas> f = Lambdas::getBase;
+        return f.applyAsInt(new Lambdas(9));
+    }
+
+    static int constructorReference() {
+        LambdasFactory factory = Lambdas::new;
+        return factory.create(4).base;
+    }
+
+    int capturingThis() {
+        IntUnaryOperator f = x -> x * base;
+        return f.applyAsInt(3);
+    }
+
+    static int instanceLambda() {
+        return new Lambdas(7).capturingThis();
+    }
+
+    static int nested() {
+        IntOp op = (a, b) -> a - b;
+        IntUnaryOperator f = x -> apply(op, x, 1);
+        return f.applyAsInt(10);
+    }
+}
@@ -0,0 +1,21 @@
+// synchronized methods on the stack for thread dumps. `pause` is a native of the test which
+// requests the dump.
+class Monitors {
+    private int count;
+
+    static native void pause();
+
+    synchronized int add(int n) {
+        pause();
+        count += n;
+        return count;
+    }
+
+    static synchronized int twice(Monitors monitors) {
+        return monitors.add(2) * 2;
+    }
+
+    static int run() {
+        return twice(new Monitors());
+    }
+}
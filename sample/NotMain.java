@@ -0,0 +1,9 @@
+public class NotMain {
+    public void main(String[] args) {
+        System.out.println("instance main");
+    }
+
+    public static void main(int argc) {
+        System.out.println("main with an int");
+    }
+}
@@ -0,0 +1,26 @@
+// Plugin is loaded by a class loader of its own, Plugins by the boot loader
+class Plugin {
+    static Plugin last;
+    static int instances;
+
+    Plugin() {
+        instances++;
+        last = this;
+    }
+
+    static Object create() {
+        return new Plugin();
+    }
+
+    static int instances() {
+        return instances;
+    }
+}
+
+public class Plugins {
+    static Object kept;
+
+    static void keep(Object plugin) {
+        kept = plugin;
+    }
+}
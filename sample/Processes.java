@@ -0,0 +1,44 @@
+import java.io.InputStream;
+import java.io.IOException;
+import java.io.OutputStream;
+
+// ProcessBuilder and Runtime.exec
+class Processes {
+    static String read(InputStream in) throws IOException {
+        byte[] buffer = new byte[64];
+        String text = "";
+        int count;
+        while ((count = in.read(buffer)) >= 0) {
+            byte[] chunk = new byte[count];
+            for (int i = 0; i < count; i++) {
+                chunk[i] = buffer[i];
+            }
+            text = text + new String(chunk);
+        }
+        in.close();
+        return text;
+    }
+
+    static String run(String[] command) throws Exception {
+        ProcessBuilder builder = new ProcessBuilder(command).redirectErrorStream(true);
+        builder.environment().put("GREETING", "world");
+        Process process = builder.start();
+        String output = read(process.getInputStream());
+        return output + process.waitFor();
+    }
+
+    static String exec(String[] command) throws Exception {
+        Process process = Runtime.getRuntime().exec(command, new String[] { "GREETING=only" });
+        OutputStream stdin = process.getOutputStream();
+        stdin.write("from stdin\n".getBytes());
+        stdin.close();
+        return read(process.getInputStream()) + process.waitFor();
+    }
+
+    static String destroy(String[] command) throws Exception {
+        Process process = new ProcessBuilder(command).redirectOutput(ProcessBuilder.Redirect.DISCARD).start();
+        boolean alive = process.isAlive();
+        process.destroy();
+        return alive + " " + process.waitFor();
+    }
+}
@@ -0,0 +1,39 @@
+record RecordPoint(int x, int y) {
+}
+
+record RecordPerson(String name, long id, boolean active, char grade, RecordPoint home) {
+}
+
+public class Records {
+    static RecordPerson person(String name) {
+        return new RecordPerson(name, 1L, true, 'A', new RecordPoint(3, 4));
+    }
+
+    static String show() {
+        return new RecordPoint(1, -2).toString();
+    }
+
+    static String showNested() {
+        return person("Ann").toString();
+    }
+
+    static int hash(int x, int y) {
+        return new RecordPoint(x, y).hashCode();
+    }
+
+    static int hashNested() {
+        return person("Ann").hashCode();
+    }
+
+    static boolean same(int x, int y) {
+        return new RecordPoint(1, 2).equals(new RecordPoint(x, y));
+    }
+
+    static boolean sameNested(String name) {
+        return person("Ann").equals(person(name));
+    }
+
+    static boolean notARecord() {
+        return new RecordPoint(1, 2).equals("RecordPoint[x=1, y=2]");
+    }
+}
@@ -0,0 +1,12 @@
+public class Reload {
+    static int calls;
+
+    static int answer() {
+        calls++;
+        return 1;
+    }
+
+    static int calls() {
+        return calls;
+    }
+}
@@ -0,0 +1,46 @@
+class SmallArrays {
+    static int byteValue(int value) {
+        byte[] array = new byte[1];
+        array[0] = (byte) value;
+        return array[0];
+    }
+
+    static int charValue(int value) {
+        char[] array = new char[1];
+        array[0] = (char) value;
+        return array[0];
+    }
+
+    static int shortValue(int value) {
+        short[] array = new short[1];
+        array[0] = (short) value;
+        return array[0];
+    }
+
+    static int booleanValue(boolean value) {
+        boolean[] array = new boolean[1];
+        array[0] = value;
+        return array[0] ? 1 : 0;
+    }
+
+    static int sum(int n) {
+        int[] array = new int[n];
+        for (int i = 0; i < n; i++) {
+            array[i] = i + 1;
+        }
+        int s = 0;
+        for (int i = 0; i < array.length; i++) {
+            s += array[i];
+        }
+        return s;
+    }
+
+    static int length(int n) {
+        return new byte[n].length;
+    }
+
+    static int outOfBounds(int index) {
+        byte[] array = new byte[2];
+        return array[index];
+    }
+}
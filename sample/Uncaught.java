@@ -0,0 +1,26 @@
+public class Uncaught {
+    static boolean logged;
+
+    static class Logger implements Thread.UncaughtExceptionHandler {
+        public void uncaughtException(Thread thread, Throwable e) {
+            System.out.println(thread.getName());
+            System.out.println(e.getMessage());
+        }
+    }
+
+    static void logOnThisThread() {
+        logged = true;
+    }
+
+    static void fail(String message) {
+        throw new IllegalStateException(message);
+    }
+
+    public static void main(String[] args) {
+        Thread.setDefaultUncaughtExceptionHandler((thread, e) -> System.out.println("default"));
+        if (logged) {
+            Thread.currentThread().setUncaughtExceptionHandler(new Logger());
+        }
+        fail("boom");
+    }
+}
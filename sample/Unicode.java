@@ -0,0 +1,11 @@
+// constants in modified utf-8: NUL as two bytes and supplementary characters as surrogate pairs
+class Unicode {
+    static String text() {
+        return "a\0\uD83D\uDE00";
+    }
+
+    // MATHEMATICAL SCRIPT CAPITAL A
+    static int \uD835\uDC9C() {
+        return 1;
+    }
+}
@@ -0,0 +1,66 @@
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.VarHandle;
+
+class Slot {
+    static int hits;
+
+    int value;
+    long total;
+    String label;
+}
+
+public class VarHandles {
+    public static int plain() throws Throwable {
+        VarHandle value = MethodHandles.lookup().findVarHandle(Slot.class, "value", int.class);
+        Slot slot = new Slot();
+        value.set(slot, 40);
+        return (int) value.get(slot) + 2;
+    }
+
+    public static long volatileLong(long start, long delta) throws Throwable {
+        VarHandle total = MethodHandles.lookup().findVarHandle(Slot.class, "total", long.class);
+        Slot slot = new Slot();
+        total.setVolatile(slot, start);
+        total.getAndAdd(slot, delta);
+        return (long) total.getVolatile(slot);
+    }
+
+    public static long previousLong(long start, long delta) throws Throwable {
+        VarHandle total = MethodHandles.lookup().findVarHandle(Slot.class, "total", long.class);
+        Slot slot = new Slot();
+        total.setVolatile(slot, start);
+        return (long) total.getAndAdd(slot, delta);
+    }
+
+    public static int compareAndSet() throws Throwable {
+        VarHandle value = MethodHandles.lookup().findVarHandle(Slot.class, "value", int.class);
+        Slot slot = new Slot();
+        boolean first = value.compareAndSet(slot, 0, 7);
+        boolean second = value.compareAndSet(slot, 0, 9);
+        return (first ? 100 : 0) + (second ? 10 : 0) + slot.value;
+    }
+
+    public static String references() throws Throwable {
+        VarHandle label = MethodHandles.lookup().findVarHandle(Slot.class, "label", String.class);
+        Slot slot = new Slot();
+        String empty = (String) label.getAndSet(slot, "a");
+        label.compareAndSet(slot, slot.label, "b");
+        return empty + ":" + slot.label;
+    }
+
+    public static int statics() throws Throwable {
+        VarHandle hits = MethodHandles.lookup().findStaticVarHandle(Slot.class, "hits", int.class);
+        hits.setRelease(3);
+        hits.getAndAdd(4);
+        return (int) hits.getAcquire() + Slot.hits;
+    }
+
+    public static boolean missing() throws Throwable {
+        try {
+            MethodHandles.lookup().findVarHandle(Slot.class, "count", int.class);
+            return false;
+        } catch (NoSuchFieldException e) {
+            return true;
+        }
+    }
+}
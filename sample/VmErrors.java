@@ -0,0 +1,54 @@
+public class VmErrors {
+    static int recurse(int depth) {
+        try {
+            return recurse(depth + 1);
+        } catch (StackOverflowError e) {
+            return depth;
+        }
+    }
+
+    static int overflow() {
+        try {
+            return recurse(0);
+        } catch (VirtualMachineError e) {
+            return -1;
+        }
+    }
+
+    static int unbounded(int depth) {
+        return unbounded(depth + 1);
+    }
+
+    static int deref(Object value) {
+        try {
+            return value.hashCode();
+        } catch (RuntimeException e) {
+            return -1;
+        }
+    }
+
+    static int index(int i) {
+        int[] values = new int[2];
+        try {
+            return values[i];
+        } catch (IndexOutOfBoundsException e) {
+            return -1;
+        }
+    }
+
+    static int cast(Object value) {
+        try {
+            return ((String) value).length();
+        } catch (RuntimeException e) {
+            return -1;
+        }
+    }
+
+    static int allocate(int size) {
+        try {
+            return new int[size].length;
+        } catch (Error e) {
+            return -1;
+        }
+    }
+}
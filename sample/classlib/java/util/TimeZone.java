@@ -0,0 +1,6 @@
+package java.util;
+
+// stands in for the class of the jdk, with the native ZoneId.systemDefault needs
+public abstract class TimeZone {
+    private static native String getSystemTimeZoneID(String javaHome);
+}
@@ -0,0 +1,8 @@
+package jdk.internal.misc;
+
+// stands in for the class of the jdk, with the natives Instant.now needs
+public class VM {
+    public static native long getNanoTimeAdjustment(long offsetInSeconds);
+
+    private static native void initialize();
+}
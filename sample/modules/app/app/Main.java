@@ -0,0 +1,19 @@
+package app;
+
+import lib.api.Api;
+import lib.internal.Secret;
+
+public class Main {
+    static int exported() {
+        return Api.answer();
+    }
+
+    // lib does not export lib.internal, this only compiled against a version which did
+    static int internal() {
+        return Secret.value();
+    }
+
+    public static void main(String[] args) {
+        exported();
+    }
+}
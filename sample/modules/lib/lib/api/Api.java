@@ -0,0 +1,7 @@
+package lib.api;
+
+public class Api {
+    public static int answer() {
+        return 42;
+    }
+}
@@ -0,0 +1,7 @@
+package lib.internal;
+
+public class Secret {
+    public static int value() {
+        return 7;
+    }
+}
@@ -0,0 +1,13 @@
+// Reload.java with a new body for answer, for Runtime::redefine_class
+public class Reload {
+    static int calls;
+
+    static int answer() {
+        calls++;
+        return 40 + calls;
+    }
+
+    static int calls() {
+        return calls;
+    }
+}
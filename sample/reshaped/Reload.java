@@ -0,0 +1,18 @@
+// Reload.java with another method, which Runtime::redefine_class rejects
+public class Reload {
+    static int calls;
+
+    static int answer() {
+        calls++;
+        return 1;
+    }
+
+    static int calls() {
+        return calls;
+    }
+
+    static int reset() {
+        calls = 0;
+        return 0;
+    }
+}
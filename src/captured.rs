@@ -0,0 +1,37 @@
+//! a writer for tests which keeps everything written to it, to check what a runtime printed.
+
+use java::runtime::Output;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+/// collects what was written. clones share the bytes, so one clone can be handed to a runtime and
+/// the other one read afterwards.
+#[derive(Clone, Default)]
+pub struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Captured {
+    /// an `Output` which writes here, for `RuntimeBuilder::stdout` and friends.
+    pub fn output(&self) -> Output {
+        Output::new(Box::new(self.clone()))
+    }
+
+    /// everything written so far.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+
+    /// everything written since the last call, to check the output of several steps one by one.
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap().split_off(0)).into_owned()
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
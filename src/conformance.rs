@@ -0,0 +1,100 @@
+//! runs the programs in `conformance/` on a real jvm and on this one and compares what they print
+//! to `System.out` and their exit code. the programs are grouped in a directory per feature area,
+//! each `.java` file has a `main` method in the public class of its name.
+//!
+//! needs `javac` and `java` from `$JAVA_HOME/bin` or the `PATH`:
+//!
+//! ```text
+//! cargo test --features conformance conformance
+//! ```
+
+use captured::Captured;
+use java::class_file::read_class_file;
+use java::runtime::{ByteStore, LocalVariable, Output, Runtime};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// what a program printed to `System.out` and its exit code.
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    stdout: String,
+    exit_code: i32,
+}
+
+/// the path of a jdk tool, preferring `$JAVA_HOME`.
+pub(crate) fn jdk_tool(name: &str) -> PathBuf {
+    let tool = env::var_os("JAVA_HOME").map(|home| PathBuf::from(home).join("bin").join(name)).filter(|tool| tool.is_file());
+    let tool = tool.unwrap_or_else(|| PathBuf::from(name));
+    match Command::new(&tool).arg("-version").output() {
+        Ok(ref output) if output.status.success() => tool,
+        _ => panic!("the conformance tests need {} from $JAVA_HOME/bin or the PATH", name)
+    }
+}
+
+/// the `.java` files below `conformance/` by feature area, in a stable order.
+fn fixtures() -> Vec<(String, PathBuf)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance");
+    let mut fixtures = Vec::new();
+    for area in fs::read_dir(&root).expect("cannot read the conformance fixtures") {
+        let area = area.unwrap().path();
+        for fixture in fs::read_dir(&area).unwrap() {
+            let fixture = fixture.unwrap().path();
+            if fixture.extension().map_or(false, |extension| extension == "java") {
+                fixtures.push((area.file_name().unwrap().to_string_lossy().into_owned(), fixture));
+            }
+        }
+    }
+    fixtures.sort();
+    fixtures
+}
+
+fn run_on_jvm(java: &Path, classes: &Path, main_class: &str) -> Outcome {
+    let output = Command::new(java).arg("-cp").arg(classes).arg(main_class).output().expect("cannot run java");
+    Outcome { stdout: String::from_utf8_lossy(&output.stdout).into_owned(), exit_code: output.status.code().unwrap_or(-1) }
+}
+
+/// runs `main` like the launcher does: an uncaught exception or internal error exits with 1.
+fn run_on_rjvm(classes: &Path, main_class: &str) -> Outcome {
+    let store = ByteStore::new();
+    let bytes = store.keep(fs::read(classes.join(format!("{}.class", main_class))).unwrap());
+    let stdout = Captured::default();
+    let mut rt = Runtime::builder()
+        .byte_store(&store)
+        .classpath(vec![classes.to_path_buf()])
+        .eager_loading(Some(1))
+        .stdout(stdout.output())
+        .stderr(Output::new(Box::new(io::sink())))
+        .build(read_class_file(bytes).unwrap().1);
+    let exit_code = match rt.invoke_static(main_class, "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]) {
+        Ok(_) => 0,
+        Err(_) => 1
+    };
+    let stdout = stdout.text();
+    Outcome { stdout, exit_code }
+}
+
+#[test]
+fn conformance() {
+    let (javac, java) = (jdk_tool("javac"), jdk_tool("java"));
+    let out = env::temp_dir().join(format!("rjvm-conformance-{}", std::process::id()));
+
+    let mut failures = Vec::new();
+    for (area, fixture) in fixtures() {
+        let main_class = fixture.file_stem().unwrap().to_string_lossy().into_owned();
+        let classes = out.join(&area).join(&main_class);
+        let compiled = Command::new(&javac).arg("--release").arg("11").arg("-d").arg(&classes).arg(&fixture).status().expect("cannot run javac");
+        assert!(compiled.success(), "cannot compile {}", fixture.display());
+
+        let expected = run_on_jvm(&java, &classes, &main_class);
+        let actual = run_on_rjvm(&classes, &main_class);
+        if actual != expected {
+            failures.push(format!("{}/{}:\n  expected {:?}\n  actual   {:?}", area, main_class, expected, actual));
+        }
+    }
+    let _ = fs::remove_dir_all(&out);
+
+    assert!(failures.is_empty(), "{} programs behave differently:\n{}", failures.len(), failures.join("\n"));
+}
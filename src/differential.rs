@@ -0,0 +1,311 @@
+//! property based tests of the int arithmetic of both interpreters. random expressions over the
+//! two `int` arguments of a static method are compiled to straight-line bytecode with the
+//! `ClassWriter`, run, and compared against `Program::evaluate`, which follows the jvm
+//! specification. the constants and arguments favour the edges: overflow, division of
+//! `Integer.MIN_VALUE` by -1 and by zero, shift distances beyond 31 and narrowing conversions.
+//!
+//! with the `conformance` feature the programs are also compared against a real jvm.
+
+use java::class_file::{ClassFile, ClassWriter};
+use java::instructions::Instruction;
+use java::runtime::{InterpreterMode, LocalVariable, Runtime, RuntimeError, StackValue};
+use proptest::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unary {
+    Neg,
+    ToByte,
+    ToChar,
+    ToShort,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Binary {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    UShr,
+    And,
+    Or,
+    Xor,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    /// the argument in local 0 or 1.
+    Argument(u8),
+    Constant(i32),
+    Unary(Unary, Box<Expr>),
+    Binary(Binary, Box<Expr>, Box<Expr>),
+}
+
+/// the body of `static int run(int a, int b)`: `iinc` of both arguments, then an expression.
+#[derive(Debug, Clone)]
+struct Program {
+    increments: [i8; 2],
+    body: Expr,
+}
+
+/// what a program returns, or the exception it throws.
+type Outcome = Result<i32, String>;
+
+const ARITHMETIC_EXCEPTION: &str = "java/lang/ArithmeticException";
+
+impl Unary {
+    fn instruction(self) -> Instruction {
+        match self {
+            Unary::Neg => Instruction::INeg(()),
+            Unary::ToByte => Instruction::I2B(()),
+            Unary::ToChar => Instruction::I2C(()),
+            Unary::ToShort => Instruction::I2S(()),
+        }
+    }
+
+    fn evaluate(self, value: i32) -> i32 {
+        match self {
+            // -Integer.MIN_VALUE is Integer.MIN_VALUE
+            Unary::Neg => (-i64::from(value)) as i32,
+            Unary::ToByte => i32::from(value as i8),
+            Unary::ToChar => i32::from(value as u16),
+            Unary::ToShort => i32::from(value as i16),
+        }
+    }
+}
+
+impl Binary {
+    fn instruction(self) -> Instruction {
+        match self {
+            Binary::Add => Instruction::IAdd(()),
+            Binary::Sub => Instruction::ISub(()),
+            Binary::Mul => Instruction::IMul(()),
+            Binary::Div => Instruction::IDiv(()),
+            Binary::Rem => Instruction::IRem(()),
+            Binary::Shl => Instruction::IShl(()),
+            Binary::Shr => Instruction::IShr(()),
+            Binary::UShr => Instruction::IUSHR(()),
+            Binary::And => Instruction::IAnd(()),
+            Binary::Or => Instruction::IOr(()),
+            Binary::Xor => Instruction::IXor(()),
+        }
+    }
+
+    /// the result as jvms §6.5 describes it, computed in 64 bits and truncated to the low 32.
+    fn evaluate(self, lh: i32, rh: i32) -> Outcome {
+        let (wide_lh, wide_rh) = (i64::from(lh), i64::from(rh));
+        let distance = (rh & 0x1f) as u32;
+        Ok(match self {
+            Binary::Add => (wide_lh + wide_rh) as i32,
+            Binary::Sub => (wide_lh - wide_rh) as i32,
+            Binary::Mul => (wide_lh * wide_rh) as i32,
+            Binary::Div | Binary::Rem if rh == 0 => return Err(String::from(ARITHMETIC_EXCEPTION)),
+            Binary::Div => (wide_lh / wide_rh) as i32,
+            Binary::Rem => (wide_lh % wide_rh) as i32,
+            Binary::Shl => (wide_lh << distance) as i32,
+            Binary::Shr => (wide_lh >> distance) as i32,
+            Binary::UShr => (u64::from(lh as u32) >> distance) as i32,
+            Binary::And => lh & rh,
+            Binary::Or => lh | rh,
+            Binary::Xor => lh ^ rh,
+        })
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, locals: [i32; 2]) -> Outcome {
+        match self {
+            Expr::Argument(index) => Ok(locals[usize::from(*index)]),
+            Expr::Constant(value) => Ok(*value),
+            Expr::Unary(op, operand) => operand.evaluate(locals).map(|value| op.evaluate(value)),
+            Expr::Binary(op, lh, rh) => {
+                let lh = lh.evaluate(locals)?;
+                op.evaluate(lh, rh.evaluate(locals)?)
+            }
+        }
+    }
+
+    /// the operand stack the expression needs.
+    fn depth(&self) -> u16 {
+        match self {
+            Expr::Argument(_) | Expr::Constant(_) => 1,
+            Expr::Unary(_, operand) => operand.depth(),
+            Expr::Binary(_, lh, rh) => lh.depth().max(rh.depth() + 1),
+        }
+    }
+
+    fn compile(&self, writer: &mut ClassWriter, code: &mut Vec<Instruction>) {
+        match self {
+            Expr::Argument(0) => code.push(Instruction::ILoad0(())),
+            Expr::Argument(_) => code.push(Instruction::ILoad1(())),
+            Expr::Constant(value) => code.push(push_int(writer, *value)),
+            Expr::Unary(op, operand) => {
+                operand.compile(writer, code);
+                code.push(op.instruction());
+            },
+            Expr::Binary(op, lh, rh) => {
+                lh.compile(writer, code);
+                rh.compile(writer, code);
+                code.push(op.instruction());
+            }
+        }
+    }
+}
+
+/// the shortest instruction which pushes `value`, like javac picks it.
+fn push_int(writer: &mut ClassWriter, value: i32) -> Instruction {
+    match value {
+        -1 => Instruction::IConstm1(()),
+        0 => Instruction::IConst0(()),
+        1 => Instruction::IConst1(()),
+        2 => Instruction::IConst2(()),
+        3 => Instruction::IConst3(()),
+        4 => Instruction::IConst4(()),
+        5 => Instruction::IConst5(()),
+        -128..=127 => Instruction::BIPush(value as i8 as u8),
+        -32768..=32767 => Instruction::SIPush(value as i16 as u16),
+        _ => match writer.integer(value) {
+            index if index <= 0xff => Instruction::LDC(index as u8),
+            index => Instruction::LDCW(index)
+        }
+    }
+}
+
+impl Program {
+    fn evaluate(&self, a: i32, b: i32) -> Outcome {
+        let locals = [a.wrapping_add(i32::from(self.increments[0])), b.wrapping_add(i32::from(self.increments[1]))];
+        self.body.evaluate(locals)
+    }
+
+    /// adds the program as the static method `name` with the descriptor `(II)I`.
+    fn write(&self, writer: &mut ClassWriter, name: &str) {
+        let mut code = Vec::new();
+        for (local, &delta) in self.increments.iter().enumerate() {
+            if delta != 0 {
+                code.push(Instruction::IInc((local as u16) << 8 | u16::from(delta as u8)));
+            }
+        }
+        self.body.compile(writer, &mut code);
+        code.push(Instruction::IReturn(()));
+        writer.method(0x0009, name, "(II)I", self.body.depth(), 2, &code);
+    }
+}
+
+fn int_value() -> impl Strategy<Value = i32> {
+    prop_oneof![
+        2 => any::<i32>(),
+        2 => prop::sample::select(vec![0, 1, -1, 2, 31, 32, 33, -31, -32, 127, 128, -129, 65535, 65536, i32::MAX, i32::MIN, i32::MIN + 1]),
+        1 => -200..200,
+    ]
+}
+
+fn expression() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        (0..2u8).prop_map(Expr::Argument),
+        int_value().prop_map(Expr::Constant),
+    ];
+    leaf.prop_recursive(4, 24, 2, |operand| {
+        let unary = prop::sample::select(vec![Unary::Neg, Unary::ToByte, Unary::ToChar, Unary::ToShort]);
+        let binary = prop::sample::select(vec![Binary::Add, Binary::Sub, Binary::Mul, Binary::Div, Binary::Rem, Binary::Shl,
+                                               Binary::Shr, Binary::UShr, Binary::And, Binary::Or, Binary::Xor]);
+        prop_oneof![
+            (unary, operand.clone()).prop_map(|(op, operand)| Expr::Unary(op, Box::new(operand))),
+            (binary, operand.clone(), operand).prop_map(|(op, lh, rh)| Expr::Binary(op, Box::new(lh), Box::new(rh))),
+        ]
+    })
+}
+
+fn program() -> impl Strategy<Value = Program> {
+    let increment = prop_oneof![Just(0i8), any::<i8>()];
+    (increment.clone(), increment, expression()).prop_map(|(a, b, body)| Program { increments: [a, b], body })
+}
+
+/// runs `program` on the interpreter `mode`.
+fn run(program: &Program, mode: InterpreterMode, a: i32, b: i32) -> Outcome {
+    let mut writer = ClassWriter::new("Generated", "java/lang/Object");
+    program.write(&mut writer, "run");
+    let bytes = writer.to_bytes();
+
+    let mut rt = Runtime::builder().interpreter(mode).build(ClassFile::parse(&bytes).unwrap());
+    match rt.invoke_static("Generated", "run", "(II)I", vec![LocalVariable::Integer(i64::from(a)), LocalVariable::Integer(i64::from(b))]) {
+        Ok(Some(StackValue::Integer(value))) => Ok(value as i32),
+        Ok(other) => panic!("run returned {:?}", other),
+        Err(err) => match err.root() {
+            RuntimeError::Exception { class_name, .. } => Err(class_name.clone()),
+            other => panic!("run failed with {:?}", other)
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn int_arithmetic_follows_the_specification(program in program(), a in int_value(), b in int_value()) {
+        let expected = program.evaluate(a, b);
+        prop_assert_eq!(run(&program, InterpreterMode::Checked, a, b), expected.clone(), "checked interpreter");
+        prop_assert_eq!(run(&program, InterpreterMode::Fast, a, b), expected, "fast interpreter");
+    }
+}
+
+#[test]
+fn the_reference_knows_the_edge_cases() {
+    let (min, max) = (i32::MIN, i32::MAX);
+    assert_eq!(Binary::Add.evaluate(max, 1), Ok(min));
+    assert_eq!(Binary::Div.evaluate(min, -1), Ok(min));
+    assert_eq!(Binary::Rem.evaluate(min, -1), Ok(0));
+    assert_eq!(Binary::Rem.evaluate(-7, 2), Ok(-1));
+    assert_eq!(Binary::Div.evaluate(1, 0), Err(String::from(ARITHMETIC_EXCEPTION)));
+    assert_eq!(Binary::Shl.evaluate(1, 33), Ok(2));
+    assert_eq!(Binary::Shr.evaluate(-8, -31), Ok(-4));
+    assert_eq!(Binary::UShr.evaluate(-1, 28), Ok(15));
+    assert_eq!(Unary::Neg.evaluate(min), min);
+    assert_eq!(Unary::ToByte.evaluate(200), -56);
+    assert_eq!(Unary::ToChar.evaluate(-1), 65535);
+    assert_eq!(Unary::ToShort.evaluate(40000), -25536);
+}
+
+/// runs a batch of generated programs which do not throw on `java` in a single class whose
+/// `main` prints the result of each.
+#[cfg(feature = "conformance")]
+#[test]
+fn int_arithmetic_matches_a_real_jvm() {
+    use conformance::jdk_tool;
+    use proptest::strategy::ValueTree;
+    use proptest::test_runner::TestRunner;
+    use std::{env, fs, process::Command};
+
+    let mut runner = TestRunner::deterministic();
+    let mut cases = Vec::new();
+    while cases.len() < 200 {
+        let (program, a, b) = (program(), int_value(), int_value()).new_tree(&mut runner).unwrap().current();
+        if program.evaluate(a, b).is_ok() {
+            cases.push((program, a, b));
+        }
+    }
+
+    let mut writer = ClassWriter::new("Generated", "java/lang/Object");
+    let (out, println) = (writer.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;"), writer.method_ref("java/io/PrintStream", "println", "(I)V"));
+    let mut main = Vec::new();
+    for (index, (program, a, b)) in cases.iter().enumerate() {
+        let name = format!("run{}", index);
+        program.write(&mut writer, &name);
+        let run = writer.method_ref("Generated", &name, "(II)I");
+        main.extend(vec![Instruction::GetStatic(out), push_int(&mut writer, *a), push_int(&mut writer, *b), Instruction::InvokeStatic(run), Instruction::InvokeVirtual(println)]);
+    }
+    main.push(Instruction::Return(()));
+    writer.method(0x0009, "main", "([Ljava/lang/String;)V", 3, 1, &main);
+
+    let classes = env::temp_dir().join(format!("rjvm-differential-{}", std::process::id()));
+    fs::create_dir_all(&classes).unwrap();
+    fs::write(classes.join("Generated.class"), writer.to_bytes()).unwrap();
+    let output = Command::new(jdk_tool("java")).arg("-cp").arg(&classes).arg("Generated").output().expect("cannot run java");
+    let _ = fs::remove_dir_all(&classes);
+    assert!(output.status.success(), "java failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let printed = String::from_utf8(output.stdout).unwrap();
+    for ((program, a, b), line) in cases.iter().zip(printed.lines()) {
+        assert_eq!(run(program, InterpreterMode::Checked, *a, *b), Ok(line.parse().unwrap()), "{:?} with {} and {}", program, a, b);
+    }
+    assert_eq!(printed.lines().count(), cases.len());
+}
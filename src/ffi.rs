@@ -0,0 +1,482 @@
+//! the C interface for embedding rjvm in programs written in other languages, e.g. C or python
+//! through ctypes. it is exported by the shared library `cargo build` builds in `ffi/` and declared
+//! in `include/rjvm.h`, which is generated from this file: `RJVM_UPDATE_HEADER=1 cargo test ffi`
+//! rewrites it.
+//!
+//! ```text
+//! rjvm_runtime *rt = rjvm_runtime_new(bytes, length);
+//! rjvm_value arguments[] = { { RJVM_INT, 36 }, { RJVM_INT, 6 } }, result;
+//! if (rjvm_invoke_static(rt, "Tiny", "add", "(II)I", arguments, 2, &result) == RJVM_OK) {
+//!     printf("%lld\n", (long long) result.value);
+//! }
+//! rjvm_runtime_free(rt);
+//! ```
+//!
+//! every function returns a status, the message of the last failure is kept by the runtime.
+//! panics do not cross the interface, they are reported as `RJVM_ERROR`.
+
+use java::class_file::ClassFile;
+use java::runtime::{ByteStore, LocalVariable, ObjectRef, Runtime, RuntimeError, StackValue};
+use libc::{c_char, c_void};
+use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+pub const RJVM_OK: i32 = 0;
+/// the java code threw an exception, the result holds the throwable.
+pub const RJVM_EXCEPTION: i32 = 1;
+/// the vm failed, e.g. a class or method was not found.
+pub const RJVM_ERROR: i32 = 2;
+/// a null pointer, a string which is not utf-8 or a value of an unknown kind was passed.
+pub const RJVM_INVALID_ARGUMENT: i32 = 3;
+
+/// the kinds of an `rjvm_value`.
+pub const RJVM_VOID: i32 = 0;
+pub const RJVM_NULL: i32 = 1;
+/// an int, boolean, byte, char or short, in the lower 32 bits.
+pub const RJVM_INT: i32 = 2;
+pub const RJVM_LONG: i32 = 3;
+/// an object, the value is its handle. the handle of a collected object stays invalid, it never
+/// refers to an object allocated later.
+pub const RJVM_REFERENCE: i32 = 4;
+
+/// a java value crossing the interface.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RjvmValue {
+    pub kind: i32,
+    pub value: i64,
+}
+
+/// a native implemented in C. gets the `user_data` it was registered with and the arguments,
+/// stores the return value in `result` and returns `RJVM_OK`, or anything else to fail the call.
+pub type RjvmNative = extern "C" fn(user_data: *mut c_void, arguments: *const RjvmValue, count: usize, result: *mut RjvmValue) -> i32;
+
+/// a runtime owned by the host, with the messages and strings handed out to it.
+pub struct RjvmRuntime {
+    runtime: Runtime<'static>,
+    /// the message of the last failure.
+    error: Option<CString>,
+    /// the value of the last string read with `rjvm_string_value`.
+    string: Option<CString>,
+    /// the copy of the class file of the main class and the classes of the classpath, which the
+    /// classes borrow from. declared after `runtime`, so it is dropped after it.
+    _store: Arc<ByteStore>,
+}
+
+/// the data the host registered a native with, which only the host itself may touch.
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl RjvmValue {
+    fn void() -> RjvmValue {
+        RjvmValue { kind: RJVM_VOID, value: 0 }
+    }
+
+    fn to_local(self) -> Option<LocalVariable> {
+        Some(match self.kind {
+            RJVM_NULL => LocalVariable::Null,
+            RJVM_INT => LocalVariable::Integer(i64::from(self.value as i32)),
+            RJVM_LONG => LocalVariable::Long(self.value),
+            RJVM_REFERENCE => LocalVariable::Reference(ObjectRef::from_handle(self.value as u64)),
+            _ => return None
+        })
+    }
+
+    fn from_local(value: &LocalVariable) -> RjvmValue {
+        RjvmValue::from_stack(&StackValue::from(value.clone()))
+    }
+
+    fn from_stack(value: &StackValue) -> RjvmValue {
+        match *value {
+            StackValue::None => RjvmValue::void(),
+            StackValue::Null => RjvmValue { kind: RJVM_NULL, value: 0 },
+            StackValue::Integer(value) => RjvmValue { kind: RJVM_INT, value },
+            StackValue::Long(value) => RjvmValue { kind: RJVM_LONG, value },
+            StackValue::Reference(reference) => RjvmValue { kind: RJVM_REFERENCE, value: reference.handle() as i64 },
+        }
+    }
+
+    fn to_stack(self) -> Option<StackValue> {
+        match self.kind {
+            RJVM_VOID => Some(StackValue::None),
+            _ => self.to_local().map(StackValue::from)
+        }
+    }
+}
+
+impl RjvmRuntime {
+    fn fail(&mut self, status: i32, message: String) -> i32 {
+        self.error = CString::new(message.replace('\0', " ")).ok();
+        status
+    }
+}
+
+/// the utf-8 string behind a pointer from the host, `None` for null or invalid utf-8.
+unsafe fn text<'s>(pointer: *const c_char) -> Option<&'s str> {
+    if pointer.is_null() {
+        return None;
+    }
+    CStr::from_ptr(pointer).to_str().ok()
+}
+
+/// creates a runtime for the class file in `bytes`. the bytes are copied and freed with the runtime.
+/// returns null if they are not a valid class file.
+///
+/// # Safety
+///
+/// `bytes` must point to `length` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_runtime_new(bytes: *const u8, length: usize) -> *mut RjvmRuntime {
+    if bytes.is_null() {
+        return ptr::null_mut();
+    }
+    let store = Arc::new(ByteStore::new());
+    // the runtime never hands its classes to the host, and it is dropped before the store, which
+    // moving the `Arc` into the `RjvmRuntime` does not move
+    let borrowed: &'static ByteStore = &*Arc::as_ptr(&store);
+    match ClassFile::parse(borrowed.keep(slice::from_raw_parts(bytes, length).to_vec())) {
+        Ok(class) => {
+            // the host learns about failures from `rjvm_last_error`, not from files in its working directory
+            let runtime = Runtime::builder().byte_store(borrowed).build(class);
+            Box::into_raw(Box::new(RjvmRuntime { runtime, error: None, string: None, _store: store }))
+        }
+        Err(_) => ptr::null_mut()
+    }
+}
+
+/// destroys a runtime. does nothing for null.
+///
+/// # Safety
+///
+/// `runtime` must be null or come from `rjvm_runtime_new` and not be freed yet. it must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_runtime_free(runtime: *mut RjvmRuntime) {
+    if !runtime.is_null() {
+        drop(Box::from_raw(runtime));
+    }
+}
+
+/// loads the classes in the directory `path` and its subdirectories and adds it to the classpath.
+/// returns the number of classes loaded, or -1 for an invalid path.
+///
+/// # Safety
+///
+/// `runtime` must be null or a live runtime not used by another thread. `path` must be null or a
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_add_classpath(runtime: *mut RjvmRuntime, path: *const c_char) -> i64 {
+    let (runtime, path) = match (runtime.as_mut(), text(path)) {
+        (Some(runtime), Some(path)) => (runtime, path),
+        _ => return -1
+    };
+    runtime.runtime.add_classpath(PathBuf::from(path)) as i64
+}
+
+/// implements a static method with a function of the host, which gets `user_data` with every call.
+/// natives take precedence over the bytecode of loaded classes.
+///
+/// # Safety
+///
+/// `runtime` must be null or a live runtime not used by another thread. the names must be null or
+/// nul-terminated strings. `native` is called with `user_data` as long as the runtime lives, so
+/// both must stay valid until it is freed.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_register_native(runtime: *mut RjvmRuntime, class_name: *const c_char, method_name: *const c_char, descriptor: *const c_char, native: Option<RjvmNative>, user_data: *mut c_void) -> i32 {
+    let runtime = match runtime.as_mut() {
+        Some(runtime) => runtime,
+        None => return RJVM_INVALID_ARGUMENT
+    };
+    let (class_name, method_name, descriptor, native) = match (text(class_name), text(method_name), text(descriptor), native) {
+        (Some(class_name), Some(method_name), Some(descriptor), Some(native)) => (class_name, method_name, descriptor, native),
+        _ => return runtime.fail(RJVM_INVALID_ARGUMENT, String::from("invalid native"))
+    };
+
+    let name = format!("{}.{}{}", class_name, method_name, descriptor);
+    let user_data = UserData(user_data);
+    runtime.runtime.register_native(class_name, method_name, descriptor, move |_, arguments| {
+        let arguments = arguments.iter().map(RjvmValue::from_local).collect::<Vec<_>>();
+        let mut result = RjvmValue::void();
+        let status = native(user_data.0, arguments.as_ptr(), arguments.len(), &mut result);
+        if status != RJVM_OK {
+            return Err(RuntimeError::Host { action: format!("call native {}", name), message: format!("status {}", status) });
+        }
+        match result.to_stack() {
+            Some(StackValue::None) => Ok(None),
+            Some(value) => Ok(Some(value)),
+            None => Err(RuntimeError::Host { action: format!("call native {}", name), message: format!("returned a value of kind {}", result.kind) })
+        }
+    });
+    RJVM_OK
+}
+
+/// runs a static method with `count` arguments and stores its return value in `result`, or the
+/// throwable for `RJVM_EXCEPTION`. references in results stay valid until the next call into
+/// java, which may collect them.
+///
+/// # Safety
+///
+/// `runtime` must be null or a live runtime not used by another thread. the names must be null or
+/// nul-terminated strings. `arguments` must be null or point to `count` values, `result` must be null or
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_invoke_static(runtime: *mut RjvmRuntime, class_name: *const c_char, method_name: *const c_char, descriptor: *const c_char, arguments: *const RjvmValue, count: usize, result: *mut RjvmValue) -> i32 {
+    let runtime = match runtime.as_mut() {
+        Some(runtime) => runtime,
+        None => return RJVM_INVALID_ARGUMENT
+    };
+    let (class_name, method_name, descriptor) = match (text(class_name), text(method_name), text(descriptor)) {
+        (Some(class_name), Some(method_name), Some(descriptor)) => (class_name, method_name, descriptor),
+        _ => return runtime.fail(RJVM_INVALID_ARGUMENT, String::from("invalid method name"))
+    };
+    if (arguments.is_null() && count > 0) || result.is_null() {
+        return runtime.fail(RJVM_INVALID_ARGUMENT, String::from("null arguments or result"));
+    }
+    let arguments = if count > 0 { slice::from_raw_parts(arguments, count) } else { &[] };
+    let mut locals = Vec::with_capacity(count);
+    for argument in arguments {
+        match argument.to_local() {
+            Some(LocalVariable::Reference(reference)) if runtime.runtime.heap().get(reference).is_none() => {
+                return runtime.fail(RJVM_INVALID_ARGUMENT, format!("no object with handle {}", reference.handle()));
+            }
+            Some(local) => locals.push(local),
+            None => return runtime.fail(RJVM_INVALID_ARGUMENT, format!("invalid value of kind {}", argument.kind))
+        }
+    }
+
+    *result = RjvmValue::void();
+    let invoked = panic::catch_unwind(AssertUnwindSafe(|| runtime.runtime.invoke_static(class_name, method_name, descriptor, locals)));
+    match invoked {
+        Ok(Ok(value)) => {
+            *result = RjvmValue::from_stack(&value.unwrap_or(StackValue::None));
+            runtime.error = None;
+            RJVM_OK
+        }
+        Ok(Err(RuntimeError::Exception { exception, .. })) => {
+            *result = RjvmValue::from_stack(&StackValue::Reference(exception));
+            let trace = runtime.runtime.format_stack_trace(exception);
+            runtime.fail(RJVM_EXCEPTION, trace)
+        }
+        Ok(Err(err)) => runtime.fail(RJVM_ERROR, err.render()),
+        Err(_) => runtime.fail(RJVM_ERROR, format!("the vm panicked in {}.{}{}", class_name, method_name, descriptor))
+    }
+}
+
+/// stores the interned string with the utf-8 `value` in `result`. interned strings are never collected.
+///
+/// # Safety
+///
+/// `runtime` must be null or a live runtime not used by another thread. `value` must be null or a
+/// nul-terminated string, `result` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_string_new(runtime: *mut RjvmRuntime, value: *const c_char, result: *mut RjvmValue) -> i32 {
+    let runtime = match runtime.as_mut() {
+        Some(runtime) => runtime,
+        None => return RJVM_INVALID_ARGUMENT
+    };
+    match (text(value), result.is_null()) {
+        (Some(value), false) => {
+            *result = RjvmValue::from_stack(&StackValue::Reference(runtime.runtime.string(value)));
+            RJVM_OK
+        }
+        _ => runtime.fail(RJVM_INVALID_ARGUMENT, String::from("invalid string"))
+    }
+}
+
+/// the value of a `java.lang.String` as utf-8, or null if `string` is not a string.
+/// the text belongs to the runtime and stays valid until the next call of this function.
+///
+/// # Safety
+///
+/// `runtime` must be null or a live runtime not used by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_string_value(runtime: *mut RjvmRuntime, string: RjvmValue) -> *const c_char {
+    let runtime = match runtime.as_mut() {
+        Some(runtime) => runtime,
+        None => return ptr::null()
+    };
+    let value = match string.to_local() {
+        Some(LocalVariable::Reference(reference)) => runtime.runtime.heap().string_value(reference).map(|value| value.replace('\0', "\u{fffd}")),
+        _ => None
+    };
+    runtime.string = value.and_then(|value| CString::new(value).ok());
+    runtime.string.as_ref().map_or(ptr::null(), |value| value.as_ptr())
+}
+
+/// the message of the last failed call, the stack trace for an exception. null if the last call
+/// succeeded. belongs to the runtime and stays valid until the next call.
+///
+/// # Safety
+///
+/// `runtime` must be null or a live runtime not used by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn rjvm_last_error(runtime: *const RjvmRuntime) -> *const c_char {
+    match runtime.as_ref().and_then(|runtime| runtime.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => ptr::null()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    const TINY: &[u8] = include_bytes!("../sample/Tiny.class");
+
+    /// the header for the functions, constants and types of this file.
+    fn header() -> String {
+        let source = include_str!("ffi.rs");
+        let mut out = String::from("/* generated from src/ffi.rs, do not edit. */\n\
+            #ifndef RJVM_H\n#define RJVM_H\n\n#include <stddef.h>\n#include <stdint.h>\n\n\
+            #ifdef __cplusplus\nextern \"C\" {\n#endif\n\n\
+            typedef struct rjvm_runtime rjvm_runtime;\n\n\
+            typedef struct rjvm_value {\n    int32_t kind;\n    int64_t value;\n} rjvm_value;\n\n\
+            typedef int32_t (*rjvm_native)(void *user_data, const rjvm_value *arguments, size_t count, rjvm_value *result);\n");
+        let mut docs = Vec::new();
+        for line in source.lines().take_while(|line| !line.starts_with("#[cfg(test)]")) {
+            if let Some(doc) = line.strip_prefix("///") {
+                docs.push(doc.trim());
+                continue;
+            }
+            if line.starts_with("#[") {
+                continue;
+            }
+            // blank lines of the docs stay blank in the header, without the indentation
+            let comment = if docs.is_empty() { String::new() } else { format!("/* {} */\n", docs.join("\n   ").replace("   \n", "\n")) };
+            if let Some(constant) = line.strip_prefix("pub const ") {
+                let (name, value) = constant.split_at(constant.find(':').unwrap());
+                let value = value.split('=').nth(1).unwrap().trim().trim_end_matches(';');
+                out.push_str(&format!("\n{}#define {} {}\n", comment, name, value));
+            } else if let Some(function) = line.strip_prefix("pub unsafe extern \"C\" fn ") {
+                let signature = function[..function.rfind('{').unwrap()].trim();
+                let (name, rest) = signature.split_at(signature.find('(').unwrap());
+                let (parameters, returns) = rest.split_at(rest.rfind(')').unwrap());
+                let parameters = parameters[1..].split(", ")
+                    .map(|parameter| {
+                        let (name, ty) = parameter.split_at(parameter.find(": ").unwrap());
+                        format!("{}{}", c_type(&ty[2..]), name)
+                    })
+                    .collect::<Vec<_>>();
+                let returns = returns[1..].trim().trim_start_matches("->").trim();
+                out.push_str(&format!("\n{}{}{}({});\n", comment, c_type(returns), name, parameters.join(", ")));
+            }
+            docs.clear();
+        }
+        out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+        out
+    }
+
+    fn c_type(ty: &str) -> &'static str {
+        match ty {
+            "" => "void ",
+            "i32" => "int32_t ",
+            "i64" => "int64_t ",
+            "usize" => "size_t ",
+            "*const u8" => "const uint8_t *",
+            "*const c_char" => "const char *",
+            "*mut c_void" => "void *",
+            "RjvmValue" => "rjvm_value ",
+            "*const RjvmValue" => "const rjvm_value *",
+            "*mut RjvmValue" => "rjvm_value *",
+            "*mut RjvmRuntime" => "rjvm_runtime *",
+            "*const RjvmRuntime" => "const rjvm_runtime *",
+            "Option<RjvmNative>" => "rjvm_native ",
+            other => panic!("no c type for {}", other)
+        }
+    }
+
+    #[test]
+    fn the_header_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/rjvm.h");
+        if std::env::var_os("RJVM_UPDATE_HEADER").is_some() {
+            fs::write(path, header()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(path).unwrap(), header(), "include/rjvm.h is outdated, rerun the tests with RJVM_UPDATE_HEADER=1");
+    }
+
+    fn c_string(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    fn invoke(runtime: *mut RjvmRuntime, class_name: &str, method_name: &str, descriptor: &str, arguments: &[RjvmValue]) -> (i32, RjvmValue) {
+        let mut result = RjvmValue::void();
+        let status = unsafe {
+            rjvm_invoke_static(runtime, c_string(class_name).as_ptr(), c_string(method_name).as_ptr(), c_string(descriptor).as_ptr(), arguments.as_ptr(), arguments.len(), &mut result)
+        };
+        (status, result)
+    }
+
+    fn int(value: i64) -> RjvmValue {
+        RjvmValue { kind: RJVM_INT, value }
+    }
+
+    extern "C" fn answer(user_data: *mut c_void, _: *const RjvmValue, count: usize, result: *mut RjvmValue) -> i32 {
+        unsafe {
+            *(user_data as *mut usize) += count + 1;
+            *result = int(42);
+        }
+        RJVM_OK
+    }
+
+    #[test]
+    fn hosts_invoke_methods_and_implement_natives() {
+        let runtime = unsafe { rjvm_runtime_new(TINY.as_ptr(), TINY.len()) };
+        assert!(!runtime.is_null());
+        assert_eq!(invoke(runtime, "Tiny", "add", "(II)I", &[int(36), int(6)]), (RJVM_OK, int(42)));
+        assert!(unsafe { rjvm_last_error(runtime) }.is_null());
+
+        let mut calls = 0usize;
+        let status = unsafe {
+            rjvm_register_native(runtime, c_string("Tiny").as_ptr(), c_string("get_number").as_ptr(), c_string("()I").as_ptr(), Some(answer), &mut calls as *mut usize as *mut c_void)
+        };
+        assert_eq!(status, RJVM_OK);
+        assert_eq!(invoke(runtime, "Tiny", "get_number", "()I", &[]), (RJVM_OK, int(42)));
+        assert_eq!(calls, 1);
+
+        let (status, _) = invoke(runtime, "Tiny", "missing", "()V", &[]);
+        assert_eq!(status, RJVM_ERROR);
+        let error = unsafe { CStr::from_ptr(rjvm_last_error(runtime)) }.to_str().unwrap();
+        assert!(error.contains("Tiny.missing()V"), "unexpected error {}", error);
+        // a handle which does not refer to an object
+        let (status, _) = invoke(runtime, "Tiny", "add", "(II)I", &[RjvmValue { kind: RJVM_REFERENCE, value: 1 << 40 }, int(1)]);
+        assert_eq!(status, RJVM_INVALID_ARGUMENT);
+        unsafe { rjvm_runtime_free(runtime) };
+
+        let garbage = b"not a class";
+        assert!(unsafe { rjvm_runtime_new(garbage.as_ptr(), garbage.len()) }.is_null());
+    }
+
+    #[test]
+    fn hosts_load_classpaths_and_read_strings_and_exceptions() {
+        let dir = std::env::temp_dir().join(format!("rjvm-ffi-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("IntMath.class"), &include_bytes!("../sample/IntMath.class")[..]).unwrap();
+        fs::write(dir.join("Concat.class"), &include_bytes!("../sample/Concat.class")[..]).unwrap();
+
+        let runtime = unsafe { rjvm_runtime_new(TINY.as_ptr(), TINY.len()) };
+        assert_eq!(unsafe { rjvm_add_classpath(runtime, c_string(dir.to_str().unwrap()).as_ptr()) }, 2);
+
+        let mut name = RjvmValue::void();
+        assert_eq!(unsafe { rjvm_string_new(runtime, c_string("C").as_ptr(), &mut name) }, RJVM_OK);
+        let (status, greeting) = invoke(runtime, "Concat", "greet", "(Ljava/lang/String;)Ljava/lang/String;", &[name]);
+        assert_eq!(status, RJVM_OK);
+        let greeting = unsafe { CStr::from_ptr(rjvm_string_value(runtime, greeting)) };
+        assert_eq!(greeting.to_str().unwrap(), "Hello, C!");
+        assert!(unsafe { rjvm_string_value(runtime, int(1)) }.is_null());
+
+        let (status, exception) = invoke(runtime, "IntMath", "div", "(II)I", &[int(1), int(0)]);
+        assert_eq!((status, exception.kind), (RJVM_EXCEPTION, RJVM_REFERENCE));
+        let trace = unsafe { CStr::from_ptr(rjvm_last_error(runtime)) }.to_str().unwrap();
+        assert!(trace.starts_with("java.lang.ArithmeticException: / by zero\n"), "unexpected trace {}", trace);
+
+        unsafe { rjvm_runtime_free(runtime) };
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
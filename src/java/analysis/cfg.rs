@@ -0,0 +1,421 @@
+use java::class_file::{ClassFile, Method};
+use java::instructions::{Instruction, ReadInstructionError};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::ops::Range;
+
+#[derive(Debug, Fail)]
+pub enum CfgError {
+    #[fail(display = "method has no code")]
+    NoCode,
+    #[fail(display = "invalid bytecode: {}", message)]
+    InvalidBytecode { message: String },
+    #[fail(display = "invalid jump target {}", pc)]
+    InvalidJumpTarget { pc: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// the next block in the code, reached without a jump.
+    FallThrough,
+    Branch,
+    /// to an exception handler covering the block.
+    Exception,
+}
+
+/// a straight line of instructions which is only entered at its first and left after its last one.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub id: usize,
+    /// the indices of its instructions in `ControlFlowGraph::instructions`.
+    pub instructions: Range<usize>,
+    pub successors: Vec<(usize, EdgeKind)>,
+    pub predecessors: Vec<usize>,
+}
+
+/// the local variables live on entry to and on exit from each block.
+#[derive(Debug, Clone)]
+pub struct Liveness {
+    pub live_in: Vec<BTreeSet<u16>>,
+    pub live_out: Vec<BTreeSet<u16>>,
+}
+
+/// the basic blocks of a method and the edges between them. block 0 is the entry.
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    /// the decoded instructions with their pc.
+    pub instructions: Vec<(usize, Instruction)>,
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// the local variables an instruction reads and writes. longs and doubles take two slots.
+pub(super) fn locals_used(instruction: &Instruction) -> (Vec<u16>, Vec<u16>) {
+    let wide = |index: u8| vec![u16::from(index), u16::from(index) + 1];
+    match *instruction {
+        Instruction::ILoad(index) | Instruction::FLoad(index) | Instruction::ALoad(index) => (vec![u16::from(index)], vec![]),
+        Instruction::LLoad(index) | Instruction::DLoad(index) => (wide(index), vec![]),
+        Instruction::ILoad0(()) | Instruction::FLoad0(()) | Instruction::ALoad0(()) => (vec![0], vec![]),
+        Instruction::ILoad1(()) | Instruction::FLoad1(()) | Instruction::ALoad1(()) => (vec![1], vec![]),
+        Instruction::ILoad2(()) | Instruction::FLoad2(()) | Instruction::ALoad2(()) => (vec![2], vec![]),
+        Instruction::ILoad3(()) | Instruction::FLoad3(()) | Instruction::ALoad3(()) => (vec![3], vec![]),
+        Instruction::LLoad0(()) | Instruction::DLoad0(()) => (wide(0), vec![]),
+        Instruction::LLoad1(()) | Instruction::DLoad1(()) => (wide(1), vec![]),
+        Instruction::LLoad2(()) | Instruction::DLoad2(()) => (wide(2), vec![]),
+        Instruction::LLoad3(()) | Instruction::DLoad3(()) => (wide(3), vec![]),
+        Instruction::IStore(index) | Instruction::FStore(index) | Instruction::AStore(index) => (vec![], vec![u16::from(index)]),
+        Instruction::LStore(index) | Instruction::DStore(index) => (vec![], wide(index)),
+        Instruction::IStore0(()) | Instruction::FStore0(()) | Instruction::AStore0(()) => (vec![], vec![0]),
+        Instruction::IStore1(()) | Instruction::FStore1(()) | Instruction::AStore1(()) => (vec![], vec![1]),
+        Instruction::IStore2(()) | Instruction::FStore2(()) | Instruction::AStore2(()) => (vec![], vec![2]),
+        Instruction::IStore3(()) | Instruction::FStore3(()) | Instruction::AStore3(()) => (vec![], vec![3]),
+        Instruction::LStore0(()) | Instruction::DStore0(()) => (vec![], wide(0)),
+        Instruction::LStore1(()) | Instruction::DStore1(()) => (vec![], wide(1)),
+        Instruction::LStore2(()) | Instruction::DStore2(()) => (vec![], wide(2)),
+        Instruction::LStore3(()) | Instruction::DStore3(()) => (vec![], wide(3)),
+        Instruction::IInc(operands) => (vec![operands >> 8], vec![operands >> 8]),
+        _ => (vec![], vec![])
+    }
+}
+
+/// an instruction as a listing shows it, like javap: the mnemonic with branch targets as pcs and
+/// constant pool references with what they refer to, if the class is known.
+fn listing(pc: usize, instruction: &Instruction, class: Option<&ClassFile>) -> String {
+    let mnemonic = instruction.mnemonic();
+    if let Some(offset) = instruction.branch_offset() {
+        return format!("{} {}", mnemonic, pc as i64 + offset);
+    }
+    if let Some(offsets) = instruction.switch_offsets() {
+        let targets = offsets.iter().map(|offset| (pc as i64 + offset).to_string()).collect::<Vec<_>>();
+        return format!("{} {{ {} default: {} }}", mnemonic, targets[1..].join(", "), targets[0]);
+    }
+    if let Some(index) = instruction.constant_index() {
+        return match class {
+            Some(class) => format!("{} #{} // {}", mnemonic, index, class.describe_constant(index)),
+            None => format!("{} #{}", mnemonic, index)
+        };
+    }
+    // the operands of everything else as they are decoded
+    let operands = format!("{:?}", instruction);
+    match operands.find('(') {
+        Some(start) if &operands[start..] != "(())" => format!("{} {}", mnemonic, &operands[start + 1..operands.len() - 1]),
+        _ => mnemonic
+    }
+}
+
+impl ControlFlowGraph {
+    pub fn build(method: &Method) -> Result<ControlFlowGraph, CfgError> {
+        let code = method.get_code().ok_or(CfgError::NoCode)?;
+        let instructions = code.instructions_with_pc()
+            .map_err(|err| CfgError::InvalidBytecode {
+                message: match err {
+                    ReadInstructionError::InvalidOpcode { opcode } => format!("invalid opcode 0x{:02x}", opcode),
+                    ReadInstructionError::ParsingIncomplete => String::from("truncated instruction"),
+                    ReadInstructionError::ParsingError(_) => String::from("malformed instruction")
+                }
+            })?;
+        let index_of = |pc: usize| instructions.binary_search_by_key(&pc, |&(instruction_pc, _)| instruction_pc)
+            .map_err(|_| CfgError::InvalidJumpTarget { pc });
+
+        // a block starts at the entry, at every jump target and handler and after every jump
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for (index, &(pc, ref instruction)) in instructions.iter().enumerate() {
+            if let Some(offset) = instruction.branch_offset() {
+                leaders.insert(index_of((pc as i64 + offset) as usize)?);
+                leaders.insert(index + 1);
+            } else if let Some(offsets) = instruction.switch_offsets() {
+                for offset in offsets {
+                    leaders.insert(index_of((pc as i64 + offset) as usize)?);
+                }
+                leaders.insert(index + 1);
+            } else if instruction.ends_flow() {
+                leaders.insert(index + 1);
+            }
+        }
+        for &(start, end, handler, _) in code.exception_table.iter() {
+            leaders.insert(index_of(usize::from(start))?);
+            leaders.insert(index_of(usize::from(handler))?);
+            if let Ok(end) = index_of(usize::from(end)) {
+                leaders.insert(end);
+            }
+        }
+        leaders.retain(|&leader| leader < instructions.len());
+
+        let starts = leaders.into_iter().collect::<Vec<_>>();
+        let mut blocks = starts.iter().enumerate()
+            .map(|(id, &start)| BasicBlock {
+                id,
+                instructions: start..starts.get(id + 1).cloned().unwrap_or(instructions.len()),
+                successors: Vec::new(),
+                predecessors: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let block_of = |index: usize| starts.binary_search(&index).unwrap_or_else(|next| next - 1);
+
+        for block in blocks.iter_mut() {
+            let (pc, ref last) = instructions[block.instructions.end - 1];
+            if let Some(offset) = last.branch_offset() {
+                block.successors.push((block_of(index_of((pc as i64 + offset) as usize)?), EdgeKind::Branch));
+            }
+            for offset in last.switch_offsets().unwrap_or_default() {
+                let target = (block_of(index_of((pc as i64 + offset) as usize)?), EdgeKind::Branch);
+                if !block.successors.contains(&target) {
+                    block.successors.push(target);
+                }
+            }
+            if !last.ends_flow() && block.id + 1 < starts.len() {
+                block.successors.push((block.id + 1, EdgeKind::FallThrough));
+            }
+
+            let (first_pc, _) = instructions[block.instructions.start];
+            for &(start, end, handler, _) in code.exception_table.iter() {
+                if usize::from(start) <= first_pc && first_pc < usize::from(end) {
+                    let handler = (block_of(index_of(usize::from(handler))?), EdgeKind::Exception);
+                    if !block.successors.contains(&handler) {
+                        block.successors.push(handler);
+                    }
+                }
+            }
+        }
+        for id in 0..blocks.len() {
+            for (successor, _) in blocks[id].successors.clone() {
+                blocks[successor].predecessors.push(id);
+            }
+        }
+
+        Ok(ControlFlowGraph { instructions, blocks })
+    }
+
+    /// the block containing the instruction at `pc`.
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        let index = self.instructions.binary_search_by_key(&pc, |&(instruction_pc, _)| instruction_pc).ok()?;
+        self.blocks.iter().position(|block| block.instructions.start <= index && index < block.instructions.end)
+    }
+
+    /// the blocks in reverse postorder from the entry. unreachable blocks are left out.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut visited = vec![false; self.blocks.len()];
+        let mut order = Vec::with_capacity(self.blocks.len());
+        // (block, next successor to visit)
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some(&mut (block, ref mut next)) = stack.last_mut() {
+            match self.blocks[block].successors.get(*next) {
+                Some(&(successor, _)) => {
+                    *next += 1;
+                    if !visited[successor] {
+                        visited[successor] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    order.push(block);
+                    stack.pop();
+                }
+            }
+        }
+
+        order.reverse();
+        order
+    }
+
+    /// blocks which can never run, i.e. dead code.
+    pub fn unreachable_blocks(&self) -> Vec<usize> {
+        let reachable = self.reverse_postorder();
+        (0..self.blocks.len()).filter(|block| !reachable.contains(block)).collect()
+    }
+
+    /// the immediate dominator of every block. the entry and unreachable blocks have none.
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (index, &block) in order.iter().enumerate() {
+            position[block] = index;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut new_idom: Option<usize> = None;
+                for &predecessor in self.blocks[block].predecessors.iter().filter(|&&predecessor| idom[predecessor].is_some()) {
+                    new_idom = Some(match new_idom {
+                        None => predecessor,
+                        Some(mut other) => {
+                            // walk both up the tree until they meet
+                            let mut current = predecessor;
+                            while current != other {
+                                while position[current] > position[other] {
+                                    current = idom[current].unwrap();
+                                }
+                                while position[other] > position[current] {
+                                    other = idom[other].unwrap();
+                                }
+                            }
+                            current
+                        }
+                    });
+                }
+                if new_idom.is_some() && idom[block] != new_idom {
+                    idom[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        idom[0] = None;
+        idom
+    }
+
+    /// true if every path from the entry to `block` goes through `dominator`.
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        let idom = self.dominators();
+        let mut current = Some(block);
+        while let Some(block) = current {
+            if block == dominator {
+                return true;
+            }
+            current = idom[block];
+        }
+        false
+    }
+
+    /// the live local variables, found by iterating the backwards data flow to a fixed point.
+    pub fn liveness(&self) -> Liveness {
+        let (mut uses, mut defs) = (Vec::new(), Vec::new());
+        for block in self.blocks.iter() {
+            let (mut used, mut defined) = (BTreeSet::new(), BTreeSet::new());
+            for (_, ref instruction) in self.instructions[block.instructions.clone()].iter() {
+                let (reads, writes) = locals_used(instruction);
+                used.extend(reads.into_iter().filter(|local| !defined.contains(local)));
+                defined.extend(writes);
+            }
+            uses.push(used);
+            defs.push(defined);
+        }
+
+        let mut live_in = vec![BTreeSet::new(); self.blocks.len()];
+        let mut live_out = vec![BTreeSet::new(); self.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in self.blocks.iter().rev() {
+                let out = block.successors.iter()
+                    .flat_map(|&(successor, _)| live_in[successor].iter().cloned())
+                    .collect::<BTreeSet<u16>>();
+                let mut inside = uses[block.id].clone();
+                inside.extend(out.difference(&defs[block.id]).cloned());
+
+                if inside != live_in[block.id] || out != live_out[block.id] {
+                    live_in[block.id] = inside;
+                    live_out[block.id] = out;
+                    changed = true;
+                }
+            }
+        }
+
+        Liveness { live_in, live_out }
+    }
+
+    /// renders the graph in the Graphviz DOT language. branch edges are blue, exception edges dashed.
+    pub fn to_dot(&self, name: &str) -> String {
+        self.render_dot(name, None)
+    }
+
+    /// like `to_dot`, with the constants the instructions of `class` refer to in the listings.
+    pub fn to_dot_with_constants(&self, name: &str, class: &ClassFile) -> String {
+        self.render_dot(name, Some(class))
+    }
+
+    fn render_dot(&self, name: &str, class: Option<&ClassFile>) -> String {
+        let escape = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::new();
+        writeln!(dot, "digraph \"{}\" {{", escape(name)).unwrap();
+        writeln!(dot, "    node [shape=box, fontname=monospace];").unwrap();
+        for block in self.blocks.iter() {
+            let mut label = format!("B{}\\l", block.id);
+            for &(pc, ref instruction) in self.instructions[block.instructions.clone()].iter() {
+                label.push_str(&format!("{}: {}", pc, escape(&listing(pc, instruction, class))));
+                label.push_str("\\l");
+            }
+            writeln!(dot, "    b{} [label=\"{}\"];", block.id, label).unwrap();
+        }
+        for block in self.blocks.iter() {
+            for &(successor, kind) in block.successors.iter() {
+                let style = match kind {
+                    EdgeKind::FallThrough => "",
+                    EdgeKind::Branch => " [color=blue]",
+                    EdgeKind::Exception => " [style=dashed, color=red]",
+                };
+                writeln!(dot, "    b{} -> b{}{};", block.id, successor, style).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn sum_graph() -> ControlFlowGraph {
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let sum = class.methods.iter().find(|method| method.name == "sum").unwrap();
+        ControlFlowGraph::build(sum).unwrap()
+    }
+
+    #[test]
+    fn it_splits_a_loop_into_blocks() {
+        let cfg = sum_graph();
+        let starts = cfg.blocks.iter().map(|block| cfg.instructions[block.instructions.start].0).collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 4, 9, 19]);
+        assert_eq!(cfg.blocks[1].successors, vec![(3, EdgeKind::Branch), (2, EdgeKind::FallThrough)]);
+        assert_eq!(cfg.blocks[2].successors, vec![(1, EdgeKind::Branch)]);
+        assert_eq!(cfg.blocks[1].predecessors, vec![0, 2]);
+        assert_eq!(cfg.block_at(13), Some(2));
+        assert!(cfg.unreachable_blocks().is_empty());
+    }
+
+    #[test]
+    fn it_computes_dominators_and_liveness() {
+        let cfg = sum_graph();
+        assert_eq!(cfg.dominators(), vec![None, Some(0), Some(1), Some(1)]);
+        assert!(cfg.dominates(1, 2));
+        assert!(!cfg.dominates(2, 3));
+
+        let liveness = cfg.liveness();
+        let live = |locals: &[u16]| locals.iter().cloned().collect::<BTreeSet<u16>>();
+        assert_eq!(liveness.live_in[0], live(&[0]));
+        assert_eq!(liveness.live_in[1], live(&[0, 1, 2]));
+        assert_eq!(liveness.live_in[3], live(&[1]));
+        assert_eq!(liveness.live_out[3], live(&[]));
+    }
+
+    #[test]
+    fn it_exports_dot() {
+        let dot = sum_graph().to_dot("Loop.sum(I)I");
+        assert!(dot.starts_with("digraph \"Loop.sum(I)I\" {"));
+        assert!(dot.contains("b2 -> b1 [color=blue];"));
+        assert!(dot.contains("16: goto 4\\l"));
+        assert!(dot.contains("B1\\l4: iload_2\\l5: iload_0\\l6: if_icmpge 19\\l"));
+    }
+
+    #[test]
+    fn it_exports_dot_with_constants_and_exception_edges() {
+        let class = read_class_file(include_bytes!("../../../sample/ExceptionExample.class")).unwrap().1;
+        let caught = class.methods.iter().find(|method| method.name == "caught").unwrap();
+        let dot = ControlFlowGraph::build(caught).unwrap().to_dot_with_constants("ExceptionExample.caught()I", &class);
+        assert!(dot.contains("invokestatic #14 // ExceptionExample.thrower()I\\l"), "{}", dot);
+        assert!(dot.contains("[style=dashed, color=red];"), "{}", dot);
+
+        let thrower = class.methods.iter().find(|method| method.name == "thrower").unwrap();
+        let dot = ControlFlowGraph::build(thrower).unwrap().to_dot_with_constants("ExceptionExample.thrower()I", &class);
+        assert!(dot.contains("new #7 // class java/lang/RuntimeException\\l"), "{}", dot);
+        assert!(dot.contains("ldc #9 // \\\"boom\\\"\\l"), "{}", dot);
+    }
+}
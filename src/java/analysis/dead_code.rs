@@ -0,0 +1,503 @@
+use java::analysis::{ClassHierarchy, ControlFlowGraph};
+use java::class_file::{ClassFile, ConstantType, Method};
+use java::runtime::builtin_superclass;
+use java::instructions::Instruction;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+const ACC_PRIVATE: u16 = 0x0002;
+const ACC_STATIC: u16 = 0x0008;
+
+/// the methods of `java/lang/Object` which classes override to be called by the jdk.
+const OBJECT_METHODS: &[(&str, &str)] = &[
+    ("toString", "()Ljava/lang/String;"),
+    ("equals", "(Ljava/lang/Object;)Z"),
+    ("hashCode", "()I"),
+    ("clone", "()Ljava/lang/Object;"),
+    ("finalize", "()V"),
+];
+
+/// the `MethodHandle` kinds which dispatch on the receiver, `invokeVirtual` and `invokeInterface`.
+const VIRTUAL_HANDLE_KINDS: [u8; 2] = [5, 9];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MethodId {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl MethodId {
+    fn new(class_name: &str, name: &str, descriptor: &str) -> MethodId {
+        MethodId { class_name: String::from(class_name), name: String::from(name), descriptor: String::from(descriptor) }
+    }
+}
+
+/// `Class.method(descriptor)`.
+impl fmt::Display for MethodId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}{}", self.class_name, self.name, self.descriptor)
+    }
+}
+
+/// a method the analysis of unused methods starts from, `Class::method` for every overload of the
+/// method or `Class::method(I)V` for one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: Option<String>,
+}
+
+impl EntryPoint {
+    fn matches(&self, class_name: &str, method: &Method) -> bool {
+        self.class_name == class_name && self.method_name == method.name
+            && self.descriptor.as_ref().is_none_or(|descriptor| *descriptor == method.descriptor)
+    }
+}
+
+impl FromStr for EntryPoint {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<EntryPoint, String> {
+        let mut parts = text.splitn(2, "::");
+        let (class_name, method) = match (parts.next(), parts.next()) {
+            (Some(class_name), Some(method)) if !class_name.is_empty() && !method.is_empty() => (class_name.replace('.', "/"), method),
+            _ => return Err(format!("expected Class::method, got {}", text))
+        };
+        let (method_name, descriptor) = match method.find('(') {
+            Some(start) => (&method[..start], Some(String::from(&method[start..]))),
+            None => (method, None)
+        };
+        Ok(EntryPoint { class_name, method_name: String::from(method_name), descriptor })
+    }
+}
+
+/// a basic block no path from the start of its method reaches, from `start_pc` up to `end_pc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachableBlock {
+    pub method: MethodId,
+    pub start_pc: usize,
+    pub end_pc: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandlerProblem {
+    /// no instruction of the range it covers can throw.
+    NothingThrows,
+    /// everything in its range which can throw is caught first by earlier handlers of the same or a
+    /// wider type, the ones at these pcs.
+    Shadowed { by: Vec<usize> },
+}
+
+/// an entry of an exception table which can never catch anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImpossibleHandler {
+    pub method: MethodId,
+    pub start_pc: usize,
+    pub end_pc: usize,
+    pub handler_pc: usize,
+    /// `None` for a handler of every exception, like the ones of `finally`.
+    pub catch_type: Option<String>,
+    pub problem: HandlerProblem,
+}
+
+/// the code of a set of classes which can never run: basic blocks no path reaches, exception
+/// handlers nothing can reach, and methods the entry points never call, directly or not.
+///
+/// calls are followed with class hierarchy analysis: a virtual call reaches the method in every
+/// subtype which overrides it, whether the subtype is instantiated or not. methods which override
+/// methods of classes outside the set, like `toString`, count as called once their class is used,
+/// since the jdk may call them. reflection is not followed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeadCodeReport {
+    pub unreachable_blocks: Vec<UnreachableBlock>,
+    pub impossible_handlers: Vec<ImpossibleHandler>,
+    /// the methods with code which are never called, sorted.
+    pub unused_methods: Vec<MethodId>,
+    /// the methods whose code could not be analyzed, with the reason.
+    pub skipped: Vec<(MethodId, String)>,
+}
+
+/// true if a handler of `outer` catches everything a handler of `inner` catches, `None` standing
+/// for every exception. the supertypes of the exceptions of the jdk are the ones the vm knows.
+fn catches_all(hierarchy: &ClassHierarchy, outer: Option<&str>, inner: Option<&str>) -> bool {
+    let (outer, inner) = match (outer, inner) {
+        (None, _) => return true,
+        (Some(outer), None) => return outer == "java/lang/Throwable",
+        (Some(outer), Some(inner)) => (outer, inner)
+    };
+    let mut seen = BTreeSet::new();
+    let mut current = Some(inner);
+    while let Some(class_name) = current {
+        if class_name == outer {
+            return true;
+        }
+        if !seen.insert(class_name) {
+            return false;
+        }
+        current = match hierarchy.get(class_name) {
+            Some(node) => node.super_class.as_deref(),
+            None => builtin_superclass(class_name)
+        };
+    }
+    false
+}
+
+/// follows the calls from the entry points through the classes of the set.
+struct Reachability<'c, 'a: 'c> {
+    classes: BTreeMap<&'c str, &'c ClassFile<'a>>,
+    hierarchy: ClassHierarchy,
+    reached: BTreeSet<MethodId>,
+    used_classes: BTreeSet<String>,
+    pending: Vec<MethodId>,
+}
+
+impl<'c, 'a: 'c> Reachability<'c, 'a> {
+    fn declares(&self, class_name: &str, name: &str, descriptor: &str) -> bool {
+        self.classes.get(class_name).is_some_and(|class| class.methods.iter().any(|method| method.name == name && method.descriptor == descriptor))
+    }
+
+    fn reach(&mut self, class_name: &str, name: &str, descriptor: &str) {
+        let id = MethodId::new(class_name, name, descriptor);
+        if self.declares(class_name, name, descriptor) && self.reached.insert(id.clone()) {
+            self.pending.push(id);
+        }
+    }
+
+    /// a method which the jdk may call on instances of `class`: one which overrides a method of a
+    /// supertype outside the set.
+    fn overrides_outside(&self, class: &ClassFile, method: &Method) -> bool {
+        if method.access_flags & (ACC_PRIVATE | ACC_STATIC) != 0 || method.name.starts_with('<') {
+            return false;
+        }
+        self.hierarchy.supertypes(class.get_class_name()).into_iter()
+            .filter(|supertype| !self.classes.contains_key(supertype))
+            .any(|supertype| supertype != "java/lang/Object" || OBJECT_METHODS.contains(&(&method.name, &method.descriptor)))
+    }
+
+    /// a class is used when code refers to it, which runs its initializer and its super class's and
+    /// makes the methods the jdk may call reachable.
+    fn use_class(&mut self, class_name: &str) {
+        let class = match self.classes.get(class_name) {
+            Some(&class) => class,
+            None => return
+        };
+        if !self.used_classes.insert(String::from(class_name)) {
+            return;
+        }
+        self.reach(class_name, "<clinit>", "()V");
+        let overrides = class.methods.iter().filter(|method| self.overrides_outside(class, method)).collect::<Vec<_>>();
+        for method in overrides {
+            self.reach(class_name, &method.name, &method.descriptor);
+        }
+        if let Some(super_class) = class.get_super_class_name() {
+            self.use_class(super_class);
+        }
+    }
+
+    /// reaches the method a call of `owner.name` resolves to, the first declaration in the class or
+    /// its super classes or else in its interfaces. a virtual call also reaches the overrides.
+    fn call(&mut self, owner: &str, name: &str, descriptor: &str, virtual_call: bool) {
+        self.use_class(owner);
+        let mut seen = BTreeSet::new();
+        let mut current = Some(String::from(owner));
+        let mut resolved = false;
+        while let Some(class_name) = current.take().filter(|class_name| seen.insert(class_name.clone())) {
+            if self.declares(&class_name, name, descriptor) {
+                self.reach(&class_name, name, descriptor);
+                resolved = true;
+                break;
+            }
+            current = self.hierarchy.get(&class_name).and_then(|node| node.super_class.clone());
+        }
+        if !resolved {
+            let interfaces = self.hierarchy.supertypes(owner).into_iter().map(String::from).collect::<Vec<_>>();
+            for interface in interfaces {
+                self.reach(&interface, name, descriptor);
+            }
+        }
+        if virtual_call {
+            let subtypes = self.hierarchy.subtypes(owner).into_iter().map(String::from).collect::<Vec<_>>();
+            for subtype in subtypes {
+                self.reach(&subtype, name, descriptor);
+            }
+        }
+    }
+
+    /// follows the constant `index` of `class` which code refers to.
+    fn refer(&mut self, class: &'c ClassFile<'a>, index: u16, virtual_call: bool) {
+        match class.get_constant(index) {
+            Some(ConstantType::MethodRef { class_index, name_and_type_index }) |
+            Some(ConstantType::InterfaceMethodRef { class_index, name_and_type_index }) => {
+                if let (Some(owner), Some((name, descriptor))) = (class.get_class_name_at(*class_index), class.get_name_and_type(*name_and_type_index)) {
+                    self.call(owner, name, descriptor, virtual_call);
+                }
+            },
+            Some(ConstantType::FieldRef { class_index, .. }) => {
+                if let Some(owner) = class.get_class_name_at(*class_index) {
+                    self.use_class(owner);
+                }
+            },
+            Some(ConstantType::Class { .. }) => {
+                if let Some(name) = class.get_class_name_at(index) {
+                    self.use_class(name);
+                }
+            },
+            Some(ConstantType::MethodHandle { reference_kind, reference_index }) =>
+                self.refer(class, *reference_index, VIRTUAL_HANDLE_KINDS.contains(reference_kind)),
+            _ => ()
+        }
+    }
+
+    /// follows the calls and class references of a reached method, and the method handles passed to
+    /// the bootstrap methods of its `invokedynamic`, like the bodies of lambdas.
+    fn visit(&mut self, id: &MethodId) {
+        let class = match self.classes.get(id.class_name.as_str()) {
+            Some(&class) => class,
+            None => return
+        };
+        let instructions = match class.methods.iter().find(|method| method.name == id.name && method.descriptor == id.descriptor)
+            .and_then(|method| method.get_code()).and_then(|code| code.instructions().ok()) {
+            Some(instructions) => instructions,
+            None => return
+        };
+        for instruction in instructions.iter() {
+            match instruction {
+                Instruction::InvokeDynamic((index, _)) => {
+                    let bootstrap = match class.get_constant(*index) {
+                        Some(ConstantType::InvokeDynamic { bootstrap_method_attr_index, .. }) => class.get_bootstrap_method(*bootstrap_method_attr_index),
+                        _ => None
+                    };
+                    if let Some((handle, arguments)) = bootstrap {
+                        for constant in Some(handle).into_iter().chain(arguments) {
+                            self.refer(class, constant, false);
+                        }
+                    }
+                },
+                Instruction::InvokeVirtual(index) | Instruction::InvokeInterface((index, _, _)) => self.refer(class, *index, true),
+                _ => if let Some(index) = instruction.constant_index() {
+                    self.refer(class, index, false);
+                }
+            }
+        }
+    }
+}
+
+impl DeadCodeReport {
+    /// analyzes `classes`, starting from `entry_points` or, without any, from the `main` methods.
+    pub fn analyze(classes: &[ClassFile], entry_points: &[EntryPoint]) -> DeadCodeReport {
+        let mut hierarchy = ClassHierarchy::new();
+        for class in classes.iter() {
+            hierarchy.add(class);
+        }
+        let mut reachability = Reachability {
+            classes: classes.iter().map(|class| (class.get_class_name(), class)).collect(),
+            hierarchy,
+            reached: BTreeSet::new(),
+            used_classes: BTreeSet::new(),
+            pending: Vec::new(),
+        };
+
+        let mut report = DeadCodeReport::default();
+        let sorted = reachability.classes.values().cloned().collect::<Vec<_>>();
+        for class in sorted.iter() {
+            let class_name = class.get_class_name();
+            for method in class.methods.iter() {
+                let entry = match entry_points {
+                    [] => method.name == "main" && method.descriptor == "([Ljava/lang/String;)V" && method.access_flags & ACC_STATIC != 0,
+                    _ => entry_points.iter().any(|entry| entry.matches(class_name, method))
+                };
+                if entry {
+                    reachability.use_class(class_name);
+                    reachability.reach(class_name, &method.name, &method.descriptor);
+                }
+                report.check_code(class, method, &reachability.hierarchy);
+            }
+        }
+        while let Some(id) = reachability.pending.pop() {
+            reachability.visit(&id);
+        }
+
+        for class in sorted.iter() {
+            for method in class.methods.iter().filter(|method| method.get_code().is_some()) {
+                let id = MethodId::new(class.get_class_name(), &method.name, &method.descriptor);
+                if !reachability.reached.contains(&id) {
+                    report.unused_methods.push(id);
+                }
+            }
+        }
+        report.unused_methods.sort();
+        report
+    }
+
+    /// finds the unreachable blocks and impossible handlers of one method.
+    fn check_code(&mut self, class: &ClassFile, method: &Method, hierarchy: &ClassHierarchy) {
+        let code = match method.get_code() {
+            Some(code) => code,
+            None => return
+        };
+        let id = MethodId::new(class.get_class_name(), &method.name, &method.descriptor);
+        let cfg = match ControlFlowGraph::build(method) {
+            Ok(cfg) => cfg,
+            Err(err) => return self.skipped.push((id, err.to_string()))
+        };
+
+        for block in cfg.unreachable_blocks() {
+            let instructions = &cfg.blocks[block].instructions;
+            self.unreachable_blocks.push(UnreachableBlock {
+                method: id.clone(),
+                start_pc: cfg.instructions[instructions.start].0,
+                end_pc: cfg.instructions.get(instructions.end).map_or_else(|| code.code_length(), |&(pc, _)| pc),
+            });
+        }
+
+        let catch_type = |index: u16| match index {
+            0 => None,
+            index => Some(class.get_class_name_at(index).unwrap_or(""))
+        };
+        let throwing = cfg.instructions.iter().filter(|(_, instruction)| instruction.can_throw()).map(|&(pc, _)| pc).collect::<Vec<_>>();
+        for (index, &(start, end, handler, catches)) in code.exception_table.iter().enumerate() {
+            let range = usize::from(start)..usize::from(end);
+            let covered = throwing.iter().filter(|pc| range.contains(pc)).collect::<Vec<_>>();
+            let problem = if covered.is_empty() {
+                HandlerProblem::NothingThrows
+            } else {
+                // the first earlier handler catching everything this one does, for each instruction
+                let first = covered.iter().map(|&&pc| code.exception_table[..index].iter()
+                    .find(|&&(start, end, _, earlier)| usize::from(start) <= pc && pc < usize::from(end) && catches_all(hierarchy, catch_type(earlier), catch_type(catches)))
+                    .map(|&(_, _, handler, _)| usize::from(handler)))
+                    .collect::<Option<BTreeSet<_>>>();
+                match first {
+                    Some(by) => HandlerProblem::Shadowed { by: by.into_iter().collect() },
+                    None => continue
+                }
+            };
+            self.impossible_handlers.push(ImpossibleHandler {
+                method: id.clone(),
+                start_pc: range.start,
+                end_pc: range.end,
+                handler_pc: usize::from(handler),
+                catch_type: catch_type(catches).map(String::from),
+                problem,
+            });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unreachable_blocks.is_empty() && self.impossible_handlers.is_empty() && self.unused_methods.is_empty()
+    }
+
+    /// the report as a json object with a list for each kind of finding:
+    ///
+    /// ```text
+    /// {"unreachable_blocks": [{"class": "A", "method": "m", "descriptor": "()I", "start_pc": 3, "end_pc": 4}],
+    ///  "impossible_handlers": [{"class": .., "start_pc": 0, "end_pc": 2, "handler_pc": 5,
+    ///                           "catch_type": "java/lang/RuntimeException", "reason": "nothing_throws", "shadowed_by": []}],
+    ///  "unused_methods": [{"class": .., "method": .., "descriptor": ..}],
+    ///  "skipped": [{"class": .., "method": .., "descriptor": .., "error": ".."}]}
+    /// ```
+    pub fn to_json(&self) -> String {
+        let method = |id: &MethodId| format!(r#""class": {}, "method": {}, "descriptor": {}"#,
+                                             json_string(&id.class_name), json_string(&id.name), json_string(&id.descriptor));
+        let blocks = self.unreachable_blocks.iter()
+            .map(|block| format!(r#"{{{}, "start_pc": {}, "end_pc": {}}}"#, method(&block.method), block.start_pc, block.end_pc));
+        let handlers = self.impossible_handlers.iter().map(|handler| {
+            let (reason, by) = match handler.problem {
+                HandlerProblem::NothingThrows => ("nothing_throws", &[][..]),
+                HandlerProblem::Shadowed { ref by } => ("shadowed", &by[..])
+            };
+            format!(r#"{{{}, "start_pc": {}, "end_pc": {}, "handler_pc": {}, "catch_type": {}, "reason": "{}", "shadowed_by": [{}]}}"#,
+                    method(&handler.method), handler.start_pc, handler.end_pc, handler.handler_pc,
+                    handler.catch_type.as_ref().map_or_else(|| String::from("null"), |name| json_string(name)), reason,
+                    by.iter().map(usize::to_string).collect::<Vec<_>>().join(", "))
+        });
+        let unused = self.unused_methods.iter().map(|id| format!("{{{}}}", method(id)));
+        let skipped = self.skipped.iter().map(|(id, err)| format!(r#"{{{}, "error": {}}}"#, method(id), json_string(err)));
+
+        let list = |items: Vec<String>| match items.len() {
+            0 => String::from("[]"),
+            _ => format!("[\n    {}\n  ]", items.join(",\n    "))
+        };
+        format!("{{\n  \"unreachable_blocks\": {},\n  \"impossible_handlers\": {},\n  \"unused_methods\": {},\n  \"skipped\": {}\n}}\n",
+                list(blocks.collect()), list(handlers.collect()), list(unused.collect()), list(skipped.collect()))
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::{read_class_file, ClassWriter};
+    use super::*;
+
+    fn dead_code_sample() -> Vec<ClassFile<'static>> {
+        let classes: [&'static [u8]; 4] = [include_bytes!("../../../sample/DeadCode.class"), include_bytes!("../../../sample/DeadCode$Action.class"),
+            include_bytes!("../../../sample/DeadCode$Twice.class"), include_bytes!("../../../sample/DeadCode$Never.class")];
+        classes.iter().map(|bytes| read_class_file(bytes).unwrap().1).collect()
+    }
+
+    fn handlers_of<'r>(report: &'r DeadCodeReport, method: &str) -> Vec<&'r ImpossibleHandler> {
+        report.impossible_handlers.iter().filter(|handler| handler.method.name == method).collect()
+    }
+
+    #[test]
+    fn it_finds_handlers_which_catch_nothing() {
+        let report = DeadCodeReport::analyze(&dead_code_sample(), &[]);
+        // `x = 1` cannot throw
+        let quiet = handlers_of(&report, "quiet");
+        assert_eq!(quiet.len(), 1);
+        assert_eq!((quiet[0].start_pc, quiet[0].end_pc, quiet[0].handler_pc), (0, 2, 5));
+        assert_eq!(quiet[0].catch_type.as_deref(), Some("java/lang/RuntimeException"));
+        assert_eq!(quiet[0].problem, HandlerProblem::NothingThrows);
+
+        // the outer catch of `RuntimeException` only sees what the inner catch of `Exception` lets through
+        let nested = handlers_of(&report, "nested");
+        assert_eq!(nested.iter().map(|handler| (handler.start_pc, handler.handler_pc, handler.problem.clone())).collect::<Vec<_>>(),
+                   vec![(0, 7, HandlerProblem::Shadowed { by: vec![4] }), (4, 7, HandlerProblem::NothingThrows)]);
+    }
+
+    #[test]
+    fn it_finds_methods_the_entry_points_never_call() {
+        let report = DeadCodeReport::analyze(&dead_code_sample(), &[]);
+        let unused = report.unused_methods.iter().map(MethodId::to_string).collect::<Vec<_>>();
+        // `Never` is not instantiated, but `act` overrides the method `run` calls
+        assert_eq!(unused, vec!["DeadCode.<init>()V", "DeadCode.unused()I", "DeadCode.unusedHelper()I",
+                                "DeadCode$Never.<init>()V", "DeadCode$Never.toString()Ljava/lang/String;"]);
+
+        let entry = "DeadCode::unused".parse::<EntryPoint>().unwrap();
+        let report = DeadCodeReport::analyze(&dead_code_sample(), &[entry]);
+        assert!(!report.unused_methods.iter().any(|id| id.name == "unusedHelper"));
+        assert!(report.unused_methods.iter().any(|id| id.name == "main"));
+        assert!("DeadCode".parse::<EntryPoint>().is_err());
+    }
+
+    #[test]
+    fn it_finds_unreachable_blocks() {
+        let mut writer = ClassWriter::new("Skipped", "java/lang/Object");
+        // the `iconst_0` after the jump is never run
+        writer.method(0x0009, "one", "()I", 1, 0, &[Instruction::Goto(4), Instruction::IConst0(()), Instruction::IConst1(()), Instruction::IReturn(())]);
+        let bytes = writer.to_bytes();
+        let report = DeadCodeReport::analyze(&[ClassFile::parse(&bytes).unwrap()], &[]);
+        assert_eq!(report.unreachable_blocks, vec![UnreachableBlock { method: MethodId::new("Skipped", "one", "()I"), start_pc: 3, end_pc: 4 }]);
+        assert_eq!(report.unused_methods, vec![MethodId::new("Skipped", "one", "()I")]);
+
+        let json = report.to_json();
+        assert!(json.contains(r#"{"class": "Skipped", "method": "one", "descriptor": "()I", "start_pc": 3, "end_pc": 4}"#), "{}", json);
+        assert!(json.contains("\"impossible_handlers\": [],"), "{}", json);
+    }
+}
@@ -0,0 +1,421 @@
+use java::analysis::cfg::locals_used;
+use java::analysis::{CfgError, ControlFlowGraph, EdgeKind};
+use java::class_file::{ClassFile, ConstantType, Method, MethodDescriptor, ValueType};
+use java::instructions::Instruction;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+const ACC_STATIC: u16 = 0x0008;
+
+/// why an object can outlive the method which created it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    Returned,
+    Thrown,
+    /// written to a field, a static or an array.
+    Stored,
+    /// passed to a method, other than the receiver of a constructor which keeps it to itself.
+    Argument,
+    /// a local variable or stack slot holds it on one path into a block and something else on another.
+    Merged,
+    /// used by an instruction the analysis does not follow.
+    Unknown,
+}
+
+/// a `new` instruction of the analyzed method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub pc: usize,
+    pub class_name: String,
+    /// `None` if the object never leaves the frame of the method.
+    pub escape: Option<Escape>,
+    /// true if the object the instruction created before is dead whenever it runs again,
+    /// so the same storage can hold the new object.
+    pub reusable: bool,
+}
+
+/// the abstract value of a local variable or stack slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    /// anything the analysis does not track.
+    Other,
+    /// the object of an allocation, by index, or the receiver of the method after the allocations.
+    Object(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    locals: Vec<Value>,
+    stack: Vec<Value>,
+}
+
+/// which objects created by a method escape it, found by following references through the local
+/// variables and the operand stack of its control flow graph.
+///
+/// objects which never escape can live in storage of the frame instead of the heap. the analysis
+/// is conservative: an object stored anywhere, even into another object which does not escape,
+/// escapes, and so does one a live local variable holds different objects for on different paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscapeAnalysis {
+    pub allocations: Vec<Allocation>,
+    /// why the receiver escapes an instance method, `None` if it does not.
+    pub receiver: Option<Escape>,
+}
+
+impl EscapeAnalysis {
+    /// analyzes `method` of `class`. `receiver_escapes` tells whether the constructor of a class,
+    /// named by class, name and descriptor, lets its receiver escape. constructors it does not know
+    /// should count as escaping.
+    pub fn analyze<F>(class: &ClassFile, method: &Method, mut receiver_escapes: F) -> Result<EscapeAnalysis, CfgError>
+        where F: FnMut(&str, &str, &str) -> bool {
+        let cfg = ControlFlowGraph::build(method)?;
+        let code = method.get_code().ok_or(CfgError::NoCode)?;
+
+        let mut allocations = cfg.instructions.iter()
+            .filter_map(|&(pc, ref instruction)| match *instruction {
+                Instruction::New(index) => Some(Allocation {
+                    pc,
+                    class_name: String::from(class.get_class_name_at(index).unwrap_or("")),
+                    escape: None,
+                    reusable: true,
+                }),
+                _ => None
+            })
+            .collect::<Vec<_>>();
+        let has_receiver = method.access_flags & ACC_STATIC == 0;
+        let receiver = allocations.len();
+        let mut escapes: Vec<Option<Escape>> = vec![None; receiver + 1];
+
+        let mut entry = State { locals: vec![Value::Other; usize::from(code.max_locals)], stack: Vec::new() };
+        if has_receiver && !entry.locals.is_empty() {
+            entry.locals[0] = Value::Object(receiver);
+        }
+
+        let live_before = live_before(&cfg);
+        let mut states: Vec<Option<State>> = vec![None; cfg.blocks.len()];
+        states[0] = Some(entry);
+        let mut pending = vec![0];
+        while let Some(block) = pending.pop() {
+            let mut state = match states[block].clone() {
+                Some(state) => state,
+                None => continue
+            };
+            let mut handler_states = Vec::new();
+            for index in cfg.blocks[block].instructions.clone() {
+                let (pc, ref instruction) = cfg.instructions[index];
+                // a handler sees the local variables as they are before any instruction which throws
+                handler_states.push(State { locals: state.locals.clone(), stack: vec![Value::Other] });
+
+                if let Instruction::New(_) = *instruction {
+                    let site = allocations.iter().position(|allocation| allocation.pc == pc).unwrap();
+                    let live = &live_before[index];
+                    let held = state.stack.contains(&Value::Object(site)) || state.locals.iter().enumerate()
+                        .any(|(local, &value)| value == Value::Object(site) && live.contains(&(local as u16)));
+                    if held {
+                        allocations[site].reusable = false;
+                    }
+                    state.stack.push(Value::Object(site));
+                    continue;
+                }
+                if step(class, instruction, &mut state, &mut escapes, &mut receiver_escapes).is_none() {
+                    // whatever the instruction touches is lost track of
+                    for escape in escapes.iter_mut().filter(|escape| escape.is_none()) {
+                        *escape = Some(Escape::Unknown);
+                    }
+                    return Ok(EscapeAnalysis::finish(allocations, escapes, has_receiver));
+                }
+            }
+
+            let successors = cfg.blocks[block].successors.iter()
+                .map(|&(successor, kind)| (successor, kind == EdgeKind::Exception))
+                .collect::<Vec<_>>();
+            for (successor, exceptional) in successors {
+                let incoming = if exceptional { handler_states.clone() } else { vec![state.clone()] };
+                let mut changed = false;
+                for incoming in incoming {
+                    changed |= merge(&mut states[successor], incoming, &live_before[cfg.blocks[successor].instructions.start], &mut escapes);
+                }
+                if changed && !pending.contains(&successor) {
+                    pending.push(successor);
+                }
+            }
+        }
+
+        Ok(EscapeAnalysis::finish(allocations, escapes, has_receiver))
+    }
+
+    fn finish(mut allocations: Vec<Allocation>, escapes: Vec<Option<Escape>>, has_receiver: bool) -> EscapeAnalysis {
+        for (allocation, escape) in allocations.iter_mut().zip(escapes.iter()) {
+            allocation.escape = *escape;
+        }
+        let receiver = if has_receiver { escapes[allocations.len()] } else { None };
+        EscapeAnalysis { allocations, receiver }
+    }
+
+    /// the allocation of the `new` instruction at `pc`.
+    pub fn allocation_at(&self, pc: usize) -> Option<&Allocation> {
+        self.allocations.iter().find(|allocation| allocation.pc == pc)
+    }
+
+    /// the allocations whose objects never leave the method.
+    pub fn non_escaping(&self) -> impl Iterator<Item=&Allocation> {
+        self.allocations.iter().filter(|allocation| allocation.escape.is_none())
+    }
+}
+
+/// the local variables live before each instruction, by index.
+fn live_before(cfg: &ControlFlowGraph) -> Vec<BTreeSet<u16>> {
+    let liveness = cfg.liveness();
+    let mut live_before = vec![BTreeSet::new(); cfg.instructions.len()];
+    for block in cfg.blocks.iter() {
+        let mut live = liveness.live_out[block.id].clone();
+        for index in block.instructions.clone().rev() {
+            let (reads, writes) = locals_used(&cfg.instructions[index].1);
+            for local in writes {
+                live.remove(&local);
+            }
+            live.extend(reads);
+            live_before[index] = live.clone();
+        }
+    }
+    live_before
+}
+
+/// merges the state flowing along an edge into the state at the start of a block. objects a live
+/// slot holds on one path but not on the other escape. returns true if the state changed.
+fn merge(existing: &mut Option<State>, incoming: State, live: &BTreeSet<u16>, escapes: &mut [Option<Escape>]) -> bool {
+    let mut incoming = incoming;
+    // dead locals do not matter, like the object of a loop iteration before the next one is created
+    for (local, value) in incoming.locals.iter_mut().enumerate() {
+        if !live.contains(&(local as u16)) {
+            *value = Value::Other;
+        }
+    }
+
+    let state = match existing {
+        Some(state) => state,
+        None => {
+            *existing = Some(incoming);
+            return true;
+        }
+    };
+    if state.stack.len() != incoming.stack.len() {
+        for value in state.stack.iter().chain(incoming.stack.iter()) {
+            escape(escapes, *value, Escape::Merged);
+        }
+        state.stack = vec![Value::Other; state.stack.len()];
+        return false;
+    }
+
+    let mut changed = false;
+    let slots = state.locals.iter_mut().zip(incoming.locals.iter())
+        .chain(state.stack.iter_mut().zip(incoming.stack.iter()));
+    for (current, &other) in slots {
+        if *current != other {
+            escape(escapes, *current, Escape::Merged);
+            escape(escapes, other, Escape::Merged);
+            if *current != Value::Other {
+                *current = Value::Other;
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+fn escape(escapes: &mut [Option<Escape>], value: Value, reason: Escape) {
+    if let Value::Object(object) = value {
+        if escapes[object].is_none() {
+            escapes[object] = Some(reason);
+        }
+    }
+}
+
+/// the class, name and descriptor of a `MethodRef` or `InterfaceMethodRef` constant.
+fn method_ref<'c>(class: &'c ClassFile, index: u16) -> Option<(&'c str, &'c str, &'c str)> {
+    match class.get_constant(index)? {
+        ConstantType::MethodRef { class_index, name_and_type_index } |
+        ConstantType::InterfaceMethodRef { class_index, name_and_type_index } => {
+            let (name, descriptor) = class.get_name_and_type(*name_and_type_index)?;
+            Some((class.get_class_name_at(*class_index)?, name, descriptor))
+        }
+        _ => None
+    }
+}
+
+/// the number of values a plain instruction pops and pushes, for the ones which do nothing a
+/// reference could escape by.
+fn plain_effect(instruction: &Instruction) -> Option<(usize, usize)> {
+    Some(match instruction {
+        Instruction::NOOP(()) | Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::IInc(_) | Instruction::Return(()) => (0, 0),
+        Instruction::AConstNull(()) | Instruction::IConstm1(()) | Instruction::IConst0(()) | Instruction::IConst1(()) |
+        Instruction::IConst2(()) | Instruction::IConst3(()) | Instruction::IConst4(()) | Instruction::IConst5(()) |
+        Instruction::LConst0(()) | Instruction::LConst1(()) | Instruction::FConst0(()) | Instruction::FConst1(()) |
+        Instruction::FConst2(()) | Instruction::DConst0(()) | Instruction::DConst1(()) | Instruction::BIPush(_) |
+        Instruction::SIPush(_) | Instruction::LDC(_) | Instruction::LDCW(_) | Instruction::LDC2W(_) | Instruction::GetStatic(_) => (0, 1),
+        Instruction::IAdd(()) | Instruction::LAdd(()) | Instruction::FAdd(()) | Instruction::DAdd(()) |
+        Instruction::ISub(()) | Instruction::LSub(()) | Instruction::FSub(()) | Instruction::DSub(()) |
+        Instruction::IMul(()) | Instruction::LMul(()) | Instruction::FMul(()) | Instruction::DMul(()) |
+        Instruction::IDiv(()) | Instruction::LDiv(()) | Instruction::FDiv(()) | Instruction::DDiv(()) |
+        Instruction::IRem(()) | Instruction::LRem(()) | Instruction::FRem(()) | Instruction::DRem(()) |
+        Instruction::IShl(()) | Instruction::LShl(()) | Instruction::IShr(()) | Instruction::LShr(()) |
+        Instruction::IUSHR(()) | Instruction::LUSHR(()) | Instruction::IAnd(()) | Instruction::LAnd(()) |
+        Instruction::IOr(()) | Instruction::LOr(()) | Instruction::IXor(()) | Instruction::LXor(()) |
+        Instruction::LCmp(()) | Instruction::FCmpL(()) | Instruction::FCmpG(()) | Instruction::DCmpL(()) | Instruction::DCmpG(()) |
+        Instruction::IALoad(()) | Instruction::LALoad(()) | Instruction::FALoad(()) | Instruction::DALoad(()) |
+        Instruction::AALoad(()) | Instruction::BALoad(()) | Instruction::CALoad(()) | Instruction::ScALoad(()) => (2, 1),
+        Instruction::INeg(()) | Instruction::LNeg(()) | Instruction::FNeg(()) | Instruction::DNeg(()) |
+        Instruction::I2L(()) | Instruction::I2F(()) | Instruction::I2D(()) | Instruction::L2I(()) | Instruction::L2F(()) |
+        Instruction::L2D(()) | Instruction::F2I(()) | Instruction::F2L(()) | Instruction::F2D(()) | Instruction::D2I(()) |
+        Instruction::D2L(()) | Instruction::D2F(()) | Instruction::I2B(()) | Instruction::I2C(()) | Instruction::I2S(()) |
+        Instruction::NewArray(_) | Instruction::AAewArray(_) | Instruction::ArrayLength(()) | Instruction::InstanceOf(_) |
+        // reading a field of an object does not let it escape
+        Instruction::GetField(_) => (1, 1),
+        Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) | Instruction::Ifge(_) | Instruction::Ifgt(_) |
+        Instruction::Ifle(_) | Instruction::IfNull(_) | Instruction::IfNonNull(_) | Instruction::TableSwitch(_) |
+        Instruction::LookupSwitch(_) | Instruction::Pop(()) | Instruction::MonitorEnter(()) | Instruction::MonitorExit(()) |
+        Instruction::IReturn(()) | Instruction::LReturn(()) | Instruction::FReturn(()) | Instruction::DReturn(()) => (1, 0),
+        // comparing references does not either, the objects keep their identity
+        Instruction::IfICmpEQ(_) | Instruction::IfICmpNE(_) | Instruction::IfICmpLT(_) | Instruction::IfICmpGE(_) |
+        Instruction::IfICmpGT(_) | Instruction::IfICmpLE(_) | Instruction::IfACmpEQ(_) | Instruction::IfACmpNE(_) => (2, 0),
+        Instruction::IAStore(()) | Instruction::LAStore(()) | Instruction::FAStore(()) | Instruction::DAStore(()) |
+        Instruction::BAStore(()) | Instruction::CAStore(()) | Instruction::SAStore(()) => (3, 0),
+        _ => return None
+    })
+}
+
+/// the local variable an instruction loads or stores a single slot value from or to.
+fn local_of(instruction: &Instruction) -> Option<usize> {
+    let (reads, writes) = locals_used(instruction);
+    match (reads.len(), writes.len()) {
+        (1, 0) | (2, 0) => Some(usize::from(reads[0])),
+        (0, 1) | (0, 2) => Some(usize::from(writes[0])),
+        _ => None
+    }
+}
+
+/// runs `instruction` on the abstract state. `None` if the analysis does not follow it.
+fn step<F>(class: &ClassFile, instruction: &Instruction, state: &mut State, escapes: &mut [Option<Escape>], receiver_escapes: &mut F) -> Option<()>
+    where F: FnMut(&str, &str, &str) -> bool {
+    let (reads, writes) = locals_used(instruction);
+    match *instruction {
+        Instruction::ALoad(_) | Instruction::ALoad0(()) | Instruction::ALoad1(()) | Instruction::ALoad2(()) | Instruction::ALoad3(()) => {
+            let value = *state.locals.get(local_of(instruction)?)?;
+            state.stack.push(value);
+        }
+        Instruction::AStore(_) | Instruction::AStore0(()) | Instruction::AStore1(()) | Instruction::AStore2(()) | Instruction::AStore3(()) => {
+            let value = state.stack.pop()?;
+            *state.locals.get_mut(local_of(instruction)?)? = value;
+        }
+        Instruction::IInc(_) => (),
+        // the other loads and stores of ints, longs, floats and doubles
+        _ if !reads.is_empty() && writes.is_empty() => state.stack.push(Value::Other),
+        _ if reads.is_empty() && !writes.is_empty() => {
+            state.stack.pop()?;
+            for local in writes {
+                *state.locals.get_mut(usize::from(local))? = Value::Other;
+            }
+        }
+        Instruction::Dup(()) => {
+            let value = *state.stack.last()?;
+            state.stack.push(value);
+        }
+        Instruction::Swap(()) => {
+            let (top, below) = (state.stack.pop()?, state.stack.pop()?);
+            state.stack.push(top);
+            state.stack.push(below);
+        }
+        Instruction::CheckCast(_) => (),
+        Instruction::AReturn(()) => escape(escapes, state.stack.pop()?, Escape::Returned),
+        Instruction::AThrow(()) => escape(escapes, state.stack.pop()?, Escape::Thrown),
+        Instruction::PutStatic(_) => escape(escapes, state.stack.pop()?, Escape::Stored),
+        Instruction::PutField(_) => {
+            escape(escapes, state.stack.pop()?, Escape::Stored);
+            state.stack.pop()?;
+        }
+        Instruction::AAStore(()) => {
+            escape(escapes, state.stack.pop()?, Escape::Stored);
+            state.stack.truncate(state.stack.len().checked_sub(2)?);
+        }
+        Instruction::InvokeVirtual(index) | Instruction::InvokeSpecial(index) | Instruction::InvokeStatic(index) |
+        Instruction::InvokeInterface((index, _, _)) => {
+            let (owner, name, descriptor) = method_ref(class, index)?;
+            let signature = MethodDescriptor::from_str(descriptor).ok()?;
+            for _ in 0..signature.arguments.len() {
+                escape(escapes, state.stack.pop()?, Escape::Argument);
+            }
+            let has_receiver = !matches!(*instruction, Instruction::InvokeStatic(_));
+            if has_receiver {
+                let receiver = state.stack.pop()?;
+                let constructed = match *instruction {
+                    Instruction::InvokeSpecial(_) if name == "<init>" => !receiver_escapes(owner, name, descriptor),
+                    _ => false
+                };
+                if !constructed {
+                    escape(escapes, receiver, Escape::Argument);
+                }
+            }
+            if signature.return_type != ValueType::Void {
+                state.stack.push(Value::Other);
+            }
+        }
+        _ => {
+            let (pops, pushes) = plain_effect(instruction)?;
+            let len = state.stack.len().checked_sub(pops)?;
+            state.stack.truncate(len);
+            for _ in 0..pushes {
+                state.stack.push(Value::Other);
+            }
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn analyze(class: &ClassFile, name: &str) -> EscapeAnalysis {
+        let method = class.methods.iter().find(|method| method.name == name).unwrap();
+        // of the constructors the sample calls only the one of Tracked leaks its receiver
+        EscapeAnalysis::analyze(class, method, |owner, _, _| owner == "Tracked").unwrap()
+    }
+
+    fn escapes(class: &ClassFile, name: &str) -> Vec<Option<Escape>> {
+        analyze(class, name).allocations.into_iter().map(|allocation| allocation.escape).collect()
+    }
+
+    #[test]
+    fn temporaries_do_not_escape() {
+        let class = read_class_file(include_bytes!("../../../sample/Escapes.class")).unwrap().1;
+        let analysis = analyze(&class, "temporaries");
+        assert_eq!(analysis.allocations.len(), 1);
+        assert_eq!(analysis.non_escaping().map(|allocation| (allocation.class_name.as_str(), allocation.reusable)).collect::<Vec<_>>(), vec![("Vec2", true)]);
+        assert_eq!(analysis.allocation_at(analysis.allocations[0].pc), Some(&analysis.allocations[0]));
+        assert_eq!(analysis.receiver, None);
+
+        assert_eq!(escapes(&class, "pairs"), vec![None, None]);
+    }
+
+    #[test]
+    fn objects_escape_by_returns_stores_calls_and_merges() {
+        let class = read_class_file(include_bytes!("../../../sample/Escapes.class")).unwrap().1;
+        assert_eq!(escapes(&class, "make"), vec![Some(Escape::Returned)]);
+        assert_eq!(escapes(&class, "stored"), vec![Some(Escape::Stored)]);
+        assert_eq!(escapes(&class, "passed"), vec![Some(Escape::Argument)]);
+        assert_eq!(escapes(&class, "tracked"), vec![Some(Escape::Argument)]);
+        assert_eq!(escapes(&class, "either"), vec![Some(Escape::Merged), Some(Escape::Merged)]);
+    }
+
+    #[test]
+    fn constructors_can_leak_their_receiver() {
+        let vec2 = read_class_file(include_bytes!("../../../sample/Vec2.class")).unwrap().1;
+        assert_eq!(analyze(&vec2, "<init>").receiver, None);
+
+        let tracked = read_class_file(include_bytes!("../../../sample/Tracked.class")).unwrap().1;
+        let constructor = tracked.methods.iter().find(|method| method.name == "<init>").unwrap();
+        let analysis = EscapeAnalysis::analyze(&tracked, constructor, |owner, _, _| owner != "java/lang/Object").unwrap();
+        assert_eq!(analysis.receiver, Some(Escape::Stored));
+    }
+}
@@ -0,0 +1,300 @@
+use java::class_file::ClassFile;
+use java::runtime::scan_classpath;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+const ACC_FINAL: u16 = 0x0010;
+const ACC_ABSTRACT: u16 = 0x0400;
+const ACC_MODULE: u16 = 0x8000;
+
+/// a class or interface of a `ClassHierarchy` with its direct supertypes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HierarchyNode {
+    pub name: String,
+    /// `None` for `java/lang/Object`. interfaces extend `java/lang/Object` like in the class file.
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub is_interface: bool,
+    pub is_abstract: bool,
+    pub is_final: bool,
+}
+
+/// the subtype graph of a set of classes, like the classes of a classpath, for class hierarchy
+/// analysis: which classes are never extended and which interfaces have a single implementation.
+///
+/// the results only hold for the classes added. supertypes which were not added, like the classes
+/// of the jdk, are known by name but have no node.
+#[derive(Debug, Clone, Default)]
+pub struct ClassHierarchy {
+    nodes: BTreeMap<String, HierarchyNode>,
+    /// the classes which extend or implement a type directly, by the name of the type.
+    subtypes: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ClassHierarchy {
+    pub fn new() -> ClassHierarchy {
+        ClassHierarchy::default()
+    }
+
+    /// the hierarchy of every class on `classpath`. files which cannot be read or parsed are
+    /// returned with the reason instead.
+    pub fn from_classpath(classpath: &[PathBuf]) -> (ClassHierarchy, Vec<(PathBuf, String)>) {
+        let mut hierarchy = ClassHierarchy::new();
+        let mut failures = Vec::new();
+        for path in scan_classpath(classpath) {
+            let parsed = fs::read(&path).map_err(|err| err.to_string())
+                .and_then(|bytes| ClassFile::parse(&bytes).map(|class| hierarchy.add(&class)).map_err(|err| err.to_string()));
+            if let Err(err) = parsed {
+                failures.push((path, err));
+            }
+        }
+        (hierarchy, failures)
+    }
+
+    /// adds a class, replacing an earlier one of the same name. `module-info` is no type and ignored.
+    pub fn add(&mut self, class: &ClassFile) {
+        if class.access_flags & ACC_MODULE != 0 {
+            return;
+        }
+        let name = String::from(class.get_class_name());
+        self.remove(&name);
+
+        let node = HierarchyNode {
+            name: name.clone(),
+            super_class: class.get_super_class_name().map(String::from),
+            interfaces: class.interfaces.iter().filter_map(|&index| class.get_class_name_at(index)).map(String::from).collect(),
+            is_interface: class.is_interface(),
+            is_abstract: class.access_flags & ACC_ABSTRACT != 0,
+            is_final: class.access_flags & ACC_FINAL != 0,
+        };
+        for supertype in node.super_class.iter().chain(node.interfaces.iter()) {
+            self.subtypes.entry(supertype.clone()).or_default().insert(name.clone());
+        }
+        self.nodes.insert(name, node);
+    }
+
+    /// removes a type with the edges to its supertypes. its subtypes keep theirs.
+    pub fn remove(&mut self, name: &str) -> Option<HierarchyNode> {
+        let node = self.nodes.remove(name)?;
+        for supertype in node.super_class.iter().chain(node.interfaces.iter()) {
+            if let Some(subtypes) = self.subtypes.get_mut(supertype) {
+                subtypes.remove(name);
+            }
+        }
+        Some(node)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HierarchyNode> {
+        self.nodes.get(name)
+    }
+
+    /// the classes and interfaces added, sorted by name.
+    pub fn nodes(&self) -> impl Iterator<Item=&HierarchyNode> {
+        self.nodes.values()
+    }
+
+    /// the types which extend or implement `name` directly, sorted.
+    pub fn direct_subtypes(&self, name: &str) -> Vec<&str> {
+        self.subtypes.get(name).map_or_else(Vec::new, |subtypes| subtypes.iter().map(String::as_str).collect())
+    }
+
+    /// the types which extend or implement `name` directly or through other types, sorted.
+    pub fn subtypes(&self, name: &str) -> Vec<&str> {
+        let mut found = BTreeSet::new();
+        let mut pending = self.direct_subtypes(name);
+        while let Some(subtype) = pending.pop() {
+            if found.insert(subtype) {
+                pending.extend(self.direct_subtypes(subtype));
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    /// the classes and interfaces `name` extends or implements, directly or not, sorted. supertypes
+    /// which were not added are listed but not followed.
+    pub fn supertypes(&self, name: &str) -> Vec<&str> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![name];
+        while let Some(current) = pending.pop() {
+            if let Some(node) = self.nodes.get(current) {
+                for supertype in node.super_class.iter().chain(node.interfaces.iter()) {
+                    if found.insert(supertype.as_str()) {
+                        pending.push(supertype);
+                    }
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    /// true if `sub` is `sup` or one of its subtypes.
+    pub fn is_subtype(&self, sub: &str, sup: &str) -> bool {
+        sub == sup || self.supertypes(sub).contains(&sup)
+    }
+
+    /// a class which is not declared final but has no subclasses, so calls of its methods on its
+    /// instances can be bound statically as long as no other classes are loaded.
+    pub fn is_effectively_final(&self, name: &str) -> bool {
+        match self.nodes.get(name) {
+            Some(node) => !node.is_interface && !node.is_final && self.direct_subtypes(name).is_empty(),
+            None => false
+        }
+    }
+
+    pub fn effectively_final_classes(&self) -> Vec<&str> {
+        self.nodes.keys().map(String::as_str).filter(|name| self.is_effectively_final(name)).collect()
+    }
+
+    /// the classes which can be instantiated and are subtypes of `name`, sorted.
+    pub fn implementations(&self, name: &str) -> Vec<&str> {
+        self.subtypes(name).into_iter()
+            .filter(|subtype| self.nodes.get(*subtype).is_some_and(|node| !node.is_interface && !node.is_abstract))
+            .collect()
+    }
+
+    /// the interfaces with exactly one class implementing them, with that class.
+    pub fn single_implementation_interfaces(&self) -> Vec<(&str, &str)> {
+        self.nodes.values().filter(|node| node.is_interface).filter_map(|node| match self.implementations(&node.name)[..] {
+            [implementation] => Some((node.name.as_str(), implementation)),
+            _ => None
+        }).collect()
+    }
+
+    /// renders the subtype graph in the Graphviz DOT language, with edges from each type to its
+    /// supertypes. interfaces are ellipses, effectively final classes are filled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph hierarchy {\n    rankdir=BT;\n    node [shape=box, fontname=monospace];\n");
+        for node in self.nodes.values() {
+            let style = match (node.is_interface, node.is_final || self.is_effectively_final(&node.name)) {
+                (true, _) => " [shape=ellipse]",
+                (false, true) => " [style=filled]",
+                (false, false) => ""
+            };
+            dot.push_str(&format!("    \"{}\"{};\n", node.name, style));
+        }
+        for node in self.nodes.values() {
+            if let Some(ref super_class) = node.super_class {
+                if !node.is_interface {
+                    dot.push_str(&format!("    \"{}\" -> \"{}\";\n", node.name, super_class));
+                }
+            }
+            for interface in node.interfaces.iter() {
+                dot.push_str(&format!("    \"{}\" -> \"{}\" [style=dashed];\n", node.name, interface));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// a line per type with its supertypes and subtypes, then the effectively final classes and the
+/// interfaces with a single implementation.
+impl fmt::Display for ClassHierarchy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in self.nodes.values() {
+            let kind = match (node.is_interface, node.is_abstract, node.is_final) {
+                (true, _, _) => "interface",
+                (false, true, _) => "abstract class",
+                (false, false, true) => "final class",
+                (false, false, false) => "class"
+            };
+            write!(f, "{} {}", kind, node.name)?;
+            match node.super_class {
+                Some(ref super_class) if !node.is_interface => write!(f, " extends {}", super_class)?,
+                _ => ()
+            }
+            if !node.interfaces.is_empty() {
+                write!(f, " {} {}", if node.is_interface { "extends" } else { "implements" }, node.interfaces.join(", "))?;
+            }
+            writeln!(f)?;
+            let subtypes = self.subtypes(&node.name);
+            if !subtypes.is_empty() {
+                writeln!(f, "  subtypes: {}", subtypes.join(", "))?;
+            }
+        }
+
+        writeln!(f, "\neffectively final classes: {}", self.effectively_final_classes().len())?;
+        for class in self.effectively_final_classes() {
+            writeln!(f, "  {}", class)?;
+        }
+        writeln!(f, "\ninterfaces with a single implementation: {}", self.single_implementation_interfaces().len())?;
+        for (interface, implementation) in self.single_implementation_interfaces() {
+            writeln!(f, "  {} -> {}", interface, implementation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use std::path::Path;
+    use super::*;
+
+    fn figures() -> ClassHierarchy {
+        let mut hierarchy = ClassHierarchy::new();
+        let classes: [&'static [u8]; 5] = [include_bytes!("../../../sample/Figure.class"), include_bytes!("../../../sample/Labeled.class"),
+            include_bytes!("../../../sample/Polygon.class"), include_bytes!("../../../sample/Triangle.class"), include_bytes!("../../../sample/Pentagon.class")];
+        for bytes in classes.iter() {
+            hierarchy.add(&read_class_file(bytes).unwrap().1);
+        }
+        hierarchy
+    }
+
+    #[test]
+    fn it_computes_subtypes_and_supertypes() {
+        let hierarchy = figures();
+        assert_eq!(hierarchy.direct_subtypes("Polygon"), vec!["Pentagon", "Triangle"]);
+        assert_eq!(hierarchy.subtypes("Figure"), vec!["Pentagon", "Polygon", "Triangle"]);
+        assert_eq!(hierarchy.subtypes("java/lang/Object"), vec!["Figure", "Labeled", "Pentagon", "Polygon", "Triangle"]);
+        assert_eq!(hierarchy.supertypes("Pentagon"), vec!["Figure", "Labeled", "Polygon", "java/lang/Object"]);
+        assert!(hierarchy.is_subtype("Triangle", "Figure"));
+        assert!(!hierarchy.is_subtype("Triangle", "Labeled"));
+        assert_eq!(hierarchy.implementations("Figure"), vec!["Pentagon", "Triangle"]);
+    }
+
+    #[test]
+    fn it_finds_final_classes_and_single_implementations() {
+        let hierarchy = figures();
+        // `Triangle` is declared final, `Polygon` is extended
+        assert_eq!(hierarchy.effectively_final_classes(), vec!["Pentagon"]);
+        assert_eq!(hierarchy.single_implementation_interfaces(), vec![("Labeled", "Pentagon")]);
+
+        let report = hierarchy.to_string();
+        assert!(report.contains("class Pentagon extends Polygon implements Labeled\n"), "{}", report);
+        assert!(report.contains("interface Figure\n  subtypes: Pentagon, Polygon, Triangle\n"), "{}", report);
+        assert!(report.contains("interfaces with a single implementation: 1\n  Labeled -> Pentagon\n"), "{}", report);
+
+        let dot = hierarchy.to_dot();
+        assert!(dot.contains("\"Pentagon\" [style=filled];"));
+        assert!(dot.contains("\"Pentagon\" -> \"Labeled\" [style=dashed];"));
+    }
+
+    #[test]
+    fn replaced_classes_leave_the_graph() {
+        let mut hierarchy = figures();
+        hierarchy.add(&read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        assert!(hierarchy.is_effectively_final("Loop"));
+        // a class of the same name replaces the old one with its edges
+        let mut renamed = read_class_file(include_bytes!("../../../sample/Triangle.class")).unwrap().1;
+        renamed.access_flags &= !ACC_FINAL;
+        renamed.super_index = 0;
+        hierarchy.add(&renamed);
+        assert_eq!(hierarchy.direct_subtypes("Polygon"), vec!["Pentagon"]);
+
+        assert!(hierarchy.remove("Pentagon").is_some());
+        assert!(hierarchy.direct_subtypes("Polygon").is_empty());
+        assert!(hierarchy.is_effectively_final("Polygon"));
+    }
+
+    #[test]
+    fn it_reads_the_classpath() {
+        let (hierarchy, failures) = ClassHierarchy::from_classpath(&[Path::new(env!("CARGO_MANIFEST_DIR")).join("sample")]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(hierarchy.direct_subtypes("Dispatch"), vec!["Sub"]);
+        assert!(hierarchy.get("Valued").unwrap().is_interface);
+        assert!(hierarchy.get("module-info").is_none());
+    }
+}
@@ -0,0 +1,236 @@
+use java::analysis::cfg::locals_used;
+use java::analysis::{CfgError, ControlFlowGraph, EdgeKind};
+use java::class_file::Method;
+use java::instructions::Instruction;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// a loop counting an int local up by one from a non-negative start while it is below the length
+/// of an array, the way javac compiles `for (int i = 0; i < a.length; i++)`:
+///
+/// ```text
+///         iconst_0
+///         istore i
+/// header: iload i
+///         aload a
+///         arraylength
+///         if_icmpge exit
+///         ...
+///         iinc i 1
+///         goto header
+/// ```
+///
+/// the header is the guard of the loop. it throws on a null array and leaves the loop once the index
+/// reached the length, so `a[i]` is in bounds until the index is incremented again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountedLoop {
+    /// the block comparing the index with the length.
+    pub header: usize,
+    /// the blocks of the loop, including the header.
+    pub blocks: BTreeSet<usize>,
+    pub index: u16,
+    pub array: u16,
+    /// the instructions loading `a[i]` where it cannot be out of bounds, by index. the two
+    /// instructions before each of them load the array and the index.
+    pub safe_loads: Vec<usize>,
+}
+
+fn int_load(instruction: &Instruction) -> Option<u16> {
+    Some(match *instruction {
+        Instruction::ILoad(index) => u16::from(index),
+        Instruction::ILoad0(()) => 0,
+        Instruction::ILoad1(()) => 1,
+        Instruction::ILoad2(()) => 2,
+        Instruction::ILoad3(()) => 3,
+        _ => return None
+    })
+}
+
+fn reference_load(instruction: &Instruction) -> Option<u16> {
+    Some(match *instruction {
+        Instruction::ALoad(index) => u16::from(index),
+        Instruction::ALoad0(()) => 0,
+        Instruction::ALoad1(()) => 1,
+        Instruction::ALoad2(()) => 2,
+        Instruction::ALoad3(()) => 3,
+        _ => return None
+    })
+}
+
+fn non_negative_constant(instruction: &Instruction) -> bool {
+    match *instruction {
+        Instruction::IConst0(()) | Instruction::IConst1(()) | Instruction::IConst2(()) | Instruction::IConst3(()) |
+        Instruction::IConst4(()) | Instruction::IConst5(()) => true,
+        Instruction::BIPush(value) => value as i8 >= 0,
+        Instruction::SIPush(value) => value as i16 >= 0,
+        _ => false
+    }
+}
+
+/// the loads of the elements of int and reference arrays, which the interpreter keeps as they are.
+fn is_element_load(instruction: &Instruction) -> bool {
+    matches!(instruction, Instruction::IALoad(()) | Instruction::BALoad(()) | Instruction::CALoad(()) | Instruction::ScALoad(()) |
+        Instruction::AALoad(()))
+}
+
+impl CountedLoop {
+    /// the counted loops of a method, inner loops included. loops covered by exception handlers
+    /// are left out, a handler inside of one could be entered after the increment.
+    pub fn find(method: &Method) -> Result<Vec<CountedLoop>, CfgError> {
+        let cfg = ControlFlowGraph::build(method)?;
+        let idom = cfg.dominators();
+        let dominates = |dominator: usize, block: usize| {
+            let mut current = Some(block);
+            while let Some(block) = current {
+                if block == dominator {
+                    return true;
+                }
+                current = idom[block];
+            }
+            false
+        };
+
+        // the blocks jumping back to the header of each loop
+        let mut latches: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for block in cfg.blocks.iter() {
+            for &(successor, _) in block.successors.iter().filter(|&&(successor, _)| dominates(successor, block.id)) {
+                latches.entry(successor).or_default().push(block.id);
+            }
+        }
+
+        Ok(latches.into_iter().filter_map(|(header, latches)| CountedLoop::recognize(&cfg, header, &latches)).collect())
+    }
+
+    fn recognize(cfg: &ControlFlowGraph, header: usize, latches: &[usize]) -> Option<CountedLoop> {
+        let blocks = natural_loop(cfg, header, latches);
+        let (index, array, exit) = match &cfg.instructions[cfg.blocks[header].instructions.clone()] {
+            [(_, load), (_, array), (_, Instruction::ArrayLength(())), (pc, compare @ Instruction::IfICmpGE(_))] =>
+                (int_load(load)?, reference_load(array)?, (*pc as i64 + compare.branch_offset()?) as usize),
+            _ => return None
+        };
+        if blocks.contains(&cfg.block_at(exit)?) {
+            return None;
+        }
+
+        let mut increments = BTreeSet::new();
+        for &block in blocks.iter() {
+            if cfg.blocks[block].successors.iter().any(|&(_, kind)| kind == EdgeKind::Exception) {
+                return None;
+            }
+            for (_, instruction) in cfg.instructions[cfg.blocks[block].instructions.clone()].iter() {
+                match *instruction {
+                    // locals_used does not know the operands of wide instructions
+                    Instruction::Wide(_) => return None,
+                    Instruction::IInc(operands) if operands >> 8 == index => {
+                        if operands & 0xff != 1 {
+                            return None;
+                        }
+                        increments.insert(block);
+                    }
+                    _ => {
+                        let (_, written) = locals_used(instruction);
+                        if written.contains(&index) || written.contains(&array) {
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+        let mut entries = cfg.blocks[header].predecessors.iter().filter(|block| !blocks.contains(block));
+        if !entries.all(|&entry| starts_non_negative(cfg, entry, index)) {
+            return None;
+        }
+
+        // whether the index is the one the header compared when a block is entered, a fixed point
+        // over all paths from the header which starts out optimistic
+        let mut unchanged: BTreeMap<usize, bool> = blocks.iter().map(|&block| (block, true)).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in blocks.iter().filter(|&&block| block != header) {
+                let entered = cfg.blocks[block].predecessors.iter()
+                    .all(|&predecessor| predecessor == header || (unchanged.get(&predecessor) == Some(&true) && !increments.contains(&predecessor)));
+                if unchanged[&block] != entered {
+                    unchanged.insert(block, entered);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut safe_loads = Vec::new();
+        for &block in blocks.iter().filter(|&&block| block != header) {
+            let range = cfg.blocks[block].instructions.clone();
+            let mut in_bounds = unchanged[&block];
+            for position in range.clone() {
+                let instruction = &cfg.instructions[position].1;
+                if let Instruction::IInc(operands) = *instruction {
+                    if operands >> 8 == index {
+                        in_bounds = false;
+                    }
+                }
+                if in_bounds && is_element_load(instruction) && position >= range.start + 2
+                    && reference_load(&cfg.instructions[position - 2].1) == Some(array) && int_load(&cfg.instructions[position - 1].1) == Some(index) {
+                    safe_loads.push(position);
+                }
+            }
+        }
+
+        Some(CountedLoop { header, blocks, index, array, safe_loads })
+    }
+}
+
+/// the header and the blocks which reach one of the latches without going through it.
+fn natural_loop(cfg: &ControlFlowGraph, header: usize, latches: &[usize]) -> BTreeSet<usize> {
+    let mut blocks = BTreeSet::new();
+    blocks.insert(header);
+    let mut pending = latches.to_vec();
+    while let Some(block) = pending.pop() {
+        if blocks.insert(block) {
+            pending.extend(cfg.blocks[block].predecessors.iter().cloned());
+        }
+    }
+    blocks
+}
+
+/// true if the last write of `index` in `block` stores a non-negative constant.
+fn starts_non_negative(cfg: &ControlFlowGraph, block: usize, index: u16) -> bool {
+    let instructions = &cfg.instructions[cfg.blocks[block].instructions.clone()];
+    for position in (0..instructions.len()).rev() {
+        let (_, written) = locals_used(&instructions[position].1);
+        if written.contains(&index) {
+            let is_store = !matches!(instructions[position].1, Instruction::IInc(_));
+            return is_store && position > 0 && non_negative_constant(&instructions[position - 1].1);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    /// the pcs of the safe loads of the loops of a method, outer loops first.
+    fn safe_loads(name: &str) -> Vec<Vec<usize>> {
+        let class = read_class_file(include_bytes!("../../../sample/CountedLoops.class")).unwrap().1;
+        let method = class.methods.iter().find(|method| method.name == name).unwrap();
+        let pcs = method.get_code().unwrap().instructions_with_pc().unwrap();
+        CountedLoop::find(method).unwrap().iter()
+            .map(|counted| counted.safe_loads.iter().map(|&index| pcs[index].0).collect())
+            .collect()
+    }
+
+    #[test]
+    fn loads_before_the_increment_are_in_bounds() {
+        assert_eq!(safe_loads("sum"), vec![vec![18]]);
+        assert_eq!(safe_loads("filled"), vec![Vec::<usize>::new()]);
+        // the load after the increment in the branch is not
+        assert_eq!(safe_loads("incrementedFirst"), vec![vec![17]]);
+        assert_eq!(safe_loads("nested").iter().map(Vec::len).collect::<Vec<_>>(), vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn other_loops_are_not_counted() {
+        assert!(safe_loads("strided").is_empty());
+        assert!(safe_loads("shifted").is_empty());
+    }
+}
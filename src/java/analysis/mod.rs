@@ -0,0 +1,15 @@
+mod cfg;
+mod dead_code;
+mod escape;
+mod hierarchy;
+mod loops;
+mod rewrite;
+mod ssa;
+
+pub use self::cfg::{BasicBlock, CfgError, ControlFlowGraph, EdgeKind, Liveness};
+pub use self::escape::{Allocation, Escape, EscapeAnalysis};
+pub use self::dead_code::{DeadCodeReport, EntryPoint, HandlerProblem, ImpossibleHandler, MethodId, UnreachableBlock};
+pub use self::hierarchy::{ClassHierarchy, HierarchyNode};
+pub use self::loops::CountedLoop;
+pub use self::rewrite::{Rewrite, RewriteError, RewrittenCode};
+pub use self::ssa::{BinaryOp, Definition, Op, SsaBlock, SsaError, SsaFunction, Terminator, Value};
@@ -0,0 +1,244 @@
+use java::class_file::Method;
+use java::instructions::{Instruction, ReadInstructionError};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Fail)]
+pub enum RewriteError {
+    #[fail(display = "method has no code")]
+    NoCode,
+    #[fail(display = "invalid bytecode: {}", message)]
+    InvalidBytecode { message: String },
+    #[fail(display = "invalid jump target {}", pc)]
+    InvalidJumpTarget { pc: usize },
+    #[fail(display = "the branch at {} cannot reach {}", pc, target)]
+    BranchOutOfRange { pc: usize, target: usize },
+    #[fail(display = "the rewritten code is {} bytes long", length)]
+    CodeTooLong { length: usize },
+}
+
+/// what becomes of one instruction of the original code.
+#[derive(Debug)]
+pub enum Rewrite {
+    Keep,
+    /// runs the instructions right before the original one. jumps, handlers and lines of the
+    /// original instruction start at the first inserted one.
+    Insert(Vec<Instruction>),
+    /// replaces the original instruction, an empty replacement removes it. jumps to it continue
+    /// with whatever follows.
+    Replace(Vec<Instruction>),
+}
+
+/// the code of a method after a rewrite, with its branches, switches, exception table and line
+/// numbers moved to the new pcs.
+///
+/// the class file reader does not keep `StackMapTable` attributes, a writer moves their frames
+/// with `pc_map` like the exception table.
+#[derive(Debug)]
+pub struct RewrittenCode {
+    pub code: Vec<u8>,
+    /// (start_pc, end_pc, handler_pc, catch_type), ranges which became empty are dropped.
+    pub exception_table: Vec<(u16, u16, u16, u16)>,
+    /// (start_pc, line)
+    pub line_number_table: Vec<(u16, u16)>,
+    /// the new pc of each original instruction keyed by its old pc, and of the end of the code.
+    pub pc_map: BTreeMap<usize, usize>,
+}
+
+fn invalid_bytecode(err: ReadInstructionError<&[u8]>) -> RewriteError {
+    RewriteError::InvalidBytecode {
+        message: match err {
+            ReadInstructionError::InvalidOpcode { opcode } => format!("invalid opcode 0x{:02x}", opcode),
+            ReadInstructionError::ParsingIncomplete => String::from("truncated instruction"),
+            ReadInstructionError::ParsingError(_) => String::from("malformed instruction")
+        }
+    }
+}
+
+/// replaces each jump offset of a branch or switch with `retarget(offset, limit)`, which must return
+/// an offset between `-limit - 1` and `limit`.
+fn retarget_offsets<F>(instruction: &mut Instruction, mut retarget: F) -> Result<(), RewriteError>
+    where F: FnMut(i64, i64) -> Result<i64, RewriteError> {
+    let (short, long) = (i64::from(i16::MAX), i64::from(i32::MAX));
+    match instruction {
+        Instruction::Ifeq(offset) | Instruction::Ifne(offset) | Instruction::Iflt(offset) | Instruction::Ifge(offset) |
+        Instruction::Ifgt(offset) | Instruction::Ifle(offset) | Instruction::IfICmpEQ(offset) | Instruction::IfICmpNE(offset) |
+        Instruction::IfICmpLT(offset) | Instruction::IfICmpGE(offset) | Instruction::IfICmpGT(offset) | Instruction::IfICmpLE(offset) |
+        Instruction::IfACmpEQ(offset) | Instruction::IfACmpNE(offset) | Instruction::IfNull(offset) | Instruction::IfNonNull(offset) |
+        Instruction::Goto(offset) => *offset = retarget(i64::from(*offset as i16), short)? as i16 as u16,
+        Instruction::TableSwitch((default, _, _, offsets)) => {
+            *default = retarget(i64::from(*default), long)? as i32;
+            for offset in offsets.iter_mut() {
+                *offset = retarget(i64::from(*offset), long)? as i32;
+            }
+        },
+        Instruction::LookupSwitch((default, pairs)) => {
+            *default = retarget(i64::from(*default), long)? as i32;
+            for (_, offset) in pairs.iter_mut() {
+                *offset = retarget(i64::from(*offset), long)? as i32;
+            }
+        },
+        _ => ()
+    }
+    Ok(())
+}
+
+impl RewrittenCode {
+    /// visits the instructions of `method` in order with their pc and rewrites the code as `visit` asks.
+    ///
+    /// the jump offsets of every instruction in the result, kept or new, are read relative to the pc of
+    /// the original instruction it was emitted for, so they name a pc of the original code and
+    /// are moved to wherever that instruction ends up.
+    pub fn build<F>(method: &Method, mut visit: F) -> Result<RewrittenCode, RewriteError>
+        where F: FnMut(usize, &Instruction) -> Rewrite {
+        let code = method.get_code().ok_or(RewriteError::NoCode)?;
+        let instructions = code.instructions_with_pc().map_err(invalid_bytecode)?;
+
+        // lay out the new code, the padding of a switch depends on its new pc
+        let mut emitted = Vec::new();
+        let mut pc_map = BTreeMap::new();
+        let mut new_pc = 0;
+        for (pc, instruction) in instructions {
+            let group = match visit(pc, &instruction) {
+                Rewrite::Keep => vec![instruction],
+                Rewrite::Insert(mut inserted) => {
+                    inserted.push(instruction);
+                    inserted
+                },
+                Rewrite::Replace(replacement) => replacement
+            };
+            pc_map.insert(pc, new_pc);
+            for instruction in group {
+                let mut bytes = Vec::new();
+                instruction.write(new_pc, &mut bytes);
+                emitted.push((pc, new_pc, instruction));
+                new_pc += bytes.len();
+            }
+        }
+        pc_map.insert(code.code_length(), new_pc);
+        if new_pc > usize::from(u16::MAX) {
+            return Err(RewriteError::CodeTooLong { length: new_pc });
+        }
+
+        let mut bytes = Vec::with_capacity(new_pc);
+        for (old_pc, pc, mut instruction) in emitted {
+            retarget_offsets(&mut instruction, |offset, limit| {
+                let target = (old_pc as i64 + offset) as usize;
+                let target = *pc_map.get(&target).ok_or(RewriteError::InvalidJumpTarget { pc: target })?;
+                let offset = target as i64 - pc as i64;
+                if offset > limit || offset < -limit - 1 {
+                    return Err(RewriteError::BranchOutOfRange { pc, target });
+                }
+                Ok(offset)
+            })?;
+            instruction.write(pc, &mut bytes);
+        }
+
+        let moved = |pc: u16| pc_map.get(&usize::from(pc)).map(|&pc| pc as u16);
+        let mut exception_table = Vec::new();
+        for &(start, end, handler, catch_type) in code.exception_table.iter() {
+            let invalid = |pc: u16| RewriteError::InvalidJumpTarget { pc: usize::from(pc) };
+            let range = (moved(start).ok_or(invalid(start))?, moved(end).ok_or(invalid(end))?);
+            if range.0 < range.1 {
+                exception_table.push((range.0, range.1, moved(handler).ok_or(invalid(handler))?, catch_type));
+            }
+        }
+        let line_number_table = code.line_number_table().into_iter()
+            .filter_map(|(start, line)| moved(start).map(|start| (start, line)))
+            .collect();
+
+        Ok(RewrittenCode { code: bytes, exception_table, line_number_table, pc_map })
+    }
+
+    /// the instructions of the rewritten code with their pc.
+    pub fn instructions(&self) -> Result<Vec<(usize, Instruction)>, ReadInstructionError<&[u8]>> {
+        Instruction::read_all_with_offsets(&self.code[..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::{read_class_file, ClassFile};
+    use super::*;
+
+    fn class(bytes: &[u8]) -> ClassFile<'_> {
+        read_class_file(bytes).unwrap().1
+    }
+
+    fn method<'a>(class: &'a ClassFile<'a>, name: &str) -> &'a Method<'a> {
+        class.methods.iter().find(|method| method.name == name).unwrap()
+    }
+
+    /// the name of the instruction each branch and switch of `code` jumps to.
+    fn targets(instructions: &[(usize, Instruction)]) -> Vec<&'static str> {
+        let name_at = |pc: i64| instructions.iter().find(|&&(at, _)| at as i64 == pc).unwrap().1.name();
+        instructions.iter()
+            .flat_map(|&(pc, ref instruction)| instruction.branch_offset().into_iter().chain(instruction.switch_offsets().unwrap_or_default())
+                .map(move |offset| pc as i64 + offset))
+            .map(name_at)
+            .collect()
+    }
+
+    #[test]
+    fn keeping_every_instruction_reproduces_the_code() {
+        for &(bytes, name) in [(&include_bytes!("../../../sample/Loop.class")[..], "sum"), (&include_bytes!("../../../sample/Enums.class")[..], "code")].iter() {
+            let class = class(bytes);
+            let rewritten = RewrittenCode::build(method(&class, name), |_, _| Rewrite::Keep).unwrap();
+            assert_eq!(&rewritten.code[..], method(&class, name).get_code().unwrap().code());
+        }
+    }
+
+    #[test]
+    fn inserted_code_moves_branches_and_tables() {
+        let class = class(include_bytes!("../../../sample/ExceptionExample.class"));
+        let caught = method(&class, "caught");
+        let rewritten = RewrittenCode::build(caught, |_, _| Rewrite::Insert(vec![Instruction::NOOP(())])).unwrap();
+        assert_eq!(rewritten.exception_table, vec![(0, 4, 6, caught.get_code().unwrap().exception_table[0].3)]);
+        assert_eq!(rewritten.line_number_table, vec![(0, 8), (6, 9), (8, 10)]);
+        assert_eq!(rewritten.pc_map[&4], 6);
+        assert_eq!(rewritten.pc_map[&8], 13);
+
+        // jumps into the loop enter the inserted instruction first
+        let class = self::class(include_bytes!("../../../sample/Loop.class"));
+        let rewritten = RewrittenCode::build(method(&class, "sum"), |_, _| Rewrite::Insert(vec![Instruction::NOOP(())])).unwrap();
+        assert_eq!(targets(&rewritten.instructions().unwrap()), vec!["NOOP", "NOOP"]);
+    }
+
+    #[test]
+    fn switches_are_padded_at_their_new_pc() {
+        let class = class(include_bytes!("../../../sample/Enums.class"));
+        let original = method(&class, "code").get_code().unwrap().instructions_with_pc().unwrap();
+        let rewritten = RewrittenCode::build(method(&class, "code"), |pc, _| match pc {
+            0 => Rewrite::Insert(vec![Instruction::NOOP(())]),
+            _ => Rewrite::Keep
+        }).unwrap();
+        let instructions = rewritten.instructions().unwrap();
+        // the switch moved from pc 1 to 2, so it needs one byte of padding less
+        assert_eq!(instructions[2].0, 2);
+        assert_eq!(rewritten.code.len(), method(&class, "code").get_code().unwrap().code_length());
+        assert_eq!(targets(&instructions), targets(&original));
+
+        // removing the array load moves the switch to pc 7, where it needs no padding
+        let rewritten = RewrittenCode::build(method(&class, "describe"), |_, instruction| match instruction {
+            Instruction::IALoad(()) => Rewrite::Replace(vec![]),
+            _ => Rewrite::Keep
+        }).unwrap();
+        let instructions = rewritten.instructions().unwrap();
+        assert_eq!(instructions[3].0, 7);
+        assert_eq!(instructions[4].0, 32);
+        assert_eq!(targets(&instructions), vec!["IConst0", "IConst1", "IConst3", "IConst4"]);
+    }
+
+    #[test]
+    fn branches_which_cannot_reach_their_target_are_rejected() {
+        let class = class(include_bytes!("../../../sample/Loop.class"));
+        let padding = (0..40000).map(|_| Instruction::NOOP(())).collect::<Vec<_>>();
+        let mut padding = Some(padding);
+        match RewrittenCode::build(method(&class, "sum"), |pc, _| match pc {
+            9 => Rewrite::Insert(padding.take().unwrap()),
+            _ => Rewrite::Keep
+        }) {
+            Err(RewriteError::BranchOutOfRange { .. }) => (),
+            other => panic!("build returned {:?}", other)
+        }
+    }
+}
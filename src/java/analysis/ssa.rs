@@ -0,0 +1,758 @@
+use java::analysis::{CfgError, ControlFlowGraph};
+use java::class_file::{ClassFile, ConstantType, Method, MethodDescriptor, ValueType};
+use java::instructions::Instruction;
+use java::runtime::Comparison;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Debug, Fail)]
+pub enum SsaError {
+    #[fail(display = "{}", _0)]
+    Cfg(CfgError),
+    #[fail(display = "{} at {} is not supported", mnemonic, pc)]
+    Unsupported { pc: usize, mnemonic: String },
+    #[fail(display = "methods with exception handlers are not supported")]
+    ExceptionHandlers,
+    #[fail(display = "the entry block is a loop header")]
+    EntryLoop,
+    #[fail(display = "the operand stack underflows at {}", pc)]
+    StackUnderflow { pc: usize },
+    #[fail(display = "the operand stack has different heights where control flow merges at {}", pc)]
+    StackMismatch { pc: usize },
+}
+
+impl From<CfgError> for SsaError {
+    fn from(err: CfgError) -> SsaError {
+        SsaError::Cfg(err)
+    }
+}
+
+/// a value of an `SsaFunction`, by its index in `SsaFunction::values`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Value(pub usize);
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    Ushr,
+    And,
+    Or,
+    Xor,
+}
+
+impl BinaryOp {
+    fn of(instruction: &Instruction) -> Option<BinaryOp> {
+        match instruction {
+            Instruction::IAdd(()) => Some(BinaryOp::Add),
+            Instruction::ISub(()) => Some(BinaryOp::Sub),
+            Instruction::IMul(()) => Some(BinaryOp::Mul),
+            Instruction::IDiv(()) => Some(BinaryOp::Div),
+            Instruction::IRem(()) => Some(BinaryOp::Rem),
+            Instruction::IShl(()) => Some(BinaryOp::Shl),
+            Instruction::IShr(()) => Some(BinaryOp::Shr),
+            Instruction::IUSHR(()) => Some(BinaryOp::Ushr),
+            Instruction::IAnd(()) => Some(BinaryOp::And),
+            Instruction::IOr(()) => Some(BinaryOp::Or),
+            Instruction::IXor(()) => Some(BinaryOp::Xor),
+            _ => None
+        }
+    }
+
+    /// the result for int operands, `None` for a division by zero which throws instead.
+    pub fn apply(self, lh: i32, rh: i32) -> Option<i32> {
+        Some(match self {
+            BinaryOp::Add => lh.wrapping_add(rh),
+            BinaryOp::Sub => lh.wrapping_sub(rh),
+            BinaryOp::Mul => lh.wrapping_mul(rh),
+            BinaryOp::Div if rh == 0 => return None,
+            BinaryOp::Div => lh.wrapping_div(rh),
+            BinaryOp::Rem if rh == 0 => return None,
+            BinaryOp::Rem => lh.wrapping_rem(rh),
+            BinaryOp::Shl => lh.wrapping_shl(rh as u32 & 0x1f),
+            BinaryOp::Shr => lh.wrapping_shr(rh as u32 & 0x1f),
+            BinaryOp::Ushr => ((lh as u32) >> (rh as u32 & 0x1f)) as i32,
+            BinaryOp::And => lh & rh,
+            BinaryOp::Or => lh | rh,
+            BinaryOp::Xor => lh ^ rh,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::Rem => "rem",
+            BinaryOp::Shl => "shl",
+            BinaryOp::Shr => "shr",
+            BinaryOp::Ushr => "ushr",
+            BinaryOp::And => "and",
+            BinaryOp::Or => "or",
+            BinaryOp::Xor => "xor",
+        }
+    }
+}
+
+/// how a value is computed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// the local variable on entry: an argument, or nothing for the others.
+    Entry(u16),
+    Constant(i32),
+    Binary(BinaryOp, Value, Value),
+    Negate(Value),
+    /// one of the values flowing in along the edges into its block, by predecessor.
+    Phi(Vec<(usize, Value)>),
+    /// the same value as another one, left behind by copy propagation.
+    Copy(Value),
+    /// an `invokestatic` of the method at a constant pool index. calls of void methods have no result.
+    Call { method: u16, arguments: Vec<Value> },
+}
+
+/// a value with the block it belongs to.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub op: Op,
+    pub block: usize,
+    /// the index of the instruction which computes it in `ControlFlowGraph::instructions`,
+    /// `None` for phis and the local variables on entry.
+    pub origin: Option<usize>,
+}
+
+/// how a block ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Goto(usize),
+    /// to `taken` if `comparison` holds for the operands, to `not_taken` otherwise.
+    Branch { comparison: Comparison, lh: Value, rh: Value, taken: usize, not_taken: usize },
+    Return(Option<Value>),
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<usize> {
+        match *self {
+            Terminator::Goto(target) => vec![target],
+            Terminator::Branch { taken, not_taken, .. } => vec![taken, not_taken],
+            Terminator::Return(_) => vec![]
+        }
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match *self {
+            Terminator::Branch { ref mut lh, ref mut rh, .. } => vec![lh, rh],
+            Terminator::Return(Some(ref mut value)) => vec![value],
+            _ => vec![]
+        }
+    }
+}
+
+/// a basic block of the control flow graph, with the same id.
+#[derive(Debug, Clone)]
+pub struct SsaBlock {
+    /// the indices of its instructions in `ControlFlowGraph::instructions`.
+    pub instructions: Range<usize>,
+    /// the values it computes in order, its phis first.
+    pub values: Vec<Value>,
+    pub terminator: Terminator,
+    pub predecessors: Vec<usize>,
+    /// false for blocks which never run, including the ones constant folding cut off.
+    pub reachable: bool,
+}
+
+/// a method in static single assignment form: each value is assigned once, and the local
+/// variables and operand stack slots merging different values where control flow joins become
+/// phis.
+///
+/// only the int subset of the bytecode is lowered: constants, loads and stores of int locals,
+/// arithmetic, branches on ints, `invokestatic` of methods taking and returning ints and the
+/// returns. constant folding, copy propagation and dead code elimination run on the IR, and the
+/// interpreter uses what they find to skip computations whose result is known.
+#[derive(Debug, Clone)]
+pub struct SsaFunction {
+    pub values: Vec<Definition>,
+    pub blocks: Vec<SsaBlock>,
+    /// the value on top of the stack after each instruction which pushes one, by index.
+    pub results: Vec<Option<Value>>,
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    locals: Vec<Value>,
+    stack: Vec<Value>,
+}
+
+/// the comparison of a conditional branch on ints and whether it compares with zero.
+fn comparison_of(instruction: &Instruction) -> Option<(Comparison, bool)> {
+    Some(match instruction {
+        Instruction::Ifeq(_) => (Comparison::Eq, true),
+        Instruction::Ifne(_) => (Comparison::Ne, true),
+        Instruction::Iflt(_) => (Comparison::Lt, true),
+        Instruction::Ifge(_) => (Comparison::Ge, true),
+        Instruction::Ifgt(_) => (Comparison::Gt, true),
+        Instruction::Ifle(_) => (Comparison::Le, true),
+        Instruction::IfICmpEQ(_) => (Comparison::Eq, false),
+        Instruction::IfICmpNE(_) => (Comparison::Ne, false),
+        Instruction::IfICmpLT(_) => (Comparison::Lt, false),
+        Instruction::IfICmpGE(_) => (Comparison::Ge, false),
+        Instruction::IfICmpGT(_) => (Comparison::Gt, false),
+        Instruction::IfICmpLE(_) => (Comparison::Le, false),
+        _ => return None
+    })
+}
+
+fn int_constant(instruction: &Instruction) -> Option<i32> {
+    Some(match *instruction {
+        Instruction::IConstm1(()) => -1,
+        Instruction::IConst0(()) => 0,
+        Instruction::IConst1(()) => 1,
+        Instruction::IConst2(()) => 2,
+        Instruction::IConst3(()) => 3,
+        Instruction::IConst4(()) => 4,
+        Instruction::IConst5(()) => 5,
+        Instruction::BIPush(value) => i32::from(value as i8),
+        Instruction::SIPush(value) => i32::from(value as i16),
+        _ => return None
+    })
+}
+
+fn int_load(instruction: &Instruction) -> Option<usize> {
+    Some(match *instruction {
+        Instruction::ILoad(index) => usize::from(index),
+        Instruction::ILoad0(()) => 0,
+        Instruction::ILoad1(()) => 1,
+        Instruction::ILoad2(()) => 2,
+        Instruction::ILoad3(()) => 3,
+        _ => return None
+    })
+}
+
+fn int_store(instruction: &Instruction) -> Option<usize> {
+    Some(match *instruction {
+        Instruction::IStore(index) => usize::from(index),
+        Instruction::IStore0(()) => 0,
+        Instruction::IStore1(()) => 1,
+        Instruction::IStore2(()) => 2,
+        Instruction::IStore3(()) => 3,
+        _ => return None
+    })
+}
+
+fn is_int(value_type: &ValueType) -> bool {
+    matches!(value_type, ValueType::Integer | ValueType::Boolean | ValueType::Byte | ValueType::Char | ValueType::Short)
+}
+
+/// the number of int arguments of a static method and whether it returns an int, `None` if it
+/// takes or returns anything else.
+fn int_signature(class: &ClassFile, index: u16) -> Option<(usize, bool)> {
+    let descriptor = match class.get_constant(index)? {
+        ConstantType::MethodRef { name_and_type_index, .. } |
+        ConstantType::InterfaceMethodRef { name_and_type_index, .. } => class.get_name_and_type(*name_and_type_index)?.1,
+        _ => return None
+    };
+    let signature = MethodDescriptor::from_str(descriptor).ok()?;
+    if !signature.arguments.iter().all(is_int) {
+        return None;
+    }
+    match signature.return_type {
+        ValueType::Void => Some((signature.arguments.len(), false)),
+        ref return_type if is_int(return_type) => Some((signature.arguments.len(), true)),
+        _ => None
+    }
+}
+
+impl SsaFunction {
+    /// lowers the bytecode of `method` into SSA form, with a phi for every local variable and
+    /// stack slot at every block which more than one block flows into.
+    pub fn lower(class: &ClassFile, method: &Method) -> Result<SsaFunction, SsaError> {
+        let cfg = ControlFlowGraph::build(method)?;
+        let code = method.get_code().ok_or(CfgError::NoCode)?;
+        if !code.exception_table.is_empty() {
+            return Err(SsaError::ExceptionHandlers);
+        }
+        if !cfg.blocks[0].predecessors.is_empty() {
+            return Err(SsaError::EntryLoop);
+        }
+
+        let order = cfg.reverse_postorder();
+        let mut function = SsaFunction {
+            values: Vec::new(),
+            blocks: cfg.blocks.iter()
+                .map(|block| SsaBlock {
+                    instructions: block.instructions.clone(),
+                    values: Vec::new(),
+                    terminator: Terminator::Return(None),
+                    predecessors: unique(&block.predecessors).into_iter().filter(|pred| order.contains(pred)).collect(),
+                    reachable: order.contains(&block.id),
+                })
+                .collect(),
+            results: vec![None; cfg.instructions.len()],
+        };
+
+        let entry = State {
+            locals: (0..code.max_locals).map(|local| function.define(Op::Entry(local), 0, None)).collect(),
+            stack: Vec::new(),
+        };
+        let mut exits: Vec<Option<State>> = vec![None; cfg.blocks.len()];
+        // the phis of each block, with the slot they merge: locals first, then the stack
+        let mut phis: Vec<Vec<(Value, usize)>> = vec![Vec::new(); cfg.blocks.len()];
+        for &block in order.iter() {
+            let start = cfg.blocks[block].instructions.start;
+            let predecessors = function.blocks[block].predecessors.clone();
+            let mut state = match predecessors.as_slice() {
+                [] => entry.clone(),
+                [single] => exits[*single].clone().unwrap(),
+                predecessors => {
+                    // a predecessor which comes first in reverse postorder is reached by a forward edge
+                    let known = predecessors.iter().filter_map(|&pred| exits[pred].as_ref()).next().unwrap().clone();
+                    let mut merged = State { locals: Vec::new(), stack: Vec::new() };
+                    for (slot, _) in known.locals.iter().chain(known.stack.iter()).enumerate() {
+                        let phi = function.define(Op::Phi(Vec::new()), block, None);
+                        phis[block].push((phi, slot));
+                        if slot < known.locals.len() {
+                            merged.locals.push(phi);
+                        } else {
+                            merged.stack.push(phi);
+                        }
+                    }
+                    merged
+                }
+            };
+
+            let end = cfg.blocks[block].instructions.end;
+            for index in start..end {
+                let (pc, ref instruction) = cfg.instructions[index];
+                function.results[index] = function.lower_instruction(class, block, index, pc, instruction, &mut state)?;
+            }
+            let (pc, ref last) = cfg.instructions[end - 1];
+            let target = last.branch_offset().map(|offset| cfg.block_at((pc as i64 + offset) as usize).unwrap());
+            function.blocks[block].terminator = match (last, target) {
+                (Instruction::IReturn(()), _) => Terminator::Return(Some(pop(&mut state, pc)?)),
+                (Instruction::Return(()), _) => Terminator::Return(None),
+                (_, Some(target)) if comparison_of(last).is_some() => {
+                    let (comparison, with_zero) = comparison_of(last).unwrap();
+                    let rh = if with_zero { function.define(Op::Constant(0), block, Some(end - 1)) } else { pop(&mut state, pc)? };
+                    let lh = pop(&mut state, pc)?;
+                    Terminator::Branch { comparison, lh, rh, taken: target, not_taken: block + 1 }
+                }
+                (_, Some(target)) => Terminator::Goto(target),
+                _ => Terminator::Goto(block + 1)
+            };
+            exits[block] = Some(state);
+        }
+
+        for block in order {
+            for &(phi, slot) in phis[block].iter() {
+                let mut operands = Vec::new();
+                for &pred in function.blocks[block].predecessors.iter() {
+                    let exit = exits[pred].as_ref().unwrap();
+                    if exit.locals.len() + exit.stack.len() != phis[block].len() {
+                        return Err(SsaError::StackMismatch { pc: cfg.instructions[cfg.blocks[block].instructions.start].0 });
+                    }
+                    operands.push((pred, *exit.locals.iter().chain(exit.stack.iter()).nth(slot).unwrap()));
+                }
+                function.values[phi.0].op = Op::Phi(operands);
+            }
+        }
+        Ok(function)
+    }
+
+    fn define(&mut self, op: Op, block: usize, origin: Option<usize>) -> Value {
+        let value = Value(self.values.len());
+        self.values.push(Definition { op, block, origin });
+        self.blocks[block].values.push(value);
+        value
+    }
+
+    /// adds the values `instruction` computes to `block` and applies it to `state`. branches and
+    /// returns only have their operands left on the stack for the terminator. returns the value
+    /// the instruction pushes, if any.
+    fn lower_instruction(&mut self, class: &ClassFile, block: usize, index: usize, pc: usize, instruction: &Instruction,
+                         state: &mut State) -> Result<Option<Value>, SsaError> {
+        let pushed = if let Some(constant) = int_constant(instruction) {
+            Some(self.define(Op::Constant(constant), block, Some(index)))
+        } else if let Some(local) = int_load(instruction) {
+            Some(*state.locals.get(local).ok_or_else(|| unsupported(pc, instruction))?)
+        } else if let Some(local) = int_store(instruction) {
+            let value = pop(state, pc)?;
+            *state.locals.get_mut(local).ok_or_else(|| unsupported(pc, instruction))? = value;
+            None
+        } else if let Some(op) = BinaryOp::of(instruction) {
+            let rh = pop(state, pc)?;
+            let lh = pop(state, pc)?;
+            Some(self.define(Op::Binary(op, lh, rh), block, Some(index)))
+        } else {
+            match *instruction {
+                Instruction::IInc(operands) => {
+                    let local = usize::from(operands >> 8);
+                    let lh = *state.locals.get(local).ok_or_else(|| unsupported(pc, instruction))?;
+                    let rh = self.define(Op::Constant(i32::from((operands & 0xff) as u8 as i8)), block, Some(index));
+                    state.locals[local] = self.define(Op::Binary(BinaryOp::Add, lh, rh), block, Some(index));
+                    None
+                }
+                Instruction::INeg(()) => {
+                    let operand = pop(state, pc)?;
+                    Some(self.define(Op::Negate(operand), block, Some(index)))
+                }
+                Instruction::InvokeStatic(method) => {
+                    let (arity, returns) = int_signature(class, method).ok_or_else(|| unsupported(pc, instruction))?;
+                    if state.stack.len() < arity {
+                        return Err(SsaError::StackUnderflow { pc });
+                    }
+                    let arguments = state.stack.split_off(state.stack.len() - arity);
+                    let value = self.define(Op::Call { method, arguments }, block, Some(index));
+                    if returns { Some(value) } else { None }
+                }
+                Instruction::Pop(()) => {
+                    pop(state, pc)?;
+                    None
+                }
+                Instruction::Dup(()) => Some(*state.stack.last().ok_or(SsaError::StackUnderflow { pc })?),
+                Instruction::NOOP(()) | Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::IReturn(()) | Instruction::Return(()) => None,
+                _ if comparison_of(instruction).is_some() => None,
+                _ => return Err(unsupported(pc, instruction))
+            }
+        };
+        if let Some(value) = pushed {
+            state.stack.push(value);
+        }
+        Ok(pushed)
+    }
+
+    /// the value `value` is a copy of, or itself.
+    pub fn resolve(&self, value: Value) -> Value {
+        let mut value = value;
+        while let Op::Copy(original) = self.values[value.0].op {
+            value = original;
+        }
+        value
+    }
+
+    /// the int `value` always has, if it is known.
+    pub fn constant(&self, value: Value) -> Option<i32> {
+        match self.values[self.resolve(value).0].op {
+            Op::Constant(constant) => Some(constant),
+            _ => None
+        }
+    }
+
+    /// the operands of a phi which can flow in: the ones along edges from reachable blocks which
+    /// still branch to its block.
+    fn incoming(&self, block: usize, operands: &[(usize, Value)]) -> Vec<Value> {
+        operands.iter()
+            .filter(|&&(pred, _)| self.blocks[pred].reachable && self.blocks[pred].terminator.successors().contains(&block))
+            .map(|&(_, value)| self.resolve(value))
+            .collect()
+    }
+
+    /// runs constant folding and copy propagation until neither finds anything, then dead code
+    /// elimination.
+    pub fn optimize(&mut self) {
+        while self.fold_constants() | self.propagate_copies() {}
+        self.eliminate_dead_code();
+    }
+
+    /// replaces arithmetic on constants and phis merging a single constant by the constant, and
+    /// branches on constants by jumps, until nothing changes. blocks which are no longer branched
+    /// to become unreachable, so the phis of their successors can fold too. returns true if
+    /// anything was folded.
+    pub fn fold_constants(&mut self) -> bool {
+        let mut folded_any = false;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..self.values.len() {
+                let block = self.values[index].block;
+                if !self.blocks[block].reachable {
+                    continue;
+                }
+                let folded = match self.values[index].op {
+                    Op::Binary(op, lh, rh) => match (self.constant(lh), self.constant(rh)) {
+                        (Some(lh), Some(rh)) => op.apply(lh, rh),
+                        _ => None
+                    },
+                    Op::Negate(operand) => self.constant(operand).map(|operand| operand.wrapping_neg()),
+                    Op::Phi(ref operands) => {
+                        // a loop which does not change the value merges it with itself
+                        let constants = self.incoming(block, operands).into_iter()
+                            .filter(|&value| value != Value(index))
+                            .map(|value| self.constant(value))
+                            .collect::<BTreeSet<_>>();
+                        match constants.into_iter().collect::<Vec<_>>().as_slice() {
+                            [Some(constant)] => Some(*constant),
+                            _ => None
+                        }
+                    }
+                    _ => None
+                };
+                if let Some(constant) = folded {
+                    self.values[index].op = Op::Constant(constant);
+                    changed = true;
+                }
+            }
+
+            for block in 0..self.blocks.len() {
+                let target = match self.blocks[block].terminator {
+                    Terminator::Branch { comparison, lh, rh, taken, not_taken } if self.blocks[block].reachable => {
+                        match (self.constant(lh), self.constant(rh)) {
+                            (Some(lh), Some(rh)) => if comparison.holds(i64::from(lh), i64::from(rh)) { taken } else { not_taken },
+                            _ => continue
+                        }
+                    }
+                    _ => continue
+                };
+                self.blocks[block].terminator = Terminator::Goto(target);
+                changed = true;
+            }
+            self.update_reachability();
+            folded_any |= changed;
+        }
+        folded_any
+    }
+
+    fn update_reachability(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending = vec![0];
+        while let Some(block) = pending.pop() {
+            if reachable[block] {
+                continue;
+            }
+            reachable[block] = true;
+            pending.extend(self.blocks[block].terminator.successors());
+        }
+        for (block, reachable) in self.blocks.iter_mut().zip(reachable) {
+            block.reachable = reachable;
+        }
+    }
+
+    /// turns phis whose incoming values are all the same value, apart from the phi itself, into
+    /// copies of it, and makes every use of a copy use the original value. returns true if any
+    /// phi became a copy.
+    pub fn propagate_copies(&mut self) -> bool {
+        let mut copied = false;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 0..self.values.len() {
+                let block = self.values[index].block;
+                let incoming = match self.values[index].op {
+                    Op::Phi(ref operands) if self.blocks[block].reachable => self.incoming(block, operands),
+                    _ => continue
+                };
+                let distinct = incoming.into_iter().filter(|&value| value != Value(index)).collect::<BTreeSet<_>>();
+                if distinct.len() == 1 {
+                    self.values[index].op = Op::Copy(distinct.into_iter().next().unwrap());
+                    changed = true;
+                    copied = true;
+                }
+            }
+        }
+
+        for index in 0..self.values.len() {
+            let mut op = self.values[index].op.clone();
+            for operand in operands_mut(&mut op) {
+                *operand = self.resolve(*operand);
+            }
+            self.values[index].op = op;
+        }
+        for block in 0..self.blocks.len() {
+            let mut terminator = self.blocks[block].terminator.clone();
+            for operand in terminator.operands_mut() {
+                *operand = self.resolve(*operand);
+            }
+            self.blocks[block].terminator = terminator;
+        }
+        copied
+    }
+
+    /// removes the values nothing uses which have no effect, and the values of unreachable blocks.
+    /// calls stay, and so do divisions which can throw.
+    pub fn eliminate_dead_code(&mut self) {
+        let mut live = vec![false; self.values.len()];
+        let mut pending = Vec::new();
+        for block in self.blocks.iter().filter(|block| block.reachable) {
+            let mut terminator = block.terminator.clone();
+            pending.extend(terminator.operands_mut().into_iter().map(|value| *value));
+            pending.extend(block.values.iter().cloned().filter(|&value| self.has_effect(value)));
+        }
+        while let Some(value) = pending.pop() {
+            if live[value.0] {
+                continue;
+            }
+            live[value.0] = true;
+            let mut op = self.values[value.0].op.clone();
+            pending.extend(operands_mut(&mut op).into_iter().map(|value| *value));
+        }
+
+        for block in self.blocks.iter_mut() {
+            if block.reachable {
+                block.values.retain(|value| live[value.0]);
+            } else {
+                block.values.clear();
+            }
+        }
+    }
+
+    fn has_effect(&self, value: Value) -> bool {
+        match self.values[value.0].op {
+            Op::Call { .. } => true,
+            Op::Binary(BinaryOp::Div, _, rh) | Op::Binary(BinaryOp::Rem, _, rh) => self.constant(rh).is_none_or(|rh| rh == 0),
+            _ => false
+        }
+    }
+}
+
+fn operands_mut(op: &mut Op) -> Vec<&mut Value> {
+    match *op {
+        Op::Binary(_, ref mut lh, ref mut rh) => vec![lh, rh],
+        Op::Negate(ref mut operand) | Op::Copy(ref mut operand) => vec![operand],
+        Op::Phi(ref mut operands) => operands.iter_mut().map(|&mut (_, ref mut value)| value).collect(),
+        Op::Call { ref mut arguments, .. } => arguments.iter_mut().collect(),
+        Op::Entry(_) | Op::Constant(_) => vec![]
+    }
+}
+
+fn unique(blocks: &[usize]) -> Vec<usize> {
+    let mut unique = Vec::new();
+    for &block in blocks {
+        if !unique.contains(&block) {
+            unique.push(block);
+        }
+    }
+    unique
+}
+
+fn pop(state: &mut State, pc: usize) -> Result<Value, SsaError> {
+    state.stack.pop().ok_or(SsaError::StackUnderflow { pc })
+}
+
+fn unsupported(pc: usize, instruction: &Instruction) -> SsaError {
+    SsaError::Unsupported { pc, mnemonic: instruction.mnemonic() }
+}
+
+/// lists the reachable blocks with their values, like `b0: v1 = add v0, v2`.
+impl fmt::Display for SsaFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (id, block) in self.blocks.iter().enumerate().filter(|(_, block)| block.reachable) {
+            writeln!(f, "b{}:", id)?;
+            for &value in block.values.iter() {
+                let operands = |values: &[Value]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(", ");
+                match self.values[value.0].op {
+                    Op::Entry(local) => writeln!(f, "  {} = entry {}", value, local)?,
+                    Op::Constant(constant) => writeln!(f, "  {} = {}", value, constant)?,
+                    Op::Binary(op, lh, rh) => writeln!(f, "  {} = {} {}, {}", value, op.name(), lh, rh)?,
+                    Op::Negate(operand) => writeln!(f, "  {} = neg {}", value, operand)?,
+                    Op::Copy(operand) => writeln!(f, "  {} = {}", value, operand)?,
+                    Op::Phi(ref incoming) => {
+                        let incoming = incoming.iter().map(|(pred, value)| format!("b{}: {}", pred, value)).collect::<Vec<_>>();
+                        writeln!(f, "  {} = phi [{}]", value, incoming.join(", "))?
+                    }
+                    Op::Call { method, ref arguments } => writeln!(f, "  {} = call #{}({})", value, method, operands(arguments))?,
+                }
+            }
+            match block.terminator {
+                Terminator::Goto(target) => writeln!(f, "  goto b{}", target)?,
+                Terminator::Branch { comparison, lh, rh, taken, not_taken } =>
+                    writeln!(f, "  if {:?} {}, {} goto b{} else b{}", comparison, lh, rh, taken, not_taken)?,
+                Terminator::Return(Some(value)) => writeln!(f, "  return {}", value)?,
+                Terminator::Return(None) => writeln!(f, "  return")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn lower(name: &str) -> SsaFunction {
+        let class = read_class_file(include_bytes!("../../../sample/Folding.class")).unwrap().1;
+        let method = class.methods.iter().find(|method| method.name == name).unwrap();
+        SsaFunction::lower(&class, method).unwrap()
+    }
+
+    /// the values left in the reachable blocks.
+    fn ops(function: &SsaFunction) -> Vec<Op> {
+        function.blocks.iter().filter(|block| block.reachable)
+            .flat_map(|block| block.values.iter().map(|value| function.values[value.0].op.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn loop_invariant_arithmetic_folds() {
+        let mut function = lower("scaled");
+        // step * 2 + 1, the iadd at pc 15, uses the loop header phi for step, which is not a constant yet
+        assert_eq!(function.constant(function.results[13].unwrap()), None);
+        function.optimize();
+        assert_eq!(function.constant(function.results[13].unwrap()), Some(7));
+        // the phis of step and of the locals unused before the loop are gone, and so is their arithmetic
+        assert_eq!(function.to_string(), "\
+b0:
+  v0 = entry 0
+  v5 = 0
+  v7 = 0
+  goto b1
+b1:
+  v9 = phi [b0: v5, b2: v17]
+  v11 = phi [b0: v7, b2: v19]
+  if Ge v11, v0 goto b3 else b2
+b2:
+  v16 = 7
+  v17 = add v9, v16
+  v18 = 1
+  v19 = add v11, v18
+  goto b1
+b3:
+  return v9
+");
+    }
+
+    #[test]
+    fn branches_on_constants_become_jumps() {
+        let mut function = lower("branches");
+        let branching = function.blocks.iter().position(|block| matches!(block.terminator, Terminator::Branch { comparison: Comparison::Le, .. })).unwrap();
+        function.optimize();
+
+        let else_block = branching + 2;
+        assert_eq!(function.blocks[branching].terminator, Terminator::Goto(branching + 1));
+        assert!(!function.blocks[else_block].reachable);
+        assert!(!ops(&function).iter().any(|op| matches!(op, Op::Binary(BinaryOp::Sub, _, _))));
+        assert!(!function.to_string().contains(&format!("b{}:", else_block)));
+    }
+
+    #[test]
+    fn dead_values_are_removed_but_calls_stay() {
+        let mut function = lower("unused");
+        function.optimize();
+        let ops = ops(&function);
+        assert!(!ops.iter().any(|op| matches!(op, Op::Binary(BinaryOp::Mul, _, _))));
+        assert_eq!(ops.iter().filter(|op| matches!(op, Op::Call { .. })).count(), 1);
+        assert_eq!(function.to_string().lines().last(), Some("  return v6"));
+    }
+
+    #[test]
+    fn references_are_not_lowered() {
+        let class = read_class_file(include_bytes!("../../../sample/Folding.class")).unwrap().1;
+        let method = class.methods.iter().find(|method| method.name == "length").unwrap();
+        match SsaFunction::lower(&class, method) {
+            Err(SsaError::Unsupported { pc: 0, ref mnemonic }) if mnemonic == "aload_0" => (),
+            other => panic!("expected aload_0 to be unsupported, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! the conversions between java strings and bytes: the standard charsets of
+//! `java.nio.charset.StandardCharsets` and the modified utf-8 of class files and `DataInput`.
+
+#[cfg(not(feature = "std"))]
+use prelude::*;
+
+/// the charsets every java platform supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Charset {
+    UsAscii,
+    Iso8859_1,
+    Utf8,
+    /// big endian with a byte order mark when encoding, decodes either byte order by its mark.
+    Utf16,
+    Utf16Be,
+    Utf16Le,
+}
+
+/// the canonical names and the aliases java accepts for them, compared ignoring case.
+const NAMES: &[(Charset, &str, &[&str])] = &[
+    (Charset::UsAscii, "US-ASCII", &["ASCII", "US_ASCII", "ISO646-US", "646", "default"]),
+    (Charset::Iso8859_1, "ISO-8859-1", &["ISO8859_1", "ISO8859-1", "ISO_8859_1", "ISO_8859-1", "latin1", "l1", "8859_1"]),
+    (Charset::Utf8, "UTF-8", &["UTF8", "unicode-1-1-utf-8"]),
+    (Charset::Utf16, "UTF-16", &["UTF_16", "utf16", "unicode"]),
+    (Charset::Utf16Be, "UTF-16BE", &["UTF_16BE", "ISO-10646-UCS-2", "X-UTF-16BE", "UnicodeBigUnmarked"]),
+    (Charset::Utf16Le, "UTF-16LE", &["UTF_16LE", "X-UTF-16LE", "UnicodeLittleUnmarked"]),
+];
+
+impl Charset {
+    /// like `Charset.forName`, `None` if the charset is not supported.
+    pub fn for_name(name: &str) -> Option<Charset> {
+        NAMES.iter()
+            .find(|&&(_, canonical, aliases)| canonical.eq_ignore_ascii_case(name) || aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name)))
+            .map(|&(charset, _, _)| charset)
+    }
+
+    /// the canonical name, like `UTF-8`.
+    pub fn name(self) -> &'static str {
+        NAMES.iter().find(|&&(charset, _, _)| charset == self).map_or("", |&(_, name, _)| name)
+    }
+
+    /// like `String.getBytes`, characters the charset cannot represent become `?`.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Charset::UsAscii => text.chars().map(|char| if char.is_ascii() { char as u8 } else { b'?' }).collect(),
+            Charset::Iso8859_1 => text.chars().map(|char| if (char as u32) < 0x100 { char as u8 } else { b'?' }).collect(),
+            Charset::Utf8 => text.as_bytes().to_vec(),
+            Charset::Utf16 => [0xFE, 0xFF].iter().cloned().chain(text.encode_utf16().flat_map(|unit| unit.to_be_bytes())).collect(),
+            Charset::Utf16Be => text.encode_utf16().flat_map(|unit| unit.to_be_bytes()).collect(),
+            Charset::Utf16Le => text.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect(),
+        }
+    }
+
+    /// like `new String(bytes, charset)`, malformed input becomes U+FFFD.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Charset::UsAscii => bytes.iter().map(|&byte| if byte.is_ascii() { char::from(byte) } else { '\u{fffd}' }).collect(),
+            Charset::Iso8859_1 => bytes.iter().map(|&byte| char::from(byte)).collect(),
+            Charset::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Charset::Utf16 => match bytes {
+                [0xFF, 0xFE, rest @ ..] => Charset::Utf16Le.decode(rest),
+                [0xFE, 0xFF, rest @ ..] => Charset::Utf16Be.decode(rest),
+                _ => Charset::Utf16Be.decode(bytes)
+            },
+            Charset::Utf16Be | Charset::Utf16Le => {
+                let mut units = bytes.chunks_exact(2)
+                    .map(|pair| if self == Charset::Utf16Be { u16::from_be_bytes([pair[0], pair[1]]) } else { u16::from_le_bytes([pair[0], pair[1]]) })
+                    .collect::<Vec<u16>>();
+                // a trailing odd byte is malformed
+                if !bytes.len().is_multiple_of(2) {
+                    units.push(0xFFFD);
+                }
+                String::from_utf16_lossy(&units)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub enum ModifiedUtf8Error {
+    #[fail(display = "malformed modified utf-8 at byte {}", offset)]
+    Malformed { offset: usize },
+    #[fail(display = "unpaired surrogate in modified utf-8 at byte {}", offset)]
+    UnpairedSurrogate { offset: usize },
+}
+
+/// the modified utf-8 of the jvm: NUL is `0xC0 0x80` and characters outside of the basic
+/// multilingual plane are their two surrogates, encoded as three bytes each.
+pub fn encode_modified_utf8(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+    for unit in text.encode_utf16() {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => bytes.extend_from_slice(&[0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8]),
+            _ => bytes.extend_from_slice(&[0xE0 | (unit >> 12) as u8, 0x80 | ((unit >> 6) & 0x3F) as u8, 0x80 | (unit & 0x3F) as u8]),
+        }
+    }
+    bytes
+}
+
+/// the utf-16 units of modified utf-8, each with the offset of its first byte.
+fn modified_utf8_units(bytes: &[u8]) -> Vec<Result<(usize, u16), usize>> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut offset = 0;
+    let continuation = |index: usize| bytes.get(index).filter(|&&byte| byte & 0xC0 == 0x80).map(|&byte| u16::from(byte & 0x3F));
+    while offset < bytes.len() {
+        let first = bytes[offset];
+        let (unit, length) = match first {
+            0x01..=0x7F => (Some(u16::from(first)), 1),
+            0xC0..=0xDF => (continuation(offset + 1).map(|second| u16::from(first & 0x1F) << 6 | second), 2),
+            0xE0..=0xEF => (continuation(offset + 1).and_then(|second| continuation(offset + 2)
+                .map(|third| u16::from(first & 0x0F) << 12 | second << 6 | third)), 3),
+            // NUL and four byte forms are not part of modified utf-8
+            _ => (None, 1)
+        };
+        match unit {
+            Some(unit) => {
+                units.push(Ok((offset, unit)));
+                offset += length;
+            }
+            None => {
+                units.push(Err(offset));
+                offset += 1;
+            }
+        }
+    }
+    units
+}
+
+/// decodes modified utf-8, failing on malformed bytes and surrogates without their pair.
+pub fn decode_modified_utf8(bytes: &[u8]) -> Result<String, ModifiedUtf8Error> {
+    let units = modified_utf8_units(bytes).into_iter()
+        .map(|unit| unit.map_err(|offset| ModifiedUtf8Error::Malformed { offset }))
+        .collect::<Result<Vec<(usize, u16)>, ModifiedUtf8Error>>()?;
+    let mut text = String::with_capacity(units.len());
+    let mut index = 0;
+    for decoded in ::std::char::decode_utf16(units.iter().map(|&(_, unit)| unit)) {
+        match decoded {
+            Ok(char) => {
+                text.push(char);
+                index += char.len_utf16();
+            }
+            Err(_) => return Err(ModifiedUtf8Error::UnpairedSurrogate { offset: units[index].0 })
+        }
+    }
+    Ok(text)
+}
+
+/// decodes modified utf-8, replacing malformed bytes and unpaired surrogates with U+FFFD.
+pub fn decode_modified_utf8_lossy(bytes: &[u8]) -> String {
+    let units = modified_utf8_units(bytes).into_iter()
+        .map(|unit| unit.map_or(0xFFFD, |(_, unit)| unit))
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn charsets_are_found_by_their_aliases() {
+        assert_eq!(Charset::for_name("utf8"), Some(Charset::Utf8));
+        assert_eq!(Charset::for_name("Latin1"), Some(Charset::Iso8859_1));
+        assert_eq!(Charset::for_name("UTF-16LE").map(Charset::name), Some("UTF-16LE"));
+        assert_eq!(Charset::for_name("EBCDIC"), None);
+    }
+
+    #[test]
+    fn strings_survive_a_round_trip() {
+        let text = "grüße \u{1F600}";
+        for &charset in [Charset::Utf8, Charset::Utf16, Charset::Utf16Be, Charset::Utf16Le].iter() {
+            assert_eq!(charset.decode(&charset.encode(text)), text, "{}", charset.name());
+        }
+        assert_eq!(Charset::Utf16.encode("a"), vec![0xFE, 0xFF, 0, b'a']);
+        assert_eq!(Charset::Utf16.decode(&[0xFF, 0xFE, b'a', 0]), "a");
+    }
+
+    #[test]
+    fn unmappable_characters_become_question_marks() {
+        assert_eq!(Charset::Iso8859_1.encode("ü€\u{1F600}"), vec![0xFC, b'?', b'?']);
+        assert_eq!(Charset::UsAscii.encode("ü"), vec![b'?']);
+        assert_eq!(Charset::UsAscii.decode(&[b'a', 0xFC]), "a\u{fffd}");
+        assert_eq!(Charset::Utf8.decode(&[b'a', 0xFF]), "a\u{fffd}");
+    }
+
+    #[test]
+    fn modified_utf8_encodes_nul_and_supplementary_characters() {
+        let text = "a\u{0}\u{1F600}";
+        let bytes = encode_modified_utf8(text);
+        assert_eq!(bytes, vec![b'a', 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]);
+        assert_eq!(decode_modified_utf8(&bytes), Ok(String::from(text)));
+    }
+
+    #[test]
+    fn malformed_modified_utf8_is_rejected_or_replaced() {
+        assert_eq!(decode_modified_utf8(&[b'a', 0]), Err(ModifiedUtf8Error::Malformed { offset: 1 }));
+        assert_eq!(decode_modified_utf8(&[0xF0, 0x9F, 0x98, 0x80]), Err(ModifiedUtf8Error::Malformed { offset: 0 }));
+        assert_eq!(decode_modified_utf8(&[b'a', 0xED, 0xA0, 0xBD]), Err(ModifiedUtf8Error::UnpairedSurrogate { offset: 1 }));
+        assert_eq!(decode_modified_utf8_lossy(&[b'a', 0xED, 0xA0, 0xBD, 0xC3]), "a\u{fffd}\u{fffd}");
+    }
+}
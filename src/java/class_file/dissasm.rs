@@ -1,5 +1,7 @@
 use java::class_file::Method;
 use java::instructions::Instruction;
+#[cfg(not(feature = "std"))]
+use prelude::*;
 
 pub fn disassemble<'a>(method: &Method<'a>) -> String {
     let code_block = method.get_code().unwrap();
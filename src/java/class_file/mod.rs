@@ -1,13 +1,106 @@
 mod parser;
 pub mod dissasm;
+#[cfg(feature = "std")]
+mod writer;
 
+#[cfg(not(feature = "std"))]
+use prelude::*;
 use java::instructions::*;
-pub use self::parser::read_class_file;
-use std::collections::HashSet;
+pub use self::parser::{read_class_file, read_class_file_with};
+#[cfg(feature = "std")]
+pub use self::writer::ClassWriter;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+// without threads a class is never shared between them
+#[cfg(not(feature = "std"))]
+use core::cell::OnceCell as OnceLock;
+
+/// the major versions of the class files the runtime can run, from java 1.1 up to java 17.
+pub const SUPPORTED_VERSIONS: RangeInclusive<u16> = 45..=61;
+
+/// what the parser does with utf8 constants which are not well-formed modified utf-8, like
+/// surrogates without their pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Utf8Decoding {
+    /// fails to parse the class file, like the jvm throws a `ClassFormatError`.
+    Strict,
+    /// replaces what cannot be decoded with U+FFFD.
+    Lossy,
+}
+
+/// why the runtime cannot run a class file, found out from its header before the rest is parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionError {
+    NotAClassFile,
+    Unsupported { major: u16, minor: u16 },
+    /// the class was compiled with `--enable-preview`.
+    Preview { major: u16 },
+}
+
+/// the java release which introduced a class file version.
+fn java_release(major: u16) -> String {
+    match major {
+        0..=48 => format!("Java 1.{}", major.saturating_sub(44)),
+        _ => format!("Java {}", major - 44)
+    }
+}
+
+impl fmt::Display for VersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let newest = *SUPPORTED_VERSIONS.end();
+        match *self {
+            VersionError::NotAClassFile => write!(f, "incompatible magic value, not a class file"),
+            VersionError::Unsupported { major, minor } if major > newest =>
+                write!(f, "compiled by a more recent version of the Java Runtime (class file version {}.{}, {}), this runtime only recognizes class file versions up to {}.0 ({})",
+                       major, minor, java_release(major), newest, java_release(newest)),
+            VersionError::Unsupported { major, minor } =>
+                write!(f, "class file version {}.{} is older than the oldest supported version {}.0", major, minor, SUPPORTED_VERSIONS.start()),
+            VersionError::Preview { major } =>
+                write!(f, "compiled with preview features of {}, which this runtime does not support", java_release(major)),
+        }
+    }
+}
+
+/// reads the (major, minor) version of the class file `bytes` and checks that the runtime supports it.
+pub fn check_version(bytes: &[u8]) -> Result<(u16, u16), VersionError> {
+    if bytes.len() < 8 || bytes[..4] != [0xCA, 0xFE, 0xBA, 0xBE] {
+        return Err(VersionError::NotAClassFile);
+    }
+    let (minor, major) = (u16::from(bytes[4]) << 8 | u16::from(bytes[5]), u16::from(bytes[6]) << 8 | u16::from(bytes[7]));
+    if !SUPPORTED_VERSIONS.contains(&major) {
+        return Err(VersionError::Unsupported { major, minor });
+    }
+    // since java 12 the minor version only marks classes using preview features
+    if major >= 56 && minor == 0xFFFF {
+        return Err(VersionError::Preview { major });
+    }
+    Ok((major, minor))
+}
+
+/// why `ClassFile::parse` rejected a class file.
+#[derive(Debug, Fail, PartialEq)]
+pub enum ClassFormatError {
+    #[fail(display = "{}", _0)]
+    Version(VersionError),
+    #[fail(display = "truncated or malformed class file")]
+    Malformed,
+    #[fail(display = "{} extra bytes at the end of the class file", count)]
+    TrailingBytes { count: usize },
+    #[fail(display = "invalid constant pool index {} for the {}", index, what)]
+    InvalidConstant { index: u16, what: &'static str },
+    #[fail(display = "method {} has the invalid descriptor {}", name, descriptor)]
+    InvalidDescriptor { name: String, descriptor: String },
+}
 
 #[derive(Debug)]
 pub struct ClassFile<'a> {
+    /// (major, minor)
     pub version: (u16, u16),
     pub constants: Vec<ConstantType<'a>>,
     pub access_flags: u16,
@@ -20,8 +113,31 @@ pub struct ClassFile<'a> {
 }
 
 impl<'a> ClassFile<'a> {
-    pub fn get_constant(&self, index: u16) -> Option<&ConstantType> {
-        self.constants.get(index as usize - 1)
+    /// parses and checks a complete class file. unlike `read_class_file` it fails instead of
+    /// returning a class whose name or method descriptors cannot be read, so any input can be passed
+    /// to it, it is the entry point of the fuzz targets in `fuzz/`.
+    pub fn parse(bytes: &'a [u8]) -> Result<ClassFile<'a>, ClassFormatError> {
+        check_version(bytes).map_err(ClassFormatError::Version)?;
+        let class = match read_class_file(bytes) {
+            Ok((rest, _)) if !rest.is_empty() => return Err(ClassFormatError::TrailingBytes { count: rest.len() }),
+            Ok((_, class)) => class,
+            Err(_) => return Err(ClassFormatError::Malformed)
+        };
+
+        if class.get_class_name_at(class.this_index).is_none() {
+            return Err(ClassFormatError::InvalidConstant { index: class.this_index, what: "this class" });
+        }
+        if class.super_index != 0 && class.get_super_class_name().is_none() {
+            return Err(ClassFormatError::InvalidConstant { index: class.super_index, what: "super class" });
+        }
+        if let Some(method) = class.methods.iter().find(|method| MethodDescriptor::from_str(&method.descriptor).is_err()) {
+            return Err(ClassFormatError::InvalidDescriptor { name: String::from(&*method.name), descriptor: String::from(&*method.descriptor) });
+        }
+        Ok(class)
+    }
+
+    pub fn get_constant(&self, index: u16) -> Option<&ConstantType<'a>> {
+        self.constants.get(usize::from(index).checked_sub(1)?)
     }
 
     pub fn get_class_name(&self) -> &str {
@@ -33,12 +149,175 @@ impl<'a> ClassFile<'a> {
 
 
         match cls_name {
-            ConstantType::Utf8 { value } => &value,
+            ConstantType::Utf8 { value } => value,
             _ => panic!("cannot read class name")
         }
     }
 
-    pub fn get_method_from_nat(&self, nat_index: u16) -> Option<&Method> {
+    /// resolves a `Class` constant to the name of the class.
+    pub fn get_class_name_at(&self, class_index: u16) -> Option<&str> {
+        match self.get_constant(class_index) {
+            Some(ConstantType::Class { name_index }) => match self.get_constant(*name_index) {
+                Some(ConstantType::Utf8 { value }) => Some(value),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.access_flags & 0x0200 == 0x0200
+    }
+
+    /// the name of the super class, `None` for `java/lang/Object`.
+    pub fn get_super_class_name(&self) -> Option<&str> {
+        if self.super_index == 0 {
+            return None;
+        }
+
+        self.get_class_name_at(self.super_index)
+    }
+
+    /// the name of the source file this class was compiled from, if the `SourceFile` attribute is present.
+    pub fn get_source_file(&self) -> Option<&str> {
+        let info = self.raw_attribute("SourceFile").filter(|info| info.len() == 2)?;
+
+        match self.get_constant(u16::from(info[0]) << 8 | u16::from(info[1])) {
+            Some(ConstantType::Utf8 { value }) => Some(value),
+            _ => None
+        }
+    }
+
+    /// the entry `index` of the `BootstrapMethods` attribute: the `MethodHandle` constant of the
+    /// bootstrap method and the constants passed to it as static arguments.
+    pub fn get_bootstrap_method(&self, index: u16) -> Option<(u16, Vec<u16>)> {
+        let info = self.raw_attribute("BootstrapMethods")?;
+
+        let u16_at = |offset: usize| info.get(offset..offset + 2).map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]));
+        let mut offset = 2;
+        for _ in 0..index {
+            offset += 4 + 2 * usize::from(u16_at(offset + 2)?);
+        }
+        if index >= u16_at(0)? {
+            return None;
+        }
+
+        let count = u16_at(offset + 2)?;
+        let arguments = (0..usize::from(count)).map(|argument| u16_at(offset + 4 + 2 * argument)).collect::<Option<Vec<_>>>()?;
+        Some((u16_at(offset)?, arguments))
+    }
+
+    /// the bytes of the first attribute `name` of the class which the parser keeps as they are,
+    /// which is every attribute but `Code` and `LineNumberTable`.
+    pub fn raw_attribute(&self, name: &str) -> Option<&'a [u8]> {
+        raw_attribute(&self.attributes, name)
+    }
+
+    /// the module a `module-info` class declares, read from its `Module` attribute.
+    pub fn get_module(&self) -> Option<ModuleDescriptor> {
+        let info = self.raw_attribute("Module")?;
+
+        let u16_at = |offset: usize| info.get(offset..offset + 2).map(|bytes| u16::from(bytes[0]) << 8 | u16::from(bytes[1]));
+        let name_at = |offset: usize| match self.get_constant(u16_at(offset)?) {
+            Some(ConstantType::Module { name_index }) | Some(ConstantType::Package { name_index }) => match self.get_constant(*name_index) {
+                Some(ConstantType::Utf8 { value }) => Some(String::from(&**value)),
+                _ => None
+            },
+            _ => None
+        };
+
+        let name = name_at(0)?;
+        let mut offset = 6;
+        let mut requires = Vec::new();
+        for _ in 0..u16_at(offset)? {
+            requires.push((name_at(offset + 2)?, u16_at(offset + 4)? & ACC_TRANSITIVE != 0));
+            offset += 6;
+        }
+        offset += 2;
+        let mut exports = Vec::new();
+        for _ in 0..u16_at(offset)? {
+            let targets = (0..usize::from(u16_at(offset + 6)?)).map(|target| name_at(offset + 8 + 2 * target)).collect::<Option<Vec<_>>>()?;
+            exports.push((name_at(offset + 2)?, targets));
+            offset += 6 + 2 * exports.last()?.1.len();
+        }
+
+        Some(ModuleDescriptor { name, requires, exports })
+    }
+
+    /// true if the class itself declares a field `name` of type `descriptor`.
+    pub fn declares_field(&self, name: &str, descriptor: &str) -> bool {
+        let utf8 = |index| match self.get_constant(index) {
+            Some(ConstantType::Utf8 { value }) => Some(value),
+            _ => None
+        };
+        self.fields.iter().any(|field| utf8(field.name_index).is_some_and(|value| *value == name)
+            && utf8(field.descriptor_index).is_some_and(|value| *value == descriptor))
+    }
+
+    /// the access flags, name and descriptor of each field the class declares, in declaration order.
+    pub fn field_signatures(&self) -> Vec<(u16, &str, &str)> {
+        let utf8 = |index| match self.get_constant(index) {
+            Some(ConstantType::Utf8 { value }) => &**value,
+            _ => ""
+        };
+        self.fields.iter().map(|field| (field.access_flags, utf8(field.name_index), utf8(field.descriptor_index))).collect()
+    }
+
+    /// the other classes this class depends on, sorted: the ones its constant pool names, like the
+    /// classes it instantiates or whose members it uses, and the ones in the descriptors of its
+    /// fields and methods and of the members it refers to. arrays count as their element class.
+    pub fn referenced_classes(&self) -> Vec<&str> {
+        let mut classes = BTreeSet::new();
+        for constant in self.constants.iter() {
+            match constant {
+                ConstantType::Class { name_index } => match self.get_constant(*name_index) {
+                    Some(ConstantType::Utf8 { value }) if value.starts_with('[') => classes.extend(descriptor_classes(value)),
+                    Some(ConstantType::Utf8 { value }) => { classes.insert(&**value); },
+                    _ => ()
+                },
+                ConstantType::NameAndType { descriptor_index, .. } | ConstantType::MethodType { descriptor_index } => {
+                    if let Some(ConstantType::Utf8 { value }) = self.get_constant(*descriptor_index) {
+                        classes.extend(descriptor_classes(value));
+                    }
+                },
+                _ => ()
+            }
+        }
+        for (_, _, descriptor) in self.field_signatures() {
+            classes.extend(descriptor_classes(descriptor));
+        }
+        for method in self.methods.iter() {
+            classes.extend(descriptor_classes(&method.descriptor));
+        }
+        let this_class = self.get_class_name_at(self.this_index);
+        classes.into_iter().filter(|&name| Some(name) != this_class).collect()
+    }
+
+    /// the class, name and descriptor of every method the constant pool refers to, in its order.
+    pub fn referenced_methods(&self) -> Vec<(&str, &str, &str)> {
+        self.constants.iter().filter_map(|constant| match constant {
+            ConstantType::MethodRef { class_index, name_and_type_index } |
+            ConstantType::InterfaceMethodRef { class_index, name_and_type_index } => {
+                let (name, descriptor) = self.get_name_and_type(*name_and_type_index)?;
+                Some((self.get_class_name_at(*class_index)?, name, descriptor))
+            },
+            _ => None
+        }).collect()
+    }
+
+    /// the values of the string literals in the constant pool, in its order.
+    pub fn string_constants(&self) -> Vec<&str> {
+        self.constants.iter().filter_map(|constant| match constant {
+            ConstantType::String { string_index } => match self.get_constant(*string_index) {
+                Some(ConstantType::Utf8 { value }) => Some(&**value),
+                _ => None
+            },
+            _ => None
+        }).collect()
+    }
+
+    /// resolves a `NameAndType` constant to its name and descriptor.
+    pub fn get_name_and_type(&self, nat_index: u16) -> Option<(&str, &str)> {
         let name_and_type = self.get_constant(nat_index);
 
         let (name_index, type_index) = match name_and_type {
@@ -50,20 +329,90 @@ impl<'a> ClassFile<'a> {
 
         let name = match self.get_constant(*name_index) {
             Some(ConstantType::Utf8 { value }) => {
-                *value
+                &**value
             }
             _ => return None
         };
 
         let type_desc = match self.get_constant(*type_index) {
             Some(ConstantType::Utf8 { value }) => {
-                *value
+                &**value
             }
             _ => return None
         };
 
+        Some((name, type_desc))
+    }
+
+    pub fn get_method_from_nat(&self, nat_index: u16) -> Option<&Method<'a>> {
+        let (name, type_desc) = self.get_name_and_type(nat_index)?;
+
         self.methods.iter().find(|method| method.name == name && method.descriptor == type_desc)
     }
+
+    /// a constant as an instruction uses it, like `Point.x:I` for a field, `Point.move(II)V` for a
+    /// method or `class Point`. `#index` if it cannot be resolved.
+    pub fn describe_constant(&self, index: u16) -> String {
+        let member = |class_index: u16, name_and_type_index: u16, separator: &str| {
+            match (self.get_class_name_at(class_index), self.get_name_and_type(name_and_type_index)) {
+                (Some(class_name), Some((name, descriptor))) => Some(format!("{}.{}{}{}", class_name, name, separator, descriptor)),
+                _ => None
+            }
+        };
+        let utf8 = |index: u16| match self.get_constant(index) {
+            Some(ConstantType::Utf8 { value }) => Some(&**value),
+            _ => None
+        };
+        let described = match self.get_constant(index) {
+            Some(ConstantType::Integer { value }) => Some(format!("int {}", value)),
+            Some(ConstantType::Long { value }) => Some(format!("long {}", value)),
+            Some(ConstantType::Float { value }) => Some(format!("float {}", value)),
+            Some(ConstantType::Double { value }) => Some(format!("double {}", value)),
+            Some(ConstantType::String { string_index }) => utf8(*string_index).map(|value| format!("{:?}", value)),
+            Some(ConstantType::Class { .. }) => self.get_class_name_at(index).map(|name| format!("class {}", name)),
+            Some(ConstantType::FieldRef { class_index, name_and_type_index }) => member(*class_index, *name_and_type_index, ":"),
+            Some(ConstantType::MethodRef { class_index, name_and_type_index }) |
+            Some(ConstantType::InterfaceMethodRef { class_index, name_and_type_index }) => member(*class_index, *name_and_type_index, ""),
+            Some(ConstantType::MethodType { descriptor_index }) => utf8(*descriptor_index).map(|descriptor| format!("method type {}", descriptor)),
+            Some(ConstantType::InvokeDynamic { name_and_type_index, .. }) =>
+                self.get_name_and_type(*name_and_type_index).map(|(name, descriptor)| format!("{}{}", name, descriptor)),
+            _ => None
+        };
+        described.unwrap_or_else(|| format!("#{}", index))
+    }
+}
+
+/// the names of the classes in a field or method descriptor, like `java/lang/String` in
+/// `([Ljava/lang/String;)V`.
+fn descriptor_classes(descriptor: &str) -> impl Iterator<Item=&str> {
+    let mut rest = descriptor;
+    ::std::iter::from_fn(move || {
+        let start = rest.find('L')?;
+        let end = start + rest[start..].find(';')?;
+        let name = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        Some(name)
+    })
+}
+
+const ACC_TRANSITIVE: u16 = 0x0020;
+
+fn raw_attribute<'a>(attributes: &[Attribute<'a>], name: &str) -> Option<&'a [u8]> {
+    attributes.iter().filter_map(|attr| match attr {
+        Attribute::GenericAttribute { name: attr_name, info } if attr_name == name => Some(*info),
+        _ => None
+    }).next()
+}
+
+/// the name, dependencies and exported packages of a module. names of packages use slashes like
+/// the names of classes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleDescriptor {
+    pub name: String,
+    /// the modules it reads, with true for `requires transitive`.
+    pub requires: Vec<(String, bool)>,
+    /// the exported packages with the modules they are exported to, empty if exported to every module.
+    pub exports: Vec<(String, Vec<String>)>,
 }
 
 #[derive(Debug)]
@@ -74,15 +423,26 @@ pub struct Field<'a> {
     attributes: Vec<Attribute<'a>>,
 }
 
+impl<'a> Field<'a> {
+    pub fn attributes(&self) -> &[Attribute<'a>] {
+        &self.attributes
+    }
+
+    /// the bytes of the first attribute `name` of the field, see `ClassFile::raw_attribute`.
+    pub fn raw_attribute(&self, name: &str) -> Option<&'a [u8]> {
+        raw_attribute(&self.attributes, name)
+    }
+}
+
 #[derive(Debug)]
 pub struct Method<'a> {
     pub access_flags: u16,
-    pub name: &'a str,
-    pub descriptor: &'a str,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
     pub attributes: Vec<Attribute<'a>>,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
+#[derive(Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum MethodAccess {
     Public,
     Private,
@@ -95,30 +455,59 @@ pub enum MethodAccess {
     Strict,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ValueType {
     Void,
     Integer,
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Long,
     Object(String),
     Array(Box<ValueType>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct MethodDescriptor {
     pub return_type: ValueType,
     pub arguments: Vec<ValueType>,
 }
 
-use nom::IResult;
-use std::slice::Iter;
+/// formats the type as a field descriptor, like `I` or `Ljava/lang/String;`.
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueType::Void => write!(f, "V"),
+            ValueType::Integer => write!(f, "I"),
+            ValueType::Boolean => write!(f, "Z"),
+            ValueType::Byte => write!(f, "B"),
+            ValueType::Char => write!(f, "C"),
+            ValueType::Short => write!(f, "S"),
+            ValueType::Long => write!(f, "J"),
+            ValueType::Object(class_name) => write!(f, "L{};", class_name),
+            ValueType::Array(component) => write!(f, "[{}", component),
+        }
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for argument in self.arguments.iter() {
+            write!(f, "{}", argument)?;
+        }
+        write!(f, "){}", self.return_type)
+    }
+}
 
 impl FromStr for MethodDescriptor {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
         match parser::method_desc(s.as_bytes()) {
-            Ok((rem, (args, ret))) => Ok(MethodDescriptor { arguments: args, return_type: ret }),
-            Err(_) => panic!("asdf"),
+            Ok((&[], (args, ret))) => Ok(MethodDescriptor { arguments: args, return_type: ret }),
+            _ => Err(()),
         }
     }
 }
@@ -128,28 +517,36 @@ impl<'a> Method<'a> {
         self.get_code().unwrap().instructions().unwrap()
     }
 
+    /// the instructions of this method, each paired with its pc.
+    pub fn instructions_with_pc(&self) -> Vec<(usize, Instruction)> {
+        self.get_code().unwrap().instructions_with_pc().unwrap()
+    }
+
+    /// the code of the method, parsed on the first call.
+    /// the bytes of the first attribute `name` of the method, see `ClassFile::raw_attribute`.
+    pub fn raw_attribute(&self, name: &str) -> Option<&'a [u8]> {
+        raw_attribute(&self.attributes, name)
+    }
+
     pub fn get_code(&self) -> Option<&CodeBlock<'a>> {
         self.attributes.iter()
-            .filter_map(
+            .find_map(
                 |attr| match attr {
-                    Attribute::CodeAttribute(code) => Some(code),
+                    Attribute::CodeAttribute(code) => code.get(),
                     _ => None
                 }
             )
-            .collect::<Vec<&CodeBlock>>()
-            .first()
-            .map(|x| *x)
     }
 
-    pub fn get_signature(&self) -> MethodDescriptor {
-        match MethodDescriptor::from_str(self.descriptor) {
-            Ok(method) => method,
-            Err(err) => panic!("{:?}", err)
-        }
+    /// the parsed descriptor of the method, an error for descriptors `ClassFile::parse` rejects.
+    pub fn get_signature(&self) -> Result<MethodDescriptor, ClassFormatError> {
+        MethodDescriptor::from_str(&self.descriptor)
+            .map_err(|_| ClassFormatError::InvalidDescriptor { name: String::from(&*self.name), descriptor: String::from(&*self.descriptor) })
     }
 
-    pub fn get_access(&self) -> HashSet<MethodAccess> {
-        let mut set = HashSet::new();
+    #[cfg(feature = "std")]
+    pub fn get_access(&self) -> BTreeSet<MethodAccess> {
+        let mut set = BTreeSet::new();
         if self.access_flags & 0x0001 == 0x0001 {
             set.insert(MethodAccess::Public);
         }
@@ -175,7 +572,7 @@ impl<'a> Method<'a> {
             set.insert(MethodAccess::Strict);
         }
 
-        return set;
+        set
     }
 }
 
@@ -184,6 +581,8 @@ pub struct CodeBlock<'a> {
     pub max_stack: u16,
     pub max_locals: u16,
     code: Vec<u8>,
+    /// (start_pc, end_pc, handler_pc, catch_type)
+    pub exception_table: Vec<(u16, u16, u16, u16)>,
     attributes: Vec<Attribute<'a>>,
 }
 
@@ -192,33 +591,82 @@ impl<'a> CodeBlock<'a> {
         Instruction::read_all(&self.code[..])
     }
 
+    pub fn instructions_with_pc(&self) -> Result<Vec<(usize, Instruction)>, ReadInstructionError<&[u8]>> {
+        Instruction::read_all_with_offsets(&self.code[..])
+    }
+
+    /// the source line of the instruction at `pc`, if the `LineNumberTable` attribute is present.
+    pub fn line_number_at(&self, pc: usize) -> Option<usize> {
+        self.attributes.iter()
+            .filter_map(|attr| match attr {
+                Attribute::LineNumberTable(table) => Some(table),
+                _ => None
+            })
+            .flat_map(|table| table.iter())
+            .filter(|&&(start, _)| usize::from(start) <= pc)
+            .max_by_key(|&&(start, _)| start)
+            .map(|&(_, line)| usize::from(line))
+    }
+
+    /// the entries of the `LineNumberTable` attribute as (start_pc, line), empty if it is missing.
+    pub fn line_number_table(&self) -> Vec<(u16, u16)> {
+        self.attributes.iter()
+            .filter_map(|attr| match attr {
+                Attribute::LineNumberTable(table) => Some(table),
+                _ => None
+            })
+            .flat_map(|table| table.iter().cloned())
+            .collect()
+    }
+
+    /// the number of bytes of bytecode.
+    pub fn code_length(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn attributes(&self) -> &[Attribute<'a>] {
+        &self.attributes
+    }
+
+    /// the bytes of the first attribute `name` of the code, like `LocalVariableTable` or
+    /// `StackMapTable`, see `ClassFile::raw_attribute`.
+    pub fn raw_attribute(&self, name: &str) -> Option<&'a [u8]> {
+        raw_attribute(&self.attributes, name)
+    }
+
+    /// the bytecode as it is stored in the class file.
+    pub fn code(&self) -> &[u8] {
+        &self.code[..]
+    }
+
     ///  Vec<usize>  pc -> ln
     pub fn get_line_numbers(&self) -> Vec<usize> {
-        let line_number_attr = self.attributes.iter().find(|x| match x {
-            Attribute::LineNumberTable(_) => true,
-            _ => false
-        });
+        let line_number_attr = self.attributes.iter().find(|x| matches!(x, Attribute::LineNumberTable(_)));
 
         let line_number = match line_number_attr {
             Some(Attribute::LineNumberTable(t)) => t,
             _ => return Vec::new()
         };
 
-        let max = line_number.last().unwrap();
+        let max = match line_number.iter().map(|&(pc, _)| usize::from(pc)).max() {
+            Some(max) => max,
+            None => return Vec::new()
+        };
 
+        // pcs before the first entry of a table which is out of order get line 0
         let mut numbers = Vec::new();
-        for x in 0..(max.0 + 1) {
+        for x in 0..(max + 1) {
             let ln = line_number
                 .iter()
                 .filter_map(|&(pc, ln)|
-                    if pc <= x {
+                    if usize::from(pc) <= x {
                         Some(ln)
                     } else {
                         None
                     }
                 )
-                .last()
-                .expect("no matching line number");
+                .next_back()
+                .unwrap_or(0);
 
             numbers.push(ln as usize);
         }
@@ -227,19 +675,75 @@ impl<'a> CodeBlock<'a> {
     }
 }
 
+/// the utf8 constants of a class by their index, which lazily parsed attributes need for attribute names.
+#[derive(Debug)]
+pub struct ConstantNames<'a> {
+    names: Vec<Option<Cow<'a, str>>>,
+}
+
+impl<'a> ConstantNames<'a> {
+    pub fn new(constants: &[ConstantType<'a>]) -> ConstantNames<'a> {
+        ConstantNames {
+            names: constants.iter().map(|constant| match constant {
+                ConstantType::Utf8 { value } => Some(value.clone()),
+                _ => None
+            }).collect()
+        }
+    }
+
+    pub fn get(&self, index: u16) -> Option<&Cow<'a, str>> {
+        self.names.get(usize::from(index).checked_sub(1)?)?.as_ref()
+    }
+}
+
+/// a `Code` attribute which is kept as raw bytes until the method is first used,
+/// so loading a class does not pay for the code of methods which never run.
+#[derive(Debug)]
+pub struct LazyCode<'a> {
+    data: &'a [u8],
+    names: Arc<ConstantNames<'a>>,
+    code: OnceLock<Option<CodeBlock<'a>>>,
+}
+
+impl<'a> LazyCode<'a> {
+    pub fn new(data: &'a [u8], names: Arc<ConstantNames<'a>>) -> LazyCode<'a> {
+        LazyCode { data, names, code: OnceLock::new() }
+    }
+
+    /// the parsed code, `None` if the attribute is malformed.
+    pub fn get(&self) -> Option<&CodeBlock<'a>> {
+        self.code.get_or_init(|| parser::code_block(self.data, &self.names)).as_ref()
+    }
+
+    pub fn is_parsed(&self) -> bool {
+        self.code.get().is_some()
+    }
+}
+
 #[derive(Debug)]
 pub enum Attribute<'a> {
     LineNumberTable(Vec<(u16, u16)>),
-    CodeAttribute(CodeBlock<'a>),
+    CodeAttribute(LazyCode<'a>),
+    /// any other attribute, with the bytes after its length.
     GenericAttribute {
         name: String,
         info: &'a [u8],
     },
 }
 
+impl<'a> Attribute<'a> {
+    pub fn name(&self) -> &str {
+        match self {
+            Attribute::LineNumberTable(_) => "LineNumberTable",
+            Attribute::CodeAttribute(_) => "Code",
+            Attribute::GenericAttribute { name, .. } => name
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ConstantType<'a> {
-    Utf8 { value: &'a str },
+    Utf8 { value: Cow<'a, str> },
     Integer { value: i32 },
     Float { value: f32 },
     Long { value: i64 },
@@ -2,30 +2,65 @@ use nom::*;
 
 use super::*;
 
+use java::charset::{decode_modified_utf8, decode_modified_utf8_lossy};
 use std::string::String;
 use std::str::from_utf8;
 
+/// nom's `dbg_dmp!` prints the input a parser failed on, which needs std. without it, it only
+/// runs the parser.
+#[cfg(not(feature = "std"))]
+macro_rules! dbg_dmp {
+    ($i:expr, $submac:ident!( $($args:tt)* )) => { $submac!($i, $($args)*) };
+}
+
 named!(
     parse_type<&[u8], ValueType>,
     dbg_dmp!(switch!(take!(1),
-        b"L" => do_parse!( tn: map_res!(take_until!(";"), from_utf8) >> (ValueType::Object(String::from(tn)))) |
+        b"L" => do_parse!( tn: map_res!(take_until_and_consume!(";"), from_utf8) >> (ValueType::Object(String::from(tn)))) |
         b"I" => value!(ValueType::Integer) |
+        b"Z" => value!(ValueType::Boolean) |
+        b"B" => value!(ValueType::Byte) |
+        b"C" => value!(ValueType::Char) |
+        b"S" => value!(ValueType::Short) |
+        b"J" => value!(ValueType::Long) |
         b"V" => value!(ValueType::Void) |
-        b"[" => do_parse!( ele: parse_type >> (ValueType::Array(Box::new(ele)))) |
+        b"[" => call!(array_type) |
         _ => value!(ValueType::Void)
     ))
 );
 
+/// the most dimensions an array type may have.
+const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+/// an array type after its first `[`. the dimensions are counted instead of parsed recursively,
+/// so a descriptor of thousands of `[` cannot overflow the stack.
+fn array_type(input: &[u8]) -> IResult<&[u8], ValueType> {
+    let dimensions = 1 + input.iter().take_while(|&&c| c == b'[').count();
+    if dimensions > MAX_ARRAY_DIMENSIONS {
+        return Err(Err::Error(error_position!(input, ErrorKind::Custom(2))));
+    }
+
+    let (rem, mut typ) = parse_type(&input[dimensions - 1..])?;
+    for _ in 0..dimensions {
+        typ = ValueType::Array(Box::new(typ));
+    }
+    Ok((rem, typ))
+}
+
 pub fn param_list(input: &[u8]) -> IResult<&[u8], Vec<ValueType>> {
-    if input[0] != b'(' {
-        return Err(Err::Incomplete(Needed::Size(2)));
+    match input.first() {
+        Some(b'(') => (),
+        Some(_) => return Err(Err::Error(error_position!(input, ErrorKind::Tag))),
+        None => return Err(Err::Incomplete(Needed::Size(2)))
     }
 
     let mut input = &input[1..];
     let mut vec = Vec::new();
     loop {
-        if input[0] == b')' {
-            return Ok((&input[1..], vec));
+        match input.first() {
+            Some(b')') => return Ok((&input[1..], vec)),
+            Some(_) => (),
+            None => return Err(Err::Incomplete(Needed::Size(1)))
         }
 
         match parse_type(input) {
@@ -35,7 +70,7 @@ pub fn param_list(input: &[u8]) -> IResult<&[u8], Vec<ValueType>> {
             },
             Err(err) => return Err(err)
         }
-    }.clone()
+    }
 }
 
 named!(
@@ -86,10 +121,24 @@ named!(
     const_name_and_type<ConstantType>,
     do_parse!(name_index: be_u16 >> descriptor_index: be_u16 >> ( ConstantType::NameAndType { name_index, descriptor_index } ))
 );
-named!(
-    const_utf8<ConstantType>,
-    do_parse!(bytes: length_data!(be_u16) >> ( ConstantType::Utf8 { value: from_utf8(bytes).unwrap() } ) )
-);
+/// a utf8 constant, which is modified utf-8. javac writes everything but NUL and supplementary
+/// characters like standard utf-8, so those constants are borrowed from the class file; the others
+/// are decoded and owned by the constant.
+fn const_utf8<'a>(input: &'a [u8], decoding: Utf8Decoding) -> IResult<&'a [u8], ConstantType<'a>> {
+    let (rem, bytes) = length_data!(input, be_u16)?;
+    let standard = !bytes.iter().any(|&byte| byte == 0 || byte >= 0xF0);
+    let value = match from_utf8(bytes) {
+        Ok(value) if standard => Cow::Borrowed(value),
+        _ => Cow::Owned(match decoding {
+            Utf8Decoding::Strict => match decode_modified_utf8(bytes) {
+                Ok(value) => value,
+                Err(_) => return Err(Err::Error(error_position!(input, ErrorKind::Custom(3))))
+            },
+            Utf8Decoding::Lossy => decode_modified_utf8_lossy(bytes)
+        })
+    };
+    Ok((rem, ConstantType::Utf8 { value }))
+}
 named!(
     const_method_handle<ConstantType>,
     do_parse!(reference_kind: be_u8 >> reference_index: be_u16 >> ( ConstantType::MethodHandle { reference_kind, reference_index } )  )
@@ -111,10 +160,10 @@ named!(
     do_parse!(name_index: be_u16 >> ( ConstantType::Package { name_index} ) )
 );
 
-named!(
-    constant<&[u8], ConstantType>,
+named_args!(
+    constant(decoding: Utf8Decoding)<ConstantType>,
     dbg_dmp!(switch!(be_u8,
-        1 => dbg_dmp!(call!(const_utf8 )) |
+        1 => dbg_dmp!(call!(const_utf8, decoding )) |
         3 => dbg_dmp!(call!(const_integer )) |
         4 => dbg_dmp!(call!(const_float )) |
         5 => dbg_dmp!(call!(const_long )) |
@@ -147,7 +196,6 @@ named!(
 named!(
     line_number_table<Attribute>,
     do_parse!(
-        length: be_u32 >>
         line_numbers: length_count!(
             be_u16,
             do_parse!(
@@ -162,117 +210,108 @@ named!(
     )
 );
 
-fn select_attribute<'t, 'a>(input: &'t [u8], name: &str, constants: &'a Vec<ConstantType<'a>>) -> IResult<&'t [u8], Attribute<'t>> {
-    match name {
-        "LineNumberTable" => {
-            match line_number_table(input) {
-                Ok((rem, line_numbers)) => {
-                    Ok((&rem, line_numbers))
-                }
-                Err(err) => return Err(err)
-            }
-        }
-        "Code" => {
-            match do_parse!( input,
-                    be_u32 >>
-                    max_stack: be_u16 >>
-                    max_locals: be_u16 >>
-                    code: length_data!( be_u32 ) >>
-                    exception_table: length_count!( be_u16, exception_table ) >>
-                    attributes: length_count!( be_u16, call!(attribute, &constants)) >>
-                    (
-                        Attribute::CodeAttribute( CodeBlock { max_stack, max_locals, code: code.to_vec(), attributes } )
-                    )
-                ) {
-                Ok((rem, attribute)) => Ok((&rem, attribute)),
-                Err(err) => return Err(err)
-            }
-        }
-        _ => {
-            let nm = String::from(name);
-            match be_u32(input) {
-                Ok((rem, length)) => {
-                    Ok((&rem[(length as usize)..], Attribute::GenericAttribute { name: nm, info: &rem[0..(length as usize)] }))
-                }
-                Err(err) => return Err(err)
-            }
-        }
-    }
+/// parses an attribute of the class file, its length already read. attributes which are unknown
+/// or do not look as expected are kept as `GenericAttribute` with their raw bytes, like the jvm
+/// ignores them, so classes of newer compilers and other tools still load.
+fn select_attribute<'t>(input: &'t [u8], name: &str, names: &Arc<ConstantNames<'t>>) -> IResult<&'t [u8], Attribute<'t>> {
+    let (rem, info) = length_data!(input, be_u32)?;
+    let attribute = match name {
+        "LineNumberTable" => match line_number_table(info) {
+            Ok((&[], line_numbers)) => Some(line_numbers),
+            _ => None
+        },
+        // the code is parsed when the method is first used, see `LazyCode`
+        "Code" => Some(Attribute::CodeAttribute(LazyCode::new(info, names.clone()))),
+        _ => None
+    };
+
+    Ok((rem, attribute.unwrap_or_else(|| Attribute::GenericAttribute { name: String::from(name), info })))
 }
 
-fn attribute<'t, 'a>(input: &'t [u8], constants: &'a Vec<ConstantType<'a>>) -> IResult<&'t [u8], Attribute<'t>> {
+/// parses the body of a `Code` attribute.
+pub fn code_block<'t>(input: &'t [u8], names: &Arc<ConstantNames<'t>>) -> Option<CodeBlock<'t>> {
+    let parsed = do_parse!( input,
+        max_stack: be_u16 >>
+        max_locals: be_u16 >>
+        code: length_data!( be_u32 ) >>
+        exception_table: length_count!( be_u16, exception_table ) >>
+        attributes: length_count!( be_u16, call!(attribute, names)) >>
+        ( CodeBlock { max_stack, max_locals, code: code.to_vec(), exception_table, attributes } )
+    );
+
+    parsed.ok().map(|(_, code)| code)
+}
+
+fn attribute<'t>(input: &'t [u8], names: &Arc<ConstantNames<'t>>) -> IResult<&'t [u8], Attribute<'t>> {
     let idx_res = be_u16(input);
     match idx_res {
         Ok((remaining, index)) => {
-            match constants.get(index as usize - 1) {
-                Some(ConstantType::Utf8 { value: ref name }) => {
-                    select_attribute(remaining, name, constants)
+            match names.get(index) {
+                Some(name) => {
+                    select_attribute(remaining, name, names)
                 }
                 _ => {
                     Err(Err::Error(error_position!(remaining, ErrorKind::Custom(1))))
                 }
             }
         }
-        Err(err) => return Err(err)
+        Err(err) => Err(err)
     }
 }
 
 named_args!(
-    field<'a>(constants: &'a Vec<ConstantType<'this_is_probably_unique_i_hope_please>>)<Field<'this_is_probably_unique_i_hope_please>>,
+    field<'a>(names: &'a Arc<ConstantNames<'this_is_probably_unique_i_hope_please>>)<Field<'this_is_probably_unique_i_hope_please>>,
     do_parse!(
         access_flags:     be_u16 >>
         name_index:       be_u16 >>
         descriptor_index: be_u16 >>
         attributes_count: be_u16 >>
-        attributes:       count!( call!(attribute, constants), attributes_count as usize ) >>
+        attributes:       count!( call!(attribute, names), attributes_count as usize ) >>
         ( Field { access_flags, name_index, descriptor_index, attributes } )
     )
 );
 
 
 named_args!(
-    method<'a>(constants: &'a Vec<ConstantType<'this_is_probably_unique_i_hope_please>>)<Method<'this_is_probably_unique_i_hope_please>>,
+    method<'a>(names: &'a Arc<ConstantNames<'this_is_probably_unique_i_hope_please>>)<Method<'this_is_probably_unique_i_hope_please>>,
     do_parse!(
         access_flags:     be_u16 >>
         name_index:       be_u16 >>
         descriptor_index: be_u16 >>
         attributes_count: be_u16 >>
-        attributes:       count!( call!(attribute, constants), attributes_count as usize ) >>
-        ( Method {
-            access_flags,
-            name: match constants.get(usize::from(name_index - 1)).unwrap() {
-                ConstantType::Utf8 { value: str } => str,
-                _ => panic!("wrong constant type")
-            },
-            descriptor: match constants.get(usize::from(descriptor_index - 1)).unwrap() {
-                ConstantType::Utf8 { value: str } => str,
-                _ => panic!("wrong constant type")
-            },
-            attributes
-          }
-        )
+        name:             expr_opt!( names.get(name_index).cloned() ) >>
+        descriptor:       expr_opt!( names.get(descriptor_index).cloned() ) >>
+        attributes:       count!( call!(attribute, names), attributes_count as usize ) >>
+        ( Method { access_flags, name, descriptor, attributes } )
     )
 );
 
-named!(
-    pub read_class_file<ClassFile>,
-    dbg!(do_parse!(
+/// parses a class file whose utf8 constants must be well-formed modified utf-8.
+pub fn read_class_file<'a>(input: &'a [u8]) -> IResult<&'a [u8], ClassFile<'a>> {
+    read_class_file_with(input, Utf8Decoding::Strict)
+}
+
+// parses a class file, decoding its utf8 constants like `decoding` says
+named_args!(
+    pub read_class_file_with(decoding: Utf8Decoding)<ClassFile>,
+    dbg_dmp!(do_parse!(
         tag!(&[0xCAu8, 0xFEu8, 0xBAu8, 0xBEu8][..]) >>
         minor:              be_u16    >>
         major:              be_u16    >>
         constants_length:   be_u16    >>
-        constants:          count!( constant, constants_length as usize - 1) >>
+        constants:          count!( call!(constant, decoding), usize::from(constants_length).saturating_sub(1) ) >>
+        names:              value!( Arc::new(ConstantNames::new(&constants)) ) >>
         access_flags:       be_u16    >>
         this_index:         be_u16    >>
         super_index:        be_u16    >>
         interfaces_count:   be_u16    >>
         interfaces:         count!( be_u16, interfaces_count as usize ) >>
         fields_count:       be_u16    >>
-        fields:             count!( call!(field, &constants), fields_count as usize ) >>
+        fields:             count!( call!(field, &names), fields_count as usize ) >>
         methods_count:      be_u16    >>
-        methods:            count!( call!(method, &constants), methods_count as usize ) >>
+        methods:            count!( call!(method, &names), methods_count as usize ) >>
         attributes_count:   be_u16    >>
-        attributes:         count!( call!(attribute, &constants), attributes_count as usize ) >>
+        attributes:         count!( call!(attribute, &names), attributes_count as usize ) >>
         ( ClassFile { version: (major, minor), constants, access_flags, this_index, super_index, interfaces, fields, methods, attributes } )
     ))
 );
@@ -280,11 +319,11 @@ named!(
 
 #[cfg(test)]
 mod test {
-    use super::read_class_file;
-    use java::class_file::ClassFile;
+    use super::{read_class_file, read_class_file_with};
+    use java::class_file::{check_version, Attribute, ClassFile, Utf8Decoding, VersionError};
 
-    const CLASSFILE: &'static [u8] = include_bytes!("../../../sample/HelloWorld.class");
-    const DEMOCLASS: &'static [u8] = include_bytes!("../../../sample/DemoClass.class");
+    const CLASSFILE: &[u8] = include_bytes!("../../../sample/HelloWorld.class");
+    const DEMOCLASS: &[u8] = include_bytes!("../../../sample/DemoClass.class");
 
 
     fn get_cf<'a>() -> ClassFile<'a> {
@@ -309,6 +348,23 @@ mod test {
         assert_eq!((54, 0), cf.version)
     }
 
+    #[test]
+    fn it_rejects_unsupported_versions() {
+        assert_eq!(check_version(CLASSFILE), Ok((54, 0)));
+
+        let mut newer = CLASSFILE.to_vec();
+        newer[7] = 65;
+        let err = check_version(&newer).unwrap_err();
+        assert_eq!(err, VersionError::Unsupported { major: 65, minor: 0 });
+        assert_eq!(err.to_string(), "compiled by a more recent version of the Java Runtime (class file version 65.0, Java 21), \
+                                     this runtime only recognizes class file versions up to 61.0 (Java 17)");
+
+        let mut preview = CLASSFILE.to_vec();
+        preview[4..8].copy_from_slice(&[0xFF, 0xFF, 0, 61]);
+        assert_eq!(check_version(&preview), Err(VersionError::Preview { major: 61 }));
+        assert_eq!(check_version(b"not a class file"), Err(VersionError::NotAClassFile));
+    }
+
     #[test]
     fn it_reads_the_correct_number_of_constants() {
         let cf = get_cf();
@@ -320,11 +376,160 @@ mod test {
         assert_eq!("HelloWorld", get_cf().get_class_name())
     }
 
+    #[test]
+    fn it_lists_the_classes_methods_and_strings_it_refers_to() {
+        let cf = get_cf();
+        assert_eq!(cf.referenced_classes(), vec!["java/io/PrintStream", "java/lang/Object", "java/lang/String", "java/lang/System"]);
+        assert_eq!(cf.referenced_methods(), vec![("java/lang/Object", "<init>", "()V"), ("java/io/PrintStream", "println", "(Ljava/lang/String;)V")]);
+        assert_eq!(cf.string_constants(), vec!["Hello World!"]);
+
+        let dead_code = read_class_file(include_bytes!("../../../sample/DeadCode.class")).unwrap().1;
+        let classes = dead_code.referenced_classes();
+        // the type of the argument of `run` and the exception of a handler
+        assert!(classes.contains(&"DeadCode$Action"));
+        assert!(classes.contains(&"java/lang/RuntimeException"));
+        assert!(!classes.contains(&"DeadCode"));
+    }
+
+    #[test]
+    fn constants_are_decoded_from_modified_utf8() {
+        const UNICODE: &[u8] = include_bytes!("../../../sample/Unicode.class");
+        let cf = read_class_file(UNICODE).unwrap().1;
+        assert_eq!(cf.string_constants(), vec!["a\u{0}\u{1F600}"]);
+        assert!(cf.methods.iter().any(|method| method.name == "\u{1D49C}"));
+        // only the constants which needed decoding own their value, the others borrow the class file
+        assert!(cf.methods.iter().all(|method| matches!(method.name, Cow::Owned(_)) == (method.name == "\u{1D49C}")));
+
+        // without its low surrogate the emoji cannot be decoded
+        let low = [0xED, 0xB8, 0x80];
+        let at = UNICODE.windows(3).position(|window| window == low).unwrap();
+        let mut unpaired = UNICODE.to_vec();
+        unpaired[at..at + 3].copy_from_slice(b"xyz");
+        assert!(read_class_file(&unpaired).is_err());
+        let cf = read_class_file_with(&unpaired, Utf8Decoding::Lossy).unwrap().1;
+        assert_eq!(cf.string_constants(), vec!["a\u{0}\u{fffd}xyz"]);
+    }
+
+    #[test]
+    fn code_is_parsed_on_first_use() {
+        let cf = get_cf();
+        let parsed = |method: &str| cf.methods.iter()
+            .find(|m| m.name == method).unwrap()
+            .attributes.iter()
+            .any(|attribute| match attribute {
+                Attribute::CodeAttribute(code) => code.is_parsed(),
+                _ => false
+            });
+        assert!(!parsed("main"));
+
+        let main = cf.methods.iter().find(|m| m.name == "main").unwrap();
+        assert!(main.get_code().unwrap().code_length() > 0);
+        assert!(main.get_code().unwrap().line_number_at(0).is_some());
+        assert!(parsed("main"));
+        assert!(!parsed("<init>"));
+    }
+
+    /// the class file with an attribute `name` of `info` added to the class.
+    fn with_class_attribute(name: &str, info: &[u8]) -> Vec<u8> {
+        use java::class_file::ConstantType;
+
+        let cf = get_cf();
+        let name_index = (1..=cf.constants.len() as u16)
+            .find(|&index| match cf.get_constant(index) {
+                Some(ConstantType::Utf8 { value }) => *value == name,
+                _ => false
+            })
+            .unwrap();
+        // the attributes of the class come last, after their count
+        let count_at = CLASSFILE.len() - cf.attributes.iter().map(|attribute| match attribute {
+            Attribute::GenericAttribute { info, .. } => 6 + info.len(),
+            _ => unreachable!()
+        }).sum::<usize>() - 2;
+
+        let mut bytes = CLASSFILE.to_vec();
+        bytes[count_at + 1] += 1;
+        bytes.extend_from_slice(&name_index.to_be_bytes());
+        bytes.extend_from_slice(&(info.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(info);
+        bytes
+    }
+
+    #[test]
+    fn unknown_attributes_are_kept() {
+        // javac never puts an attribute called like the class on it
+        let bytes = with_class_attribute("HelloWorld", &[1, 2, 3]);
+        let cf = read_class_file(&bytes).unwrap().1;
+        assert_eq!(cf.raw_attribute("HelloWorld"), Some(&[1u8, 2, 3][..]));
+        assert_eq!(cf.attributes.last().unwrap().name(), "HelloWorld");
+        assert_eq!(cf.get_source_file(), Some("hello_world.java"));
+
+        // a line number table which does not fit its length is kept as it is too
+        let bytes = with_class_attribute("LineNumberTable", &[0, 2, 0, 0]);
+        let cf = read_class_file(&bytes).unwrap().1;
+        assert_eq!(cf.raw_attribute("LineNumberTable"), Some(&[0u8, 2, 0, 0][..]));
+
+        // an attribute longer than the class file is an error and not a panic
+        let mut bytes = with_class_attribute("HelloWorld", &[]);
+        let length_at = bytes.len() - 4;
+        bytes[length_at..].copy_from_slice(&[0, 0, 1, 0]);
+        assert!(read_class_file(&bytes).is_err());
+    }
+
+    #[test]
+    fn malformed_class_files_are_errors() {
+        use java::class_file::ClassFormatError;
+
+        assert!(ClassFile::parse(CLASSFILE).is_ok());
+        assert!(get_cf().get_constant(0).is_none());
+
+        let mut trailing = CLASSFILE.to_vec();
+        trailing.push(0);
+        assert_eq!(ClassFile::parse(&trailing).unwrap_err(), ClassFormatError::TrailingBytes { count: 1 });
+
+        let mut invalid_utf8 = CLASSFILE.to_vec();
+        let name_at = CLASSFILE.windows(10).position(|bytes| bytes == b"HelloWorld").unwrap();
+        invalid_utf8[name_at] = 0xFF;
+        assert_eq!(ClassFile::parse(&invalid_utf8).unwrap_err(), ClassFormatError::Malformed);
+
+        let mut no_constants = CLASSFILE.to_vec();
+        no_constants[8..10].copy_from_slice(&[0, 0]);
+        assert!(ClassFile::parse(&no_constants).is_err());
+        assert!(ClassFile::parse(&CLASSFILE[..CLASSFILE.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn mutated_class_files_do_not_panic() {
+        // a few rounds of what the fuzz targets do, with a fixed xorshift sequence
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..500 {
+            let mut bytes = DEMOCLASS.to_vec();
+            for _ in 0..1 + next() % 4 {
+                let at = next() % bytes.len();
+                bytes[at] = next() as u8;
+            }
+            if next() % 8 == 0 {
+                let length = next() % bytes.len();
+                bytes.truncate(length);
+            }
+            if let Ok(class) = ClassFile::parse(&bytes) {
+                for method in class.methods.iter() {
+                    let _ = method.get_signature();
+                    let _ = method.get_code().map(|code| (code.instructions(), code.get_line_numbers()));
+                }
+            }
+        }
+    }
 
     ///////// method descriptor
     use super::*;
-    use nom::*;
-    use java::class_file::ValueType;
+    use java::class_file::{MethodDescriptor, ValueType};
+    use std::str::FromStr;
 
     #[test]
     fn test_param_list_int_int() {
@@ -337,14 +542,50 @@ mod test {
         };
     }
 
+    #[test]
+    fn test_param_list_object_int() {
+        let res = param_list(b"(Ljava/lang/String;I)");
+        let vec = vec![ValueType::Object(String::from("java/lang/String")), ValueType::Integer];
+
+        match res {
+            Ok((_, rvec)) => assert_eq!(rvec, vec),
+            _ => assert_eq!(true, false)
+        };
+    }
+
     #[test]
     fn test_method_desc_void() {
         let input = b"()V";
         let vec = vec![];
         let retvalue = ValueType::Void;
-        match method_desc(b"()V") {
+        match method_desc(input) {
             Ok((_, rvec)) => assert_eq!(rvec, (vec, retvalue)),
             _ => assert_eq!(true, false)
         };
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_method_desc_small_ints() {
+        let vec = vec![ValueType::Boolean, ValueType::Byte, ValueType::Char, ValueType::Short];
+        match method_desc(b"(ZBCS)Z") {
+            Ok((_, rvec)) => assert_eq!(rvec, (vec, ValueType::Boolean)),
+            _ => assert_eq!(true, false)
+        };
+    }
+
+    #[test]
+    fn invalid_method_descriptors_are_errors() {
+        for descriptor in ["", "I", "(I", "(I)", "(I)VV"].iter() {
+            assert!(MethodDescriptor::from_str(descriptor).is_err(), "{:?} was parsed", descriptor);
+        }
+        assert!(MethodDescriptor::from_str(&format!("({}I)V", "[".repeat(255))).is_ok());
+        assert!(MethodDescriptor::from_str(&format!("({}I)V", "[".repeat(60000))).is_err());
+    }
+
+    #[test]
+    fn test_method_desc_display() {
+        for descriptor in ["()V", "(IJ[[Ljava/lang/String;)Z", "(LCounter;I)[I"].iter() {
+            assert_eq!(MethodDescriptor::from_str(descriptor).unwrap().to_string(), *descriptor);
+        }
+    }
+}
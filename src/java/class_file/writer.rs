@@ -0,0 +1,218 @@
+use java::charset::encode_modified_utf8;
+use java::instructions::Instruction;
+use std::collections::HashMap;
+
+/// the class file version the writer emits, java 8. code without branches or handlers needs no
+/// `StackMapTable` attribute at this version.
+const VERSION: (u16, u16) = (52, 0);
+
+/// an entry of the constant pool as the writer keeps it, to find constants which are already there.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Constant {
+    Utf8(String),
+    Integer(i32),
+    Class(u16),
+    String(u16),
+    FieldRef(u16, u16),
+    MethodRef(u16, u16),
+    NameAndType(u16, u16),
+}
+
+const ACC_SUPER: u16 = 0x0020;
+
+/// writes new class files, like the classes a test generates or a tool emits. constants are
+/// added on demand and shared, methods get a `Code` attribute from their instructions.
+///
+/// ```text
+/// let mut writer = ClassWriter::new("Answer", "java/lang/Object");
+/// writer.method(0x0009, "answer", "()I", 1, 0, &[Instruction::BIPush(42), Instruction::IReturn(())]);
+/// let bytes = writer.to_bytes();
+/// ```
+#[derive(Debug)]
+pub struct ClassWriter {
+    constants: Vec<Constant>,
+    indices: HashMap<Constant, u16>,
+    access_flags: u16,
+    this_index: u16,
+    super_index: u16,
+    /// (access_flags, name_index, descriptor_index, code attribute)
+    methods: Vec<(u16, u16, u16, Vec<u8>)>,
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+impl ClassWriter {
+    /// a public class `class_name` which extends `super_name`.
+    pub fn new(class_name: &str, super_name: &str) -> ClassWriter {
+        let mut writer = ClassWriter {
+            constants: Vec::new(),
+            indices: HashMap::new(),
+            access_flags: 0x0001 | ACC_SUPER,
+            this_index: 0,
+            super_index: 0,
+            methods: Vec::new(),
+        };
+        writer.this_index = writer.class(class_name);
+        writer.super_index = writer.class(super_name);
+        writer
+    }
+
+    fn constant(&mut self, constant: Constant) -> u16 {
+        if let Some(&index) = self.indices.get(&constant) {
+            return index;
+        }
+        self.constants.push(constant.clone());
+        let index = self.constants.len() as u16;
+        self.indices.insert(constant, index);
+        index
+    }
+
+    pub fn utf8(&mut self, value: &str) -> u16 {
+        self.constant(Constant::Utf8(String::from(value)))
+    }
+
+    pub fn integer(&mut self, value: i32) -> u16 {
+        self.constant(Constant::Integer(value))
+    }
+
+    pub fn class(&mut self, class_name: &str) -> u16 {
+        let name_index = self.utf8(class_name);
+        self.constant(Constant::Class(name_index))
+    }
+
+    pub fn string(&mut self, value: &str) -> u16 {
+        let string_index = self.utf8(value);
+        self.constant(Constant::String(string_index))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+        let (name_index, descriptor_index) = (self.utf8(name), self.utf8(descriptor));
+        self.constant(Constant::NameAndType(name_index, descriptor_index))
+    }
+
+    pub fn field_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> u16 {
+        let (class_index, name_and_type_index) = (self.class(class_name), self.name_and_type(name, descriptor));
+        self.constant(Constant::FieldRef(class_index, name_and_type_index))
+    }
+
+    pub fn method_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> u16 {
+        let (class_index, name_and_type_index) = (self.class(class_name), self.name_and_type(name, descriptor));
+        self.constant(Constant::MethodRef(class_index, name_and_type_index))
+    }
+
+    /// adds a method with `code`, whose jump offsets are relative like in the class file.
+    pub fn method(&mut self, access_flags: u16, name: &str, descriptor: &str, max_stack: u16, max_locals: u16, code: &[Instruction]) -> &mut ClassWriter {
+        let mut bytes = Vec::new();
+        for instruction in code {
+            let pc = bytes.len();
+            instruction.write(pc, &mut bytes);
+        }
+
+        let mut attribute = Vec::new();
+        put_u16(&mut attribute, max_stack);
+        put_u16(&mut attribute, max_locals);
+        put_u32(&mut attribute, bytes.len() as u32);
+        attribute.extend_from_slice(&bytes);
+        // no exception table and no attributes of the code
+        put_u16(&mut attribute, 0);
+        put_u16(&mut attribute, 0);
+
+        let (name_index, descriptor_index) = (self.utf8(name), self.utf8(descriptor));
+        self.methods.push((access_flags, name_index, descriptor_index, attribute));
+        self
+    }
+
+    /// the class file.
+    pub fn to_bytes(&mut self) -> Vec<u8> {
+        let code_index = self.utf8("Code");
+        let mut out = vec![0xCA, 0xFE, 0xBA, 0xBE];
+        put_u16(&mut out, VERSION.1);
+        put_u16(&mut out, VERSION.0);
+
+        put_u16(&mut out, self.constants.len() as u16 + 1);
+        for constant in self.constants.iter() {
+            match constant {
+                Constant::Utf8(value) => {
+                    let bytes = encode_modified_utf8(value);
+                    out.push(1);
+                    put_u16(&mut out, bytes.len() as u16);
+                    out.extend_from_slice(&bytes);
+                },
+                Constant::Integer(value) => {
+                    out.push(3);
+                    out.extend_from_slice(&value.to_be_bytes());
+                },
+                Constant::Class(name_index) => {
+                    out.push(7);
+                    put_u16(&mut out, *name_index);
+                },
+                Constant::String(string_index) => {
+                    out.push(8);
+                    put_u16(&mut out, *string_index);
+                },
+                Constant::FieldRef(class_index, name_and_type_index) | Constant::MethodRef(class_index, name_and_type_index) => {
+                    out.push(if let Constant::FieldRef(..) = constant { 9 } else { 10 });
+                    put_u16(&mut out, *class_index);
+                    put_u16(&mut out, *name_and_type_index);
+                },
+                Constant::NameAndType(name_index, descriptor_index) => {
+                    out.push(12);
+                    put_u16(&mut out, *name_index);
+                    put_u16(&mut out, *descriptor_index);
+                }
+            }
+        }
+
+        put_u16(&mut out, self.access_flags);
+        put_u16(&mut out, self.this_index);
+        put_u16(&mut out, self.super_index);
+        // no interfaces and no fields
+        put_u16(&mut out, 0);
+        put_u16(&mut out, 0);
+
+        put_u16(&mut out, self.methods.len() as u16);
+        for (access_flags, name_index, descriptor_index, code) in self.methods.iter() {
+            put_u16(&mut out, *access_flags);
+            put_u16(&mut out, *name_index);
+            put_u16(&mut out, *descriptor_index);
+            put_u16(&mut out, 1);
+            put_u16(&mut out, code_index);
+            put_u32(&mut out, code.len() as u32);
+            out.extend_from_slice(code);
+        }
+        // no attributes of the class
+        put_u16(&mut out, 0);
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::ClassFile;
+    use java::runtime::{LocalVariable, Runtime, StackValue};
+    use super::*;
+
+    #[test]
+    fn written_classes_can_be_parsed_and_run() {
+        let mut writer = ClassWriter::new("Generated", "java/lang/Object");
+        let big = writer.integer(100_000);
+        writer.method(0x0009, "add", "(II)I", 2, 2, &[Instruction::ILoad0(()), Instruction::ILoad1(()), Instruction::IAdd(()), Instruction::IReturn(())])
+            .method(0x0009, "big", "()I", 1, 0, &[Instruction::LDC(big as u8), Instruction::IReturn(())]);
+        let bytes = writer.to_bytes();
+
+        let class = ClassFile::parse(&bytes).unwrap();
+        assert_eq!(class.get_class_name(), "Generated");
+        assert_eq!(class.get_super_class_name(), Some("java/lang/Object"));
+        assert_eq!(class.methods.iter().map(|method| &method.descriptor).collect::<Vec<_>>(), vec!["(II)I", "()I"]);
+
+        let mut rt = Runtime::builder().build(class);
+        assert_eq!(rt.invoke_static("Generated", "add", "(II)I", vec![LocalVariable::Integer(2), LocalVariable::Integer(3)]).unwrap(), Some(StackValue::Integer(5)));
+        assert_eq!(rt.invoke_static("Generated", "big", "()I", vec![]).unwrap(), Some(StackValue::Integer(100_000)));
+    }
+}
@@ -0,0 +1,614 @@
+//! a small interpreter for targets without `std`, e.g. microcontrollers. it only needs `core` and
+//! `alloc` and runs static methods on ints, longs and strings: arithmetic, comparisons and branches,
+//! local variables, static fields and calls and `System.out.println`. the host provides the
+//! classes through a `ClassSource` and the output through a `Console`.
+//!
+//! ```text
+//! let classes: &[(&str, &[u8])] = &[("Blink", include_bytes!("Blink.class"))];
+//! let mut interpreter = Interpreter::new(classes, Serial::new());
+//! interpreter.invoke_static("Blink", "main", "()V", &[])?;
+//! ```
+//!
+//! everything else, objects, arrays, exceptions and the class library, needs the `Runtime` of
+//! `java::runtime`, which needs `std`.
+
+#[cfg(not(feature = "std"))]
+use prelude::*;
+use java::class_file::{ClassFile, ClassFormatError, ConstantType, MethodDescriptor};
+use java::instructions::Instruction;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// how deep static calls may nest before a `StackOverflowError`, the interpreter recurses on the
+/// stack of the host for every call.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// where the interpreter gets the class files from, e.g. flash memory or tables compiled into the
+/// firmware. the classes borrow from their bytes for as long as the interpreter lives.
+pub trait ClassSource<'a> {
+    /// the bytes of the class file of `class_name`, like `java/lang/Math`. `None` if there is none.
+    fn class_bytes(&mut self, class_name: &str) -> Option<&'a [u8]>;
+}
+
+/// a table of class names and their class files.
+impl<'a> ClassSource<'a> for &'a [(&'a str, &'a [u8])] {
+    fn class_bytes(&mut self, class_name: &str) -> Option<&'a [u8]> {
+        self.iter().find(|&&(name, _)| name == class_name).map(|&(_, bytes)| bytes)
+    }
+}
+
+/// where `System.out` and `System.err` print to, e.g. a serial port.
+pub trait Console {
+    fn write_out(&mut self, text: &str);
+
+    fn write_err(&mut self, text: &str) {
+        self.write_out(text)
+    }
+}
+
+/// collects everything printed, e.g. for tests.
+impl Console for String {
+    fn write_out(&mut self, text: &str) {
+        self.push_str(text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Out,
+    Err,
+}
+
+/// a value on the operand stack or in a local variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Null,
+    /// a `java.lang.String`, see `Interpreter::string`.
+    Str(usize),
+    /// `System.out` or `System.err`.
+    PrintStream(Stream),
+}
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum InterpreterError {
+    #[fail(display = "class not found: {}", class_name)]
+    ClassNotFound { class_name: String },
+    #[fail(display = "malformed class {}: {}", class_name, error)]
+    ClassFormat { class_name: String, error: ClassFormatError },
+    #[fail(display = "method not found: {}.{}{}", class_name, method_name, descriptor)]
+    MethodNotFound { class_name: String, method_name: String, descriptor: String },
+    #[fail(display = "instruction {} is not supported by the embedded interpreter", instruction)]
+    UnsupportedInstruction { instruction: &'static str },
+    #[fail(display = "invalid bytecode in {}", message)]
+    InvalidBytecode { message: String },
+    /// the jvm would throw `class_name`, which the embedded interpreter cannot catch.
+    #[fail(display = "uncaught exception {}: {}", class_name, message)]
+    Exception { class_name: &'static str, message: String },
+}
+
+/// the instructions of a method with their pc.
+type Code = Rc<[(usize, Instruction)]>;
+
+/// runs static methods single-threaded, without `std`.
+pub struct Interpreter<'a, S, C> {
+    source: S,
+    console: C,
+    classes: Vec<ClassFile<'a>>,
+    /// the instructions of the methods which ran, by class and method index.
+    code: BTreeMap<(usize, usize), Code>,
+    statics: BTreeMap<(String, String), Value>,
+    strings: Vec<String>,
+    depth: usize,
+    max_depth: usize,
+}
+
+fn invalid<T>(message: &str) -> Result<T, InterpreterError> {
+    Err(InterpreterError::InvalidBytecode { message: String::from(message) })
+}
+
+fn int(value: Option<Value>) -> Result<i32, InterpreterError> {
+    match value {
+        Some(Value::Int(value)) => Ok(value),
+        _ => invalid("expected an int")
+    }
+}
+
+fn long(value: Option<Value>) -> Result<i64, InterpreterError> {
+    match value {
+        Some(Value::Long(value)) => Ok(value),
+        _ => invalid("expected a long")
+    }
+}
+
+/// the value a static field has before it is assigned, by its descriptor.
+fn default_value(descriptor: &str) -> Value {
+    match descriptor {
+        "J" => Value::Long(0),
+        "I" | "Z" | "B" | "C" | "S" => Value::Int(0),
+        _ => Value::Null
+    }
+}
+
+impl<'a, S: ClassSource<'a>, C: Console> Interpreter<'a, S, C> {
+    pub fn new(source: S, console: C) -> Interpreter<'a, S, C> {
+        Interpreter {
+            source,
+            console,
+            classes: Vec::new(),
+            code: BTreeMap::new(),
+            statics: BTreeMap::new(),
+            strings: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// limits how deep calls may nest, `DEFAULT_MAX_DEPTH` unless set.
+    pub fn max_depth(mut self, max_depth: usize) -> Interpreter<'a, S, C> {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn console(&self) -> &C {
+        &self.console
+    }
+
+    /// the value of a string returned by a method.
+    pub fn string(&self, value: Value) -> Option<&str> {
+        match value {
+            Value::Str(index) => self.strings.get(index).map(String::as_str),
+            _ => None
+        }
+    }
+
+    /// the string with the given value, to pass it to `invoke_static`.
+    pub fn intern(&mut self, value: &str) -> Value {
+        match self.strings.iter().position(|string| string == value) {
+            Some(index) => Value::Str(index),
+            None => {
+                self.strings.push(String::from(value));
+                Value::Str(self.strings.len() - 1)
+            }
+        }
+    }
+
+    /// runs the static method `method_name` with the given `descriptor` of `class_name`, loading
+    /// classes from the source as they are used. returns `None` for void methods.
+    pub fn invoke_static(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[Value]) -> Result<Option<Value>, InterpreterError> {
+        let class = self.load(class_name)?;
+        let method = self.classes[class].methods.iter()
+            .position(|method| method.name == method_name && method.descriptor == descriptor)
+            .ok_or_else(|| InterpreterError::MethodNotFound { class_name: String::from(class_name), method_name: String::from(method_name), descriptor: String::from(descriptor) })?;
+        self.execute(class, method, arguments)
+    }
+
+    /// the index of the class `class_name`, which is loaded and initialized on first use.
+    fn load(&mut self, class_name: &str) -> Result<usize, InterpreterError> {
+        if let Some(index) = self.classes.iter().position(|class| class.get_class_name() == class_name) {
+            return Ok(index);
+        }
+
+        let bytes = self.source.class_bytes(class_name).ok_or_else(|| InterpreterError::ClassNotFound { class_name: String::from(class_name) })?;
+        let class = ClassFile::parse(bytes).map_err(|error| InterpreterError::ClassFormat { class_name: String::from(class_name), error })?;
+        self.classes.push(class);
+        let index = self.classes.len() - 1;
+        for (_, name, descriptor) in self.classes[index].field_signatures() {
+            self.statics.insert((String::from(class_name), String::from(name)), default_value(descriptor));
+        }
+        if let Some(initializer) = self.classes[index].methods.iter().position(|method| method.name == "<clinit>") {
+            self.execute(index, initializer, &[])?;
+        }
+        Ok(index)
+    }
+
+    fn instructions(&mut self, class: usize, method: usize) -> Result<Code, InterpreterError> {
+        if let Some(instructions) = self.code.get(&(class, method)) {
+            return Ok(instructions.clone());
+        }
+
+        let code = match self.classes[class].methods[method].get_code() {
+            Some(code) => code,
+            None => return invalid("a method without code")
+        };
+        let instructions: Code = match code.instructions_with_pc() {
+            Ok(instructions) => instructions.into(),
+            Err(_) => return invalid("malformed code")
+        };
+        self.code.insert((class, method), instructions.clone());
+        Ok(instructions)
+    }
+
+    /// the class, name and descriptor of the field or method constant `index` of `class`.
+    fn member(&self, class: usize, index: u16) -> Result<(String, String, String), InterpreterError> {
+        let class = &self.classes[class];
+        let (class_index, name_and_type_index) = match class.get_constant(index) {
+            Some(ConstantType::FieldRef { class_index, name_and_type_index }) |
+            Some(ConstantType::MethodRef { class_index, name_and_type_index }) => (*class_index, *name_and_type_index),
+            _ => return invalid("expected a field or method constant")
+        };
+        match (class.get_class_name_at(class_index), class.get_name_and_type(name_and_type_index)) {
+            (Some(class_name), Some((name, descriptor))) => Ok((String::from(class_name), String::from(name), String::from(descriptor))),
+            _ => invalid("malformed member constant")
+        }
+    }
+
+    fn constant(&mut self, class: usize, index: u16) -> Result<Value, InterpreterError> {
+        let value = match self.classes[class].get_constant(index) {
+            Some(ConstantType::Integer { value }) => Value::Int(*value),
+            Some(ConstantType::Long { value }) => Value::Long(*value),
+            Some(ConstantType::String { string_index }) => match self.classes[class].get_constant(*string_index) {
+                Some(ConstantType::Utf8 { value }) => {
+                    let value = String::from(&**value);
+                    self.intern(&value)
+                }
+                _ => return invalid("malformed string constant")
+            },
+            Some(ConstantType::Float { .. }) | Some(ConstantType::Double { .. }) => return Err(InterpreterError::UnsupportedInstruction { instruction: "LDC" }),
+            _ => return invalid("expected a loadable constant")
+        };
+        Ok(value)
+    }
+
+    fn print(&mut self, stream: Stream, text: &str) {
+        match stream {
+            Stream::Out => self.console.write_out(text),
+            Stream::Err => self.console.write_err(text)
+        }
+    }
+
+    /// `PrintStream.print` and `println`, the only methods of the class library there are.
+    fn invoke_print_stream(&mut self, method_name: &str, descriptor: &str, stack: &mut Vec<Value>) -> Result<(), InterpreterError> {
+        let argument = match descriptor {
+            "()V" => None,
+            _ => Some(stack.pop())
+        };
+        let stream = match stack.pop() {
+            Some(Value::PrintStream(stream)) => stream,
+            Some(Value::Null) => return Err(InterpreterError::Exception { class_name: "java/lang/NullPointerException", message: String::from(method_name) }),
+            _ => return invalid("expected a print stream")
+        };
+        let mut text = match (descriptor, argument) {
+            ("()V", _) => String::new(),
+            ("(I)V", Some(Some(Value::Int(value)))) => format!("{}", value),
+            ("(J)V", Some(Some(Value::Long(value)))) => format!("{}", value),
+            ("(Z)V", Some(Some(Value::Int(value)))) => String::from(if value != 0 { "true" } else { "false" }),
+            ("(C)V", Some(Some(Value::Int(value)))) => ::std::char::from_u32(value as u32 & 0xffff).unwrap_or('\u{fffd}').to_string(),
+            ("(Ljava/lang/String;)V", Some(Some(Value::Null))) => String::from("null"),
+            ("(Ljava/lang/String;)V", Some(Some(value))) => String::from(self.string(value).unwrap_or_default()),
+            _ => return Err(InterpreterError::MethodNotFound { class_name: String::from("java/io/PrintStream"), method_name: String::from(method_name), descriptor: String::from(descriptor) })
+        };
+        match method_name {
+            "println" => text.push('\n'),
+            "print" => (),
+            _ => return Err(InterpreterError::MethodNotFound { class_name: String::from("java/io/PrintStream"), method_name: String::from(method_name), descriptor: String::from(descriptor) })
+        }
+        self.print(stream, &text);
+        Ok(())
+    }
+
+    fn execute(&mut self, class: usize, method: usize, arguments: &[Value]) -> Result<Option<Value>, InterpreterError> {
+        if self.depth >= self.max_depth {
+            return Err(InterpreterError::Exception { class_name: "java/lang/StackOverflowError", message: String::new() });
+        }
+        self.depth += 1;
+        let result = self.run(class, method, arguments);
+        self.depth -= 1;
+        result
+    }
+
+    fn run(&mut self, class: usize, method: usize, arguments: &[Value]) -> Result<Option<Value>, InterpreterError> {
+        let instructions = self.instructions(class, method)?;
+        let max_locals = self.classes[class].methods[method].get_code().map_or(0, |code| usize::from(code.max_locals));
+        // a long takes two slots, the second one stays unused
+        let mut locals = Vec::with_capacity(max_locals);
+        for argument in arguments {
+            locals.push(Some(*argument));
+            if let Value::Long(_) = argument {
+                locals.push(None);
+            }
+        }
+        if locals.len() > max_locals {
+            return invalid("too many arguments");
+        }
+        locals.resize(max_locals, None);
+        let mut stack: Vec<Value> = Vec::new();
+        let mut next = 0;
+
+        loop {
+            let (pc, instruction) = match instructions.get(next) {
+                Some(&(pc, ref instruction)) => (pc, instruction),
+                None => return invalid("fell off the end of the code")
+            };
+            next += 1;
+
+            if let Some(offset) = instruction.branch_offset() {
+                let taken = match instruction {
+                    Instruction::Goto(_) => true,
+                    Instruction::Ifeq(_) => int(stack.pop())? == 0,
+                    Instruction::Ifne(_) => int(stack.pop())? != 0,
+                    Instruction::Iflt(_) => int(stack.pop())? < 0,
+                    Instruction::Ifge(_) => int(stack.pop())? >= 0,
+                    Instruction::Ifgt(_) => int(stack.pop())? > 0,
+                    Instruction::Ifle(_) => int(stack.pop())? <= 0,
+                    Instruction::IfNull(_) => stack.pop() == Some(Value::Null),
+                    Instruction::IfNonNull(_) => stack.pop() != Some(Value::Null),
+                    Instruction::IfACmpEQ(_) | Instruction::IfACmpNE(_) => {
+                        let (rh, lh) = (stack.pop(), stack.pop());
+                        (lh == rh) == (instruction.opcode() == 0xa5)
+                    }
+                    _ => {
+                        let (rh, lh) = (int(stack.pop())?, int(stack.pop())?);
+                        match instruction {
+                            Instruction::IfICmpEQ(_) => lh == rh,
+                            Instruction::IfICmpNE(_) => lh != rh,
+                            Instruction::IfICmpLT(_) => lh < rh,
+                            Instruction::IfICmpGE(_) => lh >= rh,
+                            Instruction::IfICmpGT(_) => lh > rh,
+                            _ => lh <= rh
+                        }
+                    }
+                };
+                if taken {
+                    let target = pc as i64 + offset;
+                    next = match instructions.binary_search_by_key(&target, |&(pc, _)| pc as i64) {
+                        Ok(index) => index,
+                        Err(_) => return invalid("a jump into the middle of an instruction")
+                    };
+                }
+                continue;
+            }
+
+            match *instruction {
+                Instruction::NOOP(()) => (),
+                Instruction::AConstNull(()) => stack.push(Value::Null),
+                Instruction::IConstm1(()) => stack.push(Value::Int(-1)),
+                Instruction::IConst0(()) => stack.push(Value::Int(0)),
+                Instruction::IConst1(()) => stack.push(Value::Int(1)),
+                Instruction::IConst2(()) => stack.push(Value::Int(2)),
+                Instruction::IConst3(()) => stack.push(Value::Int(3)),
+                Instruction::IConst4(()) => stack.push(Value::Int(4)),
+                Instruction::IConst5(()) => stack.push(Value::Int(5)),
+                Instruction::LConst0(()) => stack.push(Value::Long(0)),
+                Instruction::LConst1(()) => stack.push(Value::Long(1)),
+                Instruction::BIPush(value) => stack.push(Value::Int(i32::from(value as i8))),
+                Instruction::SIPush(value) => stack.push(Value::Int(i32::from(value as i16))),
+                Instruction::LDC(index) => stack.push(self.constant(class, u16::from(index))?),
+                Instruction::LDCW(index) | Instruction::LDC2W(index) => stack.push(self.constant(class, index)?),
+
+                Instruction::ILoad(slot) | Instruction::LLoad(slot) | Instruction::ALoad(slot) => stack.push(load(&locals, usize::from(slot))?),
+                Instruction::ILoad0(()) | Instruction::LLoad0(()) | Instruction::ALoad0(()) => stack.push(load(&locals, 0)?),
+                Instruction::ILoad1(()) | Instruction::LLoad1(()) | Instruction::ALoad1(()) => stack.push(load(&locals, 1)?),
+                Instruction::ILoad2(()) | Instruction::LLoad2(()) | Instruction::ALoad2(()) => stack.push(load(&locals, 2)?),
+                Instruction::ILoad3(()) | Instruction::LLoad3(()) | Instruction::ALoad3(()) => stack.push(load(&locals, 3)?),
+                Instruction::IStore(slot) | Instruction::LStore(slot) | Instruction::AStore(slot) => store(&mut locals, usize::from(slot), stack.pop())?,
+                Instruction::IStore0(()) | Instruction::LStore0(()) | Instruction::AStore0(()) => store(&mut locals, 0, stack.pop())?,
+                Instruction::IStore1(()) | Instruction::LStore1(()) | Instruction::AStore1(()) => store(&mut locals, 1, stack.pop())?,
+                Instruction::IStore2(()) | Instruction::LStore2(()) | Instruction::AStore2(()) => store(&mut locals, 2, stack.pop())?,
+                Instruction::IStore3(()) | Instruction::LStore3(()) | Instruction::AStore3(()) => store(&mut locals, 3, stack.pop())?,
+                Instruction::IInc(operands) => {
+                    let slot = usize::from(operands >> 8);
+                    let value = int(Some(load(&locals, slot)?))?.wrapping_add(i32::from(operands as u8 as i8));
+                    store(&mut locals, slot, Some(Value::Int(value)))?;
+                }
+
+                Instruction::Pop(()) => { stack.pop(); }
+                Instruction::Dup(()) => match stack.last() {
+                    Some(&value) => stack.push(value),
+                    None => return invalid("dup on an empty stack")
+                },
+
+                Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) | Instruction::IDiv(()) | Instruction::IRem(()) |
+                Instruction::IShl(()) | Instruction::IShr(()) | Instruction::IUSHR(()) | Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(()) => {
+                    let (rh, lh) = (int(stack.pop())?, int(stack.pop())?);
+                    let value = match *instruction {
+                        Instruction::IAdd(()) => lh.wrapping_add(rh),
+                        Instruction::ISub(()) => lh.wrapping_sub(rh),
+                        Instruction::IMul(()) => lh.wrapping_mul(rh),
+                        Instruction::IDiv(()) | Instruction::IRem(()) if rh == 0 => return Err(division_by_zero()),
+                        Instruction::IDiv(()) => lh.wrapping_div(rh),
+                        Instruction::IRem(()) => lh.wrapping_rem(rh),
+                        Instruction::IShl(()) => lh.wrapping_shl(rh as u32),
+                        Instruction::IShr(()) => lh.wrapping_shr(rh as u32),
+                        Instruction::IUSHR(()) => (lh as u32).wrapping_shr(rh as u32) as i32,
+                        Instruction::IAnd(()) => lh & rh,
+                        Instruction::IOr(()) => lh | rh,
+                        _ => lh ^ rh
+                    };
+                    stack.push(Value::Int(value));
+                }
+                Instruction::LAdd(()) | Instruction::LSub(()) | Instruction::LMul(()) | Instruction::LDiv(()) | Instruction::LRem(()) |
+                Instruction::LAnd(()) | Instruction::LOr(()) | Instruction::LXor(()) => {
+                    let (rh, lh) = (long(stack.pop())?, long(stack.pop())?);
+                    let value = match *instruction {
+                        Instruction::LAdd(()) => lh.wrapping_add(rh),
+                        Instruction::LSub(()) => lh.wrapping_sub(rh),
+                        Instruction::LMul(()) => lh.wrapping_mul(rh),
+                        Instruction::LDiv(()) | Instruction::LRem(()) if rh == 0 => return Err(division_by_zero()),
+                        Instruction::LDiv(()) => lh.wrapping_div(rh),
+                        Instruction::LRem(()) => lh.wrapping_rem(rh),
+                        Instruction::LAnd(()) => lh & rh,
+                        Instruction::LOr(()) => lh | rh,
+                        _ => lh ^ rh
+                    };
+                    stack.push(Value::Long(value));
+                }
+                // the distance of a long shift is an int
+                Instruction::LShl(()) | Instruction::LShr(()) | Instruction::LUSHR(()) => {
+                    let (distance, value) = (int(stack.pop())? as u32, long(stack.pop())?);
+                    stack.push(Value::Long(match *instruction {
+                        Instruction::LShl(()) => value.wrapping_shl(distance),
+                        Instruction::LShr(()) => value.wrapping_shr(distance),
+                        _ => (value as u64).wrapping_shr(distance) as i64
+                    }));
+                }
+                Instruction::INeg(()) => {
+                    let value = int(stack.pop())?;
+                    stack.push(Value::Int(value.wrapping_neg()));
+                }
+                Instruction::LNeg(()) => {
+                    let value = long(stack.pop())?;
+                    stack.push(Value::Long(value.wrapping_neg()));
+                }
+                Instruction::LCmp(()) => {
+                    let (rh, lh) = (long(stack.pop())?, long(stack.pop())?);
+                    stack.push(Value::Int(lh.cmp(&rh) as i32));
+                }
+                Instruction::I2L(()) => {
+                    let value = int(stack.pop())?;
+                    stack.push(Value::Long(i64::from(value)));
+                }
+                Instruction::L2I(()) => {
+                    let value = long(stack.pop())?;
+                    stack.push(Value::Int(value as i32));
+                }
+                Instruction::I2B(()) => {
+                    let value = int(stack.pop())?;
+                    stack.push(Value::Int(i32::from(value as i8)));
+                }
+                Instruction::I2C(()) => {
+                    let value = int(stack.pop())?;
+                    stack.push(Value::Int(i32::from(value as u16)));
+                }
+                Instruction::I2S(()) => {
+                    let value = int(stack.pop())?;
+                    stack.push(Value::Int(i32::from(value as i16)));
+                }
+
+                Instruction::GetStatic(index) => {
+                    let (class_name, name, _) = self.member(class, index)?;
+                    let value = match (class_name.as_str(), name.as_str()) {
+                        ("java/lang/System", "out") => Value::PrintStream(Stream::Out),
+                        ("java/lang/System", "err") => Value::PrintStream(Stream::Err),
+                        _ => {
+                            self.load(&class_name)?;
+                            match self.statics.get(&(class_name, name)) {
+                                Some(&value) => value,
+                                None => return invalid("no such static field")
+                            }
+                        }
+                    };
+                    stack.push(value);
+                }
+                Instruction::PutStatic(index) => {
+                    let (class_name, name, _) = self.member(class, index)?;
+                    self.load(&class_name)?;
+                    let value = match stack.pop() {
+                        Some(value) => value,
+                        None => return invalid("putstatic on an empty stack")
+                    };
+                    match self.statics.get_mut(&(class_name, name)) {
+                        Some(field) => *field = value,
+                        None => return invalid("no such static field")
+                    }
+                }
+                Instruction::InvokeStatic(index) => {
+                    let (class_name, name, descriptor) = self.member(class, index)?;
+                    let signature = match MethodDescriptor::from_str(&descriptor) {
+                        Ok(signature) => signature,
+                        Err(_) => return invalid("malformed method descriptor")
+                    };
+                    if stack.len() < signature.arguments.len() {
+                        return invalid("too few arguments on the stack");
+                    }
+                    let arguments = stack.split_off(stack.len() - signature.arguments.len());
+                    if let Some(value) = self.invoke_static(&class_name, &name, &descriptor, &arguments)? {
+                        stack.push(value);
+                    }
+                }
+                Instruction::InvokeVirtual(index) => {
+                    let (class_name, name, descriptor) = self.member(class, index)?;
+                    if class_name != "java/io/PrintStream" {
+                        return Err(InterpreterError::UnsupportedInstruction { instruction: instruction.name() });
+                    }
+                    self.invoke_print_stream(&name, &descriptor, &mut stack)?;
+                }
+
+                Instruction::IReturn(()) | Instruction::LReturn(()) | Instruction::AReturn(()) => return match stack.pop() {
+                    Some(value) => Ok(Some(value)),
+                    None => invalid("return on an empty stack")
+                },
+                Instruction::Return(()) => return Ok(None),
+                _ => return Err(InterpreterError::UnsupportedInstruction { instruction: instruction.name() })
+            }
+        }
+    }
+}
+
+fn load(locals: &[Option<Value>], slot: usize) -> Result<Value, InterpreterError> {
+    match locals.get(slot) {
+        Some(&Some(value)) => Ok(value),
+        _ => invalid("read of an undefined local variable")
+    }
+}
+
+fn store(locals: &mut [Option<Value>], slot: usize, value: Option<Value>) -> Result<(), InterpreterError> {
+    let value = match value {
+        Some(value) => value,
+        None => return invalid("store from an empty stack")
+    };
+    let slots = if let Value::Long(_) = value { 2 } else { 1 };
+    if slot + slots > locals.len() {
+        return invalid("local variable out of range");
+    }
+    locals[slot] = Some(value);
+    if slots == 2 {
+        locals[slot + 1] = None;
+    }
+    Ok(())
+}
+
+fn division_by_zero() -> InterpreterError {
+    InterpreterError::Exception { class_name: "java/lang/ArithmeticException", message: String::from("/ by zero") }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CLASSES: &[(&str, &[u8])] = &[("Embedded", include_bytes!("../../sample/Embedded.class"))];
+
+    fn interpreter() -> Interpreter<'static, &'static [(&'static str, &'static [u8])], String> {
+        Interpreter::new(CLASSES, String::new())
+    }
+
+    #[test]
+    fn it_runs_static_methods_without_std() {
+        let mut interpreter = interpreter();
+        assert_eq!(interpreter.invoke_static("Embedded", "fib", "(I)I", &[Value::Int(10)]), Ok(Some(Value::Int(55))));
+        assert_eq!(interpreter.invoke_static("Embedded", "sum", "(I)I", &[Value::Int(5)]), Ok(Some(Value::Int(10))));
+        assert_eq!(interpreter.invoke_static("Embedded", "scale", "(IJ)J", &[Value::Int(3), Value::Long(1 << 40)]), Ok(Some(Value::Long(3 << 40 | 1))));
+        // the static initializer ran when the class was loaded
+        assert_eq!(interpreter.invoke_static("Embedded", "bump", "()I", &[]), Ok(Some(Value::Int(42))));
+        assert_eq!(interpreter.invoke_static("Embedded", "bump", "()I", &[]), Ok(Some(Value::Int(44))));
+
+        let greeting = interpreter.invoke_static("Embedded", "greeting", "()Ljava/lang/String;", &[]).unwrap().unwrap();
+        assert_eq!(interpreter.string(greeting), Some("hello from a microcontroller"));
+    }
+
+    #[test]
+    fn it_prints_to_the_console() {
+        let mut interpreter = interpreter();
+        assert_eq!(interpreter.invoke_static("Embedded", "report", "(I)V", &[Value::Int(7)]), Ok(None));
+        assert_eq!(interpreter.console(), "hello from a microcontroller\n13\ndone\n");
+    }
+
+    #[test]
+    fn it_reports_what_it_cannot_run() {
+        let mut interpreter = interpreter().max_depth(16);
+        match interpreter.invoke_static("Embedded", "divide", "(II)I", &[Value::Int(1), Value::Int(0)]) {
+            Err(InterpreterError::Exception { class_name: "java/lang/ArithmeticException", .. }) => (),
+            other => panic!("expected an ArithmeticException, got {:?}", other)
+        }
+        match interpreter.invoke_static("Embedded", "recurse", "(I)I", &[Value::Int(0)]) {
+            Err(InterpreterError::Exception { class_name: "java/lang/StackOverflowError", .. }) => (),
+            other => panic!("expected a StackOverflowError, got {:?}", other)
+        }
+        match interpreter.invoke_static("Missing", "main", "()V", &[]) {
+            Err(InterpreterError::ClassNotFound { ref class_name }) if class_name == "Missing" => (),
+            other => panic!("expected a missing class, got {:?}", other)
+        }
+    }
+}
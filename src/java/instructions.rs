@@ -1,4 +1,6 @@
 use nom::*;
+#[cfg(not(feature = "std"))]
+use prelude::*;
 
 #[derive(Debug, Fail)]
 pub enum ReadInstructionError<P> {
@@ -11,8 +13,10 @@ pub enum ReadInstructionError<P> {
 }
 
 macro_rules! instruction {
-    ( $( $num:pat => [ $($parser:tt)* ] => $name:ident ( $($a:ident: $t:ty ),* ) ),* ) => {
-          #[derive(Debug)]
+    ( $( $num:literal => [ $($parser:tt)* ] => $name:ident ( $($a:ident: $t:ty ),* ) ),* ) => {
+          // the operands of an instruction with a single one are in parentheses too
+          #[allow(unused_parens)]
+          #[derive(Debug, Clone, PartialEq)]
           pub enum Instruction {
             $(
                 $name ( ( $($t),* ) )
@@ -20,7 +24,17 @@ macro_rules! instruction {
           }
 
           impl Instruction {
+                /// every opcode with the name of its variant, in the order of the opcodes.
+                pub const OPCODES: &'static [(u8, &'static str)] = &[ $( ($num, stringify!($name)) ),* ];
+
                 pub fn read_all(input: &[u8]) -> Result<Vec<Instruction>, ReadInstructionError<&[u8]>> {
+                    Instruction::read_all_with_offsets(input).map(|instructions| {
+                        instructions.into_iter().map(|(_, ins)| ins).collect()
+                    })
+                }
+
+                /// reads all instructions together with their offset (pc) in the code array.
+                pub fn read_all_with_offsets(input: &[u8]) -> Result<Vec<(usize, Instruction)>, ReadInstructionError<&[u8]>> {
                     let mut vec = Vec::new();
                     let mut remaining = &input[..];
                     loop {
@@ -28,9 +42,9 @@ macro_rules! instruction {
                             break;
                         }
 
-                        match Instruction::read(remaining) {
+                        match Instruction::read(remaining, input.len() - remaining.len()) {
                             Ok((rem, ins)) => {
-                                vec.push(ins);
+                                vec.push((input.len() - remaining.len(), ins));
                                 remaining = rem;
                             },
                             Err(Err::Incomplete(_)) => return Result::Err(ReadInstructionError::ParsingIncomplete),
@@ -42,11 +56,46 @@ macro_rules! instruction {
                     return Result::Ok(vec);
                 }
 
-                fn read(input: &[u8]) -> IResult<&[u8], Instruction> {
+                /// the mnemonic of this instruction as it is named in this enum.
+                pub fn name(&self) -> &'static str {
+                    match self {
+                        $(
+                            Instruction::$name(..) => stringify!($name)
+                        ),*
+                    }
+                }
+
+                pub fn opcode(&self) -> u8 {
+                    match self {
+                        $(
+                            Instruction::$name(..) => $num
+                        ),*
+                    }
+                }
+
+                /// appends the bytes of this instruction at offset `pc` of the code array to `out`,
+                /// the inverse of reading it.
+                pub fn write(&self, pc: usize, out: &mut Vec<u8>) {
+                    out.push(self.opcode());
+                    if let Instruction::TableSwitch(_) | Instruction::LookupSwitch(_) = self {
+                        out.extend(::std::iter::repeat(0).take((4 - (pc + 1) % 4) % 4));
+                    }
+                    match self {
+                        $(
+                            Instruction::$name(operands) => operands.write(out)
+                        ),*
+                    }
+                }
+
+                /// reads the instruction at offset `pc` of the code array.
+                fn read(input: &[u8], pc: usize) -> IResult<&[u8], Instruction> {
                     match be_u8(input) {
                         $(
-                            Ok((rem, $num)) => match do_parse!(rem, $($parser)* ) {
-                                Ok((rem, ins)) => Ok((rem, Instruction::$name(ins))),
+                            Ok((rem, $num)) => match switch_padding(rem, $num, pc) {
+                                Ok((rem, _)) => match do_parse!(rem, $($parser)* ) {
+                                    Ok((rem, ins)) => Ok((rem, Instruction::$name(ins))),
+                                    Err(err) => Err(err),
+                                },
                                 Err(err) => Err(err),
                             }
                         ),*,
@@ -59,6 +108,103 @@ macro_rules! instruction {
     };
 }
 
+/// the operands of `tableswitch` and `lookupswitch` start at a multiple of four bytes from the start
+/// of the code array. skips the padding after the opcode at `pc`.
+fn switch_padding(input: &[u8], opcode: u8, pc: usize) -> IResult<&[u8], &[u8]> {
+    match opcode {
+        0xaa | 0xab => take!(input, (4 - (pc + 1) % 4) % 4),
+        _ => Ok((input, &input[..0]))
+    }
+}
+
+/// the operands of an instruction as they are written after its opcode.
+trait Operands {
+    fn write(&self, out: &mut Vec<u8>);
+}
+
+impl Operands for () {
+    fn write(&self, _out: &mut Vec<u8>) {}
+}
+
+impl Operands for u8 {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl Operands for u16 {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl Operands for u64 {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl Operands for (u16, u8) {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.0.write(out);
+        self.1.write(out);
+    }
+}
+
+impl Operands for (u16, u16) {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.0.write(out);
+        self.1.write(out);
+    }
+}
+
+impl Operands for (u16, u8, u8) {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.0.write(out);
+        self.1.write(out);
+        self.2.write(out);
+    }
+}
+
+/// default, low, high and the jump offsets of a `tableswitch`.
+impl Operands for (i32, i32, i32, Vec<i32>) {
+    fn write(&self, out: &mut Vec<u8>) {
+        for value in [self.0, self.1, self.2].iter().chain(self.3.iter()) {
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+/// the default and the match and offset pairs of a `lookupswitch`, preceded by their count.
+impl Operands for (i32, Vec<(i32, i32)>) {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.to_be_bytes());
+        out.extend_from_slice(&(self.1.len() as i32).to_be_bytes());
+        for &(key, offset) in self.1.iter() {
+            out.extend_from_slice(&key.to_be_bytes());
+            out.extend_from_slice(&offset.to_be_bytes());
+        }
+    }
+}
+
+/// the jump offsets of a `tableswitch` from `low` to `high`. a count larger than the remaining code
+/// is malformed, so nothing gets allocated for it.
+fn table_offsets(input: &[u8], low: i32, high: i32) -> IResult<&[u8], Vec<i32>> {
+    let count = i64::from(high) - i64::from(low) + 1;
+    if count < 0 || count as usize > input.len() / 4 {
+        return Err(Err::Error(error_position!(input, ErrorKind::Count)));
+    }
+    count!(input, be_i32, count as usize)
+}
+
+/// the match and offset pairs of a `lookupswitch`.
+fn lookup_pairs(input: &[u8], count: i32) -> IResult<&[u8], Vec<(i32, i32)>> {
+    if count < 0 || count as usize > input.len() / 8 {
+        return Err(Err::Error(error_position!(input, ErrorKind::Count)));
+    }
+    count!(input, pair!(be_i32, be_i32), count as usize)
+}
+
 
 instruction!(
     0x00 => [ () ] => NOOP(),
@@ -231,8 +377,8 @@ instruction!(
     0xa7 => [ a: be_u16 >> ( ( a ) ) ] => Goto( a: u16 ),
     0xa8 => [ a: be_u16 >> ( ( a ) ) ] => JSR( a: u16 ),
     0xa9 => [ a: be_u8  >> ( ( a ) ) ] => Ret( a: u8 ),
-    0xaa => [ a: be_u8  >> ( ( a ) ) ] => TableSwitch( a: u8 ),
-    0xab => [ a: be_u64 >> b: be_u64 >> ( ( a, b ) ) ] => LookupSwitch( a: u64, b: u64 ),
+    0xaa => [ a: be_i32 >> b: be_i32 >> c: be_i32 >> d: call!(table_offsets, b, c) >> ( ( a, b, c, d ) ) ] => TableSwitch( a: i32, b: i32, c: i32, d: Vec<i32> ),
+    0xab => [ a: be_i32 >> b: be_i32 >> c: call!(lookup_pairs, b) >> ( ( a, c ) ) ] => LookupSwitch( a: i32, b: Vec<(i32, i32)> ),
     0xac => [ () ] => IReturn(),
     0xad => [ () ] => LReturn(),
     0xae => [ () ] => FReturn(),
@@ -246,8 +392,8 @@ instruction!(
     0xb6 => [ a: be_u16 >> ( ( a ) ) ] => InvokeVirtual( a: u16 ),
     0xb7 => [ a: be_u16 >> ( ( a ) ) ] => InvokeSpecial( a: u16 ),
     0xb8 => [ a: be_u16 >> ( ( a ) ) ] => InvokeStatic( a: u16 ),
-    0xb9 => [ a: be_u64 >> ( ( a ) ) ] => InvokeInterface( a: u64 ),
-    0xba => [ a: be_u64 >> ( ( a ) ) ] => InvokeDynamic( a: u64),
+    0xb9 => [ a: be_u16 >> b: be_u8 >> c: be_u8 >> ( ( a, b, c ) ) ] => InvokeInterface( a: u16, b: u8, c: u8 ),
+    0xba => [ a: be_u16 >> b: be_u16 >> ( ( a, b ) ) ] => InvokeDynamic( a: u16, b: u16 ),
     0xbb => [ a: be_u16 >> ( ( a ) ) ] => New( a: u16 ),
     0xbc => [ a: be_u8 >> ( ( a ) ) ] => NewArray( a: u8 ),
     0xbd => [ a: be_u16 >> ( ( a ) ) ] => AAewArray( a: u16 ),
@@ -266,4 +412,89 @@ instruction!(
     0xca => [ () ] => Breakpoint(),
     0xfe => [ () ] => ImpDep1(),
     0xff => [ () ] => ImpDep2()
-);
\ No newline at end of file
+);
+impl Instruction {
+    /// the signed offset of a branch relative to its own pc.
+    pub fn branch_offset(&self) -> Option<i64> {
+        match self {
+            Instruction::Ifeq(offset) | Instruction::Ifne(offset) | Instruction::Iflt(offset) | Instruction::Ifge(offset) |
+            Instruction::Ifgt(offset) | Instruction::Ifle(offset) | Instruction::IfICmpEQ(offset) | Instruction::IfICmpNE(offset) |
+            Instruction::IfICmpLT(offset) | Instruction::IfICmpGE(offset) | Instruction::IfICmpGT(offset) | Instruction::IfICmpLE(offset) |
+            Instruction::IfACmpEQ(offset) | Instruction::IfACmpNE(offset) | Instruction::IfNull(offset) | Instruction::IfNonNull(offset) |
+            Instruction::Goto(offset) => Some(i64::from(*offset as i16)),
+            _ => None
+        }
+    }
+
+    /// the signed offsets of a `tableswitch` or `lookupswitch` relative to its own pc, the default first
+    /// and then one for each case.
+    pub fn switch_offsets(&self) -> Option<Vec<i64>> {
+        match self {
+            Instruction::TableSwitch((default, _, _, offsets)) =>
+                Some(Some(default).into_iter().chain(offsets.iter()).map(|&offset| i64::from(offset)).collect()),
+            Instruction::LookupSwitch((default, pairs)) =>
+                Some(Some(*default).into_iter().chain(pairs.iter().map(|&(_, offset)| offset)).map(i64::from).collect()),
+            _ => None
+        }
+    }
+
+    /// the constant pool entry the instruction refers to.
+    pub fn constant_index(&self) -> Option<u16> {
+        match self {
+            Instruction::LDC(index) => Some(u16::from(*index)),
+            Instruction::LDCW(index) | Instruction::LDC2W(index) | Instruction::GetStatic(index) | Instruction::PutStatic(index) |
+            Instruction::GetField(index) | Instruction::PutField(index) | Instruction::InvokeVirtual(index) | Instruction::InvokeSpecial(index) |
+            Instruction::InvokeStatic(index) | Instruction::InvokeInterface((index, _, _)) | Instruction::InvokeDynamic((index, _)) |
+            Instruction::New(index) | Instruction::AAewArray(index) | Instruction::CheckCast(index) | Instruction::InstanceOf(index) |
+            Instruction::MultianeWArray((index, _)) => Some(*index),
+            _ => None
+        }
+    }
+
+    /// the name of the instruction in the jvm specification, like `iload_1` or `if_icmpeq`.
+    pub fn mnemonic(&self) -> String {
+        let special = match self {
+            Instruction::NOOP(()) => "nop",
+            Instruction::IConstm1(()) => "iconst_m1",
+            Instruction::LDCW(_) => "ldc_w",
+            Instruction::LDC2W(_) => "ldc2_w",
+            Instruction::ScALoad(()) => "saload",
+            Instruction::DupX1(()) => "dup_x1",
+            Instruction::DupX2(()) => "dup_x2",
+            Instruction::Dup2X1(()) => "dup2_x1",
+            Instruction::Dup2X2(()) => "dup2_x2",
+            Instruction::AAewArray(_) => "anewarray",
+            Instruction::GotoW(_) => "goto_w",
+            Instruction::JSRW(_) => "jsr_w",
+            _ => ""
+        };
+        if !special.is_empty() {
+            return String::from(special);
+        }
+
+        let name = self.name().to_lowercase();
+        if name.starts_with("ificmp") || name.starts_with("ifacmp") {
+            return format!("if_{}", &name[2..]);
+        }
+        // `<t>load_<n>`, `<t>store_<n>` and `<t>const_<n>`
+        let (stem, last) = name.split_at(name.len() - 1);
+        if last.chars().all(|c| c.is_ascii_digit()) && (stem.ends_with("load") || stem.ends_with("store") || stem.ends_with("const")) {
+            return format!("{}_{}", stem, last);
+        }
+        name
+    }
+
+    /// false for the instructions which never throw, like loads, stores, stack operations, integer
+    /// arithmetic without a division, branches and returns. everything else is assumed to throw.
+    pub fn can_throw(&self) -> bool {
+        !matches!(self.opcode(), 0x00..=0x11 | 0x15..=0x2d | 0x36..=0x4e | 0x57..=0x6b | 0x6e | 0x6f | 0x72..=0xb1 | 0xc6..=0xc9)
+    }
+
+    /// true if execution never continues with the next instruction.
+    pub fn ends_flow(&self) -> bool {
+        matches!(self,
+            Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::IReturn(()) | Instruction::LReturn(()) |
+            Instruction::FReturn(()) | Instruction::DReturn(()) | Instruction::AReturn(()) | Instruction::Return(()) |
+            Instruction::AThrow(()) | Instruction::TableSwitch(_) | Instruction::LookupSwitch(_))
+    }
+}
@@ -1,3 +1,8 @@
+#[cfg(feature = "std")]
+pub mod analysis;
+pub mod charset;
 pub mod class_file;
+pub mod embedded;
 pub mod instructions;
-pub mod runtime;
\ No newline at end of file
+#[cfg(feature = "std")]
+pub mod runtime;
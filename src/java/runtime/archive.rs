@@ -0,0 +1,411 @@
+use java::class_file::ClassFile;
+use java::runtime::{scan_classpath, DecodedMethod, FastMethod, Runtime, SlotType, TypeState};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8] = b"RJVMCDS2";
+
+/// the bytes of an archive file, mapped into memory where the platform allows it.
+enum Mapping {
+    #[cfg(unix)]
+    Mapped { address: *mut libc::c_void, length: usize },
+    Read(Vec<u8>),
+}
+
+// the mapping is read only and owned by this value
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    #[cfg(unix)]
+    fn open(path: &Path) -> io::Result<Mapping> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path)?;
+        let length = file.metadata()?.len() as usize;
+        if length == 0 {
+            return Ok(Mapping::Read(Vec::new()));
+        }
+
+        let address = unsafe { libc::mmap(std::ptr::null_mut(), length, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0) };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping::Mapped { address, length })
+    }
+
+    #[cfg(not(unix))]
+    fn open(path: &Path) -> io::Result<Mapping> {
+        fs::read(path).map(Mapping::Read)
+    }
+}
+
+impl Deref for Mapping {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match *self {
+            #[cfg(unix)]
+            Mapping::Mapped { address, length } => unsafe { std::slice::from_raw_parts(address as *const u8, length) },
+            Mapping::Read(ref bytes) => bytes,
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Mapping::Mapped { address, length } = *self {
+                unsafe { libc::munmap(address, length); }
+            }
+        }
+    }
+}
+
+/// a class stored in a `ClassArchive`, with the verification result of each of its methods.
+/// `verified` is empty if the class file bytes do not match the hash they were archived with.
+#[derive(Debug)]
+pub struct ArchivedClass<'a> {
+    pub name: &'a str,
+    pub data: &'a [u8],
+    pub hash: u64,
+    pub verified: Vec<Option<FastMethod>>,
+}
+
+/// the 64 bit FNV-1a hash of class file bytes, which ties verification results to them.
+fn class_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// the classes of a classpath together with their verification results, like the shared archive
+/// of HotSpot's class data sharing. loading classes from an archive skips scanning the classpath
+/// and verifying their methods again.
+///
+/// the format is a sequence of classes, each with its name, its class file bytes, their
+/// `class_hash` and for every method either nothing or its `FastMethod`. numbers are little endian.
+///
+/// the verification results are only used for bytes with the archived hash and states of the
+/// shape verification gives them, other methods are verified again. a class whose file on the
+/// classpath changed since the archive was written is left out.
+pub struct ClassArchive {
+    bytes: Mapping,
+}
+
+fn invalid(message: &str) -> io::Error {
+    invalid_data("class archive", message)
+}
+
+pub(super) fn invalid_data(what: &str, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid {}: {}", what, message))
+}
+
+fn write_slots<W: Write>(out: &mut W, slots: &[SlotType]) -> io::Result<()> {
+    out.write_all(&(slots.len() as u16).to_le_bytes())?;
+    for slot in slots {
+        out.write_all(&[match slot {
+            SlotType::Int => 0,
+            SlotType::Reference => 1,
+            SlotType::Unset => 2,
+        }])?;
+    }
+    Ok(())
+}
+
+/// reads the archive format from a byte slice, also used for snapshots. `what` names the format
+/// in errors.
+pub(super) struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, length: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < length {
+            return Err(invalid_data(self.what, "unexpected end"));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    /// a string preceded by its length in bytes.
+    pub fn string(&mut self) -> io::Result<&'a str> {
+        let length = self.u32()? as usize;
+        let what = self.what;
+        std::str::from_utf8(self.take(length)?).map_err(|_| invalid_data(what, "string is not utf-8"))
+    }
+
+    fn slots(&mut self) -> io::Result<Vec<SlotType>> {
+        let count = self.u16()?;
+        self.take(usize::from(count))?.iter()
+            .map(|&slot| match slot {
+                0 => Ok(SlotType::Int),
+                1 => Ok(SlotType::Reference),
+                2 => Ok(SlotType::Unset),
+                _ => Err(invalid("unknown slot type"))
+            })
+            .collect()
+    }
+}
+
+impl ClassArchive {
+    /// writes the archive of all classes on the classpath. returns the number of classes archived,
+    /// class files which cannot be parsed are left out.
+    pub fn dump<W: Write>(classpath: &[PathBuf], mut out: W) -> io::Result<usize> {
+        let mut classes = Vec::new();
+        for path in scan_classpath(classpath) {
+            let bytes = fs::read(&path)?;
+            let verified = match ClassFile::parse(&bytes) {
+                Ok(class) => (String::from(class.get_class_name()), class.methods.iter()
+                    .map(|method| DecodedMethod::decode(&class, method).ok().and_then(|decoded| decoded.fast))
+                    .collect::<Vec<_>>()),
+                Err(err) => {
+                    warn!(path = %path.display(), error = %err, "cannot archive class file");
+                    continue;
+                }
+            };
+            classes.push((verified, bytes));
+        }
+
+        out.write_all(MAGIC)?;
+        out.write_all(&(classes.len() as u32).to_le_bytes())?;
+        for ((name, verified), bytes) in classes.iter() {
+            out.write_all(&(name.len() as u32).to_le_bytes())?;
+            out.write_all(name.as_bytes())?;
+            out.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            out.write_all(bytes)?;
+            out.write_all(&class_hash(bytes).to_le_bytes())?;
+            out.write_all(&(verified.len() as u16).to_le_bytes())?;
+            for fast in verified.iter() {
+                match fast {
+                    Some(fast) => {
+                        out.write_all(&[1])?;
+                        out.write_all(&(fast.max_stack as u32).to_le_bytes())?;
+                        out.write_all(&(fast.states.len() as u32).to_le_bytes())?;
+                        for state in fast.states.iter() {
+                            write_slots(&mut out, &state.locals)?;
+                            write_slots(&mut out, &state.stack)?;
+                        }
+                    }
+                    None => out.write_all(&[0])?
+                }
+            }
+        }
+
+        Ok(classes.len())
+    }
+
+    pub fn open(path: &Path) -> io::Result<ClassArchive> {
+        let archive = ClassArchive { bytes: Mapping::open(path)? };
+        if !archive.bytes.starts_with(MAGIC) {
+            return Err(invalid("bad magic"));
+        }
+        Ok(archive)
+    }
+
+    /// the archived classes. the class file bytes are not copied out of the archive.
+    pub fn classes(&self) -> io::Result<Vec<ArchivedClass<'_>>> {
+        let mut reader = Reader { bytes: &self.bytes[MAGIC.len()..], what: "class archive" };
+        let count = reader.u32()?;
+        // the count is not trusted with an allocation, every class takes at least a few bytes
+        let mut classes = Vec::new();
+        for _ in 0..count {
+            let length = reader.u32()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?).map_err(|_| invalid("class name is not utf-8"))?;
+            let length = reader.u32()? as usize;
+            let data = reader.take(length)?;
+            let hash = reader.u64()?;
+            let methods = reader.u16()?;
+            let mut verified = Vec::with_capacity(usize::from(methods));
+            for _ in 0..methods {
+                verified.push(match reader.u8()? {
+                    0 => None,
+                    _ => {
+                        let max_stack = reader.u32()? as usize;
+                        let count = reader.u32()?;
+                        let states = (0..count)
+                            .map(|_| Ok(TypeState { locals: reader.slots()?, stack: reader.slots()? }))
+                            .collect::<io::Result<Vec<_>>>()?;
+                        Some(FastMethod { states, max_stack })
+                    }
+                });
+            }
+            if class_hash(data) != hash {
+                warn!(class = name, "archived class does not match its hash, verifying it again");
+                verified.clear();
+            }
+            classes.push(ArchivedClass { name, data, hash, verified });
+        }
+
+        Ok(classes)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// loads all classes of an archive. returns the number of classes loaded.
+    pub(super) fn load_archive(&mut self, archive: &'a ClassArchive) -> io::Result<usize> {
+        let classes = archive.classes()?;
+        let mut count = 0;
+        for archived in classes {
+            if self.is_stale(&archived) {
+                warn!(class = archived.name, "archived class differs from the one on the classpath, leaving it out");
+                continue;
+            }
+            let class = match ClassFile::parse(archived.data) {
+                Ok(class) => class,
+                Err(err) => {
+                    warn!(class = archived.name, error = %err, "cannot parse archived class");
+                    continue;
+                }
+            };
+            let (class, transformed) = match self.transform_class(class, archived.data) {
+                Ok(class) => class,
+                Err(err) => {
+                    warn!(class = archived.name, error = %err, "cannot transform archived class");
+                    continue;
+                }
+            };
+            for hook in self.hooks.iter_mut() {
+                hook.on_class_load(&class);
+            }
+            let class_name = String::from(class.get_class_name());
+            // the verification results belong to the archived bytes and their methods
+            if transformed || archived.verified.len() != class.methods.len() {
+                self.classes.insert(class);
+            } else {
+                self.classes.insert_verified(class, archived.verified);
+            }
+            self.class_linked(&class_name);
+            count += 1;
+        }
+
+        info!(classes = count, "loaded class archive");
+        Ok(count)
+    }
+
+    /// true if the first class file for the archived class on the classpath has other bytes.
+    fn is_stale(&self, archived: &ArchivedClass) -> bool {
+        self.classpath.iter()
+            .map(|directory| directory.join(format!("{}.class", archived.name)))
+            .find(|path| path.is_file())
+            .is_some_and(|path| fs::read(path).map_or(true, |bytes| class_hash(&bytes) != archived.hash))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{ByteStore, LocalVariable, StackValue};
+    use super::*;
+
+    #[test]
+    fn archived_classes_keep_their_verification() {
+        let dir = std::env::temp_dir().join(format!("rjvm-archive-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Loop.class"), &include_bytes!("../../../sample/Loop.class")[..]).unwrap();
+        let path = dir.join("classes.jsa");
+        assert_eq!(ClassArchive::dump(std::slice::from_ref(&dir), File::create(&path).unwrap()).unwrap(), 1);
+
+        let archive = ClassArchive::open(&path).unwrap();
+        let classes = archive.classes().unwrap();
+        assert_eq!(classes[0].name, "Loop");
+        // <init> calls into java/lang/Object, sum and max are verified
+        assert_eq!(classes[0].verified.iter().map(Option::is_some).collect::<Vec<_>>(), vec![false, true, true]);
+
+        let store = ByteStore::new();
+        let mut rt = Runtime::builder()
+            .byte_store(&store)
+            .class_archive(Some(path.clone()))
+            .build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        let id = rt.classes.id("Loop").unwrap();
+        let max = rt.classes.method(id, "max", "(II)I").unwrap();
+        assert!(rt.classes.decoded_at(id, max).unwrap().fast.is_some());
+        match rt.invoke_static("Loop", "max", "(II)I", vec![LocalVariable::Integer(3), LocalVariable::Integer(8)]) {
+            Ok(Some(StackValue::Integer(8))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(ClassArchive::open(&dir.join("missing.jsa")).is_err());
+    }
+
+    #[test]
+    fn damaged_and_stale_archives_are_not_trusted() {
+        let dir = std::env::temp_dir().join(format!("rjvm-archive-damaged-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let loop_class = &include_bytes!("../../../sample/Loop.class")[..];
+        fs::write(dir.join("Loop.class"), loop_class).unwrap();
+        let mut bytes = Vec::new();
+        ClassArchive::dump(std::slice::from_ref(&dir), &mut bytes).unwrap();
+        // the magic, the class count, the name and the class file come before the hash
+        let hash = MAGIC.len() + 4 + 4 + "Loop".len() + 4 + loop_class.len();
+        // `<init>` was not verified, `sum` was and starts with its max_stack
+        let max_stack = hash + 8 + 2 + 1 + 1;
+        let max = |path: &Path, classpath: Vec<PathBuf>| {
+            let store = ByteStore::new();
+            let rt = Runtime::builder()
+                .byte_store(&store)
+                .classpath(classpath)
+                .class_archive(Some(path.to_path_buf()))
+                .build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+            rt.classes.id("Loop").map(|id| {
+                let slot = rt.classes.method(id, "sum", "(I)I").unwrap();
+                rt.classes.decoded_at(id, slot).unwrap().fast.as_ref().map(|fast| fast.max_stack)
+            })
+        };
+        let write = |name: &str, change: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = bytes.clone();
+            change(&mut bytes);
+            let path = dir.join(name);
+            fs::write(&path, bytes).unwrap();
+            path
+        };
+
+        let intact = write("intact.jsa", &|_| ());
+        let verified = max(&intact, vec![]);
+        assert!(verified.unwrap().is_some());
+        // a wrong hash drops the verification results, the methods are verified again
+        let rehashed = write("rehashed.jsa", &|bytes| bytes[hash] ^= 0xff);
+        assert!(ClassArchive::open(&rehashed).unwrap().classes().unwrap()[0].verified.is_empty());
+        assert_eq!(max(&rehashed, vec![]), verified);
+        // as are states which do not fit the method
+        let misshapen = write("misshapen.jsa", &|bytes| bytes[max_stack] = 200);
+        assert_eq!(max(&misshapen, vec![]), verified);
+        // a class file on the classpath with other bytes replaces the archived one
+        let classpath = dir.join("classpath");
+        fs::create_dir_all(&classpath).unwrap();
+        fs::write(classpath.join("Loop.class"), &include_bytes!("../../../sample/Tiny.class")[..]).unwrap();
+        assert_eq!(max(&intact, vec![classpath.clone()]), None);
+        fs::write(classpath.join("Loop.class"), loop_class).unwrap();
+        assert_eq!(max(&intact, vec![classpath]), verified);
+
+        let truncated = write("truncated.jsa", &|bytes| bytes.truncate(hash));
+        assert!(ClassArchive::open(&truncated).unwrap().classes().is_err());
+        let huge = write("huge.jsa", &|bytes| bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&u32::MAX.to_le_bytes()));
+        assert!(ClassArchive::open(&huge).unwrap().classes().is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
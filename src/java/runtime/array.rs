@@ -0,0 +1,399 @@
+use java::class_file::Method;
+use java::runtime::{ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use super::StackFrame;
+use super::heap::array_size;
+
+/// the primitive element types of arrays which hold ints on the operand stack.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ElementType {
+    Boolean,
+    Char,
+    Byte,
+    Short,
+    Int,
+}
+
+impl ElementType {
+    /// the element type of a `newarray` instruction.
+    pub fn from_atype(atype: u8) -> Option<ElementType> {
+        match atype {
+            4 => Some(ElementType::Boolean),
+            5 => Some(ElementType::Char),
+            8 => Some(ElementType::Byte),
+            9 => Some(ElementType::Short),
+            10 => Some(ElementType::Int),
+            _ => None
+        }
+    }
+
+    pub fn from_class_name(class_name: &str) -> Option<ElementType> {
+        match class_name {
+            "[Z" => Some(ElementType::Boolean),
+            "[C" => Some(ElementType::Char),
+            "[B" => Some(ElementType::Byte),
+            "[S" => Some(ElementType::Short),
+            "[I" => Some(ElementType::Int),
+            _ => None
+        }
+    }
+
+    pub fn class_name(&self) -> &'static str {
+        match self {
+            ElementType::Boolean => "[Z",
+            ElementType::Char => "[C",
+            ElementType::Byte => "[B",
+            ElementType::Short => "[S",
+            ElementType::Int => "[I",
+        }
+    }
+
+    /// truncates an int to the element type when it is stored. bytes and shorts are sign extended again
+    /// and chars zero extended, so loading an element is what the jvm loads. booleans are packed
+    /// into bytes but only keep the lowest bit.
+    pub fn narrow(&self, value: i32) -> i32 {
+        match self {
+            ElementType::Boolean => value & 1,
+            ElementType::Char => i32::from(value as u16),
+            ElementType::Byte => i32::from(value as i8),
+            ElementType::Short => i32::from(value as i16),
+            ElementType::Int => value,
+        }
+    }
+
+    /// true if the array load and store instructions of `self` may access arrays of `array`.
+    /// `baload` and `bastore` serve both byte and boolean arrays.
+    fn accesses(&self, array: ElementType) -> bool {
+        *self == array || (*self == ElementType::Byte && array == ElementType::Boolean)
+    }
+}
+
+/// the internal name of the components of an array class, `I` for `[I`, `java/lang/String` for
+/// `[Ljava/lang/String;` and `[I` for `[[I`. `None` if `class_name` is no array.
+pub fn component_type(class_name: &str) -> Option<&str> {
+    if !class_name.starts_with('[') {
+        return None;
+    }
+
+    let component = &class_name[1..];
+    if component.starts_with('L') && component.ends_with(';') {
+        Some(&component[1..component.len() - 1])
+    } else {
+        Some(component)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// creates an array of `length` zeroed elements, throws a `NegativeArraySizeException` for negative lengths.
+    pub(super) fn new_array(&mut self, method: &Method, stack_frame: &StackFrame, element: ElementType, length: i64) -> Result<ObjectRef, RuntimeError> {
+        if length < 0 {
+            return Err(self.throw(method, "java/lang/NegativeArraySizeException", Some(&length.to_string())));
+        }
+
+        // the space is reserved before the elements are created, which for a large array may be a lot
+        self.reserve(method, stack_frame, array_size(length as usize))?;
+        let data = ObjectData::Array(vec![StackValue::Integer(0); length as usize]);
+        Ok(self.allocate(element.class_name(), data))
+    }
+
+    /// creates an array of `length` null references to objects of class `component`.
+    pub(super) fn new_reference_array(&mut self, method: &Method, stack_frame: &StackFrame, component: &str, length: i64) -> Result<ObjectRef, RuntimeError> {
+        if length < 0 {
+            return Err(self.throw(method, "java/lang/NegativeArraySizeException", Some(&length.to_string())));
+        }
+
+        let class_name = if component.starts_with('[') { format!("[{}", component) } else { format!("[L{};", component) };
+        self.reserve(method, stack_frame, array_size(length as usize))?;
+        let data = ObjectData::Array(vec![StackValue::Null; length as usize]);
+        Ok(self.allocate(&class_name, data))
+    }
+
+    /// pops an index and an array reference and checks them like every array instruction does.
+    /// `element` is the element type the instruction expects, `None` for arrays of references.
+    /// returns the array and the index.
+    fn array_access(&mut self, method: &Method, stack_frame: &mut StackFrame, element: Option<ElementType>) -> Result<(ObjectRef, usize), RuntimeError> {
+        let index = Runtime::pop_int(stack_frame)?;
+        let array = match Runtime::pop_reference(stack_frame)? {
+            Some(array) => array,
+            None => return Err(self.throw(method, "java/lang/NullPointerException", None))
+        };
+
+        let length = match self.heap.get(array) {
+            Some(object) => {
+                let accessible = match element {
+                    Some(element) => ElementType::from_class_name(&object.class_name).is_some_and(|actual| element.accesses(actual)),
+                    None => object.class_name.starts_with("[L") || object.class_name.starts_with("[[")
+                };
+                match object.data {
+                    ObjectData::Array(ref elements) if accessible => elements.len(),
+                    _ => return Err(RuntimeError::StackType { expected: format!("{} array", element.map_or("reference", |element| element.class_name())) })
+                }
+            }
+            None => return Err(RuntimeError::StackType { expected: String::from("array") })
+        };
+        if index < 0 || index as usize >= length {
+            let message = format!("Index {} out of bounds for length {}", index, length);
+            return Err(self.throw(method, "java/lang/ArrayIndexOutOfBoundsException", Some(&message)));
+        }
+
+        Ok((array, index as usize))
+    }
+
+    /// `iaload`, `baload`, `caload` and `saload`. elements are kept narrowed, so they load as they are.
+    pub(super) fn exec_array_load(&mut self, method: &Method, stack_frame: &mut StackFrame, element: ElementType) -> Result<(), RuntimeError> {
+        self.load_element(method, stack_frame, Some(element))
+    }
+
+    pub(super) fn exec_aaload(&mut self, method: &Method, stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
+        self.load_element(method, stack_frame, None)
+    }
+
+    fn load_element(&mut self, method: &Method, stack_frame: &mut StackFrame, element: Option<ElementType>) -> Result<(), RuntimeError> {
+        let (array, index) = self.array_access(method, stack_frame, element)?;
+        let value = match self.heap.get(array).map(|object| &object.data) {
+            Some(ObjectData::Array(elements)) => elements[index].clone(),
+            _ => unreachable!("checked by array_access")
+        };
+
+        stack_frame.push_stack(value);
+        Ok(())
+    }
+
+    /// stores a reference into an array. arrays are covariant, so a `String[]` may be used as an
+    /// `Object[]`, and the class of the value is checked against the actual component type.
+    /// throws an `ArrayStoreException` if it does not fit.
+    pub(super) fn exec_aastore(&mut self, method: &Method, stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
+        let value = match stack_frame.pop_stack() {
+            Some(value @ StackValue::Reference(_)) | Some(value @ StackValue::Null) => value,
+            Some(_) => return Err(RuntimeError::StackType { expected: String::from("reference") }),
+            None => return Err(RuntimeError::EmptyStack)
+        };
+        let (array, index) = self.array_access(method, stack_frame, None)?;
+
+        if let StackValue::Reference(reference) = value {
+            let value_class = self.heap.get(reference).map(|object| object.class_name.clone()).unwrap_or_default();
+            let array_class = self.heap.get(array).map(|object| object.class_name.clone()).unwrap_or_default();
+            let component = component_type(&array_class).unwrap();
+            if !self.is_assignable(&value_class, component) {
+                return Err(self.throw(method, "java/lang/ArrayStoreException", Some(&value_class.replace('/', "."))));
+            }
+        }
+
+        if let Some(ObjectData::Array(ref mut elements)) = self.heap.get_mut(array).map(|object| &mut object.data) {
+            elements[index] = value;
+        }
+        Ok(())
+    }
+
+    /// `iastore`, `bastore`, `castore` and `sastore`, which truncate the value to the element type of the array.
+    pub(super) fn exec_array_store(&mut self, method: &Method, stack_frame: &mut StackFrame, element: ElementType) -> Result<(), RuntimeError> {
+        let value = Runtime::pop_int(stack_frame)? as i32;
+        let (array, index) = self.array_access(method, stack_frame, Some(element))?;
+        let object = self.heap.get_mut(array).unwrap();
+        // the class of the array decides for bastore whether it holds bytes or booleans
+        let actual = ElementType::from_class_name(&object.class_name).unwrap();
+        if let ObjectData::Array(ref mut elements) = object.data {
+            elements[index] = StackValue::Integer(i64::from(actual.narrow(value)));
+        }
+
+        Ok(())
+    }
+
+    /// `Object.clone`, a shallow copy of an array or of an object whose class implements `Cloneable`.
+    /// throws a `CloneNotSupportedException` for other objects.
+    pub(super) fn clone_object(&mut self, object: ObjectRef) -> Result<Option<StackValue>, RuntimeError> {
+        let (class_name, fields, data) = match self.heap.get(object) {
+            Some(original) => {
+                let data = match original.data {
+                    ObjectData::Array(ref elements) => Some(ObjectData::Array(elements.clone())),
+                    ObjectData::Instance => Some(ObjectData::Instance),
+                    _ => None
+                };
+                (original.class_name.clone(), original.fields.clone(), data)
+            }
+            None => return Err(RuntimeError::StackType { expected: String::from("reference") })
+        };
+
+        let data = match data {
+            Some(data @ ObjectData::Array(_)) => data,
+            Some(data) if self.implements(&class_name, "java/lang/Cloneable") => data,
+            _ => return Err(self.throw_in_caller("java/lang/CloneNotSupportedException", Some(&class_name.replace('/', "."))))
+        };
+        let copy = self.allocate(&class_name, data);
+        if let Some(copy) = self.heap.get_mut(copy) {
+            copy.fields = fields;
+        }
+        Ok(Some(StackValue::Reference(copy)))
+    }
+
+    /// the length of any array.
+    pub(super) fn exec_array_length(&mut self, method: &Method, stack_frame: &mut StackFrame) -> Result<(), RuntimeError> {
+        let array = match Runtime::pop_reference(stack_frame)? {
+            Some(array) => array,
+            None => return Err(self.throw(method, "java/lang/NullPointerException", None))
+        };
+
+        match self.heap.get(array).map(|object| &object.data) {
+            Some(ObjectData::Array(elements)) => {
+                stack_frame.push_stack(StackValue::Integer(elements.len() as i64));
+                Ok(())
+            }
+            _ => Err(RuntimeError::StackType { expected: String::from("array") })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::LocalVariable;
+    use super::*;
+
+    const SMALL_ARRAYS: &[u8] = include_bytes!("../../../sample/SmallArrays.class");
+
+    fn call(rt: &mut Runtime, name: &str, descriptor: &str, argument: i64) -> Result<Option<StackValue>, RuntimeError> {
+        rt.invoke_static("SmallArrays", name, descriptor, vec![LocalVariable::Integer(argument)])
+    }
+
+    #[test]
+    fn stores_narrow_to_the_element_type() {
+        assert_eq!(ElementType::Byte.narrow(200), -56);
+        assert_eq!(ElementType::Byte.narrow(-129), 127);
+        assert_eq!(ElementType::Short.narrow(40000), -25536);
+        assert_eq!(ElementType::Char.narrow(-1), 0xffff);
+        assert_eq!(ElementType::Char.narrow(0x12345), 0x2345);
+        assert_eq!(ElementType::Boolean.narrow(2), 0);
+        assert_eq!(ElementType::Boolean.narrow(3), 1);
+        assert_eq!(ElementType::Int.narrow(-7), -7);
+    }
+
+    #[test]
+    fn small_elements_load_like_on_the_jvm() {
+        let mut rt = Runtime::builder().build(read_class_file(SMALL_ARRAYS).unwrap().1);
+        let cases: &[(&str, &str, i64, i64)] = &[
+            ("byteValue", "(I)I", 200, -56),
+            ("byteValue", "(I)I", 127, 127),
+            ("charValue", "(I)I", -1, 0xffff),
+            ("shortValue", "(I)I", 40000, -25536),
+            ("booleanValue", "(Z)I", 1, 1),
+            ("booleanValue", "(Z)I", 0, 0),
+            ("sum", "(I)I", 4, 1 + 2 + 3 + 4),
+            ("length", "(I)I", 3, 3),
+        ];
+        for &(name, descriptor, argument, expected) in cases.iter() {
+            match call(&mut rt, name, descriptor, argument) {
+                Ok(Some(StackValue::Integer(value))) => assert_eq!(value, expected, "{}({})", name, argument),
+                other => panic!("{}({}) returned {:?}", name, argument, other)
+            }
+        }
+    }
+
+    #[test]
+    fn bastore_keeps_the_lowest_bit_of_booleans() {
+        let mut rt = Runtime::builder().build(read_class_file(SMALL_ARRAYS).unwrap().1);
+        let class = rt.classes.get("SmallArrays").unwrap().clone();
+        let method = &class.methods[0];
+        let mut frame = StackFrame { local_variables: Vec::new(), stack: Vec::new() };
+        for &(element, value, expected) in [(ElementType::Boolean, 6, 0), (ElementType::Boolean, 7, 1), (ElementType::Byte, 0x1ff, -1)].iter() {
+            let array = rt.new_array(method, &frame, element, 1).unwrap();
+            frame.stack.extend(vec![StackValue::Reference(array), StackValue::Integer(0), StackValue::Integer(value)]);
+            rt.exec_array_store(method, &mut frame, ElementType::Byte).unwrap();
+            frame.stack.extend(vec![StackValue::Reference(array), StackValue::Integer(0)]);
+            rt.exec_array_load(method, &mut frame, ElementType::Byte).unwrap();
+            match frame.stack.pop() {
+                Some(StackValue::Integer(value)) => assert_eq!(value, expected),
+                other => panic!("unexpected element {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn bad_accesses_throw() {
+        let mut rt = Runtime::builder().build(read_class_file(SMALL_ARRAYS).unwrap().1);
+        for &(name, argument, exception) in [
+            ("outOfBounds", 2, "java/lang/ArrayIndexOutOfBoundsException"),
+            ("outOfBounds", -1, "java/lang/ArrayIndexOutOfBoundsException"),
+            ("length", -1, "java/lang/NegativeArraySizeException"),
+        ].iter() {
+            match call(&mut rt, name, "(I)I", argument) {
+                Err(RuntimeError::Exception { ref class_name, .. }) if class_name == exception => (),
+                other => panic!("{}({}) returned {:?}", name, argument, other)
+            }
+        }
+    }
+
+    #[test]
+    fn big_arrays_go_to_the_large_object_space() {
+        let mut rt = Runtime::builder().max_heap(Some(array_size(1000))).large_object_threshold(array_size(100))
+            .build(read_class_file(SMALL_ARRAYS).unwrap().1);
+        let class = rt.classes.get("SmallArrays").unwrap().clone();
+        let method = &class.methods[0];
+        let frame = StackFrame { local_variables: Vec::new(), stack: Vec::new() };
+
+        let small = rt.new_array(method, &frame, ElementType::Byte, 10).unwrap();
+        let large = rt.new_array(method, &frame, ElementType::Byte, 500).unwrap();
+        assert!(!rt.heap.is_large(small) && rt.heap.is_large(large));
+        assert_eq!(rt.heap.large_object_space().used, array_size(500));
+
+        // the elements of an array which cannot fit are never created
+        match rt.new_array(method, &frame, ElementType::Byte, 100_000_000) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/OutOfMemoryError" => (),
+            other => panic!("expected an OutOfMemoryError, got {:?}", other)
+        }
+        assert_eq!(rt.gc_stats().freed_large_objects, 1);
+    }
+
+    fn covariant_runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Covariant.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Shape.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Square.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Circle.class")).unwrap().1);
+        rt
+    }
+
+    #[test]
+    fn it_names_component_types() {
+        assert_eq!(component_type("[I"), Some("I"));
+        assert_eq!(component_type("[Ljava/lang/String;"), Some("java/lang/String"));
+        assert_eq!(component_type("[[Ljava/lang/String;"), Some("[Ljava/lang/String;"));
+        assert_eq!(component_type("java/lang/String"), None);
+    }
+
+    #[test]
+    fn reference_arrays_check_stores() {
+        let mut rt = covariant_runtime();
+        let cases: &[(&str, &str, Vec<LocalVariable>, i64)] = &[
+            ("store", "(I)I", vec![LocalVariable::Integer(0)], 1),
+            ("store", "(I)I", vec![LocalVariable::Integer(1)], 1),
+            ("storeCaught", "()I", vec![], -1),
+            ("interfaces", "()I", vec![], 2),
+            ("nested", "(I)I", vec![LocalVariable::Integer(0)], 4),
+            ("nested", "(I)I", vec![LocalVariable::Integer(1)], 4),
+        ];
+        for &(name, descriptor, ref arguments, expected) in cases.iter() {
+            match rt.invoke_static("Covariant", name, descriptor, arguments.clone()) {
+                Ok(Some(StackValue::Integer(value))) => assert_eq!(value, expected, "{}{:?}", name, arguments),
+                other => panic!("{}{:?} returned {:?}", name, arguments, other)
+            }
+        }
+
+        for &(name, descriptor, ref arguments) in [("store", "(I)I", vec![LocalVariable::Integer(2)]), ("nestedWrong", "()I", vec![])].iter() {
+            match rt.invoke_static("Covariant", name, descriptor, arguments.clone()) {
+                Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/ArrayStoreException" => (),
+                other => panic!("{}{:?} returned {:?}", name, arguments, other)
+            }
+        }
+    }
+
+    #[test]
+    fn arrays_are_assignable_covariantly() {
+        let rt = covariant_runtime();
+        assert!(rt.is_assignable("Square", "Shape"));
+        assert!(rt.is_assignable("[LSquare;", "[LShape;"));
+        assert!(rt.is_assignable("[LSquare;", "[Ljava/lang/Object;"));
+        assert!(rt.is_assignable("[[I", "[Ljava/lang/Object;"));
+        assert!(rt.is_assignable("[I", "java/io/Serializable"));
+        assert!(!rt.is_assignable("[LCircle;", "[LSquare;"));
+        assert!(!rt.is_assignable("[I", "[Ljava/lang/Object;"));
+        assert!(!rt.is_assignable("[B", "[I"));
+    }
+}
@@ -0,0 +1,148 @@
+use java::runtime::{ObjectRef, Runtime, RuntimeError, StackValue};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Target {
+    All,
+    /// a package and its subpackages, the unnamed package is empty.
+    Package(String),
+    Class(String),
+}
+
+/// which classes run their `assert` statements, configured with the `-ea` and `-da` flags of the launcher.
+/// the most specific flag wins: a class before its packages, a package before its parent packages,
+/// and among equally specific flags the last one.
+#[derive(Debug, Clone, Default)]
+pub struct AssertionStatus {
+    rules: Vec<(Target, bool)>,
+}
+
+impl AssertionStatus {
+    /// assertions disabled everywhere, like the jvm starts.
+    pub fn new() -> AssertionStatus {
+        AssertionStatus::default()
+    }
+
+    /// applies a launcher flag: `-ea`, `-ea:com.example...`, `-ea:...` for the unnamed package,
+    /// `-ea:com.example.Main`, the same for `-da`, or their long forms `-enableassertions` and
+    /// `-disableassertions`. returns false if `flag` is none of them.
+    pub fn apply_flag(&mut self, flag: &str) -> bool {
+        let (enabled, rest) = if let Some(rest) = flag.strip_prefix("-enableassertions") {
+            (true, rest)
+        } else if let Some(rest) = flag.strip_prefix("-disableassertions") {
+            (false, rest)
+        } else if let Some(rest) = flag.strip_prefix("-ea") {
+            (true, rest)
+        } else if let Some(rest) = flag.strip_prefix("-da") {
+            (false, rest)
+        } else {
+            return false;
+        };
+
+        let target = match rest.strip_prefix(':') {
+            _ if rest.is_empty() => Target::All,
+            None => return false,
+            Some(name) => match name.strip_suffix("...") {
+                Some(package) => Target::Package(package.replace('.', "/")),
+                None => Target::Class(name.replace('.', "/"))
+            }
+        };
+        self.rules.push((target, enabled));
+        true
+    }
+
+    /// whether assertions are enabled for a class, given by its internal name like `com/example/Main`.
+    pub fn desired(&self, class_name: &str) -> bool {
+        let package = class_name.rfind('/').map_or("", |end| &class_name[..end]);
+        let mut best: Option<(usize, bool)> = None;
+        for &(ref target, enabled) in self.rules.iter() {
+            let specificity = match target {
+                Target::All => 0,
+                Target::Package(name) if name.is_empty() && package.is_empty() => 1,
+                Target::Package(name) if !name.is_empty() && (package == name || package.starts_with(&format!("{}/", name))) => name.len() + 1,
+                Target::Class(name) if name == class_name => usize::MAX,
+                _ => continue
+            };
+            if best.is_none_or(|(best, _)| specificity >= best) {
+                best = Some((specificity, enabled));
+            }
+        }
+
+        best.is_some_and(|(_, enabled)| enabled)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// `Class.desiredAssertionStatus`, which the static initializer of every class with an `assert`
+    /// statement calls to set its `$assertionsDisabled` field.
+    pub(super) fn desired_assertion_status(&self, class: ObjectRef) -> Result<Option<StackValue>, RuntimeError> {
+        let name = self.class_object_name(class).ok_or_else(|| RuntimeError::StackType { expected: String::from("java/lang/Class") })?;
+
+        Ok(Some(StackValue::Integer(if self.assertions.desired(&name) { 1 } else { 0 })))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::LocalVariable;
+    use super::*;
+
+    fn status(flags: &[&str]) -> AssertionStatus {
+        let mut status = AssertionStatus::new();
+        for flag in flags {
+            assert!(status.apply_flag(flag), "{} not accepted", flag);
+        }
+        status
+    }
+
+    #[test]
+    fn the_most_specific_flag_wins() {
+        assert!(!status(&[]).desired("Main"));
+        assert!(status(&["-ea"]).desired("com/example/Main"));
+        assert!(!status(&["-ea", "-da"]).desired("Main"));
+
+        let status = status(&["-ea:com.example...", "-da:com.example.internal...", "-ea:com.example.internal.Checked"]);
+        assert!(status.desired("com/example/Main"));
+        assert!(status.desired("com/example/util/Strings"));
+        assert!(!status.desired("com/example/internal/Cache"));
+        assert!(status.desired("com/example/internal/Checked"));
+        assert!(!status.desired("com/examples/Main"));
+        assert!(!status.desired("Main"));
+
+        let status = self::status(&["-enableassertions:...", "-da:Quiet"]);
+        assert!(status.desired("Main"));
+        assert!(!status.desired("Quiet"));
+        assert!(!status.desired("com/example/Main"));
+
+        assert!(!AssertionStatus::new().apply_flag("-verbose:gc"));
+        assert!(!AssertionStatus::new().apply_flag("-eax"));
+    }
+
+    #[test]
+    fn assert_statements_follow_the_flags() {
+        let class = include_bytes!("../../../sample/Asserts.class");
+        let mut rt = Runtime::builder().build(read_class_file(class).unwrap().1);
+        match rt.invoke_static("Asserts", "check", "(I)I", vec![LocalVariable::Integer(-1)]) {
+            Ok(Some(StackValue::Integer(-1))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+        match rt.invoke_static("Asserts", "sameClass", "()I", vec![]) {
+            Ok(Some(StackValue::Integer(1))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+
+        let mut rt = Runtime::builder()
+            .assertions(status(&["-ea:Asserts"]))
+            .build(read_class_file(class).unwrap().1);
+        match rt.invoke_static("Asserts", "check", "(I)I", vec![LocalVariable::Integer(-1)]) {
+            Err(RuntimeError::Exception { exception, ref class_name }) if class_name == "java/lang/AssertionError" => {
+                assert!(rt.format_stack_trace(exception).starts_with("java.lang.AssertionError: x must be positive\n"));
+            }
+            other => panic!("unexpected result {:?}", other)
+        }
+        match rt.invoke_static("Asserts", "check", "(I)I", vec![LocalVariable::Integer(3)]) {
+            Ok(Some(StackValue::Integer(3))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use java::runtime::{LocalVariable, Runtime, RuntimeError};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// timings of repeated invocations of a method.
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub iterations: usize,
+    pub mean: Duration,
+    pub median: Duration,
+    pub p99: Duration,
+    /// instructions retired per iteration, on average.
+    pub instructions: u64,
+}
+
+impl BenchReport {
+    /// summarizes the duration of every measured iteration.
+    pub fn from_samples(mut samples: Vec<Duration>, instructions: u64) -> BenchReport {
+        samples.sort();
+        let iterations = samples.len();
+        if iterations == 0 {
+            return BenchReport { iterations, mean: Duration::default(), median: Duration::default(), p99: Duration::default(), instructions: 0 };
+        }
+
+        let total = samples.iter().sum::<Duration>();
+        // nearest rank, the smallest sample which at least 99% of the samples do not exceed
+        let p99 = (iterations * 99).div_ceil(100);
+        BenchReport {
+            iterations,
+            mean: total / iterations as u32,
+            median: samples[iterations / 2],
+            p99: samples[p99 - 1],
+            instructions: instructions / iterations as u64,
+        }
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "iterations:   {}", self.iterations)?;
+        writeln!(f, "mean:         {:?}", self.mean)?;
+        writeln!(f, "median:       {:?}", self.median)?;
+        writeln!(f, "p99:          {:?}", self.p99)?;
+        writeln!(f, "instructions: {} per iteration", self.instructions)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// invokes a static method `warmup` times without measuring, then `iterations` times measuring
+    /// each invocation and the instructions it retired.
+    pub fn bench(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>,
+                 warmup: usize, iterations: usize) -> Result<BenchReport, RuntimeError> {
+        for _ in 0..warmup {
+            self.invoke_static(class_name, method_name, descriptor, arguments.clone())?;
+        }
+
+        let retired = self.instructions_retired();
+        let mut samples = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            self.invoke_static(class_name, method_name, descriptor, arguments.clone())?;
+            samples.push(start.elapsed());
+        }
+
+        Ok(BenchReport::from_samples(samples, self.instructions_retired() - retired))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::InterpreterMode;
+    use super::*;
+
+    #[test]
+    fn it_summarizes_samples() {
+        let samples = (1..=200).map(Duration::from_millis).collect::<Vec<_>>();
+        let report = BenchReport::from_samples(samples, 2000);
+        assert_eq!(report.mean, Duration::from_micros(100_500));
+        assert_eq!(report.median, Duration::from_millis(101));
+        assert_eq!(report.p99, Duration::from_millis(198));
+        assert_eq!(report.instructions, 10);
+    }
+
+    #[test]
+    fn it_benchmarks_a_static_method() {
+        let mut rt = Runtime::builder()
+            .interpreter(InterpreterMode::Fast)
+            .build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        let report = rt.bench("Loop", "max", "(II)I", vec![LocalVariable::Integer(1), LocalVariable::Integer(2)], 3, 10).unwrap();
+        assert_eq!(report.iterations, 10);
+        assert!(report.median <= report.p99);
+        // iload_0 iload_1 if_icmple iload_1 ireturn
+        assert_eq!(report.instructions, 5);
+    }
+}
@@ -0,0 +1,243 @@
+use java::class_file::Method;
+use java::runtime::{FrameView, Heap, Runtime};
+use super::StackFrame;
+
+/// where in a method a breakpoint is set.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CodeLocation {
+    /// the instruction at this bytecode offset.
+    Pc(usize),
+    /// the first instruction of this source line, taken from the `LineNumberTable`.
+    Line(usize),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct BreakpointId(usize);
+
+/// how far to run before suspending again.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum StepMode {
+    /// the next instruction, in whatever frame it is.
+    Instruction,
+    /// the next line, stepping into called methods.
+    Into,
+    /// the next line in the current method or its caller.
+    Over,
+    /// the next instruction after the current method returned.
+    Out,
+}
+
+/// what the interpreter does after the debug handler returned.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Resume {
+    Continue,
+    Step(StepMode),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SuspendReason {
+    Breakpoint(BreakpointId),
+    Step,
+}
+
+/// the state of the suspended interpreter, handed to the debug handler.
+pub struct Suspension<'s> {
+    pub reason: SuspendReason,
+    /// the java stack, innermost frame first.
+    pub frames: Vec<FrameView>,
+    pub heap: &'s Heap,
+}
+
+pub type DebugHandler = Box<dyn FnMut(&Suspension) -> Resume + Send>;
+
+struct Breakpoint {
+    id: BreakpointId,
+    class_name: String,
+    method_name: String,
+    location: CodeLocation,
+}
+
+struct Step {
+    mode: StepMode,
+    /// the frame count and line (or pc without line numbers) where stepping started.
+    /// `None` stops at the next instruction.
+    start: Option<(usize, usize)>,
+}
+
+/// breakpoints, the pending step and the handler of a `Runtime`.
+#[derive(Default)]
+pub struct Breakpoints {
+    breakpoints: Vec<Breakpoint>,
+    step: Option<Step>,
+    handler: Option<DebugHandler>,
+    next_id: usize,
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        Breakpoints::default()
+    }
+
+    /// true if the interpreter has to check every instruction.
+    pub fn is_active(&self) -> bool {
+        self.handler.is_some() && (!self.breakpoints.is_empty() || self.step.is_some())
+    }
+
+    fn hit(&self, class_name: &str, method: &Method, pc: usize) -> Option<BreakpointId> {
+        self.breakpoints.iter()
+            .find(|breakpoint| breakpoint.class_name == class_name && breakpoint.method_name == method.name && match breakpoint.location {
+                CodeLocation::Pc(location) => location == pc,
+                CodeLocation::Line(line) => method.get_code()
+                    .map(|code| code.line_number_table().iter().any(|&(start, entry)| usize::from(start) == pc && usize::from(entry) == line))
+                    .unwrap_or(false)
+            })
+            .map(|breakpoint| breakpoint.id)
+    }
+}
+
+impl Step {
+    fn should_stop(&self, depth: usize, position: usize) -> bool {
+        let (start_depth, start_position) = match self.start {
+            Some(start) => start,
+            None => return true
+        };
+
+        let moved = depth < start_depth || (depth == start_depth && position != start_position);
+        match self.mode {
+            StepMode::Instruction => true,
+            StepMode::Into => moved || depth > start_depth,
+            StepMode::Over => moved,
+            StepMode::Out => depth < start_depth,
+        }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// suspends the interpreter before the instruction at `location` in `method_name` of `class_name`
+    /// is executed, and calls the debug handler.
+    pub fn set_breakpoint(&mut self, class_name: &str, method_name: &str, location: CodeLocation) -> BreakpointId {
+        let id = BreakpointId(self.breakpoints.next_id);
+        self.breakpoints.next_id += 1;
+        self.breakpoints.breakpoints.push(Breakpoint { id, class_name: String::from(class_name), method_name: String::from(method_name), location });
+        id
+    }
+
+    pub fn clear_breakpoint(&mut self, id: BreakpointId) {
+        self.breakpoints.breakpoints.retain(|breakpoint| breakpoint.id != id);
+    }
+
+    /// suspends the interpreter before the next instruction, e.g. to step through a method from its start.
+    pub fn step(&mut self, mode: StepMode) {
+        self.breakpoints.step = Some(Step { mode, start: None });
+    }
+
+    /// installs the callback which gets control while the interpreter is suspended.
+    /// breakpoints and steps have no effect without a handler.
+    pub fn set_debug_handler<F>(&mut self, handler: F)
+        where F: FnMut(&Suspension) -> Resume + Send + 'static {
+        self.breakpoints.handler = Some(Box::new(handler));
+    }
+
+    /// checks breakpoints and the pending step before the instruction at `pc` is executed.
+    pub(super) fn check_breakpoints(&mut self, class_name: &str, method: &Method, stack_frame: &StackFrame, pc: usize) {
+        let depth = self.thread.frames.len();
+        let position = method.get_code().and_then(|code| code.line_number_at(pc)).unwrap_or(pc);
+
+        let reason = match self.breakpoints.hit(class_name, method, pc) {
+            Some(id) => SuspendReason::Breakpoint(id),
+            None => match self.breakpoints.step {
+                Some(ref step) => {
+                    let position = if step.mode == StepMode::Instruction { pc } else { position };
+                    if !step.should_stop(depth, position) {
+                        return;
+                    }
+                    SuspendReason::Step
+                }
+                None => return
+            }
+        };
+
+        let mut handler = match self.breakpoints.handler.take() {
+            Some(handler) => handler,
+            None => return
+        };
+        let resume = handler(&Suspension { reason, frames: self.frame_views(stack_frame), heap: &self.heap });
+        self.breakpoints.handler = Some(handler);
+
+        self.breakpoints.step = match resume {
+            Resume::Continue => None,
+            Resume::Step(mode) => {
+                let position = if mode == StepMode::Instruction { pc } else { position };
+                Some(Step { mode, start: Some((depth, position)) })
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{FrameValue, LocalVariable, Runtime, StackValue};
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    fn tiny() -> Runtime<'static> {
+        Runtime::create(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1)
+    }
+
+    fn ints(frame: &FrameView) -> Vec<i32> {
+        frame.stack().filter_map(|value| match value {
+            FrameValue::Int(value) => Some(value),
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn breakpoints_suspend_with_frame_access() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+
+        let mut rt = tiny();
+        let breakpoint = rt.set_breakpoint("Tiny", "add", CodeLocation::Pc(2));
+        rt.set_debug_handler(move |suspension| {
+            let frame = &suspension.frames[0];
+            recorder.lock().unwrap().push((suspension.reason, frame.pc(), ints(frame)));
+            if frame.pc() == 2 { Resume::Step(StepMode::Instruction) } else { Resume::Continue }
+        });
+
+        let result = rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(40), LocalVariable::Integer(2)]);
+        match result {
+            Ok(Some(StackValue::Integer(42))) => (),
+            other => panic!("expected 42, got {:?}", other)
+        }
+
+        assert_eq!(*seen.lock().unwrap(), vec![
+            (SuspendReason::Breakpoint(breakpoint), 2, vec![40, 2]),
+            (SuspendReason::Step, 3, vec![42]),
+        ]);
+    }
+
+    #[test]
+    fn steps_follow_lines_and_frames() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+
+        let mut rt = tiny();
+        rt.set_breakpoint("Tiny", "main", CodeLocation::Line(13));
+        let mut modes = vec![StepMode::Into, StepMode::Out, StepMode::Over].into_iter();
+        rt.set_debug_handler(move |suspension| {
+            let frame = &suspension.frames[0];
+            recorder.lock().unwrap().push((String::from(frame.method_name()), frame.line(), suspension.frames.len()));
+            modes.next().map(Resume::Step).unwrap_or(Resume::Continue)
+        });
+
+        rt.invoke_static("Tiny", "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]).unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), vec![
+            (String::from("main"), Some(13), 1),
+            (String::from("add"), Some(2), 2),
+            (String::from("main"), Some(13), 1),
+            (String::from("main"), Some(14), 1),
+        ]);
+    }
+}
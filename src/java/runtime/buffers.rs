@@ -0,0 +1,1148 @@
+use java::runtime::{GcReason, LocalVariable, NativeContext, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::fs::File;
+use std::io;
+
+pub(super) const BUFFER: &str = "java/nio/Buffer";
+pub(super) const BYTE_BUFFER: &str = "java/nio/ByteBuffer";
+pub(super) const MAPPED_BYTE_BUFFER: &str = "java/nio/MappedByteBuffer";
+pub(super) const DIRECT_BYTE_BUFFER: &str = "java/nio/DirectByteBuffer";
+pub(super) const HEAP_BYTE_BUFFER: &str = "java/nio/HeapByteBuffer";
+pub(super) const BYTE_ORDER: &str = "java/nio/ByteOrder";
+pub(super) const FILE_CHANNEL: &str = "java/nio/channels/FileChannel";
+pub(super) const MAP_MODE: &str = "java/nio/channels/FileChannel$MapMode";
+pub(super) const RANDOM_ACCESS_FILE: &str = "java/io/RandomAccessFile";
+pub(super) const UNSAFE: &str = "sun/misc/Unsafe";
+
+/// what hotspot throws when an unsafe access hits memory it may not touch, which here is any
+/// memory the program did not allocate or map.
+const UNSAFE_FAULT: &str = "a fault occurred in an unsafe memory access";
+
+/// memory outside of the heap: the storage of a direct buffer, a mapped file or a block of
+/// `Unsafe.allocateMemory`. `Runtime::native_memory` keeps it by its address.
+pub(super) struct NativeMemory {
+    /// the buffer the memory belongs to, it is released when the buffer is collected.
+    /// `None` for memory the program frees itself.
+    owner: Option<ObjectRef>,
+    length: usize,
+    writable: bool,
+    storage: Storage,
+}
+
+enum Storage {
+    /// never empty, so that every block has an address of its own.
+    Allocated(Box<[u8]>),
+    #[cfg(unix)]
+    Mapped { address: *mut libc::c_void, length: usize },
+}
+
+// the memory is owned by this value and only accessed through it
+unsafe impl Send for Storage {}
+unsafe impl Sync for Storage {}
+
+impl NativeMemory {
+    /// zeroed memory of `length` bytes, `None` if the system has no memory for it.
+    fn allocate(length: usize, owner: Option<ObjectRef>) -> Option<NativeMemory> {
+        let mut bytes = Vec::new();
+        bytes.try_reserve_exact(length.max(1)).ok()?;
+        bytes.resize(length.max(1), 0);
+        Some(NativeMemory { owner, length, writable: true, storage: Storage::Allocated(bytes.into_boxed_slice()) })
+    }
+
+    /// the number of bytes allocated for this memory, 0 for mapped files.
+    fn allocated(&self) -> usize {
+        match self.storage {
+            Storage::Allocated(_) => self.length,
+            #[cfg(unix)]
+            Storage::Mapped { .. } => 0,
+        }
+    }
+
+    /// maps `length` bytes of `file` from `position` on, which has to be a multiple of the page size.
+    #[cfg(unix)]
+    fn map(file: &File, mode: MapMode, position: u64, length: usize, owner: ObjectRef) -> io::Result<NativeMemory> {
+        use std::os::unix::io::AsRawFd;
+
+        if length == 0 {
+            let memory = NativeMemory::allocate(0, Some(owner)).ok_or_else(|| io::Error::from(io::ErrorKind::OutOfMemory))?;
+            return Ok(NativeMemory { writable: mode != MapMode::ReadOnly, ..memory });
+        }
+        let (protection, flags) = match mode {
+            MapMode::ReadOnly => (libc::PROT_READ, libc::MAP_SHARED),
+            MapMode::ReadWrite => (libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED),
+            MapMode::Private => (libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE),
+        };
+        let address = unsafe { libc::mmap(std::ptr::null_mut(), length, protection, flags, file.as_raw_fd(), position as libc::off_t) };
+        if address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(NativeMemory { owner: Some(owner), length, writable: mode != MapMode::ReadOnly, storage: Storage::Mapped { address, length } })
+    }
+
+    /// without mmap the file is read into memory, so changes do not reach the file.
+    #[cfg(not(unix))]
+    fn map(file: &File, mode: MapMode, position: u64, length: usize, owner: ObjectRef) -> io::Result<NativeMemory> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let mut memory = NativeMemory::allocate(length, Some(owner)).ok_or_else(|| io::Error::from(io::ErrorKind::OutOfMemory))?;
+        memory.writable = mode != MapMode::ReadOnly;
+        let mut file = file;
+        file.seek(SeekFrom::Start(position))?;
+        file.take(length as u64).read(memory.bytes_mut())?;
+        Ok(memory)
+    }
+
+    fn address(&self) -> usize {
+        match self.storage {
+            Storage::Allocated(ref bytes) => bytes.as_ptr() as usize,
+            #[cfg(unix)]
+            Storage::Mapped { address, .. } => address as usize,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        match self.storage {
+            Storage::Allocated(ref bytes) => &bytes[..self.length],
+            #[cfg(unix)]
+            Storage::Mapped { address, length } => unsafe { std::slice::from_raw_parts(address as *const u8, length) },
+        }
+    }
+
+    /// the bytes to write to, which for read only mappings must not happen.
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        match self.storage {
+            Storage::Allocated(ref mut bytes) => &mut bytes[..self.length],
+            #[cfg(unix)]
+            Storage::Mapped { address, length } => unsafe { std::slice::from_raw_parts_mut(address as *mut u8, length) },
+        }
+    }
+
+    /// writes the changes of a shared mapping back to its file, like `MappedByteBuffer.force`.
+    fn force(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            if let Storage::Mapped { address, length } = self.storage {
+                if unsafe { libc::msync(address, length, libc::MS_SYNC) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Storage {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if let Storage::Mapped { address, length } = *self {
+                unsafe { libc::munmap(address, length); }
+            }
+        }
+    }
+}
+
+/// a file opened by a `RandomAccessFile`, which it and its channel keep the index of in
+/// `Runtime::files` in the field `handle`.
+pub(super) struct OpenFile {
+    file: File,
+    writable: bool,
+}
+
+/// the constants of `FileChannel.MapMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MapMode {
+    ReadOnly,
+    ReadWrite,
+    Private,
+}
+
+/// the position, limit and friends of a buffer, kept in fields of the same names.
+/// `offset` is where the buffer starts in its memory or array.
+#[derive(Debug, Clone, Copy)]
+struct BufferState {
+    offset: usize,
+    capacity: i32,
+    limit: i32,
+    position: i32,
+    mark: i32,
+    big_endian: bool,
+    read_only: bool,
+}
+
+impl BufferState {
+    fn remaining(&self) -> i32 {
+        self.limit - self.position
+    }
+}
+
+/// a typed `get` or `put` of `ByteBuffer`, like `putInt`, by its name and parameters.
+struct BufferAccess<'s> {
+    put: bool,
+    size: usize,
+    /// the type in the name of the method, empty for bytes and bulk transfers.
+    type_name: &'s str,
+    /// the descriptor of the value a `put` takes.
+    value_type: &'s str,
+    /// the parameters of the method descriptor, with their parentheses.
+    parameters: &'s str,
+}
+
+/// the number of bytes of the values the typed accessors of `ByteBuffer` and `Unsafe` read and
+/// write, by the type in their name.
+fn value_size(type_name: &str) -> Option<usize> {
+    match type_name {
+        "" | "Byte" => Some(1),
+        "Short" | "Char" => Some(2),
+        "Int" => Some(4),
+        "Long" => Some(8),
+        _ => None
+    }
+}
+
+fn decode_value(bytes: &[u8], big_endian: bool, type_name: &str) -> StackValue {
+    let fold = |value: u64, byte: &u8| value << 8 | u64::from(*byte);
+    let value = match big_endian {
+        true => bytes.iter().fold(0, fold),
+        false => bytes.iter().rev().fold(0, fold)
+    };
+    match type_name {
+        "Short" => StackValue::Integer(i64::from(value as i16)),
+        "Char" => StackValue::Integer(i64::from(value as u16)),
+        "Int" => StackValue::Integer(i64::from(value as i32)),
+        "Long" => StackValue::Long(value as i64),
+        _ => StackValue::Integer(i64::from(value as i8)),
+    }
+}
+
+fn encode_value(value: &LocalVariable, size: usize, big_endian: bool) -> Vec<u8> {
+    let value = match value {
+        LocalVariable::Integer(value) | LocalVariable::Long(value) => *value,
+        _ => 0
+    };
+    let mut bytes = (value as u64).to_be_bytes()[8 - size..].to_vec();
+    if !big_endian {
+        bytes.reverse();
+    }
+    bytes
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+impl<'a> Runtime<'a> {
+    /// implements `ByteBuffer` with its heap and direct variants, `ByteOrder`, `RandomAccessFile`
+    /// as far as it takes to get a `FileChannel`, `FileChannel.map` and the raw memory access of
+    /// `sun.misc.Unsafe`.
+    ///
+    /// direct buffers and mapped files live in `NativeMemory`, which is released when the buffer
+    /// owning it is collected. slices and duplicates keep the owner reachable through their field
+    /// `att`, like the class library does. files are opened through the `NativeContext`, so
+    /// mapping needs the capabilities to read, and for writable mappings to write, the file.
+    /// the contents of mapped files are not recorded for replay.
+    /// returns `None` for methods the classes do not have.
+    pub(super) fn invoke_buffer(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        match class_name {
+            BYTE_ORDER => return self.invoke_byte_order(method_name, descriptor, arguments),
+            UNSAFE => return self.invoke_unsafe(method_name, descriptor, arguments),
+            RANDOM_ACCESS_FILE | FILE_CHANNEL => return self.invoke_file(class_name, method_name, descriptor, arguments),
+            _ => ()
+        }
+        let result = match (method_name, descriptor, arguments.first()) {
+            ("allocate", "(I)Ljava/nio/ByteBuffer;", Some(LocalVariable::Integer(capacity))) => self.allocate_byte_buffer(*capacity, false),
+            ("allocateDirect", "(I)Ljava/nio/ByteBuffer;", Some(LocalVariable::Integer(capacity))) => self.allocate_byte_buffer(*capacity, true),
+            ("wrap", "([B)Ljava/nio/ByteBuffer;", array) | ("wrap", "([BII)Ljava/nio/ByteBuffer;", array) => {
+                let array = match array {
+                    Some(LocalVariable::Reference(array)) => *array,
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let length = match self.heap.get(array).map(|array| &array.data) {
+                    Some(ObjectData::Array(elements)) => elements.len() as i32,
+                    _ => return Some(Err(RuntimeError::StackType { expected: String::from("[B") }))
+                };
+                let (offset, count) = match (arguments.get(1), arguments.get(2)) {
+                    (Some(LocalVariable::Integer(offset)), Some(LocalVariable::Integer(count))) => (*offset as i32, *count as i32),
+                    _ => (0, length)
+                };
+                if offset < 0 || count < 0 || offset > length - count {
+                    return Some(Err(self.throw_in_caller("java/lang/IndexOutOfBoundsException", None)));
+                }
+                let buffer = self.new_byte_buffer(HEAP_BYTE_BUFFER, ("hb", StackValue::Reference(array)), 0, length);
+                self.set_field_value(buffer, "position", StackValue::Integer(i64::from(offset)));
+                self.set_field_value(buffer, "limit", StackValue::Integer(i64::from(offset + count)));
+                Ok(Some(StackValue::Reference(buffer)))
+            }
+            (_, _, Some(LocalVariable::Reference(this))) => {
+                let this = *this;
+                return self.invoke_byte_buffer(this, method_name, descriptor, arguments);
+            }
+            _ => return None
+        };
+        Some(result)
+    }
+
+    /// the bytes of native memory at `address`, `None` unless they lie within one block of
+    /// memory the program allocated or mapped.
+    pub fn native_memory(&self, address: usize, length: usize) -> Option<&[u8]> {
+        let (start, memory) = self.native_memory.range(..=address).next_back()?;
+        let offset = address - start;
+        memory.bytes().get(offset..offset.checked_add(length)?)
+    }
+
+    /// like `native_memory`, for memory which may be written to.
+    pub fn native_memory_mut(&mut self, address: usize, length: usize) -> Option<&mut [u8]> {
+        let (start, memory) = self.native_memory.range_mut(..=address).next_back()?;
+        let offset = address - start;
+        if !memory.writable {
+            return None;
+        }
+        memory.bytes_mut().get_mut(offset..offset.checked_add(length)?)
+    }
+
+    /// the number of bytes of native memory the program has allocated or mapped.
+    pub fn native_memory_used(&self) -> usize {
+        self.native_memory.values().map(|memory| memory.length).sum()
+    }
+
+    /// the address of the first byte of a direct buffer, `None` for other objects.
+    pub fn direct_buffer_address(&self, buffer: ObjectRef) -> Option<usize> {
+        match self.field_value(buffer, "address") {
+            StackValue::Long(address) => Some(address as usize + self.buffer_state(buffer).offset),
+            _ => None
+        }
+    }
+
+    /// the number of bytes allocated for direct buffers and `Unsafe.allocateMemory`, which count
+    /// against the maximum heap size. mapped files do not.
+    pub(super) fn native_used(&self) -> usize {
+        self.native_memory.values().map(NativeMemory::allocated).sum()
+    }
+
+    /// allocates `length` bytes of native memory, collecting garbage first if they do not fit into
+    /// the maximum heap size. throws an `OutOfMemoryError` with `message` if they still do not fit
+    /// or the system has no memory for them.
+    fn allocate_native(&mut self, length: usize, message: &str) -> Result<NativeMemory, RuntimeError> {
+        if let Some(max_heap) = self.max_heap {
+            if self.heap.used() + self.native_used() + length > max_heap {
+                self.collect_garbage(GcReason::AllocationFailure, None);
+            }
+            if self.heap.used() + self.native_used() + length > max_heap {
+                return Err(self.throw_in_caller("java/lang/OutOfMemoryError", Some(message)));
+            }
+        }
+        match NativeMemory::allocate(length, None) {
+            Some(memory) => Ok(memory),
+            None => Err(self.throw_in_caller("java/lang/OutOfMemoryError", Some(message)))
+        }
+    }
+
+    /// frees the native memory of buffers which were collected. called after every collection.
+    pub(super) fn release_native_memory(&mut self) {
+        let heap = &self.heap;
+        let before = self.native_memory.len();
+        self.native_memory.retain(|_, memory| memory.owner.is_none_or(|owner| heap.get(owner).is_some()));
+        if self.native_memory.len() < before {
+            debug!(released = before - self.native_memory.len(), "released native memory");
+        }
+    }
+
+    /// the constants of `ByteOrder` and `FileChannel.MapMode`, one object per name which keeps it
+    /// in the field `name`.
+    pub(super) fn nio_constant(&mut self, class_name: &str, field_name: &str) -> Option<StackValue> {
+        let names: &[&str] = match class_name {
+            BYTE_ORDER => &["BIG_ENDIAN", "LITTLE_ENDIAN"],
+            MAP_MODE => &["READ_ONLY", "READ_WRITE", "PRIVATE"],
+            _ => return None
+        };
+        if !names.contains(&field_name) {
+            return None;
+        }
+        let key = (String::from(class_name), String::from(field_name));
+        if let Some(constant) = self.statics.get(&key) {
+            return Some(constant.clone());
+        }
+        let constant = self.allocate(class_name, ObjectData::Instance);
+        let name = self.intern(field_name);
+        self.set_field_value(constant, "name", StackValue::Reference(name));
+        self.statics.insert(key, StackValue::Reference(constant));
+        Some(StackValue::Reference(constant))
+    }
+
+    /// the name of a constant made by `nio_constant`.
+    fn constant_name(&self, constant: ObjectRef) -> String {
+        match self.field_value(constant, "name") {
+            StackValue::Reference(name) => self.heap.string_value(name).map(String::from).unwrap_or_default(),
+            _ => String::new()
+        }
+    }
+
+    fn byte_order(&mut self, big_endian: bool) -> StackValue {
+        self.nio_constant(BYTE_ORDER, if big_endian { "BIG_ENDIAN" } else { "LITTLE_ENDIAN" }).unwrap_or(StackValue::Null)
+    }
+
+    fn invoke_byte_order(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        match (method_name, descriptor, arguments.first()) {
+            ("nativeOrder", "()Ljava/nio/ByteOrder;", _) => Some(Ok(Some(self.byte_order(cfg!(target_endian = "big"))))),
+            ("toString", "()Ljava/lang/String;", Some(LocalVariable::Reference(this))) => {
+                let name = self.constant_name(*this);
+                Some(Ok(Some(StackValue::Reference(self.intern(&name)))))
+            }
+            _ => None
+        }
+    }
+
+    fn allocate_byte_buffer(&mut self, capacity: i64, direct: bool) -> Result<Option<StackValue>, RuntimeError> {
+        if capacity < 0 {
+            let message = format!("capacity < 0: ({} < 0)", capacity);
+            return Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some(&message)));
+        }
+        let buffer = match direct {
+            true => {
+                let mut memory = self.allocate_native(capacity as usize, "Direct buffer memory")?;
+                let buffer = self.new_byte_buffer(DIRECT_BYTE_BUFFER, ("address", StackValue::Null), 0, capacity as i32);
+                memory.owner = Some(buffer);
+                self.set_field_value(buffer, "address", StackValue::Long(memory.address() as i64));
+                self.native_memory.insert(memory.address(), memory);
+                buffer
+            }
+            false => {
+                let array = self.allocate("[B", ObjectData::Array(vec![StackValue::Integer(0); capacity as usize]));
+                self.new_byte_buffer(HEAP_BYTE_BUFFER, ("hb", StackValue::Reference(array)), 0, capacity as i32)
+            }
+        };
+        Ok(Some(StackValue::Reference(buffer)))
+    }
+
+    /// a big endian buffer over all of its `capacity`, stored in the memory at `address` or the
+    /// array `hb` from `offset` on.
+    fn new_byte_buffer(&mut self, class_name: &str, storage: (&str, StackValue), offset: usize, capacity: i32) -> ObjectRef {
+        let buffer = self.allocate(class_name, ObjectData::Instance);
+        self.set_field_value(buffer, storage.0, storage.1);
+        self.store_buffer_state(buffer, BufferState { offset, capacity, limit: capacity, position: 0, mark: -1, big_endian: true, read_only: false });
+        buffer
+    }
+
+    fn buffer_state(&self, buffer: ObjectRef) -> BufferState {
+        let field = |name: &str| match self.field_value(buffer, name) {
+            StackValue::Integer(value) => value as i32,
+            _ => 0
+        };
+        BufferState {
+            offset: field("offset") as usize,
+            capacity: field("capacity"),
+            limit: field("limit"),
+            position: field("position"),
+            mark: field("mark"),
+            big_endian: field("bigEndian") != 0,
+            read_only: field("readOnly") != 0,
+        }
+    }
+
+    fn store_buffer_state(&mut self, buffer: ObjectRef, state: BufferState) {
+        let fields = [
+            ("offset", state.offset as i32), ("capacity", state.capacity), ("limit", state.limit), ("position", state.position),
+            ("mark", state.mark), ("bigEndian", state.big_endian as i32), ("readOnly", state.read_only as i32),
+        ];
+        for &(name, value) in fields.iter() {
+            self.set_field_value(buffer, name, StackValue::Integer(i64::from(value)));
+        }
+    }
+
+    /// reads `count` bytes at `index` of a buffer, which the caller checked against its limit.
+    fn read_buffer(&self, buffer: ObjectRef, index: usize, count: usize) -> Vec<u8> {
+        let start = self.buffer_state(buffer).offset + index;
+        let bytes = match (self.field_value(buffer, "hb"), self.field_value(buffer, "address")) {
+            (StackValue::Reference(array), _) => match self.heap.get(array).map(|array| &array.data) {
+                Some(ObjectData::Array(elements)) => elements.get(start..start + count).map(|elements| elements.iter().map(|element| match element {
+                    StackValue::Integer(byte) => *byte as u8,
+                    _ => 0
+                }).collect()),
+                _ => None
+            },
+            (_, StackValue::Long(address)) => self.native_memory.get(&(address as usize))
+                .and_then(|memory| memory.bytes().get(start..start + count))
+                .map(|bytes| bytes.to_vec()),
+            _ => None
+        };
+        bytes.unwrap_or_else(|| vec![0; count])
+    }
+
+    fn write_buffer(&mut self, buffer: ObjectRef, index: usize, bytes: &[u8]) {
+        let start = self.buffer_state(buffer).offset + index;
+        let end = start + bytes.len();
+        match (self.field_value(buffer, "hb"), self.field_value(buffer, "address")) {
+            (StackValue::Reference(array), _) => {
+                if let Some(ObjectData::Array(elements)) = self.heap.get_mut(array).map(|array| &mut array.data) {
+                    if let Some(elements) = elements.get_mut(start..end) {
+                        for (element, byte) in elements.iter_mut().zip(bytes) {
+                            *element = StackValue::Integer(i64::from(*byte as i8));
+                        }
+                    }
+                }
+            }
+            (_, StackValue::Long(address)) => {
+                if let Some(target) = self.native_memory.get_mut(&(address as usize)).filter(|memory| memory.writable).and_then(|memory| memory.bytes_mut().get_mut(start..end)) {
+                    target.copy_from_slice(bytes);
+                }
+            }
+            _ => ()
+        }
+    }
+
+    /// a buffer sharing the storage of `buffer` from `offset` on. direct buffers point to the buffer
+    /// owning their memory in the field `att`, so it is not collected while they are in use.
+    fn derive_buffer(&mut self, buffer: ObjectRef, state: BufferState) -> ObjectRef {
+        let class_name = self.heap.get(buffer).map(|object| object.class_name.clone()).unwrap_or_default();
+        let derived = self.allocate(&class_name, ObjectData::Instance);
+        for name in ["hb", "address"].iter() {
+            let value = self.field_value(buffer, name);
+            if value != StackValue::Null {
+                self.set_field_value(derived, name, value);
+            }
+        }
+        if let StackValue::Long(_) = self.field_value(buffer, "address") {
+            let owner = match self.field_value(buffer, "att") {
+                StackValue::Reference(owner) => owner,
+                _ => buffer
+            };
+            self.set_field_value(derived, "att", StackValue::Reference(owner));
+        }
+        self.store_buffer_state(derived, state);
+        derived
+    }
+
+    /// the arrays of the bulk `get` and `put`, `None` for null arrays and bad bounds.
+    fn byte_array_range(&self, arguments: &[LocalVariable]) -> Option<(ObjectRef, usize, usize)> {
+        let array = match arguments.get(1) {
+            Some(LocalVariable::Reference(array)) => *array,
+            _ => return None
+        };
+        let length = match self.heap.get(array).map(|array| &array.data) {
+            Some(ObjectData::Array(elements)) => elements.len(),
+            _ => return None
+        };
+        match (arguments.get(2), arguments.get(3)) {
+            (Some(LocalVariable::Integer(offset)), Some(LocalVariable::Integer(count))) if *offset >= 0 && *count >= 0 && (*offset + *count) as usize <= length =>
+                Some((array, *offset as usize, *count as usize)),
+            (None, None) => Some((array, 0, length)),
+            _ => None
+        }
+    }
+
+    fn invoke_byte_buffer(&mut self, this: ObjectRef, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let mut state = self.buffer_state(this);
+        let direct = self.field_value(this, "address") != StackValue::Null;
+        let parameters = &descriptor[..=descriptor.find(')')?];
+        let int = |index: usize| match arguments.get(index) {
+            Some(LocalVariable::Integer(value)) => Some(*value as i32),
+            _ => None
+        };
+        let returns_this = Some(Ok(Some(StackValue::Reference(this))));
+
+        if method_name.starts_with("get") || method_name.starts_with("put") {
+            let (access, type_name) = method_name.split_at(3);
+            if let Some(size) = value_size(type_name) {
+                let value_type = match type_name {
+                    "" => "B",
+                    "Short" => "S",
+                    "Char" => "C",
+                    "Int" => "I",
+                    _ => "J"
+                };
+                let access = BufferAccess { put: access == "put", size, type_name, value_type, parameters };
+                return self.access_byte_buffer(this, state, access, arguments);
+            }
+        }
+
+        let value = match (method_name, parameters) {
+            ("capacity", "()") => StackValue::Integer(i64::from(state.capacity)),
+            ("position", "()") => StackValue::Integer(i64::from(state.position)),
+            ("limit", "()") => StackValue::Integer(i64::from(state.limit)),
+            ("remaining", "()") => StackValue::Integer(i64::from(state.remaining().max(0))),
+            ("hasRemaining", "()") => StackValue::Integer(i64::from(state.remaining() > 0)),
+            ("isDirect", "()") => StackValue::Integer(i64::from(direct)),
+            ("isReadOnly", "()") => StackValue::Integer(i64::from(state.read_only)),
+            ("hasArray", "()") => StackValue::Integer(i64::from(!direct && !state.read_only)),
+            ("isLoaded", "()") => StackValue::Integer(1),
+            ("array", "()") | ("arrayOffset", "()") => {
+                if direct {
+                    return Some(Err(self.throw_in_caller("java/lang/UnsupportedOperationException", None)));
+                }
+                if state.read_only {
+                    return Some(Err(self.throw_in_caller("java/nio/ReadOnlyBufferException", None)));
+                }
+                match method_name {
+                    "array" => self.field_value(this, "hb"),
+                    _ => StackValue::Integer(state.offset as i64)
+                }
+            }
+            ("order", "()") => self.byte_order(state.big_endian),
+            ("order", "(Ljava/nio/ByteOrder;)") => {
+                let order = match arguments.get(1) {
+                    Some(LocalVariable::Reference(order)) => *order,
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                state.big_endian = self.constant_name(order) == "BIG_ENDIAN";
+                self.store_buffer_state(this, state);
+                return returns_this;
+            }
+            ("position", "(I)") | ("limit", "(I)") => {
+                let value = int(1)?;
+                let bound = if method_name == "position" { state.limit } else { state.capacity };
+                if value < 0 || value > bound {
+                    let message = match (method_name, value < 0) {
+                        ("position", true) => format!("newPosition < 0: ({} < 0)", value),
+                        ("position", false) => format!("newPosition > limit: ({} > {})", value, bound),
+                        (_, true) => format!("newLimit < 0: ({} < 0)", value),
+                        (_, false) => format!("newLimit > capacity: ({} > {})", value, bound)
+                    };
+                    return Some(Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some(&message))));
+                }
+                if method_name == "position" {
+                    state.position = value;
+                } else {
+                    state.limit = value;
+                    state.position = state.position.min(value);
+                }
+                if state.mark > value {
+                    state.mark = -1;
+                }
+                self.store_buffer_state(this, state);
+                return returns_this;
+            }
+            ("mark", "()") | ("reset", "()") | ("clear", "()") | ("flip", "()") | ("rewind", "()") => {
+                match method_name {
+                    "mark" => state.mark = state.position,
+                    "reset" if state.mark < 0 => return Some(Err(self.throw_in_caller("java/nio/InvalidMarkException", None))),
+                    "reset" => state.position = state.mark,
+                    "clear" => {
+                        state.position = 0;
+                        state.limit = state.capacity;
+                        state.mark = -1;
+                    }
+                    "flip" => {
+                        state.limit = state.position;
+                        state.position = 0;
+                        state.mark = -1;
+                    }
+                    _ => {
+                        state.position = 0;
+                        state.mark = -1;
+                    }
+                }
+                self.store_buffer_state(this, state);
+                return returns_this;
+            }
+            ("compact", "()") => {
+                if state.read_only {
+                    return Some(Err(self.throw_in_caller("java/nio/ReadOnlyBufferException", None)));
+                }
+                let remaining = state.remaining().max(0);
+                let bytes = self.read_buffer(this, state.position as usize, remaining as usize);
+                self.write_buffer(this, 0, &bytes);
+                state.position = remaining;
+                state.limit = state.capacity;
+                state.mark = -1;
+                self.store_buffer_state(this, state);
+                return returns_this;
+            }
+            // derived buffers start out big endian, whatever the order of the original
+            ("slice", "()") => {
+                let remaining = state.remaining().max(0);
+                let offset = state.offset + state.position as usize;
+                StackValue::Reference(self.derive_buffer(this, BufferState { offset, capacity: remaining, limit: remaining, position: 0, mark: -1, big_endian: true, ..state }))
+            }
+            ("duplicate", "()") => StackValue::Reference(self.derive_buffer(this, BufferState { big_endian: true, ..state })),
+            ("asReadOnlyBuffer", "()") => StackValue::Reference(self.derive_buffer(this, BufferState { big_endian: true, read_only: true, ..state })),
+            ("force", "()") | ("load", "()") => {
+                if method_name == "force" {
+                    if let StackValue::Long(address) = self.field_value(this, "address") {
+                        if let Some(Err(err)) = self.native_memory.get(&(address as usize)).map(NativeMemory::force) {
+                            return Some(Err(self.throw_in_caller("java/io/UncheckedIOException", Some(&err.to_string()))));
+                        }
+                    }
+                }
+                return returns_this;
+            }
+            ("toString", "()") => {
+                let class_name = self.heap.get(this).map(|object| object.class_name.replace('/', ".")).unwrap_or_default();
+                let text = format!("{}[pos={} lim={} cap={}]", class_name, state.position, state.limit, state.capacity);
+                StackValue::Reference(self.allocate("java/lang/String", ObjectData::String(text.into())))
+            }
+            _ => return None
+        };
+        Some(Ok(Some(value)))
+    }
+
+    /// the `get` and `put` methods of `ByteBuffer`: relative and absolute, of single values of each
+    /// type and in bulk from arrays and other buffers.
+    fn access_byte_buffer(&mut self, this: ObjectRef, mut state: BufferState, access: BufferAccess, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let BufferAccess { put, size, type_name, value_type, parameters } = access;
+        if put && state.read_only {
+            return Some(Err(self.throw_in_caller("java/nio/ReadOnlyBufferException", None)));
+        }
+        let relative = format!("({})", if put { value_type } else { "" });
+        let absolute = format!("(I{})", if put { value_type } else { "" });
+
+        if parameters == relative || parameters == absolute {
+            let index = match (parameters == relative, arguments.get(1)) {
+                (true, _) => {
+                    if (state.remaining() as usize) < size {
+                        let exception = if put { "java/nio/BufferOverflowException" } else { "java/nio/BufferUnderflowException" };
+                        return Some(Err(self.throw_in_caller(exception, None)));
+                    }
+                    state.position += size as i32;
+                    self.store_buffer_state(this, state);
+                    (state.position - size as i32) as usize
+                }
+                (false, Some(LocalVariable::Integer(index))) if *index >= 0 && *index as usize + size <= state.limit as usize => *index as usize,
+                (false, _) => return Some(Err(self.throw_in_caller("java/lang/IndexOutOfBoundsException", None)))
+            };
+            if !put {
+                let bytes = self.read_buffer(this, index, size);
+                return Some(Ok(Some(decode_value(&bytes, state.big_endian, type_name))));
+            }
+            let bytes = encode_value(arguments.last()?, size, state.big_endian);
+            self.write_buffer(this, index, &bytes);
+            return Some(Ok(Some(StackValue::Reference(this))));
+        }
+
+        if !type_name.is_empty() {
+            return None;
+        }
+        // bulk transfers move all of their bytes or none
+        let (bytes, source) = match parameters {
+            "([B)" | "([BII)" => {
+                let (array, offset, count) = match self.byte_array_range(arguments) {
+                    Some(range) => range,
+                    None => return Some(Err(self.throw_in_caller("java/lang/IndexOutOfBoundsException", None)))
+                };
+                match put {
+                    true => (self.read_buffer_array(array, offset, count), None),
+                    false => (Vec::new(), Some((array, offset, count)))
+                }
+            }
+            "(Ljava/nio/ByteBuffer;)" if put => {
+                let source = match arguments.get(1) {
+                    Some(LocalVariable::Reference(source)) if *source != this => *source,
+                    Some(LocalVariable::Reference(_)) => return Some(Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("The source buffer is this buffer")))),
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let mut source_state = self.buffer_state(source);
+                let remaining = source_state.remaining().max(0);
+                if remaining > state.remaining() {
+                    return Some(Err(self.throw_in_caller("java/nio/BufferOverflowException", None)));
+                }
+                let bytes = self.read_buffer(source, source_state.position as usize, remaining as usize);
+                source_state.position += remaining;
+                self.store_buffer_state(source, source_state);
+                (bytes, None)
+            }
+            _ => return None
+        };
+        match source {
+            Some((array, offset, count)) => {
+                if count > state.remaining() as usize {
+                    return Some(Err(self.throw_in_caller("java/nio/BufferUnderflowException", None)));
+                }
+                let bytes = self.read_buffer(this, state.position as usize, count);
+                if let Some(ObjectData::Array(elements)) = self.heap.get_mut(array).map(|array| &mut array.data) {
+                    for (element, byte) in elements[offset..offset + count].iter_mut().zip(bytes) {
+                        *element = StackValue::Integer(i64::from(byte as i8));
+                    }
+                }
+                state.position += count as i32;
+            }
+            None => {
+                if bytes.len() > state.remaining() as usize {
+                    return Some(Err(self.throw_in_caller("java/nio/BufferOverflowException", None)));
+                }
+                self.write_buffer(this, state.position as usize, &bytes);
+                state.position += bytes.len() as i32;
+            }
+        }
+        self.store_buffer_state(this, state);
+        Some(Ok(Some(StackValue::Reference(this))))
+    }
+
+    fn read_buffer_array(&self, array: ObjectRef, offset: usize, count: usize) -> Vec<u8> {
+        match self.heap.get(array).map(|array| &array.data) {
+            Some(ObjectData::Array(elements)) => elements[offset..offset + count].iter().map(|element| match element {
+                StackValue::Integer(byte) => *byte as u8,
+                _ => 0
+            }).collect(),
+            _ => Vec::new()
+        }
+    }
+
+    fn file_of(&self, object: ObjectRef) -> Option<usize> {
+        match self.field_value(object, "handle") {
+            StackValue::Integer(handle) if self.files.get(handle as usize).is_some_and(Option::is_some) => Some(handle as usize),
+            _ => None
+        }
+    }
+
+    fn invoke_file(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let this = match arguments.first() {
+            Some(LocalVariable::Reference(this)) => *this,
+            _ => return None
+        };
+        let value = match (class_name, method_name, descriptor) {
+            (RANDOM_ACCESS_FILE, "<init>", "(Ljava/lang/String;Ljava/lang/String;)V") => {
+                let strings = arguments[1..].iter().map(|argument| match argument {
+                    LocalVariable::Reference(string) => self.heap.string_value(*string).map(String::from),
+                    _ => None
+                }).collect::<Option<Vec<String>>>();
+                let (path, mode) = match strings.as_deref() {
+                    Some([path, mode]) => (path.clone(), mode.clone()),
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let writable = match mode.as_str() {
+                    "r" => false,
+                    "rw" | "rws" | "rwd" => true,
+                    _ => {
+                        let message = format!("Illegal mode \"{}\" must be one of \"r\", \"rw\", \"rws\", or \"rwd\"", mode);
+                        return Some(Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some(&message))));
+                    }
+                };
+                let context = NativeContext::new(&mut self.environment, &self.capabilities);
+                let opened = match writable {
+                    true => context.open_read_write(path.as_ref()),
+                    false => context.open_read(path.as_ref())
+                };
+                let file = match opened {
+                    Ok(file) => file,
+                    Err(RuntimeError::Host { message, .. }) => {
+                        let message = format!("{} ({})", path, message);
+                        return Some(Err(self.throw_in_caller("java/io/FileNotFoundException", Some(&message))));
+                    }
+                    Err(err) => return Some(Err(err))
+                };
+                let handle = StackValue::Integer(self.files.len() as i64);
+                self.files.push(Some(OpenFile { file, writable }));
+                self.set_field_value(this, "handle", handle);
+                return Some(Ok(None));
+            }
+            (RANDOM_ACCESS_FILE, "getChannel", "()Ljava/nio/channels/FileChannel;") => {
+                if let StackValue::Reference(channel) = self.field_value(this, "channel") {
+                    return Some(Ok(Some(StackValue::Reference(channel))));
+                }
+                let channel = self.allocate(FILE_CHANNEL, ObjectData::Instance);
+                let handle = self.field_value(this, "handle");
+                self.set_field_value(channel, "handle", handle);
+                self.set_field_value(this, "channel", StackValue::Reference(channel));
+                StackValue::Reference(channel)
+            }
+            (_, "close", "()V") => {
+                if let StackValue::Integer(handle) = self.field_value(this, "handle") {
+                    if let Some(file) = self.files.get_mut(handle as usize) {
+                        *file = None;
+                    }
+                }
+                return Some(Ok(None));
+            }
+            (FILE_CHANNEL, "isOpen", "()Z") => StackValue::Integer(i64::from(self.file_of(this).is_some())),
+            (RANDOM_ACCESS_FILE, "length", "()J") | (FILE_CHANNEL, "size", "()J") => {
+                let handle = match self.file_of(this) {
+                    Some(handle) => handle,
+                    None => return Some(Err(self.throw_closed(class_name)))
+                };
+                match self.files[handle].as_ref().map(|file| file.file.metadata()) {
+                    Some(Ok(metadata)) => StackValue::Long(metadata.len() as i64),
+                    Some(Err(err)) => return Some(Err(self.throw_in_caller("java/io/IOException", Some(&err.to_string())))),
+                    None => StackValue::Long(0)
+                }
+            }
+            (FILE_CHANNEL, "map", "(Ljava/nio/channels/FileChannel$MapMode;JJ)Ljava/nio/MappedByteBuffer;") => return Some(self.map_file(this, arguments)),
+            _ => return None
+        };
+        Some(Ok(Some(value)))
+    }
+
+    fn throw_closed(&mut self, class_name: &str) -> RuntimeError {
+        match class_name {
+            FILE_CHANNEL => self.throw_in_caller("java/nio/channels/ClosedChannelException", None),
+            _ => self.throw_in_caller("java/io/IOException", Some("Stream Closed"))
+        }
+    }
+
+    /// `FileChannel.map`. writable mappings grow the file to cover them, like the class library.
+    fn map_file(&mut self, channel: ObjectRef, arguments: &[LocalVariable]) -> Result<Option<StackValue>, RuntimeError> {
+        let (mode, position, size) = match (arguments.get(1), arguments.get(2), arguments.get(3)) {
+            (Some(LocalVariable::Reference(mode)), Some(LocalVariable::Long(position)), Some(LocalVariable::Long(size))) => (*mode, *position, *size),
+            (Some(LocalVariable::Null), _, _) => return Err(self.throw_in_caller("java/lang/NullPointerException", Some("Mode is null"))),
+            _ => return Err(RuntimeError::StackType { expected: String::from("(Ljava/nio/channels/FileChannel$MapMode;JJ)") })
+        };
+        let mode = match self.constant_name(mode).as_str() {
+            "READ_WRITE" => MapMode::ReadWrite,
+            "PRIVATE" => MapMode::Private,
+            _ => MapMode::ReadOnly
+        };
+        if position < 0 {
+            return Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("Negative position")));
+        }
+        if size < 0 {
+            return Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("Negative size")));
+        }
+        if size > i64::from(i32::MAX) {
+            return Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("Size exceeds Integer.MAX_VALUE")));
+        }
+        let handle = match self.file_of(channel) {
+            Some(handle) => handle,
+            None => return Err(self.throw_closed(FILE_CHANNEL))
+        };
+        let writable = self.files[handle].as_ref().is_some_and(|file| file.writable);
+        if mode != MapMode::ReadOnly && !writable {
+            return Err(self.throw_in_caller("java/nio/channels/NonWritableChannelException", None));
+        }
+
+        // mappings start at a page, the buffer starts within the first one
+        let start = position as u64 / page_size() as u64 * page_size() as u64;
+        let offset = (position as u64 - start) as usize;
+        let buffer = self.new_byte_buffer(DIRECT_BYTE_BUFFER, ("address", StackValue::Null), offset, size as i32);
+        let mapped = self.files[handle].as_ref().map(|open| {
+            let file = &open.file;
+            let end = (position + size) as u64;
+            if mode == MapMode::ReadWrite && file.metadata()?.len() < end {
+                file.set_len(end)?;
+            }
+            NativeMemory::map(file, mode, start, offset + size as usize, buffer)
+        });
+        let memory = match mapped {
+            Some(Ok(memory)) => memory,
+            Some(Err(err)) => return Err(self.throw_in_caller("java/io/IOException", Some(&err.to_string()))),
+            None => return Err(self.throw_closed(FILE_CHANNEL))
+        };
+        debug!(address = memory.address(), length = memory.length, mode = ?mode, "mapped file");
+        self.set_field_value(buffer, "address", StackValue::Long(memory.address() as i64));
+        self.set_field_value(buffer, "readOnly", StackValue::Integer(i64::from(mode == MapMode::ReadOnly)));
+        self.native_memory.insert(memory.address(), memory);
+        Ok(Some(StackValue::Reference(buffer)))
+    }
+
+    /// the memory of `count` bytes at `address` for `Unsafe`, which throws an `InternalError` for
+    /// anything but memory the program allocated or mapped.
+    fn unsafe_memory(&mut self, address: i64, count: usize, write: bool) -> Result<(usize, usize), RuntimeError> {
+        let address = address as usize;
+        let found = self.native_memory.range(..=address).next_back()
+            .filter(|&(start, memory)| address - start + count <= memory.length && (memory.writable || !write))
+            .map(|(start, _)| (*start, address - start));
+        found.ok_or_else(|| self.throw_in_caller("java/lang/InternalError", Some(UNSAFE_FAULT)))
+    }
+
+    fn invoke_unsafe(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let long = |index: usize| match arguments.get(index) {
+            Some(LocalVariable::Long(value)) => Some(*value),
+            _ => None
+        };
+        let value = match (method_name, descriptor) {
+            ("getUnsafe", "()Lsun/misc/Unsafe;") => {
+                let key = (String::from(UNSAFE), String::from("theUnsafe"));
+                if let Some(unsafe_object) = self.statics.get(&key) {
+                    return Some(Ok(Some(unsafe_object.clone())));
+                }
+                let unsafe_object = StackValue::Reference(self.allocate(UNSAFE, ObjectData::Instance));
+                self.statics.insert(key, unsafe_object.clone());
+                unsafe_object
+            }
+            ("addressSize", "()I") => StackValue::Integer(std::mem::size_of::<usize>() as i64),
+            ("pageSize", "()I") => StackValue::Integer(page_size() as i64),
+            ("allocateMemory", "(J)J") => {
+                let size = long(1)?;
+                if size < 0 {
+                    return Some(Err(self.throw_in_caller("java/lang/IllegalArgumentException", None)));
+                }
+                if size == 0 {
+                    return Some(Ok(Some(StackValue::Long(0))));
+                }
+                let memory = match self.allocate_native(size as usize, &format!("Unable to allocate {} bytes", size)) {
+                    Ok(memory) => memory,
+                    Err(err) => return Some(Err(err))
+                };
+                let address = memory.address();
+                self.native_memory.insert(address, memory);
+                StackValue::Long(address as i64)
+            }
+            ("freeMemory", "(J)V") => {
+                let address = long(1)? as usize;
+                // memory of buffers is only released with them
+                match self.native_memory.get(&address).map(|memory| memory.owner.is_none()) {
+                    Some(true) => {
+                        self.native_memory.remove(&address);
+                    }
+                    Some(false) => return Some(Err(self.throw_in_caller("java/lang/InternalError", Some(UNSAFE_FAULT)))),
+                    None if address == 0 => (),
+                    None => return Some(Err(self.throw_in_caller("java/lang/InternalError", Some(UNSAFE_FAULT))))
+                }
+                return Some(Ok(None));
+            }
+            ("setMemory", "(JJB)V") => {
+                let (address, count) = (long(1)?, long(2)?.max(0) as usize);
+                let byte = match arguments.get(3) {
+                    Some(LocalVariable::Integer(byte)) => *byte as u8,
+                    _ => 0
+                };
+                let (start, offset) = match self.unsafe_memory(address, count, true) {
+                    Ok(found) => found,
+                    Err(err) => return Some(Err(err))
+                };
+                if let Some(memory) = self.native_memory.get_mut(&start) {
+                    memory.bytes_mut()[offset..offset + count].iter_mut().for_each(|target| *target = byte);
+                }
+                return Some(Ok(None));
+            }
+            ("copyMemory", "(JJJ)V") => {
+                let (source, target, count) = (long(1)?, long(2)?, long(3)?.max(0) as usize);
+                let bytes = match self.unsafe_memory(source, count, false) {
+                    Ok((start, offset)) => self.native_memory[&start].bytes()[offset..offset + count].to_vec(),
+                    Err(err) => return Some(Err(err))
+                };
+                let (start, offset) = match self.unsafe_memory(target, count, true) {
+                    Ok(found) => found,
+                    Err(err) => return Some(Err(err))
+                };
+                if let Some(memory) = self.native_memory.get_mut(&start) {
+                    memory.bytes_mut()[offset..offset + count].copy_from_slice(&bytes);
+                }
+                return Some(Ok(None));
+            }
+            _ => {
+                // the typed accessors, in the byte order of the platform
+                let big_endian = cfg!(target_endian = "big");
+                let (access, type_name) = method_name.split_at(method_name.len().min(3));
+                let size = value_size(type_name)?;
+                let address = long(1)?;
+                match access {
+                    "get" if descriptor.starts_with("(J)") => {
+                        let (start, offset) = match self.unsafe_memory(address, size, false) {
+                            Ok(found) => found,
+                            Err(err) => return Some(Err(err))
+                        };
+                        decode_value(&self.native_memory[&start].bytes()[offset..offset + size], big_endian, type_name)
+                    }
+                    "put" if descriptor.len() == 5 && descriptor.starts_with("(J") && descriptor.ends_with(")V") => {
+                        let (start, offset) = match self.unsafe_memory(address, size, true) {
+                            Ok(found) => found,
+                            Err(err) => return Some(Err(err))
+                        };
+                        let bytes = encode_value(arguments.get(2)?, size, big_endian);
+                        if let Some(memory) = self.native_memory.get_mut(&start) {
+                            memory.bytes_mut()[offset..offset + size].copy_from_slice(&bytes);
+                        }
+                        return Some(Ok(None));
+                    }
+                    _ => return None
+                }
+            }
+        };
+        Some(Ok(Some(value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::Capabilities;
+    use std::fs;
+    use super::*;
+
+    fn runtime() -> Runtime<'static> {
+        Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Buffers.class")).unwrap().1)
+    }
+
+    fn call(rt: &mut Runtime, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        rt.invoke_static("Buffers", method_name, descriptor, arguments)
+    }
+
+    fn expect_exception(result: Result<Option<StackValue>, RuntimeError>, exception: &str) {
+        match result {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == exception => (),
+            other => panic!("expected a {}, got {:?}", exception, other)
+        }
+    }
+
+    #[test]
+    fn direct_buffers_keep_their_position_and_byte_order() {
+        let mut rt = runtime();
+        let values = match call(&mut rt, "direct", "()[I", vec![]).unwrap() {
+            Some(StackValue::Reference(array)) => match rt.heap.get(array).map(|array| &array.data) {
+                Some(ObjectData::Array(elements)) => elements.clone(),
+                other => panic!("expected an array, got {:?}", other)
+            },
+            other => panic!("expected an array, got {:?}", other)
+        };
+        let expected = [1, 7, 0x0403_0201, 1, 6, 5, -1, 3, 1].iter().map(|&value| StackValue::Integer(value)).collect::<Vec<_>>();
+        assert_eq!(values, expected);
+        for &direct in [true, false].iter() {
+            assert_eq!(call(&mut rt, "longs", "(ZJ)J", vec![LocalVariable::Integer(i64::from(direct)), LocalVariable::Long(-2)]).unwrap(), Some(StackValue::Long(-2)));
+        }
+        expect_exception(call(&mut rt, "overflow", "()V", vec![]), "java/nio/BufferOverflowException");
+        expect_exception(call(&mut rt, "readOnly", "()V", vec![]), "java/nio/ReadOnlyBufferException");
+    }
+
+    #[test]
+    fn native_memory_is_released_with_its_buffer() {
+        let mut rt = runtime();
+        let buffer = match call(&mut rt, "allocate", "(I)Ljava/nio/ByteBuffer;", vec![LocalVariable::Integer(64)]).unwrap() {
+            Some(StackValue::Reference(buffer)) => buffer,
+            other => panic!("expected a buffer, got {:?}", other)
+        };
+        let address = rt.direct_buffer_address(buffer).unwrap();
+        rt.native_memory_mut(address, 64).unwrap()[0] = 42;
+        assert_eq!(call(&mut rt, "peek", "(J)I", vec![LocalVariable::Long(address as i64)]).unwrap(), Some(StackValue::Integer(42)));
+        assert_eq!(rt.native_memory_used(), 64);
+
+        rt.gc();
+        assert_eq!(rt.native_memory_used(), 0);
+        assert!(rt.native_memory(address, 1).is_none());
+        expect_exception(call(&mut rt, "peek", "(J)I", vec![LocalVariable::Long(address as i64)]), "java/lang/InternalError");
+
+        assert_eq!(call(&mut rt, "unsafe", "(J)I", vec![LocalVariable::Long(8)]).unwrap(), Some(StackValue::Integer(42)));
+        assert_eq!(rt.native_memory_used(), 0);
+    }
+
+    #[test]
+    fn files_are_mapped_into_buffers() {
+        let path = std::env::temp_dir().join(format!("rjvm-mapped-{}", std::process::id()));
+        fs::write(&path, "abcd").unwrap();
+        let mut rt = runtime();
+        let name = rt.intern(path.to_str().unwrap());
+
+        match call(&mut rt, "read", "(Ljava/lang/String;)Ljava/lang/String;", vec![LocalVariable::Reference(name)]).unwrap() {
+            Some(StackValue::Reference(text)) => assert_eq!(rt.heap.string_value(text), Some("abcd")),
+            other => panic!("expected a string, got {:?}", other)
+        }
+        // the mapping grows the file to eight bytes
+        assert_eq!(call(&mut rt, "write", "(Ljava/lang/String;J)I", vec![LocalVariable::Reference(name), LocalVariable::Long(8)]).unwrap(), Some(StackValue::Integer(0x6162_6364)));
+        assert_eq!(fs::read(&path).unwrap(), b"abcdabcd");
+
+        let mut denied = Runtime::builder().capabilities(Capabilities::none().allow_read(std::env::temp_dir()))
+            .build(read_class_file(include_bytes!("../../../sample/Buffers.class")).unwrap().1);
+        let name = denied.intern(path.to_str().unwrap());
+        match call(&mut denied, "write", "(Ljava/lang/String;J)I", vec![LocalVariable::Reference(name), LocalVariable::Long(8)]).map_err(|err| format!("{:?}", err.root())) {
+            Err(ref err) if err.starts_with("PermissionDenied") => (),
+            other => panic!("expected the write to be denied, got {:?}", other)
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn direct_buffers_count_against_the_maximum_heap_size() {
+        let mut rt = Runtime::builder().max_heap(Some(4096))
+            .build(read_class_file(include_bytes!("../../../sample/Buffers.class")).unwrap().1);
+        expect_exception(call(&mut rt, "allocate", "(I)Ljava/nio/ByteBuffer;", vec![LocalVariable::Integer(8192)]), "java/lang/OutOfMemoryError");
+        assert_eq!(rt.native_memory_used(), 0);
+
+        // each buffer is garbage once the next one is allocated, a collection makes room for it
+        for _ in 0..3 {
+            call(&mut rt, "allocate", "(I)Ljava/nio/ByteBuffer;", vec![LocalVariable::Integer(2048)]).unwrap();
+        }
+        assert_eq!(rt.native_memory_used(), 2048);
+    }
+}
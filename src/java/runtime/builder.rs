@@ -0,0 +1,423 @@
+use java::class_file::ClassFile;
+use java::runtime::{AllocationProfiler, AssertionStatus, Breakpoints, ByteStore, Capabilities, CancellationHandle, CheckpointTrigger, ClassArchive, ClassLoadListener, ClassLoaders, ClassRegistry, Cleaners, DEFAULT_LARGE_OBJECT_THRESHOLD, DEFAULT_MAX_STACK_DEPTH, Environment, ExecutionMode, GcStats, Heap, HeapDumpTrigger, InputLog, InterpreterMode, InvocationCounters, JavaThread, Journal, ModuleGraph, NativeRegistry, Output, Runtime, SafepointHandle, ThreadDumpTrigger, WallClock};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+/// configures a `Runtime` before it is created.
+///
+/// `Runtime::create` is a shorthand for `RuntimeBuilder::new().build(main_class)`.
+#[derive(Debug)]
+pub struct RuntimeBuilder<'a> {
+    classpath: Vec<PathBuf>,
+    module_path: Vec<PathBuf>,
+    added_exports: Vec<(String, String, String)>,
+    mode: ExecutionMode,
+    journal: Journal,
+    random_seed: Option<u64>,
+    clock: Option<Box<dyn WallClock>>,
+    capabilities: Capabilities,
+    crash_dump_path: Option<PathBuf>,
+    stdout: Output,
+    stderr: Output,
+    allocation_profiling: bool,
+    max_heap: Option<usize>,
+    max_stack_depth: usize,
+    heap_dump_path: PathBuf,
+    heap_dump_on_out_of_memory: bool,
+    verbose_gc: bool,
+    verbose_jni: bool,
+    string_deduplication: bool,
+    large_object_threshold: usize,
+    finalization: bool,
+    explain: Option<Output>,
+    interpreter: InterpreterMode,
+    hot_method_threshold: u64,
+    inlining: bool,
+    intrinsics: bool,
+    escape_analysis: bool,
+    optimize_bytecode: bool,
+    eager_loading: Option<usize>,
+    class_archive: Option<PathBuf>,
+    assertions: AssertionStatus,
+    class_load_listeners: Vec<Box<dyn ClassLoadListener>>,
+    byte_store: Option<&'a ByteStore>,
+}
+
+impl<'a> Default for RuntimeBuilder<'a> {
+    fn default() -> RuntimeBuilder<'a> {
+        RuntimeBuilder::new()
+    }
+}
+
+impl<'a> RuntimeBuilder<'a> {
+    pub fn new() -> RuntimeBuilder<'a> {
+        RuntimeBuilder {
+            classpath: vec![PathBuf::from(".")],
+            module_path: Vec::new(),
+            added_exports: Vec::new(),
+            mode: ExecutionMode::Native,
+            journal: Journal::Off,
+            random_seed: None,
+            clock: None,
+            capabilities: Capabilities::all(),
+            crash_dump_path: None,
+            stdout: Output::stdout(),
+            stderr: Output::stderr(),
+            allocation_profiling: false,
+            max_heap: None,
+            max_stack_depth: DEFAULT_MAX_STACK_DEPTH,
+            heap_dump_path: PathBuf::from(format!("java_pid{}.hprof", process::id())),
+            heap_dump_on_out_of_memory: false,
+            verbose_gc: false,
+            verbose_jni: false,
+            string_deduplication: false,
+            large_object_threshold: DEFAULT_LARGE_OBJECT_THRESHOLD,
+            finalization: false,
+            explain: None,
+            interpreter: InterpreterMode::Checked,
+            hot_method_threshold: 10_000,
+            inlining: true,
+            intrinsics: true,
+            escape_analysis: true,
+            optimize_bytecode: false,
+            eager_loading: None,
+            class_archive: None,
+            assertions: AssertionStatus::new(),
+            class_load_listeners: Vec::new(),
+            byte_store: None,
+        }
+    }
+
+    pub fn classpath(mut self, classpath: Vec<PathBuf>) -> RuntimeBuilder<'a> {
+        self.classpath = classpath;
+        self
+    }
+
+    /// the directories of modules, or of directories of exploded modules, like `--module-path`.
+    /// the classes of these modules are loaded while the runtime is built and can only access the
+    /// packages the modules they read export to them.
+    pub fn module_path(mut self, module_path: Vec<PathBuf>) -> RuntimeBuilder<'a> {
+        self.module_path = module_path;
+        self
+    }
+
+    /// lets the classes of `target` access `package` of `module` although it is not exported to
+    /// them, like `--add-exports module/package=target`. `target` may be `ALL_UNNAMED`.
+    pub fn add_exports(mut self, module: &str, package: &str, target: &str) -> RuntimeBuilder<'a> {
+        self.added_exports.push((String::from(module), String::from(package), String::from(target)));
+        self
+    }
+
+    /// replaces time, random seeds and hash codes with reproducible values derived from `seed`.
+    /// there is only one java thread, so `Environment::schedule` is not used by the interpreter yet.
+    pub fn deterministic(mut self, seed: u64) -> RuntimeBuilder<'a> {
+        self.mode = ExecutionMode::Deterministic { seed };
+        self
+    }
+
+    /// derives the seeds of `java.util.Random` instances created without one and the bytes of
+    /// `java.security.SecureRandom` from `seed`, for reproducible tests of programs which use
+    /// randomness. unlike `deterministic` the clock and hash codes still come from the host.
+    pub fn random_seed(mut self, seed: u64) -> RuntimeBuilder<'a> {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// the wall clock of `System.currentTimeMillis` and `Instant.now`, instead of the one of the
+    /// host or of the deterministic mode. for programs which should run at a fixed or shifted time.
+    pub fn clock<C: WallClock + 'static>(mut self, clock: C) -> RuntimeBuilder<'a> {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// records every nondeterministic input, see `Environment::recording`.
+    pub fn record_inputs(mut self) -> RuntimeBuilder<'a> {
+        self.journal = Journal::Record(InputLog::new());
+        self
+    }
+
+    /// feeds the inputs of a recorded execution to the program instead of reading them from the host,
+    /// so it runs exactly like it did when it was recorded.
+    pub fn replay(mut self, log: InputLog) -> RuntimeBuilder<'a> {
+        self.journal = Journal::Replay { log, position: 0, diverged: None };
+        self
+    }
+
+    /// restricts what natives may do on the host. everything is allowed by default.
+    pub fn capabilities(mut self, capabilities: Capabilities) -> RuntimeBuilder<'a> {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// where to write the report when an internal error escapes the runtime, like
+    /// `hs_err_pid<pid>.log` of the launcher. `None`, the default, disables the reports.
+    pub fn crash_dump_path(mut self, path: Option<PathBuf>) -> RuntimeBuilder<'a> {
+        self.crash_dump_path = path;
+        self
+    }
+
+    /// where `System.out` writes to, the standard output of the process by default.
+    pub fn stdout(mut self, stdout: Output) -> RuntimeBuilder<'a> {
+        self.stdout = stdout;
+        self
+    }
+
+    /// where `System.err` writes to, the standard error of the process by default.
+    pub fn stderr(mut self, stderr: Output) -> RuntimeBuilder<'a> {
+        self.stderr = stderr;
+        self
+    }
+
+    /// records every allocation for `Runtime::allocation_profile`. off by default.
+    pub fn allocation_profiling(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.allocation_profiling = enabled;
+        self
+    }
+
+    /// the maximum number of bytes on the heap, like `-Xmx`. the memory of direct buffers and
+    /// `Unsafe.allocateMemory` counts against it too.
+    /// allocations beyond it throw an `OutOfMemoryError`. unlimited by default.
+    pub fn max_heap(mut self, bytes: Option<usize>) -> RuntimeBuilder<'a> {
+        self.max_heap = bytes;
+        self
+    }
+
+    /// the maximum number of methods running on a thread at once, like `-Xss`. calls beyond it
+    /// throw a `StackOverflowError`. every call also nests on the stack of the native thread, so
+    /// deep limits need a thread with a big enough stack.
+    pub fn max_stack_depth(mut self, depth: usize) -> RuntimeBuilder<'a> {
+        self.max_stack_depth = depth;
+        self
+    }
+
+    /// where heap dumps requested through the `HeapDumpTrigger` or on `OutOfMemoryError` are written.
+    /// defaults to `java_pid<pid>.hprof` in the working directory.
+    pub fn heap_dump_path(mut self, path: PathBuf) -> RuntimeBuilder<'a> {
+        self.heap_dump_path = path;
+        self
+    }
+
+    /// dumps the heap before an `OutOfMemoryError` is thrown, like `-XX:+HeapDumpOnOutOfMemoryError`.
+    pub fn heap_dump_on_out_of_memory(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.heap_dump_on_out_of_memory = enabled;
+        self
+    }
+
+    /// prints a line for every garbage collection to the `stdout` output, like `-verbose:gc`.
+    pub fn verbose_gc(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.verbose_gc = enabled;
+        self
+    }
+
+    /// prints a line with the arguments, result and duration of every call of a registered
+    /// native to the stdout of the runtime, like `-verbose:jni`.
+    pub fn verbose_jni(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.verbose_jni = enabled;
+        self
+    }
+
+    /// lets every garbage collection make equal strings share their characters, like
+    /// `-XX:+UseStringDeduplication`.
+    pub fn string_deduplication(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.string_deduplication = enabled;
+        self
+    }
+
+    /// objects of at least `bytes` are allocated in the large object space, where collections never
+    /// move them, like `-XX:G1HeapRegionSize` makes bigger objects humongous.
+    pub fn large_object_threshold(mut self, bytes: usize) -> RuntimeBuilder<'a> {
+        self.large_object_threshold = bytes;
+        self
+    }
+
+    /// calls `finalize` of the objects of classes which override it once they became unreachable,
+    /// like `--finalization=enabled`. the objects of a `Cleaner` are cleaned either way.
+    pub fn finalization(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.finalization = enabled;
+        self
+    }
+
+    /// writes a line to `output` explaining each instruction before it runs, with the values it
+    /// works on, like `--explain`. every instruction then runs on its own in the checked interpreter.
+    pub fn explain(mut self, output: Option<Output>) -> RuntimeBuilder<'a> {
+        self.explain = output;
+        self
+    }
+
+    /// selects the interpreter, the checked one by default.
+    pub fn interpreter(mut self, mode: InterpreterMode) -> RuntimeBuilder<'a> {
+        self.interpreter = mode;
+        self
+    }
+
+    /// the number of invocations and back edges after which a method counts as hot, 10000 by default.
+    pub fn hot_method_threshold(mut self, threshold: u64) -> RuntimeBuilder<'a> {
+        self.hot_method_threshold = threshold;
+        self
+    }
+
+    /// lets call sites run tiny hot methods, like getters, on the operands of the caller instead of
+    /// calling them. on by default.
+    pub fn inlining(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.inlining = enabled;
+        self
+    }
+
+    /// links calls to the methods of the class library listed in `Intrinsic`, like `Math.max` or
+    /// `System.arraycopy`, to implementations of the runtime, even if the class library has their
+    /// bytecode. on by default.
+    pub fn intrinsics(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.intrinsics = enabled;
+        self
+    }
+
+    /// lets objects which never escape the method creating them live in its frame, where they are
+    /// freed when it returns. on by default.
+    pub fn escape_analysis(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.escape_analysis = enabled;
+        self
+    }
+
+    /// lets hot methods push the constants and take the branches constant folding of their SSA
+    /// form finds instead of computing them. off by default.
+    pub fn optimize_bytecode(mut self, enabled: bool) -> RuntimeBuilder<'a> {
+        self.optimize_bytecode = enabled;
+        self
+    }
+
+    /// loads, parses and decodes every class on the classpath before the runtime is returned,
+    /// on the given number of worker threads or one per cpu for `Some(0)`. off by default.
+    pub fn eager_loading(mut self, threads: Option<usize>) -> RuntimeBuilder<'a> {
+        self.eager_loading = threads;
+        self
+    }
+
+    /// loads the classes of an archive written by `ClassArchive::dump` before the main class,
+    /// without verifying their methods again. an archive which cannot be read is ignored.
+    pub fn class_archive(mut self, path: Option<PathBuf>) -> RuntimeBuilder<'a> {
+        self.class_archive = path;
+        self
+    }
+
+    /// which classes run their `assert` statements, disabled for all classes by default.
+    pub fn assertions(mut self, assertions: AssertionStatus) -> RuntimeBuilder<'a> {
+        self.assertions = assertions;
+        self
+    }
+
+    /// adds a listener which also sees the classes loaded while the runtime is built, from the
+    /// classpath, the class archive and the main class.
+    pub fn class_load_listener(mut self, listener: Box<dyn ClassLoadListener>) -> RuntimeBuilder<'a> {
+        self.class_load_listeners.push(listener);
+        self
+    }
+
+    /// where the runtime keeps the bytes it reads or creates, see `ByteStore`. without a store the
+    /// classpath is not loaded eagerly, the module path and the class archive are ignored and
+    /// defining a class a listener transforms fails.
+    pub fn byte_store(mut self, store: &'a ByteStore) -> RuntimeBuilder<'a> {
+        self.byte_store = Some(store);
+        self
+    }
+
+    pub fn build(self, main_class: ClassFile<'a>) -> Runtime<'a> {
+        let name = String::from(main_class.get_class_name());
+        let eager_loading = self.eager_loading;
+        let class_archive = self.class_archive;
+        let (mut modules, module_classes) = ModuleGraph::scan(&self.module_path);
+        for (module, package, target) in self.added_exports.iter() {
+            modules.add_exports(module, package, target);
+        }
+        let mut environment = Environment::with_journal(self.mode, self.journal);
+        if let Some(seed) = self.random_seed {
+            environment.fix_random_seeds(seed);
+        }
+        if let Some(clock) = self.clock {
+            environment.set_clock(clock);
+        }
+        let mut rt = Runtime {
+            classes: ClassRegistry::new(),
+            classpath: self.classpath,
+            main_class: name,
+            cancellation: CancellationHandle::new(),
+            environment,
+            capabilities: self.capabilities,
+            natives: NativeRegistry::new(),
+            hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            class_load_listeners: self.class_load_listeners,
+            class_bytes: HashMap::new(),
+            byte_store: self.byte_store,
+            crash_dump_path: self.crash_dump_path,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            trace: None,
+            heap: Heap::with_large_object_threshold(self.large_object_threshold),
+            thread: JavaThread::main(),
+            default_uncaught_exception_handler: None,
+            allocations: AllocationProfiler::new(self.allocation_profiling),
+            max_heap: self.max_heap,
+            max_stack_depth: self.max_stack_depth,
+            heap_dump_path: self.heap_dump_path,
+            heap_dump_on_out_of_memory: self.heap_dump_on_out_of_memory,
+            heap_dump_trigger: HeapDumpTrigger::new(),
+            thread_dump_trigger: ThreadDumpTrigger::new(),
+            checkpoint_trigger: CheckpointTrigger::new(),
+            safepoints: SafepointHandle::new(),
+            verbose_gc: self.verbose_gc,
+            verbose_jni: self.verbose_jni,
+            string_deduplication: self.string_deduplication,
+            finalization: self.finalization,
+            cleaners: Cleaners::new(),
+            explain: self.explain,
+            gc_stats: GcStats::default(),
+            debugger: None,
+            breakpoints: Breakpoints::new(),
+            statics: HashMap::new(),
+            initialization: HashMap::new(),
+            class_objects: HashMap::new(),
+            assertions: self.assertions,
+            lambda_classes: HashMap::new(),
+            loaders: ClassLoaders::new(),
+            modules,
+            watchpoints: Vec::new(),
+            interpreter: self.interpreter,
+            inlining: self.inlining,
+            intrinsics: self.intrinsics,
+            escape_analysis: self.escape_analysis,
+            optimize_bytecode: self.optimize_bytecode,
+            counters: InvocationCounters::new(self.hot_method_threshold),
+            processes: Vec::new(),
+            native_memory: BTreeMap::new(),
+            files: Vec::new(),
+        };
+
+        if let Some(threads) = eager_loading {
+            let threads = match threads {
+                0 => thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+                threads => threads
+            };
+            rt.preload_classpath(threads);
+        }
+        if !module_classes.is_empty() {
+            let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+            let count = rt.load_files(&module_classes, threads);
+            info!(classes = count, "loaded module path");
+        }
+        if let Some(path) = class_archive {
+            let loaded = ClassArchive::open(&path).and_then(|archive| match rt.byte_store {
+                Some(store) => rt.load_archive(store.keep_archive(archive)),
+                None => Err(io::Error::other("the runtime was built without a byte store"))
+            });
+            if let Err(err) = loaded {
+                warn!(path = %path.display(), error = %err, "cannot load class archive");
+            }
+        }
+        rt.load_class(main_class);
+
+        rt
+    }
+}
@@ -0,0 +1,126 @@
+/// classes the vm knows without loading them from a class library.
+/// maps each class to its super class.
+const BUILTIN_CLASSES: &[(&str, Option<&str>)] = &[
+    ("java/lang/Object", None),
+    ("java/lang/String", Some("java/lang/Object")),
+    ("java/lang/Throwable", Some("java/lang/Object")),
+    ("java/lang/Exception", Some("java/lang/Throwable")),
+    ("java/lang/RuntimeException", Some("java/lang/Exception")),
+    ("java/lang/NullPointerException", Some("java/lang/RuntimeException")),
+    ("java/lang/ArithmeticException", Some("java/lang/RuntimeException")),
+    ("java/lang/IndexOutOfBoundsException", Some("java/lang/RuntimeException")),
+    ("java/lang/ArrayIndexOutOfBoundsException", Some("java/lang/IndexOutOfBoundsException")),
+    ("java/lang/NegativeArraySizeException", Some("java/lang/RuntimeException")),
+    ("java/lang/ArrayStoreException", Some("java/lang/RuntimeException")),
+    ("java/lang/ClassCastException", Some("java/lang/RuntimeException")),
+    ("java/lang/IllegalArgumentException", Some("java/lang/RuntimeException")),
+    ("java/lang/UnsupportedOperationException", Some("java/lang/RuntimeException")),
+    ("java/lang/CloneNotSupportedException", Some("java/lang/Exception")),
+    ("java/lang/ReflectiveOperationException", Some("java/lang/Exception")),
+    ("java/lang/ClassNotFoundException", Some("java/lang/ReflectiveOperationException")),
+    ("java/lang/NoSuchMethodException", Some("java/lang/ReflectiveOperationException")),
+    ("java/lang/NoSuchFieldException", Some("java/lang/ReflectiveOperationException")),
+    ("java/lang/IllegalAccessException", Some("java/lang/ReflectiveOperationException")),
+    ("java/lang/Error", Some("java/lang/Throwable")),
+    ("java/lang/VirtualMachineError", Some("java/lang/Error")),
+    ("java/lang/OutOfMemoryError", Some("java/lang/VirtualMachineError")),
+    ("java/lang/StackOverflowError", Some("java/lang/VirtualMachineError")),
+    ("java/lang/LinkageError", Some("java/lang/Error")),
+    ("java/lang/NoClassDefFoundError", Some("java/lang/LinkageError")),
+    ("java/lang/IncompatibleClassChangeError", Some("java/lang/LinkageError")),
+    ("java/lang/NoSuchFieldError", Some("java/lang/IncompatibleClassChangeError")),
+    ("java/lang/ExceptionInInitializerError", Some("java/lang/LinkageError")),
+    ("java/lang/AssertionError", Some("java/lang/Error")),
+    ("java/lang/Class", Some("java/lang/Object")),
+    ("java/lang/System", Some("java/lang/Object")),
+    ("java/io/PrintStream", Some("java/lang/Object")),
+    ("java/lang/Record", Some("java/lang/Object")),
+    ("java/lang/Enum", Some("java/lang/Object")),
+    ("java/lang/StackTraceElement", Some("java/lang/Object")),
+    ("java/lang/invoke/MethodHandles", Some("java/lang/Object")),
+    ("java/lang/invoke/MethodHandles$Lookup", Some("java/lang/Object")),
+    ("java/lang/invoke/MethodHandle", Some("java/lang/Object")),
+    ("java/lang/invoke/MethodType", Some("java/lang/Object")),
+    ("java/lang/invoke/VarHandle", Some("java/lang/Object")),
+    ("java/lang/invoke/WrongMethodTypeException", Some("java/lang/RuntimeException")),
+    ("java/lang/Thread", Some("java/lang/Object")),
+    ("java/lang/Thread$UncaughtExceptionHandler", Some("java/lang/Object")),
+    ("java/lang/IllegalThreadStateException", Some("java/lang/IllegalArgumentException")),
+    ("java/lang/InterruptedException", Some("java/lang/Exception")),
+    ("java/lang/Runtime", Some("java/lang/Object")),
+    ("java/lang/ProcessBuilder", Some("java/lang/Object")),
+    ("java/lang/ProcessBuilder$Redirect", Some("java/lang/Object")),
+    ("java/lang/Process", Some("java/lang/Object")),
+    ("java/lang/ProcessEnvironment", Some("java/lang/Object")),
+    ("java/io/IOException", Some("java/lang/Exception")),
+    ("java/io/InputStream", Some("java/lang/Object")),
+    ("java/io/OutputStream", Some("java/lang/Object")),
+    ("java/io/UnsupportedEncodingException", Some("java/io/IOException")),
+    ("java/nio/charset/Charset", Some("java/lang/Object")),
+    ("java/nio/charset/StandardCharsets", Some("java/lang/Object")),
+    ("java/nio/charset/UnsupportedCharsetException", Some("java/lang/IllegalArgumentException")),
+    ("java/util/Random", Some("java/lang/Object")),
+    ("java/security/SecureRandom", Some("java/util/Random")),
+    ("java/lang/IllegalStateException", Some("java/lang/RuntimeException")),
+    ("java/lang/InternalError", Some("java/lang/VirtualMachineError")),
+    ("java/io/FileNotFoundException", Some("java/io/IOException")),
+    ("java/io/UncheckedIOException", Some("java/lang/RuntimeException")),
+    ("java/io/RandomAccessFile", Some("java/lang/Object")),
+    ("java/nio/Buffer", Some("java/lang/Object")),
+    ("java/nio/ByteBuffer", Some("java/nio/Buffer")),
+    ("java/nio/MappedByteBuffer", Some("java/nio/ByteBuffer")),
+    ("java/nio/DirectByteBuffer", Some("java/nio/MappedByteBuffer")),
+    ("java/nio/HeapByteBuffer", Some("java/nio/ByteBuffer")),
+    ("java/nio/ByteOrder", Some("java/lang/Object")),
+    ("java/nio/BufferOverflowException", Some("java/lang/RuntimeException")),
+    ("java/nio/BufferUnderflowException", Some("java/lang/RuntimeException")),
+    ("java/nio/ReadOnlyBufferException", Some("java/lang/UnsupportedOperationException")),
+    ("java/nio/InvalidMarkException", Some("java/lang/IllegalStateException")),
+    ("java/nio/channels/FileChannel", Some("java/lang/Object")),
+    ("java/nio/channels/FileChannel$MapMode", Some("java/lang/Object")),
+    ("java/nio/channels/ClosedChannelException", Some("java/io/IOException")),
+    ("java/nio/channels/NonWritableChannelException", Some("java/lang/IllegalStateException")),
+    ("sun/misc/Unsafe", Some("java/lang/Object")),
+    ("java/lang/UnsatisfiedLinkError", Some("java/lang/LinkageError")),
+    ("java/util/TimeZone", Some("java/lang/Object")),
+    ("jdk/internal/misc/VM", Some("java/lang/Object")),
+    ("java/lang/ref/Cleaner", Some("java/lang/Object")),
+    ("java/lang/ref/Cleaner$Cleanable", Some("java/lang/Object")),
+    ("jdk/internal/ref/CleanerImpl$PhantomCleanableRef", Some("java/lang/Object")),
+];
+
+pub fn is_builtin(class_name: &str) -> bool {
+    BUILTIN_CLASSES.iter().any(|&(name, _)| name == class_name)
+}
+
+/// the super class of a builtin class. `None` for `java/lang/Object` and unknown classes.
+pub fn superclass(class_name: &str) -> Option<&'static str> {
+    BUILTIN_CLASSES.iter()
+        .find(|&&(name, _)| name == class_name)
+        .and_then(|&(_, superclass)| superclass)
+}
+
+/// the names of all builtin classes, for the debugger.
+#[cfg(all(feature = "threads", feature = "sockets"))]
+pub fn names() -> impl Iterator<Item=&'static str> {
+    BUILTIN_CLASSES.iter().map(|&(name, _)| name)
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, ObjectData, Runtime, StackValue};
+
+    #[test]
+    fn exceptions_of_the_vm_are_caught_by_their_builtin_super_classes() {
+        let mut rt = Runtime::builder().max_heap(Some(4096))
+            .build(read_class_file(include_bytes!("../../../sample/VmErrors.class")).unwrap().1);
+        let mut call = |method, descriptor, argument| rt.invoke_static("VmErrors", method, descriptor, vec![argument]).unwrap();
+        assert_eq!(call("deref", "(Ljava/lang/Object;)I", LocalVariable::Null), Some(StackValue::Integer(-1)));
+        assert_eq!(call("index", "(I)I", LocalVariable::Integer(2)), Some(StackValue::Integer(-1)));
+        assert_eq!(call("index", "(I)I", LocalVariable::Integer(1)), Some(StackValue::Integer(0)));
+        assert_eq!(call("allocate", "(I)I", LocalVariable::Integer(1 << 20)), Some(StackValue::Integer(-1)));
+        let object = rt.allocate("java/lang/Object", ObjectData::Instance);
+        assert_eq!(rt.invoke_static("VmErrors", "cast", "(Ljava/lang/Object;)I", vec![LocalVariable::Reference(object)]).unwrap(), Some(StackValue::Integer(-1)));
+    }
+}
@@ -0,0 +1,80 @@
+use java::runtime::{ClassArchive, RuntimeError};
+use std::any::Any;
+use std::sync::Mutex;
+
+/// owns the bytes which the classes of a runtime borrow from but which the runtime reads or creates
+/// itself: the class files of the classpath and the module path it loads, its class archive and
+/// the versions of classes a `ClassLoadListener` transformed. the store outlives the runtime, see
+/// `RuntimeBuilder::byte_store`, and frees everything it keeps when it is dropped.
+///
+/// ```text
+/// let store = ByteStore::new();
+/// let mut rt = Runtime::builder().byte_store(&store).class_load_listener(agent).build(main_class);
+/// ```
+#[derive(Debug, Default)]
+pub struct ByteStore {
+    kept: Mutex<Vec<Box<dyn Any + Send + Sync>>>,
+}
+
+impl ByteStore {
+    pub fn new() -> ByteStore {
+        ByteStore::default()
+    }
+
+    /// keeps `bytes` until the store is dropped.
+    pub fn keep(&self, bytes: Vec<u8>) -> &[u8] {
+        self.keep_value(bytes)
+    }
+
+    /// keeps `archive` until the store is dropped.
+    pub(super) fn keep_archive(&self, archive: ClassArchive) -> &ClassArchive {
+        self.keep_value(archive)
+    }
+
+    /// the number of buffers and archives kept.
+    pub fn len(&self) -> usize {
+        self.kept.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn keep_value<T: Any + Send + Sync>(&self, value: T) -> &T {
+        let mut kept = self.kept.lock().unwrap();
+        kept.push(Box::new(value));
+        let value = kept.last().and_then(|value| value.downcast_ref::<T>()).unwrap() as *const T;
+        // nothing is removed from `kept` before the store is dropped, and the boxed values stay
+        // where they are when it grows
+        unsafe { &*value }
+    }
+}
+
+/// keeps the bytes of `class_name` in `store`, for runtimes built without one an error.
+pub(super) fn keep_in<'a>(store: Option<&'a ByteStore>, bytes: Vec<u8>, class_name: &str) -> Result<&'a [u8], RuntimeError> {
+    match store {
+        Some(store) => Ok(store.keep(bytes)),
+        None => Err(RuntimeError::Host {
+            action: format!("keep the bytes of {}", class_name),
+            message: String::from("the runtime was built without a byte store")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kept_bytes_stay_in_place() {
+        let store = ByteStore::new();
+        let first = store.keep(vec![1, 2, 3]);
+        for length in 0..64 {
+            store.keep(vec![0; length]);
+        }
+
+        assert_eq!(first, [1, 2, 3]);
+        assert_eq!(store.len(), 65);
+        assert!(keep_in(None, vec![1], "Tiny").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use java::class_file::{ClassFile, ConstantType, Method};
+use java::runtime::{ConcatRecipe, LambdaClass, RecordMethod, Resolved, ResolvedMethod, Runtime, RuntimeConstantPool, RuntimeError};
+use std::sync::Arc;
+use super::StackFrame;
+
+/// how a method handle invokes its method, see jvms 5.4.3.5. only the kinds referencing methods.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReferenceKind {
+    InvokeVirtual,
+    InvokeStatic,
+    InvokeSpecial,
+    NewInvokeSpecial,
+    InvokeInterface,
+}
+
+impl ReferenceKind {
+    pub fn from_u8(kind: u8) -> Option<ReferenceKind> {
+        match kind {
+            5 => Some(ReferenceKind::InvokeVirtual),
+            6 => Some(ReferenceKind::InvokeStatic),
+            7 => Some(ReferenceKind::InvokeSpecial),
+            8 => Some(ReferenceKind::NewInvokeSpecial),
+            9 => Some(ReferenceKind::InvokeInterface),
+            _ => None
+        }
+    }
+}
+
+/// an `invokedynamic` call site, linked on first use by running its bootstrap method natively
+/// instead of through `java.lang.invoke`.
+#[derive(Debug, Clone)]
+pub enum CallSite {
+    /// `LambdaMetafactory.metafactory`, creates lambda objects.
+    Lambda(Arc<LambdaClass>),
+    /// `StringConcatFactory.makeConcatWithConstants`, concatenates its arguments.
+    Concat(Arc<ConcatRecipe>),
+    /// `ObjectMethods.bootstrap`, implements `toString`, `hashCode` and `equals` of records.
+    Record(Arc<RecordMethod>),
+}
+
+/// what a bootstrap method gets to link a call site.
+pub(super) struct Bootstrap<'c> {
+    /// the name and descriptor of the call site.
+    pub name: &'c str,
+    pub descriptor: &'c str,
+    /// the constants passed as static arguments.
+    pub arguments: Vec<u16>,
+}
+
+impl<'a> Runtime<'a> {
+    /// links the `InvokeDynamic` constant `index` on first use.
+    fn link_call_site(&mut self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, index: u16) -> Result<CallSite, RuntimeError> {
+        if let Some(Resolved::CallSite(call_site)) = pool.get(index) {
+            return Ok(call_site);
+        }
+
+        let (bootstrap_index, name_and_type_index) = match class.get_constant(index) {
+            Some(ConstantType::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index }) => (*bootstrap_method_attr_index, *name_and_type_index),
+            _ => return Err(RuntimeError::InvalidConstant { index, expected: String::from("InvokeDynamic") })
+        };
+        let (name, descriptor) = class.get_name_and_type(name_and_type_index)
+            .ok_or_else(|| RuntimeError::InvalidConstant { index: name_and_type_index, expected: String::from("NameAndType") })?;
+        let (bootstrap_method, arguments) = class.get_bootstrap_method(bootstrap_index)
+            .ok_or_else(|| RuntimeError::InvalidBytecode { message: format!("missing bootstrap method {}", bootstrap_index) })?;
+
+        let (_, bootstrap_method) = self.resolve_method_handle(pool, class, bootstrap_method)?;
+        let bootstrap = Bootstrap { name, descriptor, arguments };
+        let call_site = match (bootstrap_method.class_name.as_str(), bootstrap_method.method_name.as_str()) {
+            // the flags of altMetafactory for serializable lambdas and bridges do not matter here
+            ("java/lang/invoke/LambdaMetafactory", "metafactory") | ("java/lang/invoke/LambdaMetafactory", "altMetafactory") =>
+                CallSite::Lambda(self.link_lambda(pool, class, &bootstrap)?),
+            ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") =>
+                CallSite::Concat(Arc::new(ConcatRecipe::link(class, &bootstrap)?)),
+            ("java/lang/runtime/ObjectMethods", "bootstrap") =>
+                CallSite::Record(Arc::new(self.link_record_method(pool, class, &bootstrap)?)),
+            (class_name, method_name) => return Err(RuntimeError::UnsupportedBootstrapMethod {
+                class_name: String::from(class_name),
+                method_name: String::from(method_name),
+            })
+        };
+
+        pool.set(index, Resolved::CallSite(call_site.clone()));
+        Ok(call_site)
+    }
+
+    /// resolves a `MethodHandle` constant which references a method.
+    pub(super) fn resolve_method_handle(&self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, index: u16) -> Result<(ReferenceKind, Arc<ResolvedMethod>), RuntimeError> {
+        match class.get_constant(index) {
+            Some(ConstantType::MethodHandle { reference_kind, reference_index }) => match ReferenceKind::from_u8(*reference_kind) {
+                Some(kind) => Ok((kind, self.resolve_method(pool, class, *reference_index)?)),
+                None => Err(RuntimeError::InvalidConstant { index, expected: String::from("method handle of a method") })
+            },
+            _ => Err(RuntimeError::InvalidConstant { index, expected: String::from("MethodHandle") })
+        }
+    }
+
+    /// `invokedynamic`: links the call site and runs it with the arguments on the stack.
+    pub(super) fn exec_invokedynamic(&mut self, method: &Method, class: &ClassFile<'a>, pool: &RuntimeConstantPool, stack_frame: &mut StackFrame, index: u16) -> Result<(), RuntimeError> {
+        match self.link_call_site(pool, class, index)? {
+            CallSite::Lambda(lambda) => self.new_lambda(method, stack_frame, &lambda),
+            CallSite::Concat(recipe) => self.exec_concat(method, stack_frame, &recipe),
+            CallSite::Record(record_method) => self.exec_record_method(method, stack_frame, &record_method)
+        }
+    }
+}
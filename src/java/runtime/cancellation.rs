@@ -0,0 +1,36 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// a cloneable token that can stop a running `Runtime` from another thread.
+///
+/// the interpreter polls the token at every safepoint. once `cancel` was called, the currently
+/// running method returns `RuntimeError::Cancelled` and unwinds all frames.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationHandle {
+    pub fn new() -> CancellationHandle {
+        CancellationHandle { cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// requests the runtime to stop at the next safepoint.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// the raw flag, for `signal_hook::flag::register`.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// clears a previous cancellation request, so the runtime can be used again.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst)
+    }
+}
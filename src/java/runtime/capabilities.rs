@@ -0,0 +1,196 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone)]
+enum PathAccess {
+    Any,
+    Roots(Vec<PathBuf>),
+}
+
+impl PathAccess {
+    fn allows(&self, path: &Path) -> bool {
+        match self {
+            PathAccess::Any => true,
+            PathAccess::Roots(roots) => match resolve(path) {
+                Some(path) => roots.iter().filter_map(|root| resolve(root)).any(|root| path.starts_with(root)),
+                None => false
+            }
+        }
+    }
+
+    /// relative roots, like `.` or the empty path, would depend on the current directory and
+    /// grant nothing.
+    fn add_root(&mut self, root: PathBuf) {
+        match self {
+            PathAccess::Any => (),
+            PathAccess::Roots(_) if !root.is_absolute() => warn!(root = %root.display(), "ignoring a relative capability root"),
+            PathAccess::Roots(roots) => roots.push(root),
+        }
+    }
+}
+
+/// the path the file system will actually open for `path`: the longest part which exists is
+/// canonicalized, so symlinks cannot lead out of a root, and `.` and `..` in the rest are resolved
+/// without touching the file system, so `/data/../etc/passwd` cannot sneak out of the `/data` root.
+/// relative paths are relative to the current directory. `None` if the path cannot be resolved.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let path = if path.is_absolute() { path.to_path_buf() } else { env::current_dir().ok()?.join(path) };
+    let components = path.components().collect::<Vec<_>>();
+    let existing = (1..=components.len()).rev()
+        .find_map(|length| components[..length].iter().collect::<PathBuf>().canonicalize().ok().map(|canonical| (length, canonical)));
+    let (length, mut resolved) = existing?;
+    for component in components[length..].iter() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other.as_os_str()),
+        }
+    }
+
+    Some(resolved)
+}
+
+/// what the bytecode running inside a `Runtime` may do on the host.
+///
+/// the checks happen inside the native layer: natives only reach the host through
+/// `NativeContext`, which consults these capabilities before every access.
+/// the default allows everything, `Capabilities::none()` is the starting point for untrusted code.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    read: PathAccess,
+    write: PathAccess,
+    network: bool,
+    environment: bool,
+    process_spawn: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities::all()
+    }
+}
+
+impl Capabilities {
+    pub fn all() -> Capabilities {
+        Capabilities {
+            read: PathAccess::Any,
+            write: PathAccess::Any,
+            network: true,
+            environment: true,
+            process_spawn: true,
+        }
+    }
+
+    pub fn none() -> Capabilities {
+        Capabilities {
+            read: PathAccess::Roots(Vec::new()),
+            write: PathAccess::Roots(Vec::new()),
+            network: false,
+            environment: false,
+            process_spawn: false,
+        }
+    }
+
+    /// allows reading everything below the absolute path `root`.
+    pub fn allow_read<P: Into<PathBuf>>(mut self, root: P) -> Capabilities {
+        self.read.add_root(root.into());
+        self
+    }
+
+    /// allows writing everything below the absolute path `root`.
+    pub fn allow_write<P: Into<PathBuf>>(mut self, root: P) -> Capabilities {
+        self.write.add_root(root.into());
+        self
+    }
+
+    pub fn allow_network(mut self, allowed: bool) -> Capabilities {
+        self.network = allowed;
+        self
+    }
+
+    pub fn allow_environment(mut self, allowed: bool) -> Capabilities {
+        self.environment = allowed;
+        self
+    }
+
+    pub fn allow_process_spawn(mut self, allowed: bool) -> Capabilities {
+        self.process_spawn = allowed;
+        self
+    }
+
+    pub fn can_read(&self, path: &Path) -> bool {
+        self.read.allows(path)
+    }
+
+    pub fn can_write(&self, path: &Path) -> bool {
+        self.write.allows(path)
+    }
+
+    pub fn can_use_network(&self) -> bool {
+        self.network
+    }
+
+    pub fn can_read_environment(&self) -> bool {
+        self.environment
+    }
+
+    pub fn can_spawn_processes(&self) -> bool {
+        self.process_spawn
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn none_denies_everything() {
+        let caps = Capabilities::none();
+        assert!(!caps.can_read(Path::new("/tmp/x")));
+        assert!(!caps.can_write(Path::new("/tmp/x")));
+        assert!(!caps.can_use_network());
+        assert!(!caps.can_read_environment());
+        assert!(!caps.can_spawn_processes());
+    }
+
+    #[test]
+    fn it_allows_paths_below_a_root() {
+        let caps = Capabilities::none().allow_read("/data");
+        assert!(caps.can_read(Path::new("/data/input.txt")));
+        assert!(caps.can_read(Path::new("/data/./sub/../input.txt")));
+        assert!(!caps.can_read(Path::new("/data/../etc/passwd")));
+        assert!(!caps.can_read(Path::new("/database")));
+        assert!(!caps.can_write(Path::new("/data/input.txt")));
+    }
+
+    #[test]
+    fn relative_roots_grant_nothing() {
+        let caps = Capabilities::none().allow_read(".").allow_read("");
+        assert!(!caps.can_read(Path::new("/etc/passwd")));
+        assert!(!caps.can_read(Path::new("input.txt")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn symlinks_cannot_leave_a_root() {
+        use std::fs;
+
+        let dir = env::temp_dir().join(format!("rjvm-capabilities-{}", std::process::id()));
+        let (root, outside) = (dir.join("root"), dir.join("outside"));
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        let _ = fs::remove_file(root.join("escape"));
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+
+        let caps = Capabilities::none().allow_write(&root);
+        assert!(caps.can_write(&root.join("new.txt")));
+        assert!(!caps.can_write(&root.join("escape/secret.txt")));
+        assert!(!caps.can_write(&root.join("escape/new.txt")));
+        assert!(!caps.can_write(&root.join("escape/../../outside/secret.txt")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use java::charset::Charset;
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+
+pub(super) const CHARSET: &str = "java/nio/charset/Charset";
+pub(super) const STANDARD_CHARSETS: &str = "java/nio/charset/StandardCharsets";
+const STRING: &str = "java/lang/String";
+
+/// the charset of `String.getBytes()` and `new String(bytes)`, which is utf-8 since java 18.
+const DEFAULT_CHARSET: Charset = Charset::Utf8;
+
+impl<'a> Runtime<'a> {
+    /// the constants of `StandardCharsets`.
+    pub(super) fn standard_charset(&mut self, field_name: &str) -> Option<StackValue> {
+        let charset = Charset::for_name(&field_name.replace('_', "-"))?;
+        Some(StackValue::Reference(self.charset_object(charset)))
+    }
+
+    /// the `java.nio.charset.Charset` object of `charset`, created on first use. like the class
+    /// library, there is one per charset and it keeps its canonical name in the field `name`.
+    fn charset_object(&mut self, charset: Charset) -> ObjectRef {
+        let key = (String::from(CHARSET), String::from(charset.name()));
+        if let Some(StackValue::Reference(object)) = self.statics.get(&key) {
+            return *object;
+        }
+        let object = self.allocate(CHARSET, ObjectData::Instance);
+        let name = self.intern(charset.name());
+        if let Some(charset) = self.heap.get_mut(object) {
+            charset.fields.insert(String::from("name"), StackValue::Reference(name));
+        }
+        self.statics.insert(key, StackValue::Reference(object));
+        object
+    }
+
+    fn charset_of(&self, object: ObjectRef) -> Option<Charset> {
+        match self.heap.get(object).and_then(|object| object.fields.get("name")) {
+            Some(StackValue::Reference(name)) => self.heap.string_value(*name).and_then(Charset::for_name),
+            _ => None
+        }
+    }
+
+    /// implements `Charset.forName`, `Charset.defaultCharset`, `name` and `toString`.
+    /// returns `None` for methods `Charset` does not have.
+    pub(super) fn invoke_charset(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let argument = match arguments.first() {
+            Some(LocalVariable::Reference(argument)) => Some(*argument),
+            _ => None
+        };
+        let charset = match (method_name, descriptor, argument) {
+            ("defaultCharset", "()Ljava/nio/charset/Charset;", _) => DEFAULT_CHARSET,
+            ("forName", "(Ljava/lang/String;)Ljava/nio/charset/Charset;", Some(name)) => {
+                let name = self.heap.string_value(name).map(String::from).unwrap_or_default();
+                match Charset::for_name(&name) {
+                    Some(charset) => charset,
+                    None => return Some(Err(self.throw_in_caller("java/nio/charset/UnsupportedCharsetException", Some(&name))))
+                }
+            }
+            ("name", "()Ljava/lang/String;", Some(this)) | ("toString", "()Ljava/lang/String;", Some(this)) => {
+                let name = self.charset_of(this).map_or("", Charset::name);
+                return Some(Ok(Some(StackValue::Reference(self.intern(name)))));
+            }
+            ("forName", "(Ljava/lang/String;)Ljava/nio/charset/Charset;", None) => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None))),
+            _ => return None
+        };
+        Some(Ok(Some(StackValue::Reference(self.charset_object(charset)))))
+    }
+
+    /// the charset a `String` method names with its argument at `index`, either a `Charset` or
+    /// the name of one. the default charset if the method has no such argument.
+    fn charset_argument(&mut self, descriptor: &str, arguments: &[LocalVariable], index: usize) -> Result<Charset, RuntimeError> {
+        let argument = match arguments.get(index) {
+            Some(LocalVariable::Reference(argument)) => *argument,
+            Some(LocalVariable::Null) => return Err(self.throw_in_caller("java/lang/NullPointerException", None)),
+            _ => return Ok(DEFAULT_CHARSET)
+        };
+        if descriptor.contains("Ljava/nio/charset/Charset;") {
+            return Ok(self.charset_of(argument).unwrap_or(DEFAULT_CHARSET));
+        }
+        let name = self.heap.string_value(argument).map(String::from).unwrap_or_default();
+        Charset::for_name(&name).ok_or_else(|| self.throw_in_caller("java/io/UnsupportedEncodingException", Some(&name)))
+    }
+
+    /// implements `String.getBytes` and the constructors of `String` from bytes.
+    /// returns `None` for other methods.
+    pub(super) fn invoke_string_bytes(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let this = match arguments.first() {
+            Some(LocalVariable::Reference(this)) => *this,
+            _ => return None
+        };
+        match (method_name, descriptor) {
+            ("getBytes", "()[B") | ("getBytes", "(Ljava/lang/String;)[B") | ("getBytes", "(Ljava/nio/charset/Charset;)[B") => {
+                let charset = match self.charset_argument(descriptor, arguments, 1) {
+                    Ok(charset) => charset,
+                    Err(err) => return Some(Err(err))
+                };
+                let bytes = charset.encode(self.heap.string_value(this).unwrap_or_default()).into_iter()
+                    .map(|byte| StackValue::Integer(i64::from(byte as i8)))
+                    .collect();
+                Some(Ok(Some(StackValue::Reference(self.allocate("[B", ObjectData::Array(bytes))))))
+            }
+            ("<init>", "([B)V") | ("<init>", "([BLjava/lang/String;)V") | ("<init>", "([BLjava/nio/charset/Charset;)V") => {
+                let bytes = match arguments.get(1).map(|array| match array {
+                    LocalVariable::Reference(array) => self.heap.get(*array).map(|array| &array.data),
+                    _ => None
+                }) {
+                    Some(Some(ObjectData::Array(elements))) => elements.iter().map(|element| match element {
+                        StackValue::Integer(byte) => *byte as u8,
+                        _ => 0
+                    }).collect::<Vec<u8>>(),
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let charset = match self.charset_argument(descriptor, arguments, 2) {
+                    Ok(charset) => charset,
+                    Err(err) => return Some(Err(err))
+                };
+                let value = charset.decode(&bytes);
+                if let Some(string) = self.heap.get_mut(this).filter(|object| object.class_name == STRING) {
+                    string.data = ObjectData::String(value.into());
+                }
+                Some(Ok(None))
+            }
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime() -> Runtime<'static> {
+        Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Charsets.class")).unwrap().1)
+    }
+
+    fn bytes(rt: &Runtime, array: Option<StackValue>) -> Vec<i64> {
+        match array {
+            Some(StackValue::Reference(array)) => match rt.heap.get(array).map(|array| &array.data) {
+                Some(ObjectData::Array(elements)) => elements.iter().map(|element| match element {
+                    StackValue::Integer(value) => *value,
+                    other => panic!("expected a byte, got {:?}", other)
+                }).collect(),
+                other => panic!("expected an array, got {:?}", other)
+            },
+            other => panic!("expected an array, got {:?}", other)
+        }
+    }
+
+    fn string(rt: &Runtime, value: Option<StackValue>) -> String {
+        match value {
+            Some(StackValue::Reference(string)) => String::from(rt.heap.string_value(string).unwrap()),
+            other => panic!("expected a string, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn strings_are_encoded_with_the_charset_asked_for() {
+        let mut rt = runtime();
+        let encoded = rt.invoke_static("Charsets", "encode", "()[B", vec![]).unwrap();
+        // "grüß" in utf-8, iso-8859-1 and utf-16 with its byte order mark
+        assert_eq!(bytes(&rt, encoded), vec![
+            103, 114, -61, -68, -61, -97,
+            103, 114, -4, -33,
+            -2, -1, 0, 103, 0, 114, 0, -4, 0, -33,
+        ]);
+    }
+
+    #[test]
+    fn bytes_are_decoded_with_the_charset_asked_for() {
+        let mut rt = runtime();
+        let decoded = rt.invoke_static("Charsets", "decode", "()Ljava/lang/String;", vec![]).unwrap();
+        assert_eq!(string(&rt, decoded), "grüß grüß grüß UTF-16LE");
+    }
+
+    #[test]
+    fn unknown_charsets_throw() {
+        let mut rt = runtime();
+        let name = rt.intern("EBCDIC");
+        for &(method_name, exception) in [("byName", "java/io/UnsupportedEncodingException"), ("forName", "java/nio/charset/UnsupportedCharsetException")].iter() {
+            match rt.invoke_static("Charsets", method_name, "(Ljava/lang/String;)[B", vec![LocalVariable::Reference(name)]) {
+                Err(RuntimeError::Exception { ref class_name, .. }) if class_name == exception => (),
+                other => panic!("expected an {}, got {:?}", exception, other)
+            }
+        }
+    }
+}
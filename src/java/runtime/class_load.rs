@@ -0,0 +1,165 @@
+use java::class_file::{ClassFile, ClassFormatError};
+use java::runtime::{ClassId, Runtime, RuntimeError};
+use java::runtime::byte_store::keep_in;
+use std::fmt;
+
+/// gets told about the classes of a `Runtime` as they are defined, linked and initialized, like a
+/// `java.lang.instrument` agent. unlike a `RuntimeHook` it may change the bytes of a class before
+/// the class is defined.
+///
+/// every method has an empty default implementation. listeners are called in the order they were
+/// added, each transformation sees the bytes the previous listeners returned.
+pub trait ClassLoadListener: Send {
+    /// `class_name` is about to be defined from `bytes`. returns the bytes to define instead, `None`
+    /// keeps them. classes handed to `Runtime::load_class` already parsed cannot be transformed.
+    fn before_define(&mut self, _class_name: &str, _bytes: &[u8]) -> Option<Vec<u8>> { None }
+
+    /// `class` was defined and its methods indexed, its methods can be called from now on.
+    fn on_link(&mut self, _class: &ClassFile) {}
+
+    /// the static initializer of `class_name` ran to completion.
+    fn on_initialize(&mut self, _class_name: &str) {}
+}
+
+impl fmt::Debug for dyn ClassLoadListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ClassLoadListener")
+    }
+}
+
+/// the error of a class file which `ClassFile::parse` rejected, its message starting with `context`.
+fn class_format_error(err: ClassFormatError, context: String) -> RuntimeError {
+    match err {
+        ClassFormatError::Version(err) => RuntimeError::UnsupportedClassVersion { message: context + &err.to_string() },
+        err => RuntimeError::ClassFormat { message: context + &err.to_string() }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// adds a listener for the classes defined from now on. listeners which should see the classes
+    /// loaded while the runtime is built go to `RuntimeBuilder::class_load_listener`.
+    pub fn add_class_load_listener(&mut self, listener: Box<dyn ClassLoadListener>) {
+        self.class_load_listeners.push(listener);
+    }
+
+    /// parses, transforms and loads the class file `bytes`. returns the id of the new class.
+    pub fn define_class(&mut self, bytes: &'a [u8]) -> Result<ClassId, RuntimeError> {
+        let class = ClassFile::parse(bytes).map_err(|err| class_format_error(err, String::new()))?;
+        let (class, _) = self.transform_class(class, bytes)?;
+        let class_name = String::from(class.get_class_name());
+        self.load_class(class);
+        self.classes.id(&class_name).ok_or(RuntimeError::ClassNotFound { class_name })
+    }
+
+    /// passes the bytes of a class about to be defined through the listeners. returns the class to
+    /// define and true if a listener changed it.
+    ///
+    /// the transformed bytes go to the byte store of the runtime.
+    pub(super) fn transform_class(&mut self, class: ClassFile<'a>, bytes: &'a [u8]) -> Result<(ClassFile<'a>, bool), RuntimeError> {
+        self.class_bytes.insert(String::from(class.get_class_name()), bytes);
+        let (mut class, mut bytes, mut transformed) = (class, bytes, false);
+        for listener in self.class_load_listeners.iter_mut() {
+            let new_bytes = match listener.before_define(class.get_class_name(), bytes) {
+                Some(new_bytes) => keep_in(self.byte_store, new_bytes, class.get_class_name())?,
+                None => continue
+            };
+            let context = format!("{} as transformed by a listener: ", class.get_class_name());
+            class = ClassFile::parse(new_bytes).map_err(|err| class_format_error(err, context))?;
+            bytes = new_bytes;
+            transformed = true;
+        }
+
+        Ok((class, transformed))
+    }
+
+    /// notifies the listeners about a class which was just linked.
+    pub(super) fn class_linked(&mut self, class_name: &str) {
+        if self.class_load_listeners.is_empty() {
+            return;
+        }
+        if let Some(class) = self.classes.get(class_name) {
+            for listener in self.class_load_listeners.iter_mut() {
+                listener.on_link(&class);
+            }
+        }
+    }
+
+    /// notifies the listeners about a class whose static initializer completed.
+    pub(super) fn class_initialized(&mut self, class_name: &str) {
+        for listener in self.class_load_listeners.iter_mut() {
+            listener.on_initialize(class_name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{ByteStore, StackValue};
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    /// records the events and replaces `Reload` with the version in `sample/redefined`.
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl ClassLoadListener for Recorder {
+        fn before_define(&mut self, class_name: &str, _bytes: &[u8]) -> Option<Vec<u8>> {
+            self.0.lock().unwrap().push(format!("define {}", class_name));
+            match class_name {
+                "Reload" => Some(include_bytes!("../../../sample/redefined/Reload.class").to_vec()),
+                _ => None
+            }
+        }
+
+        fn on_link(&mut self, class: &ClassFile) {
+            self.0.lock().unwrap().push(format!("link {}", class.get_class_name()));
+        }
+
+        fn on_initialize(&mut self, class_name: &str) {
+            self.0.lock().unwrap().push(format!("initialize {}", class_name));
+        }
+    }
+
+    #[test]
+    fn listeners_see_and_transform_classes() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let store = ByteStore::new();
+        let mut rt = Runtime::builder()
+            .byte_store(&store)
+            .class_load_listener(Box::new(Recorder(events.clone())))
+            .build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        rt.define_class(include_bytes!("../../../sample/Reload.class")).unwrap();
+
+        // the transformed version adds 40 to the number of calls
+        assert_eq!(rt.invoke_static("Reload", "answer", "()I", vec![]).unwrap(), Some(StackValue::Integer(41)));
+        assert_eq!(*events.lock().unwrap(), vec!["link Tiny", "define Reload", "link Reload", "initialize Reload"]);
+
+        match rt.define_class(b"not a class") {
+            Err(RuntimeError::UnsupportedClassVersion { .. }) => (),
+            other => panic!("define_class returned {:?}", other)
+        }
+        let mut newer = include_bytes!("../../../sample/Plugin.class").to_vec();
+        newer[7] = 65;
+        match rt.define_class(store.keep(newer)) {
+            Err(RuntimeError::UnsupportedClassVersion { ref message }) => assert!(message.contains("class file version 65.0")),
+            other => panic!("define_class returned {:?}", other)
+        }
+        assert!(rt.classes.get("Plugin").is_none());
+
+        let mut trailing = include_bytes!("../../../sample/Plugin.class").to_vec();
+        trailing.push(0);
+        match rt.define_class(store.keep(trailing)) {
+            Err(RuntimeError::ClassFormat { ref message }) => assert_eq!(message, "1 extra bytes at the end of the class file"),
+            other => panic!("define_class returned {:?}", other)
+        }
+
+        // without a store the transformed bytes cannot be kept
+        let mut rt = Runtime::builder()
+            .class_load_listener(Box::new(Recorder(events.clone())))
+            .build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        match rt.define_class(include_bytes!("../../../sample/Reload.class")) {
+            Err(RuntimeError::Host { ref action, .. }) => assert_eq!(action, "keep the bytes of Reload"),
+            other => panic!("define_class returned {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use java::runtime::{Heap, LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::collections::VecDeque;
+
+pub(super) const CLEANER: &str = "java/lang/ref/Cleaner";
+pub(super) const CLEANABLE: &str = "java/lang/ref/Cleaner$Cleanable";
+/// the class of the cleanables `Cleaner.register` returns.
+pub(super) const PHANTOM_CLEANABLE: &str = "jdk/internal/ref/CleanerImpl$PhantomCleanableRef";
+
+/// what runs once the object of a registration became unreachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cleanup {
+    /// the `Runnable` registered with a `Cleaner` and the cleanable `register` returned for it.
+    Action { cleanable: ObjectRef, action: ObjectRef },
+    /// the `finalize` method of the object itself.
+    Finalize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Registration {
+    object: ObjectRef,
+    cleanup: Cleanup,
+}
+
+/// the objects registered with a `java.lang.ref.Cleaner` and, with finalization enabled, the
+/// objects of classes which override `finalize`.
+///
+/// the runtime holds the cleanables and actions, but not the registered objects. a collection which
+/// finds one of them unreachable queues its cleanup, an object to finalize survives until its
+/// `finalize` ran. the queue is worked off at the next safepoint, where the jvm would let its
+/// cleaner and finalizer threads run, so a cleanup never interrupts an instruction.
+#[derive(Debug, Default)]
+pub struct Cleaners {
+    registered: Vec<Registration>,
+    pending: VecDeque<Registration>,
+    /// true while the queue is worked off, so that cleanups do not run the ones after them.
+    running: bool,
+    completed: usize,
+}
+
+impl Cleaners {
+    pub fn new() -> Cleaners {
+        Cleaners::default()
+    }
+
+    /// the number of objects whose cleanup did not run yet.
+    pub fn registered(&self) -> usize {
+        self.registered.len() + self.pending.len()
+    }
+
+    /// the number of cleanups which ran, including the ones `Cleanable.clean` ran.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    pub(super) fn has_pending(&self) -> bool {
+        !self.running && !self.pending.is_empty()
+    }
+
+    /// the objects the cleanups need: the cleanables and their actions and the queued objects to
+    /// finalize.
+    pub(super) fn roots(&self) -> Vec<ObjectRef> {
+        let mut roots = Vec::new();
+        for registration in self.registered.iter().chain(self.pending.iter()) {
+            match registration.cleanup {
+                Cleanup::Action { cleanable, action } => roots.extend_from_slice(&[cleanable, action]),
+                Cleanup::Finalize => ()
+            }
+        }
+        roots.extend(self.pending.iter().filter(|registration| registration.cleanup == Cleanup::Finalize).map(|registration| registration.object));
+        roots
+    }
+
+    /// queues the cleanups of the registered objects the mark of a collection did not reach, and
+    /// marks the objects to finalize again so they survive it. returns the number of cleanups queued.
+    pub(super) fn enqueue_unreachable(&mut self, heap: &mut Heap) -> usize {
+        let (unreachable, registered): (Vec<_>, Vec<_>) = self.registered.drain(..).partition(|registration| !heap.is_marked(registration.object));
+        self.registered = registered;
+        let mut queued = 0;
+        for registration in unreachable {
+            // objects which are gone already were freed with the frame they never escaped
+            if heap.get(registration.object).is_none() {
+                continue;
+            }
+            if registration.cleanup == Cleanup::Finalize {
+                heap.mark(&[registration.object]);
+            }
+            self.pending.push_back(registration);
+            queued += 1;
+        }
+        queued
+    }
+
+    /// drops the registrations of `object`, which was freed without a collection, before its slot
+    /// is reused by another object.
+    pub(super) fn forget(&mut self, object: ObjectRef) {
+        if !self.registered.is_empty() {
+            self.registered.retain(|registration| registration.object != object);
+        }
+    }
+
+    /// removes the registration of `cleanable`, returning its action if it did not run yet.
+    fn unregister(&mut self, cleanable: ObjectRef) -> Option<ObjectRef> {
+        let is_cleanable = |registration: &Registration| match registration.cleanup {
+            Cleanup::Action { cleanable: candidate, .. } => candidate == cleanable,
+            Cleanup::Finalize => false
+        };
+        let registration = match self.registered.iter().position(is_cleanable) {
+            Some(index) => self.registered.remove(index),
+            None => self.pending.remove(self.pending.iter().position(is_cleanable)?)?
+        };
+        match registration.cleanup {
+            Cleanup::Action { action, .. } => Some(action),
+            Cleanup::Finalize => None
+        }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    pub fn cleaners(&self) -> &Cleaners {
+        &self.cleaners
+    }
+
+    /// implements `java.lang.ref.Cleaner` and its cleanables. every cleaner runs its cleanups on
+    /// the thread of the runtime, so the `ThreadFactory` of `create` is not used.
+    /// returns `None` for methods the classes do not have.
+    pub(super) fn invoke_cleaner(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        match (class_name, method_name, descriptor) {
+            (CLEANER, "create", "()Ljava/lang/ref/Cleaner;") | (CLEANER, "create", "(Ljava/util/concurrent/ThreadFactory;)Ljava/lang/ref/Cleaner;") => {
+                Some(Ok(Some(StackValue::Reference(self.allocate(CLEANER, ObjectData::Instance)))))
+            }
+            (CLEANER, "register", "(Ljava/lang/Object;Ljava/lang/Runnable;)Ljava/lang/ref/Cleaner$Cleanable;") => {
+                let (object, action) = match (arguments.get(1), arguments.get(2)) {
+                    (Some(LocalVariable::Reference(object)), Some(LocalVariable::Reference(action))) => (*object, *action),
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let cleanable = self.allocate(PHANTOM_CLEANABLE, ObjectData::Instance);
+                self.cleaners.registered.push(Registration { object, cleanup: Cleanup::Action { cleanable, action } });
+                Some(Ok(Some(StackValue::Reference(cleanable))))
+            }
+            // runs the action right away, once
+            (_, "clean", "()V") => {
+                let cleanable = match arguments.first() {
+                    Some(LocalVariable::Reference(cleanable)) => *cleanable,
+                    _ => return None
+                };
+                let action = match self.cleaners.unregister(cleanable) {
+                    Some(action) => action,
+                    None => return Some(Ok(None))
+                };
+                self.cleaners.completed += 1;
+                Some(self.run_action(action).map(|()| None))
+            }
+            _ => None
+        }
+    }
+
+    /// true if objects of `class_name` are finalized: finalization is enabled and the class or one
+    /// of its super classes overrides `finalize`.
+    pub(super) fn has_finalizer(&self, class_name: &str) -> bool {
+        self.finalization && self.classes.id(class_name).is_some() && self.method_target(class_name, "finalize", "()V").is_some()
+    }
+
+    /// registers a new object for finalization if its class has a finalizer.
+    pub(super) fn register_finalizer(&mut self, object: ObjectRef, class_name: &str) {
+        if self.has_finalizer(class_name) {
+            self.cleaners.registered.push(Registration { object, cleanup: Cleanup::Finalize });
+        }
+    }
+
+    /// runs the queued cleanups, from a safepoint or `System.runFinalization`. the frames have to be
+    /// suspended. exceptions thrown by a cleanup are ignored, like on the cleaner thread of the jvm.
+    pub(super) fn run_cleanups(&mut self) -> Result<(), RuntimeError> {
+        self.cleaners.running = true;
+        let result = self.run_pending_cleanups();
+        self.cleaners.running = false;
+        result
+    }
+
+    fn run_pending_cleanups(&mut self) -> Result<(), RuntimeError> {
+        while let Some(registration) = self.cleaners.pending.pop_front() {
+            let result = match registration.cleanup {
+                Cleanup::Action { action, .. } => self.run_action(action),
+                Cleanup::Finalize => {
+                    let class_name = self.heap.get(registration.object).map(|object| object.class_name.clone()).unwrap_or_default();
+                    self.invoke(&class_name, "finalize", "()V", vec![LocalVariable::Reference(registration.object)]).map(|_| ())
+                }
+            };
+            self.cleaners.completed += 1;
+            match result {
+                Ok(()) => (),
+                Err(RuntimeError::Exception { class_name, .. }) => debug!(exception = %class_name, "cleanup threw"),
+                Err(err) => return Err(err)
+            }
+        }
+        Ok(())
+    }
+
+    /// calls `run` of a `Runnable`, which usually is a lambda.
+    fn run_action(&mut self, action: ObjectRef) -> Result<(), RuntimeError> {
+        let class_name = self.heap.get(action).map(|object| object.class_name.clone()).unwrap_or_default();
+        let arguments = vec![LocalVariable::Reference(action)];
+        match (self.lambda_class(&class_name), self.thread.frames.last().map(|frame| (frame.class, frame.slot))) {
+            (Some(lambda), Some((caller, slot))) => {
+                let caller = self.classes.class(caller).clone();
+                self.invoke_lambda(&caller.methods[slot], &lambda, "run", "()V", arguments)?;
+            }
+            _ => {
+                self.invoke(&class_name, "run", "()V", arguments)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>(finalization: bool) -> Runtime<'a> {
+        let mut rt = Runtime::builder().finalization(finalization)
+            .build(read_class_file(include_bytes!("../../../sample/Cleanups.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Cleanups$Finalized.class")).unwrap().1);
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str, n: i64) -> i64 {
+        match rt.invoke_static("Cleanups", name, "(I)I", vec![LocalVariable::Integer(n)]) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn actions_of_unreachable_objects_run_after_a_collection() {
+        let mut rt = runtime(false);
+        assert_eq!(int(&mut rt, "reachable", 0), 0);
+        assert_eq!(rt.cleaners().registered(), 1);
+        assert_eq!(int(&mut rt, "unreachable", 3), 4);
+        // the object of `reachable` became unreachable when it returned
+        assert_eq!(rt.cleaners().registered(), 0);
+        assert_eq!(rt.cleaners().completed(), 4);
+    }
+
+    #[test]
+    fn clean_runs_the_action_once() {
+        let mut rt = runtime(false);
+        assert_eq!(int(&mut rt, "explicit", 0), 10);
+        rt.gc();
+        assert_eq!(int(&mut rt, "explicit", 0), 20);
+        assert_eq!(rt.cleaners().completed(), 2);
+    }
+
+    #[test]
+    fn finalizers_run_when_enabled() {
+        let mut rt = runtime(true);
+        assert_eq!(int(&mut rt, "finalizers", 2), 2);
+        // the finalized objects survived the collection which found them, but not the next one
+        assert_eq!(rt.gc().freed_objects, 2);
+        assert_eq!(int(&mut rt, "finalizers", 1), 3);
+
+        let mut rt = runtime(false);
+        assert_eq!(int(&mut rt, "finalizers", 2), 0);
+    }
+}
@@ -0,0 +1,128 @@
+use java::runtime::{LocalVariable, ObjectData, Runtime, RuntimeError, StackValue};
+use std::fmt;
+use std::io::{self, Write};
+
+pub(super) const PRINT_STREAM: &str = "java/io/PrintStream";
+const SYSTEM: &str = "java/lang/System";
+
+/// where `System.out` or `System.err` of a `Runtime` write to.
+pub struct Output(Box<dyn Write + Send>);
+
+impl Output {
+    pub fn new(writer: Box<dyn Write + Send>) -> Output {
+        Output(writer)
+    }
+
+    pub fn stdout() -> Output {
+        Output(Box::new(io::stdout()))
+    }
+
+    pub fn stderr() -> Output {
+        Output(Box::new(io::stderr()))
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Output")
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// writes a line of `-verbose` output to the stdout of the runtime, next to what the program
+    /// prints. like a `PrintStream`, write errors are not reported.
+    pub(super) fn print_verbose(&mut self, line: &dyn fmt::Display) {
+        let _ = writeln!(self.stdout, "{}", line).and_then(|_| self.stdout.flush());
+    }
+
+    /// `System.out` and `System.err`, created on first use and kept in the static fields of `System`.
+    pub(super) fn print_stream(&mut self, field_name: &str) -> Option<StackValue> {
+        if field_name != "out" && field_name != "err" {
+            return None;
+        }
+        let key = (String::from(SYSTEM), String::from(field_name));
+        if let Some(stream) = self.statics.get(&key) {
+            return Some(stream.clone());
+        }
+        let stream = StackValue::Reference(self.allocate(PRINT_STREAM, ObjectData::Instance));
+        self.statics.insert(key, stream.clone());
+        Some(stream)
+    }
+
+    /// the `print`, `println` and `flush` methods of `PrintStream`. everything is written as utf-8.
+    pub(super) fn invoke_print_stream(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let this = match arguments.first() {
+            Some(LocalVariable::Reference(this)) => *this,
+            _ => return None
+        };
+        let mut text = match (method_name, descriptor, arguments.get(1)) {
+            ("flush", "()V", _) | ("println", "()V", _) => String::new(),
+            (_, "(Z)V", Some(LocalVariable::Integer(value))) => String::from(if *value != 0 { "true" } else { "false" }),
+            (_, "(C)V", Some(LocalVariable::Integer(value))) => std::char::from_u32(*value as u32).map(String::from).unwrap_or_default(),
+            (_, "(I)V", Some(LocalVariable::Integer(value))) => (*value as i32).to_string(),
+            (_, "(J)V", Some(LocalVariable::Long(value))) => value.to_string(),
+            (_, "(Ljava/lang/String;)V", _) | (_, "(Ljava/lang/Object;)V", _) => match arguments.get(1) {
+                Some(LocalVariable::Reference(object)) => match self.object_to_string(*object) {
+                    Ok(text) => text,
+                    Err(err) => return Some(Err(err))
+                },
+                _ => String::from("null")
+            },
+            (_, "([C)V", Some(LocalVariable::Reference(array))) => match self.heap.get(*array).map(|array| &array.data) {
+                Some(ObjectData::Array(chars)) => chars.iter()
+                    .filter_map(|char| match char {
+                        StackValue::Integer(char) => std::char::from_u32(*char as u32),
+                        _ => None
+                    })
+                    .collect(),
+                _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+            },
+            _ => return None
+        };
+        if method_name == "println" {
+            text.push('\n');
+        } else if method_name != "print" && method_name != "flush" {
+            return None;
+        }
+
+        let err = self.statics.get(&(String::from(SYSTEM), String::from("err")));
+        let output = match err {
+            Some(StackValue::Reference(err)) if *err == this => &mut self.stderr,
+            _ => &mut self.stdout
+        };
+        // like a PrintStream, write errors are not reported to the program
+        let _ = output.0.write_all(text.as_bytes()).and_then(|_| output.0.flush());
+        Some(Ok(None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use captured::Captured;
+    use java::class_file::read_class_file;
+    use super::*;
+
+    #[test]
+    fn programs_print_to_the_configured_output() {
+        let (out, err) = (Captured::default(), Captured::default());
+        let mut rt = Runtime::builder()
+            .stdout(out.output())
+            .stderr(err.output())
+            .build(read_class_file(include_bytes!("../../../sample/Console.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Point.class")).unwrap().1);
+        rt.invoke_static("Console", "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]).unwrap();
+
+        assert_eq!(out.text(), "hello 42\ntrue x-7 null\nPoint[1, 2]\n");
+        assert_eq!(err.text(), "oops\n");
+    }
+}
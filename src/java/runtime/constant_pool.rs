@@ -0,0 +1,339 @@
+use java::class_file::{ClassFile, ConstantType};
+use java::runtime::{builtin, CallSite, ClassId, FieldSlot, Intrinsic, Runtime, RuntimeError, Signature};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+const ACC_PRIVATE: u16 = 0x0002;
+pub(super) const ACC_STATIC: u16 = 0x0008;
+pub(super) const ACC_NATIVE: u16 = 0x0100;
+
+/// a `MethodRef` resolved to its symbolic names and, for static dispatch, the method it invokes.
+#[derive(Debug)]
+pub struct ResolvedMethod {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    /// the parsed descriptor, shared with every method and reference of the same descriptor.
+    pub signature: Arc<Signature>,
+    /// the intrinsic calls run instead of the method, see `Intrinsic`.
+    pub intrinsic: Option<Intrinsic>,
+    /// the declaring class and the index of the method in it. `None` if the call goes to a native
+    /// or builtin, or the method is selected by the receiver.
+    pub target: Option<(ClassId, usize)>,
+}
+
+/// a `FieldRef` resolved to its symbolic names.
+#[derive(Debug)]
+pub struct ResolvedField {
+    pub class_name: String,
+    pub field_name: String,
+    pub descriptor: String,
+    /// the slot of an instance field, `None` for static fields and fields of classes which are not loaded.
+    pub slot: Option<FieldSlot>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Resolved {
+    Class(Arc<String>),
+    Method(Arc<ResolvedMethod>),
+    Field(Arc<ResolvedField>),
+    /// a linked `InvokeDynamic` call site.
+    CallSite(CallSite),
+}
+
+/// the resolved symbolic references of one class, indexed like its constant pool.
+///
+/// entries are resolved on first use and cached until `clear` is called,
+/// which happens whenever classes or natives change what a reference resolves to.
+#[derive(Debug)]
+pub struct RuntimeConstantPool {
+    entries: RwLock<Vec<Option<Resolved>>>,
+}
+
+impl RuntimeConstantPool {
+    pub fn new(class: &ClassFile) -> RuntimeConstantPool {
+        RuntimeConstantPool { entries: RwLock::new(vec![None; class.constants.len() + 1]) }
+    }
+
+    pub fn get(&self, index: u16) -> Option<Resolved> {
+        self.entries.read().unwrap().get(usize::from(index)).cloned().and_then(|entry| entry)
+    }
+
+    pub fn set(&self, index: u16, resolved: Resolved) {
+        if let Some(entry) = self.entries.write().unwrap().get_mut(usize::from(index)) {
+            *entry = Some(resolved);
+        }
+    }
+
+    pub fn clear(&self) {
+        for entry in self.entries.write().unwrap().iter_mut() {
+            *entry = None;
+        }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// resolves a `Class` constant of `class` to the class name.
+    pub(super) fn resolve_class(&self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, index: u16) -> Result<Arc<String>, RuntimeError> {
+        if let Some(Resolved::Class(name)) = pool.get(index) {
+            return Ok(name);
+        }
+
+        let name = match class.get_class_name_at(index) {
+            Some(name) => Arc::new(String::from(name)),
+            None => return Err(RuntimeError::InvalidConstant { index, expected: String::from("Class") })
+        };
+        self.check_module_access(class.get_class_name(), &name)?;
+        pool.set(index, Resolved::Class(name.clone()));
+        Ok(name)
+    }
+
+    /// resolves a `MethodRef` or `InterfaceMethodRef` constant of `class`.
+    pub(super) fn resolve_method(&self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, index: u16) -> Result<Arc<ResolvedMethod>, RuntimeError> {
+        if let Some(Resolved::Method(method)) = pool.get(index) {
+            return Ok(method);
+        }
+
+        let (class_index, name_and_type_index, interface) = match class.get_constant(index) {
+            Some(ConstantType::MethodRef { class_index, name_and_type_index }) => (*class_index, *name_and_type_index, false),
+            Some(ConstantType::InterfaceMethodRef { class_index, name_and_type_index }) => (*class_index, *name_and_type_index, true),
+            _ => return Err(RuntimeError::InvalidConstant { index, expected: String::from("MethodRef") })
+        };
+        let class_name = self.resolve_class(pool, class, class_index)?;
+        let (method_name, descriptor) = match class.get_name_and_type(name_and_type_index) {
+            Some(name_and_type) => name_and_type,
+            None => return Err(RuntimeError::InvalidConstant { index: name_and_type_index, expected: String::from("NameAndType") })
+        };
+        // methods of interfaces are only referenced by InterfaceMethodRef and the other way round, see jvms 5.4.3.3 and 5.4.3.4
+        if let Some(referenced) = self.classes.get(&class_name) {
+            if referenced.is_interface() != interface {
+                let kind = if interface { "an interface" } else { "a class" };
+                return Err(RuntimeError::IncompatibleClassChange { message: format!("found {}, but {} was expected", class_name, kind) });
+            }
+        }
+
+        let signature = match self.classes.signatures().intern(descriptor) {
+            Some(signature) => signature,
+            None => return Err(RuntimeError::InvalidConstant { index: name_and_type_index, expected: String::from("method descriptor") })
+        };
+        let method = Arc::new(ResolvedMethod {
+            signature,
+            intrinsic: self.intrinsic(&class_name, method_name, descriptor),
+            target: self.method_target(&class_name, method_name, descriptor),
+            class_name: String::clone(&class_name),
+            method_name: String::from(method_name),
+            descriptor: String::from(descriptor),
+        });
+        pool.set(index, Resolved::Method(method.clone()));
+        Ok(method)
+    }
+
+    /// resolves a `FieldRef` constant of `class`.
+    pub(super) fn resolve_field(&self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, index: u16) -> Result<Arc<ResolvedField>, RuntimeError> {
+        if let Some(Resolved::Field(field)) = pool.get(index) {
+            return Ok(field);
+        }
+
+        let (class_index, name_and_type_index) = match class.get_constant(index) {
+            Some(ConstantType::FieldRef { class_index, name_and_type_index }) => (*class_index, *name_and_type_index),
+            _ => return Err(RuntimeError::InvalidConstant { index, expected: String::from("FieldRef") })
+        };
+        let class_name = self.resolve_class(pool, class, class_index)?;
+        let field = match class.get_name_and_type(name_and_type_index) {
+            Some((field_name, descriptor)) => {
+                let owner = self.field_owner(&class_name, field_name, descriptor).unwrap_or_else(|| String::clone(&class_name));
+                let slot = self.classes.id(&owner).and_then(|id| FieldSlot::new(self.classes.layout(id).clone(), field_name));
+                Arc::new(ResolvedField { class_name: owner, field_name: String::from(field_name), descriptor: String::from(descriptor), slot })
+            }
+            None => return Err(RuntimeError::InvalidConstant { index: name_and_type_index, expected: String::from("NameAndType") })
+        };
+        pool.set(index, Resolved::Field(field.clone()));
+        Ok(field)
+    }
+
+    /// the class which declares a field referenced through `class_name`, searching its interfaces
+    /// and then its super classes like jvms 5.4.3.2. `None` if no loaded class declares it.
+    pub(super) fn field_owner(&self, class_name: &str, field_name: &str, descriptor: &str) -> Option<String> {
+        let class = self.classes.get(class_name)?;
+        if class.declares_field(field_name, descriptor) {
+            return Some(String::from(class_name));
+        }
+
+        class.interfaces.iter()
+            .filter_map(|&index| class.get_class_name_at(index))
+            .chain(class.get_super_class_name())
+            .filter_map(|name| self.field_owner(name, field_name, descriptor))
+            .next()
+    }
+
+    /// the bytecode method a call to `method_name` of `class_name` ends up in, searching the super
+    /// classes like `invoke` does and then the default methods of the interfaces. `None` if a native or
+    /// builtin would run instead.
+    pub(super) fn method_target(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<(ClassId, usize)> {
+        let mut current = Some(String::from(class_name));
+        while let Some(name) = current {
+            if self.natives.get(&name, method_name, descriptor).is_some() {
+                return None;
+            }
+            // builtin classes end the search through the super classes, the interfaces come next
+            if builtin::is_builtin(&name) {
+                return self.default_method(class_name, method_name, descriptor);
+            }
+
+            let class = match self.classes.id(&name) {
+                Some(class) => class,
+                None => break
+            };
+            if let Some(slot) = self.classes.method(class, method_name, descriptor) {
+                return Some((class, slot));
+            }
+
+            current = self.superclass_of(&name);
+        }
+
+        self.default_method(class_name, method_name, descriptor)
+    }
+
+    /// the maximally specific default method of the super interfaces of `class_name` and its super
+    /// classes, see jvms 5.4.3.3. an interface which declares the method abstract hides the defaults of
+    /// its own super interfaces, static and private interface methods are never inherited.
+    fn default_method(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<(ClassId, usize)> {
+        let mut pending = Vec::new();
+        let mut current = Some(String::from(class_name));
+        while let Some(name) = current {
+            if let Some(class) = self.classes.get(&name) {
+                pending.extend(class.interfaces.iter().filter_map(|&index| class.get_class_name_at(index)).map(String::from));
+            }
+            current = self.superclass_of(&name);
+        }
+
+        let mut visited = HashSet::new();
+        let mut candidates = Vec::new();
+        while let Some(interface) = pending.pop() {
+            if !visited.insert(interface.clone()) {
+                continue;
+            }
+            let id = match self.classes.id(&interface) {
+                Some(id) => id,
+                None => continue
+            };
+            let class = self.classes.class(id);
+            match self.classes.method(id, method_name, descriptor) {
+                Some(slot) if class.methods[slot].access_flags & (ACC_STATIC | ACC_PRIVATE) == 0 => if class.methods[slot].get_code().is_some() {
+                    candidates.push((interface, id, slot));
+                },
+                _ => pending.extend(class.interfaces.iter().filter_map(|&index| class.get_class_name_at(index)).map(String::from))
+            }
+        }
+
+        candidates.iter()
+            .find(|&(name, _, _)| !candidates.iter().any(|(other, _, _)| other != name && self.implements(other, name)))
+            .map(|&(_, id, slot)| (id, slot))
+    }
+
+    /// true if `resolved` is a private method, which is invoked without selecting an override.
+    pub(super) fn is_private(&self, resolved: &ResolvedMethod) -> bool {
+        resolved.target.is_some_and(|(class, slot)| self.classes.class(class).methods[slot].access_flags & ACC_PRIVATE != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, StackValue};
+    use super::*;
+
+    #[test]
+    fn resolutions_are_cached_until_natives_change() {
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        let class = rt.classes.get("Tiny").unwrap();
+        let pool = rt.classes.constant_pool("Tiny").unwrap();
+        let index = (1..=class.constants.len() as u16)
+            .find(|&index| match class.get_constant(index) {
+                Some(ConstantType::MethodRef { name_and_type_index, .. }) => class.get_name_and_type(*name_and_type_index).map(|(name, _)| name) == Some("add"),
+                _ => false
+            })
+            .unwrap();
+
+        let first = rt.resolve_method(&pool, &class, index).unwrap();
+        assert!(Arc::ptr_eq(&first, &rt.resolve_method(&pool, &class, index).unwrap()));
+        match first.target {
+            Some((target, slot)) => assert_eq!(rt.classes.class(target).methods[slot].name, "add"),
+            None => panic!("Tiny.add should resolve to bytecode")
+        }
+
+        rt.register_native("Tiny", "add", "(II)I", |_, _| Ok(None));
+        assert!(rt.resolve_method(&pool, &class, index).unwrap().target.is_none());
+    }
+
+    fn interfaces_runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Interfaces.class")).unwrap().1);
+        for class in [
+            &include_bytes!("../../../sample/Sized.class")[..], &include_bytes!("../../../sample/Weighted.class")[..],
+            &include_bytes!("../../../sample/Box.class")[..], &include_bytes!("../../../sample/Crate.class")[..],
+            &include_bytes!("../../../sample/Parcel.class")[..],
+        ].iter() {
+            rt.load_class(read_class_file(class).unwrap().1);
+        }
+        rt
+    }
+
+    #[test]
+    fn interface_methods_are_invoked() {
+        let mut rt = interfaces_runtime();
+        // a static interface method calling a private static one
+        assert_eq!(rt.invoke_static("Interfaces", "helper", "(I)I", vec![LocalVariable::Integer(2)]).unwrap(), Some(StackValue::Integer(6)));
+
+        // Box inherits the default method, which invokes a private interface method with invokespecial.
+        // Crate calls the default of Weighted, which calls the one of Sized, and Parcel gets the
+        // default of Weighted as the more specific one
+        for &(kind, expected) in [(0, 6 + 4), (1, (10 + 4 + 100) * 10), (2, 6 + 4 + 100)].iter() {
+            match rt.invoke_static("Interfaces", "score", "(I)I", vec![LocalVariable::Integer(kind)]) {
+                Ok(Some(StackValue::Integer(score))) => assert_eq!(score, expected, "score({})", kind),
+                other => panic!("score({}) returned {:?}", kind, other)
+            }
+        }
+    }
+
+    #[test]
+    fn method_references_must_match_the_kind_of_class() {
+        let rt = interfaces_runtime();
+        let class = rt.classes.get("Interfaces").unwrap();
+        let pool = rt.classes.constant_pool("Interfaces").unwrap();
+        let index = (1..=class.constants.len() as u16)
+            .find(|&index| matches!(class.get_constant(index), Some(ConstantType::InterfaceMethodRef { .. })))
+            .unwrap();
+        assert!(rt.resolve_method(&pool, &class, index).is_ok());
+
+        // the same reference from a class which expects Sized to be a class
+        let mut changed = read_class_file(include_bytes!("../../../sample/Interfaces.class")).unwrap().1;
+        let (class_index, name_and_type_index) = match changed.get_constant(index) {
+            Some(ConstantType::InterfaceMethodRef { class_index, name_and_type_index }) => (*class_index, *name_and_type_index),
+            _ => unreachable!()
+        };
+        changed.constants[usize::from(index) - 1] = ConstantType::MethodRef { class_index, name_and_type_index };
+        match rt.resolve_method(&RuntimeConstantPool::new(&changed), &changed, index) {
+            Err(RuntimeError::IncompatibleClassChange { .. }) => (),
+            other => panic!("unexpected resolution {:?}", other)
+        }
+    }
+
+    #[test]
+    fn every_member_reference_resolves_to_its_class() {
+        let mut rt = interfaces_runtime();
+        rt.load_class(read_class_file(include_bytes!("../../../sample/FieldExample.class")).unwrap().1);
+        for class_name in ["Interfaces", "FieldExample"].iter() {
+            let class = rt.classes.get(class_name).unwrap();
+            let pool = rt.classes.constant_pool(class_name).unwrap();
+            for index in 1..=class.constants.len() as u16 {
+                let (class_index, resolved) = match class.get_constant(index) {
+                    Some(ConstantType::FieldRef { class_index, .. }) => (*class_index, rt.resolve_field(&pool, &class, index).unwrap().class_name.clone()),
+                    Some(ConstantType::MethodRef { class_index, .. }) | Some(ConstantType::InterfaceMethodRef { class_index, .. }) =>
+                        (*class_index, rt.resolve_method(&pool, &class, index).unwrap().class_name.clone()),
+                    _ => continue
+                };
+                assert_eq!(Some(resolved.as_str()), class.get_class_name_at(class_index), "constant {} of {}", index, class_name);
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use java::runtime::{ClassId, ClassRegistry, Runtime};
+use std::fmt;
+
+/// how often a method was invoked and how many backward branches it took.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct MethodCounters {
+    pub invocations: u64,
+    pub back_edges: u64,
+}
+
+impl MethodCounters {
+    /// the weight used to rank methods, loops count like calls.
+    pub fn total(&self) -> u64 {
+        self.invocations + self.back_edges
+    }
+}
+
+/// the counters of one method in a `MethodProfile`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HotMethod {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    pub counters: MethodCounters,
+}
+
+/// the methods which ran, hottest first.
+#[derive(Debug, Clone, Default)]
+pub struct MethodProfile {
+    pub methods: Vec<HotMethod>,
+}
+
+impl MethodProfile {
+    pub fn method(&self, class_name: &str, method_name: &str) -> Option<&MethodCounters> {
+        self.methods.iter()
+            .find(|method| method.class_name == class_name && method.method_name == method_name)
+            .map(|method| &method.counters)
+    }
+}
+
+impl fmt::Display for MethodProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>12} {:>12}  method", "invocations", "back edges")?;
+        for method in self.methods.iter() {
+            writeln!(f, "{:>12} {:>12}  {}.{}{}", method.counters.invocations, method.counters.back_edges, method.class_name, method.method_name, method.descriptor)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// counts invocations and back edges per method and notices when a method gets hot,
+/// which is what a compiling tier would use to pick its candidates.
+#[derive(Debug)]
+pub struct InvocationCounters {
+    /// indexed by class id and method slot.
+    counters: Vec<Vec<MethodCounters>>,
+    threshold: u64,
+    hot: Vec<(ClassId, usize)>,
+    retired: u64,
+}
+
+impl InvocationCounters {
+    pub fn new(threshold: u64) -> InvocationCounters {
+        InvocationCounters { counters: Vec::new(), threshold, hot: Vec::new(), retired: 0 }
+    }
+
+    fn counters_mut(&mut self, class: ClassId, slot: usize) -> &mut MethodCounters {
+        if self.counters.len() <= class.index() {
+            self.counters.resize(class.index() + 1, Vec::new());
+        }
+        let methods = &mut self.counters[class.index()];
+        if methods.len() <= slot {
+            methods.resize(slot + 1, MethodCounters::default());
+        }
+        &mut methods[slot]
+    }
+
+    /// adds to the counters of a method. returns true if this made the method hot.
+    fn count(&mut self, class: ClassId, slot: usize, invocations: u64, back_edges: u64) -> bool {
+        let threshold = self.threshold;
+        let counters = self.counters_mut(class, slot);
+        let before = counters.total();
+        counters.invocations += invocations;
+        counters.back_edges += back_edges;
+        let hot = before < threshold && counters.total() >= threshold;
+        if hot {
+            self.hot.push((class, slot));
+        }
+        hot
+    }
+
+    pub fn invoked(&mut self, class: ClassId, slot: usize) -> bool {
+        self.count(class, slot, 1, 0)
+    }
+
+    pub fn back_edge(&mut self, class: ClassId, slot: usize) -> bool {
+        self.count(class, slot, 0, 1)
+    }
+
+    /// counts instructions run to completion, a superinstruction counts as the instructions it replaces.
+    pub fn retire(&mut self, instructions: u64) {
+        self.retired += instructions;
+    }
+
+    /// the number of instructions run so far.
+    pub fn instructions_retired(&self) -> u64 {
+        self.retired
+    }
+
+    pub fn get(&self, class: ClassId, slot: usize) -> MethodCounters {
+        self.counters.get(class.index()).and_then(|methods| methods.get(slot)).cloned().unwrap_or_default()
+    }
+
+    /// true once a method crossed the threshold.
+    pub fn is_hot(&self, class: ClassId, slot: usize) -> bool {
+        self.get(class, slot).total() >= self.threshold
+    }
+
+    /// drops the counters of an unloaded class.
+    pub fn forget(&mut self, class: ClassId) {
+        if let Some(methods) = self.counters.get_mut(class.index()) {
+            methods.clear();
+        }
+        self.hot.retain(|&(hot, _)| hot != class);
+    }
+
+    /// the methods which crossed the threshold, in the order they did.
+    pub fn hot_methods(&self) -> &[(ClassId, usize)] {
+        &self.hot
+    }
+
+    pub fn profile(&self, classes: &ClassRegistry) -> MethodProfile {
+        let mut methods = Vec::new();
+        for (index, counters) in self.counters.iter().enumerate().filter(|&(index, _)| classes.is_loaded(ClassId::from_index(index))) {
+            let class = classes.class(ClassId::from_index(index));
+            for (slot, counters) in counters.iter().enumerate().filter(|(_, counters)| counters.total() > 0) {
+                methods.push(HotMethod {
+                    class_name: String::from(class.get_class_name()),
+                    method_name: String::from(&*class.methods[slot].name),
+                    descriptor: String::from(&*class.methods[slot].descriptor),
+                    counters: *counters,
+                });
+            }
+        }
+        methods.sort_by(|a, b| b.counters.total().cmp(&a.counters.total()).then_with(|| a.class_name.cmp(&b.class_name)));
+
+        MethodProfile { methods }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// the invocation and back edge counts of all methods which ran.
+    pub fn method_profile(&self) -> MethodProfile {
+        self.counters.profile(&self.classes)
+    }
+
+    /// the number of bytecode instructions the runtime has executed.
+    pub fn instructions_retired(&self) -> u64 {
+        self.counters.instructions_retired()
+    }
+
+    /// counts an invocation of a method.
+    pub(super) fn count_invocation(&mut self, class: ClassId, slot: usize) {
+        if self.counters.invoked(class, slot) {
+            self.became_hot(class, slot);
+        }
+    }
+
+    /// counts a backward branch taken in a method.
+    pub(super) fn count_back_edge(&mut self, class: ClassId, slot: usize) {
+        if self.counters.back_edge(class, slot) {
+            self.became_hot(class, slot);
+        }
+    }
+
+    fn became_hot(&self, class: ClassId, slot: usize) {
+        let class = self.classes.class(class);
+        let method = &class.methods[slot];
+        debug!(class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor, "method is hot");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{InterpreterMode, LocalVariable};
+    use super::*;
+
+    #[test]
+    fn it_counts_invocations_and_back_edges() {
+        let mut rt = Runtime::builder()
+            .interpreter(InterpreterMode::Fast)
+            .hot_method_threshold(6)
+            .build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(5)]).unwrap();
+        rt.invoke_static("Loop", "max", "(II)I", vec![LocalVariable::Integer(1), LocalVariable::Integer(2)]).unwrap();
+
+        let profile = rt.method_profile();
+        assert_eq!(profile.method("Loop", "sum"), Some(&MethodCounters { invocations: 1, back_edges: 5 }));
+        assert_eq!(profile.method("Loop", "max"), Some(&MethodCounters { invocations: 1, back_edges: 0 }));
+        assert_eq!(profile.methods[0].method_name, "sum");
+
+        let id = rt.classes.id("Loop").unwrap();
+        let sum = rt.classes.method(id, "sum", "(I)I").unwrap();
+        assert_eq!(rt.counters.hot_methods(), &[(id, sum)]);
+        assert!(rt.instructions_retired() > 5 * 7);
+    }
+}
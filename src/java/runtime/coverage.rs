@@ -0,0 +1,458 @@
+use java::class_file::{ClassFile, Method};
+use java::instructions::Instruction;
+use java::runtime::RuntimeHook;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// the executed instructions and branches of one method.
+#[derive(Debug, Clone, Default)]
+pub struct MethodCoverage {
+    pub name: String,
+    pub descriptor: String,
+    pub invocations: u64,
+    /// how often each instruction ran, keyed by pc.
+    pub instructions: BTreeMap<usize, u64>,
+    /// the source line of each instruction, keyed by pc.
+    pub lines: BTreeMap<usize, usize>,
+    /// how often each conditional branch was taken and how often it fell through, keyed by pc.
+    pub branches: BTreeMap<usize, (u64, u64)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClassCoverage {
+    pub source_file: String,
+    pub methods: Vec<MethodCoverage>,
+}
+
+/// covered and missed counts, like the counters of a JaCoCo report.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Counter {
+    pub missed: usize,
+    pub covered: usize,
+}
+
+/// the counters of one source line.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct LineCoverage {
+    pub hits: u64,
+    pub instructions: Counter,
+    pub branches: Counter,
+}
+
+/// the coverage of all classes loaded while the `CoverageCollector` was installed.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    pub classes: BTreeMap<String, ClassCoverage>,
+}
+
+impl Counter {
+    fn add(&mut self, covered: bool) {
+        if covered {
+            self.covered += 1;
+        } else {
+            self.missed += 1;
+        }
+    }
+
+    fn merge(&mut self, other: Counter) {
+        self.missed += other.missed;
+        self.covered += other.covered;
+    }
+}
+
+fn is_conditional_branch(instruction: &Instruction) -> bool {
+    matches!(instruction,
+        Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) | Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) |
+        Instruction::IfICmpEQ(_) | Instruction::IfICmpNE(_) | Instruction::IfICmpLT(_) | Instruction::IfICmpGE(_) | Instruction::IfICmpGT(_) | Instruction::IfICmpLE(_) |
+        Instruction::IfACmpEQ(_) | Instruction::IfACmpNE(_) | Instruction::IfNull(_) | Instruction::IfNonNull(_))
+}
+
+/// the source file javac would have used for a class without a `SourceFile` attribute.
+fn default_source_file(class_name: &str) -> String {
+    let outer = class_name.split('$').next().unwrap_or(class_name);
+    format!("{}.java", outer.rsplit('/').next().unwrap_or(outer))
+}
+
+fn package_of(class_name: &str) -> &str {
+    class_name.rfind('/').map(|index| &class_name[..index]).unwrap_or("")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+impl MethodCoverage {
+    fn new(method: &Method) -> MethodCoverage {
+        let mut coverage = MethodCoverage {
+            name: String::from(&*method.name),
+            descriptor: String::from(&*method.descriptor),
+            ..MethodCoverage::default()
+        };
+
+        if let Some(code) = method.get_code() {
+            for (pc, instruction) in code.instructions_with_pc().unwrap_or_default() {
+                coverage.instructions.insert(pc, 0);
+                if let Some(line) = code.line_number_at(pc) {
+                    coverage.lines.insert(pc, line);
+                }
+                if is_conditional_branch(&instruction) {
+                    coverage.branches.insert(pc, (0, 0));
+                }
+            }
+        }
+
+        coverage
+    }
+
+    pub fn first_line(&self) -> Option<usize> {
+        self.lines.values().cloned().min()
+    }
+
+    /// the counters of every line of this method.
+    pub fn line_coverage(&self) -> BTreeMap<usize, LineCoverage> {
+        let mut lines: BTreeMap<usize, LineCoverage> = BTreeMap::new();
+        for (pc, &hits) in self.instructions.iter() {
+            let line = match self.lines.get(pc) {
+                Some(&line) => lines.entry(line).or_default(),
+                None => continue
+            };
+            line.hits = line.hits.max(hits);
+            line.instructions.add(hits > 0);
+            if let Some(&(taken, not_taken)) = self.branches.get(pc) {
+                line.branches.add(taken > 0);
+                line.branches.add(not_taken > 0);
+            }
+        }
+        lines
+    }
+
+    pub fn instruction_counter(&self) -> Counter {
+        let mut counter = Counter::default();
+        for &hits in self.instructions.values() {
+            counter.add(hits > 0);
+        }
+        counter
+    }
+
+    pub fn branch_counter(&self) -> Counter {
+        let mut counter = Counter::default();
+        for &(taken, not_taken) in self.branches.values() {
+            counter.add(taken > 0);
+            counter.add(not_taken > 0);
+        }
+        counter
+    }
+
+    pub fn line_counter(&self) -> Counter {
+        let mut counter = Counter::default();
+        for line in self.line_coverage().values() {
+            counter.add(line.hits > 0);
+        }
+        counter
+    }
+}
+
+impl ClassCoverage {
+    /// the counters of every line of the class, merged over all methods.
+    pub fn line_coverage(&self) -> BTreeMap<usize, LineCoverage> {
+        let mut lines: BTreeMap<usize, LineCoverage> = BTreeMap::new();
+        for method in self.methods.iter() {
+            for (number, coverage) in method.line_coverage() {
+                let line = lines.entry(number).or_default();
+                line.hits = line.hits.max(coverage.hits);
+                line.instructions.merge(coverage.instructions);
+                line.branches.merge(coverage.branches);
+            }
+        }
+        lines
+    }
+}
+
+impl Coverage {
+    /// writes the coverage in the LCOV tracefile format read by `genhtml` and most CI tools.
+    pub fn write_lcov<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (class_name, class) in self.classes.iter() {
+            let package = package_of(class_name);
+            writeln!(out, "TN:")?;
+            if package.is_empty() {
+                writeln!(out, "SF:{}", class.source_file)?;
+            } else {
+                writeln!(out, "SF:{}/{}", package, class.source_file)?;
+            }
+
+            for method in class.methods.iter() {
+                writeln!(out, "FN:{},{}.{}{}", method.first_line().unwrap_or(0), class_name, method.name, method.descriptor)?;
+            }
+            for method in class.methods.iter() {
+                writeln!(out, "FNDA:{},{}.{}{}", method.invocations, class_name, method.name, method.descriptor)?;
+            }
+            writeln!(out, "FNF:{}", class.methods.len())?;
+            writeln!(out, "FNH:{}", class.methods.iter().filter(|method| method.invocations > 0).count())?;
+
+            let mut branches = Counter::default();
+            for method in class.methods.iter() {
+                for (block, (pc, &(taken, not_taken))) in method.branches.iter().enumerate() {
+                    let line = method.lines.get(pc).cloned().unwrap_or(0);
+                    let reached = method.instructions.get(pc).cloned().unwrap_or(0) > 0;
+                    for (branch, &count) in [taken, not_taken].iter().enumerate() {
+                        if reached {
+                            writeln!(out, "BRDA:{},{},{},{}", line, block, branch, count)?;
+                        } else {
+                            writeln!(out, "BRDA:{},{},{},-", line, block, branch)?;
+                        }
+                        branches.add(count > 0);
+                    }
+                }
+            }
+            writeln!(out, "BRF:{}", branches.missed + branches.covered)?;
+            writeln!(out, "BRH:{}", branches.covered)?;
+
+            let lines = class.line_coverage();
+            for (line, coverage) in lines.iter() {
+                writeln!(out, "DA:{},{}", line, coverage.hits)?;
+            }
+            writeln!(out, "LF:{}", lines.len())?;
+            writeln!(out, "LH:{}", lines.values().filter(|line| line.hits > 0).count())?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    /// writes the coverage in the XML format of JaCoCo reports, understood by most java tooling.
+    pub fn write_jacoco_xml<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut packages: BTreeMap<&str, Vec<(&String, &ClassCoverage)>> = BTreeMap::new();
+        for (class_name, class) in self.classes.iter() {
+            packages.entry(package_of(class_name)).or_default().push((class_name, class));
+        }
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#)?;
+        writeln!(out, r#"<!DOCTYPE report PUBLIC "-//JACOCO//DTD Report 1.1//EN" "report.dtd">"#)?;
+        writeln!(out, r#"<report name="rjvm">"#)?;
+        let mut report = Counters::default();
+        for (package, classes) in packages {
+            writeln!(out, r#"  <package name="{}">"#, xml_escape(package))?;
+            let mut package_counters = Counters::default();
+            let mut source_files: BTreeMap<&str, Vec<&ClassCoverage>> = BTreeMap::new();
+
+            for (class_name, class) in classes {
+                writeln!(out, r#"    <class name="{}" sourcefilename="{}">"#, xml_escape(class_name), xml_escape(&class.source_file))?;
+                let mut class_counters = Counters::default();
+                for method in class.methods.iter() {
+                    writeln!(out, r#"      <method name="{}" desc="{}" line="{}">"#, xml_escape(&method.name), xml_escape(&method.descriptor), method.first_line().unwrap_or(0))?;
+                    let counters = Counters::of_method(method);
+                    counters.write_to(out, "        ")?;
+                    writeln!(out, "      </method>")?;
+                    class_counters.merge(&counters);
+                }
+                class_counters.classes.add(class.methods.iter().any(|method| method.invocations > 0));
+                class_counters.write_to(out, "      ")?;
+                writeln!(out, "    </class>")?;
+                package_counters.merge(&class_counters);
+                source_files.entry(&class.source_file).or_default().push(class);
+            }
+
+            for (source_file, classes) in source_files {
+                writeln!(out, r#"    <sourcefile name="{}">"#, xml_escape(source_file))?;
+                let mut lines: BTreeMap<usize, LineCoverage> = BTreeMap::new();
+                for class in classes.iter() {
+                    for (number, coverage) in class.line_coverage() {
+                        let line = lines.entry(number).or_default();
+                        line.hits = line.hits.max(coverage.hits);
+                        line.instructions.merge(coverage.instructions);
+                        line.branches.merge(coverage.branches);
+                    }
+                }
+                for (number, line) in lines.iter() {
+                    writeln!(out, r#"      <line nr="{}" mi="{}" ci="{}" mb="{}" cb="{}"/>"#, number,
+                             line.instructions.missed, line.instructions.covered, line.branches.missed, line.branches.covered)?;
+                }
+                writeln!(out, "    </sourcefile>")?;
+            }
+
+            package_counters.write_to(out, "    ")?;
+            writeln!(out, "  </package>")?;
+            report.merge(&package_counters);
+        }
+        report.write_to(out, "  ")?;
+        writeln!(out, "</report>")
+    }
+}
+
+/// the counters JaCoCo writes for every element of its reports.
+#[derive(Default)]
+struct Counters {
+    instructions: Counter,
+    branches: Counter,
+    lines: Counter,
+    methods: Counter,
+    classes: Counter,
+}
+
+impl Counters {
+    fn of_method(method: &MethodCoverage) -> Counters {
+        let mut methods = Counter::default();
+        methods.add(method.invocations > 0);
+        Counters {
+            instructions: method.instruction_counter(),
+            branches: method.branch_counter(),
+            lines: method.line_counter(),
+            methods,
+            classes: Counter::default(),
+        }
+    }
+
+    fn merge(&mut self, other: &Counters) {
+        self.instructions.merge(other.instructions);
+        self.branches.merge(other.branches);
+        self.lines.merge(other.lines);
+        self.methods.merge(other.methods);
+        self.classes.merge(other.classes);
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W, indent: &str) -> io::Result<()> {
+        let counters = [("INSTRUCTION", self.instructions), ("BRANCH", self.branches), ("LINE", self.lines), ("METHOD", self.methods), ("CLASS", self.classes)];
+        for &(name, counter) in counters.iter() {
+            // like JaCoCo, leave out what does not exist at this level
+            if counter.missed + counter.covered > 0 {
+                writeln!(out, r#"{}<counter type="{}" missed="{}" covered="{}"/>"#, indent, name, counter.missed, counter.covered)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct CollectorState {
+    coverage: Coverage,
+    /// the conditional branch executed last, resolved by the next instruction of the same method.
+    pending_branch: Option<(String, String, String, usize)>,
+}
+
+/// a `RuntimeHook` recording which instructions and branches run.
+///
+/// clones share the recorded data, so install one clone with `Runtime::add_hook`
+/// and read the `Coverage` from another.
+#[derive(Clone, Default)]
+pub struct CoverageCollector {
+    state: Arc<Mutex<CollectorState>>,
+}
+
+impl CoverageCollector {
+    pub fn new() -> CoverageCollector {
+        CoverageCollector::default()
+    }
+
+    pub fn coverage(&self) -> Coverage {
+        self.state.lock().unwrap().coverage.clone()
+    }
+}
+
+impl CollectorState {
+    fn method(&mut self, class_name: &str, method: &Method) -> Option<&mut MethodCoverage> {
+        self.coverage.classes.get_mut(class_name)?.methods.iter_mut()
+            .find(|coverage| coverage.name == method.name && coverage.descriptor == method.descriptor)
+    }
+}
+
+impl RuntimeHook for CoverageCollector {
+    fn on_class_load(&mut self, class: &ClassFile) {
+        let coverage = ClassCoverage {
+            source_file: class.get_source_file().map(String::from).unwrap_or_else(|| default_source_file(class.get_class_name())),
+            methods: class.methods.iter().filter(|method| method.get_code().is_some()).map(MethodCoverage::new).collect(),
+        };
+        self.state.lock().unwrap().coverage.classes.insert(String::from(class.get_class_name()), coverage);
+    }
+
+    fn on_method_enter(&mut self, class_name: &str, method: &Method) {
+        if let Some(coverage) = self.state.lock().unwrap().method(class_name, method) {
+            coverage.invocations += 1;
+        }
+    }
+
+    fn on_instruction(&mut self, class_name: &str, method: &Method, pc: usize, instruction: &Instruction) {
+        let mut state = self.state.lock().unwrap();
+
+        let pending = match state.pending_branch {
+            Some((ref class, ref name, ref descriptor, branch_pc)) if class == class_name && *name == method.name && *descriptor == method.descriptor => Some(branch_pc),
+            _ => None
+        };
+        if let Some(branch_pc) = pending {
+            state.pending_branch = None;
+            if let Some(counts) = state.method(class_name, method).and_then(|coverage| coverage.branches.get_mut(&branch_pc)) {
+                // every conditional branch is three bytes long
+                if pc == branch_pc + 3 {
+                    counts.1 += 1;
+                } else {
+                    counts.0 += 1;
+                }
+            }
+        }
+
+        if let Some(coverage) = state.method(class_name, method) {
+            *coverage.instructions.entry(pc).or_insert(0) += 1;
+        }
+        if is_conditional_branch(instruction) {
+            state.pending_branch = Some((String::from(class_name), String::from(&*method.name), String::from(&*method.descriptor), pc));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, Runtime};
+    use super::*;
+
+    fn tiny_coverage() -> Coverage {
+        let collector = CoverageCollector::new();
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        rt.add_hook(Box::new(collector.clone()));
+        rt.invoke_static("Tiny", "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]).unwrap();
+        collector.coverage()
+    }
+
+    #[test]
+    fn it_records_lines_and_methods() {
+        let coverage = tiny_coverage();
+        let tiny = &coverage.classes["Tiny"];
+
+        let lines = tiny.line_coverage();
+        let hit = lines.iter().filter(|(_, line)| line.hits > 0).map(|(&number, _)| number).collect::<Vec<_>>();
+        let missed = lines.iter().filter(|(_, line)| line.hits == 0).map(|(&number, _)| number).collect::<Vec<_>>();
+        assert_eq!(hit, vec![2, 6, 10, 11, 13, 14]);
+        assert_eq!(missed, vec![1, 4]);
+
+        let add = tiny.methods.iter().find(|method| method.name == "add").unwrap();
+        assert_eq!(add.invocations, 1);
+        assert_eq!(add.instruction_counter(), Counter { missed: 0, covered: 4 });
+    }
+
+    #[test]
+    fn it_writes_lcov() {
+        let mut out = Vec::new();
+        tiny_coverage().write_lcov(&mut out).unwrap();
+        let lcov = String::from_utf8(out).unwrap();
+
+        assert!(lcov.starts_with("TN:\nSF:tiny.java\n"));
+        assert!(lcov.contains("FNDA:1,Tiny.add(II)I\n"));
+        assert!(lcov.contains("FNDA:0,Tiny.test()Ljava/lang/String;\n"));
+        assert!(lcov.contains("DA:4,0\n"));
+        assert!(lcov.contains("DA:13,1\n"));
+        assert!(lcov.contains("LF:8\nLH:6\nend_of_record\n"));
+    }
+
+    #[test]
+    fn it_writes_jacoco_xml() {
+        let mut out = Vec::new();
+        tiny_coverage().write_jacoco_xml(&mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+
+        assert!(xml.contains(r#"<class name="Tiny" sourcefilename="tiny.java">"#));
+        assert!(xml.contains(r#"<method name="add" desc="(II)I" line="2">"#));
+        assert!(xml.contains(r#"<line nr="4" mi="2" ci="0" mb="0" cb="0"/>"#));
+        assert!(xml.contains(r#"<counter type="METHOD" missed="2" covered="3"/>"#));
+        assert!(xml.ends_with("</report>\n"));
+    }
+}
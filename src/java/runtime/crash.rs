@@ -0,0 +1,93 @@
+use java::runtime::{Runtime, RuntimeError};
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// a snapshot of one java frame, taken while an error unwinds through it.
+#[derive(Debug, Clone)]
+pub struct FrameDump {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    pub pc: usize,
+    pub line: Option<usize>,
+    pub locals: Vec<String>,
+    pub stack: Vec<String>,
+}
+
+impl RuntimeError {
+    /// internal errors point to a bug in the vm or broken bytecode.
+    /// cancellation, denied permissions, failing host calls and arguments which do not match the
+    /// invoked method are regular outcomes.
+    pub fn is_internal(&self) -> bool {
+        !matches!(self.root(),
+            RuntimeError::Cancelled | RuntimeError::Exit { .. } | RuntimeError::PermissionDenied { .. } | RuntimeError::Host { .. } | RuntimeError::Exception { .. }
+            | RuntimeError::ArgumentCount { .. } | RuntimeError::ArgumentType { .. })
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// renders a hs_err style report for `error`: the failing instruction, all java frames the
+    /// error unwound through, the loaded classes and the configuration of this runtime.
+    pub fn crash_report(&self, error: &RuntimeError) -> String {
+        let mut report = String::new();
+        writeln!(report, "#").unwrap();
+        writeln!(report, "# An internal error has been detected by the rjvm runtime:").unwrap();
+        writeln!(report, "#").unwrap();
+        writeln!(report, "#  {}", error.root()).unwrap();
+        if let Some(location) = error.location() {
+            writeln!(report, "#").unwrap();
+            writeln!(report, "# Instruction: {} (0x{:02x}) at pc {}", location.instruction, location.opcode, location.pc).unwrap();
+            writeln!(report, "# Method: {}.{}{}", location.class_name, location.method_name, location.descriptor).unwrap();
+        }
+        writeln!(report, "#").unwrap();
+        writeln!(report).unwrap();
+
+        writeln!(report, "---------------  T H R E A D  ---------------").unwrap();
+        writeln!(report).unwrap();
+        writeln!(report, "Java frames: (innermost first)").unwrap();
+        for frame in self.thread.unwound_frames.iter() {
+            let line = frame.line.map(|line| format!(" line {}", line)).unwrap_or_default();
+            writeln!(report, "j  {}.{}{} pc {}{}", frame.class_name, frame.method_name, frame.descriptor, frame.pc, line).unwrap();
+            for (index, local) in frame.locals.iter().enumerate() {
+                writeln!(report, "     local[{}] = {}", index, local).unwrap();
+            }
+            for (index, value) in frame.stack.iter().enumerate() {
+                writeln!(report, "     stack[{}] = {}", index, value).unwrap();
+            }
+        }
+        writeln!(report).unwrap();
+
+        writeln!(report, "---------------  C L A S S E S  ---------------").unwrap();
+        writeln!(report).unwrap();
+        for class in self.classes.classes() {
+            writeln!(report, "{} (version {}.{}, {} methods)", class.get_class_name(), class.version.0, class.version.1, class.methods.len()).unwrap();
+        }
+        writeln!(report).unwrap();
+
+        writeln!(report, "---------------  S Y S T E M  ---------------").unwrap();
+        writeln!(report).unwrap();
+        writeln!(report, "main class: {}", self.main_class).unwrap();
+        writeln!(report, "classpath: {:?}", self.classpath).unwrap();
+        writeln!(report, "execution mode: {:?}", self.environment.mode()).unwrap();
+        writeln!(report, "capabilities: {:?}", self.capabilities).unwrap();
+
+        report
+    }
+
+    /// writes the crash report for `error` to the configured crash dump file.
+    /// returns the path of the written file, or `None` if dumping is disabled or the error is not internal.
+    pub fn write_crash_dump(&self, error: &RuntimeError) -> io::Result<Option<PathBuf>> {
+        let path = match self.crash_dump_path {
+            Some(ref path) if error.is_internal() => path.clone(),
+            _ => return Ok(None)
+        };
+
+        let mut file = File::create(&path)?;
+        file.write_all(self.crash_report(error).as_bytes())?;
+
+        Ok(Some(path))
+    }
+}
@@ -0,0 +1,316 @@
+use java::class_file::{ClassFile, Method};
+use java::instructions::{Instruction, ReadInstructionError};
+use java::runtime::{fast, ClassId, FastMethod, FrameAllocations, HandlerTable, InlineSite, RuntimeError};
+use std::sync::{Mutex, OnceLock};
+
+/// the method a virtual call site dispatched to for the last receiver class.
+#[derive(Debug, Clone)]
+struct CachedTarget {
+    receiver_class: String,
+    target: Option<(ClassId, usize)>,
+    generation: u64,
+}
+
+/// the method class hierarchy analysis bound a virtual call site to.
+#[derive(Debug, Clone)]
+struct BoundTarget {
+    /// `None` if more than one method can be called.
+    target: Option<(ClassId, usize)>,
+    hierarchy_generation: u64,
+}
+
+/// a monomorphic inline cache of an `invokevirtual` or `invokeinterface` site.
+///
+/// entries from an older generation of the `ClassRegistry` are ignored, since loading classes
+/// or registering natives can change where a call goes. sites which can only call a single method
+/// are bound to it until the class hierarchy changes and skip the receiver class.
+#[derive(Debug, Default)]
+pub struct InlineCache {
+    entry: Mutex<Option<CachedTarget>>,
+    bound: Mutex<Option<BoundTarget>>,
+}
+
+impl InlineCache {
+    /// the cached target for `receiver_class`. the outer `None` is a miss,
+    /// the inner one means the call has to go through the full lookup.
+    pub fn lookup(&self, receiver_class: &str, generation: u64) -> Option<Option<(ClassId, usize)>> {
+        match *self.entry.lock().unwrap() {
+            Some(ref entry) if entry.generation == generation && entry.receiver_class == receiver_class => Some(entry.target),
+            _ => None
+        }
+    }
+
+    pub fn update(&self, receiver_class: &str, target: Option<(ClassId, usize)>, generation: u64) {
+        *self.entry.lock().unwrap() = Some(CachedTarget { receiver_class: String::from(receiver_class), target, generation });
+    }
+
+    /// the method the site is bound to. the outer `None` means the site was not analyzed for this
+    /// generation of the hierarchy, the inner one that it is polymorphic.
+    pub fn bound(&self, hierarchy_generation: u64) -> Option<Option<(ClassId, usize)>> {
+        match *self.bound.lock().unwrap() {
+            Some(ref bound) if bound.hierarchy_generation == hierarchy_generation => Some(bound.target),
+            _ => None
+        }
+    }
+
+    pub fn bind(&self, target: Option<(ClassId, usize)>, hierarchy_generation: u64) {
+        *self.bound.lock().unwrap() = Some(BoundTarget { target, hierarchy_generation });
+    }
+
+    /// the receiver class of the cached entry.
+    pub fn receiver_class(&self) -> Option<String> {
+        self.entry.lock().unwrap().as_ref().map(|entry| entry.receiver_class.clone())
+    }
+}
+
+/// the condition of a fused integer compare and branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl Comparison {
+    fn of(instruction: &Instruction) -> Option<Comparison> {
+        match instruction {
+            Instruction::IfICmpEQ(_) => Some(Comparison::Eq),
+            Instruction::IfICmpNE(_) => Some(Comparison::Ne),
+            Instruction::IfICmpLT(_) => Some(Comparison::Lt),
+            Instruction::IfICmpGE(_) => Some(Comparison::Ge),
+            Instruction::IfICmpGT(_) => Some(Comparison::Gt),
+            Instruction::IfICmpLE(_) => Some(Comparison::Le),
+            _ => None
+        }
+    }
+
+    pub fn holds(self, lh: i64, rh: i64) -> bool {
+        match self {
+            Comparison::Eq => lh == rh,
+            Comparison::Ne => lh != rh,
+            Comparison::Lt => lh < rh,
+            Comparison::Ge => lh >= rh,
+            Comparison::Gt => lh > rh,
+            Comparison::Le => lh <= rh,
+        }
+    }
+}
+
+/// a common sequence of instructions which the interpreter runs with a single dispatch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Superinstruction {
+    /// `iload a; iload b; iadd`
+    LoadLoadAdd(u16, u16),
+    /// `iload a; iload b; if_icmp<cond>`
+    LoadLoadCompare(u16, u16, Comparison),
+    /// `iinc index delta; goto`
+    IncGoto(u16, i64),
+    /// instructions without effects which always leave the same int on the stack, found by
+    /// constant folding of the SSA form, and how many they are.
+    Constant(i32, usize),
+    /// a conditional branch on operands which are always the same, with the instructions pushing
+    /// them. true if it is always taken.
+    Branch(bool, usize),
+    /// `aload a; iload i; <x>aload` in a counted loop which keeps the index in bounds, without the
+    /// bounds check.
+    UncheckedLoad(u16, u16),
+}
+
+impl Superinstruction {
+    /// the number of instructions it replaces.
+    pub fn len(self) -> usize {
+        match self {
+            Superinstruction::LoadLoadAdd(..) | Superinstruction::LoadLoadCompare(..) | Superinstruction::UncheckedLoad(..) => 3,
+            Superinstruction::IncGoto(..) => 2,
+            Superinstruction::Constant(_, len) | Superinstruction::Branch(_, len) => len,
+        }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// the local variable index of an `iload`.
+fn int_load(instruction: &Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::ILoad(offset) => Some(u16::from(*offset)),
+        Instruction::ILoad0(()) => Some(0),
+        Instruction::ILoad1(()) => Some(1),
+        Instruction::ILoad2(()) => Some(2),
+        Instruction::ILoad3(()) => Some(3),
+        _ => None
+    }
+}
+
+/// the superinstruction starting with `sequence[0]`, if any.
+fn fuse(sequence: &[(usize, Instruction)]) -> Option<Superinstruction> {
+    match sequence {
+        [(_, Instruction::IInc(operands)), (_, Instruction::Goto(_)), ..] =>
+            Some(Superinstruction::IncGoto(operands >> 8, i64::from((operands & 0xff) as u8 as i8))),
+        [(_, first), (_, second), (_, Instruction::IAdd(())), ..] =>
+            Some(Superinstruction::LoadLoadAdd(int_load(first)?, int_load(second)?)),
+        [(_, first), (_, second), (_, third), ..] =>
+            Some(Superinstruction::LoadLoadCompare(int_load(first)?, int_load(second)?, Comparison::of(third)?)),
+        _ => None
+    }
+}
+
+/// an instruction of a `DecodedMethod`.
+#[derive(Debug)]
+pub struct DecodedInsn {
+    pub pc: usize,
+    pub instruction: Instruction,
+    /// the index of the branch target in `DecodedMethod::instructions`, for jumps.
+    pub target: Option<usize>,
+    /// the indices of the targets of a `tableswitch` or `lookupswitch`, the default first.
+    pub switch_targets: Vec<usize>,
+    /// the inline cache of virtual call sites.
+    pub cache: Option<InlineCache>,
+    /// the superinstruction starting here.
+    pub fused: Option<Superinstruction>,
+    /// the callee inlined at call sites.
+    pub inline: Option<InlineSite>,
+}
+
+/// the bytecode of a method, decoded once when its class is linked.
+#[derive(Debug, Default)]
+pub struct DecodedMethod {
+    pub instructions: Vec<DecodedInsn>,
+    /// the verified form for the fast interpreter, if the method qualifies.
+    pub fast: Option<FastMethod>,
+    /// the allocations which can live in the frame, once the method is hot.
+    pub frame_allocations: FrameAllocations,
+    /// the superinstructions with the computations constant folding removed, by instruction,
+    /// once the method is hot.
+    pub optimized: OnceLock<Vec<Option<Superinstruction>>>,
+    /// the exception table, indexed by pc.
+    pub handlers: HandlerTable,
+}
+
+impl DecodedMethod {
+    pub fn decode(class: &ClassFile, method: &Method) -> Result<DecodedMethod, RuntimeError> {
+        let mut decoded = DecodedMethod::decode_unverified(class, method)?;
+        decoded.fast = fast::verify(class, method, &decoded.instructions);
+        Ok(decoded)
+    }
+
+    /// decodes the bytecode without verifying it for the fast interpreter.
+    pub fn decode_unverified(class: &ClassFile, method: &Method) -> Result<DecodedMethod, RuntimeError> {
+        let code = match method.get_code() {
+            Some(code) => code,
+            None => return Ok(DecodedMethod::default())
+        };
+
+        let instructions = code.instructions_with_pc()
+            .map_err(|err| {
+                let reason = match err {
+                    ReadInstructionError::InvalidOpcode { opcode } => format!("invalid opcode 0x{:02x}", opcode),
+                    ReadInstructionError::ParsingIncomplete => String::from("truncated instruction"),
+                    ReadInstructionError::ParsingError(_) => String::from("malformed instruction")
+                };
+                RuntimeError::InvalidBytecode { message: format!("{}{}: {}", method.name, method.descriptor, reason) }
+            })?;
+
+        let index_of = |pc: usize, offset: i64| {
+            let target = pc as i64 + offset;
+            instructions.binary_search_by_key(&target, |&(instruction_pc, _)| instruction_pc as i64)
+                .map_err(|_| RuntimeError::InvalidJumpTarget { pc: target as usize })
+        };
+        let targets = instructions.iter()
+            .map(|(pc, instruction)| match instruction.branch_offset() {
+                Some(offset) => index_of(*pc, offset).map(Some),
+                None => Ok(None)
+            })
+            .collect::<Result<Vec<Option<usize>>, RuntimeError>>()?;
+        let switch_targets = instructions.iter()
+            .map(|(pc, instruction)| instruction.switch_offsets().unwrap_or_default().into_iter()
+                .map(|offset| index_of(*pc, offset))
+                .collect::<Result<Vec<usize>, RuntimeError>>())
+            .collect::<Result<Vec<Vec<usize>>, RuntimeError>>()?;
+
+        // a sequence can only be fused if nothing jumps into its middle
+        let mut entered = vec![false; instructions.len()];
+        for &target in targets.iter().filter_map(|target| target.as_ref()).chain(switch_targets.iter().flatten()) {
+            entered[target] = true;
+        }
+        let handlers = HandlerTable::build(class, method, &instructions)?;
+        for handler in handlers.handlers() {
+            entered[handler.handler] = true;
+        }
+        let fused = (0..instructions.len())
+            .map(|index| fuse(&instructions[index..])
+                .filter(|fused| !entered[index + 1..index + fused.len()].iter().any(|&entered| entered)))
+            .collect::<Vec<_>>();
+
+        let instructions =  instructions.into_iter().zip(targets).zip(switch_targets).zip(fused)
+                .map(|((((pc, instruction), target), switch_targets), fused)| {
+                    let cache = match instruction {
+                        Instruction::InvokeVirtual(_) | Instruction::InvokeInterface(_) => Some(InlineCache::default()),
+                        _ => None
+                    };
+                    let inline = match instruction {
+                        Instruction::InvokeVirtual(_) | Instruction::InvokeInterface(_) | Instruction::InvokeSpecial(_) |
+                        Instruction::InvokeStatic(_) => Some(InlineSite::default()),
+                        _ => None
+                    };
+                    DecodedInsn { pc, instruction, target, switch_targets, cache, fused, inline }
+                })
+                .collect::<Vec<_>>();
+
+        Ok(DecodedMethod { instructions, fast: None, frame_allocations: FrameAllocations::default(), optimized: OnceLock::new(), handlers })
+    }
+
+    /// the index of the instruction at `pc`, used to enter exception handlers.
+    pub fn index_of(&self, pc: usize) -> Result<usize, RuntimeError> {
+        self.instructions.binary_search_by_key(&pc, |insn| insn.pc)
+            .map_err(|_| RuntimeError::InvalidJumpTarget { pc })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, Runtime, StackValue};
+    use super::*;
+
+    #[test]
+    fn branch_targets_are_resolved() {
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let sum = class.methods.iter().find(|method| method.name == "sum").unwrap();
+        let decoded = DecodedMethod::decode(&class, sum).unwrap();
+
+        let branches = decoded.instructions.iter()
+            .filter_map(|insn| insn.target.map(|target| (insn.pc, decoded.instructions[target].pc)))
+            .collect::<Vec<_>>();
+        // if_icmpge forward to the return, goto back to the loop condition
+        assert_eq!(branches, vec![(6, 19), (16, 4)]);
+    }
+
+    #[test]
+    fn common_sequences_are_fused() {
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let sum = class.methods.iter().find(|method| method.name == "sum").unwrap();
+        let decoded = DecodedMethod::decode(&class, sum).unwrap();
+
+        let fused = decoded.instructions.iter()
+            .filter_map(|insn| insn.fused.map(|fused| (insn.pc, fused)))
+            .collect::<Vec<_>>();
+        assert_eq!(fused, vec![
+            (4, Superinstruction::LoadLoadCompare(2, 0, Comparison::Ge)),
+            (9, Superinstruction::LoadLoadAdd(1, 2)),
+            (13, Superinstruction::IncGoto(2, 1)),
+        ]);
+
+        let mut rt = Runtime::create(class);
+        for &(a, b, expected) in [(5, 3, 5), (2, 9, 9)].iter() {
+            match rt.invoke_static("Loop", "max", "(II)I", vec![LocalVariable::Integer(a), LocalVariable::Integer(b)]) {
+                Ok(Some(StackValue::Integer(max))) => assert_eq!(max, expected),
+                other => panic!("unexpected result {:?}", other)
+            }
+        }
+    }
+}
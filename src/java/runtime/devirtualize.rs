@@ -0,0 +1,122 @@
+use java::runtime::{builtin, ClassId, DecodedInsn, ResolvedMethod, Runtime};
+
+/// call sites whose receivers can be more classes than this stay virtual.
+const MAX_RECEIVER_CLASSES: usize = 16;
+
+impl<'a> Runtime<'a> {
+    /// the method an `invokevirtual` or `invokeinterface` site calls whatever its receiver is, found
+    /// by class hierarchy analysis of the loaded classes. the result is kept in the inline cache of
+    /// the site until a class load, a native or a lambda changes the hierarchy.
+    pub(super) fn devirtualized(&self, insn: &DecodedInsn, resolved: &ResolvedMethod) -> Option<(ClassId, usize)> {
+        let cache = insn.cache.as_ref()?;
+        let generation = self.classes.hierarchy_generation();
+        if let Some(bound) = cache.bound(generation) {
+            return bound;
+        }
+
+        let target = self.monomorphic_target(resolved);
+        if let Some((class, slot)) = target {
+            debug!(class = %resolved.class_name, method = %resolved.method_name, descriptor = %resolved.descriptor,
+                target = self.classes.class(class).get_class_name(), slot, "devirtualized call site");
+        }
+        cache.bind(target, generation);
+        target
+    }
+
+    /// the method every class which can be the receiver of a call of `resolved` dispatches to,
+    /// `None` if there are several or the receivers are not all known.
+    fn monomorphic_target(&self, resolved: &ResolvedMethod) -> Option<(ClassId, usize)> {
+        let class_name = resolved.class_name.as_str();
+        // the class library has builtin subclasses the hierarchy does not know
+        if builtin::is_builtin(class_name) || class_name.starts_with("java/") {
+            return None;
+        }
+        let hierarchy = self.classes.hierarchy();
+        let node = hierarchy.get(class_name)?;
+        if node.is_interface && self.lambda_classes.values().any(|lambda| hierarchy.is_subtype(&lambda.interface, class_name)) {
+            return None;
+        }
+
+        let mut receivers = hierarchy.implementations(class_name);
+        if !node.is_interface && !node.is_abstract {
+            receivers.push(class_name);
+        }
+        if receivers.len() > MAX_RECEIVER_CLASSES {
+            return None;
+        }
+
+        let mut targets = receivers.into_iter()
+            .map(|receiver| self.method_target(receiver, &resolved.method_name, &resolved.descriptor));
+        let first = targets.next()??;
+        if targets.all(|target| target == Some(first)) { Some(first) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, RuntimeError, StackValue};
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Devirtualize.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Greeter.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/PlainGreeter.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Counted.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/CountedLeaf.class")).unwrap().1);
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> i64 {
+        match rt.invoke_static("Devirtualize", name, descriptor, arguments) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    /// the method the virtual call site in `method_name` is bound to, by name.
+    fn bound(rt: &Runtime, method_name: &str, descriptor: &str) -> Option<Option<String>> {
+        let decoded = rt.classes.decoded("Devirtualize", method_name, descriptor).unwrap();
+        let cache = decoded.instructions.iter().find_map(|insn| insn.cache.as_ref()).unwrap();
+        cache.bound(rt.classes.hierarchy_generation())
+            .map(|target| target.map(|(class, slot)| format!("{}.{}", rt.classes.class(class).get_class_name(), rt.classes.class(class).methods[slot].name)))
+    }
+
+    #[test]
+    fn monomorphic_sites_are_bound_until_an_override_is_loaded() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "leaf", "()I", vec![]), 2);
+        assert_eq!(bound(&rt, "value", "(LCounted;)I"), Some(Some(String::from("Counted.value"))));
+        assert_eq!(int(&mut rt, "plain", "(I)I", vec![LocalVariable::Integer(4)]), 5);
+        assert_eq!(bound(&rt, "greet", "(LGreeter;I)I"), Some(Some(String::from("PlainGreeter.greet"))));
+
+        // unrelated classes keep the binding
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        assert!(bound(&rt, "value", "(LCounted;)I").is_some());
+
+        rt.load_class(read_class_file(include_bytes!("../../../sample/CountedOverride.class")).unwrap().1);
+        assert_eq!(bound(&rt, "value", "(LCounted;)I"), None);
+        assert_eq!(int(&mut rt, "overridden", "()I", vec![]), 10);
+        assert_eq!(int(&mut rt, "leaf", "()I", vec![]), 2);
+        assert_eq!(bound(&rt, "value", "(LCounted;)I"), Some(None));
+    }
+
+    #[test]
+    fn lambdas_implement_interfaces_too() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "plain", "(I)I", vec![LocalVariable::Integer(1)]), 2);
+        assert_eq!(int(&mut rt, "lambda", "(I)I", vec![LocalVariable::Integer(3)]), 6);
+        assert_eq!(bound(&rt, "greet", "(LGreeter;I)I"), Some(None));
+        assert_eq!(int(&mut rt, "plain", "(I)I", vec![LocalVariable::Integer(1)]), 2);
+    }
+
+    #[test]
+    fn bound_calls_check_the_receiver() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "leaf", "()I", vec![]), 2);
+        match rt.invoke_static("Devirtualize", "nothing", "()I", vec![]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/NullPointerException" => (),
+            other => panic!("expected a NullPointerException, got {:?}", other)
+        }
+    }
+}
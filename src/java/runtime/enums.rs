@@ -0,0 +1,161 @@
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+
+pub(super) const ENUM: &str = "java/lang/Enum";
+
+impl<'a> Runtime<'a> {
+    /// implements the methods of `java.lang.Enum`. like in the class library, each constant keeps
+    /// its name and ordinal in the fields `name` and `ordinal`, which its constructor passes on.
+    /// returns `None` for methods `Enum` does not have.
+    pub(super) fn invoke_enum(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let argument = |index: usize| match arguments.get(index) {
+            Some(LocalVariable::Reference(reference)) => Some(*reference),
+            _ => None
+        };
+
+        if (method_name, descriptor) == ("valueOf", "(Ljava/lang/Class;Ljava/lang/String;)Ljava/lang/Enum;") {
+            return Some(self.enum_value_of(argument(0), argument(1)).map(|constant| Some(StackValue::Reference(constant))));
+        }
+
+        let this = argument(0)?;
+        let field = |rt: &Runtime, constant: ObjectRef, name: &str| rt.heap.get(constant)
+            .and_then(|object| object.fields.get(name).cloned())
+            .unwrap_or(StackValue::Null);
+        let value = match (method_name, descriptor) {
+            ("<init>", "(Ljava/lang/String;I)V") => {
+                let name = argument(1).map(StackValue::Reference).unwrap_or(StackValue::Null);
+                let ordinal = match arguments.get(2) {
+                    Some(LocalVariable::Integer(ordinal)) => *ordinal,
+                    _ => return Some(Err(RuntimeError::StackType { expected: String::from("integer") }))
+                };
+                if let Some(object) = self.heap.get_mut(this) {
+                    object.fields.insert(String::from("name"), name);
+                    object.fields.insert(String::from("ordinal"), StackValue::Integer(ordinal));
+                }
+                return Some(Ok(None));
+            }
+            ("name", "()Ljava/lang/String;") | ("toString", "()Ljava/lang/String;") => field(self, this, "name"),
+            ("ordinal", "()I") => field(self, this, "ordinal"),
+            ("compareTo", "(Ljava/lang/Enum;)I") | ("compareTo", "(Ljava/lang/Object;)I") => match argument(1) {
+                Some(other) => match (field(self, this, "ordinal"), field(self, other, "ordinal")) {
+                    (StackValue::Integer(ordinal), StackValue::Integer(other)) => StackValue::Integer(ordinal - other),
+                    _ => return Some(Err(RuntimeError::StackType { expected: String::from("java/lang/Enum") }))
+                },
+                None => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+            },
+            // constants are singletons, so the identity is all there is to compare
+            ("equals", "(Ljava/lang/Object;)Z") => StackValue::Integer(if argument(1) == Some(this) { 1 } else { 0 }),
+            ("hashCode", "()I") => StackValue::Integer(i64::from(self.identity_hash_code(this))),
+            ("getDeclaringClass", "()Ljava/lang/Class;") => {
+                let declaring = self.declaring_class(this);
+                StackValue::Reference(self.class_object(&declaring))
+            }
+            _ => return None
+        };
+
+        Some(Ok(Some(value)))
+    }
+
+    /// the enum class of a constant. constants with a body are instances of an anonymous subclass.
+    fn declaring_class(&self, constant: ObjectRef) -> String {
+        let class_name = self.heap.get(constant).map(|object| object.class_name.clone()).unwrap_or_default();
+        match self.superclass_of(&class_name) {
+            Some(ref superclass) if superclass != ENUM => superclass.clone(),
+            _ => class_name
+        }
+    }
+
+    /// `Enum.valueOf`, which looks the constant up among the `values()` of the enum class.
+    /// throws an `IllegalArgumentException` if it has no constant of that name.
+    fn enum_value_of(&mut self, class: Option<ObjectRef>, name: Option<ObjectRef>) -> Result<ObjectRef, RuntimeError> {
+        let (caller, slot) = self.caller()?;
+        let method = &caller.methods[slot];
+        let (class_name, name) = match (class.and_then(|class| self.class_object_name(class)), name.and_then(|name| self.heap.string_value(name))) {
+            (Some(class_name), Some(name)) => (class_name, String::from(name)),
+            (_, None) => return Err(self.throw(method, "java/lang/NullPointerException", Some("Name is null"))),
+            (None, _) => return Err(self.throw(method, "java/lang/NullPointerException", None))
+        };
+
+        self.initialize_class(method, &class_name)?;
+        let values = match self.invoke(&class_name, "values", &format!("()[L{};", class_name), Vec::new())? {
+            Some(StackValue::Reference(values)) => values,
+            _ => return Err(RuntimeError::InvalidReturnValue { expected: String::from("enum constants") })
+        };
+        let constants = match self.heap.get(values).map(|object| &object.data) {
+            Some(ObjectData::Array(constants)) => constants.clone(),
+            _ => Vec::new()
+        };
+
+        for constant in constants {
+            if let StackValue::Reference(constant) = constant {
+                let constant_name = match self.heap.get(constant).and_then(|object| object.fields.get("name")) {
+                    Some(StackValue::Reference(constant_name)) => self.heap.string_value(*constant_name),
+                    _ => None
+                };
+                if constant_name == Some(name.as_str()) {
+                    return Ok(constant);
+                }
+            }
+        }
+
+        let message = format!("No enum constant {}.{}", class_name.replace(['/', '$'], "."), name);
+        Err(self.throw(method, "java/lang/IllegalArgumentException", Some(&message)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Enums.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Enums$1.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Planet.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Op.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Op$1.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Op$2.class")).unwrap().1);
+        rt
+    }
+
+    fn call(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> StackValue {
+        match rt.invoke_static("Enums", name, descriptor, arguments) {
+            Ok(Some(value)) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    fn string(rt: &mut Runtime, name: &str, arguments: Vec<LocalVariable>) -> String {
+        let descriptor = if arguments.is_empty() { "()Ljava/lang/String;" } else { "(Ljava/lang/String;)Ljava/lang/String;" };
+        match call(rt, name, descriptor, arguments) {
+            StackValue::Reference(value) => String::from(rt.heap().string_value(value).unwrap()),
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn a_typical_enum_program_runs() {
+        let mut rt = runtime();
+        // radius times ordinal + 1 of the constants created by <clinit>
+        assert_eq!(call(&mut rt, "sumRadii", "()I", vec![]), StackValue::Integer(3 + 6 * 2 + 6 * 3 + 3 * 4));
+        // the $SwitchMap of Enums$1 compiles to a tableswitch, the sparse int cases to a lookupswitch
+        assert_eq!(call(&mut rt, "switches", "()I", vec![]), StackValue::Integer(10_340 + 1 + 2 + 3));
+        assert_eq!(call(&mut rt, "valuesAreCopies", "()Z", vec![]), StackValue::Integer(1));
+        // MARS - VENUS, equals and getDeclaringClass of a constant with a body
+        assert_eq!(call(&mut rt, "compare", "()I", vec![]), StackValue::Integer(2 + 10 + 100));
+        assert_eq!(call(&mut rt, "apply", "()I", vec![]), StackValue::Integer(13 * 100 + 42));
+    }
+
+    #[test]
+    fn value_of_finds_constants_by_name() {
+        let mut rt = runtime();
+        let earth = LocalVariable::Reference(rt.heap.intern("EARTH"));
+        assert_eq!(string(&mut rt, "lookup", vec![earth]), "EARTH:home:EARTH");
+        assert_eq!(string(&mut rt, "missing", vec![]), "No enum constant Planet.PLUTO");
+
+        let pluto = LocalVariable::Reference(rt.heap.intern("PLUTO"));
+        match rt.invoke_static("Enums", "lookup", "(Ljava/lang/String;)Ljava/lang/String;", vec![pluto]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/IllegalArgumentException" => (),
+            other => panic!("lookup returned {:?}", other)
+        }
+    }
+}
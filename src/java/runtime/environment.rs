@@ -0,0 +1,424 @@
+use java::runtime::{Input, InputLog, Journal};
+use std::fs::File;
+use std::io;
+use std::fmt;
+use std::io::Read;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// the epoch the deterministic clock starts at: 2000-01-01T00:00:00Z in milliseconds.
+const DETERMINISTIC_EPOCH_MILLIS: i64 = 946_684_800_000;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExecutionMode {
+    /// time, random seeds and hash codes come from the host.
+    Native,
+    /// every nondeterministic input is derived from `seed`,
+    /// so the same program with the same inputs always produces the same trace.
+    Deterministic { seed: u64 },
+}
+
+/// a wall clock which replaces the one of the host, see `RuntimeBuilder::clock`.
+/// closures returning the time since the unix epoch are clocks.
+pub trait WallClock: Send {
+    /// the time since 1970-01-01T00:00:00Z.
+    fn now(&mut self) -> Duration;
+}
+
+impl<F> WallClock for F where F: FnMut() -> Duration + Send {
+    fn now(&mut self) -> Duration {
+        self()
+    }
+}
+
+impl fmt::Debug for dyn WallClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WallClock")
+    }
+}
+
+/// the single source of all nondeterministic inputs of a `Runtime`.
+///
+/// natives must not read clocks or entropy from the host directly but always go through this,
+/// otherwise deterministic execution (and everything built on top of it) breaks.
+///
+/// the `Journal` records every input handed out, or replays the inputs of an earlier recording.
+#[derive(Debug)]
+pub struct Environment {
+    mode: ExecutionMode,
+    rng_state: u64,
+    /// the generator of random seeds if they are fixed independently of the mode.
+    seed_state: Option<u64>,
+    clock_ticks: i64,
+    /// the clock which replaces the one of the host and of the deterministic mode, if any.
+    clock: Option<Box<dyn WallClock>>,
+    started: Instant,
+    next_thread: usize,
+    journal: Journal,
+}
+
+impl Environment {
+    pub fn new(mode: ExecutionMode) -> Environment {
+        Environment::with_journal(mode, Journal::Off)
+    }
+
+    pub fn with_journal(mode: ExecutionMode, journal: Journal) -> Environment {
+        let seed = match mode {
+            ExecutionMode::Deterministic { seed } => seed,
+            ExecutionMode::Native => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs() ^ u64::from(duration.subsec_nanos()))
+                .unwrap_or(0),
+        };
+
+        Environment {
+            mode,
+            rng_state: seed,
+            seed_state: None,
+            clock_ticks: 0,
+            clock: None,
+            started: Instant::now(),
+            next_thread: 0,
+            journal,
+        }
+    }
+
+    /// the inputs recorded so far, if recording.
+    pub fn recording(&self) -> Option<&InputLog> {
+        match self.journal {
+            Journal::Record(ref log) => Some(log),
+            _ => None
+        }
+    }
+
+    /// the position in the replayed log where the program asked for a different input than it did
+    /// when it was recorded. from there on the inputs come from the host again.
+    pub fn replay_divergence(&self) -> Option<usize> {
+        match self.journal {
+            Journal::Replay { diverged, .. } => diverged,
+            _ => None
+        }
+    }
+
+    /// takes an input from the replayed log, or from `live` and records it.
+    fn input<T: Clone>(&mut self, live: fn(&mut Environment) -> T, wrap: fn(T) -> Input, unwrap: fn(&Input) -> Option<T>) -> T {
+        if let Some(value) = self.journal.replay(unwrap) {
+            return value;
+        }
+
+        let value = live(self);
+        self.journal.record(wrap(value.clone()));
+        value
+    }
+
+    /// reads through `read`, which must be the only access to the host for this input.
+    /// natives use this for file and network reads so they can be replayed.
+    pub fn read<F>(&mut self, read: F) -> io::Result<Vec<u8>>
+        where F: FnOnce() -> io::Result<Vec<u8>> {
+        let result = match self.journal.replay(|input| match input {
+            Input::Read(result) => Some(result.clone()),
+            _ => None
+        }) {
+            Some(result) => result,
+            None => {
+                let result = read().map_err(|err| err.to_string());
+                self.journal.record(Input::Read(result.clone()));
+                result
+            }
+        };
+
+        result.map_err(io::Error::other)
+    }
+
+    /// the value of an environment variable, read through `read` unless replaying.
+    pub fn env_var<F>(&mut self, read: F) -> Option<String>
+        where F: FnOnce() -> Option<String> {
+        match self.journal.replay(|input| match input {
+            Input::EnvVar(value) => Some(value.clone()),
+            _ => None
+        }) {
+            Some(value) => value,
+            None => {
+                let value = read();
+                self.journal.record(Input::EnvVar(value.clone()));
+                value
+            }
+        }
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    /// true unless the inputs come from the host. time, random seeds and hash codes are
+    /// reproducible then, scheduling is a stub until the runtime runs more than one thread.
+    pub fn is_deterministic(&self) -> bool {
+        self.mode != ExecutionMode::Native
+    }
+
+    /// reads the wall clock from `clock` instead of the host, in every mode. `nano_time` is not
+    /// affected, like `System.nanoTime` is not tied to the wall clock.
+    pub fn set_clock(&mut self, clock: Box<dyn WallClock>) {
+        self.clock = Some(clock);
+    }
+
+    /// backs `System.currentTimeMillis`.
+    /// the deterministic clock advances by one millisecond on every call.
+    pub fn current_time_millis(&mut self) -> i64 {
+        self.input(Environment::live_current_time_millis, Input::CurrentTimeMillis, |input| match input {
+            Input::CurrentTimeMillis(value) => Some(*value),
+            _ => None
+        })
+    }
+
+    fn live_current_time_millis(&mut self) -> i64 {
+        self.live_wall_clock().as_millis() as i64
+    }
+
+    /// the wall clock in nanoseconds since the epoch, which backs `Instant.now`.
+    /// the deterministic clock advances by one millisecond on every call, like `current_time_millis`.
+    pub fn current_time_nanos(&mut self) -> i64 {
+        self.input(Environment::live_current_time_nanos, Input::CurrentTimeNanos, |input| match input {
+            Input::CurrentTimeNanos(value) => Some(*value),
+            _ => None
+        })
+    }
+
+    fn live_current_time_nanos(&mut self) -> i64 {
+        self.live_wall_clock().as_nanos() as i64
+    }
+
+    fn live_wall_clock(&mut self) -> Duration {
+        if let Some(ref mut clock) = self.clock {
+            return clock.now();
+        }
+        match self.mode {
+            ExecutionMode::Native => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+            ExecutionMode::Deterministic { .. } => {
+                self.clock_ticks += 1;
+                Duration::from_millis((DETERMINISTIC_EPOCH_MILLIS + self.clock_ticks) as u64)
+            }
+        }
+    }
+
+    /// backs `System.nanoTime`. only the difference between two values is meaningful.
+    pub fn nano_time(&mut self) -> i64 {
+        self.input(Environment::live_nano_time, Input::NanoTime, |input| match input {
+            Input::NanoTime(value) => Some(*value),
+            _ => None
+        })
+    }
+
+    fn live_nano_time(&mut self) -> i64 {
+        match self.mode {
+            ExecutionMode::Native => {
+                let elapsed = self.started.elapsed();
+                elapsed.as_secs() as i64 * 1_000_000_000 + i64::from(elapsed.subsec_nanos())
+            }
+            ExecutionMode::Deterministic { .. } => {
+                self.clock_ticks += 1;
+                self.clock_ticks * 1_000_000
+            }
+        }
+    }
+
+    /// derives the seeds of `random_seed` from `seed`, in every mode. programs using
+    /// `java.util.Random` become reproducible while time and hash codes still come from the host.
+    pub fn fix_random_seeds(&mut self, seed: u64) {
+        self.seed_state = Some(seed);
+    }
+
+    /// a fresh seed for `java.util.Random` instances created without an explicit seed.
+    pub fn random_seed(&mut self) -> i64 {
+        self.input(Environment::live_random_seed, Input::Random, |input| match input {
+            Input::Random(value) => Some(*value),
+            _ => None
+        }) as i64
+    }
+
+    fn live_random_seed(&mut self) -> u64 {
+        match self.seed_state {
+            Some(ref mut state) => splitmix64(state),
+            None => self.next_random()
+        }
+    }
+
+    /// `count` bytes for `java.security.SecureRandom`. the native mode reads them from the entropy
+    /// of the host, fixed seeds and the deterministic mode derive them from the seed.
+    pub fn secure_random_bytes(&mut self, count: usize) -> Vec<u8> {
+        if let Some(bytes) = self.journal.replay(|input| match input {
+            Input::Entropy(bytes) => Some(bytes.clone()),
+            _ => None
+        }) {
+            return bytes;
+        }
+
+        let bytes = self.live_secure_random_bytes(count);
+        self.journal.record(Input::Entropy(bytes.clone()));
+        bytes
+    }
+
+    fn live_secure_random_bytes(&mut self, count: usize) -> Vec<u8> {
+        if self.mode == ExecutionMode::Native && self.seed_state.is_none() {
+            let mut bytes = vec![0; count];
+            if File::open("/dev/urandom").and_then(|mut urandom| urandom.read_exact(&mut bytes)).is_ok() {
+                return bytes;
+            }
+            warn!("cannot read /dev/urandom, secure random bytes come from the seed generator");
+        }
+
+        let mut bytes = Vec::with_capacity(count + 8);
+        while bytes.len() < count {
+            let value = self.live_random_seed();
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.truncate(count);
+        bytes
+    }
+
+    /// the identity hash code of a newly hashed object. never negative, like on HotSpot.
+    pub fn identity_hash(&mut self) -> i32 {
+        (self.random() >> 33) as i32
+    }
+
+    fn random(&mut self) -> u64 {
+        self.input(Environment::next_random, Input::Random, |input| match input {
+            Input::Random(value) => Some(*value),
+            _ => None
+        })
+    }
+
+    /// picks which of the `runnable` threads runs next.
+    /// the native mode schedules round robin, the deterministic mode uses the seeded generator.
+    pub fn schedule(&mut self, runnable: usize) -> usize {
+        if runnable == 0 {
+            return 0;
+        }
+
+        if let Some(picked) = self.journal.replay(|input| match input {
+            Input::Schedule(value) => Some(*value),
+            _ => None
+        }) {
+            return picked;
+        }
+
+        let picked = self.live_schedule(runnable);
+        self.journal.record(Input::Schedule(picked));
+        picked
+    }
+
+    fn live_schedule(&mut self, runnable: usize) -> usize {
+        match self.mode {
+            ExecutionMode::Native => {
+                self.next_thread = (self.next_thread + 1) % runnable;
+                self.next_thread
+            }
+            ExecutionMode::Deterministic { .. } => (self.next_random() % runnable as u64) as usize,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        splitmix64(&mut self.rng_state)
+    }
+}
+
+/// splitmix64, small and good enough for seeds and hash codes.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample(env: &mut Environment) -> (i64, i64, i64, i32, usize) {
+        (env.current_time_millis(), env.nano_time(), env.random_seed(), env.identity_hash(), env.schedule(3))
+    }
+
+    #[test]
+    fn it_is_reproducible_with_the_same_seed() {
+        let mut first = Environment::new(ExecutionMode::Deterministic { seed: 42 });
+        let mut second = Environment::new(ExecutionMode::Deterministic { seed: 42 });
+
+        for _ in 0..10 {
+            assert_eq!(sample(&mut first), sample(&mut second));
+        }
+    }
+
+    #[test]
+    fn it_depends_on_the_seed() {
+        let mut first = Environment::new(ExecutionMode::Deterministic { seed: 1 });
+        let mut second = Environment::new(ExecutionMode::Deterministic { seed: 2 });
+
+        assert_ne!(first.random_seed(), second.random_seed());
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let mut recording = Environment::with_journal(ExecutionMode::Native, Journal::Record(InputLog::new()));
+        let recorded = (0..5).map(|_| sample(&mut recording)).collect::<Vec<_>>();
+        let read = recording.read(|| Ok(vec![1, 2, 3])).unwrap();
+        let var = recording.env_var(|| Some(String::from("value")));
+        let entropy = recording.secure_random_bytes(16);
+
+        let log = recording.recording().unwrap().clone();
+        let mut replay = Environment::with_journal(ExecutionMode::Native, Journal::Replay { log, position: 0, diverged: None });
+        assert_eq!((0..5).map(|_| sample(&mut replay)).collect::<Vec<_>>(), recorded);
+        assert_eq!(replay.read(|| panic!("read the host while replaying")).unwrap(), read);
+        assert_eq!(replay.env_var(|| panic!("read the host while replaying")), var);
+        assert_eq!(replay.secure_random_bytes(16), entropy);
+        assert_eq!(replay.replay_divergence(), None);
+    }
+
+    #[test]
+    fn replays_detect_divergence() {
+        let log = InputLog { inputs: vec![Input::NanoTime(5), Input::NanoTime(7)] };
+        let mut env = Environment::with_journal(ExecutionMode::Deterministic { seed: 1 }, Journal::Replay { log, position: 0, diverged: None });
+
+        assert_eq!(env.nano_time(), 5);
+        env.current_time_millis();
+        assert_eq!(env.replay_divergence(), Some(1));
+        assert_ne!(env.nano_time(), 7);
+    }
+
+    #[test]
+    fn fixed_random_seeds_do_not_depend_on_the_mode() {
+        let mut native = Environment::new(ExecutionMode::Native);
+        let mut deterministic = Environment::new(ExecutionMode::Deterministic { seed: 3 });
+        native.fix_random_seeds(9);
+        deterministic.fix_random_seeds(9);
+
+        for _ in 0..3 {
+            assert_eq!(native.random_seed(), deterministic.random_seed());
+            assert_eq!(native.secure_random_bytes(5), deterministic.secure_random_bytes(5));
+        }
+        // hash codes keep using the generator of the mode
+        assert_eq!(deterministic.identity_hash(), Environment::new(ExecutionMode::Deterministic { seed: 3 }).identity_hash());
+    }
+
+    #[test]
+    fn injected_clocks_replace_the_wall_clock() {
+        let mut env = Environment::new(ExecutionMode::Deterministic { seed: 1 });
+        let mut now = Duration::new(1_700_000_000, 5);
+        env.set_clock(Box::new(move || {
+            now += Duration::from_secs(1);
+            now
+        }));
+
+        assert_eq!(env.current_time_millis(), 1_700_000_001_000);
+        assert_eq!(env.current_time_nanos(), 1_700_000_002_000_000_005);
+        assert_eq!(env.nano_time(), 1_000_000);
+    }
+
+    #[test]
+    fn identity_hashes_are_not_negative() {
+        let mut env = Environment::new(ExecutionMode::Deterministic { seed: 7 });
+        for _ in 0..100 {
+            assert!(env.identity_hash() >= 0);
+        }
+    }
+}
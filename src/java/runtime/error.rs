@@ -0,0 +1,173 @@
+use java::runtime::ObjectRef;
+use std::fmt;
+
+/// where in the bytecode a `RuntimeError` happened.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Location {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    pub pc: usize,
+    pub opcode: u8,
+    pub instruction: &'static str,
+    pub source_file: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}{} at pc {}: {} (0x{:02x})", self.class_name, self.method_name, self.descriptor, self.pc, self.instruction, self.opcode)
+    }
+}
+
+/// these type of errors should not happen at all.
+/// Triggering one of these means the jvm is probably buggy since the compiler should prevent these.
+/// This is for stuff like "we tried to pop the stack but it was empty" or "i need to load an int,
+/// but theres a string on the stack"….
+///
+/// we might trigger something like this when a class file does not contain the expected methods.
+/// this is something the compiler cannot prevent since the user could just swap out the class file.
+///
+/// errors raised while executing an instruction are wrapped into `AtLocation` by the frame
+/// they occurred in, use `root` to get to the actual error.
+#[derive(Debug, Fail)]
+pub enum RuntimeError {
+    #[fail(display = "top of stack had the wrong type. expected: {}", expected)]
+    StackType { expected: String },
+    #[fail(display = "stack popped when empty")]
+    EmptyStack,
+    #[fail(display = "local variable {} is not defined", index)]
+    UndefinedLocal { index: usize },
+    #[fail(display = "local variable {} is out of range", index)]
+    LocalOutOfRange { index: usize },
+    #[fail(display = "local variable {} had the wrong type. expected: {}", index, expected)]
+    LocalType { index: usize, expected: String },
+    #[fail(display = "class not found: {}", class_name)]
+    ClassNotFound { class_name: String },
+    #[fail(display = "method not found: {}.{}{}", class_name, method_name, descriptor)]
+    MethodNotFound { class_name: String, method_name: String, descriptor: String },
+    #[fail(display = "incompatible class change: {}", message)]
+    IncompatibleClassChange { message: String },
+    #[fail(display = "illegal access: {}", message)]
+    IllegalAccess { message: String },
+    #[fail(display = "unsupported class version: {}", message)]
+    UnsupportedClassVersion { message: String },
+    #[fail(display = "malformed class file: {}", message)]
+    ClassFormat { message: String },
+    #[fail(display = "cannot redefine {}: {}", class_name, message)]
+    InvalidRedefinition { class_name: String, message: String },
+    #[fail(display = "constant #{} is not a {}", index, expected)]
+    InvalidConstant { index: u16, expected: String },
+    #[fail(display = "instruction {} is not supported yet", instruction)]
+    UnsupportedInstruction { instruction: &'static str },
+    #[fail(display = "bootstrap method {}.{} is not supported", class_name, method_name)]
+    UnsupportedBootstrapMethod { class_name: String, method_name: String },
+    #[fail(display = "invalid bytecode in {}", message)]
+    InvalidBytecode { message: String },
+    #[fail(display = "no instruction at jump target pc {}", pc)]
+    InvalidJumpTarget { pc: usize },
+    #[fail(display = "{} takes {} arguments, {} were passed", method, expected, found)]
+    ArgumentCount { method: String, expected: usize, found: usize },
+    /// `parameter` is `this` for the receiver, `parameter <index>` otherwise.
+    #[fail(display = "{} of {} had the wrong type. expected: {}, found: {}", parameter, method, expected, found)]
+    ArgumentType { method: String, parameter: String, expected: String, found: String },
+    #[fail(display = "invalid return value. expected: {}", expected)]
+    InvalidReturnValue { expected: String },
+    #[fail(display = "execution was cancelled")]
+    Cancelled,
+    /// the runtime itself panicked while running `method`, its state may be inconsistent.
+    #[fail(display = "the vm panicked in {}", method)]
+    Panicked { method: String },
+    /// `System.exit` ends the program, without running the handlers of the frames it unwinds.
+    #[fail(display = "the program exited with status {}", status)]
+    Exit { status: i32 },
+    #[fail(display = "permission denied: {}", action)]
+    PermissionDenied { action: String },
+    #[fail(display = "cannot {}: {}", action, message)]
+    Host { action: String, message: String },
+    #[fail(display = "uncaught exception {}", class_name)]
+    Exception { exception: ObjectRef, class_name: String },
+    #[fail(display = "{} (in {})", error, location)]
+    /// boxed, so results with a `RuntimeError` stay small.
+    AtLocation { location: Box<Location>, error: Box<RuntimeError> },
+}
+
+impl RuntimeError {
+    /// attaches the location of the failing instruction, unless an inner frame already did.
+    pub fn at(self, location: Location) -> RuntimeError {
+        match self {
+            RuntimeError::AtLocation { .. } => self,
+            error => RuntimeError::AtLocation { location: Box::new(location), error: Box::new(error) }
+        }
+    }
+
+    /// the error without location information.
+    pub fn root(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::AtLocation { error, .. } => error.root(),
+            error => error
+        }
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            RuntimeError::AtLocation { location, .. } => Some(location),
+            _ => None
+        }
+    }
+
+    /// renders the error like javac renders compile errors:
+    ///
+    /// ```text
+    /// Tiny.java:2: error: stack popped when empty
+    ///         in Tiny.add(II)I at pc 2: IAdd (0x60)
+    /// ```
+    pub fn render(&self) -> String {
+        match self.location() {
+            Some(location) => {
+                let file = location.source_file.clone().unwrap_or_else(|| format!("{}.class", location.class_name));
+                let line = location.line.map(|line| format!("{}:", line)).unwrap_or_default();
+                format!("{}:{} error: {}\n        in {}\n", file, line, self.root(), location)
+            }
+            None => format!("error: {}\n", self)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn location() -> Location {
+        Location {
+            class_name: String::from("Tiny"),
+            method_name: String::from("add"),
+            descriptor: String::from("(II)I"),
+            pc: 2,
+            opcode: 0x60,
+            instruction: "IAdd",
+            source_file: Some(String::from("tiny.java")),
+            line: Some(2),
+        }
+    }
+
+    #[test]
+    fn it_renders_like_javac() {
+        let error = RuntimeError::EmptyStack.at(location());
+        assert_eq!("tiny.java:2: error: stack popped when empty\n        in Tiny.add(II)I at pc 2: IAdd (0x60)\n", error.render());
+    }
+
+    #[test]
+    fn it_keeps_the_innermost_location() {
+        let mut outer = location();
+        outer.method_name = String::from("main");
+
+        let error = RuntimeError::EmptyStack.at(location()).at(outer);
+        assert_eq!("add", error.location().unwrap().method_name);
+        match error.root() {
+            RuntimeError::EmptyStack => (),
+            other => panic!("unexpected root {:?}", other)
+        }
+    }
+}
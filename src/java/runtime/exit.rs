@@ -0,0 +1,122 @@
+use java::runtime::{Runtime, RuntimeError, UncaughtException};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// the status a program ended with: the one of `System.exit`, 0 when `main` returned and 1 when
+/// it threw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub(super) i32);
+
+impl ExitStatus {
+    pub const SUCCESS: ExitStatus = ExitStatus(0);
+
+    pub fn code(self) -> i32 {
+        self.0
+    }
+
+    pub fn success(self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// why `Runtime::run` could not run a program to its end.
+#[derive(Debug, Fail)]
+pub enum JvmError {
+    /// `main` threw and no uncaught exception handler took the exception.
+    #[fail(display = "{}", _0)]
+    UncaughtException(UncaughtException),
+    /// the main class is not loaded.
+    #[fail(display = "could not find or load main class {}", class_name)]
+    NoMainClass { class_name: String },
+    /// the main class has no `static void main(String[])`.
+    #[fail(display = "main method not found in class {}", class_name)]
+    NoMainMethod { class_name: String },
+    #[fail(display = "execution was cancelled")]
+    Cancelled,
+    /// the runtime failed, see `RuntimeError`. `crash_dump` is where the report was written.
+    #[fail(display = "{}", error)]
+    Internal { error: RuntimeError, crash_dump: Option<PathBuf> },
+}
+
+impl JvmError {
+    /// the exit code of the java launcher: 130 for a program stopped by SIGINT, 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            JvmError::Cancelled => 128 + 2,
+            _ => 1
+        }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// `run`, printing why the program ended early to the stderr of the runtime like the java
+    /// launcher does. returns the status the process should exit with.
+    pub fn run_and_print(&mut self) -> ExitStatus {
+        let err = match self.run() {
+            Ok(status) => return status,
+            Err(err) => err
+        };
+
+        let message = match err {
+            JvmError::UncaughtException(ref uncaught) => uncaught.to_string(),
+            JvmError::NoMainClass { ref class_name } => format!("Error: Could not find or load main class {}\n", class_name.replace('/', ".")),
+            JvmError::NoMainMethod { ref class_name } => format!("Error: Main method not found in class {}\n", class_name.replace('/', ".")),
+            JvmError::Cancelled => String::new(),
+            JvmError::Internal { ref error, ref crash_dump } => match crash_dump {
+                Some(path) => format!("{}# An error report file with more information is saved as:\n# {}\n", error.render(), path.display()),
+                None => error.render()
+            }
+        };
+        // like the launcher, which exits anyway if it cannot report why
+        let _ = self.stderr.write_all(message.as_bytes());
+        let _ = self.stderr.flush();
+        ExitStatus(err.exit_code())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use captured::Captured;
+    use java::runtime::Output;
+    use std::io;
+    use super::*;
+
+    fn run(bytes: &'static [u8]) -> Result<ExitStatus, JvmError> {
+        Runtime::builder().stdout(Output::new(Box::new(io::sink()))).build(read_class_file(bytes).unwrap().1).run()
+    }
+
+    #[test]
+    fn exit_skips_the_handlers_and_sets_the_status() {
+        let out = Captured::default();
+        let mut rt = Runtime::builder().stdout(out.output())
+            .build(read_class_file(include_bytes!("../../../sample/Exit.class")).unwrap().1);
+        assert_eq!(rt.run().unwrap(), ExitStatus(3));
+        assert_eq!(out.text(), "exiting\n");
+    }
+
+    #[test]
+    fn programs_end_with_the_status_of_main() {
+        assert_eq!(run(include_bytes!("../../../sample/HelloWorld.class")).unwrap(), ExitStatus::SUCCESS);
+        match run(include_bytes!("../../../sample/ExceptionExample.class")) {
+            Err(err @ JvmError::UncaughtException(_)) => assert_eq!(err.exit_code(), 1),
+            other => panic!("expected an uncaught exception, got {:?}", other)
+        }
+        // the handler takes the exception, but main still failed
+        assert_eq!(run(include_bytes!("../../../sample/Uncaught.class")).unwrap(), ExitStatus(1));
+    }
+
+    #[test]
+    fn main_has_to_be_static_and_take_the_arguments() {
+        match run(include_bytes!("../../../sample/NotMain.class")) {
+            Err(JvmError::NoMainMethod { ref class_name }) => assert_eq!(class_name, "NotMain"),
+            other => panic!("expected no main method, got {:?}", other)
+        }
+
+        let err = Captured::default();
+        let mut rt = Runtime::builder().stderr(err.output())
+            .build(read_class_file(include_bytes!("../../../sample/NotMain.class")).unwrap().1);
+        assert_eq!(rt.run_and_print(), ExitStatus(1));
+        assert_eq!(err.text(), "Error: Main method not found in class NotMain\n");
+    }
+}
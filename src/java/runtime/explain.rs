@@ -0,0 +1,320 @@
+use java::class_file::{ClassFile, ConstantType, Method, MethodDescriptor};
+use java::instructions::Instruction;
+use java::runtime::{ElementType, LocalVariable, ObjectData, Runtime, StackFrame, StackValue};
+use std::io::Write;
+use std::str::FromStr;
+
+/// the number of arguments of the method the constant `index` refers to.
+fn argument_count(class: &ClassFile, index: u16) -> usize {
+    let name_and_type_index = match class.get_constant(index) {
+        Some(ConstantType::MethodRef { name_and_type_index, .. }) | Some(ConstantType::InterfaceMethodRef { name_and_type_index, .. }) |
+        Some(ConstantType::InvokeDynamic { name_and_type_index, .. }) => *name_and_type_index,
+        _ => return 0
+    };
+    class.get_name_and_type(name_and_type_index)
+        .and_then(|(_, descriptor)| MethodDescriptor::from_str(descriptor).ok())
+        .map_or(0, |descriptor| descriptor.arguments.len())
+}
+
+/// the pc a branch at `pc` goes to.
+fn target(pc: usize, offset: i64) -> i64 {
+    pc as i64 + offset
+}
+
+/// what the comparison of an `if` instruction checks, from the end of its name.
+fn condition(instruction: &Instruction) -> &'static str {
+    let name = instruction.name().to_lowercase();
+    match &name[name.len() - 2..] {
+        "eq" => "equal to",
+        "ne" => "not equal to",
+        "lt" => "less than",
+        "ge" => "greater than or equal to",
+        "gt" => "greater than",
+        _ => "less than or equal to"
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// writes a line on what `instruction` at `pc` is about to do to the `explain` output, with the
+    /// values it finds in `frame` and the constants it refers to:
+    ///
+    /// ```text
+    /// Loop.sum    4  iload_1: push local variable 1 (int 3) onto the operand stack
+    /// ```
+    pub(super) fn explain_instruction(&mut self, class: &ClassFile, method: &Method, pc: usize, instruction: &Instruction, frame: &StackFrame) {
+        let indent = "  ".repeat(self.thread.frames.len().saturating_sub(1));
+        let line = format!("{}{}.{} {:>4}  {}: {}\n", indent, class.get_class_name(), method.name, pc, instruction.mnemonic(), self.explanation(class, pc, instruction, frame));
+        if let Some(ref mut out) = self.explain {
+            // like `-verbose` output, write errors do not stop the program
+            let _ = out.write_all(line.as_bytes());
+        }
+    }
+
+    /// a value as a student reads it: `int 42`, `null` or a reference like `"text"` or `Point@3`.
+    fn describe_value(&self, value: &StackValue) -> String {
+        match value {
+            StackValue::None => String::from("nothing"),
+            StackValue::Null => String::from("null"),
+            StackValue::Integer(value) => format!("int {}", value),
+            StackValue::Long(value) => format!("long {}", value),
+            StackValue::Reference(reference) => match self.heap.get(*reference) {
+                Some(object) => match object.data {
+                    ObjectData::String(ref value) => format!("{:?}", value),
+                    ObjectData::Array(ref elements) => format!("{}@{} of length {}", object.class_name, reference.0, elements.len()),
+                    _ => format!("{}@{}", object.class_name, reference.0)
+                },
+                None => format!("@{}", reference.0)
+            }
+        }
+    }
+
+    fn describe_local(&self, frame: &StackFrame, index: usize) -> String {
+        match frame.local_variables.get(index) {
+            Some(LocalVariable::None) | None => String::from("not set yet"),
+            Some(local) => self.describe_value(&StackValue::from(local.clone()))
+        }
+    }
+
+    /// the value `depth` slots below the top of the operand stack.
+    fn describe_operand(&self, frame: &StackFrame, depth: usize) -> String {
+        match frame.stack.len().checked_sub(depth + 1).and_then(|index| frame.stack.get(index)) {
+            Some(value) => self.describe_value(value),
+            None => String::from("nothing")
+        }
+    }
+
+    /// the top `count` values of the operand stack in the order they were pushed.
+    fn describe_operands(&self, frame: &StackFrame, count: usize) -> String {
+        (0..count).rev().map(|depth| self.describe_operand(frame, depth)).collect::<Vec<_>>().join(", ")
+    }
+
+    /// the arguments of a call, the top `count` values of the operand stack.
+    fn describe_arguments(&self, frame: &StackFrame, count: usize) -> String {
+        match count {
+            0 => String::from("no arguments"),
+            _ => format!("the arguments ({})", self.describe_operands(frame, count))
+        }
+    }
+
+    fn explanation(&self, class: &ClassFile, pc: usize, instruction: &Instruction, frame: &StackFrame) -> String {
+        let top = || self.describe_operand(frame, 0);
+        let second = || self.describe_operand(frame, 1);
+        let load = |index: usize| format!("push local variable {} ({}) onto the operand stack", index, self.describe_local(frame, index));
+        let store = |index: usize| format!("pop {} and store it in local variable {}", top(), index);
+        let constant = |value: &str| format!("push the constant {} onto the operand stack", value);
+        let binary = |result: &str| format!("pop {} and {} and push {}", second(), top(), result);
+        let convert = |to: &str| format!("pop {} and push it converted to {}", top(), to);
+
+        match instruction {
+            Instruction::NOOP(()) => String::from("do nothing"),
+            Instruction::AConstNull(()) => String::from("push null onto the operand stack"),
+            Instruction::IConstm1(()) => constant("int -1"),
+            Instruction::IConst0(()) | Instruction::IConst1(()) | Instruction::IConst2(()) | Instruction::IConst3(()) |
+            Instruction::IConst4(()) | Instruction::IConst5(()) => constant(&format!("int {}", i32::from(instruction.opcode()) - 3)),
+            Instruction::LConst0(()) | Instruction::LConst1(()) => constant(&format!("long {}", instruction.opcode() - 0x09)),
+            Instruction::FConst0(()) | Instruction::FConst1(()) | Instruction::FConst2(()) => constant(&format!("float {}.0", instruction.opcode() - 0x0b)),
+            Instruction::DConst0(()) | Instruction::DConst1(()) => constant(&format!("double {}.0", instruction.opcode() - 0x0e)),
+            Instruction::BIPush(value) => constant(&format!("int {}", *value as i8)),
+            Instruction::SIPush(value) => constant(&format!("int {}", *value as i16)),
+            Instruction::LDC(index) => format!("push {} from constant #{} onto the operand stack", class.describe_constant(u16::from(*index)), index),
+            Instruction::LDCW(index) | Instruction::LDC2W(index) =>
+                format!("push {} from constant #{} onto the operand stack", class.describe_constant(*index), index),
+
+            Instruction::ILoad(index) | Instruction::LLoad(index) | Instruction::FLoad(index) | Instruction::DLoad(index) |
+            Instruction::ALoad(index) => load(usize::from(*index)),
+            Instruction::ILoad0(()) | Instruction::ILoad1(()) | Instruction::ILoad2(()) | Instruction::ILoad3(()) => load(usize::from(instruction.opcode() - 0x1a)),
+            Instruction::LLoad0(()) | Instruction::LLoad1(()) | Instruction::LLoad2(()) | Instruction::LLoad3(()) => load(usize::from(instruction.opcode() - 0x1e)),
+            Instruction::FLoad0(()) | Instruction::FLoad1(()) | Instruction::FLoad2(()) | Instruction::FLoad3(()) => load(usize::from(instruction.opcode() - 0x22)),
+            Instruction::DLoad0(()) | Instruction::DLoad1(()) | Instruction::DLoad2(()) | Instruction::DLoad3(()) => load(usize::from(instruction.opcode() - 0x26)),
+            Instruction::ALoad0(()) | Instruction::ALoad1(()) | Instruction::ALoad2(()) | Instruction::ALoad3(()) => load(usize::from(instruction.opcode() - 0x2a)),
+            Instruction::IALoad(()) | Instruction::LALoad(()) | Instruction::FALoad(()) | Instruction::DALoad(()) | Instruction::AALoad(()) |
+            Instruction::BALoad(()) | Instruction::CALoad(()) | Instruction::ScALoad(()) =>
+                format!("pop {} and index {} and push the element at that index", second(), top()),
+
+            Instruction::IStore(index) | Instruction::LStore(index) | Instruction::FStore(index) | Instruction::DStore(index) |
+            Instruction::AStore(index) => store(usize::from(*index)),
+            Instruction::IStore0(()) | Instruction::IStore1(()) | Instruction::IStore2(()) | Instruction::IStore3(()) => store(usize::from(instruction.opcode() - 0x3b)),
+            Instruction::LStore0(()) | Instruction::LStore1(()) | Instruction::LStore2(()) | Instruction::LStore3(()) => store(usize::from(instruction.opcode() - 0x3f)),
+            Instruction::FStore0(()) | Instruction::FStore1(()) | Instruction::FStore2(()) | Instruction::FStore3(()) => store(usize::from(instruction.opcode() - 0x43)),
+            Instruction::DStore0(()) | Instruction::DStore1(()) | Instruction::DStore2(()) | Instruction::DStore3(()) => store(usize::from(instruction.opcode() - 0x47)),
+            Instruction::AStore0(()) | Instruction::AStore1(()) | Instruction::AStore2(()) | Instruction::AStore3(()) => store(usize::from(instruction.opcode() - 0x4b)),
+            Instruction::IAStore(()) | Instruction::LAStore(()) | Instruction::FAStore(()) | Instruction::DAStore(()) | Instruction::AAStore(()) |
+            Instruction::BAStore(()) | Instruction::CAStore(()) | Instruction::SAStore(()) =>
+                format!("pop {}, index {} and {} and store the value at that index", self.describe_operand(frame, 2), second(), top()),
+
+            Instruction::Pop(()) => format!("pop {} and discard it", top()),
+            Instruction::Pop2(()) => String::from("discard the top two slots of the operand stack"),
+            Instruction::Dup(()) => format!("push {} again", top()),
+            Instruction::DupX1(()) | Instruction::DupX2(()) | Instruction::Dup2(()) | Instruction::Dup2X1(()) | Instruction::Dup2X2(()) =>
+                String::from("copy values on top of the operand stack and insert the copies further down"),
+            Instruction::Swap(()) => format!("swap {} and {}", second(), top()),
+
+            Instruction::IAdd(()) | Instruction::LAdd(()) | Instruction::FAdd(()) | Instruction::DAdd(()) => binary("their sum"),
+            Instruction::ISub(()) | Instruction::LSub(()) | Instruction::FSub(()) | Instruction::DSub(()) => binary("the first minus the second"),
+            Instruction::IMul(()) | Instruction::LMul(()) | Instruction::FMul(()) | Instruction::DMul(()) => binary("their product"),
+            Instruction::IDiv(()) | Instruction::LDiv(()) | Instruction::FDiv(()) | Instruction::DDiv(()) => binary("the first divided by the second"),
+            Instruction::IRem(()) | Instruction::LRem(()) | Instruction::FRem(()) | Instruction::DRem(()) => binary("the remainder of the first divided by the second"),
+            Instruction::INeg(()) | Instruction::LNeg(()) | Instruction::FNeg(()) | Instruction::DNeg(()) => format!("pop {} and push its negation", top()),
+            Instruction::IShl(()) | Instruction::LShl(()) => binary("the first shifted left by the second"),
+            Instruction::IShr(()) | Instruction::LShr(()) => binary("the first shifted right by the second, keeping the sign"),
+            Instruction::IUSHR(()) | Instruction::LUSHR(()) => binary("the first shifted right by the second, filling in zeros"),
+            Instruction::IAnd(()) | Instruction::LAnd(()) => binary("their bitwise and"),
+            Instruction::IOr(()) | Instruction::LOr(()) => binary("their bitwise or"),
+            Instruction::IXor(()) | Instruction::LXor(()) => binary("their bitwise exclusive or"),
+            Instruction::IInc(operands) => {
+                let (index, delta) = (usize::from(operands >> 8), (operands & 0xff) as u8 as i8);
+                format!("add {} to local variable {} ({}) without touching the operand stack", delta, index, self.describe_local(frame, index))
+            }
+
+            Instruction::I2L(()) | Instruction::F2L(()) | Instruction::D2L(()) => convert("long"),
+            Instruction::I2F(()) | Instruction::L2F(()) | Instruction::D2F(()) => convert("float"),
+            Instruction::I2D(()) | Instruction::L2D(()) | Instruction::F2D(()) => convert("double"),
+            Instruction::L2I(()) | Instruction::F2I(()) | Instruction::D2I(()) => convert("int"),
+            Instruction::I2B(()) => convert("byte, keeping the lowest 8 bits"),
+            Instruction::I2C(()) => convert("char, keeping the lowest 16 bits"),
+            Instruction::I2S(()) => convert("short, keeping the lowest 16 bits"),
+            Instruction::LCmp(()) | Instruction::FCmpL(()) | Instruction::FCmpG(()) | Instruction::DCmpL(()) | Instruction::DCmpG(()) =>
+                binary("-1, 0 or 1 as the first is less than, equal to or greater than the second"),
+
+            Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) | Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) =>
+                format!("pop {} and jump to pc {} if it is {} 0", top(), target(pc, instruction.branch_offset().unwrap_or(0)), condition(instruction)),
+            Instruction::IfICmpEQ(_) | Instruction::IfICmpNE(_) | Instruction::IfICmpLT(_) | Instruction::IfICmpGE(_) |
+            Instruction::IfICmpGT(_) | Instruction::IfICmpLE(_) =>
+                format!("pop {} and {} and jump to pc {} if the first is {} the second", second(), top(),
+                        target(pc, instruction.branch_offset().unwrap_or(0)), condition(instruction)),
+            Instruction::IfACmpEQ(_) => format!("pop {} and {} and jump to pc {} if they are the same object", second(), top(), target(pc, instruction.branch_offset().unwrap_or(0))),
+            Instruction::IfACmpNE(_) => format!("pop {} and {} and jump to pc {} if they are different objects", second(), top(), target(pc, instruction.branch_offset().unwrap_or(0))),
+            Instruction::IfNull(_) => format!("pop {} and jump to pc {} if it is null", top(), target(pc, instruction.branch_offset().unwrap_or(0))),
+            Instruction::IfNonNull(_) => format!("pop {} and jump to pc {} if it is not null", top(), target(pc, instruction.branch_offset().unwrap_or(0))),
+            Instruction::Goto(_) => format!("jump to pc {}", target(pc, instruction.branch_offset().unwrap_or(0))),
+            Instruction::GotoW(offset) => format!("jump to pc {}", target(pc, i64::from(*offset as i32))),
+            Instruction::JSR(_) | Instruction::JSRW(_) => String::from("push the return address and jump to a subroutine"),
+            Instruction::Ret(index) => format!("jump back to the return address in local variable {}", index),
+            Instruction::TableSwitch((default, low, high, offsets)) => {
+                let key = match frame.stack.last() {
+                    Some(StackValue::Integer(key)) => *key as i32,
+                    _ => return format!("pop {} and jump to the case of its value", top())
+                };
+                match (key >= *low && key <= *high, offsets.get((i64::from(key) - i64::from(*low)) as usize)) {
+                    (true, Some(offset)) => format!("pop {} and jump to pc {}, its case in the table", top(), target(pc, i64::from(*offset))),
+                    _ => format!("pop {} and jump to pc {}, the default as it is not between {} and {}", top(), target(pc, i64::from(*default)), low, high)
+                }
+            }
+            Instruction::LookupSwitch((default, pairs)) => {
+                let key = match frame.stack.last() {
+                    Some(StackValue::Integer(key)) => *key as i32,
+                    _ => return format!("pop {} and jump to the case of its value", top())
+                };
+                match pairs.iter().find(|&&(value, _)| value == key) {
+                    Some(&(_, offset)) => format!("pop {} and jump to pc {}, its case", top(), target(pc, i64::from(offset))),
+                    None => format!("pop {} and jump to pc {}, the default as there is no case for it", top(), target(pc, i64::from(*default)))
+                }
+            }
+
+            Instruction::IReturn(()) | Instruction::LReturn(()) | Instruction::FReturn(()) | Instruction::DReturn(()) | Instruction::AReturn(()) =>
+                format!("pop {} and return it to the caller", top()),
+            Instruction::Return(()) => String::from("return to the caller"),
+
+            Instruction::GetStatic(index) => format!("push the static field {}", class.describe_constant(*index)),
+            Instruction::PutStatic(index) => format!("pop {} and store it in the static field {}", top(), class.describe_constant(*index)),
+            Instruction::GetField(index) => format!("pop {} and push its field {}", top(), class.describe_constant(*index)),
+            Instruction::PutField(index) => format!("pop {} and {} and store the value in the field {} of the object", second(), top(), class.describe_constant(*index)),
+            Instruction::InvokeVirtual(index) | Instruction::InvokeInterface((index, _, _)) => {
+                let count = argument_count(class, *index);
+                format!("pop {} and {} and call {} on it, chosen by the class of the object",
+                        self.describe_operand(frame, count), self.describe_arguments(frame, count), class.describe_constant(*index))
+            }
+            Instruction::InvokeSpecial(index) => {
+                let count = argument_count(class, *index);
+                let what = match class.describe_constant(*index) {
+                    ref method if method.contains(".<init>(") => format!("the constructor {}", method),
+                    method => method
+                };
+                format!("pop {} and {} and call {} on it, exactly this method", self.describe_operand(frame, count), self.describe_arguments(frame, count), what)
+            }
+            Instruction::InvokeStatic(index) => {
+                let count = argument_count(class, *index);
+                match count {
+                    0 => format!("call the static method {} without arguments", class.describe_constant(*index)),
+                    _ => format!("pop {} and call the static method {}", self.describe_arguments(frame, count), class.describe_constant(*index))
+                }
+            }
+            Instruction::InvokeDynamic((index, _)) => {
+                let count = argument_count(class, *index);
+                let call = format!("call the call site {}, linked by its bootstrap method on first use", class.describe_constant(*index));
+                match count {
+                    0 => call,
+                    _ => format!("pop {} and {}", self.describe_arguments(frame, count), call)
+                }
+            }
+
+            Instruction::New(index) => format!("create an object of {} with default fields and push a reference to it", class.describe_constant(*index)),
+            Instruction::NewArray(atype) => {
+                let element = ElementType::from_atype(*atype).map(|element| &element.class_name()[1..]).unwrap_or("?");
+                format!("pop the length {} and push a new array of {} filled with zeros", top(), element)
+            }
+            Instruction::AAewArray(index) => format!("pop the length {} and push a new array of {} filled with null", top(), class.describe_constant(*index)),
+            Instruction::MultianeWArray((index, dimensions)) =>
+                format!("pop {} lengths ({}) and push a new array of {}", dimensions, self.describe_operands(frame, usize::from(*dimensions)), class.describe_constant(*index)),
+            Instruction::ArrayLength(()) => format!("pop {} and push its length", top()),
+            Instruction::AThrow(()) => format!("pop {} and throw it", top()),
+            Instruction::CheckCast(index) => format!("check that {} is null or an instance of {}, else throw a ClassCastException", top(), class.describe_constant(*index)),
+            Instruction::InstanceOf(index) => format!("pop {} and push 1 if it is an instance of {}, else 0", top(), class.describe_constant(*index)),
+            Instruction::MonitorEnter(()) => format!("pop {} and lock its monitor", top()),
+            Instruction::MonitorExit(()) => format!("pop {} and unlock its monitor", top()),
+            Instruction::Wide(_) => String::from("run the next instruction with a 16 bit local variable index"),
+            Instruction::Breakpoint(()) | Instruction::ImpDep1(()) | Instruction::ImpDep2(()) => String::from("reserved for debuggers and the jvm itself"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use captured::Captured;
+    use super::*;
+
+    fn explained<F>(bytes: &'static [u8], others: &[&'static [u8]], run: F) -> Vec<String> where F: FnOnce(&mut Runtime) {
+        let out = Captured::default();
+        let mut rt = Runtime::builder().explain(Some(out.output()))
+            .build(read_class_file(bytes).unwrap().1);
+        for other in others {
+            rt.load_class(read_class_file(other).unwrap().1);
+        }
+        run(&mut rt);
+        out.text().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn every_instruction_gets_a_line_with_its_values() {
+        let lines = explained(include_bytes!("../../../sample/Loop.class"), &[], |rt| {
+            rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(3)]).unwrap();
+        });
+        assert_eq!(lines[0], "Loop.sum    0  iconst_0: push the constant int 0 onto the operand stack");
+        assert!(lines.iter().any(|line| line.ends_with("iload_0: push local variable 0 (int 3) onto the operand stack")), "{:#?}", lines);
+        assert!(lines.iter().any(|line| line.contains("if_icmp") && line.contains("and int 3 and jump to pc")), "{:#?}", lines);
+        assert!(lines.iter().any(|line| line.contains("iadd: pop int ") && line.ends_with("and push their sum")), "{:#?}", lines);
+        assert!(lines.last().unwrap().ends_with("ireturn: pop int 3 and return it to the caller"), "{:#?}", lines);
+    }
+
+    #[test]
+    fn constant_pool_targets_are_resolved() {
+        let lines = explained(include_bytes!("../../../sample/Dispatch.class"),
+                              &[include_bytes!("../../../sample/Sub.class"), include_bytes!("../../../sample/Valued.class")], |rt| {
+            rt.invoke_static("Dispatch", "run", "()I", vec![]).unwrap();
+        });
+        assert_eq!(lines[0], "Dispatch.run    0  new: create an object of class Dispatch with default fields and push a reference to it");
+        assert!(lines.contains(&String::from("Dispatch.run    7  invokestatic: pop the arguments (Dispatch@0) and call the static method Dispatch.twice(LDispatch;)I")), "{:#?}", lines);
+        assert!(lines.contains(&String::from("  Dispatch.twice    1  invokevirtual: pop Dispatch@0 and no arguments and call Dispatch.value()I on it, chosen by the class of the object")), "{:#?}", lines);
+        assert!(lines.iter().any(|line| line.contains("invokespecial: pop Sub@1 and no arguments and call the constructor Sub.<init>()V")), "{:#?}", lines);
+        assert!(lines.iter().any(|line| line == "    Sub.value    0  bipush: push the constant int 20 onto the operand stack"), "{:#?}", lines);
+    }
+
+    #[test]
+    fn mnemonics_follow_the_specification() {
+        let names = [Instruction::ILoad1(()), Instruction::IConstm1(()), Instruction::IfICmpGE(0), Instruction::LDC2W(1), Instruction::AAewArray(1),
+            Instruction::ScALoad(()), Instruction::Dup2X1(()), Instruction::I2L(()), Instruction::IUSHR(()), Instruction::NOOP(()), Instruction::InvokeVirtual(1)]
+            .iter().map(Instruction::mnemonic).collect::<Vec<_>>();
+        assert_eq!(names, vec!["iload_1", "iconst_m1", "if_icmpge", "ldc2_w", "anewarray", "saload", "dup2_x1", "i2l", "iushr", "nop", "invokevirtual"]);
+    }
+}
@@ -0,0 +1,513 @@
+use java::class_file::{ClassFile, ConstantType, Method};
+use java::instructions::Instruction;
+use java::runtime::{DecodedInsn, LocalVariable, ObjectRef, Runtime, RuntimeError, SafepointKind, StackValue};
+use super::{int_arithmetic, opcode_coverage, MethodContext, StackFrame};
+
+/// which interpreter executes bytecode.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InterpreterMode {
+    /// checks the type of every value it touches. supports everything and reports broken bytecode.
+    Checked,
+    /// keeps locals and the operand stack in raw 64-bit slots for methods which pass `verify`,
+    /// and runs everything else on the checked interpreter.
+    /// hooks, debuggers and breakpoints also switch back to the checked interpreter.
+    Fast,
+}
+
+/// what a verified slot holds.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SlotType {
+    Int,
+    /// a reference or null. slots hold the handle of the object + 1, 0 is null.
+    Reference,
+    /// not assigned on every path, must not be read.
+    Unset,
+}
+
+/// the types of the locals and the operand stack before an instruction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeState {
+    pub locals: Vec<SlotType>,
+    pub stack: Vec<SlotType>,
+}
+
+/// a method proven type safe for the fast interpreter.
+#[derive(Debug, Clone)]
+pub struct FastMethod {
+    /// the type state before every decoded instruction, used to hand typed frames to the garbage collector.
+    pub states: Vec<TypeState>,
+    pub max_stack: usize,
+}
+
+fn slot_type(descriptor: &[u8]) -> Option<(Option<SlotType>, usize)> {
+    match descriptor.first()? {
+        b'I' | b'Z' | b'B' | b'C' | b'S' => Some((Some(SlotType::Int), 1)),
+        b'V' => Some((None, 1)),
+        b'L' => descriptor.iter().position(|&c| c == b';').map(|end| (Some(SlotType::Reference), end + 1)),
+        b'[' => slot_type(&descriptor[1..]).and_then(|(typ, length)| typ.map(|_| (Some(SlotType::Reference), length + 1))),
+        // long, double and float have no fast path
+        _ => None
+    }
+}
+
+/// the argument and return types of a method descriptor, `None` if one of them is not supported.
+fn signature(descriptor: &str) -> Option<(Vec<SlotType>, Option<SlotType>)> {
+    let bytes = descriptor.as_bytes();
+    if bytes.first() != Some(&b'(') {
+        return None;
+    }
+
+    let mut arguments = Vec::new();
+    let mut position = 1;
+    while *bytes.get(position)? != b')' {
+        let (typ, length) = slot_type(&bytes[position..])?;
+        arguments.push(typ?);
+        position += length;
+    }
+
+    let (result, _) = slot_type(&bytes[position + 1..])?;
+    Some((arguments, result))
+}
+
+fn static_call_signature(class: &ClassFile, index: u16) -> Option<(Vec<SlotType>, Option<SlotType>)> {
+    match class.get_constant(index)? {
+        ConstantType::MethodRef { name_and_type_index, .. } => signature(class.get_name_and_type(*name_and_type_index)?.1),
+        _ => None
+    }
+}
+
+impl TypeState {
+    fn pop(&mut self, expected: SlotType) -> Option<()> {
+        if self.stack.pop()? == expected { Some(()) } else { None }
+    }
+
+    fn load(&mut self, index: usize, expected: SlotType) -> Option<()> {
+        if *self.locals.get(index)? != expected {
+            return None;
+        }
+        self.stack.push(expected);
+        Some(())
+    }
+
+    fn store(&mut self, index: usize, expected: SlotType) -> Option<()> {
+        self.pop(expected)?;
+        *self.locals.get_mut(index)? = expected;
+        Some(())
+    }
+
+    /// merges the state of another path into this one. locals with different types become unset.
+    fn merge(&mut self, other: &TypeState) -> Option<bool> {
+        if self.stack != other.stack {
+            return None;
+        }
+
+        let mut changed = false;
+        for (local, &other) in self.locals.iter_mut().zip(other.locals.iter()) {
+            if *local != other && *local != SlotType::Unset {
+                *local = SlotType::Unset;
+                changed = true;
+            }
+        }
+        Some(changed)
+    }
+}
+
+/// what an instruction does to the type state, `None` if it is not supported or not type safe.
+/// the flag tells whether execution can continue with the next instruction.
+fn step(class: &ClassFile, result: Option<SlotType>, instruction: &Instruction, state: &mut TypeState) -> Option<bool> {
+    use self::SlotType::{Int, Reference};
+
+    match instruction {
+        Instruction::AConstNull(()) => state.stack.push(Reference),
+        Instruction::IConstm1(()) | Instruction::IConst0(()) | Instruction::IConst1(()) | Instruction::IConst2(()) |
+        Instruction::IConst3(()) | Instruction::IConst4(()) | Instruction::IConst5(()) |
+        Instruction::BIPush(_) | Instruction::SIPush(_) => state.stack.push(Int),
+        Instruction::ILoad(offset) => state.load(usize::from(*offset), Int)?,
+        Instruction::ILoad0(()) => state.load(0, Int)?,
+        Instruction::ILoad1(()) => state.load(1, Int)?,
+        Instruction::ILoad2(()) => state.load(2, Int)?,
+        Instruction::ILoad3(()) => state.load(3, Int)?,
+        Instruction::ALoad(offset) => state.load(usize::from(*offset), Reference)?,
+        Instruction::ALoad0(()) => state.load(0, Reference)?,
+        Instruction::ALoad1(()) => state.load(1, Reference)?,
+        Instruction::ALoad2(()) => state.load(2, Reference)?,
+        Instruction::ALoad3(()) => state.load(3, Reference)?,
+        Instruction::IStore(offset) => state.store(usize::from(*offset), Int)?,
+        Instruction::IStore0(()) => state.store(0, Int)?,
+        Instruction::IStore1(()) => state.store(1, Int)?,
+        Instruction::IStore2(()) => state.store(2, Int)?,
+        Instruction::IStore3(()) => state.store(3, Int)?,
+        Instruction::AStore(offset) => state.store(usize::from(*offset), Reference)?,
+        Instruction::AStore0(()) => state.store(0, Reference)?,
+        Instruction::AStore1(()) => state.store(1, Reference)?,
+        Instruction::AStore2(()) => state.store(2, Reference)?,
+        Instruction::AStore3(()) => state.store(3, Reference)?,
+        Instruction::Pop(()) => {
+            state.stack.pop()?;
+        }
+        Instruction::Dup(()) => {
+            let top = *state.stack.last()?;
+            state.stack.push(top);
+        }
+        // division can throw, which only the checked interpreter does
+        Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) |
+        Instruction::IShl(()) | Instruction::IShr(()) | Instruction::IUSHR(()) |
+        Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(()) => {
+            state.pop(Int)?;
+            state.pop(Int)?;
+            state.stack.push(Int);
+        }
+        Instruction::INeg(()) => {
+            state.pop(Int)?;
+            state.stack.push(Int);
+        }
+        Instruction::IInc(operands) => if *state.locals.get(usize::from(operands >> 8))? != Int {
+            return None;
+        },
+        Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) |
+        Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) => state.pop(Int)?,
+        Instruction::IfICmpEQ(_) | Instruction::IfICmpNE(_) | Instruction::IfICmpLT(_) |
+        Instruction::IfICmpGE(_) | Instruction::IfICmpGT(_) | Instruction::IfICmpLE(_) => {
+            state.pop(Int)?;
+            state.pop(Int)?;
+        }
+        Instruction::IfACmpEQ(_) | Instruction::IfACmpNE(_) => {
+            state.pop(Reference)?;
+            state.pop(Reference)?;
+        }
+        Instruction::IfNull(_) | Instruction::IfNonNull(_) => state.pop(Reference)?,
+        Instruction::Goto(_) => return Some(false),
+        Instruction::IReturn(()) | Instruction::AReturn(()) => {
+            let returned = if let Instruction::IReturn(()) = instruction { Int } else { Reference };
+            if result != Some(returned) {
+                return None;
+            }
+            state.pop(returned)?;
+            return Some(false);
+        }
+        Instruction::Return(()) => return if result.is_none() { Some(false) } else { None },
+        Instruction::InvokeStatic(index) => {
+            let (arguments, returned) = static_call_signature(class, *index)?;
+            for &argument in arguments.iter().rev() {
+                state.pop(argument)?;
+            }
+            if let Some(returned) = returned {
+                state.stack.push(returned);
+            }
+        }
+        _ => return None
+    }
+
+    Some(true)
+}
+
+impl FastMethod {
+    /// true if the states have the shape `verify` gives them for `method` and its decoded
+    /// `instructions`: one per instruction, each with all locals or none for unreachable ones, and
+    /// stacks within the limit. for verification results read back from an archive.
+    pub fn fits(&self, method: &Method, instructions: &[DecodedInsn]) -> bool {
+        let code = match method.get_code() {
+            Some(code) => code,
+            None => return false
+        };
+        let (max_locals, max_stack) = (usize::from(code.max_locals), usize::from(code.max_stack));
+        self.max_stack == max_stack && self.states.len() == instructions.len() && !instructions.is_empty()
+            && self.states[0].locals.len() == max_locals
+            && self.states.iter().all(|state| {
+                (state.locals.len() == max_locals || (state.locals.is_empty() && state.stack.is_empty())) && state.stack.len() <= max_stack
+            })
+    }
+}
+
+/// checks that `method` only uses instructions of the fast interpreter and never mixes up integers and
+/// references, by computing the type of every local and stack slot before every instruction.
+pub fn verify(class: &ClassFile, method: &Method, instructions: &[DecodedInsn]) -> Option<FastMethod> {
+    let code = method.get_code()?;
+    if !code.exception_table.is_empty() || instructions.is_empty() {
+        return None;
+    }
+
+    let (arguments, result) = signature(&method.descriptor)?;
+    let mut locals = Vec::new();
+    if method.access_flags & 0x0008 == 0 {
+        locals.push(SlotType::Reference);
+    }
+    locals.extend(arguments);
+    if locals.len() > usize::from(code.max_locals) {
+        return None;
+    }
+    locals.resize(usize::from(code.max_locals), SlotType::Unset);
+
+    let mut states: Vec<Option<TypeState>> = vec![None; instructions.len()];
+    states[0] = Some(TypeState { locals, stack: Vec::new() });
+    let mut pending = vec![0];
+    while let Some(index) = pending.pop() {
+        let mut state = states[index].clone()?;
+        let insn = &instructions[index];
+        let falls_through = step(class, result, &insn.instruction, &mut state)?;
+        if state.stack.len() > usize::from(code.max_stack) {
+            return None;
+        }
+
+        let next = if falls_through { Some(index + 1) } else { None };
+        for successor in next.into_iter().chain(insn.target) {
+            let changed = match states.get_mut(successor)? {
+                Some(existing) => existing.merge(&state)?,
+                entry => {
+                    *entry = Some(state.clone());
+                    true
+                }
+            };
+            if changed {
+                pending.push(successor);
+            }
+        }
+    }
+
+    // unreachable instructions never run, any state will do for them
+    let empty = TypeState { locals: Vec::new(), stack: Vec::new() };
+    Some(FastMethod {
+        states: states.into_iter().map(|state| state.unwrap_or_else(|| empty.clone())).collect(),
+        max_stack: usize::from(code.max_stack),
+    })
+}
+
+fn to_slot(value: &LocalVariable) -> u64 {
+    match value {
+        LocalVariable::Integer(value) => *value as u64,
+        LocalVariable::Reference(reference) => reference.handle() + 1,
+        LocalVariable::Null | LocalVariable::None => 0,
+        // longs never reach the fast interpreter, verification rejects them
+        LocalVariable::Long(_) | LocalVariable::Top => 0,
+    }
+}
+
+fn from_slot(slot: u64, typ: SlotType) -> StackValue {
+    match typ {
+        SlotType::Int => StackValue::Integer(slot as i64),
+        SlotType::Reference if slot == 0 => StackValue::Null,
+        SlotType::Reference => StackValue::Reference(ObjectRef::from_handle(slot - 1)),
+        SlotType::Unset => StackValue::None,
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// true if methods with a `FastMethod` may run on the fast interpreter right now.
+    pub(super) fn can_run_fast(&self) -> bool {
+        self.interpreter == InterpreterMode::Fast && self.hooks.is_empty() && self.debugger.is_none()
+            && !self.breakpoints.is_active() && self.watchpoints.is_empty() && self.trace.is_none() && self.explain.is_none()
+    }
+
+    /// the typed frame of the fast interpreter at `index`, for the garbage collector and stack inspection.
+    fn typed_frame(fast: &FastMethod, index: usize, locals: &[u64], stack: &[u64]) -> StackFrame {
+        let state = &fast.states[index];
+        StackFrame {
+            local_variables: locals.iter().zip(state.locals.iter()).map(|(&slot, &typ)| LocalVariable::from(from_slot(slot, typ))).collect(),
+            stack: stack.iter().zip(state.stack.iter()).map(|(&slot, &typ)| from_slot(slot, typ)).collect(),
+        }
+    }
+
+    /// runs a verified method on raw slots. the verifier guarantees every pop finds a value of the
+    /// right type, so nothing is checked here.
+    pub(super) fn execute_fast(&mut self, context: MethodContext<'_, 'a>, fast: &FastMethod, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        let mut locals = self.thread.frame_pool.acquire_slots(fast.states[0].locals.len());
+        for (local, argument) in locals.iter_mut().zip(arguments.iter()) {
+            *local = to_slot(argument);
+        }
+        let mut stack = self.thread.frame_pool.acquire_slots(0);
+        stack.reserve(fast.max_stack);
+
+        let result = self.run_fast(context, fast, &mut locals, &mut stack);
+        self.thread.frame_pool.release_slots(locals);
+        self.thread.frame_pool.release_slots(stack);
+
+        result
+    }
+
+    fn run_fast(&mut self, context: MethodContext<'_, 'a>, fast: &FastMethod, locals: &mut [u64], stack: &mut Vec<u64>) -> Result<Option<StackValue>, RuntimeError> {
+        let MethodContext { class, id, slot, pool, decoded } = context;
+        let mut index = 0;
+
+        macro_rules! pop {
+            () => { stack.pop().unwrap() }
+        }
+        // the frame of the method is only complete at a safepoint if its pc is up to date
+        macro_rules! poll {
+            ($kind:expr) => {
+                if self.safepoint_pending() {
+                    if let Some(frame) = self.thread.frames.last_mut() {
+                        frame.pc = decoded.instructions[index].pc;
+                    }
+                    self.safepoint($kind, &mut Runtime::typed_frame(fast, index, locals, stack))?;
+                }
+            }
+        }
+        macro_rules! branch {
+            ($condition:expr) => {
+                if $condition {
+                    let target = decoded.instructions[index].target.unwrap();
+                    // back edges are where loops can spin forever
+                    if target <= index {
+                        poll!(SafepointKind::BackEdge);
+                        self.count_back_edge(id, slot);
+                    }
+                    index = target;
+                    continue;
+                }
+            }
+        }
+
+        poll!(SafepointKind::MethodEntry);
+        loop {
+            self.counters.retire(1);
+            opcode_coverage::record(&decoded.instructions[index].instruction);
+            match decoded.instructions[index].instruction {
+                Instruction::AConstNull(()) => stack.push(0),
+                Instruction::IConstm1(()) => stack.push(-1i64 as u64),
+                Instruction::IConst0(()) => stack.push(0),
+                Instruction::IConst1(()) => stack.push(1),
+                Instruction::IConst2(()) => stack.push(2),
+                Instruction::IConst3(()) => stack.push(3),
+                Instruction::IConst4(()) => stack.push(4),
+                Instruction::IConst5(()) => stack.push(5),
+                Instruction::BIPush(value) => stack.push(i64::from(value as i8) as u64),
+                Instruction::SIPush(value) => stack.push(i64::from(value as i16) as u64),
+                Instruction::ILoad(offset) | Instruction::ALoad(offset) => stack.push(locals[usize::from(offset)]),
+                Instruction::ILoad0(()) | Instruction::ALoad0(()) => stack.push(locals[0]),
+                Instruction::ILoad1(()) | Instruction::ALoad1(()) => stack.push(locals[1]),
+                Instruction::ILoad2(()) | Instruction::ALoad2(()) => stack.push(locals[2]),
+                Instruction::ILoad3(()) | Instruction::ALoad3(()) => stack.push(locals[3]),
+                Instruction::IStore(offset) | Instruction::AStore(offset) => locals[usize::from(offset)] = pop!(),
+                Instruction::IStore0(()) | Instruction::AStore0(()) => locals[0] = pop!(),
+                Instruction::IStore1(()) | Instruction::AStore1(()) => locals[1] = pop!(),
+                Instruction::IStore2(()) | Instruction::AStore2(()) => locals[2] = pop!(),
+                Instruction::IStore3(()) | Instruction::AStore3(()) => locals[3] = pop!(),
+                Instruction::Pop(()) => {
+                    pop!();
+                }
+                Instruction::Dup(()) => {
+                    let top = *stack.last().unwrap();
+                    stack.push(top);
+                }
+                ref instruction @ (Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) |
+                Instruction::IShl(()) | Instruction::IShr(()) | Instruction::IUSHR(()) |
+                Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(())) => {
+                    let (rh, lh) = (pop!() as i32, pop!() as i32);
+                    stack.push(i64::from(int_arithmetic(instruction, lh, rh).unwrap()) as u64);
+                }
+                Instruction::INeg(()) => {
+                    let value = pop!() as i32;
+                    stack.push(i64::from(value.wrapping_neg()) as u64);
+                }
+                Instruction::IInc(operands) => {
+                    let local = &mut locals[usize::from(operands >> 8)];
+                    *local = i64::from((*local as i32).wrapping_add(i32::from((operands & 0xff) as u8 as i8))) as u64;
+                }
+                Instruction::Ifeq(_) => branch!(pop!() as i64 == 0),
+                Instruction::Ifne(_) => branch!(pop!() as i64 != 0),
+                Instruction::Iflt(_) => branch!((pop!() as i64) < 0),
+                Instruction::Ifge(_) => branch!(pop!() as i64 >= 0),
+                Instruction::Ifgt(_) => branch!(pop!() as i64 > 0),
+                Instruction::Ifle(_) => branch!(pop!() as i64 <= 0),
+                Instruction::IfICmpEQ(_) | Instruction::IfACmpEQ(_) => branch!(pop!() == pop!()),
+                Instruction::IfICmpNE(_) | Instruction::IfACmpNE(_) => branch!(pop!() != pop!()),
+                Instruction::IfICmpLT(_) => branch!({ let rh = pop!() as i64; (pop!() as i64) < rh }),
+                Instruction::IfICmpGE(_) => branch!({ let rh = pop!() as i64; pop!() as i64 >= rh }),
+                Instruction::IfICmpGT(_) => branch!({ let rh = pop!() as i64; pop!() as i64 > rh }),
+                Instruction::IfICmpLE(_) => branch!({ let rh = pop!() as i64; pop!() as i64 <= rh }),
+                Instruction::IfNull(_) => branch!(pop!() == 0),
+                Instruction::IfNonNull(_) => branch!(pop!() != 0),
+                Instruction::Goto(_) => branch!(true),
+                Instruction::IReturn(()) => {
+                    poll!(SafepointKind::Return);
+                    return Ok(Some(from_slot(pop!(), SlotType::Int)));
+                },
+                Instruction::AReturn(()) => {
+                    poll!(SafepointKind::Return);
+                    return Ok(Some(from_slot(pop!(), SlotType::Reference)));
+                },
+                Instruction::Return(()) => {
+                    poll!(SafepointKind::Return);
+                    return Ok(None);
+                },
+                Instruction::InvokeStatic(method_offset) => {
+                    let resolved = self.resolve_method(pool, class, method_offset)?;
+                    let count = resolved.signature.parameters().len();
+                    let types = &fast.states[index].stack[stack.len() - count..];
+                    let args = stack.split_off(stack.len() - count).into_iter().zip(types.iter())
+                        .map(|(slot, &typ)| LocalVariable::from(from_slot(slot, typ)))
+                        .collect::<Vec<_>>();
+
+                    if let Some(frame) = self.thread.frames.last_mut() {
+                        frame.pc = decoded.instructions[index].pc;
+                    }
+                    let mut saved = Runtime::typed_frame(fast, index, locals, stack);
+                    let result = self.suspended(&mut saved, |rt| match resolved.intrinsic {
+                        Some(intrinsic) => rt.run_intrinsic(&class.methods[slot], intrinsic, &args),
+                        None => {
+                            rt.initialize_class(&class.methods[slot], &resolved.class_name)?;
+                            let inlined = match resolved.target {
+                                Some(target) => rt.run_inlined(&decoded.instructions[index], target, &args),
+                                None => None
+                            };
+                            match inlined {
+                                Some(value) => Ok(Some(value)),
+                                None => rt.invoke_resolved(&resolved, args)
+                            }
+                        }
+                    })?;
+                    if let Some(value) = result {
+                        stack.push(to_slot(&LocalVariable::from(value)));
+                    }
+                }
+                // the verifier only lets the instructions above through
+                _ => return Err(RuntimeError::UnsupportedInstruction { instruction: decoded.instructions[index].instruction.name() })
+            }
+
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::DecodedMethod;
+    use super::*;
+
+    #[test]
+    fn it_verifies_int_and_reference_slots() {
+        assert_eq!(signature("(ILjava/lang/String;[[I)V"), Some((vec![SlotType::Int, SlotType::Reference, SlotType::Reference], None)));
+        assert_eq!(signature("(J)I"), None);
+
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let max = class.methods.iter().find(|method| method.name == "max").unwrap();
+        let decoded = DecodedMethod::decode(&class, max).unwrap();
+        let fast = decoded.fast.as_ref().unwrap();
+        assert_eq!(fast.states[2].stack, vec![SlotType::Int, SlotType::Int]);
+
+        // field access is left to the checked interpreter
+        let class = read_class_file(include_bytes!("../../../sample/FieldExample.class")).unwrap().1;
+        let run = class.methods.iter().find(|method| method.name == "run").unwrap();
+        assert!(DecodedMethod::decode(&class, run).unwrap().fast.is_none());
+    }
+
+    #[test]
+    fn it_runs_like_the_checked_interpreter() {
+        let mut rt = Runtime::builder().interpreter(InterpreterMode::Fast).build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        for &(a, b, expected) in [(5, 3, 5), (2, 9, 9), (-4, -4, -4)].iter() {
+            match rt.invoke_static("Loop", "max", "(II)I", vec![LocalVariable::Integer(a), LocalVariable::Integer(b)]) {
+                Ok(Some(StackValue::Integer(value))) => assert_eq!(value, expected),
+                other => panic!("expected {}, got {:?}", expected, other)
+            }
+        }
+
+        match rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(10)]) {
+            Ok(Some(StackValue::Integer(45))) => (),
+            other => panic!("expected 45, got {:?}", other)
+        }
+
+        let mut rt = Runtime::builder().interpreter(InterpreterMode::Fast).build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        rt.invoke_static("Tiny", "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]).unwrap();
+    }
+}
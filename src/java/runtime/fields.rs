@@ -0,0 +1,229 @@
+use java::runtime::constant_pool::ACC_STATIC;
+use java::runtime::StackValue;
+use std::collections::{BTreeMap, HashMap};
+use std::ptr;
+use std::sync::Arc;
+
+/// the instance fields of a class and its super classes, each in its own slot.
+///
+/// the slots of the super classes come first, so a field has the same slot in a class and in all
+/// of its sub classes, and a `getfield` resolved once works for every instance it can see.
+/// fields are stored by name, so a field hiding one of a super class shares its slot.
+#[derive(Debug, Default)]
+pub struct FieldLayout {
+    parent: Option<Arc<FieldLayout>>,
+    names: Vec<String>,
+    slots: HashMap<String, usize>,
+}
+
+impl FieldLayout {
+    /// the layout of a class declaring `fields`, the access flags, names and descriptors of
+    /// `ClassFile::field_signatures`, below the layout of its super class.
+    pub fn extend(parent: Option<Arc<FieldLayout>>, fields: &[(u16, &str, &str)]) -> FieldLayout {
+        let mut names = parent.as_ref().map_or_else(Vec::new, |parent| parent.names.clone());
+        for &(access_flags, name, _) in fields {
+            if access_flags & ACC_STATIC == 0 && !names.iter().any(|known| known == name) {
+                names.push(String::from(name));
+            }
+        }
+        let slots = names.iter().enumerate().map(|(slot, name)| (name.clone(), slot)).collect();
+        FieldLayout { parent, names, slots }
+    }
+
+    /// the slot of a field.
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.slots.get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// true if this is `layout` or the layout of one of its sub classes.
+    fn extends(&self, layout: &FieldLayout) -> bool {
+        let mut current = Some(self);
+        while let Some(candidate) = current {
+            if ptr::eq(candidate, layout) {
+                return true;
+            }
+            current = candidate.parent.as_deref();
+        }
+        false
+    }
+}
+
+/// a `FieldRef` resolved to the slot of the field in the layout of the class declaring it.
+#[derive(Debug, Clone)]
+pub struct FieldSlot {
+    pub layout: Arc<FieldLayout>,
+    pub index: usize,
+}
+
+impl FieldSlot {
+    pub fn new(layout: Arc<FieldLayout>, name: &str) -> Option<FieldSlot> {
+        layout.slot(name).map(|index| FieldSlot { layout, index })
+    }
+}
+
+/// the fields of an object which were written, by name.
+///
+/// objects of loaded classes keep the fields of their class in the slots of its `FieldLayout`,
+/// everything else, like the fields of builtin objects, in a map sorted by name.
+#[derive(Debug, Clone, Default)]
+pub struct Fields {
+    layout: Option<Arc<FieldLayout>>,
+    slots: Vec<Option<StackValue>>,
+    other: BTreeMap<String, StackValue>,
+}
+
+impl Fields {
+    pub fn new() -> Fields {
+        Fields::default()
+    }
+
+    pub fn with_layout(layout: Arc<FieldLayout>) -> Fields {
+        Fields { slots: vec![None; layout.len()], layout: Some(layout), other: BTreeMap::new() }
+    }
+
+    /// moves the fields into the slots of `layout`.
+    pub fn set_layout(&mut self, layout: Arc<FieldLayout>) {
+        let fields = self.drain();
+        *self = Fields::with_layout(layout);
+        for (name, value) in fields {
+            self.insert(name, value);
+        }
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.layout.as_ref().and_then(|layout| layout.slot(name))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StackValue> {
+        match self.slot(name) {
+            Some(slot) => self.slots[slot].as_ref(),
+            None => self.other.get(name)
+        }
+    }
+
+    /// the index of `slot` in the slots of this object, if its layout extends the one of the slot.
+    fn resolved(&self, slot: Option<&FieldSlot>) -> Option<usize> {
+        match (slot, &self.layout) {
+            (Some(slot), Some(layout)) if layout.extends(&slot.layout) => Some(slot.index),
+            _ => None
+        }
+    }
+
+    /// the value of a resolved field. objects of other layouts, like the ones created before a
+    /// super class was loaded, look the field up by `name`.
+    pub fn get_resolved(&self, slot: Option<&FieldSlot>, name: &str) -> Option<&StackValue> {
+        match self.resolved(slot) {
+            Some(index) => self.slots[index].as_ref(),
+            None => self.get(name)
+        }
+    }
+
+    /// returns the previous value of the field.
+    pub fn insert(&mut self, name: String, value: StackValue) -> Option<StackValue> {
+        match self.slot(&name) {
+            Some(slot) => self.slots[slot].replace(value),
+            None => self.other.insert(name, value)
+        }
+    }
+
+    /// like `insert` for a field resolved to `slot`, see `get_resolved`.
+    pub fn insert_resolved(&mut self, slot: Option<&FieldSlot>, name: &str, value: StackValue) -> Option<StackValue> {
+        match self.resolved(slot) {
+            Some(index) => self.slots[index].replace(value),
+            None => self.insert(String::from(name), value)
+        }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// the number of fields which were written.
+    pub fn len(&self) -> usize {
+        self.slots.iter().flatten().count() + self.other.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the written fields, the ones in slots first in slot order, then the others by name.
+    pub fn iter(&self) -> impl Iterator<Item=(&String, &StackValue)> {
+        let names = self.layout.as_ref().map_or(&[][..], |layout| &layout.names[..]);
+        names.iter().zip(self.slots.iter())
+            .filter_map(|(name, value)| value.as_ref().map(|value| (name, value)))
+            .chain(self.other.iter())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item=&StackValue> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// keeps the fields for which `keep` is true.
+    pub fn retain<F: FnMut(&str, &StackValue) -> bool>(&mut self, mut keep: F) {
+        let names = self.layout.as_ref().map_or(&[][..], |layout| &layout.names[..]);
+        for (name, value) in names.iter().zip(self.slots.iter_mut()) {
+            if value.as_ref().is_some_and(|value| !keep(name, value)) {
+                *value = None;
+            }
+        }
+        self.other.retain(|name, value| keep(name, value));
+    }
+
+    fn drain(&mut self) -> Vec<(String, StackValue)> {
+        let fields = self.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+        *self = Fields::new();
+        fields
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fields_keep_their_slot_in_sub_classes() {
+        let base = Arc::new(FieldLayout::extend(None, &[(0, "x", "I"), (ACC_STATIC, "count", "I"), (0, "next", "LNode;")]));
+        let derived = Arc::new(FieldLayout::extend(Some(base.clone()), &[(0, "y", "I"), (0, "x", "J")]));
+        assert_eq!((base.slot("x"), base.slot("next"), base.slot("count")), (Some(0), Some(1), None));
+        assert_eq!((derived.slot("x"), derived.slot("next"), derived.slot("y")), (Some(0), Some(1), Some(2)));
+
+        let mut fields = Fields::with_layout(derived.clone());
+        let x = FieldSlot::new(base.clone(), "x");
+        assert_eq!(fields.insert_resolved(x.as_ref(), "x", StackValue::Integer(1)), None);
+        fields.insert(String::from("y"), StackValue::Integer(2));
+        fields.insert(String::from("arg$1"), StackValue::Null);
+        assert_eq!(fields.get("x"), Some(&StackValue::Integer(1)));
+        assert_eq!(fields.get_resolved(FieldSlot::new(derived, "y").as_ref(), "y"), Some(&StackValue::Integer(2)));
+        assert_eq!(fields.get_resolved(FieldSlot::new(base, "next").as_ref(), "next"), None);
+        assert_eq!(fields.len(), 3);
+
+        fields.retain(|name, _| name != "x");
+        let names = fields.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["y", "arg$1"]);
+    }
+
+    #[test]
+    fn objects_of_other_layouts_use_the_names() {
+        let old = Arc::new(FieldLayout::extend(None, &[(0, "value", "I")]));
+        let mut fields = Fields::with_layout(old);
+        fields.insert(String::from("value"), StackValue::Integer(3));
+
+        // the layout after the super class was loaded, which moved the field
+        let parent = Arc::new(FieldLayout::extend(None, &[(0, "id", "I")]));
+        let new = Arc::new(FieldLayout::extend(Some(parent), &[(0, "value", "I")]));
+        assert_eq!(fields.get_resolved(FieldSlot::new(new.clone(), "value").as_ref(), "value"), Some(&StackValue::Integer(3)));
+
+        fields.set_layout(new.clone());
+        assert_eq!(fields.get_resolved(FieldSlot::new(new, "value").as_ref(), "value"), Some(&StackValue::Integer(3)));
+        assert_eq!(Fields::new().get_resolved(None, "value"), None);
+    }
+}
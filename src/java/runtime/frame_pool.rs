@@ -0,0 +1,94 @@
+use java::class_file::Method;
+use java::runtime::{LocalVariable, StackFrame, StackValue};
+
+/// how many released buffers of each kind are kept for reuse.
+const MAX_POOLED: usize = 64;
+
+/// buffers of returned stack frames, reused by the next calls so a call does not allocate
+/// its locals and operand stack once the pool is warm.
+#[derive(Debug, Default)]
+pub struct FramePool {
+    locals: Vec<Vec<LocalVariable>>,
+    stacks: Vec<Vec<StackValue>>,
+    slots: Vec<Vec<u64>>,
+}
+
+impl FramePool {
+    pub fn new() -> FramePool {
+        FramePool::default()
+    }
+
+    /// a frame for `method` with the arguments in its first local variables.
+    pub(super) fn acquire(&mut self, method: &Method, arguments: Vec<LocalVariable>) -> StackFrame {
+        let code = method.get_code().unwrap();
+        let mut local_variables = self.locals.pop().unwrap_or_default();
+        for argument in arguments {
+            let category = argument.category();
+            local_variables.push(argument);
+            if category == 2 {
+                local_variables.push(LocalVariable::Top);
+            }
+        }
+        local_variables.resize(usize::from(code.max_locals).max(local_variables.len()), LocalVariable::None);
+
+        let mut stack = self.stacks.pop().unwrap_or_default();
+        stack.reserve(usize::from(code.max_stack));
+
+        StackFrame { local_variables, stack }
+    }
+
+    /// returns the buffers of a frame which is done to the pool.
+    pub(super) fn release(&mut self, frame: StackFrame) {
+        let StackFrame { mut local_variables, mut stack } = frame;
+        if self.locals.len() < MAX_POOLED {
+            local_variables.clear();
+            self.locals.push(local_variables);
+        }
+        if self.stacks.len() < MAX_POOLED {
+            stack.clear();
+            self.stacks.push(stack);
+        }
+    }
+
+    /// a zeroed slot buffer of `len` entries for the fast interpreter.
+    pub(super) fn acquire_slots(&mut self, len: usize) -> Vec<u64> {
+        let mut slots = self.slots.pop().unwrap_or_default();
+        slots.resize(len, 0);
+        slots
+    }
+
+    pub(super) fn release_slots(&mut self, mut slots: Vec<u64>) {
+        if self.slots.len() < MAX_POOLED {
+            slots.clear();
+            self.slots.push(slots);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    #[test]
+    fn released_buffers_are_reused() {
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let sum = class.methods.iter().find(|method| method.name == "sum").unwrap();
+        let mut pool = FramePool::new();
+
+        let mut frame = pool.acquire(sum, vec![LocalVariable::Integer(3)]);
+        frame.push_stack(StackValue::Integer(1));
+        let (locals, stack) = (frame.local_variables.as_ptr(), frame.stack.as_ptr());
+        pool.release(frame);
+
+        let frame = pool.acquire(sum, vec![LocalVariable::Integer(4)]);
+        assert_eq!(frame.local_variables.as_ptr(), locals);
+        assert_eq!(frame.stack.as_ptr(), stack);
+        assert!(frame.stack.is_empty());
+        assert_eq!(frame.local_variables.len(), usize::from(sum.get_code().unwrap().max_locals));
+        match frame.local_variables[0] {
+            LocalVariable::Integer(4) => (),
+            ref other => panic!("unexpected argument {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use java::runtime::{LocalVariable, ObjectRef, Runtime, StackValue};
+use super::StackFrame;
+
+/// a value in a local variable or on the operand stack of a `FrameView`.
+///
+/// booleans, bytes, chars and shorts are ints, like the interpreter keeps them. new kinds of
+/// values may be added, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FrameValue {
+    Int(i32),
+    Long(i64),
+    Null,
+    Reference(ObjectRef),
+    /// a local variable which was not assigned yet or holds the second half of a long.
+    Unset,
+}
+
+impl<'l> From<&'l LocalVariable> for FrameValue {
+    fn from(local: &'l LocalVariable) -> FrameValue {
+        match *local {
+            LocalVariable::Integer(value) => FrameValue::Int(value as i32),
+            LocalVariable::Long(value) => FrameValue::Long(value),
+            LocalVariable::Null => FrameValue::Null,
+            LocalVariable::Reference(reference) => FrameValue::Reference(reference),
+            LocalVariable::None | LocalVariable::Top => FrameValue::Unset,
+        }
+    }
+}
+
+impl<'v> From<&'v StackValue> for FrameValue {
+    fn from(value: &'v StackValue) -> FrameValue {
+        match *value {
+            StackValue::Integer(value) => FrameValue::Int(value as i32),
+            StackValue::Long(value) => FrameValue::Long(value),
+            StackValue::Null => FrameValue::Null,
+            StackValue::Reference(reference) => FrameValue::Reference(reference),
+            StackValue::None => FrameValue::Unset,
+        }
+    }
+}
+
+/// a java frame as debug handlers and hooks see it, read-only and independent of how the
+/// interpreter keeps its values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameView {
+    pub(super) class_name: String,
+    pub(super) method_name: String,
+    pub(super) descriptor: String,
+    pub(super) pc: usize,
+    pub(super) line: Option<usize>,
+    pub(super) locals: Vec<LocalVariable>,
+    pub(super) stack: Vec<StackValue>,
+}
+
+impl FrameView {
+    /// the binary name of the class declaring the method, like `java/lang/String`.
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    pub fn method_name(&self) -> &str {
+        &self.method_name
+    }
+
+    pub fn descriptor(&self) -> &str {
+        &self.descriptor
+    }
+
+    /// the bytecode offset of the instruction the frame executes.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// the source line of the instruction, if the class has a `LineNumberTable`.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// the number of local variable slots, where a long takes 2.
+    pub fn local_count(&self) -> usize {
+        self.locals.len()
+    }
+
+    /// the local variable in `slot`, `None` past the last one.
+    pub fn local(&self, slot: usize) -> Option<FrameValue> {
+        self.locals.get(slot).map(FrameValue::from)
+    }
+
+    /// the local variables by slot.
+    pub fn locals(&self) -> impl Iterator<Item=FrameValue> + '_ {
+        self.locals.iter().map(FrameValue::from)
+    }
+
+    /// the number of values on the operand stack, where a long is a single value.
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// the value `depth` entries below the top of the operand stack, 0 being the top.
+    pub fn operand(&self, depth: usize) -> Option<FrameValue> {
+        self.stack.len().checked_sub(depth + 1).map(|index| FrameValue::from(&self.stack[index]))
+    }
+
+    /// the operand stack, bottom first.
+    pub fn stack(&self) -> impl Iterator<Item=FrameValue> + '_ {
+        self.stack.iter().map(FrameValue::from)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// the java stack with the values of all frames, innermost first. `current` holds the values
+    /// of the executing method, the callers saved theirs.
+    pub(super) fn frame_views(&self, current: &StackFrame) -> Vec<FrameView> {
+        let depth = self.thread.frames.len();
+        self.thread.frames.iter().enumerate().rev().map(|(index, frame)| {
+            let line = self.classes.class(frame.class).methods.iter()
+                .find(|method| method.name == frame.method_name && method.descriptor == frame.descriptor)
+                .and_then(|method| method.get_code())
+                .and_then(|code| code.line_number_at(frame.pc));
+            let values = if index + 1 == depth { Some(current) } else { frame.saved.as_ref() };
+
+            FrameView {
+                class_name: String::from(self.classes.class(frame.class).get_class_name()),
+                method_name: String::from(&*frame.method_name),
+                descriptor: String::from(&*frame.descriptor),
+                pc: frame.pc,
+                line,
+                locals: values.map(|values| values.local_variables.clone()).unwrap_or_default(),
+                stack: values.map(|values| values.stack.clone()).unwrap_or_default(),
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values_are_typed_without_the_slots_of_the_interpreter() {
+        let frame = FrameView {
+            class_name: String::from("Tiny"),
+            method_name: String::from("add"),
+            descriptor: String::from("(JI)J"),
+            pc: 3,
+            line: Some(2),
+            locals: vec![LocalVariable::Long(1 << 40), LocalVariable::Top, LocalVariable::Integer(-2), LocalVariable::None],
+            stack: vec![StackValue::Null, StackValue::Reference(ObjectRef(7, 0)), StackValue::Integer(5)],
+        };
+
+        assert_eq!(frame.locals().collect::<Vec<_>>(), vec![FrameValue::Long(1 << 40), FrameValue::Unset, FrameValue::Int(-2), FrameValue::Unset]);
+        assert_eq!((frame.local(2), frame.local(4), frame.local_count()), (Some(FrameValue::Int(-2)), None, 4));
+        assert_eq!((frame.operand(0), frame.operand(1), frame.operand(3)), (Some(FrameValue::Int(5)), Some(FrameValue::Reference(ObjectRef(7, 0))), None));
+        assert_eq!(frame.stack().next(), Some(FrameValue::Null));
+        assert_eq!(frame.stack_depth(), 3);
+    }
+}
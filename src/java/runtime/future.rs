@@ -0,0 +1,116 @@
+use java::runtime::{CancellationHandle, LocalVariable, Runtime, RuntimeError, StackValue};
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// the value an `InvokeFuture` resolves to.
+/// the runtime is handed back to the caller, so it can be reused for further invocations.
+pub type InvokeOutput = (Runtime<'static>, Result<Option<StackValue>, RuntimeError>);
+
+#[derive(Default)]
+struct SharedState {
+    output: Option<InvokeOutput>,
+    waker: Option<Waker>,
+}
+
+/// a future for a static method running on one of the threads of the invocation pool.
+///
+/// the interpreter itself is synchronous, so it runs on a thread of the pool and never blocks
+/// the executor polling this future. the invocation does not yield at safepoints, it keeps its
+/// thread of the pool until it returns.
+/// dropping the future before it completes cancels the execution: the interpreter checks for it
+/// at every safepoint, and an invocation still waiting for a thread does not start at all.
+/// an invocation which panics completes the future with `RuntimeError::Panicked`.
+pub struct InvokeFuture {
+    state: Arc<Mutex<SharedState>>,
+    cancellation: CancellationHandle,
+    done: bool,
+}
+
+impl Future for InvokeFuture {
+    type Output = InvokeOutput;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<InvokeOutput> {
+        let output = {
+            let mut state = self.state.lock().unwrap();
+            match state.output.take() {
+                Some(output) => output,
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        self.done = true;
+        Poll::Ready(output)
+    }
+}
+
+impl Drop for InvokeFuture {
+    fn drop(&mut self) {
+        if !self.done {
+            self.cancellation.cancel();
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// the queue of the threads running the invocations of `invoke_static_async`. there is one
+/// thread per cpu, further invocations wait until one of them is free.
+fn pool() -> &'static Mutex<Sender<Job>> {
+    static POOL: OnceLock<Mutex<Sender<Job>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        for index in 0..threads {
+            let receiver = receiver.clone();
+            thread::Builder::new().name(format!("rjvm-invoke-{}", index)).spawn(move || loop {
+                let job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return
+                };
+                // the jobs complete their futures when the invocation panics, the thread must survive
+                // a panic of anything else too
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }).expect("cannot start the invocation pool");
+        }
+        Mutex::new(sender)
+    })
+}
+
+impl Runtime<'static> {
+    /// runs a static method on the invocation pool and returns a future for its result.
+    /// see `Runtime::invoke_static` for the meaning of the parameters.
+    pub fn invoke_static_async(mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> InvokeFuture {
+        let state = Arc::new(Mutex::new(SharedState::default()));
+        let cancellation = self.cancellation_handle();
+
+        let job_state = state.clone();
+        let (class_name, method_name, descriptor) = (String::from(class_name), String::from(method_name), String::from(descriptor));
+        let job: Job = Box::new(move || {
+            let result = match self.cancellation.is_cancelled() {
+                true => Err(RuntimeError::Cancelled),
+                false => panic::catch_unwind(AssertUnwindSafe(|| self.invoke_static(&class_name, &method_name, &descriptor, arguments)))
+                    .unwrap_or_else(|_| Err(RuntimeError::Panicked { method: format!("{}.{}{}", class_name, method_name, descriptor) }))
+            };
+
+            let mut state = job_state.lock().unwrap();
+            state.output = Some((self, result));
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        // the threads of the pool never stop, so they always receive
+        let _ = pool().lock().unwrap().send(job);
+
+        InvokeFuture { state, cancellation, done: false }
+    }
+}
@@ -0,0 +1,195 @@
+use java::runtime::{Collection, Deduplication, ObjectRef, Runtime, StackValue};
+use std::fmt;
+use std::time::{Duration, Instant};
+use super::StackFrame;
+
+/// why a collection happened.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GcReason {
+    /// an allocation did not fit into the maximum heap size.
+    AllocationFailure,
+    /// requested through `Runtime::gc`.
+    Explicit,
+}
+
+impl fmt::Display for GcReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GcReason::AllocationFailure => write!(f, "Allocation Failure"),
+            GcReason::Explicit => write!(f, "System.gc()"),
+        }
+    }
+}
+
+/// one garbage collection.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GcEvent {
+    pub reason: GcReason,
+    pub duration: Duration,
+    pub used_before: usize,
+    pub used_after: usize,
+    /// the maximum heap size, if there is one.
+    pub capacity: Option<usize>,
+    pub promoted: usize,
+    pub freed_objects: usize,
+    /// the objects freed in the large object space, included in `freed_objects`.
+    pub freed_large_objects: usize,
+    /// the classes unloaded because their class loader was no longer used.
+    pub unloaded_classes: usize,
+    /// the strings which were made to share the characters of an equal one.
+    pub deduplicated_strings: usize,
+    /// the bytes of characters deduplication freed, included in `used_after`.
+    pub deduplicated_bytes: usize,
+}
+
+/// formats the event like `-verbose:gc` of the hotspot vm, with sizes in bytes.
+impl fmt::Display for GcEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[GC ({}) {}B->{}B", self.reason, self.used_before, self.used_after)?;
+        if let Some(capacity) = self.capacity {
+            write!(f, "({}B)", capacity)?;
+        }
+        write!(f, ", promoted {}B, {:.7} secs]", self.promoted, self.duration.as_secs_f64())
+    }
+}
+
+/// totals over all collections of a `Runtime`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub total_pause: Duration,
+    pub max_pause: Duration,
+    pub freed_bytes: usize,
+    pub freed_objects: usize,
+    pub freed_large_objects: usize,
+    pub promoted_bytes: usize,
+    pub unloaded_classes: usize,
+    pub deduplicated_strings: usize,
+    pub deduplicated_bytes: usize,
+    pub last: Option<GcEvent>,
+}
+
+impl GcStats {
+    fn record(&mut self, event: GcEvent) {
+        self.collections += 1;
+        self.total_pause += event.duration;
+        self.max_pause = self.max_pause.max(event.duration);
+        self.freed_bytes += event.used_before - event.used_after;
+        self.freed_objects += event.freed_objects;
+        self.freed_large_objects += event.freed_large_objects;
+        self.promoted_bytes += event.promoted;
+        self.unloaded_classes += event.unloaded_classes;
+        self.deduplicated_strings += event.deduplicated_strings;
+        self.deduplicated_bytes += event.deduplicated_bytes;
+        self.last = Some(event);
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// collects all unreachable objects, like `System.gc()`.
+    pub fn gc(&mut self) -> GcEvent {
+        self.collect_garbage(GcReason::Explicit, None)
+    }
+
+    pub fn gc_stats(&self) -> &GcStats {
+        &self.gc_stats
+    }
+
+    /// runs a collection. the roots are the interned strings, the static fields, the frames saved by
+    /// the suspended callers in `frames`, the objects allocated in frames and the frame of the
+    /// currently executing method. classes of
+    /// released class loaders which turn out to be unused are unloaded. the surviving strings are
+    /// deduplicated if that is enabled.
+    pub(super) fn collect_garbage(&mut self, reason: GcReason, current: Option<&StackFrame>) -> GcEvent {
+        let start = Instant::now();
+        let callers = match current {
+            Some(_) => self.thread.frames.len().saturating_sub(1),
+            None => self.thread.frames.len()
+        };
+        let mut roots: Vec<ObjectRef> = self.thread.frames[..callers].iter()
+            .filter_map(|frame| frame.saved.as_ref())
+            .flat_map(|saved| saved.references())
+            .collect();
+        if let Some(frame) = current {
+            roots.extend(frame.references());
+        }
+        // objects allocated in frames are freed when their frame returns, not by a collection
+        roots.extend(self.thread.frames.iter().flat_map(|frame| frame.local_objects.iter().map(|&(_, object)| object)));
+        // the classes of released loaders are only roots while the loader is still in use
+        let collectable = self.loaders.collectable();
+        roots.extend(self.statics.iter()
+            .filter(|((class_name, _), _)| self.is_permanent_class(class_name, &collectable))
+            .filter_map(|(_, value)| match value {
+                StackValue::Reference(reference) => Some(*reference),
+                _ => None
+            }));
+        roots.extend(self.class_objects.iter()
+            .filter(|(class_name, _)| self.is_permanent_class(class_name, &collectable))
+            .map(|(_, class)| *class));
+
+        roots.extend(self.cleaners.roots());
+        roots.extend(self.thread.references());
+        roots.extend(self.default_uncaught_exception_handler);
+
+        self.heap.mark_roots(&roots);
+        let cleanups = self.cleaners.enqueue_unreachable(&mut self.heap);
+        let unused = self.mark_class_loaders();
+        let unloaded_classes = unused.into_iter().map(|loader| self.unload_classes(loader)).sum();
+        let Collection { used_before, freed_objects, promoted, freed_large_objects, .. } = self.heap.sweep();
+        let deduplication = if self.string_deduplication { self.heap.deduplicate_strings() } else { Deduplication::default() };
+        let used_after = self.heap.used();
+        self.release_native_memory();
+        let event = GcEvent {
+            reason, duration: start.elapsed(), used_before, used_after, capacity: self.max_heap, promoted, freed_objects, freed_large_objects,
+            unloaded_classes, deduplicated_strings: deduplication.deduplicated, deduplicated_bytes: deduplication.freed_bytes,
+        };
+
+        debug!(reason = %reason, used_before, used_after, promoted, freed_objects, deduplicated = deduplication.deduplicated, cleanups, duration = ?event.duration, "garbage collection");
+        if self.verbose_gc {
+            self.print_verbose(&event);
+        }
+        self.gc_stats.record(event);
+
+        event
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use captured::Captured;
+    use java::class_file::read_class_file;
+    use java::runtime::ObjectData;
+    use std::sync::Arc;
+    use super::*;
+
+    #[test]
+    fn verbose_collections_are_printed_to_the_stdout_of_the_runtime() {
+        let out = Captured::default();
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let mut rt = Runtime::builder().verbose_gc(true).stdout(out.output()).build(class);
+        let event = rt.gc();
+        assert_eq!(out.text(), format!("{}\n", event));
+        assert!(out.text().starts_with("[GC (System.gc()) "));
+    }
+
+    #[test]
+    fn collections_deduplicate_surviving_strings() {
+        let class = read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1;
+        let mut rt = Runtime::builder().string_deduplication(true).build(class);
+        for (index, value) in ["copy", "copy", "copy", "garbage"].iter().enumerate() {
+            let string = rt.allocate("java/lang/String", ObjectData::String(Arc::from(&**value)));
+            if *value == "copy" {
+                rt.statics.insert((String::from("Loop"), format!("s{}", index)), StackValue::Reference(string));
+            }
+        }
+
+        let event = rt.gc();
+        assert_eq!(event.freed_objects, 1);
+        assert_eq!((event.deduplicated_strings, event.deduplicated_bytes), (2, 8));
+        assert_eq!(event.used_after, rt.heap.used());
+        // the shared characters are only counted once by the next collection
+        let event = rt.gc();
+        assert_eq!((event.deduplicated_strings, event.used_before, event.used_after), (0, rt.heap.used(), rt.heap.used()));
+        assert_eq!(rt.gc_stats().deduplicated_strings, 2);
+    }
+}
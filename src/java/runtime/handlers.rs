@@ -0,0 +1,124 @@
+use java::class_file::{ClassFile, Method};
+use java::instructions::Instruction;
+use java::runtime::RuntimeError;
+use std::collections::BTreeSet;
+
+/// an entry of the exception table of a method, with the class it catches looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionHandler {
+    pub start_pc: usize,
+    /// exclusive, like in the class file.
+    pub end_pc: usize,
+    pub handler_pc: usize,
+    /// the index of the first instruction of the handler.
+    pub handler: usize,
+    /// `None` for handlers which catch everything, like the ones of `finally`.
+    pub catch_class: Option<String>,
+}
+
+impl ExceptionHandler {
+    pub fn catches_all(&self) -> bool {
+        self.catch_class.is_none()
+    }
+}
+
+/// the exception table of a method, prepared when it is decoded: the pcs are split into ranges
+/// covered by the same handlers, so the handlers of a pc are found with a binary search instead of
+/// going through the whole table, and the catch classes are looked up in the constant pool once.
+#[derive(Debug, Clone, Default)]
+pub struct HandlerTable {
+    handlers: Vec<ExceptionHandler>,
+    /// the first pc of each range with the handlers covering it, in the order of the table.
+    ranges: Vec<(usize, Vec<usize>)>,
+}
+
+impl HandlerTable {
+    /// prepares the exception table of `method`, whose decoded instructions are `instructions`.
+    /// entries catching a class the constant pool does not name never match, so they are left out.
+    pub fn build(class: &ClassFile, method: &Method, instructions: &[(usize, Instruction)]) -> Result<HandlerTable, RuntimeError> {
+        let code = match method.get_code() {
+            Some(code) => code,
+            None => return Ok(HandlerTable::default())
+        };
+
+        let mut handlers = Vec::new();
+        for &(start_pc, end_pc, handler_pc, catch_type) in code.exception_table.iter() {
+            let catch_class = match catch_type {
+                0 => None,
+                index => match class.get_class_name_at(index) {
+                    Some(catch_class) => Some(String::from(catch_class)),
+                    None => continue
+                }
+            };
+            let handler = instructions.binary_search_by_key(&usize::from(handler_pc), |&(pc, _)| pc)
+                .map_err(|_| RuntimeError::InvalidJumpTarget { pc: usize::from(handler_pc) })?;
+            handlers.push(ExceptionHandler {
+                start_pc: usize::from(start_pc),
+                end_pc: usize::from(end_pc),
+                handler_pc: usize::from(handler_pc),
+                handler,
+                catch_class,
+            });
+        }
+
+        let bounds = handlers.iter().flat_map(|handler| vec![handler.start_pc, handler.end_pc]).collect::<BTreeSet<_>>();
+        let ranges = bounds.into_iter()
+            .map(|start| (start, (0..handlers.len()).filter(|&index| handlers[index].start_pc <= start && start < handlers[index].end_pc).collect()))
+            .collect();
+        Ok(HandlerTable { handlers, ranges })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// all handlers, in the order of the exception table.
+    pub fn handlers(&self) -> &[ExceptionHandler] {
+        &self.handlers
+    }
+
+    /// the handlers covering `pc`, in the order they are tried.
+    pub fn covering(&self, pc: usize) -> impl Iterator<Item=&ExceptionHandler> {
+        let range = match self.ranges.binary_search_by_key(&pc, |&(start, _)| start) {
+            Ok(range) => Some(range),
+            Err(0) => None,
+            Err(next) => Some(next - 1)
+        };
+        range.into_iter()
+            .flat_map(move |range| self.ranges[range].1.iter())
+            .map(move |&index| &self.handlers[index])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{DecodedMethod, LocalVariable, Runtime, StackValue};
+
+    #[test]
+    fn handlers_are_found_by_pc() {
+        let class = read_class_file(include_bytes!("../../../sample/Handlers.class")).unwrap().1;
+        let nested = class.methods.iter().find(|method| method.name == "nested").unwrap();
+        let table = DecodedMethod::decode(&class, nested).unwrap().handlers;
+        let covering = |pc: usize| table.covering(pc).map(|handler| (handler.handler_pc, handler.catch_class.clone())).collect::<Vec<_>>();
+
+        let arithmetic = (10, Some(String::from("java/lang/ArithmeticException")));
+        let index = (29, Some(String::from("java/lang/IndexOutOfBoundsException")));
+        // the division, the store in the handler, the outer handler and the finally block
+        assert_eq!(covering(5), vec![arithmetic, index.clone(), (39, None)]);
+        assert_eq!(covering(22), vec![index, (39, None)]);
+        assert_eq!(covering(30), vec![(39, None)]);
+        assert_eq!(covering(40), vec![(39, None)]);
+        assert_eq!(covering(0), vec![]);
+        assert_eq!(covering(47), vec![]);
+        assert_eq!(table.handlers().len(), 5);
+    }
+
+    #[test]
+    fn nested_handlers_run_in_order() {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Handlers.class")).unwrap().1);
+        for &(n, expected) in [(5, 102), (0, 98), (-3, 97)].iter() {
+            assert_eq!(rt.invoke_static("Handlers", "nested", "(I)I", vec![LocalVariable::Integer(n)]).unwrap(), Some(StackValue::Integer(expected)));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! the header word every object on the heap starts with, and the layouts behind it.
+//!
+//! the header packs everything the runtime keeps about an object apart from its contents into a
+//! single 64 bit word, from the least significant bit:
+//!
+//! | bits  | contents                                                                  |
+//! |-------|---------------------------------------------------------------------------|
+//! | 0-1   | lock state: unlocked, thin or inflated                                    |
+//! | 2     | mark bit of the collector, only set during a collection                   |
+//! | 3     | tenured: the object survived a collection and is in the old generation    |
+//! | 4     | hashed: an identity hash code was assigned                                |
+//! | 5-35  | the identity hash code, 31 bits like the hotspot vm assigns               |
+//! | 36-63 | the `ClassId` plus one, 0 for the builtin classes and arrays              |
+//!
+//! the class id is recorded for objects of loaded classes, but the runtime still finds the class
+//! of an object by its `class_name`. the lock bits stay unlocked since monitors are not
+//! implemented.
+//!
+//! the words after the header are not laid out in memory yet: objects keep their fields in a
+//! `Fields` table and their contents in `ObjectData`. the size estimates assume the layout a
+//! compact heap would use, which depends on the kind of object:
+//!
+//! - an instance has one slot per field in the order of the `FieldLayout` of its class, the
+//!   fields of the superclasses first.
+//! - an array has a word holding its length followed by one slot per element.
+//! - a string has a slot referring to its characters, which deduplication shares between strings
+//!   with the same value, and the characters themselves.
+//! - a throwable has a slot referring to its stack trace.
+//!
+//! slots are 8 bytes wide, so sizes estimated with `Object::size` are what this layout would take,
+//! not what the rust values of an object occupy.
+
+use java::runtime::ClassId;
+
+const LOCK_BITS: u64 = 0b11;
+const MARKED: u64 = 1 << 2;
+const TENURED: u64 = 1 << 3;
+const HASHED: u64 = 1 << 4;
+const HASH_SHIFT: u32 = 5;
+const HASH_MASK: u64 = 0x7fff_ffff;
+const CLASS_SHIFT: u32 = 36;
+/// the largest `ClassId` index a header can hold.
+pub const MAX_CLASS_INDEX: usize = (1 << (64 - CLASS_SHIFT)) - 2;
+
+/// who holds the monitor of an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockState {
+    Unlocked,
+    /// held by a single thread without contention.
+    Thin,
+    /// contended, waited on or held recursively, with a monitor of its own.
+    Inflated,
+}
+
+/// the header word of an object, see the module documentation for its layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ObjectHeader(u64);
+
+impl ObjectHeader {
+    /// the header of a new object of `class`, `None` for builtin classes and arrays.
+    pub fn new(class: Option<ClassId>) -> ObjectHeader {
+        let mut header = ObjectHeader(0);
+        header.set_class(class);
+        header
+    }
+
+    /// the header as it would be stored in memory.
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    pub fn class(self) -> Option<ClassId> {
+        match self.0 >> CLASS_SHIFT {
+            0 => None,
+            id => Some(ClassId::from_index(id as usize - 1))
+        }
+    }
+
+    /// classes with an index above `MAX_CLASS_INDEX` are left out like builtin classes.
+    pub fn set_class(&mut self, class: Option<ClassId>) {
+        let id = match class {
+            Some(class) if class.index() <= MAX_CLASS_INDEX => class.index() as u64 + 1,
+            _ => 0
+        };
+        self.0 = (self.0 & ((1 << CLASS_SHIFT) - 1)) | (id << CLASS_SHIFT);
+    }
+
+    /// the identity hash code, once one was assigned.
+    pub fn identity_hash(self) -> Option<i32> {
+        if self.0 & HASHED == 0 {
+            return None;
+        }
+        Some(((self.0 >> HASH_SHIFT) & HASH_MASK) as i32)
+    }
+
+    /// assigns the identity hash code. only the low 31 bits are kept.
+    pub fn set_identity_hash(&mut self, hash: i32) {
+        self.0 = (self.0 & !(HASH_MASK << HASH_SHIFT)) | HASHED | ((hash as u64 & HASH_MASK) << HASH_SHIFT);
+    }
+
+    pub fn is_marked(self) -> bool {
+        self.0 & MARKED != 0
+    }
+
+    pub fn set_marked(&mut self, marked: bool) {
+        self.set_flag(MARKED, marked);
+    }
+
+    pub fn is_tenured(self) -> bool {
+        self.0 & TENURED != 0
+    }
+
+    pub fn set_tenured(&mut self, tenured: bool) {
+        self.set_flag(TENURED, tenured);
+    }
+
+    pub fn lock_state(self) -> LockState {
+        match self.0 & LOCK_BITS {
+            0 => LockState::Unlocked,
+            1 => LockState::Thin,
+            _ => LockState::Inflated
+        }
+    }
+
+    pub fn set_lock_state(&mut self, state: LockState) {
+        let bits = match state {
+            LockState::Unlocked => 0,
+            LockState::Thin => 1,
+            LockState::Inflated => 2,
+        };
+        self.0 = (self.0 & !LOCK_BITS) | bits;
+    }
+
+    fn set_flag(&mut self, flag: u64, set: bool) {
+        if set {
+            self.0 |= flag;
+        } else {
+            self.0 &= !flag;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fields_do_not_overlap() {
+        let class = ClassId::from_index(MAX_CLASS_INDEX);
+        let mut header = ObjectHeader::new(Some(class));
+        header.set_identity_hash(-1);
+        header.set_lock_state(LockState::Inflated);
+        header.set_tenured(true);
+        header.set_marked(true);
+
+        assert_eq!(header.class(), Some(class));
+        assert_eq!(header.identity_hash(), Some(0x7fff_ffff));
+        assert_eq!(header.lock_state(), LockState::Inflated);
+        assert!(header.is_tenured() && header.is_marked());
+
+        header.set_marked(false);
+        header.set_class(None);
+        header.set_lock_state(LockState::Thin);
+        assert_eq!((header.class(), header.identity_hash(), header.lock_state()), (None, Some(0x7fff_ffff), LockState::Thin));
+        assert!(header.is_tenured() && !header.is_marked());
+        assert_eq!(ObjectHeader::new(None).identity_hash(), None);
+    }
+}
@@ -0,0 +1,521 @@
+use java::runtime::{Fields, ObjectHeader, StackTraceElement, StackValue};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// a reference to an object on the `Heap`: the index of its slot and the generation of the slot.
+/// the generation changes whenever the object in the slot is freed, so a reference to a collected
+/// object never reaches the object a later allocation puts into its slot.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ObjectRef(pub usize, pub u32);
+
+impl ObjectRef {
+    /// the reference as one number for hosts and debuggers, with the generation in the upper half.
+    pub fn handle(self) -> u64 {
+        (u64::from(self.1) << 32) | self.0 as u64
+    }
+
+    /// the reference a `handle` was made from.
+    pub fn from_handle(handle: u64) -> ObjectRef {
+        ObjectRef((handle & u64::from(u32::MAX)) as usize, (handle >> 32) as u32)
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjectData {
+    /// a plain instance, all state lives in the fields.
+    Instance,
+    /// a `java.lang.String`. deduplication lets strings with the same value share the characters.
+    String(Arc<str>),
+    /// an array with its elements.
+    Array(Vec<StackValue>),
+    /// a `java.lang.Throwable` with the stack trace filled in when it was created.
+    Throwable(Vec<StackTraceElement>),
+}
+
+impl ObjectData {
+    /// the estimated number of bytes the data adds to the object.
+    pub fn size(&self) -> usize {
+        match self {
+            ObjectData::Instance => 0,
+            ObjectData::String(value) => SLOT_SIZE + value.len(),
+            ObjectData::Array(elements) => SLOT_SIZE + elements.len() * SLOT_SIZE,
+            ObjectData::Throwable(trace) => SLOT_SIZE + trace.len() * SLOT_SIZE,
+        }
+    }
+}
+
+/// the sizes of the parts of an object, see `ObjectHeader` for the layout.
+pub const HEADER_SIZE: usize = 8;
+const SLOT_SIZE: usize = 8;
+
+/// objects of at least this many bytes go to the large object space unless configured otherwise.
+pub const DEFAULT_LARGE_OBJECT_THRESHOLD: usize = 1 << 20;
+
+/// the estimated size of an array of `length` elements, without creating it.
+pub fn array_size(length: usize) -> usize {
+    HEADER_SIZE + SLOT_SIZE + length.saturating_mul(SLOT_SIZE)
+}
+
+#[derive(Debug)]
+pub struct Object {
+    pub header: ObjectHeader,
+    pub class_name: String,
+    pub fields: Fields,
+    pub data: ObjectData,
+}
+
+impl Object {
+    /// the estimated number of bytes this object would occupy with the layout described for
+    /// `ObjectHeader`, which is not how it is stored yet.
+    pub fn size(&self) -> usize {
+        HEADER_SIZE + self.fields.len() * SLOT_SIZE + self.data.size()
+    }
+}
+
+/// the size of an object, without the characters of a string which an object in `counted` shares.
+fn footprint(object: &Object, counted: &mut HashSet<*const u8>) -> usize {
+    match object.data {
+        ObjectData::String(ref value) if !counted.insert(value.as_ptr()) => object.size() - value.len(),
+        _ => object.size()
+    }
+}
+
+/// what string deduplication did to the heap.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Deduplication {
+    /// the live strings looked at.
+    pub inspected: usize,
+    /// the strings which now share the characters of an equal string.
+    pub deduplicated: usize,
+    /// the bytes of characters no string uses any more.
+    pub freed_bytes: usize,
+}
+
+/// what a collection did to the heap.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Collection {
+    pub used_before: usize,
+    pub used_after: usize,
+    pub freed_objects: usize,
+    /// bytes of young objects which survived their first collection and moved to the old generation.
+    pub promoted: usize,
+    /// the objects freed in the large object space, included in `freed_objects`.
+    pub freed_large_objects: usize,
+}
+
+/// the objects of a `Heap` which are too big to move around, like the humongous objects of G1.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct LargeObjectSpace {
+    pub objects: usize,
+    /// bytes, included in `Heap::used`.
+    pub used: usize,
+}
+
+/// all objects created by a `Runtime`.
+///
+/// references stay valid as long as their object is alive. the slots of collected and freed
+/// objects are reused by later allocations with a new generation, a reference to a collected
+/// object finds no object.
+///
+/// objects of at least `large_object_threshold` bytes, big arrays in practice, are allocated in a
+/// large object space with accounting of its own. they start out in the old generation, so a
+/// collection never promotes them, and are only ever freed.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Option<Object>>,
+    /// the generation of each slot in `objects`, counting how often its object was freed.
+    generations: Vec<u32>,
+    /// the indices of the empty slots in `objects`, the next allocation takes the last one.
+    free: Vec<usize>,
+    strings: HashMap<String, ObjectRef>,
+    /// the characters strings are deduplicated to, kept from one deduplication to the next.
+    deduplicated: HashSet<Arc<str>>,
+    used: usize,
+    large_object_threshold: usize,
+    /// the objects in the large object space, by index.
+    large: HashSet<usize>,
+    large_used: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Heap {
+        Heap::new()
+    }
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap::with_large_object_threshold(DEFAULT_LARGE_OBJECT_THRESHOLD)
+    }
+
+    /// a heap which puts objects of at least `threshold` bytes into the large object space.
+    pub fn with_large_object_threshold(threshold: usize) -> Heap {
+        Heap {
+            objects: Vec::new(),
+            generations: Vec::new(),
+            free: Vec::new(),
+            strings: HashMap::new(),
+            deduplicated: HashSet::new(),
+            used: 0,
+            large_object_threshold: threshold,
+            large: HashSet::new(),
+            large_used: 0,
+        }
+    }
+
+    pub fn allocate(&mut self, class_name: &str, data: ObjectData) -> ObjectRef {
+        let size = HEADER_SIZE + data.size();
+        let mut header = ObjectHeader::default();
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.objects.push(None);
+                self.generations.push(0);
+                self.objects.len() - 1
+            }
+        };
+        if size >= self.large_object_threshold {
+            header.set_tenured(true);
+            self.large.insert(index);
+            self.large_used += size;
+        }
+        self.used += size;
+        self.objects[index] = Some(Object { header, class_name: String::from(class_name), fields: Fields::new(), data });
+        ObjectRef(index, self.generations[index])
+    }
+
+    pub fn large_object_threshold(&self) -> usize {
+        self.large_object_threshold
+    }
+
+    /// true if the object lives in the large object space.
+    pub fn is_large(&self, reference: ObjectRef) -> bool {
+        self.get(reference).is_some() && self.large.contains(&reference.0)
+    }
+
+    pub fn large_object_space(&self) -> LargeObjectSpace {
+        LargeObjectSpace { objects: self.large.len(), used: self.large_used }
+    }
+
+    /// returns the canonical string object for `value`, like `String.intern`.
+    /// string constants are always interned.
+    pub fn intern(&mut self, value: &str) -> ObjectRef {
+        if let Some(reference) = self.strings.get(value) {
+            return *reference;
+        }
+
+        let reference = self.allocate("java/lang/String", ObjectData::String(Arc::from(value)));
+        self.strings.insert(String::from(value), reference);
+        reference
+    }
+
+    /// the interned string object for `value`, if there is one.
+    pub fn interned(&self, value: &str) -> Option<ObjectRef> {
+        self.strings.get(value).cloned()
+    }
+
+    /// `None` if there never was an object for `reference` or it was collected.
+    pub fn get(&self, reference: ObjectRef) -> Option<&Object> {
+        if self.generations.get(reference.0) != Some(&reference.1) {
+            return None;
+        }
+        self.objects.get(reference.0).and_then(Option::as_ref)
+    }
+
+    pub fn get_mut(&mut self, reference: ObjectRef) -> Option<&mut Object> {
+        if self.generations.get(reference.0) != Some(&reference.1) {
+            return None;
+        }
+        self.objects.get_mut(reference.0).and_then(Option::as_mut)
+    }
+
+    /// the value of a string object, `None` if `reference` is no string.
+    pub fn string_value(&self, reference: ObjectRef) -> Option<&str> {
+        match self.get(reference).map(|object| &object.data) {
+            Some(ObjectData::String(value)) => Some(value),
+            _ => None
+        }
+    }
+
+    /// frees an object nothing refers to any more without a collection. its slot is reused by a
+    /// later allocation.
+    pub fn free(&mut self, reference: ObjectRef) {
+        if self.get(reference).is_none() {
+            return;
+        }
+        if let Some(object) = self.objects[reference.0].take() {
+            self.generations[reference.0] = self.generations[reference.0].wrapping_add(1);
+            self.free.push(reference.0);
+            self.used = self.used.saturating_sub(object.size());
+            if self.large.remove(&reference.0) {
+                self.large_used -= object.size();
+            }
+        }
+    }
+
+    /// the number of slots, of live objects and of empty ones the next allocations reuse.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// the reference to the live object in slot `index`, if there is one.
+    pub fn reference_at(&self, index: usize) -> Option<ObjectRef> {
+        match self.objects.get(index) {
+            Some(Some(_)) => Some(ObjectRef(index, self.generations[index])),
+            _ => None
+        }
+    }
+
+    /// all live objects with their references, in allocation order.
+    pub fn objects(&self) -> impl Iterator<Item=(ObjectRef, &Object)> {
+        self.objects.iter().enumerate().filter_map(move |(index, object)| object.as_ref().map(|object| (ObjectRef(index, self.generations[index]), object)))
+    }
+
+    /// true if the object survived a collection and belongs to the old generation.
+    pub fn is_tenured(&self, reference: ObjectRef) -> bool {
+        self.get(reference).is_some_and(|object| object.header.is_tenured())
+    }
+
+    /// the interned strings and their objects.
+    pub fn interned_strings(&self) -> impl Iterator<Item=(&str, ObjectRef)> {
+        self.strings.iter().map(|(value, reference)| (value.as_str(), *reference))
+    }
+
+    /// a heap with the objects of a snapshot, indexed by reference and `None` for collected ones.
+    /// every slot starts at generation 0 again.
+    pub(super) fn restore(objects: Vec<Option<Object>>, strings: HashMap<String, ObjectRef>, large_object_threshold: usize) -> Heap {
+        let mut heap = Heap::with_large_object_threshold(large_object_threshold);
+        let mut counted = HashSet::new();
+        heap.used = objects.iter().flatten().map(|object| footprint(object, &mut counted)).sum();
+        for (index, object) in objects.iter().enumerate() {
+            match object {
+                Some(object) if object.size() >= large_object_threshold => {
+                    heap.large.insert(index);
+                    heap.large_used += object.size();
+                }
+                _ => ()
+            }
+        }
+        heap.free = objects.iter().enumerate().rev().filter(|(_, object)| object.is_none()).map(|(index, _)| index).collect();
+        heap.generations = vec![0; objects.len()];
+        heap.objects = objects;
+        heap.strings = strings;
+        heap
+    }
+
+    /// the estimated number of bytes allocated. exact after a collection,
+    /// in between new objects are counted with the size they had when they were created.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// frees every object which is not reachable from `roots` or the interned strings (mark and sweep).
+    pub fn collect(&mut self, roots: &[ObjectRef]) -> Collection {
+        self.mark_roots(roots);
+        self.sweep()
+    }
+
+    /// marks the objects reachable from `roots` or the interned strings in their headers.
+    pub fn mark_roots(&mut self, roots: &[ObjectRef]) {
+        let strings = self.strings.values().cloned().collect::<Vec<_>>();
+        self.mark(&strings);
+        self.mark(roots);
+    }
+
+    /// adds the objects reachable from `roots` to the ones `mark_roots` marked.
+    pub fn mark(&mut self, roots: &[ObjectRef]) {
+        let mut pending = roots.to_vec();
+        while let Some(reference) = pending.pop() {
+            let object = match self.get_mut(reference) {
+                Some(object) if !object.header.is_marked() => object,
+                _ => continue
+            };
+            object.header.set_marked(true);
+
+            let elements = match object.data {
+                ObjectData::Array(ref elements) => elements.iter().collect(),
+                _ => Vec::new()
+            };
+            for value in object.fields.values().chain(elements) {
+                if let StackValue::Reference(child) = value {
+                    pending.push(*child);
+                }
+            }
+        }
+    }
+
+    /// true if the object was marked since the last sweep.
+    pub fn is_marked(&self, reference: ObjectRef) -> bool {
+        self.get(reference).is_some_and(|object| object.header.is_marked())
+    }
+
+    /// frees every object which is not marked and clears the marks of the others.
+    pub fn sweep(&mut self) -> Collection {
+        let mut collection = Collection::default();
+        let (mut counted_before, mut counted_after) = (HashSet::new(), HashSet::new());
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            let object = match slot {
+                Some(object) => object,
+                None => continue
+            };
+
+            let size = footprint(object, &mut counted_before);
+            collection.used_before += size;
+            if !object.header.is_marked() {
+                if self.large.remove(&index) {
+                    self.large_used -= object.size();
+                    collection.freed_large_objects += 1;
+                }
+                *slot = None;
+                self.generations[index] = self.generations[index].wrapping_add(1);
+                self.free.push(index);
+                collection.freed_objects += 1;
+                continue;
+            }
+
+            object.header.set_marked(false);
+            let size = footprint(object, &mut counted_after);
+            collection.used_after += size;
+            if !object.header.is_tenured() {
+                object.header.set_tenured(true);
+                collection.promoted += size;
+            }
+        }
+
+        self.used = collection.used_after;
+        collection
+    }
+
+    /// makes live strings with the same value share their characters, like the string
+    /// deduplication of the hotspot vm. the characters of a value seen before are kept between
+    /// calls as long as a string uses them.
+    pub fn deduplicate_strings(&mut self) -> Deduplication {
+        let mut deduplication = Deduplication::default();
+        // characters only the table holds belong to strings which were collected
+        self.deduplicated.retain(|value| Arc::strong_count(value) > 1);
+        for object in self.objects.iter_mut().flatten() {
+            let value = match object.data {
+                ObjectData::String(ref mut value) => value,
+                _ => continue
+            };
+            deduplication.inspected += 1;
+            match self.deduplicated.get(value) {
+                Some(canonical) if Arc::ptr_eq(canonical, value) => (),
+                Some(canonical) => {
+                    deduplication.deduplicated += 1;
+                    if Arc::strong_count(value) == 1 {
+                        deduplication.freed_bytes += value.len();
+                    }
+                    *value = canonical.clone();
+                }
+                None => {
+                    self.deduplicated.insert(value.clone());
+                }
+            }
+        }
+        self.used = self.used.saturating_sub(deduplication.freed_bytes);
+        deduplication
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_frees_unreachable_objects() {
+        let mut heap = Heap::new();
+        let garbage = heap.allocate("Garbage", ObjectData::Instance);
+        let root = heap.allocate("Root", ObjectData::Instance);
+        let child = heap.allocate("Child", ObjectData::Instance);
+        let constant = heap.intern("constant");
+        heap.get_mut(root).unwrap().fields.insert(String::from("child"), StackValue::Reference(child));
+
+        let collection = heap.collect(&[root]);
+
+        assert_eq!(1, collection.freed_objects);
+        assert!(heap.get(garbage).is_none());
+        assert!(heap.get(child).is_some());
+        assert_eq!(Some("constant"), heap.string_value(constant));
+        assert_eq!(collection.used_after, heap.used());
+    }
+
+    #[test]
+    fn equal_strings_share_their_characters() {
+        let mut heap = Heap::new();
+        let strings = ["same", "same", "other", "same"].iter()
+            .map(|value| heap.allocate("java/lang/String", ObjectData::String(Arc::from(&**value))))
+            .collect::<Vec<_>>();
+        let used = heap.used();
+
+        let deduplication = heap.deduplicate_strings();
+        assert_eq!(deduplication, Deduplication { inspected: 4, deduplicated: 2, freed_bytes: 8 });
+        assert_eq!(heap.used(), used - 8);
+        let characters = |reference: ObjectRef| match heap.get(reference).unwrap().data {
+            ObjectData::String(ref value) => value.as_ptr(),
+            _ => panic!("not a string")
+        };
+        assert_eq!(characters(strings[0]), characters(strings[3]));
+        assert_ne!(characters(strings[0]), characters(strings[2]));
+
+        // a collection counts the shared characters once
+        let copy = heap.allocate("java/lang/String", ObjectData::String(Arc::from("same")));
+        assert_eq!(heap.collect(&strings).used_after, used - 8);
+        assert_eq!(heap.deduplicate_strings().deduplicated, 0);
+        assert!(heap.get(copy).is_none());
+    }
+
+    #[test]
+    fn freed_slots_are_reused() {
+        let mut heap = Heap::new();
+        let root = heap.allocate("Root", ObjectData::Instance);
+        for _ in 0..100 {
+            heap.allocate("Garbage", ObjectData::Instance);
+            heap.collect(&[root]);
+        }
+        assert_eq!(heap.len(), 2);
+
+        let local = heap.allocate("Local", ObjectData::Instance);
+        heap.free(local);
+        let next = heap.allocate("Next", ObjectData::Instance);
+        assert_eq!(next.0, local.0);
+        assert_eq!(heap.get(next).unwrap().class_name, "Next");
+        // the reference to the freed object does not reach the one in its slot
+        assert!(heap.get(local).is_none());
+        heap.free(local);
+        assert!(heap.get(next).is_some());
+        assert_eq!(ObjectRef::from_handle(next.handle()), next);
+    }
+
+    #[test]
+    fn survivors_are_promoted_once() {
+        let mut heap = Heap::new();
+        let root = heap.allocate("Root", ObjectData::Instance);
+
+        assert_eq!(heap.get(root).unwrap().size(), heap.collect(&[root]).promoted);
+        assert_eq!(0, heap.collect(&[root]).promoted);
+    }
+
+    #[test]
+    fn large_objects_are_never_promoted() {
+        let mut heap = Heap::with_large_object_threshold(array_size(100));
+        let small = heap.allocate("[I", ObjectData::Array(vec![StackValue::Integer(0); 99]));
+        let large = heap.allocate("[I", ObjectData::Array(vec![StackValue::Integer(0); 100]));
+        let garbage = heap.allocate("[I", ObjectData::Array(vec![StackValue::Integer(0); 1000]));
+
+        assert!(!heap.is_large(small) && heap.is_large(large));
+        assert_eq!(heap.large_object_space(), LargeObjectSpace { objects: 2, used: array_size(100) + array_size(1000) });
+        let collection = heap.collect(&[small, large]);
+        assert_eq!((collection.promoted, collection.freed_objects, collection.freed_large_objects), (array_size(99), 1, 1));
+        assert!(heap.get(garbage).is_none());
+        assert_eq!(heap.large_object_space(), LargeObjectSpace { objects: 1, used: array_size(100) });
+
+        heap.free(large);
+        assert_eq!(heap.large_object_space(), LargeObjectSpace::default());
+        assert_eq!(heap.used(), array_size(99));
+    }
+}
@@ -0,0 +1,36 @@
+use java::class_file::{ClassFile, Method};
+use java::instructions::Instruction;
+use java::runtime::{FieldWatchEvent, NativeCall, RuntimeError, StackValue};
+
+/// callbacks the interpreter invokes while executing bytecode.
+///
+/// every method has an empty default implementation, so a hook only implements what it needs.
+/// hooks are called in the order they were added to the `Runtime`.
+pub trait RuntimeHook: Send {
+    /// `class` was loaded. a hook added later is told about the classes loaded before.
+    fn on_class_load(&mut self, _class: &ClassFile) {}
+
+    /// the class `class_name` was unloaded together with its class loader.
+    fn on_class_unload(&mut self, _class_name: &str) {}
+
+    /// a new frame for `method` of `class_name` was created, before its first instruction runs.
+    fn on_method_enter(&mut self, _class_name: &str, _method: &Method) {}
+
+    /// `method` returned, either normally or with an error.
+    fn on_method_exit(&mut self, _class_name: &str, _method: &Method, _result: &Result<Option<StackValue>, RuntimeError>) {}
+
+    /// `instruction` at `pc` is about to be executed.
+    fn on_instruction(&mut self, _class_name: &str, _method: &Method, _pc: usize, _instruction: &Instruction) {}
+
+    /// a new instance of `class_name` was allocated on the heap.
+    fn on_allocation(&mut self, _class_name: &str, _size: usize) {}
+
+    /// an exception of `exception_class` was thrown at `pc` of `method`.
+    fn on_exception_thrown(&mut self, _exception_class: &str, _class_name: &str, _method: &Method, _pc: usize) {}
+
+    /// a field registered with `Runtime::watch_field` was read or written.
+    fn on_field_watch(&mut self, _event: &FieldWatchEvent) {}
+
+    /// a native registered with `Runtime::register_native` returned.
+    fn on_native_call(&mut self, _call: &NativeCall) {}
+}
@@ -0,0 +1,355 @@
+use java::runtime::{Object, ObjectData, ObjectRef, Runtime, StackValue};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const HEADER: &[u8] = b"JAVA PROFILE 1.0.2\0";
+const ID_SIZE: u32 = 8;
+
+// top level records
+const TAG_STRING: u8 = 0x01;
+const TAG_LOAD_CLASS: u8 = 0x02;
+const TAG_STACK_TRACE: u8 = 0x05;
+const TAG_HEAP_DUMP_SEGMENT: u8 = 0x1c;
+const TAG_HEAP_DUMP_END: u8 = 0x2c;
+
+// heap dump sub records
+const ROOT_UNKNOWN: u8 = 0xff;
+const ROOT_STICKY_CLASS: u8 = 0x05;
+const CLASS_DUMP: u8 = 0x20;
+const INSTANCE_DUMP: u8 = 0x21;
+const OBJECT_ARRAY_DUMP: u8 = 0x22;
+const PRIMITIVE_ARRAY_DUMP: u8 = 0x23;
+
+// basic types
+const TYPE_OBJECT: u8 = 2;
+const TYPE_BOOLEAN: u8 = 4;
+const TYPE_CHAR: u8 = 5;
+const TYPE_BYTE: u8 = 8;
+const TYPE_SHORT: u8 = 9;
+const TYPE_INT: u8 = 10;
+
+// ids of the different kinds of things in the dump must not overlap.
+// objects use their heap index + 1, so 0 stays the null reference.
+const STRING_IDS: u64 = 1 << 40;
+const CLASS_IDS: u64 = 1 << 41;
+const CHAR_ARRAY_IDS: u64 = 1 << 42;
+
+const STACK_TRACE_SERIAL: u32 = 1;
+
+/// a cloneable flag to request a heap dump from another thread or a signal handler.
+///
+/// the runtime checks the flag at every safepoint and writes the dump to the configured heap
+/// dump path.
+#[derive(Debug, Clone, Default)]
+pub struct HeapDumpTrigger {
+    requested: Arc<AtomicBool>,
+}
+
+impl HeapDumpTrigger {
+    pub fn new() -> HeapDumpTrigger {
+        HeapDumpTrigger { requested: Arc::new(AtomicBool::new(false)) }
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst)
+    }
+
+    /// true while a request is pending, without taking it.
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// true once for every request.
+    pub fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// the raw flag, for `signal_hook::flag::register`.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.requested.clone()
+    }
+}
+
+/// a record body, written big endian like everything in hprof.
+struct Record(Vec<u8>);
+
+impl Record {
+    fn new() -> Record {
+        Record(Vec::new())
+    }
+
+    fn u1(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u2(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u4(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn id(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_to<W: Write>(&self, out: &mut W, tag: u8) -> io::Result<()> {
+        out.write_all(&[tag])?;
+        out.write_all(&0u32.to_be_bytes())?;
+        out.write_all(&(self.0.len() as u32).to_be_bytes())?;
+        out.write_all(&self.0)
+    }
+}
+
+fn object_id(reference: ObjectRef) -> u64 {
+    reference.0 as u64 + 1
+}
+
+fn value_id(value: Option<&StackValue>) -> u64 {
+    match value {
+        Some(StackValue::Reference(reference)) => object_id(*reference),
+        _ => 0
+    }
+}
+
+fn field_type(value: &StackValue) -> u8 {
+    match value {
+        StackValue::Integer(_) => TYPE_INT,
+        _ => TYPE_OBJECT
+    }
+}
+
+/// the primitive element type of an array class like `[I`, `None` for arrays of objects.
+fn primitive_array_type(class_name: &str) -> Option<u8> {
+    match class_name {
+        "[Z" => Some(TYPE_BOOLEAN),
+        "[C" => Some(TYPE_CHAR),
+        "[B" => Some(TYPE_BYTE),
+        "[S" => Some(TYPE_SHORT),
+        "[I" => Some(TYPE_INT),
+        _ => None
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// a trigger to request a heap dump while the runtime is executing bytecode.
+    pub fn heap_dump_trigger(&self) -> HeapDumpTrigger {
+        self.heap_dump_trigger.clone()
+    }
+
+    /// writes all objects on the heap to `path` in the binary hprof format,
+    /// which tools like Eclipse MAT or VisualVM can open.
+    pub fn dump_heap(&mut self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_heap_dump(&mut out)?;
+        out.flush()
+    }
+
+    /// writes the heap in the hprof format to `out`.
+    ///
+    /// the runtime does not track the references held by java frames, so every object is
+    /// reported as a root of unknown type.
+    pub fn write_heap_dump<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let mut classes = BTreeSet::new();
+        classes.insert(String::from("java/lang/Object"));
+        classes.insert(String::from("java/lang/String"));
+        classes.insert(String::from("[C"));
+        for class in self.classes.classes() {
+            classes.insert(String::from(class.get_class_name()));
+        }
+
+        // the fields a class declares are the ones its instances have.
+        // super classes without instances declare none.
+        let mut fields: BTreeMap<String, BTreeMap<String, u8>> = BTreeMap::new();
+        fields.entry(String::from("java/lang/String")).or_default().insert(String::from("value"), TYPE_OBJECT);
+        for (_, object) in self.heap.objects() {
+            classes.insert(object.class_name.clone());
+            let declared = fields.entry(object.class_name.clone()).or_default();
+            for (name, value) in object.fields.iter() {
+                let typ = declared.entry(name.clone()).or_insert_with(|| field_type(value));
+                if field_type(value) == TYPE_OBJECT {
+                    *typ = TYPE_OBJECT;
+                }
+            }
+        }
+        for class_name in classes.clone() {
+            let mut current = self.superclass_of(&class_name);
+            while let Some(name) = current {
+                current = self.superclass_of(&name);
+                classes.insert(name);
+            }
+        }
+
+        let mut strings: HashMap<String, u64> = HashMap::new();
+        let mut string_records = Vec::new();
+        {
+            let mut string_id = |value: &str| -> u64 {
+                if let Some(id) = strings.get(value) {
+                    return *id;
+                }
+                let id = STRING_IDS + strings.len() as u64;
+                strings.insert(String::from(value), id);
+                let mut record = Record::new();
+                record.id(id);
+                record.0.extend_from_slice(value.as_bytes());
+                string_records.push(record);
+                id
+            };
+            for class_name in classes.iter() {
+                string_id(class_name);
+            }
+            for declared in fields.values() {
+                for name in declared.keys() {
+                    string_id(name);
+                }
+            }
+        }
+        let class_ids = classes.iter().enumerate()
+            .map(|(index, name)| (name.clone(), CLASS_IDS + index as u64))
+            .collect::<HashMap<String, u64>>();
+        let no_fields = BTreeMap::new();
+
+        out.write_all(HEADER)?;
+        out.write_all(&ID_SIZE.to_be_bytes())?;
+        out.write_all(&(self.environment.current_time_millis() as u64).to_be_bytes())?;
+
+        for record in string_records.iter() {
+            record.write_to(out, TAG_STRING)?;
+        }
+
+        for (serial, class_name) in classes.iter().enumerate() {
+            let mut record = Record::new();
+            record.u4(serial as u32 + 1);
+            record.id(class_ids[class_name]);
+            record.u4(STACK_TRACE_SERIAL);
+            record.id(strings[class_name]);
+            record.write_to(out, TAG_LOAD_CLASS)?;
+        }
+
+        let mut trace = Record::new();
+        trace.u4(STACK_TRACE_SERIAL);
+        trace.u4(0);
+        trace.u4(0);
+        trace.write_to(out, TAG_STACK_TRACE)?;
+
+        let mut dump = Record::new();
+        for class_name in classes.iter() {
+            dump.u1(ROOT_STICKY_CLASS);
+            dump.id(class_ids[class_name]);
+        }
+
+        for class_name in classes.iter() {
+            let declared = fields.get(class_name).unwrap_or(&no_fields);
+            dump.u1(CLASS_DUMP);
+            dump.id(class_ids[class_name]);
+            dump.u4(STACK_TRACE_SERIAL);
+            dump.id(self.superclass_of(class_name).map(|name| class_ids[&name]).unwrap_or(0));
+            // class loader, signers, protection domain and two reserved ids
+            for _ in 0..5 {
+                dump.id(0);
+            }
+            dump.u4(declared.values().map(|&typ| if typ == TYPE_OBJECT { ID_SIZE } else { 4 }).sum());
+            dump.u2(0);
+            dump.u2(0);
+            dump.u2(declared.len() as u16);
+            for (name, &typ) in declared.iter() {
+                dump.id(strings[name]);
+                dump.u1(typ);
+            }
+        }
+
+        for (reference, object) in self.heap.objects() {
+            dump.u1(ROOT_UNKNOWN);
+            dump.id(object_id(reference));
+            self.dump_object(&mut dump, reference, object, &fields, &class_ids);
+        }
+
+        dump.write_to(out, TAG_HEAP_DUMP_SEGMENT)?;
+        Record::new().write_to(out, TAG_HEAP_DUMP_END)
+    }
+
+    fn dump_object(&self, dump: &mut Record, reference: ObjectRef, object: &Object, fields: &BTreeMap<String, BTreeMap<String, u8>>, class_ids: &HashMap<String, u64>) {
+        match object.data {
+            ObjectData::Array(ref elements) => match primitive_array_type(&object.class_name) {
+                Some(typ) => {
+                    dump.u1(PRIMITIVE_ARRAY_DUMP);
+                    dump.id(object_id(reference));
+                    dump.u4(STACK_TRACE_SERIAL);
+                    dump.u4(elements.len() as u32);
+                    dump.u1(typ);
+                    for element in elements {
+                        let value = match element {
+                            StackValue::Integer(value) => *value,
+                            _ => 0
+                        };
+                        match typ {
+                            TYPE_BOOLEAN | TYPE_BYTE => dump.u1(value as u8),
+                            TYPE_CHAR | TYPE_SHORT => dump.u2(value as u16),
+                            _ => dump.u4(value as u32)
+                        }
+                    }
+                }
+                None => {
+                    dump.u1(OBJECT_ARRAY_DUMP);
+                    dump.id(object_id(reference));
+                    dump.u4(STACK_TRACE_SERIAL);
+                    dump.u4(elements.len() as u32);
+                    dump.id(class_ids[&object.class_name]);
+                    for element in elements {
+                        dump.id(value_id(Some(element)));
+                    }
+                }
+            },
+            _ => {
+                // strings keep their characters in a char[] like they do in java 8
+                let value = match object.data {
+                    ObjectData::String(ref value) => {
+                        let chars = value.encode_utf16().collect::<Vec<u16>>();
+                        dump.u1(PRIMITIVE_ARRAY_DUMP);
+                        dump.id(CHAR_ARRAY_IDS + reference.0 as u64);
+                        dump.u4(STACK_TRACE_SERIAL);
+                        dump.u4(chars.len() as u32);
+                        dump.u1(TYPE_CHAR);
+                        for c in chars {
+                            dump.u2(c);
+                        }
+                        CHAR_ARRAY_IDS + reference.0 as u64
+                    }
+                    _ => 0
+                };
+
+                let mut values = Record::new();
+                let mut current = Some(object.class_name.clone());
+                while let Some(class_name) = current {
+                    if let Some(declared) = fields.get(&class_name) {
+                        for (name, &typ) in declared.iter() {
+                            let field = object.fields.get(name);
+                            match typ {
+                                TYPE_INT => values.u4(match field {
+                                    Some(StackValue::Integer(value)) => *value as u32,
+                                    _ => 0
+                                }),
+                                _ if class_name == "java/lang/String" && name == "value" => values.id(value),
+                                _ => values.id(value_id(field))
+                            }
+                        }
+                    }
+                    current = self.superclass_of(&class_name);
+                }
+
+                dump.u1(INSTANCE_DUMP);
+                dump.id(object_id(reference));
+                dump.u4(STACK_TRACE_SERIAL);
+                dump.id(class_ids[&object.class_name]);
+                dump.u4(values.0.len() as u32);
+                dump.0.extend_from_slice(&values.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use java::class_file::{ClassFile, Method};
+use java::runtime::builtin;
+use java::runtime::{ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::sync::Arc;
+use super::buffers::{BYTE_ORDER, MAP_MODE};
+use super::charsets::STANDARD_CHARSETS;
+use super::process::REDIRECT;
+use super::StackFrame;
+
+/// how far the static initialization of a class got, see jvms 5.5.
+/// classes which were never asked to initialize have no state.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InitializationState {
+    /// `<clinit>` of the class or of one of its super classes is running. the runtime has a single
+    /// thread, so this thread is the one initializing it, and further requests return right away.
+    InProgress,
+    Initialized,
+    /// `<clinit>` threw, the class cannot be used anymore.
+    Erroneous,
+}
+
+impl<'a> Runtime<'a> {
+    pub fn initialization_state(&self, class_name: &str) -> Option<InitializationState> {
+        self.initialization.get(class_name).cloned()
+    }
+
+    /// initializes `class_name` when the bytecode of `method` uses it actively, keeping the frame
+    /// visible to the garbage collector while `<clinit>` runs.
+    pub(super) fn initialize_for(&mut self, method: &Method, stack_frame: &mut StackFrame, class_name: &str) -> Result<(), RuntimeError> {
+        match self.initialization.get(class_name) {
+            Some(InitializationState::InProgress) | Some(InitializationState::Initialized) => Ok(()),
+            _ => self.suspended(stack_frame, |rt| rt.initialize_class(method, class_name))
+        }
+    }
+
+    /// initializes the class of a static method called from outside the bytecode, like the launcher
+    /// calling `main`. nothing happens if the class has no such method, calling it reports that.
+    pub(super) fn initialize_target(&mut self, class_name: &str, method_name: &str, descriptor: &str) -> Result<(), RuntimeError> {
+        let id = match self.classes.id(class_name) {
+            Some(id) => id,
+            None => return Ok(())
+        };
+        match self.classes.method(id, method_name, descriptor) {
+            Some(slot) => {
+                let class = self.classes.class(id).clone();
+                self.initialize_class(&class.methods[slot], class_name)
+            }
+            None => Ok(())
+        }
+    }
+
+    /// `Class.forName`, which loads nothing but initializes the class. throws a `ClassNotFoundException`
+    /// for classes which are neither loaded nor builtin.
+    pub(super) fn class_for_name(&mut self, name: ObjectRef) -> Result<Option<StackValue>, RuntimeError> {
+        let (caller, slot) = self.caller()?;
+        let method = &caller.methods[slot];
+
+        let binary_name = match self.heap.string_value(name) {
+            Some(binary_name) => String::from(binary_name),
+            None => return Err(self.throw(method, "java/lang/NullPointerException", None))
+        };
+        let class_name = binary_name.replace('.', "/");
+        if self.classes.get(&class_name).is_none() && !builtin::is_builtin(&class_name) {
+            return Err(self.throw(method, "java/lang/ClassNotFoundException", Some(&binary_name)));
+        }
+        self.initialize_class(method, &class_name)?;
+
+        Ok(Some(StackValue::Reference(self.class_object(&class_name))))
+    }
+
+    /// the class and method slot of the innermost bytecode frame, which called a builtin method.
+    pub(super) fn caller(&self) -> Result<(Arc<ClassFile<'a>>, usize), RuntimeError> {
+        let frame = self.thread.frames.last().ok_or(RuntimeError::EmptyStack)?;
+        let slot = self.classes.method(frame.class, &frame.method_name, &frame.descriptor).ok_or(RuntimeError::EmptyStack)?;
+        Ok((self.classes.class(frame.class).clone(), slot))
+    }
+
+    /// the internal name of the class a `java/lang/Class` instance stands for.
+    pub(super) fn class_object_name(&self, class: ObjectRef) -> Option<String> {
+        match self.heap.get(class).and_then(|object| object.fields.get("name")) {
+            Some(StackValue::Reference(name)) => self.heap.string_value(*name).map(|name| name.replace('.', "/")),
+            _ => None
+        }
+    }
+
+    /// the `java/lang/Class` instance of a class, created the first time it is asked for.
+    /// its "name" field holds the binary name, like `Class.getName`.
+    pub(super) fn class_object(&mut self, class_name: &str) -> ObjectRef {
+        if let Some(class) = self.class_objects.get(class_name) {
+            return *class;
+        }
+
+        let class = self.allocate("java/lang/Class", ObjectData::Instance);
+        self.class_objects.insert(String::from(class_name), class);
+        let name = self.intern(&class_name.replace('/', "."));
+        if let Some(object) = self.heap.get_mut(class) {
+            object.fields.insert(String::from("name"), StackValue::Reference(name));
+        }
+        class
+    }
+
+    /// the static fields of builtin classes: the `TYPE` of the wrapper classes, which is the class
+    /// of their primitive type, the streams of `System` and the constants of `StandardCharsets` and
+    /// `ProcessBuilder.Redirect`.
+    pub(super) fn builtin_static(&mut self, class_name: &str, field_name: &str) -> Option<StackValue> {
+        if class_name == "java/lang/System" {
+            return self.print_stream(field_name);
+        }
+        if class_name == STANDARD_CHARSETS {
+            return self.standard_charset(field_name);
+        }
+        if class_name == REDIRECT {
+            return self.redirect(field_name);
+        }
+        if class_name == BYTE_ORDER || class_name == MAP_MODE {
+            return self.nio_constant(class_name, field_name);
+        }
+        let primitive = match (class_name, field_name) {
+            ("java/lang/Integer", "TYPE") => "int",
+            ("java/lang/Long", "TYPE") => "long",
+            ("java/lang/Boolean", "TYPE") => "boolean",
+            ("java/lang/Byte", "TYPE") => "byte",
+            ("java/lang/Character", "TYPE") => "char",
+            ("java/lang/Short", "TYPE") => "short",
+            ("java/lang/Void", "TYPE") => "void",
+            _ => return None
+        };
+        Some(StackValue::Reference(self.class_object(primitive)))
+    }
+
+    /// initializes a class before its first active use: `new`, `getstatic`, `putstatic`, `invokestatic`
+    /// and `Class.forName`. super classes are initialized first. `method` is the method which uses the class.
+    ///
+    /// an exception thrown by `<clinit>` is wrapped into an `ExceptionInInitializerError` unless it is
+    /// an `Error`, later uses of the class throw a `NoClassDefFoundError`.
+    pub(super) fn initialize_class(&mut self, method: &Method, class_name: &str) -> Result<(), RuntimeError> {
+        match self.initialization.get(class_name) {
+            Some(InitializationState::InProgress) | Some(InitializationState::Initialized) => return Ok(()),
+            Some(InitializationState::Erroneous) => {
+                let message = format!("Could not initialize class {}", class_name.replace('/', "."));
+                return Err(self.throw(method, "java/lang/NoClassDefFoundError", Some(&message)));
+            }
+            None => ()
+        }
+
+        // builtin classes need no initialization, missing classes fail when they are used
+        let id = match self.classes.id(class_name) {
+            Some(id) => id,
+            None => return Ok(())
+        };
+        let class = self.classes.class(id).clone();
+        self.initialization.insert(String::from(class_name), InitializationState::InProgress);
+
+        if let Some(superclass) = class.get_super_class_name() {
+            if let Err(err) = self.initialize_class(method, superclass) {
+                self.initialization.insert(String::from(class_name), InitializationState::Erroneous);
+                return Err(err);
+            }
+        }
+
+        let clinit = self.classes.method(id, "<clinit>", "()V");
+        debug!(class = class_name, clinit = clinit.is_some(), "initializing class");
+        let result = match clinit {
+            Some(slot) => self.run_method(id, slot, vec![]),
+            None => Ok(None)
+        };
+
+        match result {
+            Ok(_) => {
+                self.initialization.insert(String::from(class_name), InitializationState::Initialized);
+                self.class_initialized(class_name);
+                Ok(())
+            }
+            Err(RuntimeError::Exception { exception, class_name: exception_class }) => {
+                self.initialization.insert(String::from(class_name), InitializationState::Erroneous);
+                if self.is_subclass_of(&exception_class, "java/lang/Error") {
+                    return Err(RuntimeError::Exception { exception, class_name: exception_class });
+                }
+
+                let error = self.allocate("java/lang/ExceptionInInitializerError", ObjectData::Instance);
+                self.throwable_init(error, None);
+                if let Some(object) = self.heap.get_mut(error) {
+                    object.fields.insert(String::from("cause"), StackValue::Reference(exception));
+                }
+                Err(self.exception_thrown(&class.methods[clinit.unwrap()], error))
+            }
+            Err(err) => {
+                self.initialization.insert(String::from(class_name), InitializationState::Erroneous);
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Init.class")).unwrap().1);
+        for class in [&include_bytes!("../../../sample/InitBase.class")[..], &include_bytes!("../../../sample/InitDerived.class")[..],
+                      &include_bytes!("../../../sample/InitCycle.class")[..], &include_bytes!("../../../sample/InitFailing.class")[..]].iter() {
+            rt.load_class(read_class_file(class).unwrap().1);
+        }
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str) -> i64 {
+        match rt.invoke_static("Init", name, "()I", vec![]) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn super_classes_initialize_first() {
+        let mut rt = runtime();
+        assert_eq!(rt.initialization_state("InitDerived"), None);
+        // InitBase.<clinit> appends 1, InitDerived.<clinit> appends 2
+        assert_eq!(int(&mut rt, "derived"), 12);
+        assert_eq!(rt.initialization_state("InitBase"), Some(InitializationState::Initialized));
+        assert_eq!(rt.initialization_state("InitDerived"), Some(InitializationState::Initialized));
+        // initializers run only once
+        assert_eq!(int(&mut rt, "derived"), 12);
+    }
+
+    #[test]
+    fn new_and_static_fields_trigger_initialization() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "created"), 1);
+        assert_eq!(rt.initialization_state("InitDerived"), None);
+        assert_eq!(rt.initialization_state("InitBase"), Some(InitializationState::Initialized));
+    }
+
+    #[test]
+    fn recursive_initialization_sees_the_class_in_progress() {
+        let mut rt = runtime();
+        // InitCycle.<clinit> calls a static method of its own class, which reads the default 0
+        assert_eq!(int(&mut rt, "cycle"), 5);
+    }
+
+    #[test]
+    fn failing_initializers_are_wrapped() {
+        let mut rt = runtime();
+        match rt.invoke_static("Init", "failing", "()I", vec![]) {
+            Err(RuntimeError::Exception { exception, ref class_name }) if class_name == "java/lang/ExceptionInInitializerError" => {
+                match rt.heap().get(exception).and_then(|object| object.fields.get("cause")) {
+                    Some(StackValue::Reference(cause)) => assert_eq!(rt.heap().get(*cause).unwrap().class_name, "java/lang/ArithmeticException"),
+                    other => panic!("unexpected cause {:?}", other)
+                }
+            }
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert_eq!(rt.initialization_state("InitFailing"), Some(InitializationState::Erroneous));
+        match rt.invoke_static("Init", "failing", "()I", vec![]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/NoClassDefFoundError" => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert_eq!(int(&mut rt, "failingCaught"), -1);
+    }
+
+    #[test]
+    fn class_for_name_initializes() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "forName"), 1);
+        assert_eq!(rt.initialization_state("InitBase"), Some(InitializationState::Initialized));
+        assert_eq!(int(&mut rt, "forMissing"), -1);
+    }
+}
@@ -0,0 +1,300 @@
+use java::instructions::Instruction;
+use java::runtime::constant_pool::ACC_NATIVE;
+use java::runtime::{int_arithmetic, opcode_coverage, ClassId, DecodedInsn, LocalVariable, ResolvedField, Runtime, StackValue};
+use std::sync::{Arc, Mutex};
+
+const ACC_SYNCHRONIZED: u16 = 0x0020;
+
+/// callees with more instructions than this are always called.
+const MAX_INLINED_INSTRUCTIONS: usize = 8;
+
+/// an instruction of an inlined method.
+#[derive(Debug)]
+enum InlineOp {
+    LoadInt(usize),
+    LoadReference(usize),
+    Int(i32),
+    /// a binary int instruction, see `int_arithmetic`.
+    Binary(Instruction),
+    Negate,
+    GetField(Arc<ResolvedField>),
+    ReturnInt,
+    ReturnReference,
+}
+
+/// the decoded instructions of a tiny method, like a getter or an arithmetic helper, which its
+/// call sites run on the operands of the caller instead of calling it.
+///
+/// only straight line code which cannot throw, allocate or call is inlined. a getter called on
+/// `null` is called after all, so the exception comes from the callee like it would without inlining.
+#[derive(Debug)]
+pub struct InlineBody {
+    instructions: Vec<Instruction>,
+    ops: Vec<InlineOp>,
+}
+
+impl InlineBody {
+    fn len(&self) -> usize {
+        self.instructions.len()
+    }
+}
+
+/// the body a call site inlined for the method it was resolved to.
+#[derive(Debug)]
+struct InlinedCall {
+    target: (ClassId, usize),
+    /// `None` if the method cannot be inlined.
+    body: Option<Arc<InlineBody>>,
+    generation: u64,
+}
+
+/// the inlined callee of an `invoke*` site, guarded by the method the call resolved to.
+///
+/// entries from an older generation of the `ClassRegistry` are ignored, since redefining a class
+/// or registering a native can change what the method does.
+#[derive(Debug, Default)]
+pub struct InlineSite {
+    entry: Mutex<Option<InlinedCall>>,
+}
+
+impl InlineSite {
+    /// the body inlined for `target`. the outer `None` is a miss, the inner one means the method is called.
+    pub fn lookup(&self, target: (ClassId, usize), generation: u64) -> Option<Option<Arc<InlineBody>>> {
+        match *self.entry.lock().unwrap() {
+            Some(ref entry) if entry.generation == generation && entry.target == target => Some(entry.body.clone()),
+            _ => None
+        }
+    }
+
+    pub fn update(&self, target: (ClassId, usize), body: Option<Arc<InlineBody>>, generation: u64) {
+        *self.entry.lock().unwrap() = Some(InlinedCall { target, body, generation });
+    }
+
+    /// the method of the cached entry.
+    pub fn target(&self) -> Option<(ClassId, usize)> {
+        self.entry.lock().unwrap().as_ref().map(|entry| entry.target)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// runs the call of `target` at a call site inlined, without a frame. `None` if the call has
+    /// to be made, because the method is not hot yet, cannot be inlined or something observes calls.
+    pub(super) fn run_inlined(&mut self, insn: &DecodedInsn, target: (ClassId, usize), arguments: &[LocalVariable]) -> Option<StackValue> {
+        let site = insn.inline.as_ref()?;
+        if !self.can_inline() {
+            return None;
+        }
+
+        let generation = self.classes.generation();
+        let body = match site.lookup(target, generation) {
+            Some(body) => body?,
+            // methods are only inlined once they are hot, so the cold ones do not pay for the analysis
+            None if self.counters.is_hot(target.0, target.1) => {
+                let body = self.inline_body(target).map(Arc::new);
+                site.update(target, body.clone(), generation);
+                body?
+            }
+            None => return None
+        };
+
+        let value = self.execute_inlined(&body, arguments)?;
+        self.count_invocation(target.0, target.1);
+        self.counters.retire(body.len() as u64);
+        for instruction in body.instructions.iter() {
+            opcode_coverage::record(instruction);
+        }
+        Some(value)
+    }
+
+    /// inlined calls have no frame, no events and no safepoints, so like the fast interpreter
+    /// they only happen while nothing observes single calls or instructions.
+    fn can_inline(&self) -> bool {
+        self.inlining && self.hooks.is_empty() && self.debugger.is_none() && !self.breakpoints.is_active()
+            && self.watchpoints.is_empty() && self.trace.is_none() && self.explain.is_none()
+    }
+
+    /// the body of a method which qualifies for inlining.
+    fn inline_body(&self, (id, slot): (ClassId, usize)) -> Option<InlineBody> {
+        let class = self.classes.class(id).clone();
+        let method = &class.methods[slot];
+        if method.access_flags & (ACC_NATIVE | ACC_SYNCHRONIZED) != 0 || !method.get_code()?.exception_table.is_empty() {
+            return None;
+        }
+        let decoded = self.classes.decoded_at(id, slot)?.clone();
+        if decoded.instructions.len() > MAX_INLINED_INSTRUCTIONS {
+            return None;
+        }
+
+        let pool = self.classes.pool(id).clone();
+        let mut ops = Vec::with_capacity(decoded.instructions.len());
+        for insn in decoded.instructions.iter() {
+            ops.push(match insn.instruction {
+                Instruction::ILoad(offset) => InlineOp::LoadInt(usize::from(offset)),
+                Instruction::ILoad0(()) => InlineOp::LoadInt(0),
+                Instruction::ILoad1(()) => InlineOp::LoadInt(1),
+                Instruction::ILoad2(()) => InlineOp::LoadInt(2),
+                Instruction::ILoad3(()) => InlineOp::LoadInt(3),
+                Instruction::ALoad(offset) => InlineOp::LoadReference(usize::from(offset)),
+                Instruction::ALoad0(()) => InlineOp::LoadReference(0),
+                Instruction::ALoad1(()) => InlineOp::LoadReference(1),
+                Instruction::ALoad2(()) => InlineOp::LoadReference(2),
+                Instruction::ALoad3(()) => InlineOp::LoadReference(3),
+                Instruction::IConstm1(()) => InlineOp::Int(-1),
+                Instruction::IConst0(()) => InlineOp::Int(0),
+                Instruction::IConst1(()) => InlineOp::Int(1),
+                Instruction::IConst2(()) => InlineOp::Int(2),
+                Instruction::IConst3(()) => InlineOp::Int(3),
+                Instruction::IConst4(()) => InlineOp::Int(4),
+                Instruction::IConst5(()) => InlineOp::Int(5),
+                Instruction::BIPush(value) => InlineOp::Int(i32::from(value as i8)),
+                Instruction::SIPush(value) => InlineOp::Int(i32::from(value as i16)),
+                // divisions can throw
+                Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) | Instruction::IShl(()) | Instruction::IShr(()) |
+                Instruction::IUSHR(()) | Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(()) => InlineOp::Binary(insn.instruction.clone()),
+                Instruction::INeg(()) => InlineOp::Negate,
+                Instruction::GetField(index) => InlineOp::GetField(self.resolve_field(&pool, &class, index).ok()?),
+                Instruction::IReturn(()) => InlineOp::ReturnInt,
+                Instruction::AReturn(()) => InlineOp::ReturnReference,
+                _ => return None
+            });
+        }
+        // there are no branches, so the only return has to be the last instruction
+        match ops.iter().position(|op| matches!(op, InlineOp::ReturnInt | InlineOp::ReturnReference)) {
+            Some(index) if index + 1 == ops.len() => (),
+            _ => return None
+        }
+
+        debug!(class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor, instructions = ops.len(), "inlined method");
+        Some(InlineBody { instructions: decoded.instructions.iter().map(|insn| insn.instruction.clone()).collect(), ops })
+    }
+
+    /// the value an inlined body returns for `arguments`, `None` if it has to be called instead.
+    /// nothing is changed before the body returns.
+    fn execute_inlined(&self, body: &InlineBody, arguments: &[LocalVariable]) -> Option<StackValue> {
+        let mut locals = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            locals.push(argument);
+            if argument.category() == 2 {
+                locals.push(&LocalVariable::Top);
+            }
+        }
+
+        let mut stack = Vec::with_capacity(4);
+        for op in body.ops.iter() {
+            match op {
+                InlineOp::LoadInt(index) => match locals.get(*index)? {
+                    LocalVariable::Integer(value) => stack.push(StackValue::Integer(*value)),
+                    _ => return None
+                },
+                InlineOp::LoadReference(index) => match locals.get(*index)? {
+                    LocalVariable::Reference(reference) => stack.push(StackValue::Reference(*reference)),
+                    LocalVariable::Null => stack.push(StackValue::Null),
+                    _ => return None
+                },
+                InlineOp::Int(value) => stack.push(StackValue::Integer(i64::from(*value))),
+                InlineOp::Binary(instruction) => {
+                    let (rh, lh) = (int(stack.pop()?)?, int(stack.pop()?)?);
+                    stack.push(StackValue::Integer(i64::from(int_arithmetic(instruction, lh, rh)?)));
+                }
+                InlineOp::Negate => {
+                    let value = int(stack.pop()?)?;
+                    stack.push(StackValue::Integer(i64::from(value.wrapping_neg())));
+                }
+                InlineOp::GetField(field) => match stack.pop()? {
+                    StackValue::Reference(object) => {
+                        let value = self.heap.get(object)
+                            .and_then(|object| object.fields.get_resolved(field.slot.as_ref(), &field.field_name).cloned())
+                            .unwrap_or_else(|| Runtime::default_value(&field.descriptor));
+                        stack.push(value);
+                    }
+                    _ => return None
+                },
+                InlineOp::ReturnInt => return match stack.pop()? {
+                    value @ StackValue::Integer(_) => Some(value),
+                    _ => None
+                },
+                InlineOp::ReturnReference => return match stack.pop()? {
+                    value @ StackValue::Reference(_) | value @ StackValue::Null => Some(value),
+                    _ => None
+                },
+            }
+        }
+        None
+    }
+}
+
+/// an int operand, which is kept sign extended.
+fn int(value: StackValue) -> Option<i32> {
+    match value {
+        StackValue::Integer(value) => Some(value as i32),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::RuntimeError;
+    use super::*;
+
+    fn runtime<'a>(inlining: bool) -> Runtime<'a> {
+        let mut rt = Runtime::builder().hot_method_threshold(5).inlining(inlining)
+            .build(read_class_file(include_bytes!("../../../sample/Inlining.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Cell.class")).unwrap().1);
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str, arguments: Vec<LocalVariable>) -> Result<i64, RuntimeError> {
+        match rt.invoke_static("Inlining", name, &format!("({})I", "I".repeat(arguments.len())), arguments)? {
+            Some(StackValue::Integer(value)) => Ok(value),
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    /// the names of the methods the call sites in `method_name` inlined, `None` for the ones which are called.
+    fn inlined(rt: &Runtime, method_name: &str, descriptor: &str) -> Vec<Option<String>> {
+        let generation = rt.classes.generation();
+        let decoded = rt.classes.decoded("Inlining", method_name, descriptor).unwrap();
+        decoded.instructions.iter().filter_map(|insn| {
+            let (class, slot) = insn.inline.as_ref()?.target()?;
+            let body = insn.inline.as_ref()?.lookup((class, slot), generation)?;
+            Some(body.map(|_| String::from(&*rt.classes.class(class).methods[slot].name)))
+        }).collect()
+    }
+
+    #[test]
+    fn hot_getters_and_helpers_are_inlined() {
+        let mut rt = runtime(true);
+        let mut called = runtime(false);
+        for _ in 0..2 {
+            assert_eq!(int(&mut rt, "sum", vec![LocalVariable::Integer(50)]).unwrap(), 1_756_719_588);
+            assert_eq!(int(&mut called, "sum", vec![LocalVariable::Integer(50)]).unwrap(), 1_756_719_588);
+        }
+
+        let mut sites = inlined(&rt, "sum", "(I)I");
+        sites.sort();
+        assert_eq!(sites, vec![None, Some(String::from("getNext")), Some(String::from("getX")), Some(String::from("mix"))]);
+        assert_eq!(inlined(&called, "sum", "(I)I"), vec![]);
+
+        // inlined calls still count, like the instructions of their bodies
+        assert_eq!(rt.method_profile().method("Cell", "getX").map(|counters| counters.invocations), Some(100));
+        assert_eq!(rt.instructions_retired(), called.instructions_retired());
+    }
+
+    #[test]
+    fn calls_which_can_throw_are_made() {
+        let mut rt = runtime(true);
+        assert_eq!(int(&mut rt, "divide", vec![LocalVariable::Integer(20), LocalVariable::Integer(3)]).unwrap(), 57);
+        assert_eq!(inlined(&rt, "divide", "(II)I"), vec![None]);
+        match int(&mut rt, "divide", vec![LocalVariable::Integer(20), LocalVariable::Integer(0)]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/ArithmeticException" => (),
+            other => panic!("expected an ArithmeticException, got {:?}", other)
+        }
+
+        match int(&mut rt, "nullCell", vec![LocalVariable::Integer(20)]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/NullPointerException" => (),
+            other => panic!("expected a NullPointerException, got {:?}", other)
+        }
+        assert_eq!(inlined(&rt, "nullCell", "(I)I"), vec![Some(String::from("getX"))]);
+    }
+}
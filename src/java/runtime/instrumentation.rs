@@ -0,0 +1,141 @@
+use java::class_file::ClassFile;
+use java::runtime::{ClassLoadListener, ObjectRef, Runtime, RuntimeError};
+
+/// the services of `java.lang.instrument.Instrumentation` for agents written in rust: transforming
+/// classes as they are defined or again after they were loaded, listing the loaded classes and
+/// estimating the size of objects.
+///
+/// transformers are `ClassLoadListener`s, `before_define` gets the bytes of a class and returns the
+/// bytes to use instead. every transformer can retransform, so an agent attached to a running
+/// program can instrument the classes loaded before it.
+///
+/// ```text
+/// let mut instrumentation = rt.instrumentation();
+/// instrumentation.add_transformer(Box::new(TimingAgent::new()));
+/// for class_name in instrumentation.all_loaded_classes() {
+///     if instrumentation.is_modifiable_class(&class_name) {
+///         instrumentation.retransform_class(&class_name)?;
+///     }
+/// }
+/// ```
+pub struct Instrumentation<'r, 'a: 'r> {
+    runtime: &'r mut Runtime<'a>,
+}
+
+impl<'r, 'a> Instrumentation<'r, 'a> {
+    /// adds a transformer for the classes defined and retransformed from now on. transformers run
+    /// in the order they were added, each one sees the bytes the previous ones returned.
+    pub fn add_transformer(&mut self, transformer: Box<dyn ClassLoadListener>) {
+        self.runtime.add_class_load_listener(transformer);
+    }
+
+    /// true if `class_name` is loaded and the bytes it was defined from are known. classes handed to
+    /// `Runtime::load_class` already parsed cannot be retransformed.
+    pub fn is_modifiable_class(&self, class_name: &str) -> bool {
+        self.runtime.classes.get(class_name).is_some() && self.runtime.class_bytes.contains_key(class_name)
+    }
+
+    /// passes the bytes the loaded class `class_name` was defined from through all transformers
+    /// again and replaces its methods with the result, like `Runtime::redefine_class`. the result
+    /// may only change the bodies of methods, a class nobody transforms anymore gets its original
+    /// methods back.
+    pub fn retransform_class(&mut self, class_name: &str) -> Result<(), RuntimeError> {
+        let invalid = |message: String| RuntimeError::InvalidRedefinition { class_name: String::from(class_name), message };
+        if self.runtime.classes.get(class_name).is_none() {
+            return Err(RuntimeError::ClassNotFound { class_name: String::from(class_name) });
+        }
+        let bytes = *self.runtime.class_bytes.get(class_name).ok_or_else(|| invalid(String::from("the bytes of the class are not known")))?;
+        let class = ClassFile::parse(bytes)
+            .map_err(|err| invalid(err.to_string()))?;
+        let (class, _) = self.runtime.transform_class(class, bytes)?;
+        debug!(class = class_name, "retransformed class");
+        self.runtime.replace_class(class_name, class)
+    }
+
+    /// replaces the loaded class `class_name` with the class file `bytes`, see `Runtime::redefine_class`.
+    pub fn redefine_class(&mut self, class_name: &str, bytes: &'a [u8]) -> Result<(), RuntimeError> {
+        self.runtime.redefine_class(class_name, bytes)
+    }
+
+    /// the names of all loaded classes, ordered by name. builtin classes are not loaded from class files
+    /// and not listed.
+    pub fn all_loaded_classes(&self) -> Vec<String> {
+        self.runtime.classes.classes().iter().map(|class| String::from(class.get_class_name())).collect()
+    }
+
+    /// the estimated number of bytes `object` occupies on the heap, the same estimate the garbage
+    /// collector and heap dumps use. `None` if there is no such object.
+    pub fn object_size(&self, object: ObjectRef) -> Option<usize> {
+        self.runtime.heap.get(object).map(|object| object.size())
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// the instrumentation services for agents, see `Instrumentation`.
+    pub fn instrumentation(&mut self) -> Instrumentation<'_, 'a> {
+        Instrumentation { runtime: self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{ByteStore, StackValue};
+    use super::*;
+
+    /// swaps `Reload` for the version in `sample/redefined`, which adds 40 to the number of calls.
+    struct Swap;
+
+    impl ClassLoadListener for Swap {
+        fn before_define(&mut self, class_name: &str, _bytes: &[u8]) -> Option<Vec<u8>> {
+            match class_name {
+                "Reload" => Some(include_bytes!("../../../sample/redefined/Reload.class").to_vec()),
+                _ => None
+            }
+        }
+    }
+
+    fn answer(rt: &mut Runtime) -> Option<StackValue> {
+        rt.invoke_static("Reload", "answer", "()I", vec![]).unwrap()
+    }
+
+    #[test]
+    fn loaded_classes_are_retransformed() {
+        let store = ByteStore::new();
+        let mut rt = Runtime::builder().byte_store(&store).build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        rt.define_class(include_bytes!("../../../sample/Reload.class")).unwrap();
+        assert_eq!(answer(&mut rt), Some(StackValue::Integer(1)));
+
+        {
+            let mut instrumentation = rt.instrumentation();
+            instrumentation.add_transformer(Box::new(Swap));
+            assert_eq!(instrumentation.all_loaded_classes(), vec!["Reload", "Tiny"]);
+            assert!(instrumentation.is_modifiable_class("Reload"));
+            assert!(!instrumentation.is_modifiable_class("Tiny"));
+            instrumentation.retransform_class("Reload").unwrap();
+        }
+        // the static field keeps its value, so this is the second call
+        assert_eq!(answer(&mut rt), Some(StackValue::Integer(42)));
+        assert_eq!(store.len(), 1);
+
+        let mut instrumentation = rt.instrumentation();
+        match instrumentation.retransform_class("Tiny") {
+            Err(RuntimeError::InvalidRedefinition { ref message, .. }) => assert_eq!(message, "the bytes of the class are not known"),
+            other => panic!("retransform_class returned {:?}", other)
+        }
+        match instrumentation.retransform_class("Missing") {
+            Err(RuntimeError::ClassNotFound { .. }) => (),
+            other => panic!("retransform_class returned {:?}", other)
+        }
+    }
+
+    #[test]
+    fn objects_are_measured_like_the_heap_does() {
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        let hello = rt.string("hello");
+        let instrumentation = rt.instrumentation();
+        // the header, the length and the characters
+        assert_eq!(instrumentation.object_size(hello), Some(8 + 8 + 5));
+        assert_eq!(instrumentation.object_size(ObjectRef(usize::MAX, 0)), None);
+    }
+}
@@ -0,0 +1,246 @@
+use java::class_file::Method;
+use java::runtime::{component_type, LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+
+/// a method of the class library the runtime implements itself. calls linked to one skip the
+/// bytecode of the method, if the class library has any, and the natives and builtins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intrinsic {
+    AbsInt,
+    AbsLong,
+    MaxInt,
+    MinInt,
+    MaxLong,
+    MinLong,
+    ArrayCopy,
+    StringEquals,
+    StringHashCode,
+    RequireNonNull,
+    /// `requireNonNull` with the message of the `NullPointerException`.
+    RequireNonNullMessage,
+}
+
+/// the methods which are intrinsic, by class, name and descriptor.
+const INTRINSICS: &[(&str, &str, &str, Intrinsic)] = &[
+    ("java/lang/Math", "abs", "(I)I", Intrinsic::AbsInt),
+    ("java/lang/Math", "abs", "(J)J", Intrinsic::AbsLong),
+    ("java/lang/Math", "max", "(II)I", Intrinsic::MaxInt),
+    ("java/lang/Math", "min", "(II)I", Intrinsic::MinInt),
+    ("java/lang/Math", "max", "(JJ)J", Intrinsic::MaxLong),
+    ("java/lang/Math", "min", "(JJ)J", Intrinsic::MinLong),
+    ("java/lang/System", "arraycopy", "(Ljava/lang/Object;ILjava/lang/Object;II)V", Intrinsic::ArrayCopy),
+    ("java/lang/String", "equals", "(Ljava/lang/Object;)Z", Intrinsic::StringEquals),
+    ("java/lang/String", "hashCode", "()I", Intrinsic::StringHashCode),
+    ("java/util/Objects", "requireNonNull", "(Ljava/lang/Object;)Ljava/lang/Object;", Intrinsic::RequireNonNull),
+    ("java/util/Objects", "requireNonNull", "(Ljava/lang/Object;Ljava/lang/String;)Ljava/lang/Object;", Intrinsic::RequireNonNullMessage),
+];
+
+impl Intrinsic {
+    /// the intrinsic implementing a method, if it has one.
+    pub fn lookup(class_name: &str, method_name: &str, descriptor: &str) -> Option<Intrinsic> {
+        INTRINSICS.iter()
+            .find(|&&(class, method, method_descriptor, _)| class == class_name && method == method_name && method_descriptor == descriptor)
+            .map(|&(_, _, _, intrinsic)| intrinsic)
+    }
+}
+
+fn int(arguments: &[LocalVariable], index: usize) -> Result<i32, RuntimeError> {
+    match arguments.get(index) {
+        Some(LocalVariable::Integer(value)) => Ok(*value as i32),
+        _ => Err(RuntimeError::StackType { expected: String::from("integer") })
+    }
+}
+
+fn long(arguments: &[LocalVariable], index: usize) -> Result<i64, RuntimeError> {
+    match arguments.get(index) {
+        Some(LocalVariable::Long(value)) => Ok(*value),
+        _ => Err(RuntimeError::StackType { expected: String::from("long") })
+    }
+}
+
+/// a reference argument, `None` for null.
+fn reference(arguments: &[LocalVariable], index: usize) -> Result<Option<ObjectRef>, RuntimeError> {
+    match arguments.get(index) {
+        Some(LocalVariable::Reference(reference)) => Ok(Some(*reference)),
+        Some(LocalVariable::Null) => Ok(None),
+        _ => Err(RuntimeError::StackType { expected: String::from("reference") })
+    }
+}
+
+/// the hash code `String.hashCode` computes over the utf-16 code units.
+fn string_hash(value: &str) -> i32 {
+    value.encode_utf16().fold(0i32, |hash, unit| hash.wrapping_mul(31).wrapping_add(i32::from(unit)))
+}
+
+impl<'a> Runtime<'a> {
+    /// whether calls to `class_name.method_name` are linked to an intrinsic. natives registered
+    /// for a method replace its intrinsic.
+    pub(super) fn intrinsic(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<Intrinsic> {
+        if !self.intrinsics || self.natives.get(class_name, method_name, descriptor).is_some() {
+            return None;
+        }
+        Intrinsic::lookup(class_name, method_name, descriptor)
+    }
+
+    /// runs an intrinsic called from `method` with the arguments of the call, the receiver first.
+    pub(super) fn run_intrinsic(&mut self, method: &Method, intrinsic: Intrinsic, arguments: &[LocalVariable]) -> Result<Option<StackValue>, RuntimeError> {
+        let value = match intrinsic {
+            Intrinsic::AbsInt => StackValue::Integer(i64::from(int(arguments, 0)?.wrapping_abs())),
+            Intrinsic::AbsLong => StackValue::Long(long(arguments, 0)?.wrapping_abs()),
+            Intrinsic::MaxInt => StackValue::Integer(i64::from(int(arguments, 0)?.max(int(arguments, 1)?))),
+            Intrinsic::MinInt => StackValue::Integer(i64::from(int(arguments, 0)?.min(int(arguments, 1)?))),
+            Intrinsic::MaxLong => StackValue::Long(long(arguments, 0)?.max(long(arguments, 1)?)),
+            Intrinsic::MinLong => StackValue::Long(long(arguments, 0)?.min(long(arguments, 1)?)),
+            Intrinsic::ArrayCopy => {
+                self.array_copy(method, arguments)?;
+                return Ok(None);
+            }
+            Intrinsic::StringEquals => {
+                let this = reference(arguments, 0)?.and_then(|this| self.heap.string_value(this));
+                let other = reference(arguments, 1)?.and_then(|other| self.heap.string_value(other));
+                StackValue::Integer(if this.is_some() && this == other { 1 } else { 0 })
+            }
+            Intrinsic::StringHashCode => match reference(arguments, 0)?.and_then(|this| self.heap.string_value(this)) {
+                Some(value) => StackValue::Integer(i64::from(string_hash(value))),
+                None => return Err(RuntimeError::StackType { expected: String::from("string") })
+            },
+            Intrinsic::RequireNonNull | Intrinsic::RequireNonNullMessage => match reference(arguments, 0)? {
+                Some(object) => StackValue::Reference(object),
+                None => {
+                    let message = match reference(arguments, 1) {
+                        Ok(Some(message)) => self.heap.string_value(message).map(String::from),
+                        _ => None
+                    };
+                    return Err(self.throw(method, "java/lang/NullPointerException", message.as_deref()));
+                }
+            }
+        };
+        Ok(Some(value))
+    }
+
+    /// `System.arraycopy`. arrays of primitives only copy to arrays of the same type, elements of
+    /// reference arrays are checked against the destination one by one if their component types
+    /// are not assignable, the ones before the first which does not fit are copied.
+    fn array_copy(&mut self, method: &Method, arguments: &[LocalVariable]) -> Result<(), RuntimeError> {
+        let (source, destination) = match (reference(arguments, 0)?, reference(arguments, 2)?) {
+            (Some(source), Some(destination)) => (source, destination),
+            _ => return Err(self.throw(method, "java/lang/NullPointerException", None))
+        };
+        let (source_position, destination_position, length) = (int(arguments, 1)?, int(arguments, 3)?, int(arguments, 4)?);
+        let array = |rt: &Runtime, array: ObjectRef| match rt.heap.get(array) {
+            Some(object) => match object.data {
+                ObjectData::Array(ref elements) => Some((object.class_name.clone(), elements.len())),
+                _ => None
+            },
+            None => None
+        };
+        let ((source_class, source_length), (destination_class, destination_length)) = match (array(self, source), array(self, destination)) {
+            (Some(source), Some(destination)) => (source, destination),
+            (None, _) => return Err(self.throw(method, "java/lang/ArrayStoreException", Some("arraycopy: source type is not an array"))),
+            (_, None) => return Err(self.throw(method, "java/lang/ArrayStoreException", Some("arraycopy: destination type is not an array")))
+        };
+
+        let is_primitive = |class_name: &str| !(class_name.starts_with("[L") || class_name.starts_with("[["));
+        if (is_primitive(&source_class) || is_primitive(&destination_class)) && source_class != destination_class {
+            let message = format!("arraycopy: type mismatch: can not copy {} into {}", source_class, destination_class);
+            return Err(self.throw(method, "java/lang/ArrayStoreException", Some(&message)));
+        }
+        let message = if source_position < 0 {
+            Some(format!("arraycopy: source index {} out of bounds for length {}", source_position, source_length))
+        } else if destination_position < 0 {
+            Some(format!("arraycopy: destination index {} out of bounds for length {}", destination_position, destination_length))
+        } else if length < 0 {
+            Some(format!("arraycopy: length {} is negative", length))
+        } else if source_position as usize + length as usize > source_length {
+            Some(format!("arraycopy: last source index {} out of bounds for length {}", source_position as usize + length as usize, source_length))
+        } else if destination_position as usize + length as usize > destination_length {
+            Some(format!("arraycopy: last destination index {} out of bounds for length {}", destination_position as usize + length as usize, destination_length))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            return Err(self.throw(method, "java/lang/ArrayIndexOutOfBoundsException", Some(&message)));
+        }
+
+        let (source_position, destination_position, length) = (source_position as usize, destination_position as usize, length as usize);
+        let mut elements = match self.heap.get(source).map(|object| &object.data) {
+            Some(ObjectData::Array(elements)) => elements[source_position..source_position + length].to_vec(),
+            _ => unreachable!("checked above")
+        };
+        let destination_component = component_type(&destination_class).unwrap_or("java/lang/Object").to_string();
+        let checked = !is_primitive(&source_class) && !self.is_assignable(component_type(&source_class).unwrap_or(""), &destination_component);
+        let misfit = if checked {
+            elements.iter().position(|element| match *element {
+                StackValue::Reference(element) => {
+                    let class_name = self.heap.get(element).map(|object| object.class_name.clone()).unwrap_or_default();
+                    !self.is_assignable(&class_name, &destination_component)
+                }
+                _ => false
+            })
+        } else {
+            None
+        };
+        if let Some(index) = misfit {
+            elements.truncate(index);
+        }
+
+        if let Some(ObjectData::Array(ref mut target)) = self.heap.get_mut(destination).map(|object| &mut object.data) {
+            target[destination_position..destination_position + elements.len()].clone_from_slice(&elements);
+        }
+        match misfit {
+            Some(_) => {
+                let message = format!("arraycopy: element type mismatch: can not cast one of the elements of {} to the type of the destination array, {}",
+                                      source_class.replace('/', "."), destination_component.replace('/', "."));
+                Err(self.throw(method, "java/lang/ArrayStoreException", Some(&message)))
+            }
+            None => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>(intrinsics: bool) -> Runtime<'a> {
+        Runtime::builder().intrinsics(intrinsics)
+            .build(read_class_file(include_bytes!("../../../sample/Intrinsics.class")).unwrap().1)
+    }
+
+    fn int(rt: &mut Runtime, name: &str, n: i64) -> Result<i64, RuntimeError> {
+        match rt.invoke_static("Intrinsics", name, "(I)I", vec![LocalVariable::Integer(n)])? {
+            Some(StackValue::Integer(value)) => Ok(value),
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn calls_are_linked_to_intrinsics() {
+        let mut rt = runtime(true);
+        assert_eq!(int(&mut rt, "clamp", -7).unwrap(), 7);
+        assert_eq!(int(&mut rt, "clamp", 20).unwrap(), 10);
+        assert_eq!(rt.invoke_static("Intrinsics", "larger", "(JJ)J", vec![LocalVariable::Long(-(1 << 40)), LocalVariable::Long(3)]).unwrap(),
+                   Some(StackValue::Long(1 << 40)));
+        assert_eq!(int(&mut rt, "copy", 3).unwrap(), 23400);
+        assert!(format!("{:?}", int(&mut rt, "copy", 5).unwrap_err().root()).contains("ArrayIndexOutOfBoundsException"));
+        // the strings before the object are copied
+        assert_eq!(int(&mut rt, "mixed", 0).unwrap(), 1);
+        assert_eq!(int(&mut rt, "strings", 1).unwrap(), 1322);
+        assert_eq!(int(&mut rt, "strings", 0).unwrap(), 322);
+        assert_eq!(int(&mut rt, "required", 1).unwrap(), 1);
+        assert_eq!(int(&mut rt, "required", 0).unwrap(), -1);
+    }
+
+    #[test]
+    fn natives_replace_intrinsics() {
+        let mut rt = runtime(true);
+        rt.register_native("java/lang/Math", "abs", "(I)I", |_, _| Ok(Some(StackValue::Integer(0))));
+        assert_eq!(int(&mut rt, "clamp", -7).unwrap(), 0);
+
+        // without intrinsics the class library is needed
+        let mut rt = runtime(false);
+        assert!(int(&mut rt, "clamp", -7).is_err());
+        assert_eq!(Intrinsic::lookup("java/lang/System", "arraycopy", "(Ljava/lang/Object;ILjava/lang/Object;II)V"), Some(Intrinsic::ArrayCopy));
+        assert_eq!(Intrinsic::lookup("java/lang/Math", "abs", "(D)D"), None);
+    }
+}
@@ -0,0 +1,922 @@
+use java::class_file::Method;
+use java::runtime::builtin;
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime};
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use super::StackFrame;
+
+const HANDSHAKE: &[u8] = b"JDWP-Handshake";
+const REPLY_FLAG: u8 = 0x80;
+/// the longest packet accepted from a debugger. its commands are small, the length of a packet must
+/// not make the runtime allocate whatever a peer claims.
+const MAX_PACKET_LENGTH: usize = 1 << 20;
+
+// ids of the different kinds of things must not overlap.
+// objects use their heap index + 1, like in the heap dumps.
+const CLASS_IDS: u64 = 1 << 41;
+const THREAD_ID: u64 = 1 << 43;
+const THREAD_GROUP_ID: u64 = 1 << 44;
+
+// error codes
+const INVALID_THREAD: u16 = 10;
+const INVALID_OBJECT: u16 = 20;
+const INVALID_CLASS: u16 = 21;
+const INVALID_METHODID: u16 = 23;
+const INVALID_FRAMEID: u16 = 30;
+const NOT_IMPLEMENTED: u16 = 99;
+const ABSENT_INFORMATION: u16 = 101;
+
+// event kinds
+const EVENT_SINGLE_STEP: u8 = 1;
+const EVENT_BREAKPOINT: u8 = 2;
+const EVENT_VM_START: u8 = 90;
+const EVENT_VM_DEATH: u8 = 99;
+
+const SUSPEND_NONE: u8 = 0;
+const SUSPEND_ALL: u8 = 2;
+
+const STEP_MIN: u32 = 0;
+const STEP_INTO: u32 = 0;
+const STEP_OVER: u32 = 1;
+
+const TYPE_TAG_CLASS: u8 = 1;
+const TYPE_TAG_ARRAY: u8 = 3;
+const CLASS_STATUS_INITIALIZED: u32 = 7;
+
+struct Packet {
+    id: u32,
+    command_set: u8,
+    command: u8,
+    data: Vec<u8>,
+}
+
+/// reads the data of a command packet, everything is big endian.
+struct Input<'d> {
+    data: &'d [u8],
+}
+
+impl<'d> Input<'d> {
+    fn take(&mut self, count: usize) -> &'d [u8] {
+        let count = count.min(self.data.len());
+        let (head, tail) = self.data.split_at(count);
+        self.data = tail;
+        head
+    }
+
+    fn u1(&mut self) -> u8 {
+        self.take(1).first().cloned().unwrap_or(0)
+    }
+
+    fn u4(&mut self) -> u32 {
+        self.take(4).iter().fold(0, |acc, &b| acc << 8 | u32::from(b))
+    }
+
+    fn id(&mut self) -> u64 {
+        self.take(8).iter().fold(0, |acc, &b| acc << 8 | u64::from(b))
+    }
+
+    fn string(&mut self) -> String {
+        let length = self.u4() as usize;
+        String::from_utf8_lossy(self.take(length)).into_owned()
+    }
+
+    /// a location: type tag, class id, method id and code index.
+    fn location(&mut self) -> (u64, u64, u64) {
+        self.u1();
+        (self.id(), self.id(), self.id())
+    }
+}
+
+/// builds the data of a reply or event packet.
+#[derive(Default)]
+struct Output(Vec<u8>);
+
+impl Output {
+    fn u1(&mut self, value: u8) -> &mut Output {
+        self.0.push(value);
+        self
+    }
+
+    fn u4(&mut self, value: u32) -> &mut Output {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn id(&mut self, value: u64) -> &mut Output {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    fn string(&mut self, value: &str) -> &mut Output {
+        self.u4(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    fn location(&mut self, class_id: u64, method_id: u64, pc: usize) -> &mut Output {
+        self.u1(TYPE_TAG_CLASS).id(class_id).id(method_id).id(pc as u64)
+    }
+}
+
+struct Breakpoint {
+    request_id: u32,
+    class_id: u64,
+    method_id: u64,
+    pc: usize,
+    suspend_policy: u8,
+}
+
+struct Step {
+    request_id: u32,
+    size: u32,
+    depth: u32,
+    suspend_policy: u8,
+    /// the frame count and source line (or pc for `STEP_MIN`) where stepping started.
+    start: Option<(usize, usize)>,
+}
+
+/// the instruction the interpreter is about to execute.
+pub(super) struct DebugPoint<'p, 'a: 'p> {
+    pub method: &'p Method<'a>,
+    pub frame: &'p StackFrame,
+    pub pc: usize,
+}
+
+/// a connected JDWP debugger, like IntelliJ or VS Code.
+///
+/// the runtime has a single java thread, `main`. the debugger is served on the interpreter thread:
+/// before every instruction pending commands are answered and breakpoints and steps are checked.
+/// while the vm is suspended, the interpreter blocks until the debugger resumes it.
+pub struct Debugger {
+    stream: TcpStream,
+    packets: Receiver<Packet>,
+    suspended: bool,
+    connected: bool,
+    next_request_id: u32,
+    next_event_id: u32,
+    classes: Vec<String>,
+    breakpoints: Vec<Breakpoint>,
+    step: Option<Step>,
+}
+
+impl Debugger {
+    /// performs the JDWP handshake on `stream` and announces the start of the vm.
+    /// the vm starts suspended, like with `suspend=y`.
+    pub fn attach(mut stream: TcpStream) -> io::Result<Debugger> {
+        let mut handshake = [0u8; 14];
+        stream.read_exact(&mut handshake)?;
+        if &handshake[..] != HANDSHAKE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid JDWP handshake"));
+        }
+        stream.write_all(HANDSHAKE)?;
+
+        let (sender, packets) = channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            while let Ok(packet) = read_packet(&mut reader) {
+                if sender.send(packet).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut debugger = Debugger {
+            stream,
+            packets,
+            suspended: true,
+            connected: true,
+            next_request_id: 1,
+            next_event_id: 1,
+            classes: Vec::new(),
+            breakpoints: Vec::new(),
+            step: None,
+        };
+
+        let mut event = Output::default();
+        event.u1(SUSPEND_ALL).u4(1).u1(EVENT_VM_START).u4(0).id(THREAD_ID);
+        debugger.send_event(&event);
+
+        Ok(debugger)
+    }
+
+    fn class_id(&mut self, class_name: &str) -> u64 {
+        let index = match self.classes.iter().position(|name| name == class_name) {
+            Some(index) => index,
+            None => {
+                self.classes.push(String::from(class_name));
+                self.classes.len() - 1
+            }
+        };
+
+        CLASS_IDS + index as u64 + 1
+    }
+
+    fn class_name(&self, class_id: u64) -> Option<&str> {
+        class_id.checked_sub(CLASS_IDS + 1)
+            .and_then(|index| self.classes.get(index as usize))
+            .map(|name| name.as_str())
+    }
+
+    fn send(&mut self, header: &[u8], data: &[u8]) {
+        let length = (11 + data.len()) as u32;
+        let written = self.stream.write_all(&length.to_be_bytes())
+            .and_then(|_| self.stream.write_all(header))
+            .and_then(|_| self.stream.write_all(data));
+        if let Err(err) = written {
+            warn!(error = %err, "debugger connection lost");
+            self.connected = false;
+            self.suspended = false;
+        }
+    }
+
+    fn reply(&mut self, id: u32, result: Result<Output, u16>) {
+        let (error, data) = match result {
+            Ok(data) => (0, data.0),
+            Err(error) => (error, Vec::new())
+        };
+
+        let mut header = id.to_be_bytes().to_vec();
+        header.push(REPLY_FLAG);
+        header.extend_from_slice(&error.to_be_bytes());
+        self.send(&header, &data);
+    }
+
+    /// sends a composite event (command set 64, command 100).
+    fn send_event(&mut self, event: &Output) {
+        let mut header = self.next_event_id.to_be_bytes().to_vec();
+        header.extend_from_slice(&[0, 64, 100]);
+        self.next_event_id += 1;
+        self.send(&header, &event.0);
+    }
+
+    fn vm_death(&mut self) {
+        let mut event = Output::default();
+        event.u1(SUSPEND_NONE).u4(1).u1(EVENT_VM_DEATH).u4(0);
+        self.send_event(&event);
+    }
+}
+
+fn read_packet(stream: &mut TcpStream) -> io::Result<Packet> {
+    let mut header = [0u8; 11];
+    stream.read_exact(&mut header)?;
+    let mut input = Input { data: &header };
+    let length = input.u4() as usize;
+    let id = input.u4();
+    let _flags = input.u1();
+    let command_set = input.u1();
+    let command = input.u1();
+    if length > MAX_PACKET_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("JDWP packet of {} bytes exceeds the maximum of {}", length, MAX_PACKET_LENGTH)));
+    }
+
+    let mut data = vec![0u8; length.saturating_sub(11)];
+    stream.read_exact(&mut data)?;
+
+    Ok(Packet { id, command_set, command, data })
+}
+
+fn signature(class_name: &str) -> String {
+    if class_name.starts_with('[') {
+        String::from(class_name)
+    } else {
+        format!("L{};", class_name)
+    }
+}
+
+fn type_tag(class_name: &str) -> u8 {
+    if class_name.starts_with('[') { TYPE_TAG_ARRAY } else { TYPE_TAG_CLASS }
+}
+
+impl<'a> Runtime<'a> {
+    /// waits for a debugger to connect to `address` and attaches it, like `-agentlib:jdwp=transport=dt_socket,server=y`.
+    pub fn listen_for_debugger<A: ToSocketAddrs>(&mut self, address: A) -> io::Result<()> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, peer) = listener.accept()?;
+        info!(peer = %peer, "debugger attached");
+        self.attach_debugger(stream)
+    }
+
+    /// attaches a debugger connected through `stream`. the vm stays suspended until it resumes it.
+    pub fn attach_debugger(&mut self, stream: TcpStream) -> io::Result<()> {
+        self.debugger = Some(Debugger::attach(stream)?);
+        Ok(())
+    }
+
+    /// tells an attached debugger that the vm terminated and disconnects it.
+    pub fn detach_debugger(&mut self) {
+        if let Some(mut debugger) = self.debugger.take() {
+            debugger.vm_death();
+        }
+    }
+
+    /// serves the debugger before the instruction at `point` is executed.
+    pub(super) fn debug_point(&mut self, point: DebugPoint) {
+        let mut debugger = match self.debugger.take() {
+            Some(debugger) => debugger,
+            None => return
+        };
+
+        loop {
+            match debugger.packets.try_recv() {
+                Ok(packet) => self.debug_command(&mut debugger, &point, packet),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    debugger.connected = false;
+                    break;
+                }
+            }
+        }
+
+        if debugger.connected && !debugger.suspended {
+            self.check_debug_events(&mut debugger, &point);
+        }
+
+        while debugger.connected && debugger.suspended {
+            match debugger.packets.recv() {
+                Ok(packet) => self.debug_command(&mut debugger, &point, packet),
+                Err(_) => debugger.connected = false
+            }
+        }
+
+        if debugger.connected {
+            self.debugger = Some(debugger);
+        } else {
+            info!("debugger detached");
+        }
+    }
+
+    /// the class id, method id and pc of the frame at `depth`, 0 being the outermost frame.
+    fn debug_location(&self, debugger: &mut Debugger, depth: usize) -> Option<(u64, u64, usize)> {
+        let frame = self.thread.frames.get(depth)?;
+        let method = self.classes.class(frame.class).methods.iter()
+            .position(|method| method.name == frame.method_name && method.descriptor == frame.descriptor)?;
+        Some((debugger.class_id(self.classes.class(frame.class).get_class_name()), method as u64 + 1, frame.pc))
+    }
+
+    fn check_debug_events(&mut self, debugger: &mut Debugger, point: &DebugPoint) {
+        let (class_id, method_id, pc) = match self.debug_location(debugger, self.thread.frames.len() - 1) {
+            Some(location) => location,
+            None => return
+        };
+        let depth = self.thread.frames.len();
+        let line = point.method.get_code().and_then(|code| code.line_number_at(point.pc));
+
+        let mut hits = Vec::new();
+        for breakpoint in debugger.breakpoints.iter() {
+            if breakpoint.class_id == class_id && breakpoint.method_id == method_id && breakpoint.pc == pc {
+                hits.push((EVENT_BREAKPOINT, breakpoint.request_id, breakpoint.suspend_policy));
+            }
+        }
+
+        if let Some(ref mut step) = debugger.step {
+            let position = if step.size == STEP_MIN { pc } else { line.unwrap_or(pc) };
+            match step.start {
+                None => step.start = Some((depth, position)),
+                Some((start_depth, start_position)) => {
+                    let moved = depth < start_depth || (depth == start_depth && position != start_position);
+                    let stopped = match step.depth {
+                        STEP_INTO => moved || depth > start_depth,
+                        STEP_OVER => moved,
+                        _ => depth < start_depth
+                    };
+                    if stopped {
+                        hits.push((EVENT_SINGLE_STEP, step.request_id, step.suspend_policy));
+                    }
+                }
+            }
+        }
+
+        if hits.is_empty() {
+            return;
+        }
+
+        let suspend_policy = hits.iter().map(|&(_, _, policy)| policy).max().unwrap_or(SUSPEND_NONE);
+        let mut event = Output::default();
+        event.u1(suspend_policy).u4(hits.len() as u32);
+        for (kind, request_id, _) in hits {
+            event.u1(kind).u4(request_id).id(THREAD_ID).location(class_id, method_id, pc);
+        }
+        debugger.send_event(&event);
+        if suspend_policy != SUSPEND_NONE {
+            debugger.suspended = true;
+        }
+    }
+
+    /// the local variables of the frame with `frame_id`.
+    fn debug_frame<'f>(&'f self, point: &'f DebugPoint, frame_id: u64) -> Option<&'f StackFrame> {
+        let depth = (frame_id as usize).checked_sub(1)?;
+        if depth + 1 == self.thread.frames.len() {
+            Some(point.frame)
+        } else {
+            self.thread.frames.get(depth).and_then(|frame| frame.saved.as_ref())
+        }
+    }
+
+    fn debug_class_names(&self) -> Vec<String> {
+        let mut names = self.classes.classes().iter().map(|class| String::from(class.get_class_name())).collect::<Vec<_>>();
+        names.extend(builtin::names().filter(|name| self.classes.get(name).is_none()).map(String::from));
+        names
+    }
+
+    fn debug_methods(&self, class_name: &str) -> Vec<(String, String, u16)> {
+        match self.classes.get(class_name) {
+            Some(class) => class.methods.iter().map(|method| (String::from(&*method.name), String::from(&*method.descriptor), method.access_flags)).collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// writes a tagged value, `tag` is the signature byte the debugger expects.
+    fn debug_value(&self, out: &mut Output, value: Option<&LocalVariable>, tag: u8) {
+        match value {
+            Some(LocalVariable::Integer(value)) => {
+                out.u1(b'I').u4(*value as u32);
+            }
+            Some(LocalVariable::Reference(reference)) => {
+                let tag = match self.heap.get(*reference).map(|object| &object.data) {
+                    Some(ObjectData::String(_)) => b's',
+                    Some(ObjectData::Array(_)) => b'[',
+                    _ => b'L'
+                };
+                out.u1(tag).id(reference.handle() + 1);
+            }
+            _ => match tag {
+                b'L' | b's' | b'[' | b't' | b'g' | b'l' | b'c' | b'J' | b'D' => {
+                    out.u1(tag).id(0);
+                }
+                b'Z' | b'B' => {
+                    out.u1(tag).u1(0);
+                }
+                b'C' | b'S' => {
+                    out.u1(tag).u1(0).u1(0);
+                }
+                _ => {
+                    out.u1(b'I').u4(0);
+                }
+            }
+        }
+    }
+
+    fn debug_command(&mut self, debugger: &mut Debugger, point: &DebugPoint, packet: Packet) {
+        let mut input = Input { data: &packet.data };
+        let mut out = Output::default();
+        let result = match (packet.command_set, packet.command) {
+            // VirtualMachine
+            (1, 1) => {
+                out.string("rjvm").u4(1).u4(8).string("1.8.0").string("rjvm");
+                Ok(out)
+            }
+            (1, 2) => {
+                let wanted = input.string();
+                let matching = self.debug_class_names().into_iter().filter(|name| signature(name) == wanted).collect::<Vec<_>>();
+                out.u4(matching.len() as u32);
+                for name in matching {
+                    out.u1(type_tag(&name)).id(debugger.class_id(&name)).u4(CLASS_STATUS_INITIALIZED);
+                }
+                Ok(out)
+            }
+            (1, 3) | (1, 20) => {
+                let names = self.debug_class_names();
+                out.u4(names.len() as u32);
+                for name in names {
+                    out.u1(type_tag(&name)).id(debugger.class_id(&name)).string(&signature(&name));
+                    if packet.command == 20 {
+                        out.string("");
+                    }
+                    out.u4(CLASS_STATUS_INITIALIZED);
+                }
+                Ok(out)
+            }
+            (1, 4) => {
+                out.u4(1).id(THREAD_ID);
+                Ok(out)
+            }
+            (1, 5) => {
+                out.u4(1).id(THREAD_GROUP_ID);
+                Ok(out)
+            }
+            (1, 6) => {
+                debugger.breakpoints.clear();
+                debugger.step = None;
+                debugger.suspended = false;
+                debugger.connected = false;
+                Ok(out)
+            }
+            (1, 7) => {
+                out.u4(8).u4(8).u4(8).u4(8).u4(8);
+                Ok(out)
+            }
+            (1, 8) | (11, 2) => {
+                debugger.suspended = true;
+                Ok(out)
+            }
+            (1, 9) | (11, 3) => {
+                debugger.suspended = false;
+                Ok(out)
+            }
+            (1, 10) => {
+                self.cancellation.cancel();
+                debugger.suspended = false;
+                Ok(out)
+            }
+            (1, 12) => {
+                for _ in 0..7 {
+                    out.u1(0);
+                }
+                Ok(out)
+            }
+            (1, 17) => {
+                // canGetBytecodes, canGetSourceDebugExtension are false, only the line table is there
+                for _ in 0..32 {
+                    out.u1(0);
+                }
+                Ok(out)
+            }
+
+            // ReferenceType
+            (2, 1) => match debugger.class_name(input.id()).map(String::from) {
+                Some(name) => {
+                    out.string(&signature(&name));
+                    Ok(out)
+                }
+                None => Err(INVALID_CLASS)
+            },
+            (2, 2) => {
+                out.id(0);
+                Ok(out)
+            }
+            (2, 3) => match debugger.class_name(input.id()).map(String::from) {
+                Some(name) => {
+                    out.u4(u32::from(self.classes.get(&name).map(|class| class.access_flags).unwrap_or(1)));
+                    Ok(out)
+                }
+                None => Err(INVALID_CLASS)
+            },
+            (2, 4) | (2, 14) | (2, 10) => {
+                out.u4(0);
+                Ok(out)
+            }
+            (2, 5) | (2, 15) => match debugger.class_name(input.id()).map(String::from) {
+                Some(name) => {
+                    let methods = self.debug_methods(&name);
+                    out.u4(methods.len() as u32);
+                    for (index, (method_name, descriptor, access_flags)) in methods.iter().enumerate() {
+                        out.id(index as u64 + 1).string(method_name).string(descriptor);
+                        if packet.command == 15 {
+                            out.string("");
+                        }
+                        out.u4(u32::from(*access_flags));
+                    }
+                    Ok(out)
+                }
+                None => Err(INVALID_CLASS)
+            },
+            (2, 7) => match debugger.class_name(input.id()).and_then(|name| self.classes.get(name)) {
+                Some(class) => match class.get_source_file() {
+                    Some(file) => {
+                        out.string(file);
+                        Ok(out)
+                    }
+                    None => Err(ABSENT_INFORMATION)
+                },
+                None => Err(ABSENT_INFORMATION)
+            },
+            (2, 9) => {
+                out.u4(CLASS_STATUS_INITIALIZED);
+                Ok(out)
+            }
+
+            // ClassType
+            (3, 1) => match debugger.class_name(input.id()).map(String::from) {
+                Some(name) => {
+                    let superclass = self.superclass_of(&name).map(|name| debugger.class_id(&name)).unwrap_or(0);
+                    out.id(superclass);
+                    Ok(out)
+                }
+                None => Err(INVALID_CLASS)
+            },
+
+            // Method
+            (6, 1) => {
+                let class = debugger.class_name(input.id()).and_then(|name| self.classes.get(name));
+                let method_id = input.id() as usize;
+                match class.as_ref().and_then(|class| method_id.checked_sub(1).and_then(|index| class.methods.get(index))) {
+                    Some(method) => match method.get_code() {
+                        Some(code) => {
+                            let table = code.line_number_table();
+                            out.id(0).id(code.code_length().saturating_sub(1) as u64).u4(table.len() as u32);
+                            for (pc, line) in table {
+                                out.id(u64::from(pc)).u4(u32::from(line));
+                            }
+                            Ok(out)
+                        }
+                        None => Err(ABSENT_INFORMATION)
+                    },
+                    None => Err(INVALID_METHODID)
+                }
+            }
+            // no LocalVariableTable is parsed, debuggers fall back to slots
+            (6, 2) | (6, 5) => Err(ABSENT_INFORMATION),
+
+            // ObjectReference
+            (9, 1) => {
+                let object_id = input.id();
+                match object_id.checked_sub(1).and_then(|index| self.heap.get(ObjectRef::from_handle(index))) {
+                    Some(object) => {
+                        let class_name = object.class_name.clone();
+                        out.u1(type_tag(&class_name)).id(debugger.class_id(&class_name));
+                        Ok(out)
+                    }
+                    None => Err(INVALID_OBJECT)
+                }
+            }
+
+            // StringReference
+            (10, 1) => {
+                let object_id = input.id();
+                match object_id.checked_sub(1).and_then(|index| self.heap.string_value(ObjectRef::from_handle(index))) {
+                    Some(value) => {
+                        out.string(value);
+                        Ok(out)
+                    }
+                    None => Err(INVALID_OBJECT)
+                }
+            }
+
+            // ThreadReference
+            (11, 1) | (12, 1) => {
+                out.string("main");
+                Ok(out)
+            }
+            (11, 4) => {
+                out.u4(1).u4(if debugger.suspended { 1 } else { 0 });
+                Ok(out)
+            }
+            (11, 5) => {
+                out.id(THREAD_GROUP_ID);
+                Ok(out)
+            }
+            (11, 6) => {
+                if input.id() != THREAD_ID {
+                    Err(INVALID_THREAD)
+                } else {
+                    let start = input.u4() as usize;
+                    let length = input.u4() as i32;
+                    let depths = (0..self.thread.frames.len()).rev().skip(start);
+                    let depths = if length < 0 { depths.collect::<Vec<_>>() } else { depths.take(length as usize).collect() };
+                    out.u4(depths.len() as u32);
+                    for depth in depths {
+                        let (class_id, method_id, pc) = self.debug_location(debugger, depth).unwrap_or((0, 0, 0));
+                        out.id(depth as u64 + 1).location(class_id, method_id, pc);
+                    }
+                    Ok(out)
+                }
+            }
+            (11, 7) => {
+                out.u4(self.thread.frames.len() as u32);
+                Ok(out)
+            }
+            (11, 12) => {
+                out.u4(if debugger.suspended { 1 } else { 0 });
+                Ok(out)
+            }
+
+            // ThreadGroupReference
+            (12, 2) => {
+                out.id(0);
+                Ok(out)
+            }
+            (12, 3) => {
+                out.u4(1).id(THREAD_ID).u4(0);
+                Ok(out)
+            }
+
+            // EventRequest
+            (15, 1) => {
+                let kind = input.u1();
+                let suspend_policy = input.u1();
+                let modifiers = input.u4();
+                let request_id = debugger.next_request_id;
+                debugger.next_request_id += 1;
+
+                let mut location = None;
+                let mut step = None;
+                for _ in 0..modifiers {
+                    match input.u1() {
+                        1 | 2 => { input.u4(); }
+                        3 | 4 | 11 => { input.id(); }
+                        5 | 6 | 12 => { input.string(); }
+                        7 => location = Some(input.location()),
+                        8 => {
+                            input.id();
+                            input.u1();
+                            input.u1();
+                        }
+                        9 => {
+                            input.id();
+                            input.id();
+                        }
+                        10 => {
+                            input.id();
+                            step = Some((input.u4(), input.u4()));
+                        }
+                        _ => ()
+                    }
+                }
+
+                match (kind, location, step) {
+                    (EVENT_BREAKPOINT, Some((class_id, method_id, pc)), _) => {
+                        debugger.breakpoints.push(Breakpoint { request_id, class_id, method_id, pc: pc as usize, suspend_policy });
+                    }
+                    (EVENT_SINGLE_STEP, _, Some((size, depth))) => {
+                        debugger.step = Some(Step { request_id, size, depth, suspend_policy, start: None });
+                    }
+                    // other events are accepted, but never happen
+                    _ => ()
+                }
+
+                out.u4(request_id);
+                Ok(out)
+            }
+            (15, 2) => {
+                let kind = input.u1();
+                let request_id = input.u4();
+                match kind {
+                    EVENT_BREAKPOINT => debugger.breakpoints.retain(|breakpoint| breakpoint.request_id != request_id),
+                    EVENT_SINGLE_STEP if debugger.step.as_ref().map(|step| step.request_id) == Some(request_id) => debugger.step = None,
+                    _ => ()
+                }
+                Ok(out)
+            }
+            (15, 3) => {
+                debugger.breakpoints.clear();
+                Ok(out)
+            }
+
+            // StackFrame
+            (16, 1) => {
+                input.id();
+                match self.debug_frame(point, input.id()) {
+                    Some(frame) => {
+                        let count = input.u4();
+                        out.u4(count);
+                        for _ in 0..count {
+                            let slot = input.u4() as usize;
+                            let tag = input.u1();
+                            self.debug_value(&mut out, frame.local_variables.get(slot), tag);
+                        }
+                        Ok(out)
+                    }
+                    None => Err(INVALID_FRAMEID)
+                }
+            }
+            (16, 3) => {
+                input.id();
+                let frame_id = input.id();
+                match self.debug_frame(point, frame_id) {
+                    Some(frame) => {
+                        let is_static = (frame_id as usize).checked_sub(1)
+                            .and_then(|depth| self.thread.frames.get(depth))
+                            .and_then(|frame| self.classes.class(frame.class).methods.iter().find(|method| method.name == frame.method_name && method.descriptor == frame.descriptor))
+                            .map(|method| method.access_flags & 0x0008 != 0)
+                            .unwrap_or(true);
+                        let this = if is_static { None } else { frame.local_variables.first() };
+                        self.debug_value(&mut out, this, b'L');
+                        Ok(out)
+                    }
+                    None => Err(INVALID_FRAMEID)
+                }
+            }
+
+            _ => Err(NOT_IMPLEMENTED)
+        };
+
+        debug!(command_set = packet.command_set, command = packet.command, error = ?result.as_ref().err(), "jdwp command");
+        debugger.reply(packet.id, result);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, Runtime, StackValue};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    use super::*;
+
+    struct Client {
+        stream: TcpStream,
+        next_id: u32,
+    }
+
+    impl Client {
+        fn command(&mut self, command_set: u8, command: u8, data: &[u8]) -> Vec<u8> {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.stream.write_all(&((11 + data.len()) as u32).to_be_bytes()).unwrap();
+            self.stream.write_all(&id.to_be_bytes()).unwrap();
+            self.stream.write_all(&[0, command_set, command]).unwrap();
+            self.stream.write_all(data).unwrap();
+
+            let reply = self.packet();
+            assert_eq!(0, u16::from_be_bytes([reply[9], reply[10]]), "error reply to {}/{}", command_set, command);
+            reply[11..].to_vec()
+        }
+
+        fn packet(&mut self) -> Vec<u8> {
+            let mut length = [0u8; 4];
+            self.stream.read_exact(&mut length).unwrap();
+            let mut packet = vec![0u8; u32::from_be_bytes(length) as usize - 4];
+            self.stream.read_exact(&mut packet).unwrap();
+            let mut full = length.to_vec();
+            full.extend(packet);
+            full
+        }
+    }
+
+    #[test]
+    fn oversized_packets_are_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        client.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        client.write_all(&[0, 0, 0, 1, 0, 1, 1]).unwrap();
+
+        let err = read_packet(&mut server).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn debuggers_can_set_breakpoints_and_inspect_locals() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let vm = thread::spawn(move || {
+            let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+            let (stream, _) = listener.accept().unwrap();
+            rt.attach_debugger(stream).unwrap();
+            rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(40), LocalVariable::Integer(2)])
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(HANDSHAKE).unwrap();
+        let mut handshake = [0u8; 14];
+        stream.read_exact(&mut handshake).unwrap();
+        assert_eq!(HANDSHAKE, &handshake[..]);
+        let mut client = Client { stream, next_id: 1 };
+
+        let start = client.packet();
+        assert_eq!((64, 100), (start[9], start[10]));
+        assert_eq!(EVENT_VM_START, start[11 + 5]);
+
+        let mut signature = Output::default();
+        signature.string("LTiny;");
+        let classes = client.command(1, 2, &signature.0);
+        assert_eq!(1, Input { data: &classes }.u4());
+        let class_id = Input { data: &classes[5..] }.id();
+
+        let mut class = Output::default();
+        class.id(class_id);
+        let methods = client.command(2, 5, &class.0);
+        let mut input = Input { data: &methods };
+        let count = input.u4();
+        let add = (0..count).map(|_| {
+            let id = input.id();
+            let name = input.string();
+            input.string();
+            input.u4();
+            (id, name)
+        }).find(|(_, name)| name == "add").unwrap().0;
+
+        let mut breakpoint = Output::default();
+        breakpoint.u1(EVENT_BREAKPOINT).u1(SUSPEND_ALL).u4(1).u1(7).location(class_id, add, 2);
+        client.command(15, 1, &breakpoint.0);
+        client.command(1, 9, &[]);
+
+        let hit = client.packet();
+        assert_eq!(EVENT_BREAKPOINT, hit[11 + 5]);
+        let mut input = Input { data: &hit[11 + 5 + 1 + 4 + 8..] };
+        assert_eq!((class_id, add, 2), input.location());
+
+        let mut frames = Output::default();
+        frames.id(THREAD_ID).u4(0).u4(1);
+        let frames = client.command(11, 6, &frames.0);
+        let frame_id = Input { data: &frames[4..] }.id();
+
+        let mut values = Output::default();
+        values.id(THREAD_ID).id(frame_id).u4(2).u4(0).u1(b'I').u4(1).u1(b'I');
+        let values = client.command(16, 1, &values.0);
+        let mut input = Input { data: &values };
+        assert_eq!(2, input.u4());
+        assert_eq!((b'I', 40), (input.u1(), input.u4()));
+        assert_eq!((b'I', 2), (input.u1(), input.u4()));
+
+        client.command(1, 9, &[]);
+        match vm.join().unwrap() {
+            Ok(Some(StackValue::Integer(42))) => (),
+            other => panic!("expected 42, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use java::class_file::{ClassFile, ConstantType, Method, MethodDescriptor};
+use java::runtime::{LocalVariable, ObjectData, ReferenceKind, ResolvedMethod, Runtime, RuntimeConstantPool, RuntimeError, StackValue};
+use std::str::FromStr;
+use std::sync::Arc;
+use super::StackFrame;
+use super::call_site::Bootstrap;
+use super::heap::HEADER_SIZE;
+
+/// the class `LambdaMetafactory` spins for a lambda or method reference. its objects implement
+/// the single abstract method of a functional interface by calling the target with the values
+/// captured when the object was created, which are stored in the fields `arg$1`, `arg$2`, ….
+#[derive(Debug)]
+pub struct LambdaClass {
+    /// named after the class containing the lambda, like `Main$$Lambda$1`.
+    pub class_name: String,
+    pub interface: String,
+    pub method_name: String,
+    /// the erased descriptor of the interface method.
+    pub descriptor: String,
+    /// the descriptor of the `invokedynamic` call site, taking the captured values.
+    pub factory_descriptor: String,
+    pub kind: ReferenceKind,
+    pub target: Arc<ResolvedMethod>,
+}
+
+impl<'a> Runtime<'a> {
+    /// `LambdaMetafactory.metafactory`: spins the lambda class of a call site. the static arguments
+    /// start with the erased interface method type and the method handle of the implementation.
+    pub(super) fn link_lambda(&mut self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, bootstrap: &Bootstrap) -> Result<Arc<LambdaClass>, RuntimeError> {
+        let descriptor = match bootstrap.arguments.first().map(|&argument| (argument, class.get_constant(argument))) {
+            Some((_, Some(ConstantType::MethodType { descriptor_index }))) => match class.get_constant(*descriptor_index) {
+                Some(ConstantType::Utf8 { value }) => String::from(&**value),
+                _ => return Err(RuntimeError::InvalidConstant { index: *descriptor_index, expected: String::from("Utf8") })
+            },
+            Some((argument, _)) => return Err(RuntimeError::InvalidConstant { index: argument, expected: String::from("MethodType") }),
+            None => return Err(RuntimeError::InvalidBytecode { message: String::from("LambdaMetafactory without arguments") })
+        };
+        let (kind, target) = match bootstrap.arguments.get(1) {
+            Some(&argument) => self.resolve_method_handle(pool, class, argument)?,
+            None => return Err(RuntimeError::InvalidBytecode { message: String::from("LambdaMetafactory without implementation") })
+        };
+
+        let factory_descriptor = bootstrap.descriptor;
+        let interface = match factory_descriptor.rfind(")L") {
+            Some(start) if factory_descriptor.ends_with(';') => String::from(&factory_descriptor[start + 2..factory_descriptor.len() - 1]),
+            _ => return Err(RuntimeError::InvalidBytecode { message: format!("invokedynamic {} does not return an interface", factory_descriptor) })
+        };
+        let lambda = Arc::new(LambdaClass {
+            class_name: format!("{}$$Lambda${}", class.get_class_name(), self.lambda_classes.len() + 1),
+            interface,
+            method_name: String::from(bootstrap.name),
+            descriptor,
+            factory_descriptor: String::from(factory_descriptor),
+            kind,
+            target,
+        });
+        debug!(class = %lambda.class_name, interface = %lambda.interface, target = %lambda.target.method_name, "linked lambda");
+
+        self.lambda_classes.insert(lambda.class_name.clone(), lambda.clone());
+        // the interface has another implementation now
+        self.classes.invalidate_hierarchy();
+        Ok(lambda)
+    }
+
+    /// creates an object of a lambda class, capturing the arguments on the stack.
+    pub(super) fn new_lambda(&mut self, method: &Method, stack_frame: &mut StackFrame, lambda: &LambdaClass) -> Result<(), RuntimeError> {
+        self.reserve(method, stack_frame, HEADER_SIZE)?;
+
+        let captured = self.pop_arguments(stack_frame, &lambda.factory_descriptor, false)?;
+        let reference = self.allocate(&lambda.class_name, ObjectData::Instance);
+        if let Some(object) = self.heap.get_mut(reference) {
+            for (index, value) in captured.into_iter().enumerate() {
+                object.fields.insert(format!("arg${}", index + 1), StackValue::from(value));
+            }
+        }
+
+        stack_frame.push_stack(StackValue::Reference(reference));
+        Ok(())
+    }
+
+    /// the lambda class `class_name` was spun for, `None` for every other class.
+    pub(super) fn lambda_class(&self, class_name: &str) -> Option<Arc<LambdaClass>> {
+        self.lambda_classes.get(class_name).cloned()
+    }
+
+    /// calls a method on a lambda object, the first of the `arguments`. the interface method runs
+    /// the target, everything else goes to the default methods of the interface.
+    pub(super) fn invoke_lambda(&mut self, method: &Method, lambda: &LambdaClass, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        if method_name != lambda.method_name {
+            return self.invoke(&lambda.interface, method_name, descriptor, arguments);
+        }
+
+        let count = MethodDescriptor::from_str(&lambda.factory_descriptor).map(|factory| factory.arguments.len()).unwrap_or(0);
+        let mut arguments = arguments.into_iter();
+        let captured = match arguments.next() {
+            Some(LocalVariable::Reference(this)) => self.heap.get(this).map(|object| (1..=count)
+                .map(|index| object.fields.get(&format!("arg${}", index)).cloned().map_or(LocalVariable::None, LocalVariable::from))
+                .collect::<Vec<_>>()).unwrap_or_default(),
+            _ => return Err(RuntimeError::StackType { expected: String::from("reference") })
+        };
+        let mut arguments = captured.into_iter().chain(arguments).collect::<Vec<_>>();
+
+        let target = &lambda.target;
+        let result = match lambda.kind {
+            ReferenceKind::InvokeStatic => {
+                self.initialize_class(method, &target.class_name)?;
+                self.invoke_resolved(target, arguments)?
+            }
+            ReferenceKind::InvokeSpecial => self.invoke_resolved(target, arguments)?,
+            ReferenceKind::InvokeVirtual | ReferenceKind::InvokeInterface => {
+                let receiver_class = match arguments.first() {
+                    Some(LocalVariable::Reference(receiver)) => self.heap.get(*receiver).map(|object| object.class_name.clone()).unwrap_or_default(),
+                    Some(LocalVariable::Null) => return Err(self.throw(method, "java/lang/NullPointerException", None)),
+                    _ => return Err(RuntimeError::StackType { expected: String::from("reference") })
+                };
+                match self.lambda_class(&receiver_class) {
+                    Some(receiver) => self.invoke_lambda(method, &receiver, &target.method_name, &target.descriptor, arguments)?,
+                    None => self.invoke(&receiver_class, &target.method_name, &target.descriptor, arguments)?
+                }
+            }
+            ReferenceKind::NewInvokeSpecial => {
+                self.initialize_class(method, &target.class_name)?;
+                let object = self.allocate(&target.class_name, ObjectData::Instance);
+                arguments.insert(0, LocalVariable::Reference(object));
+                self.invoke_resolved(target, arguments)?;
+                Some(StackValue::Reference(object))
+            }
+        };
+
+        Ok(if lambda.descriptor.ends_with(")V") { None } else { result })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Lambdas.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/IntOp.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/LambdasFactory.class")).unwrap().1);
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> i64 {
+        match rt.invoke_static("Lambdas", name, descriptor, arguments) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn lambdas_capture_their_arguments() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "nonCapturing", "()I", vec![]), 42);
+        assert_eq!(int(&mut rt, "capturing", "(I)I", vec![LocalVariable::Integer(5)]), 15);
+        assert_eq!(int(&mut rt, "instanceLambda", "()I", vec![]), 21);
+        // a lambda capturing another lambda
+        assert_eq!(int(&mut rt, "nested", "()I", vec![]), 9);
+        assert!(rt.heap().objects().any(|(_, object)| object.class_name == "Lambdas$$Lambda$1"));
+    }
+
+    #[test]
+    fn method_references_call_their_target() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "staticReference", "()I", vec![]), 42);
+        assert_eq!(int(&mut rt, "boundReference", "()I", vec![]), 6);
+        assert_eq!(int(&mut rt, "unboundReference", "()I", vec![]), 9);
+        assert_eq!(int(&mut rt, "constructorReference", "()I", vec![]), 4);
+    }
+
+    #[test]
+    fn call_sites_are_linked_once() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "capturing", "(I)I", vec![LocalVariable::Integer(1)]), 11);
+        assert_eq!(int(&mut rt, "capturing", "(I)I", vec![LocalVariable::Integer(2)]), 12);
+        assert_eq!(rt.lambda_classes.len(), 1);
+        assert!(rt.is_assignable("Lambdas$$Lambda$1", "java/util/function/IntUnaryOperator"));
+    }
+}
@@ -0,0 +1,222 @@
+use java::class_file::ClassFile;
+use java::runtime::{ObjectRef, Runtime, StackValue};
+use std::collections::{HashMap, HashSet};
+
+/// a class loader of the embedder, created with `Runtime::create_class_loader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoaderId(u32);
+
+#[derive(Debug, Default)]
+struct Loader {
+    /// the classes the loader defined, in load order.
+    classes: Vec<String>,
+    /// set by `Runtime::release_class_loader`, from then on only its classes keep the loader alive.
+    released: bool,
+    unloaded: bool,
+}
+
+/// the class loaders of a `Runtime` besides the boot loader, which defines the classes of
+/// `Runtime::load_class` and never unloads them.
+#[derive(Debug, Default)]
+pub struct ClassLoaders {
+    loaders: Vec<Loader>,
+    /// the defining loader of each class which is not defined by the boot loader.
+    defining: HashMap<String, LoaderId>,
+}
+
+impl ClassLoaders {
+    pub fn new() -> ClassLoaders {
+        ClassLoaders { loaders: Vec::new(), defining: HashMap::new() }
+    }
+
+    /// the loader of a class, of the array classes of its instances and of the lambdas it spun.
+    /// `None` for the classes of the boot loader.
+    pub fn loader_of(&self, class_name: &str) -> Option<LoaderId> {
+        let element = class_name.trim_start_matches('[');
+        let element = if element.len() < class_name.len() && element.starts_with('L') { &element[1..element.len() - 1] } else { element };
+        let host = element.split("$$Lambda$").next().unwrap_or(element);
+        self.defining.get(host).cloned()
+    }
+
+    /// the loaders which were released but not unloaded yet.
+    pub(super) fn collectable(&self) -> Vec<LoaderId> {
+        self.loaders.iter().enumerate()
+            .filter(|(_, loader)| loader.released && !loader.unloaded)
+            .map(|(index, _)| LoaderId(index as u32))
+            .collect()
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// creates a class loader for classes which should be unloaded once they are no longer used,
+    /// like the plugins of a long running embedder.
+    pub fn create_class_loader(&mut self) -> LoaderId {
+        self.loaders.loaders.push(Loader::default());
+        LoaderId(self.loaders.loaders.len() as u32 - 1)
+    }
+
+    /// loads a class with `loader` as its defining loader.
+    pub fn load_class_in(&mut self, loader: LoaderId, class: ClassFile<'a>) {
+        let class_name = String::from(class.get_class_name());
+        self.load_class(class);
+        self.loaders.loaders[loader.0 as usize].classes.push(class_name.clone());
+        self.loaders.defining.insert(class_name, loader);
+    }
+
+    /// the embedder no longer uses `loader`. its classes are unloaded by the first garbage collection
+    /// which finds no instance, `Class` object or running method of any of them, together with their
+    /// static fields, decoded methods and constant pools.
+    pub fn release_class_loader(&mut self, loader: LoaderId) {
+        self.loaders.loaders[loader.0 as usize].released = true;
+    }
+
+    /// the loader which defined a loaded class, `None` for the boot loader.
+    pub fn class_loader(&self, class_name: &str) -> Option<LoaderId> {
+        self.loaders.loader_of(class_name)
+    }
+
+    /// true once the classes of `loader` were unloaded.
+    pub fn is_unloaded(&self, loader: LoaderId) -> bool {
+        self.loaders.loaders[loader.0 as usize].unloaded
+    }
+
+    /// true if the roots of a collection should include the static fields and `Class` object of `class_name`.
+    pub(super) fn is_permanent_class(&self, class_name: &str, collectable: &[LoaderId]) -> bool {
+        self.loaders.loader_of(class_name).is_none_or(|loader| !collectable.contains(&loader))
+    }
+
+    /// marks the objects reachable through released loaders which are still in use and returns the
+    /// loaders which are not. a loader is in use while one of its classes has a running method or a
+    /// marked instance or `Class` object, its static fields are marked then too.
+    pub(super) fn mark_class_loaders(&mut self) -> Vec<LoaderId> {
+        let collectable = self.loaders.collectable();
+        if collectable.is_empty() {
+            return collectable;
+        }
+
+        let class_objects = self.class_objects.iter()
+            .filter_map(|(class_name, class)| self.loaders.loader_of(class_name).map(|loader| (*class, loader)))
+            .collect::<HashMap<_, _>>();
+        let mut live = self.thread.frames.iter()
+            .filter_map(|frame| self.loaders.loader_of(self.classes.class(frame.class).get_class_name()))
+            .collect::<HashSet<_>>();
+        let mut marked_live = HashSet::new();
+        loop {
+            for (reference, object) in self.heap.objects().filter(|(_, object)| object.header.is_marked()) {
+                live.extend(self.loaders.loader_of(&object.class_name));
+                live.extend(class_objects.get(&reference).cloned());
+            }
+
+            let newly_live = live.iter().filter(|loader| collectable.contains(loader) && !marked_live.contains(*loader)).cloned().collect::<Vec<_>>();
+            if newly_live.is_empty() {
+                break;
+            }
+            for loader in newly_live {
+                marked_live.insert(loader);
+                let roots = self.class_roots(loader);
+                self.heap.mark(&roots);
+            }
+        }
+
+        collectable.into_iter().filter(|loader| !live.contains(loader)).collect()
+    }
+
+    /// the static fields and `Class` objects of the classes of a loader.
+    fn class_roots(&self, loader: LoaderId) -> Vec<ObjectRef> {
+        let classes = &self.loaders.loaders[loader.0 as usize].classes;
+        let statics = self.statics.iter()
+            .filter(|((class_name, _), _)| classes.contains(class_name))
+            .filter_map(|(_, value)| match value {
+                StackValue::Reference(reference) => Some(*reference),
+                _ => None
+            });
+        let class_objects = classes.iter().filter_map(|class_name| self.class_objects.get(class_name).cloned());
+        statics.chain(class_objects).collect()
+    }
+
+    /// forgets the classes of a loader which is no longer in use. returns the number of classes unloaded.
+    pub(super) fn unload_classes(&mut self, loader: LoaderId) -> usize {
+        let state = &mut self.loaders.loaders[loader.0 as usize];
+        state.unloaded = true;
+        let classes = std::mem::take(&mut state.classes);
+
+        for class_name in classes.iter() {
+            debug!(class = class_name.as_str(), "unloaded class");
+            self.loaders.defining.remove(class_name);
+            if let Some(id) = self.classes.unload(class_name) {
+                self.counters.forget(id);
+            }
+            self.initialization.remove(class_name);
+            self.class_objects.remove(class_name);
+            self.class_bytes.remove(class_name);
+            let lambdas = format!("{}$$Lambda$", class_name);
+            self.lambda_classes.retain(|name, _| !name.starts_with(&lambdas));
+            for hook in self.hooks.iter_mut() {
+                hook.on_class_unload(class_name);
+            }
+        }
+        self.statics.retain(|(class_name, _), _| !classes.contains(class_name));
+
+        classes.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::LocalVariable;
+    use super::*;
+
+    fn runtime<'a>() -> (Runtime<'a>, LoaderId) {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Plugins.class")).unwrap().1);
+        let loader = rt.create_class_loader();
+        rt.load_class_in(loader, read_class_file(include_bytes!("../../../sample/Plugin.class")).unwrap().1);
+        (rt, loader)
+    }
+
+    fn create(rt: &mut Runtime) -> LocalVariable {
+        match rt.invoke_static("Plugin", "create", "()Ljava/lang/Object;", vec![]) {
+            Ok(Some(StackValue::Reference(plugin))) => LocalVariable::Reference(plugin),
+            other => panic!("create returned {:?}", other)
+        }
+    }
+
+    #[test]
+    fn released_loaders_are_unloaded_when_unused() {
+        let (mut rt, loader) = runtime();
+        create(&mut rt);
+        assert_eq!(rt.class_loader("Plugin"), Some(loader));
+        assert_eq!(rt.class_loader("[LPlugin;"), Some(loader));
+        assert_eq!(rt.class_loader("Plugins"), None);
+
+        // the embedder still holds the loader
+        assert_eq!(rt.gc().unloaded_classes, 0);
+        assert!(rt.classes.get("Plugin").is_some());
+
+        // Plugin.last only keeps the plugin reachable through the class itself
+        rt.release_class_loader(loader);
+        let event = rt.gc();
+        assert_eq!(event.unloaded_classes, 1);
+        assert!(rt.is_unloaded(loader));
+        assert!(rt.classes.get("Plugin").is_none());
+        assert!(rt.statics.keys().all(|(class_name, _)| class_name != "Plugin"));
+        assert!(rt.heap().objects().all(|(_, object)| object.class_name != "Plugin"));
+    }
+
+    #[test]
+    fn instances_keep_their_loader_alive() {
+        let (mut rt, loader) = runtime();
+        let plugin = create(&mut rt);
+        rt.invoke_static("Plugins", "keep", "(Ljava/lang/Object;)V", vec![plugin]).unwrap();
+        rt.release_class_loader(loader);
+
+        assert_eq!(rt.gc().unloaded_classes, 0);
+        create(&mut rt);
+        // the static field survived the collection
+        assert_eq!(rt.invoke_static("Plugin", "instances", "()I", vec![]).unwrap(), Some(StackValue::Integer(2)));
+
+        rt.invoke_static("Plugins", "keep", "(Ljava/lang/Object;)V", vec![LocalVariable::Null]).unwrap();
+        assert_eq!(rt.gc().unloaded_classes, 1);
+        assert_eq!(rt.gc_stats().unloaded_classes, 1);
+    }
+}
@@ -0,0 +1,508 @@
+use java::class_file::{MethodDescriptor, ValueType};
+use java::runtime::builtin;
+use super::constant_pool::ACC_STATIC;
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::str::FromStr;
+
+pub(super) const METHOD_HANDLES: &str = "java/lang/invoke/MethodHandles";
+pub(super) const LOOKUP: &str = "java/lang/invoke/MethodHandles$Lookup";
+pub(super) const METHOD_TYPE: &str = "java/lang/invoke/MethodType";
+pub(super) const METHOD_HANDLE: &str = "java/lang/invoke/MethodHandle";
+const WRONG_METHOD_TYPE: &str = "java/lang/invoke/WrongMethodTypeException";
+
+const FIND_METHOD: &str = "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;";
+const FIND_FIELD: &str = "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/MethodHandle;";
+const FIND_VAR_HANDLE: &str = "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;)Ljava/lang/invoke/VarHandle;";
+
+/// what a method handle created by a `MethodHandles.Lookup` invokes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum HandleKind {
+    /// `findStatic`
+    Static,
+    /// `findVirtual`, selecting the method by the class of the receiver.
+    Virtual,
+    /// `findGetter`
+    Getter,
+    /// `findSetter`
+    Setter,
+}
+
+impl HandleKind {
+    fn from_i64(kind: i64) -> Option<HandleKind> {
+        match kind {
+            0 => Some(HandleKind::Static),
+            1 => Some(HandleKind::Virtual),
+            2 => Some(HandleKind::Getter),
+            3 => Some(HandleKind::Setter),
+            _ => None
+        }
+    }
+
+    fn to_i64(self) -> i64 {
+        match self {
+            HandleKind::Static => 0,
+            HandleKind::Virtual => 1,
+            HandleKind::Getter => 2,
+            HandleKind::Setter => 3,
+        }
+    }
+}
+
+/// the type of a field descriptor.
+pub(super) fn field_type(descriptor: &str) -> Result<ValueType, RuntimeError> {
+    parse_descriptor(&format!("(){}", descriptor)).map(|method_type| method_type.return_type)
+        .map_err(|_| RuntimeError::ClassFormat { message: format!("invalid field descriptor {}", descriptor) })
+}
+
+/// the parsed method descriptor of a call site or method type.
+pub(super) fn parse_descriptor(descriptor: &str) -> Result<MethodDescriptor, RuntimeError> {
+    MethodDescriptor::from_str(descriptor)
+        .map_err(|_| RuntimeError::ClassFormat { message: format!("invalid method descriptor {}", descriptor) })
+}
+
+fn is_reference(value_type: &ValueType) -> bool {
+    matches!(value_type, ValueType::Object(_) | ValueType::Array(_))
+}
+
+/// true if a value of type `from` can be passed as `to`: the same type, a widening of a primitive
+/// or any reference type, whose class is not checked.
+fn convertible(from: &ValueType, to: &ValueType) -> bool {
+    match (from, to) {
+        (from, to) if from == to => true,
+        (ValueType::Byte, ValueType::Short) | (ValueType::Byte, ValueType::Integer) | (ValueType::Short, ValueType::Integer) |
+        (ValueType::Char, ValueType::Integer) => true,
+        (ValueType::Byte, ValueType::Long) | (ValueType::Short, ValueType::Long) | (ValueType::Char, ValueType::Long) |
+        (ValueType::Integer, ValueType::Long) => true,
+        (from, to) => is_reference(from) && is_reference(to)
+    }
+}
+
+/// true if a call of type `from` can go to a method of type `to`. results may also be dropped, or
+/// be zero or null if the method returns nothing.
+fn method_convertible(from: &MethodDescriptor, to: &MethodDescriptor) -> bool {
+    from.arguments.len() == to.arguments.len()
+        && from.arguments.iter().zip(to.arguments.iter()).all(|(from, to)| convertible(from, to))
+        && (from.return_type == ValueType::Void || to.return_type == ValueType::Void || convertible(&to.return_type, &from.return_type))
+}
+
+pub(super) fn convert(value: LocalVariable, to: &ValueType) -> LocalVariable {
+    match (value, to) {
+        (LocalVariable::Integer(value), ValueType::Long) => LocalVariable::Long(value),
+        (value, _) => value
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// implements the subset of `java.lang.invoke` which looks up and invokes method handles:
+    /// `MethodHandles.lookup`, `MethodType.methodType`, the `find` methods of `Lookup` and `bindTo`,
+    /// `asType`, `invokeExact` and `invoke` of `MethodHandle`. lookups do not check access.
+    ///
+    /// handles and method types are plain objects which keep their state in fields, so the garbage
+    /// collector and heap dumps see them like any other object.
+    pub(super) fn invoke_method_handles(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let argument = |index: usize| match arguments.get(index) {
+            Some(LocalVariable::Reference(reference)) => Some(*reference),
+            _ => None
+        };
+
+        let result = match (class_name, method_name, descriptor) {
+            (METHOD_HANDLES, "lookup", "()Ljava/lang/invoke/MethodHandles$Lookup;") => self.lookup(),
+            (METHOD_TYPE, "methodType", _) => self.method_type(arguments),
+            (METHOD_TYPE, "toMethodDescriptorString", "()Ljava/lang/String;") => match argument(0).and_then(|method_type| self.string_field(method_type, "descriptor")) {
+                Some(descriptor) => Ok(StackValue::Reference(self.intern(&descriptor))),
+                None => Err(self.throw_in_caller("java/lang/NullPointerException", None))
+            },
+            (LOOKUP, "lookupClass", "()Ljava/lang/Class;") =>
+                Ok(argument(0).and_then(|lookup| self.heap.get(lookup)).and_then(|lookup| lookup.fields.get("lookupClass").cloned()).unwrap_or(StackValue::Null)),
+            (LOOKUP, "findStatic", FIND_METHOD) => self.find_method(HandleKind::Static, argument(1), argument(2), argument(3)),
+            (LOOKUP, "findVirtual", FIND_METHOD) => self.find_method(HandleKind::Virtual, argument(1), argument(2), argument(3)),
+            (LOOKUP, "findGetter", FIND_FIELD) => self.find_field(HandleKind::Getter, argument(1), argument(2), argument(3)),
+            (LOOKUP, "findSetter", FIND_FIELD) => self.find_field(HandleKind::Setter, argument(1), argument(2), argument(3)),
+            (LOOKUP, "findVarHandle", FIND_VAR_HANDLE) => self.find_var_handle(false, argument(1), argument(2), argument(3)),
+            (LOOKUP, "findStaticVarHandle", FIND_VAR_HANDLE) => self.find_var_handle(true, argument(1), argument(2), argument(3)),
+            (METHOD_HANDLE, "type", "()Ljava/lang/invoke/MethodType;") =>
+                Ok(argument(0).and_then(|handle| self.heap.get(handle)).and_then(|handle| handle.fields.get("type").cloned()).unwrap_or(StackValue::Null)),
+            (METHOD_HANDLE, "bindTo", "(Ljava/lang/Object;)Ljava/lang/invoke/MethodHandle;") => match argument(0) {
+                Some(handle) => self.bind_to(handle, arguments.get(1).cloned().map_or(StackValue::Null, StackValue::from)),
+                None => Err(self.throw_in_caller("java/lang/NullPointerException", None))
+            },
+            (METHOD_HANDLE, "asType", "(Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;") => match (argument(0), argument(1)) {
+                (Some(handle), Some(method_type)) => self.as_type(handle, method_type),
+                _ => Err(self.throw_in_caller("java/lang/NullPointerException", None))
+            },
+            // signature polymorphic, the descriptor is the one of the call site
+            (METHOD_HANDLE, "invokeExact", _) => return Some(self.invoke_handle(true, descriptor, arguments)),
+            (METHOD_HANDLE, "invoke", _) => return Some(self.invoke_handle(false, descriptor, arguments)),
+            _ => return None
+        };
+
+        Some(result.map(Some))
+    }
+
+    /// `MethodHandles.lookup`, for the class of the calling method.
+    fn lookup(&mut self) -> Result<StackValue, RuntimeError> {
+        let (caller, _) = self.caller()?;
+        let lookup_class = self.class_object(caller.get_class_name());
+        let lookup = self.allocate(LOOKUP, ObjectData::Instance);
+        if let Some(object) = self.heap.get_mut(lookup) {
+            object.fields.insert(String::from("lookupClass"), StackValue::Reference(lookup_class));
+        }
+        Ok(StackValue::Reference(lookup))
+    }
+
+    /// `MethodType.methodType`, with the return type first and then the parameter types, given one
+    /// by one or as an array.
+    fn method_type(&mut self, arguments: &[LocalVariable]) -> Result<StackValue, RuntimeError> {
+        let mut classes = Vec::new();
+        for argument in arguments {
+            match argument {
+                LocalVariable::Reference(class) => match self.heap.get(*class).map(|object| &object.data) {
+                    Some(ObjectData::Array(elements)) => classes.extend(elements.iter().map(|element| match element {
+                        StackValue::Reference(class) => Some(*class),
+                        _ => None
+                    })),
+                    _ => classes.push(Some(*class))
+                },
+                _ => classes.push(None)
+            }
+        }
+
+        let mut types = Vec::new();
+        for class in classes {
+            match class.and_then(|class| self.class_type(class)) {
+                Some(value_type) => types.push(value_type),
+                None => return Err(self.throw_in_caller("java/lang/NullPointerException", None))
+            }
+        }
+        if types.iter().skip(1).any(|value_type| *value_type == ValueType::Void) {
+            return Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("parameter type cannot be void")));
+        }
+
+        let return_type = types.remove(0);
+        Ok(StackValue::Reference(self.new_method_type(&MethodDescriptor { return_type, arguments: types })))
+    }
+
+    /// the type a `java/lang/Class` instance stands for, `int` being the class of the primitive type.
+    pub(super) fn class_type(&self, class: ObjectRef) -> Option<ValueType> {
+        let name = self.class_object_name(class)?;
+        Some(match name.as_str() {
+            "void" => ValueType::Void,
+            "int" => ValueType::Integer,
+            "long" => ValueType::Long,
+            "boolean" => ValueType::Boolean,
+            "byte" => ValueType::Byte,
+            "char" => ValueType::Char,
+            "short" => ValueType::Short,
+            array if array.starts_with('[') => field_type(array).ok()?,
+            class_name => ValueType::Object(String::from(class_name))
+        })
+    }
+
+    fn new_method_type(&mut self, descriptor: &MethodDescriptor) -> ObjectRef {
+        let descriptor = self.intern(&descriptor.to_string());
+        let method_type = self.allocate(METHOD_TYPE, ObjectData::Instance);
+        if let Some(object) = self.heap.get_mut(method_type) {
+            object.fields.insert(String::from("descriptor"), StackValue::Reference(descriptor));
+        }
+        method_type
+    }
+
+    pub(super) fn string_field(&self, object: ObjectRef, name: &str) -> Option<String> {
+        match self.heap.get(object).and_then(|object| object.fields.get(name)) {
+            Some(StackValue::Reference(value)) => self.heap.string_value(*value).map(String::from),
+            _ => None
+        }
+    }
+
+    fn handle_type(&self, handle: ObjectRef) -> Option<MethodDescriptor> {
+        let method_type = match self.heap.get(handle).and_then(|handle| handle.fields.get("type")) {
+            Some(StackValue::Reference(method_type)) => *method_type,
+            _ => return None
+        };
+        self.string_field(method_type, "descriptor").and_then(|descriptor| MethodDescriptor::from_str(&descriptor).ok())
+    }
+
+    /// `findStatic` and `findVirtual`. throws a `NoSuchMethodException` if a loaded class has no
+    /// such method, and an `IllegalAccessException` if it is static but should not be or the other way round.
+    fn find_method(&mut self, kind: HandleKind, class: Option<ObjectRef>, name: Option<ObjectRef>, method_type: Option<ObjectRef>) -> Result<StackValue, RuntimeError> {
+        let class_name = class.and_then(|class| self.class_object_name(class));
+        let method_name = name.and_then(|name| self.heap.string_value(name)).map(String::from);
+        let descriptor = method_type.and_then(|method_type| self.string_field(method_type, "descriptor"));
+        let (class_name, method_name, descriptor) = match (class_name, method_name, descriptor) {
+            (Some(class_name), Some(method_name), Some(descriptor)) => (class_name, method_name, descriptor),
+            _ => return Err(self.throw_in_caller("java/lang/NullPointerException", None))
+        };
+
+        let method = format!("{}.{}{}", class_name.replace('/', "."), method_name, descriptor);
+        if !builtin::is_builtin(&class_name) {
+            let (id, slot) = match self.method_target(&class_name, &method_name, &descriptor) {
+                Some(target) => target,
+                None => return Err(self.throw_in_caller("java/lang/NoSuchMethodException", Some(&format!("no such method: {}", method))))
+            };
+            let is_static = self.classes.class(id).methods[slot].access_flags & ACC_STATIC != 0;
+            if is_static != (kind == HandleKind::Static) {
+                let message = format!("{} is {}static", method, if is_static { "" } else { "not " });
+                return Err(self.throw_in_caller("java/lang/IllegalAccessException", Some(&message)));
+            }
+        }
+
+        let mut handle_type = parse_descriptor(&descriptor)?;
+        if kind == HandleKind::Virtual {
+            handle_type.arguments.insert(0, ValueType::Object(class_name.clone()));
+        }
+        Ok(StackValue::Reference(self.new_handle(kind, &class_name, &method_name, &descriptor, &handle_type)))
+    }
+
+    /// `findGetter` and `findSetter` of an instance field. throws a `NoSuchFieldException` if a
+    /// loaded class has no such field.
+    fn find_field(&mut self, kind: HandleKind, class: Option<ObjectRef>, name: Option<ObjectRef>, field_class: Option<ObjectRef>) -> Result<StackValue, RuntimeError> {
+        let class_name = class.and_then(|class| self.class_object_name(class));
+        let field_name = name.and_then(|name| self.heap.string_value(name)).map(String::from);
+        let value_type = field_class.and_then(|field_class| self.class_type(field_class));
+        let (class_name, field_name, value_type) = match (class_name, field_name, value_type) {
+            (Some(class_name), Some(field_name), Some(value_type)) => (class_name, field_name, value_type),
+            _ => return Err(self.throw_in_caller("java/lang/NullPointerException", None))
+        };
+
+        let descriptor = value_type.to_string();
+        if self.classes.get(&class_name).is_some() && self.field_owner(&class_name, &field_name, &descriptor).is_none() {
+            let message = format!("no such field: {}.{}/{}", class_name.replace('/', "."), field_name, descriptor);
+            return Err(self.throw_in_caller("java/lang/NoSuchFieldException", Some(&message)));
+        }
+
+        let receiver = ValueType::Object(class_name.clone());
+        let handle_type = match kind {
+            HandleKind::Getter => MethodDescriptor { return_type: value_type, arguments: vec![receiver] },
+            _ => MethodDescriptor { return_type: ValueType::Void, arguments: vec![receiver, value_type] }
+        };
+        Ok(StackValue::Reference(self.new_handle(kind, &class_name, &field_name, &descriptor, &handle_type)))
+    }
+
+    /// a handle of `kind` for the member `name` of `class_name` with the method or field `descriptor`.
+    fn new_handle(&mut self, kind: HandleKind, class_name: &str, name: &str, descriptor: &str, handle_type: &MethodDescriptor) -> ObjectRef {
+        let values = [
+            ("kind", StackValue::Integer(kind.to_i64())),
+            ("class", StackValue::Reference(self.intern(class_name))),
+            ("name", StackValue::Reference(self.intern(name))),
+            ("descriptor", StackValue::Reference(self.intern(descriptor))),
+            ("type", StackValue::Reference(self.new_method_type(handle_type))),
+            ("bound", StackValue::Integer(0)),
+        ];
+
+        let handle = self.allocate(METHOD_HANDLE, ObjectData::Instance);
+        if let Some(object) = self.heap.get_mut(handle) {
+            for (name, value) in values.iter() {
+                object.fields.insert(String::from(*name), value.clone());
+            }
+        }
+        handle
+    }
+
+    /// a copy of `handle` with another type.
+    fn copy_handle(&mut self, handle: ObjectRef, handle_type: &MethodDescriptor) -> ObjectRef {
+        let fields = self.heap.get(handle).map(|object| object.fields.clone()).unwrap_or_default();
+        let method_type = self.new_method_type(handle_type);
+        let copy = self.allocate(METHOD_HANDLE, ObjectData::Instance);
+        if let Some(object) = self.heap.get_mut(copy) {
+            object.fields = fields;
+            object.fields.insert(String::from("type"), StackValue::Reference(method_type));
+        }
+        copy
+    }
+
+    /// `bindTo`, which binds the first parameter, a reference, to `value`. the bound values are kept
+    /// in the fields `bound$1`, `bound$2`, ….
+    fn bind_to(&mut self, handle: ObjectRef, value: StackValue) -> Result<StackValue, RuntimeError> {
+        let mut handle_type = self.handle_type(handle).ok_or_else(|| RuntimeError::StackType { expected: String::from(METHOD_HANDLE) })?;
+        if !handle_type.arguments.first().is_some_and(is_reference) {
+            return Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some(&format!("no leading reference parameter: {}", handle_type))));
+        }
+        handle_type.arguments.remove(0);
+
+        let bound = match self.heap.get(handle).and_then(|object| object.fields.get("bound")) {
+            Some(StackValue::Integer(bound)) => *bound + 1,
+            _ => 1
+        };
+        let copy = self.copy_handle(handle, &handle_type);
+        if let Some(object) = self.heap.get_mut(copy) {
+            object.fields.insert(String::from("bound"), StackValue::Integer(bound));
+            object.fields.insert(format!("bound${}", bound), value);
+        }
+        Ok(StackValue::Reference(copy))
+    }
+
+    /// `asType`, which adapts the handle to a type its arguments and result can be converted from and to.
+    /// throws a `WrongMethodTypeException` if they cannot.
+    fn as_type(&mut self, handle: ObjectRef, method_type: ObjectRef) -> Result<StackValue, RuntimeError> {
+        let new_type = match self.string_field(method_type, "descriptor") {
+            Some(descriptor) => parse_descriptor(&descriptor)?,
+            None => return Err(RuntimeError::StackType { expected: String::from(METHOD_TYPE) })
+        };
+        let target_type = self.target_type(handle)?;
+        if !method_convertible(&new_type, &target_type) {
+            return Err(self.throw_in_caller(WRONG_METHOD_TYPE, Some(&format!("cannot convert {} to {}", target_type, new_type))));
+        }
+
+        Ok(StackValue::Reference(self.copy_handle(handle, &new_type)))
+    }
+
+    /// the type of the method or field access behind a handle, without the bound parameters.
+    fn target_type(&self, handle: ObjectRef) -> Result<MethodDescriptor, RuntimeError> {
+        let invalid = || RuntimeError::StackType { expected: String::from(METHOD_HANDLE) };
+        let object = self.heap.get(handle).ok_or_else(invalid)?;
+        let integer = |name: &str| match object.fields.get(name) {
+            Some(StackValue::Integer(value)) => Some(*value),
+            _ => None
+        };
+        let kind = integer("kind").and_then(HandleKind::from_i64).ok_or_else(invalid)?;
+        let bound = integer("bound").unwrap_or(0) as usize;
+        let class_name = self.string_field(handle, "class").ok_or_else(invalid)?;
+        let descriptor = self.string_field(handle, "descriptor").ok_or_else(invalid)?;
+
+        let receiver = ValueType::Object(class_name);
+        let mut target_type = match kind {
+            HandleKind::Static => parse_descriptor(&descriptor)?,
+            HandleKind::Virtual => {
+                let mut target_type = parse_descriptor(&descriptor)?;
+                target_type.arguments.insert(0, receiver);
+                target_type
+            }
+            HandleKind::Getter => MethodDescriptor { return_type: field_type(&descriptor)?, arguments: vec![receiver] },
+            HandleKind::Setter => MethodDescriptor { return_type: ValueType::Void, arguments: vec![receiver, field_type(&descriptor)?] },
+        };
+        target_type.arguments.drain(..bound.min(target_type.arguments.len()));
+        Ok(target_type)
+    }
+
+    /// `invokeExact` and `invoke` with the descriptor of the call site. `invokeExact` requires the
+    /// descriptor to be the type of the handle, `invoke` converts like `asType` does.
+    fn invoke_handle(&mut self, exact: bool, descriptor: &str, arguments: &[LocalVariable]) -> Result<Option<StackValue>, RuntimeError> {
+        let handle = match arguments.first() {
+            Some(LocalVariable::Reference(handle)) => *handle,
+            _ => return Err(self.throw_in_caller("java/lang/NullPointerException", None))
+        };
+        let call_type = parse_descriptor(descriptor)?;
+        let handle_type = self.handle_type(handle).ok_or_else(|| RuntimeError::StackType { expected: String::from(METHOD_HANDLE) })?;
+        if exact && call_type != handle_type {
+            return Err(self.throw_in_caller(WRONG_METHOD_TYPE, Some(&format!("expected {} but found {}", handle_type, call_type))));
+        }
+        let target_type = self.target_type(handle)?;
+        if !method_convertible(&call_type, &target_type) {
+            return Err(self.throw_in_caller(WRONG_METHOD_TYPE, Some(&format!("cannot convert {} to {}", target_type, call_type))));
+        }
+
+        let object = self.heap.get(handle).ok_or_else(|| RuntimeError::StackType { expected: String::from(METHOD_HANDLE) })?;
+        let kind = match object.fields.get("kind") {
+            Some(StackValue::Integer(kind)) => HandleKind::from_i64(*kind),
+            _ => None
+        }.ok_or_else(|| RuntimeError::StackType { expected: String::from(METHOD_HANDLE) })?;
+        let bound = match object.fields.get("bound") {
+            Some(StackValue::Integer(bound)) => *bound as usize,
+            _ => 0
+        };
+        let mut target_arguments = (1..=bound)
+            .map(|index| object.fields.get(&format!("bound${}", index)).cloned().map_or(LocalVariable::Null, LocalVariable::from))
+            .collect::<Vec<_>>();
+        target_arguments.extend(arguments[1..].iter().cloned().zip(target_type.arguments.iter()).map(|(value, to)| convert(value, to)));
+
+        let class_name = self.string_field(handle, "class").unwrap_or_default();
+        let name = self.string_field(handle, "name").unwrap_or_default();
+        let member_descriptor = self.string_field(handle, "descriptor").unwrap_or_default();
+        let result = self.invoke_target(kind, &class_name, &name, &member_descriptor, target_arguments)?;
+
+        Ok(match (&call_type.return_type, result) {
+            (ValueType::Void, _) => None,
+            (return_type, None) => Some(Runtime::default_value(&return_type.to_string())),
+            (return_type, Some(value)) => Some(StackValue::from(convert(LocalVariable::from(value), return_type)))
+        })
+    }
+
+    fn invoke_target(&mut self, kind: HandleKind, class_name: &str, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        let (caller, slot) = self.caller()?;
+        let method = &caller.methods[slot];
+        let receiver = match (kind, arguments.first()) {
+            (HandleKind::Static, _) => None,
+            (_, Some(LocalVariable::Reference(receiver))) => Some(*receiver),
+            (_, Some(LocalVariable::Null)) => return Err(self.throw(method, "java/lang/NullPointerException", None)),
+            _ => return Err(RuntimeError::StackType { expected: String::from("reference") })
+        };
+
+        match (kind, receiver) {
+            (HandleKind::Virtual, Some(receiver)) => {
+                let receiver_class = self.heap.get(receiver).map(|object| object.class_name.clone()).unwrap_or_default();
+                match self.lambda_class(&receiver_class) {
+                    Some(lambda) => self.invoke_lambda(method, &lambda, name, descriptor, arguments),
+                    None => match self.method_target(&receiver_class, name, descriptor) {
+                        Some((class, slot)) => self.run_method(class, slot, arguments),
+                        None => self.invoke(&receiver_class, name, descriptor, arguments)
+                    }
+                }
+            }
+            (HandleKind::Getter, Some(receiver)) => Ok(Some(self.heap.get(receiver)
+                .and_then(|object| object.fields.get(name).cloned())
+                .unwrap_or_else(|| Runtime::default_value(descriptor)))),
+            (HandleKind::Setter, Some(receiver)) => {
+                let value = arguments.get(1).cloned().map_or(StackValue::Null, StackValue::from);
+                if let Some(object) = self.heap.get_mut(receiver) {
+                    object.fields.insert(String::from(name), value);
+                }
+                Ok(None)
+            }
+            _ => {
+                self.initialize_class(method, class_name)?;
+                self.invoke(class_name, name, descriptor, arguments)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Handles.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Counter.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/LoudCounter.class")).unwrap().1);
+        rt
+    }
+
+    fn call(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> StackValue {
+        match rt.invoke_static("Handles", name, descriptor, arguments) {
+            Ok(Some(value)) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn handles_invoke_methods_and_fields() {
+        let mut rt = runtime();
+        assert_eq!(call(&mut rt, "callStatic", "(II)I", vec![LocalVariable::Integer(6), LocalVariable::Integer(7)]), StackValue::Integer(42));
+        assert_eq!(call(&mut rt, "callVirtual", "(Z)I", vec![LocalVariable::Integer(0)]), StackValue::Integer(3));
+        // findVirtual selects the method of the receiver, which multiplies by ten
+        assert_eq!(call(&mut rt, "callVirtual", "(Z)I", vec![LocalVariable::Integer(1)]), StackValue::Integer(21));
+        assert_eq!(call(&mut rt, "bound", "()I", vec![]), StackValue::Integer(7));
+        assert_eq!(call(&mut rt, "fields", "()I", vec![]), StackValue::Integer(42));
+    }
+
+    #[test]
+    fn handles_check_and_convert_types() {
+        let mut rt = runtime();
+        match call(&mut rt, "types", "()Ljava/lang/String;", vec![]) {
+            StackValue::Reference(types) => assert_eq!(rt.heap().string_value(types), Some("(II)I (II)J")),
+            other => panic!("types returned {:?}", other)
+        }
+        assert_eq!(call(&mut rt, "widened", "()J", vec![]), StackValue::Long(42));
+        assert_eq!(call(&mut rt, "wrongType", "()Z", vec![]), StackValue::Integer(1));
+        assert_eq!(call(&mut rt, "missing", "()Z", vec![]), StackValue::Integer(1));
+    }
+
+    #[test]
+    fn only_primitives_and_references_convert() {
+        assert!(convertible(&ValueType::Integer, &ValueType::Long));
+        assert!(convertible(&ValueType::Object(String::from("Counter")), &ValueType::Object(String::from("java/lang/Object"))));
+        assert!(!convertible(&ValueType::Long, &ValueType::Integer));
+        assert!(!convertible(&ValueType::Integer, &ValueType::Object(String::from("java/lang/Integer"))));
+    }
+}
@@ -1,81 +1,281 @@
+mod archive;
+mod array;
+mod assertions;
+mod bench;
+mod breakpoints;
+mod buffers;
+mod builder;
+mod builtin;
+mod byte_store;
+mod call_site;
+mod cancellation;
+mod charsets;
+mod cleaner;
+mod class_load;
+mod capabilities;
+mod coverage;
+mod console;
+mod constant_pool;
+mod counters;
+mod crash;
+mod decoder;
+mod devirtualize;
+mod enums;
+mod environment;
+mod explain;
+mod fast;
+mod fields;
+mod frame_pool;
+mod frame_view;
+mod error;
+mod exit;
+#[cfg(feature = "threads")]
+mod future;
+mod gc;
+mod handlers;
+mod header;
+mod heap;
+mod initialization;
+mod inline;
+mod instrumentation;
+mod intrinsics;
+mod hooks;
+mod hprof;
+#[cfg(all(feature = "threads", feature = "sockets"))]
+mod jdwp;
+#[cfg(not(all(feature = "threads", feature = "sockets")))]
+#[path = "no_jdwp.rs"]
+mod jdwp;
+mod lambda;
+mod loaders;
+mod method_handles;
+mod modules;
+mod native;
+mod object_methods;
+mod opcode_coverage;
+mod optimize;
+mod preload;
+mod process;
+mod profiler;
+mod random;
+mod redefinition;
+mod registry;
+mod replay;
+mod safepoint;
+mod shutdown;
+mod signature;
+mod snapshot;
+mod stack_allocation;
+mod stack_trace;
+mod string_concat;
+mod symbol;
+mod thread_dump;
+mod throwable;
+mod thread;
+mod time;
+mod trace;
+mod uncaught;
+mod var_handles;
+mod visualize;
+mod watchpoints;
+
+pub use self::archive::{ArchivedClass, ClassArchive};
+pub use self::array::{component_type, ElementType};
+pub use self::assertions::AssertionStatus;
+pub use self::bench::BenchReport;
+pub use self::frame_view::{FrameValue, FrameView};
+pub use self::breakpoints::{Breakpoints, BreakpointId, CodeLocation, DebugHandler, Resume, StepMode, SuspendReason, Suspension};
+pub use self::builder::RuntimeBuilder;
+pub use self::builtin::superclass as builtin_superclass;
+pub use self::byte_store::ByteStore;
+pub use self::call_site::{CallSite, ReferenceKind};
+pub use self::cancellation::CancellationHandle;
+pub use self::class_load::ClassLoadListener;
+pub use self::capabilities::Capabilities;
+pub use self::cleaner::Cleaners;
+pub use self::coverage::{ClassCoverage, Counter, Coverage, CoverageCollector, LineCoverage, MethodCoverage};
+pub use self::console::Output;
+pub use self::constant_pool::{Resolved, ResolvedField, ResolvedMethod, RuntimeConstantPool};
+use self::constant_pool::{ACC_NATIVE, ACC_STATIC};
+pub use self::counters::{HotMethod, InvocationCounters, MethodCounters, MethodProfile};
+pub use self::crash::FrameDump;
+pub use self::decoder::{Comparison, DecodedInsn, DecodedMethod, InlineCache, Superinstruction};
+pub use self::environment::{Environment, ExecutionMode, WallClock};
+pub use self::fast::{FastMethod, InterpreterMode, SlotType, TypeState};
+pub use self::fields::{FieldLayout, FieldSlot, Fields};
+pub use self::frame_pool::FramePool;
+pub use self::error::{Location, RuntimeError};
+#[cfg(feature = "threads")]
+pub use self::future::InvokeFuture;
+pub use self::gc::{GcEvent, GcReason, GcStats};
+pub use self::handlers::{ExceptionHandler, HandlerTable};
+pub use self::header::{LockState, ObjectHeader, MAX_CLASS_INDEX};
+pub use self::heap::{Collection, Deduplication, Heap, LargeObjectSpace, Object, ObjectData, ObjectRef, DEFAULT_LARGE_OBJECT_THRESHOLD};
+pub use self::hooks::RuntimeHook;
+pub use self::initialization::InitializationState;
+pub use self::inline::{InlineBody, InlineSite};
+pub use self::instrumentation::Instrumentation;
+pub use self::intrinsics::Intrinsic;
+pub use self::hprof::HeapDumpTrigger;
+pub use self::jdwp::Debugger;
+pub use self::lambda::LambdaClass;
+pub use self::loaders::{ClassLoaders, LoaderId};
+pub use self::method_handles::HandleKind;
+pub use self::modules::{ModuleGraph, ALL_UNNAMED};
+use self::jdwp::DebugPoint;
+use self::buffers::{NativeMemory, OpenFile};
+use self::process::ChildProcess;
+use self::watchpoints::FieldAccess;
+pub use self::native::{NativeCall, NativeContext, NativeMethod, NativeRegistry};
+pub use self::object_methods::{ObjectMethod, RecordMethod};
+pub use self::opcode_coverage::OpcodeCoverage;
+pub use self::trace::{ExecutionTrace, TraceStep};
+pub use self::preload::scan_classpath;
+pub use self::profiler::{AllocationProfile, AllocationProfiler, AllocationSite, AllocationStats};
+pub use self::registry::{ClassId, ClassReader, ClassRegistry, ClassSnapshot};
+pub use self::replay::{Input, InputLog, Journal};
+pub use self::safepoint::{SafepointHandle, SafepointKind};
+pub use self::shutdown::ShutdownHook;
+pub use self::signature::{Signature, Signatures, ValueKind};
+pub use self::snapshot::{CheckpointTrigger, Snapshot, SnapshotFrame};
+pub use self::stack_allocation::FrameAllocations;
+pub use self::stack_trace::StackTraceElement;
+pub use self::string_concat::ConcatRecipe;
+pub use self::symbol::{Symbol, SymbolTable};
+pub use self::thread::{JavaThread, DEFAULT_MAX_STACK_DEPTH};
+pub use self::uncaught::UncaughtException;
+pub use self::exit::{ExitStatus, JvmError};
+pub use self::thread_dump::{ThreadDump, ThreadDumpTrigger, ThreadFrame, ThreadInfo};
+pub use self::var_handles::AccessMode;
+pub use self::visualize::{HeapChange, ObjectState, StepState, VISUALIZATION_VERSION};
+pub use self::watchpoints::{FieldAccessKind, FieldWatchEvent};
 use java::class_file::Method;
-use std::collections::HashMap;
 use java::class_file::ClassFile;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
 use std::path::PathBuf;
 use std::sync::Arc;
 use java::class_file::ConstantType;
-use java::class_file::ValueType;
-
-/// these type of errors should not happen at all.
-/// Triggering one of these means the jvm is probably buggy since the compiler should prevent these.
-/// This is for stuff like "we tried to pop the stack but it was empty" or "i need to load an int,
-/// but theres a string on the stack"….
-///
-/// we might trigger something like this when a class file does not contain the expected methods.
-/// this is something the compiler cannot prevent since the user could just swap out the class file.
-#[derive(Debug, Fail)]
-pub enum RuntimeError {
-    #[fail(display = "runtime error: {}", message)]
-    GenericError { message: String },
-    #[fail(display = "runtime error: top of stack had the wrong type. expected: {}", expected)]
-    StackType { expected: String },
-    #[fail(display = "runtime error: stack poped when empty")]
-    EmptyStack,
-    #[fail(display = "runtime error: stack poped when empty")]
-    MethodNotFound,
-}
+use java::instructions::Instruction;
+use self::heap::HEADER_SIZE;
 
-#[derive(Debug)]
-enum LocalVariable {
+/// the value of a local variable slot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalVariable {
     None,
     Null,
     Integer(i64),
+    Reference(ObjectRef),
+    /// a category 2 value, the slot after it holds `Top`.
+    Long(i64),
+    /// the second slot of a long, which cannot be read on its own.
+    Top,
+}
+
+impl LocalVariable {
+    /// the number of slots the value occupies, 2 for longs.
+    pub fn category(&self) -> usize {
+        match self {
+            LocalVariable::Long(_) => 2,
+            _ => 1
+        }
+    }
 }
 
-#[derive(Debug)]
-enum StackValue {
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackValue {
     None,
     Null,
     Integer(i64),
+    Reference(ObjectRef),
+    Long(i64),
 }
 
-#[derive(Debug)]
-struct StackFrame {
-    local_variables: Vec<LocalVariable>,
-    stack: Vec<StackValue>,
+impl From<StackValue> for LocalVariable {
+    fn from(value: StackValue) -> LocalVariable {
+        match value {
+            StackValue::None => LocalVariable::None,
+            StackValue::Null => LocalVariable::Null,
+            StackValue::Integer(value) => LocalVariable::Integer(value),
+            StackValue::Reference(reference) => LocalVariable::Reference(reference),
+            StackValue::Long(value) => LocalVariable::Long(value),
+        }
+    }
 }
 
-impl StackFrame {
-    fn create(var_count: usize, stack_size: usize) -> StackFrame {
-        StackFrame {
-            local_variables: StackFrame::init_variables(var_count),
-            stack: Vec::with_capacity(stack_size),
+impl From<LocalVariable> for StackValue {
+    fn from(value: LocalVariable) -> StackValue {
+        match value {
+            LocalVariable::None | LocalVariable::Top => StackValue::None,
+            LocalVariable::Null => StackValue::Null,
+            LocalVariable::Integer(value) => StackValue::Integer(value),
+            LocalVariable::Reference(reference) => StackValue::Reference(reference),
+            LocalVariable::Long(value) => StackValue::Long(value),
         }
     }
+}
 
-    fn init_variables(size: usize) -> Vec<LocalVariable> {
-        let mut vec = Vec::with_capacity(size);
-        for i in 0..size {
-            vec.push(LocalVariable::None);
-        }
+/// the result of a binary int instruction. ints are 32 bit and overflow wraps around like on the jvm,
+/// they are kept sign extended in the `i64` of `StackValue::Integer`. `None` for a division by zero.
+fn int_arithmetic(instruction: &Instruction, lh: i32, rh: i32) -> Option<i32> {
+    Some(match instruction {
+        Instruction::IAdd(()) => lh.wrapping_add(rh),
+        Instruction::ISub(()) => lh.wrapping_sub(rh),
+        Instruction::IMul(()) => lh.wrapping_mul(rh),
+        // Integer.MIN_VALUE / -1 overflows to Integer.MIN_VALUE
+        Instruction::IDiv(()) if rh != 0 => lh.wrapping_div(rh),
+        Instruction::IRem(()) if rh != 0 => lh.wrapping_rem(rh),
+        Instruction::IDiv(()) | Instruction::IRem(()) => return None,
+        // only the lowest five bits of the distance count
+        Instruction::IShl(()) => lh.wrapping_shl(rh as u32),
+        Instruction::IShr(()) => lh.wrapping_shr(rh as u32),
+        Instruction::IUSHR(()) => (lh as u32).wrapping_shr(rh as u32) as i32,
+        Instruction::IAnd(()) => lh & rh,
+        Instruction::IOr(()) => lh | rh,
+        Instruction::IXor(()) => lh ^ rh,
+        _ => unreachable!("{} is not a binary int instruction", instruction.name())
+    })
+}
 
-        vec
-    }
+/// what the interpreter loop does after an instruction.
+enum Flow {
+    Next,
+    /// continue at this index of the decoded instructions.
+    Jump(usize),
+    Return,
+}
 
-    /// creates a new `StackFrame` for a given method.
-    /// also inits the local variables with the given list of variables
-    fn for_method(method: &Method, mut variables: Vec<LocalVariable>) -> StackFrame {
-        let locals = usize::from(method.get_code().unwrap().max_locals);
-        let stack = usize::from(method.get_code().unwrap().max_stack);
+/// the decoded code of a method and where it lives, shared by the interpreter and the fast path.
+#[derive(Clone, Copy)]
+struct MethodContext<'c, 'a: 'c> {
+    class: &'c Arc<ClassFile<'a>>,
+    id: ClassId,
+    slot: usize,
+    pool: &'c RuntimeConstantPool,
+    decoded: &'c DecodedMethod,
+}
 
-        let mut stack = StackFrame::create(locals, stack);
-        for i in 0..variables.len() {
-            stack.local_variables[i] = variables.remove(0);
-        }
+/// a method currently executing, used for stack traces.
+/// while it calls another method, `saved` holds its frame for the garbage collector and the debugger.
+struct ActiveFrame<'a> {
+    class: ClassId,
+    slot: usize,
+    method_name: Cow<'a, str>,
+    descriptor: Cow<'a, str>,
+    pc: usize,
+    saved: Option<StackFrame>,
+    /// the objects which cannot escape the method, with the pc of the `new` which created them.
+    local_objects: Vec<(usize, ObjectRef)>,
+}
 
-        stack
-    }
+#[derive(Debug, Clone, Default)]
+struct StackFrame {
+    local_variables: Vec<LocalVariable>,
+    stack: Vec<StackValue>,
+}
 
+impl StackFrame {
     fn get_variable_mut(&mut self, index: usize) -> Option<&mut LocalVariable> {
         self.local_variables.get_mut(index)
     }
@@ -84,8 +284,27 @@ impl StackFrame {
         self.local_variables.get(index)
     }
 
-    fn set_variable(&mut self, index: usize, var: LocalVariable) {
-        self.local_variables.insert(index, var)
+    /// stores a value in slot `index`, and its `Top` in the next slot for a long.
+    /// a long that loses either of its slots becomes undefined, like the jvm treats reused slots.
+    fn set_variable(&mut self, index: usize, var: LocalVariable) -> Result<(), RuntimeError> {
+        let category = var.category();
+        if index + category > self.local_variables.len() {
+            return Err(RuntimeError::LocalOutOfRange { index: index + category - 1 });
+        }
+
+        if let LocalVariable::Top = self.local_variables[index] {
+            self.local_variables[index - 1] = LocalVariable::None;
+        }
+        // the last slot written may be the first of another long, whose top is left behind
+        if let LocalVariable::Long(_) = self.local_variables[index + category - 1] {
+            self.local_variables[index + category] = LocalVariable::None;
+        }
+
+        self.local_variables[index] = var;
+        if category == 2 {
+            self.local_variables[index + 1] = LocalVariable::Top;
+        }
+        Ok(())
     }
 
     fn pop_stack(&mut self) -> Option<StackValue> {
@@ -95,78 +314,391 @@ impl StackFrame {
     fn push_stack(&mut self, value: StackValue) {
         self.stack.push(value)
     }
+
+    /// all objects referenced by local variables or the operand stack.
+    fn references(&self) -> Vec<ObjectRef> {
+        let locals = self.local_variables.iter().filter_map(|local| match local {
+            LocalVariable::Reference(reference) => Some(*reference),
+            _ => None
+        });
+        let stack = self.stack.iter().filter_map(|value| match value {
+            StackValue::Reference(reference) => Some(*reference),
+            _ => None
+        });
+
+        locals.chain(stack).collect()
+    }
+
+    fn dump(&self, location: &Location) -> FrameDump {
+        FrameDump {
+            class_name: location.class_name.clone(),
+            method_name: location.method_name.clone(),
+            descriptor: location.descriptor.clone(),
+            pc: location.pc,
+            line: location.line,
+            locals: self.local_variables.iter().map(|local| format!("{:?}", local)).collect(),
+            stack: self.stack.iter().map(|value| format!("{:?}", value)).collect(),
+        }
+    }
 }
 
 
 pub struct Runtime<'a> {
-    classes: HashMap<String, Arc<ClassFile<'a>>>,
+    classes: ClassRegistry<'a>,
     classpath: Vec<PathBuf>,
     main_class: String,
-    class_index_map: HashMap<String, HashMap<usize, String>>,
+    cancellation: CancellationHandle,
+    environment: Environment,
+    capabilities: Capabilities,
+    natives: NativeRegistry,
+    hooks: Vec<Box<dyn RuntimeHook>>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    class_load_listeners: Vec<Box<dyn ClassLoadListener>>,
+    /// the bytes classes were defined from before the listeners transformed them, to retransform them.
+    class_bytes: HashMap<String, &'a [u8]>,
+    /// keeps the bytes the runtime creates, like those of transformed classes.
+    byte_store: Option<&'a ByteStore>,
+    crash_dump_path: Option<PathBuf>,
+    stdout: Output,
+    stderr: Output,
+    trace: Option<ExecutionTrace>,
+    heap: Heap,
+    thread: JavaThread<'a>,
+    default_uncaught_exception_handler: Option<ObjectRef>,
+    allocations: AllocationProfiler,
+    max_heap: Option<usize>,
+    max_stack_depth: usize,
+    heap_dump_path: PathBuf,
+    heap_dump_on_out_of_memory: bool,
+    heap_dump_trigger: HeapDumpTrigger,
+    thread_dump_trigger: ThreadDumpTrigger,
+    checkpoint_trigger: CheckpointTrigger,
+    safepoints: SafepointHandle,
+    verbose_gc: bool,
+    /// whether calls of registered natives are printed.
+    verbose_jni: bool,
+    /// whether collections deduplicate the characters of strings.
+    string_deduplication: bool,
+    /// whether objects of classes which override `finalize` are finalized.
+    finalization: bool,
+    cleaners: Cleaners,
+    explain: Option<Output>,
+    gc_stats: GcStats,
+    debugger: Option<Debugger>,
+    breakpoints: Breakpoints,
+    statics: HashMap<(String, String), StackValue>,
+    initialization: HashMap<String, InitializationState>,
+    /// the `java/lang/Class` instance of each class, so that class literals compare equal.
+    class_objects: HashMap<String, ObjectRef>,
+    assertions: AssertionStatus,
+    /// the classes spun for lambdas by their name.
+    lambda_classes: HashMap<String, Arc<LambdaClass>>,
+    loaders: ClassLoaders,
+    modules: ModuleGraph,
+    watchpoints: Vec<(String, String)>,
+    interpreter: InterpreterMode,
+    /// whether call sites run tiny hot methods without calling them.
+    inlining: bool,
+    /// whether calls to the methods of the class library the runtime implements itself are linked
+    /// to its implementations.
+    intrinsics: bool,
+    /// whether objects which cannot escape their method live in its frame.
+    escape_analysis: bool,
+    /// whether hot methods skip the computations constant folding of their SSA form removes.
+    optimize_bytecode: bool,
+    counters: InvocationCounters,
+    /// the processes the program started, see `ProcessBuilder`.
+    processes: Vec<ChildProcess>,
+    /// direct buffers, mapped files and the memory of `Unsafe`, by address.
+    native_memory: BTreeMap<usize, NativeMemory>,
+    /// the files opened by `RandomAccessFile`, `None` once closed.
+    files: Vec<Option<OpenFile>>,
 }
 
 
 impl<'a> Runtime<'a> {
     pub fn create(main_class: ClassFile<'a>) -> Runtime<'a> {
-        let name = String::from(main_class.get_class_name());
-        let mut rt = Runtime {
-            classes: HashMap::new(),
-            classpath: vec![PathBuf::from(".")],
-            class_index_map: HashMap::new(),
-            main_class: name,
-        };
+        RuntimeBuilder::new().build(main_class)
+    }
 
-        rt.load_class(main_class);
+    pub fn builder() -> RuntimeBuilder<'a> {
+        RuntimeBuilder::new()
+    }
 
-        return rt;
+    /// the source of time, random seeds and hash codes for natives.
+    pub fn environment(&mut self) -> &mut Environment {
+        &mut self.environment
     }
 
-    fn build_class_index_map(class: &ClassFile<'a>) -> HashMap<usize, String> {
-        let cla_idx_map = class.constants
-            .iter()
-            .filter_map(|mref| match mref {
-                ConstantType::MethodRef { class_index: cli, .. } => Some(cli),
-                _ => None
-            })
-            .filter_map(|class_index| {
-                match class.get_constant(*class_index) {
-                    Some(ConstantType::Class { name_index: idx }) => Some((class_index, idx)),
-                    _ => None
+    /// a reader of the loaded classes for other threads, see `ClassReader`.
+    pub fn class_reader(&self) -> ClassReader<'a> {
+        self.classes.reader()
+    }
+
+    /// returns a token which can be used to cancel the execution from another thread.
+    /// all handles share the same state, so cancelling one of them stops the runtime.
+    pub fn cancellation_handle(&self) -> CancellationHandle {
+        self.cancellation.clone()
+    }
+
+    pub fn load_class(&mut self, class: ClassFile<'a>) {
+        debug!(class = class.get_class_name(), methods = class.methods.len(), "loaded class");
+        for hook in self.hooks.iter_mut() {
+            hook.on_class_load(&class);
+        }
+        let class_name = String::from(class.get_class_name());
+        self.classes.insert(class);
+        self.class_linked(&class_name);
+    }
+
+    /// runs `main` of the main class and shuts down. an exception which ends `main` goes to the
+    /// uncaught exception handlers the program installed, or else is returned with the internal
+    /// errors. see `run_and_print` to report them like the java launcher.
+    pub fn run(&mut self) -> Result<ExitStatus, JvmError> {
+        let id = match self.classes.id(&self.main_class) {
+            Some(id) => id,
+            None => return Err(JvmError::NoMainClass { class_name: self.main_class.clone() })
+        };
+        let slot = match self.classes.class(id).methods.iter().position(|method| {
+            method.name == "main" && method.descriptor == "([Ljava/lang/String;)V" && method.access_flags & ACC_STATIC != 0
+        }) {
+            Some(slot) => slot,
+            None => return Err(JvmError::NoMainMethod { class_name: self.main_class.clone() })
+        };
+
+        self.thread.start_invocation();
+        let result = self.initialize_target(&self.main_class.clone(), "main", "([Ljava/lang/String;)V")
+            .and_then(|_| self.run_method(id, slot, vec![LocalVariable::Null]));
+        let status = match result {
+            Ok(ret) => {
+                info!(return_value = ?ret, "main returned");
+                Ok(ExitStatus::SUCCESS)
+            }
+            Err(err) => match *err.root() {
+                RuntimeError::Exit { status } => Ok(ExitStatus(status)),
+                RuntimeError::Cancelled => {
+                    info!("main was cancelled");
+                    Err(JvmError::Cancelled)
                 }
-            })
-            .filter_map(|(class_index, name_index)| {
-                match class.get_constant(*name_index) {
-                    Some(ConstantType::Utf8 { value }) => Some((class_index, value.clone())),
-                    _ => None
+                RuntimeError::Exception { exception, .. } => {
+                    let class = self.classes.class(id).clone();
+                    match self.dispatch_uncaught_exception(&class.methods[slot], exception) {
+                        Some(uncaught) => Err(JvmError::UncaughtException(uncaught)),
+                        // like the jvm, main failed even if a handler took the exception
+                        None => Ok(ExitStatus(1))
+                    }
                 }
-            });
+                _ => {
+                    let crash_dump = self.write_crash_dump(&err).unwrap_or_else(|io_err| {
+                        error!(error = %io_err, "cannot write error report file");
+                        None
+                    });
+                    Err(JvmError::Internal { error: err, crash_dump })
+                }
+            }
+        };
 
-        let mut map = HashMap::new();
-        for (class_index, name) in cla_idx_map {
-            map.insert(usize::from(*class_index), String::from(name));
+        self.detach_debugger();
+        self.shutdown();
+        status
+    }
+
+    /// implements the method `method_name` with the given `descriptor` of the class `class_name` in rust.
+    /// natives take precedence over bytecode, so this can also replace methods of loaded classes.
+    pub fn register_native<F>(&mut self, class_name: &str, method_name: &str, descriptor: &str, method: F)
+        where F: Fn(&mut NativeContext, Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> + Send + Sync + 'static {
+        self.natives.register(class_name, method_name, descriptor, Arc::new(method));
+        // calls resolved to bytecode before may go to the native now
+        self.classes.clear_resolutions();
+        self.classes.invalidate_hierarchy();
+    }
+
+    /// installs a hook which gets notified about method calls, instructions, allocations and exceptions.
+    pub fn add_hook(&mut self, mut hook: Box<dyn RuntimeHook>) {
+        for class in self.classes.classes() {
+            hook.on_class_load(class);
+        }
+        self.hooks.push(hook);
+    }
+
+    /// runs the static method `method_name` with the given `descriptor` of an already loaded class
+    /// or a registered native.
+    /// the arguments are used as the initial local variables of the new stack frame.
+    /// internal errors are written to the crash dump file, if one is configured.
+    pub fn invoke_static(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        self.thread.start_invocation();
+        let result = self.initialize_target(class_name, method_name, descriptor)
+            .and_then(|_| self.invoke(class_name, method_name, descriptor, arguments));
+        if let Err(ref err) = result {
+            // the error itself is more important than a failure to report it
+            let _ = self.write_crash_dump(err);
         }
 
-        return map;
+        result
     }
 
-    pub fn load_class(&mut self, class: ClassFile<'a>) {
-        let map = Runtime::build_class_index_map(&class);
-        let name = String::from(class.get_class_name());
-        self.class_index_map.insert(name.clone(), map);
-        self.classes.insert(name, Arc::new(class));
+    /// the interned `java.lang.String` with the given value, e.g. to pass strings to `invoke_static`.
+    /// interned strings are never collected.
+    pub fn string(&mut self, value: &str) -> ObjectRef {
+        self.intern(value)
     }
 
-    pub fn run(&mut self) {
-        let class = self.classes.get(&self.main_class).expect("no main class loaded").clone();
-        let method = class.methods.iter().find(|method| method.name.eq("main"));
-        if method.is_none() {
-            eprintln!("Class {} does not have a main method", class.get_class_name());
-            return;
+    /// the objects created by the running program.
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// allocations per class and allocation site, empty unless profiling was enabled with
+    /// `RuntimeBuilder::allocation_profiling`. collects garbage first, so the retained objects are
+    /// the ones still reachable.
+    pub fn allocation_profile(&mut self) -> AllocationProfile {
+        if self.allocations.is_enabled() {
+            self.gc();
+        }
+        self.allocations.profile(&self.heap)
+    }
+
+    /// makes room for `size` more bytes if there is a maximum heap size, collecting garbage if needed.
+    /// native memory counts against the maximum. throws an `OutOfMemoryError` if the allocation
+    /// still does not fit.
+    fn reserve(&mut self, method: &Method, stack_frame: &StackFrame, size: usize) -> Result<(), RuntimeError> {
+        let max_heap = match self.max_heap {
+            Some(max_heap) if self.heap.used() + self.native_used() + size > max_heap => max_heap,
+            _ => return Ok(())
+        };
+
+        self.collect_garbage(GcReason::AllocationFailure, Some(stack_frame));
+        if self.heap.used() + self.native_used() + size <= max_heap {
+            return Ok(());
         }
 
-        match self.run_method(method.unwrap(), class.clone(), vec![]) {
-            Ok(ret) => println!("main return value: {:?}", ret),
-            Err(err) => eprintln!("runtime error: {:?}", err)
+        if self.heap_dump_on_out_of_memory {
+            let path = self.heap_dump_path.clone();
+            match self.dump_heap(&path) {
+                Ok(()) => warn!(path = %path.display(), "dumped heap on OutOfMemoryError"),
+                Err(err) => error!(path = %path.display(), error = %err, "cannot dump heap")
+            }
+        }
+
+        Err(self.throw(method, "java/lang/OutOfMemoryError", Some("Java heap space")))
+    }
+
+    /// allocates a new object and reports it to the hooks and the allocation profiler. objects of
+    /// loaded classes get the slots of their fields.
+    fn allocate(&mut self, class_name: &str, data: ObjectData) -> ObjectRef {
+        let reference = self.heap.allocate(class_name, data);
+        if let Some(id) = self.classes.id(class_name) {
+            let layout = self.classes.layout(id).clone();
+            if let Some(object) = self.heap.get_mut(reference) {
+                object.header.set_class(Some(id));
+                object.fields = Fields::with_layout(layout);
+            }
+        }
+        self.allocated(reference);
+        reference
+    }
+
+    /// like `Heap::intern`, but reports newly created strings like any other allocation.
+    fn intern(&mut self, value: &str) -> ObjectRef {
+        let interned = self.heap.interned(value).is_some();
+        let reference = self.heap.intern(value);
+        if !interned {
+            self.allocated(reference);
+        }
+        reference
+    }
+
+    /// the identity hash code of an object, assigned when it is first asked for and kept in its header.
+    fn identity_hash_code(&mut self, reference: ObjectRef) -> i32 {
+        if let Some(hash) = self.heap.get(reference).and_then(|object| object.header.identity_hash()) {
+            return hash;
+        }
+
+        let hash = self.environment.identity_hash();
+        if let Some(object) = self.heap.get_mut(reference) {
+            object.header.set_identity_hash(hash);
+        }
+        hash
+    }
+
+    fn allocated(&mut self, reference: ObjectRef) {
+        let (class_name, size) = match self.heap.get(reference) {
+            Some(object) => (object.class_name.clone(), object.size()),
+            None => return
+        };
+
+        for hook in self.hooks.iter_mut() {
+            hook.on_allocation(&class_name, size);
+        }
+
+        if self.allocations.is_enabled() {
+            let site = self.thread.frames.last().map(|frame| AllocationSite {
+                class_name: String::from(self.classes.class(frame.class).get_class_name()),
+                method_name: String::from(&*frame.method_name),
+                descriptor: String::from(&*frame.descriptor),
+                pc: frame.pc,
+            });
+            self.allocations.record(reference, &class_name, site, size);
+        }
+    }
+
+    /// looks up the method in `class_name` and its super classes and runs it.
+    /// natives take precedence over bytecode, builtin classes come last.
+    fn invoke(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        let mut current = Some(String::from(class_name));
+        while let Some(name) = current {
+            if let Some(native) = self.natives.get(&name, method_name, descriptor) {
+                return self.call_native(&native, &name, method_name, descriptor, arguments);
+            }
+
+            if let Some(id) = self.classes.id(&name) {
+                if let Some(slot) = self.classes.method(id, method_name, descriptor) {
+                    return self.run_method(id, slot, arguments);
+                }
+            } else if builtin::is_builtin(&name) {
+                if let Some(result) = self.invoke_builtin(&name, method_name, descriptor, &arguments) {
+                    return result;
+                }
+            } else if name == class_name && !name.starts_with('[') {
+                return Err(RuntimeError::ClassNotFound { class_name: String::from(class_name) });
+            }
+
+            current = self.superclass_of(&name);
+        }
+
+        Err(RuntimeError::MethodNotFound {
+            class_name: String::from(class_name),
+            method_name: String::from(method_name),
+            descriptor: String::from(descriptor),
+        })
+    }
+
+    /// calls a method resolved through the runtime constant pool, going straight to the
+    /// bytecode if resolution found it.
+    fn invoke_resolved(&mut self, resolved: &ResolvedMethod, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        match resolved.target {
+            Some((class, slot)) => self.run_method(class, slot, arguments),
+            None => self.invoke(&resolved.class_name, &resolved.method_name, &resolved.descriptor, arguments)
+        }
+    }
+
+    /// calls the method selected by the class of the receiver, through the inline cache of the call site.
+    fn invoke_virtual(&mut self, insn: &DecodedInsn, receiver_class: &str, resolved: &ResolvedMethod, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        let generation = self.classes.generation();
+        let target = match insn.cache.as_ref().and_then(|cache| cache.lookup(receiver_class, generation)) {
+            Some(target) => target,
+            None => {
+                let target = self.method_target(receiver_class, &resolved.method_name, &resolved.descriptor);
+                if let Some(ref cache) = insn.cache {
+                    cache.update(receiver_class, target, generation);
+                }
+                target
+            }
+        };
+
+        match target {
+            Some((class, slot)) => self.run_method(class, slot, arguments),
+            None => self.invoke(receiver_class, &resolved.method_name, &resolved.descriptor, arguments)
         }
     }
 
@@ -175,12 +707,10 @@ impl<'a> Runtime<'a> {
     fn exec_istore(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
         match stack_frame.pop_stack() {
             Some(StackValue::Integer(intvalue)) => {
-                stack_frame.set_variable(offset, LocalVariable::Integer(intvalue));
-                Ok(())
-            }
-            _ => {
-                Err(RuntimeError::GenericError { message: format!("stack value at index {} is not an integer", offset) })
+                stack_frame.set_variable(offset, LocalVariable::Integer(intvalue))
             }
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("integer") }),
+            None => Err(RuntimeError::EmptyStack)
         }
     }
 
@@ -194,151 +724,1454 @@ impl<'a> Runtime<'a> {
             Some(LocalVariable::Integer(intvalue)) => {
                 *intvalue
             }
-            Some(LocalVariable::None) => return Err(RuntimeError::GenericError { message: format!("local variable at index {} is not defined", offset) }),
-            Some(_) => return Err(RuntimeError::GenericError { message: format!("local variable at index {} is not an integer", offset) }),
-            None => return Err(RuntimeError::GenericError { message: format!("stack value at index {} is out of range", offset) })
+            Some(LocalVariable::None) => return Err(RuntimeError::UndefinedLocal { index: offset }),
+            Some(_) => return Err(RuntimeError::LocalType { index: offset, expected: String::from("integer") }),
+            None => return Err(RuntimeError::LocalOutOfRange { index: offset })
         };
 
         stack_frame.push_stack(StackValue::Integer(intvalue));
         Ok(())
     }
 
-    fn run_method(&mut self, method: &Method, class: Arc<ClassFile<'a>>, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
-        println!("running method {}", method.name);
-        use java::instructions::Instruction;
-        let mut stack_frame = StackFrame::for_method(method, arguments);
-        let mut return_value: Option<StackValue> = None;
-        println!("{:?}", stack_frame);
-        for instruction in method.instructions() {
-            println!("{:?}", instruction);
-            match instruction {
-                //00
-                Instruction::IConstm1(()) => stack_frame.push_stack(StackValue::Integer(-1)),
-                Instruction::IConst0(()) => stack_frame.push_stack(StackValue::Integer(0)),
-                Instruction::IConst1(()) => stack_frame.push_stack(StackValue::Integer(1)),
-                Instruction::IConst2(()) => stack_frame.push_stack(StackValue::Integer(2)),
-                Instruction::IConst3(()) => stack_frame.push_stack(StackValue::Integer(3)),
-                Instruction::IConst4(()) => stack_frame.push_stack(StackValue::Integer(4)),
-                Instruction::IConst5(()) => stack_frame.push_stack(StackValue::Integer(5)),
-                // 10...
-                Instruction::BIPush(value) =>
-                    stack_frame.push_stack(StackValue::Integer(i64::from(value))),
-                Instruction::SIPush(value) =>
-                    stack_frame.push_stack(StackValue::Integer(i64::from(value))),
-                Instruction::ILoad(offset) => Runtime::exec_iload(&mut stack_frame, usize::from(offset))?,
-                Instruction::ILoad0(()) => Runtime::exec_iload(&mut stack_frame, 0)?,
-                Instruction::ILoad1(()) => Runtime::exec_iload(&mut stack_frame, 1)?,
-                Instruction::ILoad2(()) => Runtime::exec_iload(&mut stack_frame, 2)?,
-                Instruction::ILoad3(()) => Runtime::exec_iload(&mut stack_frame, 3)?,
-                // 20..
-                // 30..
-                Instruction::IStore(offset) => Runtime::exec_istore(&mut stack_frame, usize::from(offset))?,
-                Instruction::IStore0(()) => Runtime::exec_istore(&mut stack_frame, 0)?,
-
-                Instruction::IStore1(()) => Runtime::exec_istore(&mut stack_frame, 1)?,
-
-                Instruction::IStore2(()) => Runtime::exec_istore(&mut stack_frame, 2)?,
-
-                Instruction::IStore3(()) => Runtime::exec_istore(&mut stack_frame, 3)?,
-                // 40..
-                // 50..
-                // 60..
-                Instruction::IAdd(()) => match (stack_frame.pop_stack(), stack_frame.pop_stack()) {
-                    (Some(StackValue::Integer(lh)), Some(StackValue::Integer(rh))) =>
-                        stack_frame.push_stack(StackValue::Integer(lh + rh)),
-                    (Some(_), Some(_)) =>
-                        return Err(RuntimeError::StackType { expected: format!("integer") }),
-                    (None, None) | (Some(_), None) =>
-                        return Err(RuntimeError::EmptyStack),
-                    _ =>
-                        return Err(RuntimeError::GenericError { message: format!("IAdd") })
-                }
+    /// loads a reference (or null) from local variable `offset` onto the stack.
+    fn exec_aload(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
+        let value = match stack_frame.get_variable(offset) {
+            Some(LocalVariable::Reference(reference)) => StackValue::Reference(*reference),
+            Some(LocalVariable::Null) => StackValue::Null,
+            Some(LocalVariable::None) => return Err(RuntimeError::UndefinedLocal { index: offset }),
+            Some(_) => return Err(RuntimeError::LocalType { index: offset, expected: String::from("reference") }),
+            None => return Err(RuntimeError::LocalOutOfRange { index: offset })
+        };
 
-                // a0..
-                Instruction::IfICmpGE(instruction) => {
-                    // would be nice to know the instruction offset now…
-                    // additionally we need a way to "jump" to that instruction. currently we are just looping from top to bottom
-                }
+        stack_frame.push_stack(value);
+        Ok(())
+    }
+
+    /// stores the top stack value into the local variable at `offset`, if it is a reference (or null).
+    fn exec_astore(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
+        match stack_frame.pop_stack() {
+            Some(StackValue::Reference(reference)) => stack_frame.set_variable(offset, LocalVariable::Reference(reference)),
+            Some(StackValue::Null) => stack_frame.set_variable(offset, LocalVariable::Null),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("reference") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
 
-                Instruction::IReturn(()) => match stack_frame.pop_stack() {
-                    Some(StackValue::Integer(ret)) => return_value = Some(StackValue::Integer(ret)),
-                    Some(_) => return Err(RuntimeError::StackType { expected: format!("Integer") }),
-                    None => return Err(RuntimeError::EmptyStack)
+    /// loads a long from local variable `offset` and the `Top` after it onto the stack.
+    fn exec_lload(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
+        let value = match stack_frame.get_variable(offset) {
+            Some(LocalVariable::Long(value)) => *value,
+            Some(LocalVariable::None) => return Err(RuntimeError::UndefinedLocal { index: offset }),
+            Some(_) => return Err(RuntimeError::LocalType { index: offset, expected: String::from("long") }),
+            None => return Err(RuntimeError::LocalOutOfRange { index: offset })
+        };
+
+        stack_frame.push_stack(StackValue::Long(value));
+        Ok(())
+    }
+
+    /// stores the top stack value into local variables `offset` and `offset + 1`, if it is a long.
+    fn exec_lstore(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
+        match stack_frame.pop_stack() {
+            Some(StackValue::Long(value)) => stack_frame.set_variable(offset, LocalVariable::Long(value)),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("long") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
+
+    /// pops the arguments of a call to a method with the given `descriptor` from the stack.
+    /// the receiver of instance methods becomes the first argument.
+    fn pop_arguments(&self, stack_frame: &mut StackFrame, descriptor: &str, has_receiver: bool) -> Result<Vec<LocalVariable>, RuntimeError> {
+        match self.classes.signatures().intern(descriptor) {
+            Some(signature) => signature.pop_arguments(stack_frame, has_receiver),
+            None => Err(RuntimeError::ClassFormat { message: format!("invalid method descriptor {}", descriptor) })
+        }
+    }
+
+    /// the arguments `pop_arguments` would pop, left on the stack.
+    fn peek_arguments(&self, stack_frame: &StackFrame, descriptor: &str, has_receiver: bool) -> Result<Vec<LocalVariable>, RuntimeError> {
+        match self.classes.signatures().intern(descriptor) {
+            Some(signature) => signature.peek_arguments(stack_frame, has_receiver),
+            None => Err(RuntimeError::ClassFormat { message: format!("invalid method descriptor {}", descriptor) })
+        }
+    }
+
+    /// the signature of a running method. methods which kept running while their class was
+    /// redefined or unloaded are not found at their slot anymore.
+    fn method_signature(&self, id: ClassId, slot: usize, method: &Method) -> Result<Arc<Signature>, RuntimeError> {
+        let current = self.classes.is_loaded(id) && self.classes.class(id).methods.get(slot).is_some_and(|current| current.descriptor == method.descriptor);
+        match self.classes.signature_at(id, slot).filter(|_| current) {
+            Some(signature) => Ok(signature.clone()),
+            None => self.classes.signatures().intern(&method.descriptor)
+                .ok_or_else(|| RuntimeError::ClassFormat { message: format!("invalid method descriptor {}", method.descriptor) })
+        }
+    }
+
+    /// lends the frame of the calling method to its `ActiveFrame` while `call` runs, where the
+    /// garbage collector and the debugger find it, and takes it back afterwards. the frame is
+    /// moved, not copied.
+    fn suspended<T>(&mut self, stack_frame: &mut StackFrame, call: impl FnOnce(&mut Self) -> T) -> T {
+        let depth = self.thread.frames.len();
+        if let Some(frame) = self.thread.frames.last_mut() {
+            frame.saved = Some(mem::take(stack_frame));
+        }
+        let result = call(self);
+        if let Some(saved) = depth.checked_sub(1).and_then(|index| self.thread.frames.get_mut(index)).and_then(|frame| frame.saved.take()) {
+            *stack_frame = saved;
+        }
+        result
+    }
+
+    /// the value of a field that was never written: null for references, 0 for everything else.
+    fn default_value(descriptor: &str) -> StackValue {
+        match descriptor.as_bytes().first() {
+            Some(b'L') | Some(b'[') => StackValue::Null,
+            _ => StackValue::Integer(0)
+        }
+    }
+
+    /// pops the object of a field access, throwing a `NullPointerException` for null.
+    fn pop_object(&mut self, method: &Method, stack_frame: &mut StackFrame) -> Result<ObjectRef, RuntimeError> {
+        match stack_frame.pop_stack() {
+            Some(StackValue::Reference(reference)) => Ok(reference),
+            Some(StackValue::Null) => Err(self.throw(method, "java/lang/NullPointerException", None)),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("reference") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
+
+    /// the index of the first instruction of the handler for an exception of `exception_class`
+    /// thrown at `pc`.
+    fn find_handler(&self, decoded: &DecodedMethod, pc: usize, exception_class: &str) -> Option<usize> {
+        decoded.handlers.covering(pc)
+            .find(|handler| handler.catch_class.as_ref().is_none_or(|catch_class| self.is_subclass_of(exception_class, catch_class)))
+            .map(|handler| handler.handler)
+    }
+
+    fn run_method(&mut self, id: ClassId, slot: usize, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        let class = self.classes.class(id).clone();
+        let method = &class.methods[slot];
+        self.count_invocation(id, slot);
+        let span = debug_span!("invoke", class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor);
+        let _entered = span.enter();
+
+        for hook in self.hooks.iter_mut() {
+            hook.on_method_enter(class.get_class_name(), method);
+        }
+
+        if self.thread.depth() >= self.max_stack_depth {
+            return Err(self.throw_in_caller("java/lang/StackOverflowError", None));
+        }
+        self.method_signature(id, slot, method)?.check_arguments(&arguments, method.access_flags & ACC_STATIC == 0, || {
+            format!("{}.{}{}", class.get_class_name(), method.name, method.descriptor)
+        })?;
+        self.thread.frames.push(ActiveFrame { class: id, slot, method_name: method.name.clone(), descriptor: method.descriptor.clone(), pc: 0, saved: None, local_objects: Vec::new() });
+        let result = self.execute_method(method, &class, id, slot, arguments);
+        if let Some(frame) = self.thread.frames.pop() {
+            self.free_frame_objects(frame.local_objects);
+        }
+
+        for hook in self.hooks.iter_mut() {
+            hook.on_method_exit(class.get_class_name(), method, &result);
+        }
+
+        result
+    }
+
+    fn execute_method(&mut self, method: &Method, class: &Arc<ClassFile<'a>>, id: ClassId, slot: usize, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        // the natives of the class library are implemented by the runtime, like its builtin classes
+        if method.access_flags & ACC_NATIVE != 0 {
+            return match self.invoke_builtin(class.get_class_name(), &method.name, &method.descriptor, &arguments) {
+                Some(result) => result,
+                None => {
+                    let message = format!("{}.{}{}", class.get_class_name().replace('/', "."), method.name, method.descriptor);
+                    Err(self.throw(method, "java/lang/UnsatisfiedLinkError", Some(&message)))
                 }
+            };
+        }
+        let decoded = match self.classes.decoded_at(id, slot) {
+            Some(decoded) => decoded.clone(),
+            None => Arc::new(DecodedMethod::decode(class, method)?)
+        };
+        let pool = self.classes.pool(id).clone();
+        let context = MethodContext { class, id, slot, pool: &pool, decoded: &decoded };
+        if let Some(ref fast) = decoded.fast {
+            if self.can_run_fast() {
+                return self.execute_fast(context, fast, arguments);
+            }
+        }
 
-                // b0..
-                Instruction::Return(()) => return_value = None,
-                Instruction::InvokeStatic(method_offset) => {
-                    match class.get_constant(method_offset) {
-                        Some(ConstantType::MethodRef { class_index, name_and_type_index }) => {
-                            let cls_name = {
-                                let other_class = self.class_index_map.get(class.get_class_name()).unwrap().get(&(*class_index as usize));
-                                if other_class.is_none() {
-                                    return Err(RuntimeError::GenericError { message: format!("class not found {}", class_index) });
-                                }
-                                other_class.unwrap().clone()
-                            };
-
-
-                            if cls_name.eq(class.get_class_name()) {
-                                let method = match class.get_method_from_nat(*name_and_type_index) {
-                                    Some(m) => m,
-                                    None => return Err(RuntimeError::MethodNotFound)
-                                };
-
-                                let mut args = method.get_signature().arguments.iter().map(|arg_type| {
-                                    //TODO: we really should check the type here. some day.
-                                    match stack_frame.pop_stack() {
-                                        Some(StackValue::Integer(intvalue)) => Ok(LocalVariable::Integer(intvalue)),
-                                        Some(StackValue::None) => Ok(LocalVariable::None), //??? None => undefined, Null => null.
-                                        Some(StackValue::Null) => Ok(LocalVariable::Null),
-                                        None => Err(RuntimeError::EmptyStack)
+        let mut stack_frame = self.thread.frame_pool.acquire(method, arguments);
+        trace!(frame = ?stack_frame, "frame created");
+        let result = self.interpret(method, context, &mut stack_frame);
+        self.thread.frame_pool.release(stack_frame);
+
+        result
+    }
+
+    /// runs the decoded bytecode of `method` on a frame until it returns or throws.
+    fn interpret(&mut self, method: &Method, context: MethodContext<'_, 'a>, stack_frame: &mut StackFrame) -> Result<Option<StackValue>, RuntimeError> {
+        let MethodContext { class, id, slot, pool, decoded } = context;
+        let mut return_value: Option<StackValue> = None;
+        let mut index = 0;
+        let optimized = if self.optimize_bytecode && self.counters.is_hot(id, slot) { Some(decoded.optimized(class, method)) } else { None };
+        if self.safepoint_pending() {
+            self.safepoint(SafepointKind::MethodEntry, stack_frame)?;
+        }
+        while index < decoded.instructions.len() {
+            let insn = &decoded.instructions[index];
+            let (pc, instruction) = (insn.pc, &insn.instruction);
+
+            if let Some(frame) = self.thread.frames.last_mut() {
+                frame.pc = pc;
+            }
+
+            // superinstructions skip the events of the instructions they replace,
+            // so they only run while nothing observes single instructions
+            let fused = match optimized {
+                Some(optimized) => optimized[index],
+                None => insn.fused
+            };
+            if let Some(fused) = fused {
+                if self.hooks.is_empty() && !self.breakpoints.is_active() && self.debugger.is_none() && self.trace.is_none() && self.explain.is_none() {
+                    if let Some(flow) = Runtime::execute_fused(fused, &decoded.instructions[index + fused.len() - 1], stack_frame, &self.heap) {
+                        self.counters.retire(fused.len() as u64);
+                        for replaced in decoded.instructions[index..index + fused.len()].iter() {
+                            opcode_coverage::record(&replaced.instruction);
+                        }
+                        match flow {
+                            Flow::Jump(target) => {
+                                if target <= index {
+                                    self.count_back_edge(id, slot);
+                                    if self.safepoint_pending() {
+                                        self.safepoint(SafepointKind::BackEdge, stack_frame)?;
                                     }
-                                }).collect::<Result<Vec<LocalVariable>, RuntimeError>>()?;
-                                args.reverse();
-
-                                println!("{:?}, {:?}", method, args);
-                                match self.run_method(method, class.clone(), args) {
-                                    Ok(Some(stack_value)) => stack_frame.push_stack(stack_value),
-                                    Ok(None) => (),
-                                    Err(err) => return Err(err)
-                                };
+                                }
+                                index = target;
                             }
-                            //
-                        }
-                        Some(_) => {
-                            return Err(RuntimeError::GenericError {
-                                message: format!("invalid method offset {}", method_offset)
-                            });
+                            _ => index += fused.len()
                         }
-                        None => {
-                            return Err(RuntimeError::GenericError {
-                                message: format!("invalid method offset {}", method_offset)
-                            });
+                        continue;
+                    }
+                }
+            }
+
+            if self.breakpoints.is_active() {
+                self.check_breakpoints(class.get_class_name(), method, stack_frame, pc);
+            }
+
+            if self.debugger.is_some() {
+                self.debug_point(DebugPoint { method, frame: stack_frame, pc });
+            }
+
+            for hook in self.hooks.iter_mut() {
+                hook.on_instruction(class.get_class_name(), method, pc, instruction);
+            }
+
+            trace!(pc, instruction = ?instruction);
+            if self.explain.is_some() {
+                self.explain_instruction(class, method, pc, instruction, stack_frame);
+            }
+            let traced = match self.trace {
+                Some(_) => self.begin_trace_step(class, &method.name, &method.descriptor, pc, instruction)
+                    .map(|step| (step, stack_frame.stack.clone())),
+                None => None
+            };
+            let flow = self.execute_instruction(method, class, pool, stack_frame, &mut return_value, insn);
+            if let Some((step, before)) = traced {
+                let exception = match flow {
+                    Err(RuntimeError::Exception { ref class_name, .. }) => Some(class_name.as_str()),
+                    _ => None
+                };
+                self.end_trace_step(step, &before, stack_frame, exception);
+            }
+            self.counters.retire(1);
+            opcode_coverage::record(instruction);
+            match flow {
+                Ok(Flow::Next) => index += 1,
+                Ok(Flow::Jump(target)) => {
+                    if target <= index {
+                        self.count_back_edge(id, slot);
+                        if self.safepoint_pending() {
+                            self.safepoint(SafepointKind::BackEdge, stack_frame)?;
                         }
                     }
+                    index = target;
+                }
+                Ok(Flow::Return) => break,
+                Err(RuntimeError::Exception { exception, class_name }) => match self.find_handler(decoded, pc, &class_name) {
+                    Some(handler) => {
+                        stack_frame.stack.clear();
+                        stack_frame.push_stack(StackValue::Reference(exception));
+                        index = handler;
+                    }
+                    None => return Err(RuntimeError::Exception { exception, class_name })
+                },
+                Err(error) => {
+                    let location = Runtime::location(class, method, pc, instruction);
+                    self.thread.unwound_frames.push(stack_frame.dump(&location));
+                    return Err(error.at(location));
                 }
-                _ => return Err(RuntimeError::GenericError { message: format!("unknown instruction") })
             }
 
-            println!("{:?}, return {:?}", stack_frame, return_value);
+            trace!(frame = ?stack_frame, return_value = ?return_value);
+        }
+        if self.safepoint_pending() {
+            self.safepoint(SafepointKind::Return, stack_frame)?;
         }
 
         // this is just here for internal verification.
         // the compiler should prevent these type of errors.
         // if something like this happens, the jvm has f**ked up, or the bytecode is broken
-        match method.get_signature().return_type {
-            ValueType::Void => if return_value.is_some() {
-                return Err(RuntimeError::GenericError { message: format!("invalid return type. expected void.") });
+        match self.method_signature(id, slot, method)?.returns() {
+            None => if return_value.is_some() {
+                return Err(RuntimeError::InvalidReturnValue { expected: String::from("void") });
             },
-            ValueType::Integer => match return_value {
+            Some(ValueKind::Int) => match return_value {
                 Some(StackValue::Integer(_)) => (),
                 Some(StackValue::Null) => (),
-                _ => return Err(RuntimeError::GenericError { message: format!("invalid return type. expected integer.") })
+                _ => return Err(RuntimeError::InvalidReturnValue { expected: String::from("integer") })
+            },
+            Some(ValueKind::Long) => match return_value {
+                Some(StackValue::Long(_)) => (),
+                _ => return Err(RuntimeError::InvalidReturnValue { expected: String::from("long") })
             },
-            _ => (),
+            Some(ValueKind::Reference) => (),
         };
 
         Ok(return_value)
     }
+
+    fn location(class: &ClassFile, method: &Method, pc: usize, instruction: &Instruction) -> Location {
+        Location {
+            class_name: String::from(class.get_class_name()),
+            method_name: String::from(&*method.name),
+            descriptor: String::from(&*method.descriptor),
+            pc,
+            opcode: instruction.opcode(),
+            instruction: instruction.name(),
+            source_file: class.get_source_file().map(String::from),
+            line: method.get_code().and_then(|code| code.line_number_at(pc)),
+        }
+    }
+
+    fn execute_instruction(&mut self, method: &Method, class: &Arc<ClassFile<'a>>, pool: &RuntimeConstantPool, stack_frame: &mut StackFrame, return_value: &mut Option<StackValue>, insn: &DecodedInsn) -> Result<Flow, RuntimeError> {
+        let instruction = &insn.instruction;
+        match instruction {
+            //00
+            Instruction::AConstNull(()) => stack_frame.push_stack(StackValue::Null),
+            Instruction::IConstm1(()) => stack_frame.push_stack(StackValue::Integer(-1)),
+            Instruction::IConst0(()) => stack_frame.push_stack(StackValue::Integer(0)),
+            Instruction::IConst1(()) => stack_frame.push_stack(StackValue::Integer(1)),
+            Instruction::IConst2(()) => stack_frame.push_stack(StackValue::Integer(2)),
+            Instruction::IConst3(()) => stack_frame.push_stack(StackValue::Integer(3)),
+            Instruction::IConst4(()) => stack_frame.push_stack(StackValue::Integer(4)),
+            Instruction::IConst5(()) => stack_frame.push_stack(StackValue::Integer(5)),
+            Instruction::LConst0(()) => stack_frame.push_stack(StackValue::Long(0)),
+            Instruction::LConst1(()) => stack_frame.push_stack(StackValue::Long(1)),
+            // 10...
+            // the operands are signed
+            Instruction::BIPush(value) =>
+                stack_frame.push_stack(StackValue::Integer(i64::from(*value as i8))),
+            Instruction::SIPush(value) =>
+                stack_frame.push_stack(StackValue::Integer(i64::from(*value as i16))),
+            Instruction::LDC(index) => self.exec_ldc(method, class, stack_frame, u16::from(*index))?,
+            Instruction::LDCW(index) => self.exec_ldc(method, class, stack_frame, *index)?,
+            Instruction::ILoad(offset) => Runtime::exec_iload(stack_frame, usize::from(*offset))?,
+            Instruction::ILoad0(()) => Runtime::exec_iload(stack_frame, 0)?,
+            Instruction::ILoad1(()) => Runtime::exec_iload(stack_frame, 1)?,
+            Instruction::ILoad2(()) => Runtime::exec_iload(stack_frame, 2)?,
+            Instruction::ILoad3(()) => Runtime::exec_iload(stack_frame, 3)?,
+            Instruction::ALoad(offset) => Runtime::exec_aload(stack_frame, usize::from(*offset))?,
+            // 20..
+            Instruction::ALoad0(()) => Runtime::exec_aload(stack_frame, 0)?,
+            Instruction::ALoad1(()) => Runtime::exec_aload(stack_frame, 1)?,
+            Instruction::ALoad2(()) => Runtime::exec_aload(stack_frame, 2)?,
+            Instruction::ALoad3(()) => Runtime::exec_aload(stack_frame, 3)?,
+            // 30..
+            Instruction::IStore(offset) => Runtime::exec_istore(stack_frame, usize::from(*offset))?,
+            Instruction::IStore0(()) => Runtime::exec_istore(stack_frame, 0)?,
+
+            Instruction::IStore1(()) => Runtime::exec_istore(stack_frame, 1)?,
+
+            Instruction::IStore2(()) => Runtime::exec_istore(stack_frame, 2)?,
+
+            Instruction::IStore3(()) => Runtime::exec_istore(stack_frame, 3)?,
+            Instruction::AStore(offset) => Runtime::exec_astore(stack_frame, usize::from(*offset))?,
+            // 40..
+            Instruction::AStore0(()) => Runtime::exec_astore(stack_frame, 0)?,
+            Instruction::AStore1(()) => Runtime::exec_astore(stack_frame, 1)?,
+            Instruction::AStore2(()) => Runtime::exec_astore(stack_frame, 2)?,
+            Instruction::AStore3(()) => Runtime::exec_astore(stack_frame, 3)?,
+            Instruction::IALoad(()) => self.exec_array_load(method, stack_frame, ElementType::Int)?,
+            Instruction::BALoad(()) => self.exec_array_load(method, stack_frame, ElementType::Byte)?,
+            Instruction::CALoad(()) => self.exec_array_load(method, stack_frame, ElementType::Char)?,
+            Instruction::ScALoad(()) => self.exec_array_load(method, stack_frame, ElementType::Short)?,
+            Instruction::AALoad(()) => self.exec_aaload(method, stack_frame)?,
+            Instruction::AAStore(()) => self.exec_aastore(method, stack_frame)?,
+            Instruction::IAStore(()) => self.exec_array_store(method, stack_frame, ElementType::Int)?,
+            Instruction::BAStore(()) => self.exec_array_store(method, stack_frame, ElementType::Byte)?,
+            Instruction::CAStore(()) => self.exec_array_store(method, stack_frame, ElementType::Char)?,
+            Instruction::SAStore(()) => self.exec_array_store(method, stack_frame, ElementType::Short)?,
+            Instruction::LLoad(offset) => Runtime::exec_lload(stack_frame, usize::from(*offset))?,
+            Instruction::LLoad0(()) => Runtime::exec_lload(stack_frame, 0)?,
+            Instruction::LLoad1(()) => Runtime::exec_lload(stack_frame, 1)?,
+            Instruction::LLoad2(()) => Runtime::exec_lload(stack_frame, 2)?,
+            Instruction::LLoad3(()) => Runtime::exec_lload(stack_frame, 3)?,
+            Instruction::LStore(offset) => Runtime::exec_lstore(stack_frame, usize::from(*offset))?,
+            Instruction::LStore0(()) => Runtime::exec_lstore(stack_frame, 0)?,
+            Instruction::LStore1(()) => Runtime::exec_lstore(stack_frame, 1)?,
+            Instruction::LStore2(()) => Runtime::exec_lstore(stack_frame, 2)?,
+            Instruction::LStore3(()) => Runtime::exec_lstore(stack_frame, 3)?,
+            // 50..
+            Instruction::Pop(()) => if stack_frame.pop_stack().is_none() {
+                return Err(RuntimeError::EmptyStack);
+            },
+            Instruction::Dup(()) => match stack_frame.stack.last().cloned() {
+                Some(value) => stack_frame.push_stack(value),
+                None => return Err(RuntimeError::EmptyStack)
+            },
+            // 60..
+            Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) | Instruction::IDiv(()) | Instruction::IRem(()) |
+            Instruction::IShl(()) | Instruction::IShr(()) | Instruction::IUSHR(()) |
+            Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(()) => {
+                let rh = Runtime::pop_int(stack_frame)? as i32;
+                let lh = Runtime::pop_int(stack_frame)? as i32;
+                match int_arithmetic(instruction, lh, rh) {
+                    Some(value) => stack_frame.push_stack(StackValue::Integer(i64::from(value))),
+                    None => return Err(self.throw(method, "java/lang/ArithmeticException", Some("/ by zero")))
+                }
+            }
+            Instruction::INeg(()) => {
+                let value = Runtime::pop_int(stack_frame)? as i32;
+                stack_frame.push_stack(StackValue::Integer(i64::from(value.wrapping_neg())));
+            }
+
+            Instruction::I2B(()) | Instruction::I2C(()) | Instruction::I2S(()) => {
+                let element = match instruction {
+                    Instruction::I2B(()) => ElementType::Byte,
+                    Instruction::I2C(()) => ElementType::Char,
+                    _ => ElementType::Short
+                };
+                let value = Runtime::pop_int(stack_frame)? as i32;
+                stack_frame.push_stack(StackValue::Integer(i64::from(element.narrow(value))));
+            }
+
+            Instruction::IInc(operands) => {
+                let (offset, delta) = (usize::from(operands >> 8), i32::from((operands & 0xff) as u8 as i8));
+                match stack_frame.get_variable_mut(offset) {
+                    Some(LocalVariable::Integer(value)) => *value = i64::from((*value as i32).wrapping_add(delta)),
+                    Some(LocalVariable::None) => return Err(RuntimeError::UndefinedLocal { index: offset }),
+                    Some(_) => return Err(RuntimeError::LocalType { index: offset, expected: String::from("integer") }),
+                    None => return Err(RuntimeError::LocalOutOfRange { index: offset })
+                }
+            }
+
+            // 90..
+            Instruction::Ifeq(_) => return Runtime::branch_if(insn, Runtime::pop_int(stack_frame)? == 0),
+            Instruction::Ifne(_) => return Runtime::branch_if(insn, Runtime::pop_int(stack_frame)? != 0),
+            Instruction::Iflt(_) => return Runtime::branch_if(insn, Runtime::pop_int(stack_frame)? < 0),
+            Instruction::Ifge(_) => return Runtime::branch_if(insn, Runtime::pop_int(stack_frame)? >= 0),
+            Instruction::Ifgt(_) => return Runtime::branch_if(insn, Runtime::pop_int(stack_frame)? > 0),
+            Instruction::Ifle(_) => return Runtime::branch_if(insn, Runtime::pop_int(stack_frame)? <= 0),
+            Instruction::IfICmpEQ(_) => return Runtime::branch_if_icmp(insn, stack_frame, |lh, rh| lh == rh),
+            // a0..
+            Instruction::IfICmpNE(_) => return Runtime::branch_if_icmp(insn, stack_frame, |lh, rh| lh != rh),
+            Instruction::IfICmpLT(_) => return Runtime::branch_if_icmp(insn, stack_frame, |lh, rh| lh < rh),
+            Instruction::IfICmpGE(_) => return Runtime::branch_if_icmp(insn, stack_frame, |lh, rh| lh >= rh),
+            Instruction::IfICmpGT(_) => return Runtime::branch_if_icmp(insn, stack_frame, |lh, rh| lh > rh),
+            Instruction::IfICmpLE(_) => return Runtime::branch_if_icmp(insn, stack_frame, |lh, rh| lh <= rh),
+            Instruction::IfACmpEQ(_) => {
+                let (rh, lh) = (Runtime::pop_reference(stack_frame)?, Runtime::pop_reference(stack_frame)?);
+                return Runtime::branch_if(insn, lh == rh);
+            }
+            Instruction::IfACmpNE(_) => {
+                let (rh, lh) = (Runtime::pop_reference(stack_frame)?, Runtime::pop_reference(stack_frame)?);
+                return Runtime::branch_if(insn, lh != rh);
+            }
+            Instruction::Goto(_) => return Runtime::branch_if(insn, true),
+            Instruction::TableSwitch((_, low, high, _)) => {
+                let key = Runtime::pop_int(stack_frame)? as i32;
+                let case = if key >= *low && key <= *high { (key - low) as usize + 1 } else { 0 };
+                return Runtime::switch_to(insn, case);
+            }
+            Instruction::LookupSwitch((_, pairs)) => {
+                let key = Runtime::pop_int(stack_frame)? as i32;
+                let case = pairs.iter().position(|&(value, _)| value == key).map_or(0, |position| position + 1);
+                return Runtime::switch_to(insn, case);
+            }
+
+            Instruction::IReturn(()) => match stack_frame.pop_stack() {
+                Some(StackValue::Integer(ret)) => {
+                    *return_value = Some(StackValue::Integer(ret));
+                    return Ok(Flow::Return);
+                }
+                Some(_) => return Err(RuntimeError::StackType { expected: String::from("Integer") }),
+                None => return Err(RuntimeError::EmptyStack)
+            }
+            Instruction::LReturn(()) => match stack_frame.pop_stack() {
+                Some(StackValue::Long(ret)) => {
+                    *return_value = Some(StackValue::Long(ret));
+                    return Ok(Flow::Return);
+                }
+                Some(_) => return Err(RuntimeError::StackType { expected: String::from("long") }),
+                None => return Err(RuntimeError::EmptyStack)
+            }
+
+            // b0..
+            Instruction::AReturn(()) => match stack_frame.pop_stack() {
+                Some(value @ StackValue::Reference(_)) | Some(value @ StackValue::Null) => {
+                    *return_value = Some(value);
+                    return Ok(Flow::Return);
+                }
+                Some(_) => return Err(RuntimeError::StackType { expected: String::from("reference") }),
+                None => return Err(RuntimeError::EmptyStack)
+            }
+            Instruction::Return(()) => {
+                *return_value = None;
+                return Ok(Flow::Return);
+            }
+            Instruction::GetStatic(index) => {
+                let field = self.resolve_field(pool, class, *index)?;
+                let (cls_name, field_name, descriptor) = (&field.class_name, field.field_name.as_str(), field.descriptor.as_str());
+                if self.classes.get(cls_name).is_none() {
+                    let value = self.builtin_static(cls_name, field_name)
+                        .ok_or_else(|| RuntimeError::ClassNotFound { class_name: cls_name.clone() })?;
+                    stack_frame.push_stack(value);
+                    return Ok(Flow::Next);
+                }
+                self.initialize_for(method, stack_frame, cls_name)?;
+
+                let value = self.statics.get(&(cls_name.clone(), field.field_name.clone())).cloned().unwrap_or_else(|| Runtime::default_value(descriptor));
+                self.field_accessed(FieldAccess { kind: FieldAccessKind::GetStatic, class_name: cls_name, field_name, object: None, old_value: &value, new_value: None }, stack_frame);
+                stack_frame.push_stack(value);
+            }
+            Instruction::PutStatic(index) => {
+                let field = self.resolve_field(pool, class, *index)?;
+                let (cls_name, field_name, descriptor) = (&field.class_name, field.field_name.as_str(), field.descriptor.as_str());
+                if self.classes.get(cls_name).is_none() {
+                    return Err(RuntimeError::ClassNotFound { class_name: cls_name.clone() });
+                }
+                self.initialize_for(method, stack_frame, cls_name)?;
+
+                let value = stack_frame.pop_stack().ok_or(RuntimeError::EmptyStack)?;
+                let key = (cls_name.clone(), field.field_name.clone());
+                let old_value = self.statics.get(&key).cloned().unwrap_or_else(|| Runtime::default_value(descriptor));
+                self.field_accessed(FieldAccess { kind: FieldAccessKind::PutStatic, class_name: cls_name, field_name, object: None, old_value: &old_value, new_value: Some(&value) }, stack_frame);
+                self.statics.insert(key, value);
+            }
+            Instruction::GetField(index) => {
+                let field = self.resolve_field(pool, class, *index)?;
+                let (cls_name, field_name, descriptor) = (&field.class_name, field.field_name.as_str(), field.descriptor.as_str());
+                let object = self.pop_object(method, stack_frame)?;
+                let value = self.heap.get(object).and_then(|object| object.fields.get_resolved(field.slot.as_ref(), field_name).cloned()).unwrap_or_else(|| Runtime::default_value(descriptor));
+                self.field_accessed(FieldAccess { kind: FieldAccessKind::GetField, class_name: cls_name, field_name, object: Some(object), old_value: &value, new_value: None }, stack_frame);
+                stack_frame.push_stack(value);
+            }
+            Instruction::PutField(index) => {
+                let field = self.resolve_field(pool, class, *index)?;
+                let (cls_name, field_name, descriptor) = (&field.class_name, field.field_name.as_str(), field.descriptor.as_str());
+                let value = stack_frame.pop_stack().ok_or(RuntimeError::EmptyStack)?;
+                let object = self.pop_object(method, stack_frame)?;
+                let old_value = self.heap.get(object).and_then(|object| object.fields.get_resolved(field.slot.as_ref(), field_name).cloned()).unwrap_or_else(|| Runtime::default_value(descriptor));
+                self.field_accessed(FieldAccess { kind: FieldAccessKind::PutField, class_name: cls_name, field_name, object: Some(object), old_value: &old_value, new_value: Some(&value) }, stack_frame);
+                if let Some(object) = self.heap.get_mut(object) {
+                    object.fields.insert_resolved(field.slot.as_ref(), field_name, value);
+                }
+            }
+            Instruction::InvokeVirtual(method_offset) | Instruction::InvokeInterface((method_offset, _, _)) => {
+                let resolved = self.resolve_method(pool, class, *method_offset)?;
+                let args = resolved.signature.pop_arguments(stack_frame, true)?;
+                let result = self.suspended(stack_frame, |rt| {
+                    if let LocalVariable::Null = args[0] {
+                        return Err(rt.throw(method, "java/lang/NullPointerException", None));
+                    }
+                    if let Some(intrinsic) = resolved.intrinsic {
+                        return rt.run_intrinsic(method, intrinsic, &args);
+                    }
+                    if let Some(target) = rt.devirtualized(insn, &resolved) {
+                        if let Some(value) = rt.run_inlined(insn, target, &args) {
+                            return Ok(Some(value));
+                        }
+                        return rt.run_method(target.0, target.1, args);
+                    }
+                    let receiver_class = match args[0] {
+                        LocalVariable::Reference(receiver) => match rt.heap.get(receiver) {
+                            Some(object) => object.class_name.clone(),
+                            None => return Err(RuntimeError::StackType { expected: String::from("reference") })
+                        },
+                        _ => return Err(RuntimeError::StackType { expected: String::from("reference") })
+                    };
+
+                    match rt.lambda_class(&receiver_class) {
+                        Some(lambda) => rt.invoke_lambda(method, &lambda, &resolved.method_name, &resolved.descriptor, args),
+                        // private methods of classes and interfaces are not overridden, see jvms 6.5 invokeinterface
+                        None if rt.is_private(&resolved) => rt.invoke_resolved(&resolved, args),
+                        None => rt.invoke_virtual(insn, &receiver_class, &resolved, args)
+                    }
+                })?;
+                if let Some(value) = result {
+                    stack_frame.push_stack(value);
+                }
+            }
+            Instruction::InvokeSpecial(method_offset) => {
+                let resolved = self.resolve_method(pool, class, *method_offset)?;
+                let args = resolved.signature.pop_arguments(stack_frame, true)?;
+                let result = self.suspended(stack_frame, |rt| {
+                    if let LocalVariable::Null = args[0] {
+                        return Err(rt.throw(method, "java/lang/NullPointerException", None));
+                    }
+                    if let Some(intrinsic) = resolved.intrinsic {
+                        return rt.run_intrinsic(method, intrinsic, &args);
+                    }
+                    if let Some(target) = resolved.target {
+                        if let Some(value) = rt.run_inlined(insn, target, &args) {
+                            return Ok(Some(value));
+                        }
+                    }
+                    rt.invoke_resolved(&resolved, args)
+                })?;
+                if let Some(value) = result {
+                    stack_frame.push_stack(value);
+                }
+            }
+            Instruction::InvokeStatic(method_offset) => {
+                let resolved = self.resolve_method(pool, class, *method_offset)?;
+                let args = resolved.signature.pop_arguments(stack_frame, false)?;
+                let result = self.suspended(stack_frame, |rt| {
+                    if let Some(intrinsic) = resolved.intrinsic {
+                        return rt.run_intrinsic(method, intrinsic, &args);
+                    }
+                    rt.initialize_class(method, &resolved.class_name)?;
+
+                    debug!(class = %resolved.class_name, method = %resolved.method_name, descriptor = %resolved.descriptor, arguments = ?args, "invokestatic");
+                    if let Some(target) = resolved.target {
+                        if let Some(value) = rt.run_inlined(insn, target, &args) {
+                            return Ok(Some(value));
+                        }
+                    }
+                    rt.invoke_resolved(&resolved, args)
+                })?;
+                if let Some(value) = result {
+                    stack_frame.push_stack(value);
+                }
+            }
+            Instruction::InvokeDynamic((index, _)) => self.exec_invokedynamic(method, class, pool, stack_frame, *index)?,
+            Instruction::New(class_index) => {
+                let class_name = self.resolve_class(pool, class, *class_index)?;
+                self.initialize_for(method, stack_frame, &class_name)?;
+
+                let reference = match self.allocate_in_frame(insn.pc, &class_name) {
+                    Some(reference) => reference,
+                    None => {
+                        self.reserve(method, stack_frame, HEADER_SIZE)?;
+                        let reference = self.allocate(&class_name, ObjectData::Instance);
+                        self.register_finalizer(reference, &class_name);
+                        reference
+                    }
+                };
+                stack_frame.push_stack(StackValue::Reference(reference));
+            }
+            Instruction::NewArray(atype) => {
+                let element = match ElementType::from_atype(*atype) {
+                    Some(element) => element,
+                    None => return Err(RuntimeError::UnsupportedInstruction { instruction: instruction.name() })
+                };
+                let length = Runtime::pop_int(stack_frame)?;
+                let reference = self.new_array(method, stack_frame, element, length)?;
+                stack_frame.push_stack(StackValue::Reference(reference));
+            }
+            Instruction::AAewArray(class_index) => {
+                let component = self.resolve_class(pool, class, *class_index)?;
+                let length = Runtime::pop_int(stack_frame)?;
+                let reference = self.new_reference_array(method, stack_frame, &component, length)?;
+                stack_frame.push_stack(StackValue::Reference(reference));
+            }
+            Instruction::ArrayLength(()) => self.exec_array_length(method, stack_frame)?,
+            Instruction::CheckCast(class_index) => {
+                let target = self.resolve_class(pool, class, *class_index)?;
+                if let Some(StackValue::Reference(object)) = stack_frame.stack.last() {
+                    let class_name = self.heap.get(*object).map(|object| object.class_name.clone()).unwrap_or_default();
+                    if !self.is_assignable(&class_name, &target) {
+                        let message = format!("class {} cannot be cast to class {}", class_name.replace('/', "."), target.replace('/', "."));
+                        return Err(self.throw(method, "java/lang/ClassCastException", Some(&message)));
+                    }
+                }
+            }
+            Instruction::InstanceOf(class_index) => {
+                let target = self.resolve_class(pool, class, *class_index)?;
+                let instance = match Runtime::pop_reference(stack_frame)? {
+                    Some(object) => {
+                        let class_name = self.heap.get(object).map(|object| object.class_name.clone()).unwrap_or_default();
+                        self.is_assignable(&class_name, &target)
+                    }
+                    None => false
+                };
+                stack_frame.push_stack(StackValue::Integer(if instance { 1 } else { 0 }));
+            }
+            Instruction::IfNull(_) => return Runtime::branch_if(insn, Runtime::pop_reference(stack_frame)?.is_none()),
+            Instruction::IfNonNull(_) => return Runtime::branch_if(insn, Runtime::pop_reference(stack_frame)?.is_some()),
+            Instruction::AThrow(()) => match stack_frame.pop_stack() {
+                Some(StackValue::Reference(exception)) => return Err(self.exception_thrown(method, exception)),
+                Some(StackValue::Null) => return Err(self.throw(method, "java/lang/NullPointerException", None)),
+                Some(_) => return Err(RuntimeError::StackType { expected: String::from("reference") }),
+                None => return Err(RuntimeError::EmptyStack)
+            },
+            _ => return Err(RuntimeError::UnsupportedInstruction { instruction: instruction.name() })
+        }
+
+        Ok(Flow::Next)
+    }
+
+    /// jumps to the pre-computed target of `insn` if `condition` holds.
+    /// runs a superinstruction, `last` being the last instruction it replaces. `None` if the locals
+    /// do not hold integers, then the instructions run one by one to report the error.
+    fn execute_fused(fused: Superinstruction, last: &DecodedInsn, stack_frame: &mut StackFrame, heap: &Heap) -> Option<Flow> {
+        let int = |stack_frame: &StackFrame, index: u16| match stack_frame.local_variables.get(usize::from(index)) {
+            Some(LocalVariable::Integer(value)) => Some(*value),
+            _ => None
+        };
+
+        match fused {
+            Superinstruction::LoadLoadAdd(a, b) => {
+                let sum = (int(stack_frame, a)? as i32).wrapping_add(int(stack_frame, b)? as i32);
+                stack_frame.push_stack(StackValue::Integer(i64::from(sum)));
+                Some(Flow::Next)
+            }
+            Superinstruction::LoadLoadCompare(a, b, comparison) => {
+                if comparison.holds(int(stack_frame, a)?, int(stack_frame, b)?) {
+                    Some(Flow::Jump(last.target?))
+                } else {
+                    Some(Flow::Next)
+                }
+            }
+            Superinstruction::IncGoto(index, delta) => {
+                match stack_frame.get_variable_mut(usize::from(index)) {
+                    Some(LocalVariable::Integer(value)) => *value = i64::from((*value as i32).wrapping_add(delta as i32)),
+                    _ => return None
+                }
+                Some(Flow::Jump(last.target?))
+            }
+            Superinstruction::Constant(value, _) => {
+                stack_frame.push_stack(StackValue::Integer(i64::from(value)));
+                Some(Flow::Next)
+            }
+            Superinstruction::Branch(true, _) => Some(Flow::Jump(last.target?)),
+            Superinstruction::Branch(false, _) => Some(Flow::Next),
+            Superinstruction::UncheckedLoad(array, index) => {
+                let array = match stack_frame.local_variables.get(usize::from(array)) {
+                    Some(LocalVariable::Reference(array)) => *array,
+                    _ => return None
+                };
+                let value = match heap.get(array).map(|object| &object.data) {
+                    Some(ObjectData::Array(elements)) => elements.get(int(stack_frame, index)? as usize)?.clone(),
+                    _ => return None
+                };
+                stack_frame.push_stack(value);
+                Some(Flow::Next)
+            }
+        }
+    }
+
+    fn branch_if(insn: &DecodedInsn, condition: bool) -> Result<Flow, RuntimeError> {
+        match (condition, insn.target) {
+            (false, _) => Ok(Flow::Next),
+            (true, Some(target)) => Ok(Flow::Jump(target)),
+            (true, None) => Err(RuntimeError::InvalidJumpTarget { pc: insn.pc })
+        }
+    }
+
+    /// pops two integers and branches if `compare(value1, value2)` holds.
+    fn branch_if_icmp<F>(insn: &DecodedInsn, stack_frame: &mut StackFrame, compare: F) -> Result<Flow, RuntimeError>
+        where F: Fn(i64, i64) -> bool {
+        let rh = Runtime::pop_int(stack_frame)?;
+        let lh = Runtime::pop_int(stack_frame)?;
+        Runtime::branch_if(insn, compare(lh, rh))
+    }
+
+    /// jumps to the target of `case` of a switch, 0 being the default.
+    fn switch_to(insn: &DecodedInsn, case: usize) -> Result<Flow, RuntimeError> {
+        match insn.switch_targets.get(case) {
+            Some(&target) => Ok(Flow::Jump(target)),
+            None => Err(RuntimeError::InvalidJumpTarget { pc: insn.pc })
+        }
+    }
+
+    fn pop_int(stack_frame: &mut StackFrame) -> Result<i64, RuntimeError> {
+        match stack_frame.pop_stack() {
+            Some(StackValue::Integer(value)) => Ok(value),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("integer") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
+
+    /// pops a reference, `None` for null.
+    fn pop_reference(stack_frame: &mut StackFrame) -> Result<Option<ObjectRef>, RuntimeError> {
+        match stack_frame.pop_stack() {
+            Some(StackValue::Reference(reference)) => Ok(Some(reference)),
+            Some(StackValue::Null) => Ok(None),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("reference") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
+
+    /// pushes a constant of the constant pool onto the stack.
+    fn exec_ldc(&mut self, method: &Method, class: &ClassFile, stack_frame: &mut StackFrame, index: u16) -> Result<(), RuntimeError> {
+        let value = match class.get_constant(index) {
+            Some(ConstantType::Integer { value }) => StackValue::Integer(i64::from(*value)),
+            Some(ConstantType::String { string_index }) => match class.get_constant(*string_index) {
+                Some(ConstantType::Utf8 { value }) => {
+                    if self.heap.interned(value).is_none() {
+                        self.reserve(method, stack_frame, HEADER_SIZE + ObjectData::String(Arc::from(&**value)).size())?;
+                    }
+                    StackValue::Reference(self.intern(value))
+                }
+                _ => return Err(RuntimeError::InvalidConstant { index: *string_index, expected: String::from("Utf8") })
+            },
+            Some(ConstantType::Class { name_index }) => match class.get_constant(*name_index) {
+                Some(ConstantType::Utf8 { value }) => StackValue::Reference(self.class_object(value)),
+                _ => return Err(RuntimeError::InvalidConstant { index: *name_index, expected: String::from("Utf8") })
+            },
+            _ => return Err(RuntimeError::InvalidConstant { index, expected: String::from("loadable constant") })
+        };
+
+        stack_frame.push_stack(value);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use captured::Captured;
+    use java::class_file::read_class_file;
+
+    const TINY: &[u8] = include_bytes!("../../../sample/Tiny.class");
+
+    fn get_runtime<'a>() -> Runtime<'a> {
+        Runtime::create(read_class_file(TINY).unwrap().1)
+    }
+
+    fn run_static<'a>(rt: &mut Runtime<'a>, name: &str) -> Result<Option<StackValue>, RuntimeError> {
+        let id = rt.classes.id(&rt.main_class).unwrap();
+        let slot = rt.classes.class(id).methods.iter().position(|method| method.name.eq(name)).unwrap();
+        rt.run_method(id, slot, vec![LocalVariable::Null])
+    }
+
+    #[test]
+    fn it_stops_when_cancelled() {
+        let mut rt = get_runtime();
+        let handle = rt.cancellation_handle();
+        handle.cancel();
+
+        match run_static(&mut rt, "main") {
+            Err(RuntimeError::Cancelled) => (),
+            other => panic!("expected cancellation, got {:?}", other)
+        }
+
+        handle.reset();
+        assert!(run_static(&mut rt, "main").is_ok());
+    }
+    #[test]
+    fn it_invokes_static_methods() {
+        let mut rt = get_runtime();
+        match rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(36), LocalVariable::Integer(6)]) {
+            Ok(Some(StackValue::Integer(42))) => (),
+            other => panic!("expected 42, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_checks_arguments_before_invoking() {
+        let mut rt = get_runtime();
+        match rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(36)]) {
+            Err(RuntimeError::ArgumentCount { method, expected: 2, found: 1 }) => assert_eq!(method, "Tiny.add(II)I"),
+            other => panic!("expected a wrong argument count, got {:?}", other)
+        }
+        match rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(36), LocalVariable::Null]) {
+            Err(RuntimeError::ArgumentType { parameter, expected, found, .. }) => assert_eq!((&*parameter, &*expected, &*found), ("parameter 1", "integer", "null")),
+            other => panic!("expected a wrong argument type, got {:?}", other)
+        }
+        assert!(rt.thread.frames.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "threads")]
+    fn it_invokes_static_methods_async() {
+        use std::future::Future;
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake};
+        use std::thread::{self, Thread};
+
+        struct ThreadWaker(Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark()
+            }
+        }
+
+        // more invocations than the pool has threads, the others wait for a free one
+        let count = thread::available_parallelism().map_or(1, |threads| threads.get()) + 2;
+        let futures = (0..count).map(|_| Box::pin(get_runtime().invoke_static_async("Tiny", "get_number", "()I", vec![]))).collect::<Vec<_>>();
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        for mut future in futures {
+            let (_, result) = loop {
+                match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(output) => break output,
+                    Poll::Pending => thread::park()
+                }
+            };
+
+            match result {
+                Ok(Some(StackValue::Integer(36))) => (),
+                other => panic!("expected 36, got {:?}", other)
+            }
+        }
+
+        // a panic completes the future instead of leaving it pending
+        let mut rt = get_runtime();
+        rt.register_native("Tiny", "get_number", "()I", |_, _| panic!("the native failed"));
+        let mut future = Box::pin(rt.invoke_static_async("Tiny", "get_number", "()I", vec![]));
+        let (_, result) = loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => break output,
+                Poll::Pending => thread::park()
+            }
+        };
+        match result {
+            Err(RuntimeError::Panicked { ref method }) => assert_eq!(method, "Tiny.get_number()I"),
+            other => panic!("expected a panic, got {:?}", other)
+        }
+    }
+    #[test]
+    fn runtimes_can_be_moved_between_threads() {
+        use std::thread;
+
+        fn assert_send<T: Send>() {}
+        assert_send::<Runtime<'static>>();
+
+        let workers = (0..4).map(|_| {
+            let mut rt = get_runtime();
+            thread::spawn(move || rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(40), LocalVariable::Integer(2)]))
+        }).collect::<Vec<_>>();
+
+        for worker in workers {
+            match worker.join().unwrap() {
+                Ok(Some(StackValue::Integer(42))) => (),
+                other => panic!("expected 42, got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn runtimes_do_not_share_classes() {
+        let tiny = get_runtime();
+        let hello = Runtime::create(read_class_file(include_bytes!("../../../sample/HelloWorld.class")).unwrap().1);
+
+        assert!(tiny.classes.get("Tiny").is_some());
+        assert!(tiny.classes.get("HelloWorld").is_none());
+        assert!(hello.classes.get("Tiny").is_none());
+    }
+
+    #[test]
+    fn reloading_a_class_keeps_its_id() {
+        let mut rt = get_runtime();
+        let id = rt.classes.id("Tiny").unwrap();
+        let hello = rt.classes.insert(read_class_file(include_bytes!("../../../sample/HelloWorld.class")).unwrap().1);
+
+        assert_ne!(hello, id);
+        assert_eq!(rt.classes.insert(read_class_file(TINY).unwrap().1), id);
+        assert_eq!(rt.classes.class(id).get_class_name(), "Tiny");
+        assert_eq!(rt.classes.method(id, "add", "(II)I").map(|slot| rt.classes.class(id).methods[slot].name.to_string()), Some(String::from("add")));
+    }
+    #[test]
+    fn natives_replace_bytecode() {
+        let mut rt = get_runtime();
+        rt.register_native("Tiny", "get_number", "()I", |_, _| Ok(Some(StackValue::Integer(7))));
+
+        match rt.invoke_static("Tiny", "get_number", "()I", vec![]) {
+            Ok(Some(StackValue::Integer(7))) => (),
+            other => panic!("expected 7, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn natives_are_bound_by_capabilities() {
+        use std::path::Path;
+
+        let mut rt = Runtime::builder()
+            .capabilities(Capabilities::none())
+            .build(read_class_file(TINY).unwrap().1);
+        rt.register_native("Tiny", "get_number", "()I", |context, _| {
+            context.open_read(Path::new("/etc/hostname"))?;
+            Ok(Some(StackValue::Integer(7)))
+        });
+
+        match rt.invoke_static("Tiny", "get_number", "()I", vec![]) {
+            Err(RuntimeError::PermissionDenied { .. }) => (),
+            other => panic!("expected a denied permission, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn hooks_see_native_calls() {
+        use std::sync::Mutex;
+
+        type Recorded = (String, Vec<LocalVariable>, Result<Option<StackValue>, String>);
+        struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+        impl RuntimeHook for Recorder {
+            fn on_native_call(&mut self, call: &NativeCall) {
+                let result = call.result.as_ref().cloned().map_err(RuntimeError::to_string);
+                self.0.lock().unwrap().push((format!("{}{}", call.method_name, call.descriptor), call.arguments.to_vec(), result));
+            }
+        }
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut rt = Runtime::builder().build(read_class_file(TINY).unwrap().1);
+        rt.add_hook(Box::new(Recorder(calls.clone())));
+        rt.register_native("Tiny", "add", "(II)I", |_, arguments| match (&arguments[0], &arguments[1]) {
+            (LocalVariable::Integer(a), LocalVariable::Integer(b)) => Ok(Some(StackValue::Integer(a + b))),
+            _ => Err(RuntimeError::StackType { expected: String::from("integer") })
+        });
+        rt.register_native("Tiny", "get_number", "()I", |_, _| Ok(Some(StackValue::Integer(36))));
+        rt.invoke_static("Tiny", "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]).unwrap();
+
+        rt.register_native("Tiny", "get_number", "()I", |_, _| Err(RuntimeError::Cancelled));
+        assert!(rt.invoke_static("Tiny", "get_number", "()I", vec![]).is_err());
+        assert_eq!(*calls.lock().unwrap(), vec![
+            (String::from("get_number()I"), vec![], Ok(Some(StackValue::Integer(36)))),
+            (String::from("add(II)I"), vec![LocalVariable::Integer(36), LocalVariable::Integer(6)], Ok(Some(StackValue::Integer(42)))),
+            (String::from("get_number()I"), vec![], Err(String::from("execution was cancelled"))),
+        ]);
+    }
+
+    #[test]
+    fn verbose_jni_is_printed_to_the_stdout_of_the_runtime() {
+        let out = Captured::default();
+        let mut rt = Runtime::builder().verbose_jni(true).stdout(out.output()).build(read_class_file(TINY).unwrap().1);
+        rt.register_native("Tiny", "get_number", "()I", |_, _| Ok(Some(StackValue::Integer(36))));
+        rt.invoke_static("Tiny", "get_number", "()I", vec![]).unwrap();
+
+        let text = out.text();
+        assert!(text.starts_with("[JNI Tiny.get_number()I () returned Integer(36), "), "{}", text);
+        assert!(text.ends_with(" secs]\n"), "{}", text);
+    }
+    #[test]
+    fn hooks_see_methods_and_instructions() {
+        use java::instructions::Instruction;
+        use std::sync::Mutex;
+
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+        impl RuntimeHook for Recorder {
+            fn on_method_enter(&mut self, class_name: &str, method: &Method) {
+                self.0.lock().unwrap().push(format!("enter {}.{}", class_name, method.name));
+            }
+
+            fn on_method_exit(&mut self, class_name: &str, method: &Method, _result: &Result<Option<StackValue>, RuntimeError>) {
+                self.0.lock().unwrap().push(format!("exit {}.{}", class_name, method.name));
+            }
+
+            fn on_instruction(&mut self, _class_name: &str, _method: &Method, pc: usize, instruction: &Instruction) {
+                self.0.lock().unwrap().push(format!("{} {:?}", pc, instruction));
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut rt = get_runtime();
+        rt.add_hook(Box::new(Recorder(events.clone())));
+        rt.invoke_static("Tiny", "add", "(II)I", vec![LocalVariable::Integer(1), LocalVariable::Integer(2)]).unwrap();
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            "enter Tiny.add",
+            "0 ILoad0(())",
+            "1 ILoad1(())",
+            "2 IAdd(())",
+            "3 IReturn(())",
+            "exit Tiny.add",
+        ]);
+    }
+    #[test]
+    fn it_reports_all_unwound_frames() {
+        use java::class_file::ClassWriter;
+        use java::instructions::Instruction;
+
+        // `inner` reads a local variable it never wrote, the error unwinds through `outer`
+        let mut writer = ClassWriter::new("Unwound", "java/lang/Object");
+        let inner = writer.method_ref("Unwound", "inner", "()I");
+        writer.method(0x0009, "outer", "()I", 1, 0, &[Instruction::InvokeStatic(inner), Instruction::IReturn(())])
+            .method(0x0009, "inner", "()I", 1, 1, &[Instruction::ILoad0(()), Instruction::IReturn(())]);
+        let bytes = writer.to_bytes();
+        let mut rt = Runtime::builder().build(read_class_file(TINY).unwrap().1);
+        rt.define_class(&bytes).unwrap();
+
+        let err = rt.invoke_static("Unwound", "outer", "()I", vec![]).unwrap_err();
+        assert!(err.is_internal());
+
+        let report = rt.crash_report(&err);
+        assert!(report.contains("local variable 0 is not defined"));
+        assert!(report.contains("j  Unwound.inner()I pc 0"));
+        assert!(report.contains("j  Unwound.outer()I pc 0"));
+        assert!(report.contains("Tiny (version 54.0, 5 methods)"));
+    }
+
+    #[test]
+    fn argument_errors_are_reported_before_the_frame_exists() {
+        let mut rt = Runtime::builder().build(read_class_file(TINY).unwrap().1);
+        let err = rt.invoke_static("Tiny", "add", "(II)I", vec![]).unwrap_err();
+        // the embedder passed the wrong arguments, the vm is fine
+        assert!(!err.is_internal());
+        assert_eq!(err.to_string(), "Tiny.add(II)I takes 2 arguments, 0 were passed");
+        assert!(rt.thread.unwound_frames.is_empty());
+    }
+
+    const EXCEPTIONS: &[u8] = include_bytes!("../../../sample/ExceptionExample.class");
+
+    #[test]
+    fn it_catches_exceptions() {
+        let mut rt = Runtime::create(read_class_file(EXCEPTIONS).unwrap().1);
+        match rt.invoke_static("ExceptionExample", "caught", "()I", vec![]) {
+            Ok(Some(StackValue::Integer(42))) => (),
+            other => panic!("expected 42, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn uncaught_exceptions_have_java_stack_traces() {
+        let mut rt = Runtime::create(read_class_file(EXCEPTIONS).unwrap().1);
+        let exception = match rt.invoke_static("ExceptionExample", "main", "([Ljava/lang/String;)V", vec![LocalVariable::Null]) {
+            Err(RuntimeError::Exception { exception, class_name }) => {
+                assert_eq!("java/lang/RuntimeException", class_name);
+                exception
+            }
+            other => panic!("expected an exception, got {:?}", other)
+        };
+
+        assert_eq!(
+            "java.lang.RuntimeException: boom\n\tat ExceptionExample.thrower(ExceptionExample.java:3)\n\tat ExceptionExample.main(ExceptionExample.java:15)\n",
+            rt.format_stack_trace(exception)
+        );
+    }
+
+    #[test]
+    fn circular_causes_end_the_stack_trace() {
+        let mut rt = Runtime::create(read_class_file(EXCEPTIONS).unwrap().1);
+        let first = rt.allocate("java/lang/RuntimeException", ObjectData::Throwable(vec![]));
+        let second = rt.allocate("java/lang/IllegalStateException", ObjectData::Throwable(vec![]));
+        let message = rt.string("second");
+        for &(exception, cause) in &[(first, second), (second, first)] {
+            rt.heap.get_mut(exception).unwrap().fields.insert(String::from("cause"), StackValue::Reference(cause));
+        }
+        rt.heap.get_mut(second).unwrap().fields.insert(String::from("detailMessage"), StackValue::Reference(message));
+
+        assert_eq!(
+            "java.lang.RuntimeException\nCaused by: java.lang.IllegalStateException: second\nCaused by: [CIRCULAR REFERENCE: java.lang.RuntimeException]\n",
+            rt.format_stack_trace(first)
+        );
+    }
+
+    #[test]
+    fn it_profiles_allocations_per_class_and_site() {
+        let mut rt = Runtime::builder()
+            .allocation_profiling(true)
+            .build(read_class_file(EXCEPTIONS).unwrap().1);
+        rt.invoke_static("ExceptionExample", "caught", "()I", vec![]).unwrap();
+
+        let profile = rt.allocation_profile();
+        let exceptions = profile.class("java/lang/RuntimeException").unwrap();
+        assert_eq!(1, exceptions.allocated_count);
+        // the exception was caught and dropped, so the collection of the profile took it
+        assert_eq!(0, exceptions.retained_count);
+
+        let sites = profile.sites.iter().filter_map(|(site, _)| site.as_ref()).map(|site| site.to_string()).collect::<Vec<_>>();
+        assert!(sites.contains(&String::from("ExceptionExample.thrower()I pc 0")));
+        assert!(sites.contains(&String::from("ExceptionExample.thrower()I pc 4")));
+    }
+
+    #[test]
+    fn objects_in_reused_slots_are_not_retained_twice() {
+        let mut rt = Runtime::builder()
+            .allocation_profiling(true)
+            .build(read_class_file(EXCEPTIONS).unwrap().1);
+        let garbage = rt.allocate("Garbage", ObjectData::Instance);
+        rt.gc();
+        let kept = rt.allocate("Kept", ObjectData::Instance);
+        assert_eq!(garbage.0, kept.0);
+        rt.statics.insert((String::from("ExceptionExample"), String::from("kept")), StackValue::Reference(kept));
+
+        let profile = rt.allocation_profile();
+        assert_eq!(0, profile.class("Garbage").unwrap().retained_count);
+        assert_eq!(1, profile.class("Kept").unwrap().retained_count);
+    }
+
+    #[test]
+    fn allocations_are_not_profiled_by_default() {
+        let mut rt = Runtime::create(read_class_file(EXCEPTIONS).unwrap().1);
+        rt.invoke_static("ExceptionExample", "caught", "()I", vec![]).unwrap();
+
+        assert!(rt.allocation_profile().classes.is_empty());
+    }
+
+    #[test]
+    fn it_writes_hprof_heap_dumps() {
+        let mut rt = Runtime::create(read_class_file(EXCEPTIONS).unwrap().1);
+        rt.invoke_static("ExceptionExample", "caught", "()I", vec![]).unwrap();
+
+        let mut dump = Vec::new();
+        rt.write_heap_dump(&mut dump).unwrap();
+        assert!(dump.starts_with(b"JAVA PROFILE 1.0.2\0\0\0\0\x08"));
+
+        // walk the records, their lengths have to add up to the whole file
+        let mut offset = 31;
+        let mut tags = Vec::new();
+        let mut strings = Vec::new();
+        while offset < dump.len() {
+            let tag = dump[offset];
+            let mut length = [0u8; 4];
+            length.copy_from_slice(&dump[offset + 5..offset + 9]);
+            let length = u32::from_be_bytes(length) as usize;
+            if tag == 0x01 {
+                strings.push(String::from_utf8(dump[offset + 17..offset + 9 + length].to_vec()).unwrap());
+            }
+            tags.push(tag);
+            offset += 9 + length;
+        }
+
+        assert_eq!(dump.len(), offset);
+        assert_eq!(Some(&0x2c), tags.last());
+        assert!(tags.contains(&0x1c));
+        assert!(strings.contains(&String::from("java/lang/RuntimeException")));
+        assert!(strings.contains(&String::from("detailMessage")));
+    }
+
+    #[test]
+    fn it_throws_out_of_memory_errors_and_dumps_the_heap() {
+        let path = ::std::env::temp_dir().join(format!("rjvm_oom_{}.hprof", ::std::process::id()));
+        let mut rt = Runtime::builder()
+            .max_heap(Some(20))
+            .heap_dump_path(path.clone())
+            .heap_dump_on_out_of_memory(true)
+            .build(read_class_file(EXCEPTIONS).unwrap().1);
+
+        match rt.invoke_static("ExceptionExample", "caught", "()I", vec![]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/OutOfMemoryError" => (),
+            other => panic!("expected an OutOfMemoryError, got {:?}", other)
+        }
+
+        assert!(::std::fs::metadata(&path).unwrap().len() > 0);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn heap_dumps_can_be_requested_while_running() {
+        let path = ::std::env::temp_dir().join(format!("rjvm_trigger_{}.hprof", ::std::process::id()));
+        let mut rt = Runtime::builder()
+            .heap_dump_path(path.clone())
+            .build(read_class_file(TINY).unwrap().1);
+        rt.heap_dump_trigger().request();
+        rt.invoke_static("Tiny", "get_number", "()I", vec![]).unwrap();
+
+        assert!(::std::fs::metadata(&path).unwrap().len() > 0);
+        assert!(!rt.heap_dump_trigger().take());
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn garbage_is_collected_when_the_heap_is_full() {
+        let mut rt = Runtime::builder()
+            .max_heap(Some(64))
+            .build(read_class_file(EXCEPTIONS).unwrap().1);
+
+        for _ in 0..10 {
+            match rt.invoke_static("ExceptionExample", "caught", "()I", vec![]) {
+                Ok(Some(StackValue::Integer(42))) => (),
+                other => panic!("expected 42, got {:?}", other)
+            }
+        }
+
+        let stats = rt.gc_stats();
+        assert!(stats.collections > 0);
+        assert!(stats.freed_objects > 0);
+        assert_eq!(Some(GcReason::AllocationFailure), stats.last.map(|event| event.reason));
+    }
+
+    #[test]
+    fn explicit_collections_keep_interned_strings() {
+        let mut rt = Runtime::create(read_class_file(EXCEPTIONS).unwrap().1);
+        rt.invoke_static("ExceptionExample", "caught", "()I", vec![]).unwrap();
+
+        let event = rt.gc();
+        assert_eq!(GcReason::Explicit, event.reason);
+        assert_eq!(1, event.freed_objects);
+        assert_eq!(1, rt.heap().objects().count());
+        assert_eq!(1, rt.gc_stats().collections);
+    }
+
+    #[test]
+    fn it_reads_and_writes_fields() {
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/FieldExample.class")).unwrap().1);
+        match rt.invoke_static("FieldExample", "run", "()I", vec![]) {
+            Ok(Some(StackValue::Integer(7))) => (),
+            other => panic!("expected 7, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn watched_fields_report_old_and_new_values() {
+        use std::sync::Mutex;
+
+        struct Watcher(Arc<Mutex<Vec<String>>>);
+        impl RuntimeHook for Watcher {
+            fn on_field_watch(&mut self, event: &FieldWatchEvent) {
+                self.0.lock().unwrap().push(format!("{:?} {}.{} {:?} -> {:?} at {} pc {}", event.kind, event.class_name, event.field_name,
+                                                    event.old_value, event.new_value, event.frame.method_name(), event.frame.pc()));
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/FieldExample.class")).unwrap().1);
+        rt.add_hook(Box::new(Watcher(events.clone())));
+        rt.watch_field("FieldExample", "value");
+        rt.invoke_static("FieldExample", "run", "()I", vec![]).unwrap();
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            "PutField FieldExample.value Integer(0) -> Some(Integer(5)) at run pc 10",
+            "GetField FieldExample.value Integer(5) -> None at run pc 15",
+            "PutField FieldExample.value Integer(5) -> Some(Integer(7)) at run pc 20",
+            "GetField FieldExample.value Integer(7) -> None at run pc 24",
+        ]);
+    }
+
+    #[test]
+    fn it_branches() {
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        for &(a, b, expected) in [(5, 3, 5), (2, 9, 9), (4, 4, 4)].iter() {
+            match rt.invoke_static("Loop", "max", "(II)I", vec![LocalVariable::Integer(a), LocalVariable::Integer(b)]) {
+                Ok(Some(StackValue::Integer(value))) => assert_eq!(value, expected),
+                other => panic!("expected {}, got {:?}", expected, other)
+            }
+        }
+    }
+
+    #[test]
+    fn it_loops_on_the_checked_interpreter() {
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        match rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(10)]) {
+            Ok(Some(StackValue::Integer(45))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    #[test]
+    fn ints_wrap_around_like_on_the_jvm() {
+        const MIN: i64 = i32::MIN as i64;
+        const MAX: i64 = i32::MAX as i64;
+        let cases: &[(&str, &[i64], i64)] = &[
+            ("add", &[MAX, 1], MIN),
+            ("add", &[MIN, -1], MAX),
+            ("sub", &[MIN, 1], MAX),
+            ("mul", &[MAX, 2], -2),
+            ("mul", &[0x10000, 0x10000], 0),
+            ("div", &[MIN, -1], MIN),
+            ("div", &[-7, 2], -3),
+            ("rem", &[MIN, -1], 0),
+            ("rem", &[-7, 2], -1),
+            ("neg", &[MIN], MIN),
+            ("shl", &[1, 31], MIN),
+            ("shl", &[1, 33], 2),
+            ("shr", &[MIN, 31], -1),
+            ("ushr", &[MIN, 31], 1),
+            ("ushr", &[-1, 32], -1),
+            ("bits", &[0b1100, 0b1010], 0b0110),
+            ("inc", &[MAX - 50], MIN + 49),
+        ];
+
+        for &mode in [InterpreterMode::Checked, InterpreterMode::Fast].iter() {
+            let mut rt = Runtime::builder().interpreter(mode).build(read_class_file(include_bytes!("../../../sample/IntMath.class")).unwrap().1);
+            for &(name, arguments, expected) in cases.iter() {
+                let descriptor = if arguments.len() == 1 { "(I)I" } else { "(II)I" };
+                let arguments = arguments.iter().map(|&value| LocalVariable::Integer(value)).collect();
+                match rt.invoke_static("IntMath", name, descriptor, arguments) {
+                    Ok(Some(StackValue::Integer(value))) => assert_eq!(value, expected, "{} on {:?}", name, mode),
+                    other => panic!("{} on {:?} returned {:?}", name, mode, other)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn division_by_zero_throws() {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/IntMath.class")).unwrap().1);
+        match rt.invoke_static("IntMath", "safeDiv", "(II)I", vec![LocalVariable::Integer(1), LocalVariable::Integer(0)]) {
+            Ok(Some(StackValue::Integer(-1))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+        match rt.invoke_static("IntMath", "rem", "(II)I", vec![LocalVariable::Integer(1), LocalVariable::Integer(0)]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/ArithmeticException" => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+    }
+
+    fn frame(slots: usize) -> StackFrame {
+        StackFrame { local_variables: vec![LocalVariable::None; slots], stack: Vec::new() }
+    }
+
+    fn slots(frame: &StackFrame) -> Vec<String> {
+        frame.local_variables.iter().map(|local| format!("{:?}", local)).collect()
+    }
+
+    #[test]
+    fn storing_overwrites_the_slot() {
+        let mut frame = frame(3);
+        frame.set_variable(0, LocalVariable::Integer(1)).unwrap();
+        frame.set_variable(1, LocalVariable::Integer(2)).unwrap();
+        frame.set_variable(0, LocalVariable::Integer(3)).unwrap();
+        frame.set_variable(0, LocalVariable::Null).unwrap();
+        assert_eq!(slots(&frame), vec!["Null", "Integer(2)", "None"]);
+        assert!(frame.set_variable(3, LocalVariable::Integer(4)).is_err());
+    }
+
+    #[test]
+    fn longs_occupy_two_slots() {
+        let mut frame = frame(4);
+        frame.set_variable(1, LocalVariable::Long(7)).unwrap();
+        assert_eq!(slots(&frame), vec!["None", "Long(7)", "Top", "None"]);
+        assert!(frame.set_variable(3, LocalVariable::Long(8)).is_err());
+
+        // reusing the second slot breaks the long
+        frame.set_variable(2, LocalVariable::Integer(1)).unwrap();
+        assert_eq!(slots(&frame), vec!["None", "None", "Integer(1)", "None"]);
+
+        // a long starting in the second slot of another one breaks the first
+        frame.set_variable(0, LocalVariable::Long(1)).unwrap();
+        frame.set_variable(1, LocalVariable::Long(2)).unwrap();
+        assert_eq!(slots(&frame), vec!["None", "Long(2)", "Top", "None"]);
+
+        // a long overlapping the start of another one leaves its top behind undefined
+        frame.set_variable(0, LocalVariable::Long(3)).unwrap();
+        assert_eq!(slots(&frame), vec!["Long(3)", "Top", "None", "None"]);
+        match Runtime::exec_iload(&mut frame, 1) {
+            Err(RuntimeError::LocalType { index: 1, .. }) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+        Runtime::exec_lload(&mut frame, 0).unwrap();
+        Runtime::exec_lstore(&mut frame, 2).unwrap();
+        assert_eq!(slots(&frame), vec!["Long(3)", "Top", "Long(3)", "Top"]);
+    }
+
+    #[test]
+    fn virtual_calls_dispatch_on_the_receiver() {
+        let mut rt = Runtime::create(read_class_file(include_bytes!("../../../sample/Dispatch.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Sub.class")).unwrap().1);
+        match rt.invoke_static("Dispatch", "run", "()I", vec![]) {
+            Ok(Some(StackValue::Integer(41))) => (),
+            other => panic!("expected 41, got {:?}", other)
+        }
+
+        // the site in twice saw Dispatch first, missed on Sub and now caches Sub
+        let twice = rt.classes.decoded("Dispatch", "twice", "(LDispatch;)I").unwrap();
+        let site = twice.instructions.iter().find_map(|insn| insn.cache.as_ref()).unwrap();
+        assert_eq!(site.receiver_class(), Some(String::from("Sub")));
+    }
 }
@@ -0,0 +1,217 @@
+use java::class_file::{read_class_file, ModuleDescriptor};
+use java::runtime::{scan_classpath, Runtime, RuntimeError};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+
+/// the target of `--add-exports` which stands for the classes outside of named modules.
+pub const ALL_UNNAMED: &str = "ALL-UNNAMED";
+
+/// the modules found on the module path, which classes they contain and what they may access.
+///
+/// classes outside of a named module, from the classpath or builtin, are in the unnamed module.
+/// it reads every module, and every module may access it.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    modules: HashMap<String, ModuleDescriptor>,
+    /// the module of each package of a named module.
+    packages: HashMap<String, String>,
+    /// (module, package, target) of `--add-exports`.
+    added_exports: Vec<(String, String, String)>,
+}
+
+/// the package of a class, array classes are in the package of their element type.
+fn package_of(class_name: &str) -> &str {
+    let element = class_name.trim_start_matches('[');
+    let element = if element.len() < class_name.len() && element.starts_with('L') { &element[1..element.len() - 1] } else { element };
+    element.rfind('/').map_or("", |end| &element[..end])
+}
+
+/// the directories of the modules on `module_path`: entries with a `module-info.class` and the
+/// subdirectories of the other entries which have one, like an exploded module directory.
+fn module_directories(module_path: &[PathBuf]) -> Vec<PathBuf> {
+    let is_module = |path: &Path| path.join("module-info.class").is_file();
+    let mut directories = Vec::new();
+    for entry in module_path {
+        if is_module(entry) {
+            directories.push(entry.clone());
+            continue;
+        }
+        match fs::read_dir(entry) {
+            Ok(entries) => {
+                let mut modules = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| is_module(path)).collect::<Vec<_>>();
+                modules.sort();
+                directories.extend(modules);
+            },
+            Err(err) => warn!(path = %entry.display(), error = %err, "cannot scan module path entry")
+        }
+    }
+    directories
+}
+
+impl ModuleGraph {
+    pub fn new() -> ModuleGraph {
+        ModuleGraph::default()
+    }
+
+    /// reads the modules on `module_path`. returns the graph and the class files of the modules.
+    pub fn scan(module_path: &[PathBuf]) -> (ModuleGraph, Vec<PathBuf>) {
+        let mut graph = ModuleGraph::new();
+        let mut classes = Vec::new();
+        for directory in module_directories(module_path) {
+            let descriptor = fs::read(directory.join("module-info.class")).ok()
+                .and_then(|bytes| read_class_file(&bytes).ok().and_then(|(_, class)| class.get_module()));
+            let descriptor = match descriptor {
+                Some(descriptor) => descriptor,
+                None => {
+                    warn!(path = %directory.display(), "cannot read module descriptor");
+                    continue;
+                }
+            };
+            if graph.modules.contains_key(&descriptor.name) {
+                // like on the jvm the first module of a name on the module path wins
+                continue;
+            }
+
+            for path in scan_classpath(slice::from_ref(&directory)) {
+                let relative = match path.strip_prefix(&directory) {
+                    Ok(relative) if relative != Path::new("module-info.class") => relative,
+                    _ => continue
+                };
+                let package = relative.parent().map(|parent| parent.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/"));
+                graph.packages.entry(package.unwrap_or_default()).or_insert_with(|| descriptor.name.clone());
+                classes.push(path);
+            }
+            debug!(module = descriptor.name.as_str(), path = %directory.display(), "found module");
+            graph.modules.insert(descriptor.name.clone(), descriptor);
+        }
+        (graph, classes)
+    }
+
+    /// the descriptor of a module on the module path.
+    pub fn module(&self, name: &str) -> Option<&ModuleDescriptor> {
+        self.modules.get(name)
+    }
+
+    /// the named module of a class, `None` for the unnamed module.
+    pub fn module_of(&self, class_name: &str) -> Option<&str> {
+        self.packages.get(package_of(class_name)).map(String::as_str)
+    }
+
+    /// exports `package` of `module` to `target`, which may be `ALL_UNNAMED`, like `--add-exports`.
+    pub fn add_exports(&mut self, module: &str, package: &str, target: &str) {
+        self.added_exports.push((String::from(module), package.replace('.', "/"), String::from(target)));
+    }
+
+    /// true if `from` reads `to` through its `requires`, or those required transitively by them.
+    pub fn reads(&self, from: &str, to: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![(from, true)];
+        while let Some((module, direct)) = pending.pop() {
+            if module == to {
+                return true;
+            }
+            if !seen.insert(module) {
+                continue;
+            }
+            if let Some(descriptor) = self.modules.get(module) {
+                pending.extend(descriptor.requires.iter()
+                    .filter(|&&(_, transitive)| direct || transitive)
+                    .map(|(name, _)| (name.as_str(), false)));
+            }
+        }
+        false
+    }
+
+    /// true if `module` exports `package` to `target`, `None` being the unnamed module.
+    pub fn exports(&self, module: &str, package: &str, target: Option<&str>) -> bool {
+        let exported = self.modules.get(module).is_none_or(|descriptor| descriptor.exports.iter()
+            .any(|(exported, targets)| exported == package && (targets.is_empty() || target.is_some_and(|target| targets.iter().any(|to| to == target)))));
+        exported || self.added_exports.iter()
+            .any(|(from, exported, to)| from == module && exported == package && to == target.unwrap_or(ALL_UNNAMED))
+    }
+
+    /// checks that `from_class` may access the public types of `to_class`. returns why it may not.
+    pub fn check_access(&self, from_class: &str, to_class: &str) -> Result<(), String> {
+        let (from, to) = (self.module_of(from_class), self.module_of(to_class));
+        let to = match to {
+            Some(to) if Some(to) != from => to,
+            _ => return Ok(())
+        };
+        let from_name = from.map_or_else(|| String::from("unnamed module"), |from| format!("module {}", from));
+        if let Some(from) = from {
+            if !self.reads(from, to) {
+                return Err(format!("class {} (in {}) cannot access class {} (in module {}) because {} does not read module {}", from_class, from_name, to_class, to, from_name, to));
+            }
+        }
+        if !self.exports(to, package_of(to_class), from) {
+            return Err(format!("class {} (in {}) cannot access class {} (in module {}) because module {} does not export {} to {}", from_class, from_name, to_class, to, to, package_of(to_class), from_name));
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// the modules of the module path.
+    pub fn modules(&self) -> &ModuleGraph {
+        &self.modules
+    }
+
+    /// fails if the module of `from_class` may not access `to_class`, when `from_class` resolves a
+    /// reference to it.
+    pub(super) fn check_module_access(&self, from_class: &str, to_class: &str) -> Result<(), RuntimeError> {
+        self.modules.check_access(from_class, to_class).map_err(|message| RuntimeError::IllegalAccess { message })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::runtime::{ByteStore, StackValue};
+    use super::*;
+
+    fn module_path() -> Vec<PathBuf> {
+        vec![PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/sample/modules"))]
+    }
+
+    fn main_class<'a>() -> ::java::class_file::ClassFile<'a> {
+        read_class_file(include_bytes!("../../../sample/modules/app/app/Main.class")).unwrap().1
+    }
+
+    #[test]
+    fn it_reads_the_module_path() {
+        let (graph, classes) = ModuleGraph::scan(&module_path());
+        assert_eq!(classes.len(), 3);
+        assert_eq!(graph.module_of("lib/api/Api"), Some("lib"));
+        assert_eq!(graph.module_of("[Llib/api/Api;"), Some("lib"));
+        assert_eq!(graph.module_of("Tiny"), None);
+        assert_eq!(graph.module("lib").unwrap().exports, vec![(String::from("lib/api"), vec![])]);
+
+        assert!(graph.reads("app", "lib"));
+        assert!(graph.reads("app", "java.base"));
+        assert!(!graph.reads("lib", "app"));
+        assert!(graph.check_access("app/Main", "lib/api/Api").is_ok());
+        assert!(graph.check_access("Tiny", "lib/api/Api").is_ok());
+        assert!(graph.check_access("lib/api/Api", "app/Main").is_err());
+        assert!(graph.check_access("app/Main", "java/lang/Object").is_ok());
+    }
+
+    #[test]
+    fn only_exported_packages_can_be_accessed() {
+        let store = ByteStore::new();
+        let mut rt = Runtime::builder().byte_store(&store).module_path(module_path()).build(main_class());
+        assert_eq!(rt.invoke_static("app/Main", "exported", "()I", vec![]).unwrap(), Some(StackValue::Integer(42)));
+        match rt.invoke_static("app/Main", "internal", "()I", vec![]) {
+            Err(err) => match err.root() {
+                RuntimeError::IllegalAccess { message } => assert!(message.ends_with("because module lib does not export lib/internal to module app")),
+                other => panic!("internal failed with {:?}", other)
+            },
+            other => panic!("internal returned {:?}", other)
+        }
+
+        let mut rt = Runtime::builder().byte_store(&store).module_path(module_path())
+            .add_exports("lib", "lib.internal", "app")
+            .build(main_class());
+        assert_eq!(rt.invoke_static("app/Main", "internal", "()I", vec![]).unwrap(), Some(StackValue::Integer(7)));
+    }
+}
@@ -0,0 +1,203 @@
+use java::charset::Charset;
+use java::runtime::{Capabilities, Environment, LocalVariable, Runtime, RuntimeError, StackValue};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+#[cfg(feature = "sockets")]
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// a method implemented in rust. gets the arguments in the same order as the java method declares them.
+pub type NativeMethod = Arc<dyn Fn(&mut NativeContext, Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> + Send + Sync>;
+
+/// everything a native may touch.
+///
+/// natives must reach the host only through this context. every access is checked against
+/// the `Capabilities` of the runtime and fails with `RuntimeError::PermissionDenied` if not allowed.
+pub struct NativeContext<'r> {
+    environment: &'r mut Environment,
+    capabilities: &'r Capabilities,
+}
+
+impl<'r> NativeContext<'r> {
+    pub fn new(environment: &'r mut Environment, capabilities: &'r Capabilities) -> NativeContext<'r> {
+        NativeContext { environment, capabilities }
+    }
+
+    pub fn environment(&mut self) -> &mut Environment {
+        self.environment
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        self.capabilities
+    }
+
+    pub fn open_read(&self, path: &Path) -> Result<File, RuntimeError> {
+        if !self.capabilities.can_read(path) {
+            return Err(RuntimeError::PermissionDenied { action: format!("read {}", path.display()) });
+        }
+
+        File::open(path).map_err(|err| RuntimeError::Host { action: format!("open {}", path.display()), message: err.to_string() })
+    }
+
+    pub fn open_write(&self, path: &Path, append: bool) -> Result<File, RuntimeError> {
+        if !self.capabilities.can_write(path) {
+            return Err(RuntimeError::PermissionDenied { action: format!("write {}", path.display()) });
+        }
+
+        OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(path)
+            .map_err(|err| RuntimeError::Host { action: format!("open {}", path.display()), message: err.to_string() })
+    }
+
+    /// opens a file for reading and writing, like a `RandomAccessFile` in mode `rw`. creates it if
+    /// missing and keeps the bytes of an existing file, writes past them extend it.
+    pub fn open_read_write(&self, path: &Path) -> Result<File, RuntimeError> {
+        if !self.capabilities.can_read(path) || !self.capabilities.can_write(path) {
+            return Err(RuntimeError::PermissionDenied { action: format!("read and write {}", path.display()) });
+        }
+
+        OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)
+            .map_err(|err| RuntimeError::Host { action: format!("open {}", path.display()), message: err.to_string() })
+    }
+
+    /// reads up to `max` bytes from a file or socket opened through this context.
+    /// the bytes go through the environment, so recorded executions replay them.
+    pub fn read<R: Read>(&mut self, source: &mut R, max: usize) -> Result<Vec<u8>, RuntimeError> {
+        self.environment.read(|| {
+            let mut buffer = vec![0; max];
+            let count = source.read(&mut buffer)?;
+            buffer.truncate(count);
+            Ok(buffer)
+        }).map_err(|err| RuntimeError::Host { action: String::from("read"), message: err.to_string() })
+    }
+
+    /// reads a whole text file, decoded with `charset` like a `FileReader` would.
+    pub fn read_text(&mut self, path: &Path, charset: Charset) -> Result<String, RuntimeError> {
+        let mut file = self.open_read(path)?;
+        let mut bytes = Vec::new();
+        loop {
+            let chunk = self.read(&mut file, 8192)?;
+            if chunk.is_empty() {
+                break;
+            }
+            bytes.extend(chunk);
+        }
+        Ok(charset.decode(&bytes))
+    }
+
+    /// writes `text` encoded with `charset` like a `FileWriter` would.
+    pub fn write_text(&self, path: &Path, text: &str, charset: Charset, append: bool) -> Result<(), RuntimeError> {
+        self.open_write(path, append)?.write_all(&charset.encode(text))
+            .map_err(|err| RuntimeError::Host { action: format!("write {}", path.display()), message: err.to_string() })
+    }
+
+    pub fn env_var(&mut self, name: &str) -> Result<Option<String>, RuntimeError> {
+        if !self.capabilities.can_read_environment() {
+            return Err(RuntimeError::PermissionDenied { action: format!("read environment variable {}", name) });
+        }
+
+        Ok(self.environment.env_var(|| env::var(name).ok()))
+    }
+
+    #[cfg(feature = "sockets")]
+    pub fn connect(&self, address: &str) -> Result<TcpStream, RuntimeError> {
+        if !self.capabilities.can_use_network() {
+            return Err(RuntimeError::PermissionDenied { action: format!("connect to {}", address) });
+        }
+
+        TcpStream::connect(address).map_err(|err| RuntimeError::Host { action: format!("connect to {}", address), message: err.to_string() })
+    }
+
+    pub fn spawn(&self, command: &mut Command) -> Result<Child, RuntimeError> {
+        if !self.capabilities.can_spawn_processes() {
+            return Err(RuntimeError::PermissionDenied { action: format!("spawn {:?}", command) });
+        }
+
+        command.spawn().map_err(|err| RuntimeError::Host { action: format!("spawn {:?}", command), message: err.to_string() })
+    }
+}
+
+/// natives registered on a runtime, keyed by class name, method name and descriptor.
+#[derive(Default)]
+pub struct NativeRegistry {
+    methods: HashMap<(String, String, String), NativeMethod>,
+}
+
+impl NativeRegistry {
+    pub fn new() -> NativeRegistry {
+        NativeRegistry { methods: HashMap::new() }
+    }
+
+    pub fn register(&mut self, class_name: &str, method_name: &str, descriptor: &str, method: NativeMethod) {
+        self.methods.insert((String::from(class_name), String::from(method_name), String::from(descriptor)), method);
+    }
+
+    pub fn get(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<NativeMethod> {
+        self.methods.get(&(String::from(class_name), String::from(method_name), String::from(descriptor))).cloned()
+    }
+}
+
+/// one call of a registered native, as hooks and `-verbose:jni` see it.
+#[derive(Debug)]
+pub struct NativeCall<'c> {
+    pub class_name: &'c str,
+    pub method_name: &'c str,
+    pub descriptor: &'c str,
+    /// the arguments the native got, the receiver first for instance methods.
+    pub arguments: &'c [LocalVariable],
+    pub result: &'c Result<Option<StackValue>, RuntimeError>,
+    pub duration: Duration,
+}
+
+/// formats the call like `-verbose:jni`, with the values as the interpreter keeps them.
+impl<'c> fmt::Display for NativeCall<'c> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[JNI {}.{}{} (", self.class_name, self.method_name, self.descriptor)?;
+        for (index, argument) in self.arguments.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}", argument)?;
+        }
+        write!(f, ") ")?;
+        match self.result {
+            Ok(Some(value)) => write!(f, "returned {:?}", value)?,
+            Ok(None) => write!(f, "returned")?,
+            Err(error) => write!(f, "failed: {}", error)?,
+        }
+        write!(f, ", {:.7} secs]", self.duration.as_secs_f64())
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// calls a registered native and reports the call to the hooks and `-verbose:jni`. the
+    /// arguments are only copied if someone looks at them.
+    pub(super) fn call_native(&mut self, native: &NativeMethod, class_name: &str, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+        if !self.verbose_jni && self.hooks.is_empty() {
+            let mut context = NativeContext::new(&mut self.environment, &self.capabilities);
+            return native(&mut context, arguments);
+        }
+
+        let traced = arguments.clone();
+        let start = Instant::now();
+        let result = {
+            let mut context = NativeContext::new(&mut self.environment, &self.capabilities);
+            native(&mut context, arguments)
+        };
+        let call = NativeCall { class_name, method_name, descriptor, arguments: &traced, result: &result, duration: start.elapsed() };
+        debug!(class = class_name, method = method_name, descriptor, duration = ?call.duration, "native call");
+        if self.verbose_jni {
+            self.print_verbose(&call);
+        }
+        for hook in self.hooks.iter_mut() {
+            hook.on_native_call(&call);
+        }
+
+        result
+    }
+}
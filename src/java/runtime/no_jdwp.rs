@@ -0,0 +1,23 @@
+//! stands in for the jdwp debugger in builds without the `threads` or `sockets` features, e.g. for
+//! wasm32-wasi. a debugger can never attach there, so every debug point is skipped.
+
+use java::class_file::Method;
+use java::runtime::Runtime;
+use super::StackFrame;
+
+#[allow(dead_code)]
+pub(super) struct DebugPoint<'p, 'a: 'p> {
+    pub method: &'p Method<'a>,
+    pub frame: &'p StackFrame,
+    pub pc: usize,
+}
+
+/// a connected JDWP debugger, which does not exist in this build.
+pub enum Debugger {}
+
+impl<'a> Runtime<'a> {
+    /// does nothing, no debugger is ever attached.
+    pub fn detach_debugger(&mut self) {}
+
+    pub(super) fn debug_point(&mut self, _point: DebugPoint) {}
+}
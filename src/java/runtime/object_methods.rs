@@ -0,0 +1,236 @@
+use java::class_file::{ClassFile, ConstantType, Method};
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeConstantPool, RuntimeError, StackValue};
+use super::StackFrame;
+use super::call_site::Bootstrap;
+use super::heap::HEADER_SIZE;
+
+/// `MethodHandle` reference kind of a getter reading an instance field.
+const REF_GET_FIELD: u8 = 1;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ObjectMethod {
+    ToString,
+    HashCode,
+    Equals,
+}
+
+/// `toString`, `hashCode` or `equals` of a record class, linked by `ObjectMethods.bootstrap`.
+/// all three are computed from the components of the record, in declaration order.
+#[derive(Debug)]
+pub struct RecordMethod {
+    pub method: ObjectMethod,
+    pub record: String,
+    /// the name and descriptor of the field of each component.
+    pub components: Vec<(String, String)>,
+    descriptor: String,
+}
+
+impl<'a> Runtime<'a> {
+    /// `ObjectMethods.bootstrap`: the static arguments are the record class, the names of its
+    /// components separated by `;` and a getter method handle for each component.
+    pub(super) fn link_record_method(&self, pool: &RuntimeConstantPool, class: &ClassFile<'a>, bootstrap: &Bootstrap) -> Result<RecordMethod, RuntimeError> {
+        let method = match bootstrap.name {
+            "toString" => ObjectMethod::ToString,
+            "hashCode" => ObjectMethod::HashCode,
+            "equals" => ObjectMethod::Equals,
+            name => return Err(RuntimeError::InvalidBytecode { message: format!("ObjectMethods cannot implement {}", name) })
+        };
+        let record = match bootstrap.arguments.first() {
+            Some(&index) => self.resolve_class(pool, class, index)?,
+            None => return Err(RuntimeError::InvalidBytecode { message: String::from("ObjectMethods without record class") })
+        };
+
+        let components = bootstrap.arguments.iter().skip(2)
+            .map(|&index| match class.get_constant(index) {
+                Some(ConstantType::MethodHandle { reference_kind: REF_GET_FIELD, reference_index }) => {
+                    let field = self.resolve_field(pool, class, *reference_index)?;
+                    Ok((field.field_name.clone(), field.descriptor.clone()))
+                }
+                _ => Err(RuntimeError::InvalidConstant { index, expected: String::from("getter MethodHandle") })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RecordMethod { method, record: String::clone(&record), components, descriptor: String::from(bootstrap.descriptor) })
+    }
+
+    /// runs a record method with the record and, for `equals`, the other object on the stack.
+    pub(super) fn exec_record_method(&mut self, method: &Method, stack_frame: &mut StackFrame, record_method: &RecordMethod) -> Result<(), RuntimeError> {
+        // the record stays on the stack, visible to the garbage collector, while the methods of its components run
+        let arguments = self.peek_arguments(stack_frame, &record_method.descriptor, false)?;
+        let record = match arguments.first() {
+            Some(LocalVariable::Reference(record)) => *record,
+            Some(LocalVariable::Null) => return Err(self.throw(method, "java/lang/NullPointerException", None)),
+            _ => return Err(RuntimeError::StackType { expected: String::from("record") })
+        };
+
+        let value = match record_method.method {
+            ObjectMethod::ToString => {
+                let data = ObjectData::String(self.suspended(stack_frame, |rt| rt.record_to_string(record_method, record))?.into());
+                self.reserve(method, stack_frame, HEADER_SIZE + data.size())?;
+                StackValue::Reference(self.allocate("java/lang/String", data))
+            }
+            ObjectMethod::HashCode => self.suspended(stack_frame, |rt| -> Result<StackValue, RuntimeError> {
+                let mut hash = 0i32;
+                for (name, descriptor) in record_method.components.iter() {
+                    let component = rt.component(record, name, descriptor);
+                    hash = hash.wrapping_mul(31).wrapping_add(rt.value_hash_code(descriptor, &component)?);
+                }
+                Ok(StackValue::Integer(i64::from(hash)))
+            })?,
+            ObjectMethod::Equals => {
+                let other = match arguments.get(1) {
+                    Some(LocalVariable::Reference(other)) => Some(*other),
+                    _ => None
+                };
+                let equal = match other {
+                    Some(other) if self.heap.get(other).is_some_and(|other| other.class_name == record_method.record) => self.suspended(stack_frame, |rt| -> Result<bool, RuntimeError> {
+                        for (name, descriptor) in record_method.components.iter() {
+                            let (left, right) = (rt.component(record, name, descriptor), rt.component(other, name, descriptor));
+                            if !rt.values_equal(&left, &right)? {
+                                return Ok(false);
+                            }
+                        }
+                        Ok(true)
+                    })?,
+                    _ => false
+                };
+                StackValue::Integer(if equal { 1 } else { 0 })
+            }
+        };
+
+        let depth = stack_frame.stack.len() - arguments.len();
+        stack_frame.stack.truncate(depth);
+        stack_frame.push_stack(value);
+        Ok(())
+    }
+
+    fn component(&self, record: ObjectRef, name: &str, descriptor: &str) -> StackValue {
+        self.heap.get(record).and_then(|object| object.fields.get(name).cloned())
+            .unwrap_or_else(|| Runtime::default_value(descriptor))
+    }
+
+    /// `Point[x=1, y=2]`, with the simple name of the record class.
+    fn record_to_string(&mut self, record_method: &RecordMethod, record: ObjectRef) -> Result<String, RuntimeError> {
+        let simple_name = record_method.record.rsplit(['/', '$']).next().unwrap_or_default();
+        let mut out = format!("{}[", simple_name);
+        for (index, (name, descriptor)) in record_method.components.iter().enumerate() {
+            if index > 0 {
+                out.push_str(", ");
+            }
+            let value = match (descriptor.as_str(), self.component(record, name, descriptor)) {
+                ("Z", StackValue::Integer(value)) => String::from(if value != 0 { "true" } else { "false" }),
+                ("C", StackValue::Integer(value)) => std::char::from_u32(u32::from(value as u16)).unwrap_or('\u{fffd}').to_string(),
+                (_, StackValue::Integer(value)) | (_, StackValue::Long(value)) => value.to_string(),
+                (_, StackValue::Reference(reference)) => self.object_to_string(reference)?,
+                _ => String::from("null")
+            };
+            out.push_str(&format!("{}={}", name, value));
+        }
+        out.push(']');
+        Ok(out)
+    }
+
+    /// the hash code of a component like the `hashCode` of its boxed type computes it.
+    fn value_hash_code(&mut self, descriptor: &str, value: &StackValue) -> Result<i32, RuntimeError> {
+        Ok(match (descriptor, value) {
+            ("Z", StackValue::Integer(value)) => if *value != 0 { 1231 } else { 1237 },
+            (_, StackValue::Integer(value)) => *value as i32,
+            (_, StackValue::Long(value)) => (*value ^ ((*value as u64) >> 32) as i64) as i32,
+            (_, StackValue::Reference(reference)) => self.object_hash_code(*reference)?,
+            _ => 0
+        })
+    }
+
+    /// `hashCode` of an object. strings hash their characters, classes without a `hashCode` method
+    /// use the identity hash code.
+    pub(super) fn object_hash_code(&mut self, object: ObjectRef) -> Result<i32, RuntimeError> {
+        if let Some(value) = self.heap.string_value(object) {
+            return Ok(value.encode_utf16().fold(0i32, |hash, unit| hash.wrapping_mul(31).wrapping_add(i32::from(unit))));
+        }
+
+        let class_name = self.heap.get(object).map(|object| object.class_name.clone()).unwrap_or_default();
+        match self.method_target(&class_name, "hashCode", "()I") {
+            Some((class, slot)) => match self.run_method(class, slot, vec![LocalVariable::Reference(object)])? {
+                Some(StackValue::Integer(hash)) => Ok(hash as i32),
+                _ => Err(RuntimeError::InvalidReturnValue { expected: String::from("integer") })
+            },
+            None => Ok(self.identity_hash_code(object))
+        }
+    }
+
+    /// `Objects.equals` for two components of the same type.
+    fn values_equal(&mut self, left: &StackValue, right: &StackValue) -> Result<bool, RuntimeError> {
+        match (left, right) {
+            (StackValue::Reference(left), StackValue::Reference(right)) if left != right => {
+                if let (Some(left), Some(right)) = (self.heap.string_value(*left), self.heap.string_value(*right)) {
+                    return Ok(left == right);
+                }
+
+                let class_name = self.heap.get(*left).map(|object| object.class_name.clone()).unwrap_or_default();
+                match self.method_target(&class_name, "equals", "(Ljava/lang/Object;)Z") {
+                    Some((class, slot)) => Ok(self.run_method(class, slot, vec![LocalVariable::Reference(*left), LocalVariable::Reference(*right)])?
+                        == Some(StackValue::Integer(1))),
+                    None => Ok(false)
+                }
+            }
+            (left, right) => Ok(left == right)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Records.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/RecordPoint.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/RecordPerson.class")).unwrap().1);
+        rt
+    }
+
+    fn call(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> StackValue {
+        match rt.invoke_static("Records", name, descriptor, arguments) {
+            Ok(Some(value)) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    fn string(rt: &mut Runtime, name: &str) -> String {
+        match call(rt, name, "()Ljava/lang/String;", vec![]) {
+            StackValue::Reference(value) => String::from(rt.heap().string_value(value).unwrap()),
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn records_format_their_components() {
+        let mut rt = runtime();
+        assert_eq!(string(&mut rt, "show"), "RecordPoint[x=1, y=-2]");
+        assert_eq!(string(&mut rt, "showNested"), "RecordPerson[name=Ann, id=1, active=true, grade=A, home=RecordPoint[x=3, y=4]]");
+    }
+
+    #[test]
+    fn records_hash_their_components() {
+        let mut rt = runtime();
+        assert_eq!(call(&mut rt, "hash", "(II)I", vec![LocalVariable::Integer(3), LocalVariable::Integer(4)]), StackValue::Integer(31 * 3 + 4));
+
+        // like java: "Ann".hashCode(), Long.hashCode(1), Boolean.hashCode(true), 'A' and the point
+        let expected = [65_985i32, 1, 1231, 65, 31 * 3 + 4].iter().fold(0i32, |hash, &component| hash.wrapping_mul(31).wrapping_add(component));
+        assert_eq!(call(&mut rt, "hashNested", "()I", vec![]), StackValue::Integer(i64::from(expected)));
+    }
+
+    #[test]
+    fn records_compare_their_components() {
+        let mut rt = runtime();
+        assert_eq!(call(&mut rt, "same", "(II)Z", vec![LocalVariable::Integer(1), LocalVariable::Integer(2)]), StackValue::Integer(1));
+        assert_eq!(call(&mut rt, "same", "(II)Z", vec![LocalVariable::Integer(1), LocalVariable::Integer(3)]), StackValue::Integer(0));
+        assert_eq!(call(&mut rt, "notARecord", "()Z", vec![]), StackValue::Integer(0));
+
+        let ann = LocalVariable::Reference(rt.heap.intern("Ann"));
+        assert_eq!(call(&mut rt, "sameNested", "(Ljava/lang/String;)Z", vec![ann]), StackValue::Integer(1));
+        let bob = LocalVariable::Reference(rt.heap.intern("Bob"));
+        assert_eq!(call(&mut rt, "sameNested", "(Ljava/lang/String;)Z", vec![bob]), StackValue::Integer(0));
+    }
+}
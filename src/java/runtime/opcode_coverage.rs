@@ -0,0 +1,134 @@
+use java::instructions::Instruction;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// how often each opcode ran, across every runtime of the process.
+static EXECUTED: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+
+/// counts an instruction which ran in a debug build. release builds count nothing.
+#[inline(always)]
+pub(super) fn record(instruction: &Instruction) {
+    if cfg!(debug_assertions) {
+        EXECUTED[usize::from(instruction.opcode())].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// the number of times each opcode of `Instruction` ran in this process, counted by debug builds.
+/// a superinstruction counts as the instructions it replaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeCoverage {
+    counts: BTreeMap<u8, u64>,
+}
+
+impl OpcodeCoverage {
+    /// the counts of this process so far.
+    pub fn current() -> OpcodeCoverage {
+        OpcodeCoverage {
+            counts: Instruction::OPCODES.iter().map(|&(opcode, _)| (opcode, EXECUTED[usize::from(opcode)].load(Ordering::Relaxed))).collect()
+        }
+    }
+
+    pub fn count(&self, opcode: u8) -> u64 {
+        self.counts.get(&opcode).cloned().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, other: &OpcodeCoverage) {
+        for (opcode, count) in other.counts.iter() {
+            *self.counts.entry(*opcode).or_insert(0) += count;
+        }
+    }
+
+    /// the names of the opcodes which never ran.
+    pub fn unexecuted(&self) -> Vec<&'static str> {
+        Instruction::OPCODES.iter().filter(|&&(opcode, _)| self.count(opcode) == 0).map(|&(_, name)| name).collect()
+    }
+}
+
+/// a line per opcode with its value, name and count.
+impl fmt::Display for OpcodeCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(opcode, name) in Instruction::OPCODES.iter() {
+            writeln!(f, "0x{:02x} {:<16} {}", opcode, name, self.count(opcode))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::{read_class_file, ClassFile};
+    use java::runtime::{scan_classpath, InterpreterMode, LocalVariable, Runtime};
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::Path;
+    use super::*;
+
+    /// the opcodes no class in `sample/` uses, so no test runs them yet. an opcode which is not
+    /// listed has to be used by a sample, and one which is has to be removed once a sample uses it.
+    const UNTESTED: &[&str] = &[
+        "NOOP", "FConst0", "FConst2", "DConst0", "LDCW", "LDC2W", "LLoad", "FLoad", "DLoad", "FLoad0",
+        "FLoad1", "FLoad2", "FLoad3", "DLoad0", "DLoad1", "DLoad2", "DLoad3", "LALoad", "FALoad", "DALoad",
+        "LStore", "FStore", "DStore", "LStore0", "LStore2", "FStore0", "FStore1", "FStore2", "FStore3",
+        "DStore0", "DStore1", "DStore2", "DStore3", "LAStore", "FAStore", "DAStore", "Pop2", "DupX1", "DupX2",
+        "Dup2", "Dup2X1", "Dup2X2", "Swap", "FAdd", "DAdd", "LSub", "FSub", "DSub", "FMul", "DMul", "LDiv",
+        "FDiv", "DDiv", "LRem", "FRem", "DRem", "LNeg", "FNeg", "DNeg", "LShl", "LShr", "LUSHR", "LAnd",
+        "LOr", "LXor", "I2F", "I2D", "L2I", "L2F", "L2D", "F2I", "F2L", "F2D", "D2I", "D2L", "D2F", "LCmp",
+        "FCmpL", "FCmpG", "DCmpL", "DCmpG", "Ifge", "IfICmpEQ", "IfICmpLT", "IfACmpEQ", "JSR", "Ret",
+        "FReturn", "DReturn", "InstanceOf", "MonitorEnter", "MonitorExit", "Wide", "GotoW", "JSRW",
+        "Breakpoint", "ImpDep1", "ImpDep2",
+    ];
+
+    #[test]
+    fn both_interpreters_count_opcodes() {
+        // other tests run at the same time, so only look at what grows
+        let before = OpcodeCoverage::current();
+        for &mode in [InterpreterMode::Checked, InterpreterMode::Fast].iter() {
+            let mut rt = Runtime::builder().interpreter(mode)
+                .build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+            rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(10)]).unwrap();
+        }
+        let after = OpcodeCoverage::current();
+        // `iinc` of the loop is counted as part of a superinstruction, and by the fast interpreter
+        assert!(after.count(0x84) >= before.count(0x84) + 20);
+        assert!(after.count(0xac) >= before.count(0xac) + 2);
+    }
+
+    #[test]
+    fn the_table_lists_every_opcode() {
+        let mut coverage = OpcodeCoverage::current();
+        coverage.counts.insert(0x00, 0);
+        coverage.counts.insert(0x60, 7);
+        let text = coverage.to_string();
+        assert!(text.contains("0x60 IAdd             7\n"));
+        assert_eq!(text.lines().count(), Instruction::OPCODES.len());
+        assert!(coverage.unexecuted().contains(&"NOOP"));
+
+        let mut sum = coverage.clone();
+        sum.add(&coverage);
+        assert_eq!(sum.count(0x60), 14);
+    }
+
+    #[test]
+    fn only_the_listed_opcodes_are_untested() {
+        let mut used = BTreeSet::new();
+        for path in scan_classpath(&[Path::new(env!("CARGO_MANIFEST_DIR")).join("sample")]) {
+            let bytes = fs::read(&path).unwrap();
+            let class = match ClassFile::parse(&bytes) {
+                Ok(class) => class,
+                // some samples are broken on purpose
+                Err(_) => continue
+            };
+            for code in class.methods.iter().filter_map(|method| method.get_code()) {
+                used.extend(code.instructions().unwrap_or_default().iter().map(Instruction::opcode));
+            }
+        }
+
+        let untested = Instruction::OPCODES.iter().filter(|&&(opcode, _)| !used.contains(&opcode)).map(|&(_, name)| name).collect::<Vec<_>>();
+        let covered = UNTESTED.iter().filter(|name| !untested.contains(name)).collect::<Vec<_>>();
+        assert!(covered.is_empty(), "remove the opcodes the samples use now from UNTESTED: {:?}", covered);
+        let missing = untested.iter().filter(|name| !UNTESTED.contains(name)).collect::<Vec<_>>();
+        assert!(missing.is_empty(), "no sample uses these opcodes, add one or list them in UNTESTED: {:?}", missing);
+    }
+
+}
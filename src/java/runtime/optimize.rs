@@ -0,0 +1,215 @@
+use java::analysis::{CountedLoop, SsaFunction, Terminator};
+use java::class_file::{ClassFile, Method};
+use java::instructions::Instruction;
+use java::runtime::{DecodedInsn, DecodedMethod, Superinstruction};
+
+/// the number of values an instruction without effects pops and pushes. divisions are left out,
+/// they can throw.
+fn pure_effect(instruction: &Instruction) -> Option<(usize, usize)> {
+    Some(match instruction {
+        Instruction::NOOP(()) => (0, 0),
+        Instruction::IConstm1(()) | Instruction::IConst0(()) | Instruction::IConst1(()) | Instruction::IConst2(()) |
+        Instruction::IConst3(()) | Instruction::IConst4(()) | Instruction::IConst5(()) | Instruction::BIPush(_) |
+        Instruction::SIPush(_) | Instruction::ILoad(_) | Instruction::ILoad0(()) | Instruction::ILoad1(()) |
+        Instruction::ILoad2(()) | Instruction::ILoad3(()) => (0, 1),
+        Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) | Instruction::IShl(()) | Instruction::IShr(()) |
+        Instruction::IUSHR(()) | Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(()) => (2, 1),
+        Instruction::INeg(()) => (1, 1),
+        Instruction::Dup(()) => (1, 2),
+        Instruction::Pop(()) => (1, 0),
+        _ => return None
+    })
+}
+
+/// the number of operands of a conditional branch on ints.
+fn branch_operands(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) | Instruction::Ifge(_) | Instruction::Ifgt(_) |
+        Instruction::Ifle(_) => Some(1),
+        Instruction::IfICmpEQ(_) | Instruction::IfICmpNE(_) | Instruction::IfICmpLT(_) | Instruction::IfICmpGE(_) |
+        Instruction::IfICmpGT(_) | Instruction::IfICmpLE(_) => Some(2),
+        _ => None
+    }
+}
+
+/// the index of the first of the instructions without effects right before `end` which push
+/// exactly `needed` values, not looking before `start`.
+fn pure_start(instructions: &[DecodedInsn], start: usize, end: usize, needed: usize) -> Option<usize> {
+    let mut needed = needed;
+    for index in (start..end).rev() {
+        let (pops, pushes) = pure_effect(&instructions[index].instruction)?;
+        if pushes > needed {
+            return None;
+        }
+        needed = needed - pushes + pops;
+        if needed == 0 {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// true if a superinstruction starting before `index` replaces it.
+fn covered(fused: &[Option<Superinstruction>], index: usize) -> bool {
+    (0..index).any(|start| fused[start].is_some_and(|fused| start + fused.len() > index))
+}
+
+/// lets the loads of `a[i]` the counted loops of a method keep in bounds skip the check.
+fn eliminate_bounds_checks(class: &ClassFile, method: &Method, fused: &mut [Option<Superinstruction>]) {
+    let loops = match CountedLoop::find(method) {
+        Ok(loops) => loops,
+        Err(err) => {
+            debug!(class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor, error = %err, "cannot find counted loops");
+            return;
+        }
+    };
+    for counted in loops.iter() {
+        for &load in counted.safe_loads.iter() {
+            let first = load - 2;
+            if fused[first].is_none() && !covered(fused, first) {
+                fused[first] = Some(Superinstruction::UncheckedLoad(counted.array, counted.index));
+            }
+        }
+    }
+}
+
+impl DecodedMethod {
+    /// the superinstruction to run at each instruction, with the ones replacing computations the
+    /// SSA form of the method proves constant and the array loads of counted loops which skip the
+    /// bounds check. computed on the first call.
+    pub fn optimized(&self, class: &ClassFile, method: &Method) -> &[Option<Superinstruction>] {
+        self.optimized.get_or_init(|| {
+            let mut fused = self.fold_constants(class, method);
+            eliminate_bounds_checks(class, method, &mut fused);
+            fused
+        })
+    }
+
+    /// replaces the instructions of a block computing a constant by a single push of it, and a
+    /// branch on constants with the instructions pushing its operands by a jump. a sequence starts
+    /// where its block does or later, so nothing jumps into its middle.
+    fn fold_constants(&self, class: &ClassFile, method: &Method) -> Vec<Option<Superinstruction>> {
+        let mut fused = self.instructions.iter().map(|insn| insn.fused).collect::<Vec<_>>();
+        let mut ssa = match SsaFunction::lower(class, method) {
+            Ok(ssa) => ssa,
+            Err(err) => {
+                debug!(class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor, error = %err, "cannot lower to SSA form");
+                return fused;
+            }
+        };
+        ssa.optimize();
+
+        for block in ssa.blocks.iter().filter(|block| block.reachable) {
+            let start = block.instructions.start;
+            let mut index = block.instructions.end;
+            while index > start {
+                index -= 1;
+                let insn = &self.instructions[index];
+
+                if let (Some(operands), &Terminator::Goto(target)) = (branch_operands(&insn.instruction), &block.terminator) {
+                    if let Some(first) = pure_start(&self.instructions, start, index, operands) {
+                        let taken = insn.target == Some(ssa.blocks[target].instructions.start);
+                        fused[first] = Some(Superinstruction::Branch(taken, index + 1 - first));
+                        index = first;
+                    }
+                    continue;
+                }
+
+                let constant = ssa.results[index].and_then(|value| ssa.constant(value));
+                if let Some(constant) = constant {
+                    match pure_start(&self.instructions, start, index + 1, 1) {
+                        Some(first) if first < index => {
+                            fused[first] = Some(Superinstruction::Constant(constant, index + 1 - first));
+                            index = first;
+                        }
+                        _ => ()
+                    }
+                }
+            }
+        }
+        fused
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, Runtime, StackValue};
+    use super::*;
+
+    fn runtime<'a>(optimize_bytecode: bool) -> Runtime<'a> {
+        Runtime::builder().hot_method_threshold(2).optimize_bytecode(optimize_bytecode)
+            .build(read_class_file(include_bytes!("../../../sample/Folding.class")).unwrap().1)
+    }
+
+    fn int(rt: &mut Runtime, name: &str, n: i64) -> i64 {
+        match rt.invoke_static("Folding", name, "(I)I", vec![LocalVariable::Integer(n)]) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    /// the superinstructions constant folding added to a method, by pc.
+    fn folded(rt: &Runtime, name: &str) -> Vec<(usize, Superinstruction)> {
+        let decoded = rt.classes.decoded("Folding", name, "(I)I").unwrap();
+        let optimized = decoded.optimized.get().expect("the method was not optimized");
+        decoded.instructions.iter().zip(optimized.iter())
+            .filter_map(|(insn, fused)| match fused {
+                Some(fused @ Superinstruction::Constant(..)) | Some(fused @ Superinstruction::Branch(..)) => Some((insn.pc, *fused)),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hot_methods_skip_constant_computations() {
+        let mut rt = runtime(true);
+        let mut plain = runtime(false);
+        for n in 0..4 {
+            assert_eq!(int(&mut rt, "scaled", n), int(&mut plain, "scaled", n));
+            assert_eq!(int(&mut rt, "branches", n), int(&mut plain, "branches", n));
+            assert_eq!(int(&mut rt, "unused", n), int(&mut plain, "unused", n));
+        }
+
+        // step * 2 + 1, and mode > 1 which never jumps to the else branch
+        assert_eq!(folded(&rt, "scaled"), vec![(11, Superinstruction::Constant(7, 5))]);
+        assert_eq!(folded(&rt, "branches"), vec![(11, Superinstruction::Branch(false, 3))]);
+        assert_eq!(folded(&rt, "unused"), vec![]);
+        // the instructions folded away still count
+        assert_eq!(rt.instructions_retired(), plain.instructions_retired());
+        assert!(plain.classes.decoded("Folding", "scaled", "(I)I").unwrap().optimized.get().is_none());
+    }
+
+    #[test]
+    fn counted_loops_skip_bounds_checks() {
+        let build = |optimize_bytecode| {
+            Runtime::builder().hot_method_threshold(2).optimize_bytecode(optimize_bytecode)
+                .build(read_class_file(include_bytes!("../../../sample/CountedLoops.class")).unwrap().1)
+        };
+        let call = |rt: &mut Runtime, name: &str, n: i64| rt.invoke_static("CountedLoops", name, "(I)I", vec![LocalVariable::Integer(n)])
+            .map_err(|err| format!("{:?}", err.root()));
+        let (mut rt, mut plain) = (build(true), build(false));
+        for n in 0..4 {
+            for name in ["sum", "nested", "incrementedFirst", "shifted"].iter() {
+                assert_eq!(call(&mut rt, name, n), call(&mut plain, name, n), "{}({})", name, n);
+            }
+        }
+        // the last element is even for odd lengths, the increment in the loop moves past it
+        assert!(call(&mut rt, "incrementedFirst", 3).unwrap_err().contains("ArrayIndexOutOfBoundsException"));
+        assert!(call(&mut rt, "shifted", -1).unwrap_err().contains("ArrayIndexOutOfBoundsException"));
+
+        let unchecked = |name: &str| {
+            let decoded = rt.classes.decoded("CountedLoops", name, "(I)I").unwrap();
+            decoded.instructions.iter().zip(decoded.optimized.get().expect("the method was not optimized").iter())
+                .filter_map(|(insn, fused)| match fused {
+                    Some(fused @ Superinstruction::UncheckedLoad(..)) => Some((insn.pc, *fused)),
+                    _ => None
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(unchecked("sum"), vec![(16, Superinstruction::UncheckedLoad(1, 3))]);
+        assert_eq!(unchecked("incrementedFirst"), vec![(15, Superinstruction::UncheckedLoad(1, 3))]);
+        assert_eq!(unchecked("nested").len(), 2);
+        assert_eq!(unchecked("shifted"), vec![]);
+    }
+}
@@ -0,0 +1,191 @@
+use java::class_file::ClassFile;
+use java::runtime::{ByteStore, DecodedMethod, Runtime};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::slice;
+#[cfg(feature = "threads")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "threads")]
+use std::sync::Mutex;
+use std::sync::Arc;
+use std::thread;
+
+/// a class read and decoded ahead of time, with the decoded methods indexed like its methods
+/// and the bytes it was read from.
+type LinkedClass<'a> = (ClassFile<'a>, Vec<Option<Arc<DecodedMethod>>>, &'a [u8]);
+
+/// all `.class` files in the directories of the classpath and their subdirectories, in a stable order.
+pub fn scan_classpath(classpath: &[PathBuf]) -> Vec<PathBuf> {
+    fn scan(path: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<Vec<_>>>()?;
+            entries.sort();
+            for entry in entries {
+                scan(&entry, found)?;
+            }
+        } else if path.extension().is_some_and(|extension| extension == "class") {
+            found.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    let mut found = Vec::new();
+    for entry in classpath {
+        if let Err(err) = scan(entry, &mut found) {
+            warn!(path = %entry.display(), error = %err, "cannot scan classpath entry");
+        }
+    }
+    found
+}
+
+/// reads, parses and decodes a class file, keeping its bytes in `store`.
+fn link_file<'a>(store: &'a ByteStore, path: &Path) -> Result<LinkedClass<'a>, String> {
+    let bytes = store.keep(fs::read(path).map_err(|err| err.to_string())?);
+    let class = ClassFile::parse(bytes).map_err(|err| err.to_string())?;
+    let decoded = class.methods.iter()
+        .map(|method| match DecodedMethod::decode(&class, method) {
+            Ok(decoded) => Some(Arc::new(decoded)),
+            Err(err) => {
+                warn!(class = class.get_class_name(), method = &*method.name, error = %err, "cannot decode method");
+                None
+            }
+        })
+        .collect();
+
+    Ok((class, decoded, bytes))
+}
+
+/// links the class files on `threads` worker threads. files which cannot be read or parsed are skipped.
+#[cfg(feature = "threads")]
+fn link_all<'a>(store: &'a ByteStore, paths: &[PathBuf], threads: usize) -> Vec<LinkedClass<'a>> {
+    let next = AtomicUsize::new(0);
+    let linked = Mutex::new(Vec::with_capacity(paths.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1).min(paths.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let path = match paths.get(index) {
+                    Some(path) => path,
+                    None => break
+                };
+                match link_file(store, path) {
+                    Ok(class) => linked.lock().unwrap().push((index, class)),
+                    Err(err) => warn!(path = %path.display(), error = %err, "cannot load class file")
+                }
+            });
+        }
+    });
+
+    // keep the classpath order, so the first of several classes with the same name wins like on the jvm
+    let mut linked = linked.into_inner().unwrap();
+    linked.sort_by_key(|&(index, _)| index);
+    linked.into_iter().map(|(_, class)| class).collect()
+}
+
+/// links the class files one after the other, without threads.
+#[cfg(not(feature = "threads"))]
+fn link_all<'a>(store: &'a ByteStore, paths: &[PathBuf], _threads: usize) -> Vec<LinkedClass<'a>> {
+    paths.iter()
+        .filter_map(|path| match link_file(store, path) {
+            Ok(class) => Some(class),
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "cannot load class file");
+                None
+            }
+        })
+        .collect()
+}
+
+impl<'a> Runtime<'a> {
+    /// loads every class on the classpath up front, parsing and decoding them in parallel.
+    /// returns the number of classes loaded.
+    pub(super) fn preload_classpath(&mut self, threads: usize) -> usize {
+        let paths = scan_classpath(&self.classpath);
+        let count = self.load_files(&paths, threads);
+        info!(classes = count, files = paths.len(), threads, "preloaded classpath");
+        count
+    }
+
+    /// appends a directory to the classpath and loads the classes in it. returns the number of
+    /// classes loaded.
+    pub fn add_classpath(&mut self, path: PathBuf) -> usize {
+        let paths = scan_classpath(slice::from_ref(&path));
+        self.classpath.push(path);
+        let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        self.load_files(&paths, threads)
+    }
+
+    /// loads the class files `paths`, parsing and decoding them on `threads` threads. returns the
+    /// number of classes loaded. without a byte store to keep their bytes nothing is loaded.
+    pub(super) fn load_files(&mut self, paths: &[PathBuf], threads: usize) -> usize {
+        let store = match self.byte_store {
+            Some(store) => store,
+            None => {
+                warn!(files = paths.len(), "cannot load class files, the runtime was built without a byte store");
+                return 0;
+            }
+        };
+        let linked = link_all(store, paths, threads);
+        let count = linked.len();
+        for (class, decoded, bytes) in linked.into_iter().rev() {
+            let (class, transformed) = match self.transform_class(class, bytes) {
+                Ok(class) => class,
+                Err(err) => {
+                    warn!(error = %err, "cannot transform class");
+                    continue;
+                }
+            };
+            for hook in self.hooks.iter_mut() {
+                hook.on_class_load(&class);
+            }
+            let class_name = String::from(class.get_class_name());
+            // classes changed by a listener are decoded again when their methods first run
+            if transformed {
+                self.classes.insert(class);
+            } else {
+                self.classes.insert_decoded(class, decoded);
+            }
+            self.class_linked(&class_name);
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, StackValue};
+    use super::*;
+
+    #[test]
+    fn it_preloads_the_classpath() {
+        let dir = std::env::temp_dir().join(format!("rjvm-preload-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("Loop.class"), &include_bytes!("../../../sample/Loop.class")[..]).unwrap();
+        fs::write(dir.join("nested").join("FieldExample.class"), &include_bytes!("../../../sample/FieldExample.class")[..]).unwrap();
+        fs::write(dir.join("Broken.class"), b"not a class").unwrap();
+        fs::write(dir.join("notes.txt"), b"ignored").unwrap();
+
+        let found = scan_classpath(slice::from_ref(&dir));
+        assert_eq!(found.len(), 3);
+
+        let store = ByteStore::new();
+        let mut rt = Runtime::builder()
+            .byte_store(&store)
+            .classpath(vec![dir.clone()])
+            .eager_loading(Some(2))
+            .build(read_class_file(include_bytes!("../../../sample/Tiny.class")).unwrap().1);
+        assert!(rt.classes.get("FieldExample").is_some());
+        let loop_class = rt.classes.id("Loop").unwrap();
+        let max = rt.classes.method(loop_class, "max", "(II)I").unwrap();
+        assert!(rt.classes.decoded_at(loop_class, max).is_some());
+        match rt.invoke_static("Loop", "max", "(II)I", vec![LocalVariable::Integer(3), LocalVariable::Integer(8)]) {
+            Ok(Some(StackValue::Integer(8))) => (),
+            other => panic!("unexpected result {:?}", other)
+        }
+        assert_eq!(store.len(), found.len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
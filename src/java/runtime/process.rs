@@ -0,0 +1,565 @@
+use java::runtime::{LocalVariable, NativeContext, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::io::{self, PipeReader, Write};
+use std::process::{Child, Command, Stdio};
+
+pub(super) const PROCESS_BUILDER: &str = "java/lang/ProcessBuilder";
+pub(super) const REDIRECT: &str = "java/lang/ProcessBuilder$Redirect";
+pub(super) const PROCESS: &str = "java/lang/Process";
+pub(super) const PROCESS_ENVIRONMENT: &str = "java/lang/ProcessEnvironment";
+pub(super) const JAVA_RUNTIME: &str = "java/lang/Runtime";
+pub(super) const INPUT_STREAM: &str = "java/io/InputStream";
+pub(super) const OUTPUT_STREAM: &str = "java/io/OutputStream";
+
+/// the most bytes a single `read` of a process stream asks the host for.
+const MAX_READ: usize = 8192;
+
+/// a process the program started. `Process` objects and their streams keep its index in
+/// `Runtime::processes` in the field `handle`.
+pub(super) struct ChildProcess {
+    child: Child,
+    /// the pipe both stdout and stderr write to if the error stream is redirected into the output.
+    merged: Option<PipeReader>,
+    exit_code: Option<i32>,
+}
+
+/// which stream of a process an `InputStream` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pipe {
+    Stdout,
+    Stderr,
+}
+
+/// what a process spawns from: the command line, the changes to the environment it inherits and
+/// where its streams go.
+struct Spawn {
+    command: Vec<String>,
+    clear_environment: bool,
+    environment: Vec<(String, Option<String>)>,
+    redirects: [&'static str; 3],
+    redirect_error_stream: bool,
+}
+
+fn stdio(redirect: &str) -> Stdio {
+    match redirect {
+        "INHERIT" => Stdio::inherit(),
+        "DISCARD" => Stdio::null(),
+        _ => Stdio::piped()
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// implements `ProcessBuilder`, `Runtime.exec`, `Process`, the streams of processes and the
+    /// map of `ProcessBuilder.environment`. processes are spawned through `NativeContext::spawn`, so
+    /// they fail with `RuntimeError::PermissionDenied` unless the capabilities allow spawning, and
+    /// what the program reads from them is recorded and replayed like other reads.
+    ///
+    /// the environment map starts out empty and only holds the changes to the environment the
+    /// process inherits. redirects to files and working directories are not supported.
+    /// returns `None` for methods the classes do not have.
+    pub(super) fn invoke_process(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let argument = |index: usize| match arguments.get(index) {
+            Some(LocalVariable::Reference(reference)) => Some(*reference),
+            _ => None
+        };
+        if class_name == JAVA_RUNTIME {
+            return self.invoke_java_runtime(method_name, descriptor, argument(1), argument(2));
+        }
+        let this = argument(0)?;
+        match class_name {
+            PROCESS_BUILDER => self.invoke_process_builder(this, method_name, descriptor, arguments),
+            PROCESS_ENVIRONMENT => self.invoke_process_environment(this, method_name, descriptor, argument(1), argument(2)),
+            PROCESS => self.invoke_process_handle(this, method_name, descriptor),
+            INPUT_STREAM => self.invoke_process_input(this, method_name, descriptor, arguments),
+            OUTPUT_STREAM => self.invoke_process_output(this, method_name, descriptor, arguments),
+            _ => None
+        }
+    }
+
+    /// the constants `INHERIT`, `PIPE` and `DISCARD` of `ProcessBuilder.Redirect`.
+    pub(super) fn redirect(&mut self, field_name: &str) -> Option<StackValue> {
+        if !["INHERIT", "PIPE", "DISCARD"].contains(&field_name) {
+            return None;
+        }
+        let key = (String::from(REDIRECT), String::from(field_name));
+        if let Some(redirect) = self.statics.get(&key) {
+            return Some(redirect.clone());
+        }
+        let redirect = self.allocate(REDIRECT, ObjectData::Instance);
+        let name = self.intern(field_name);
+        if let Some(object) = self.heap.get_mut(redirect) {
+            object.fields.insert(String::from("type"), StackValue::Reference(name));
+        }
+        self.statics.insert(key, StackValue::Reference(redirect));
+        Some(StackValue::Reference(redirect))
+    }
+
+    pub(super) fn field_value(&self, object: ObjectRef, name: &str) -> StackValue {
+        self.heap.get(object).and_then(|object| object.fields.get(name).cloned()).unwrap_or(StackValue::Null)
+    }
+
+    pub(super) fn set_field_value(&mut self, object: ObjectRef, name: &str, value: StackValue) {
+        if let Some(object) = self.heap.get_mut(object) {
+            object.fields.insert(String::from(name), value);
+        }
+    }
+
+    /// the strings of a `String[]`, `None` for null arrays and null elements.
+    fn string_array(&self, array: ObjectRef) -> Option<Vec<String>> {
+        match self.heap.get(array).map(|array| &array.data) {
+            Some(ObjectData::Array(elements)) => elements.iter().map(|element| match element {
+                StackValue::Reference(string) => self.heap.string_value(*string).map(String::from),
+                _ => None
+            }).collect(),
+            _ => None
+        }
+    }
+
+    fn invoke_java_runtime(&mut self, method_name: &str, descriptor: &str, first: Option<ObjectRef>, second: Option<ObjectRef>) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let (command, environment) = match (method_name, descriptor) {
+            ("getRuntime", "()Ljava/lang/Runtime;") => {
+                let key = (String::from(JAVA_RUNTIME), String::from("currentRuntime"));
+                if let Some(runtime) = self.statics.get(&key) {
+                    return Some(Ok(Some(runtime.clone())));
+                }
+                let runtime = StackValue::Reference(self.allocate(JAVA_RUNTIME, ObjectData::Instance));
+                self.statics.insert(key, runtime.clone());
+                return Some(Ok(Some(runtime)));
+            }
+            // like the class library, a command line is split at white space without any quoting
+            ("exec", "(Ljava/lang/String;)Ljava/lang/Process;") => (first.and_then(|command| self.heap.string_value(command))
+                .map(|command| command.split_whitespace().map(String::from).collect()), None),
+            ("exec", "([Ljava/lang/String;)Ljava/lang/Process;") => (first.and_then(|command| self.string_array(command)), None),
+            ("exec", "([Ljava/lang/String;[Ljava/lang/String;)Ljava/lang/Process;") => (first.and_then(|command| self.string_array(command)), second),
+            _ => return None
+        };
+        let command = match command {
+            Some(command) => command,
+            None => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+        };
+        // `envp` replaces the environment, its entries are `name=value`
+        let environment = environment.and_then(|environment| self.string_array(environment)).map(|environment| environment.iter()
+            .filter_map(|entry| {
+                let mut parts = entry.splitn(2, '=');
+                Some((String::from(parts.next()?), Some(String::from(parts.next()?))))
+            })
+            .collect());
+        Some(self.spawn_process(Spawn {
+            command,
+            clear_environment: environment.is_some(),
+            environment: environment.unwrap_or_default(),
+            redirects: ["PIPE"; 3],
+            redirect_error_stream: false,
+        }).map(|process| Some(StackValue::Reference(process))))
+    }
+
+    fn invoke_process_builder(&mut self, this: ObjectRef, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let argument = arguments.get(1).cloned().map(StackValue::from).unwrap_or(StackValue::None);
+        match (method_name, descriptor) {
+            ("<init>", "([Ljava/lang/String;)V") => {
+                self.set_field_value(this, "command", argument);
+                return Some(Ok(None));
+            }
+            ("command", "([Ljava/lang/String;)Ljava/lang/ProcessBuilder;") => self.set_field_value(this, "command", argument),
+            ("redirectErrorStream", "(Z)Ljava/lang/ProcessBuilder;") => self.set_field_value(this, "redirectErrorStream", argument),
+            ("redirectErrorStream", "()Z") => return Some(Ok(Some(match self.field_value(this, "redirectErrorStream") {
+                StackValue::Integer(value) => StackValue::Integer(value),
+                _ => StackValue::Integer(0)
+            }))),
+            ("redirectInput", "(Ljava/lang/ProcessBuilder$Redirect;)Ljava/lang/ProcessBuilder;") => self.set_field_value(this, "redirectInput", argument),
+            ("redirectOutput", "(Ljava/lang/ProcessBuilder$Redirect;)Ljava/lang/ProcessBuilder;") => self.set_field_value(this, "redirectOutput", argument),
+            ("redirectError", "(Ljava/lang/ProcessBuilder$Redirect;)Ljava/lang/ProcessBuilder;") => self.set_field_value(this, "redirectError", argument),
+            ("inheritIO", "()Ljava/lang/ProcessBuilder;") => {
+                let inherit = self.redirect("INHERIT").unwrap_or(StackValue::Null);
+                for name in ["redirectInput", "redirectOutput", "redirectError"].iter() {
+                    self.set_field_value(this, name, inherit.clone());
+                }
+            }
+            ("environment", "()Ljava/util/Map;") => {
+                if let StackValue::Reference(environment) = self.field_value(this, "environment") {
+                    return Some(Ok(Some(StackValue::Reference(environment))));
+                }
+                let environment = StackValue::Reference(self.allocate(PROCESS_ENVIRONMENT, ObjectData::Instance));
+                self.set_field_value(this, "environment", environment.clone());
+                return Some(Ok(Some(environment)));
+            }
+            ("start", "()Ljava/lang/Process;") => {
+                let command = match self.field_value(this, "command") {
+                    StackValue::Reference(command) => self.string_array(command),
+                    _ => None
+                };
+                let command = match command {
+                    Some(command) => command,
+                    None => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let (clear_environment, environment) = match self.field_value(this, "environment") {
+                    StackValue::Reference(environment) => self.environment_changes(environment),
+                    _ => (false, Vec::new())
+                };
+                let mut redirects = ["PIPE"; 3];
+                for (redirect, name) in redirects.iter_mut().zip(["redirectInput", "redirectOutput", "redirectError"].iter()) {
+                    if let StackValue::Reference(object) = self.field_value(this, name) {
+                        *redirect = match self.string_field(object, "type").as_deref() {
+                            Some("INHERIT") => "INHERIT",
+                            Some("DISCARD") => "DISCARD",
+                            _ => "PIPE"
+                        };
+                    }
+                }
+                let redirect_error_stream = self.field_value(this, "redirectErrorStream") == StackValue::Integer(1);
+                let spawn = Spawn { command, clear_environment, environment, redirects, redirect_error_stream };
+                return Some(self.spawn_process(spawn).map(|process| Some(StackValue::Reference(process))));
+            }
+            _ => return None
+        }
+        // the setters return the builder for chaining
+        Some(Ok(Some(StackValue::Reference(this))))
+    }
+
+    /// the variables the map of `ProcessBuilder.environment` sets, `None` for removed ones, and
+    /// whether it was cleared. variables are kept in the fields `=name`, which no java field is called.
+    fn environment_changes(&self, environment: ObjectRef) -> (bool, Vec<(String, Option<String>)>) {
+        let object = match self.heap.get(environment) {
+            Some(object) => object,
+            None => return (false, Vec::new())
+        };
+        let mut changes = object.fields.iter()
+            .filter(|&(name, _)| name.starts_with('='))
+            .map(|(name, value)| (String::from(&name[1..]), match value {
+                StackValue::Reference(value) => self.heap.string_value(*value).map(String::from),
+                _ => None
+            }))
+            .collect::<Vec<_>>();
+        changes.sort();
+        (object.fields.contains_key("cleared"), changes)
+    }
+
+    fn invoke_process_environment(&mut self, this: ObjectRef, method_name: &str, descriptor: &str, key: Option<ObjectRef>, value: Option<ObjectRef>) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        if (method_name, descriptor) == ("clear", "()V") {
+            if let Some(object) = self.heap.get_mut(this) {
+                object.fields.retain(|name, _| !name.starts_with('='));
+                object.fields.insert(String::from("cleared"), StackValue::Integer(1));
+            }
+            return Some(Ok(None));
+        }
+        let name = match key.and_then(|key| self.heap.string_value(key)) {
+            Some(name) => format!("={}", name),
+            None => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+        };
+        let previous = match self.field_value(this, &name) {
+            StackValue::Reference(previous) => StackValue::Reference(previous),
+            _ => StackValue::Null
+        };
+        match (method_name, descriptor) {
+            ("get", "(Ljava/lang/Object;)Ljava/lang/Object;") => (),
+            ("put", "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;") => match value {
+                Some(value) if self.heap.string_value(value).is_some() => self.set_field_value(this, &name, StackValue::Reference(value)),
+                _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+            },
+            ("remove", "(Ljava/lang/Object;)Ljava/lang/Object;") => self.set_field_value(this, &name, StackValue::Null),
+            _ => return None
+        }
+        Some(Ok(Some(previous)))
+    }
+
+    fn spawn_process(&mut self, spawn: Spawn) -> Result<ObjectRef, RuntimeError> {
+        if spawn.command.is_empty() {
+            return Err(self.throw_in_caller("java/lang/IndexOutOfBoundsException", Some("Index 0 out of bounds for length 0")));
+        }
+        let mut command = Command::new(&spawn.command[0]);
+        command.args(&spawn.command[1..])
+            .stdin(stdio(spawn.redirects[0]))
+            .stdout(stdio(spawn.redirects[1]))
+            .stderr(stdio(spawn.redirects[2]));
+        // both streams write to the same pipe, like the class library merges them
+        let merged = match spawn.redirect_error_stream {
+            true => match io::pipe().and_then(|(reader, writer)| Ok((reader, writer.try_clone()?, writer))) {
+                Ok((reader, stdout, stderr)) => {
+                    command.stdout(stdout).stderr(stderr);
+                    Some(reader)
+                }
+                Err(err) => return Err(self.throw_in_caller("java/io/IOException", Some(&err.to_string())))
+            },
+            false => None
+        };
+        if spawn.clear_environment {
+            command.env_clear();
+        }
+        for (name, value) in spawn.environment.iter() {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name)
+            };
+        }
+
+        let child = match NativeContext::new(&mut self.environment, &self.capabilities).spawn(&mut command) {
+            Ok(child) => child,
+            Err(RuntimeError::Host { message, .. }) => {
+                let message = format!("Cannot run program \"{}\": {}", spawn.command[0], message);
+                return Err(self.throw_in_caller("java/io/IOException", Some(&message)));
+            }
+            Err(err) => return Err(err)
+        };
+        // the ends of the pipe the command keeps would hold it open after the process exits
+        drop(command);
+        debug!(command = ?spawn.command, pid = child.id(), "started process");
+
+        let handle = StackValue::Integer(self.processes.len() as i64);
+        self.processes.push(ChildProcess { child, merged, exit_code: None });
+        let process = self.allocate(PROCESS, ObjectData::Instance);
+        self.set_field_value(process, "handle", handle);
+        Ok(process)
+    }
+
+    fn process_of(&self, object: ObjectRef) -> Option<usize> {
+        match self.field_value(object, "handle") {
+            StackValue::Integer(handle) if (handle as usize) < self.processes.len() => Some(handle as usize),
+            _ => None
+        }
+    }
+
+    /// the exit code of a process which has ended, without waiting for it.
+    fn poll_exit_code(&mut self, handle: usize) -> Result<Option<i32>, RuntimeError> {
+        let process = &mut self.processes[handle];
+        if process.exit_code.is_none() {
+            if let Some(status) = process.child.try_wait().map_err(|err| RuntimeError::Host { action: String::from("wait for process"), message: err.to_string() })? {
+                process.exit_code = Some(exit_code(status));
+            }
+        }
+        Ok(process.exit_code)
+    }
+
+    fn invoke_process_handle(&mut self, this: ObjectRef, method_name: &str, descriptor: &str) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let handle = self.process_of(this)?;
+        let value = match (method_name, descriptor) {
+            ("waitFor", "()I") => {
+                let process = &mut self.processes[handle];
+                if process.exit_code.is_none() {
+                    // the stdin of a process waited for is closed, so it cannot wait for input forever
+                    drop(process.child.stdin.take());
+                    match process.child.wait() {
+                        Ok(status) => process.exit_code = Some(exit_code(status)),
+                        Err(err) => return Some(Err(RuntimeError::Host { action: String::from("wait for process"), message: err.to_string() }))
+                    }
+                }
+                StackValue::Integer(i64::from(process.exit_code.unwrap_or(-1)))
+            }
+            ("exitValue", "()I") => match self.poll_exit_code(handle) {
+                Ok(Some(code)) => StackValue::Integer(i64::from(code)),
+                Ok(None) => return Some(Err(self.throw_in_caller("java/lang/IllegalThreadStateException", Some("process hasn't exited")))),
+                Err(err) => return Some(Err(err))
+            },
+            ("isAlive", "()Z") => match self.poll_exit_code(handle) {
+                Ok(code) => StackValue::Integer(if code.is_none() { 1 } else { 0 }),
+                Err(err) => return Some(Err(err))
+            },
+            ("pid", "()J") => StackValue::Long(i64::from(self.processes[handle].child.id())),
+            ("destroy", "()V") | ("destroyForcibly", "()Ljava/lang/Process;") => {
+                let forcibly = method_name == "destroyForcibly";
+                let process = &mut self.processes[handle];
+                if process.exit_code.is_none() {
+                    destroy(&mut process.child, forcibly);
+                }
+                if forcibly {
+                    return Some(Ok(Some(StackValue::Reference(this))));
+                }
+                return Some(Ok(None));
+            }
+            ("getInputStream", "()Ljava/io/InputStream;") | ("getErrorStream", "()Ljava/io/InputStream;") | ("getOutputStream", "()Ljava/io/OutputStream;") => {
+                let (class_name, pipe) = match method_name {
+                    "getInputStream" => (INPUT_STREAM, 1),
+                    "getErrorStream" => (INPUT_STREAM, 2),
+                    _ => (OUTPUT_STREAM, 0)
+                };
+                let stream = self.allocate(class_name, ObjectData::Instance);
+                self.set_field_value(stream, "handle", StackValue::Integer(handle as i64));
+                self.set_field_value(stream, "fd", StackValue::Integer(pipe));
+                StackValue::Reference(stream)
+            }
+            _ => return None
+        };
+        Some(Ok(Some(value)))
+    }
+
+    /// `read` of the stdout or stderr of a process: a byte, or the number of bytes read into an
+    /// array, -1 at the end of the stream. streams which do not go to the program are empty.
+    fn invoke_process_input(&mut self, this: ObjectRef, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let handle = self.process_of(this)?;
+        let pipe = if self.field_value(this, "fd") == StackValue::Integer(2) { Pipe::Stderr } else { Pipe::Stdout };
+        let (array, offset, length) = match (method_name, descriptor, arguments.get(1), arguments.get(2), arguments.get(3)) {
+            ("close", "()V", _, _, _) => {
+                let process = &mut self.processes[handle];
+                match pipe {
+                    Pipe::Stdout => {
+                        drop(process.child.stdout.take());
+                        drop(process.merged.take());
+                    }
+                    Pipe::Stderr => drop(process.child.stderr.take()),
+                }
+                return Some(Ok(None));
+            }
+            ("read", "()I", _, _, _) => (None, 0, 1),
+            ("read", "([B)I", Some(LocalVariable::Reference(array)), _, _) => (Some(*array), 0, self.array_length(*array)),
+            ("read", "([BII)I", Some(LocalVariable::Reference(array)), Some(LocalVariable::Integer(offset)), Some(LocalVariable::Integer(length))) => {
+                if *offset < 0 || *length < 0 || offset + length > self.array_length(*array) as i64 {
+                    return Some(Err(self.throw_in_caller("java/lang/IndexOutOfBoundsException", None)));
+                }
+                (Some(*array), *offset as usize, *length as usize)
+            }
+            ("read", _, _, _, _) => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None))),
+            _ => return None
+        };
+        if length == 0 {
+            return Some(Ok(Some(StackValue::Integer(0))));
+        }
+
+        let process = &mut self.processes[handle];
+        let mut context = NativeContext::new(&mut self.environment, &self.capabilities);
+        let bytes = match pipe {
+            Pipe::Stdout if process.merged.is_some() => process.merged.as_mut().map(|merged| context.read(merged, length.min(MAX_READ))),
+            Pipe::Stdout => process.child.stdout.as_mut().map(|stdout| context.read(stdout, length.min(MAX_READ))),
+            Pipe::Stderr => process.child.stderr.as_mut().map(|stderr| context.read(stderr, length.min(MAX_READ))),
+        };
+        let bytes = match bytes {
+            Some(Ok(bytes)) => bytes,
+            Some(Err(err)) => return Some(Err(err)),
+            None => Vec::new()
+        };
+        let value = match array {
+            None => bytes.first().map_or(-1, |&byte| i64::from(byte)),
+            Some(_) if bytes.is_empty() => -1,
+            Some(array) => {
+                if let Some(ObjectData::Array(elements)) = self.heap.get_mut(array).map(|array| &mut array.data) {
+                    for (element, byte) in elements[offset..].iter_mut().zip(bytes.iter()) {
+                        *element = StackValue::Integer(i64::from(*byte as i8));
+                    }
+                }
+                bytes.len() as i64
+            }
+        };
+        Some(Ok(Some(StackValue::Integer(value))))
+    }
+
+    /// `write`, `flush` and `close` of the stdin of a process.
+    fn invoke_process_output(&mut self, this: ObjectRef, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let handle = self.process_of(this)?;
+        let bytes = match (method_name, descriptor, arguments.get(1), arguments.get(2), arguments.get(3)) {
+            ("close", "()V", _, _, _) => {
+                drop(self.processes[handle].child.stdin.take());
+                return Some(Ok(None));
+            }
+            ("flush", "()V", _, _, _) => Vec::new(),
+            ("write", "(I)V", Some(LocalVariable::Integer(byte)), _, _) => vec![*byte as u8],
+            ("write", "([B)V", Some(LocalVariable::Reference(array)), _, _) => self.array_bytes(*array, 0, self.array_length(*array)),
+            ("write", "([BII)V", Some(LocalVariable::Reference(array)), Some(LocalVariable::Integer(offset)), Some(LocalVariable::Integer(length))) => {
+                if *offset < 0 || *length < 0 || offset + length > self.array_length(*array) as i64 {
+                    return Some(Err(self.throw_in_caller("java/lang/IndexOutOfBoundsException", None)));
+                }
+                self.array_bytes(*array, *offset as usize, *length as usize)
+            }
+            ("write", _, _, _, _) => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None))),
+            _ => return None
+        };
+        let written = match self.processes[handle].child.stdin.as_mut() {
+            Some(stdin) => stdin.write_all(&bytes).and_then(|_| stdin.flush()),
+            None => return Some(Err(self.throw_in_caller("java/io/IOException", Some("Stream closed"))))
+        };
+        match written {
+            Ok(()) => Some(Ok(None)),
+            Err(err) => Some(Err(self.throw_in_caller("java/io/IOException", Some(&err.to_string()))))
+        }
+    }
+
+    fn array_length(&self, array: ObjectRef) -> usize {
+        match self.heap.get(array).map(|array| &array.data) {
+            Some(ObjectData::Array(elements)) => elements.len(),
+            _ => 0
+        }
+    }
+
+    fn array_bytes(&self, array: ObjectRef, offset: usize, length: usize) -> Vec<u8> {
+        match self.heap.get(array).map(|array| &array.data) {
+            Some(ObjectData::Array(elements)) => elements.iter().skip(offset).take(length).map(|element| match element {
+                StackValue::Integer(byte) => *byte as u8,
+                _ => 0
+            }).collect(),
+            _ => Vec::new()
+        }
+    }
+}
+
+/// the exit code like java reports it, 128 plus the signal for processes killed by one.
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(-1)
+}
+
+/// `destroy` asks the process to terminate, `destroyForcibly` kills it.
+fn destroy(child: &mut Child, forcibly: bool) {
+    #[cfg(unix)]
+    {
+        if !forcibly {
+            unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM); }
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{Capabilities, RuntimeBuilder};
+    use super::*;
+
+    fn runtime(builder: RuntimeBuilder<'static>) -> Runtime<'static> {
+        builder.build(read_class_file(include_bytes!("../../../sample/Processes.class")).unwrap().1)
+    }
+
+    fn run(rt: &mut Runtime, method_name: &str, arguments: &[&str]) -> Result<Option<StackValue>, RuntimeError> {
+        let strings = arguments.iter().map(|argument| StackValue::Reference(rt.intern(argument))).collect();
+        let array = rt.allocate("[Ljava/lang/String;", ObjectData::Array(strings));
+        rt.invoke_static("Processes", method_name, "([Ljava/lang/String;)Ljava/lang/String;", vec![LocalVariable::Reference(array)])
+    }
+
+    fn output(rt: &mut Runtime, method_name: &str, arguments: &[&str]) -> String {
+        match run(rt, method_name, arguments).unwrap() {
+            Some(StackValue::Reference(string)) => String::from(rt.heap.string_value(string).unwrap()),
+            other => panic!("expected a string, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn processes_run_with_their_arguments_and_environment() {
+        let mut rt = runtime(Runtime::builder());
+        assert_eq!(output(&mut rt, "run", &["sh", "-c", "echo \"$1 $GREETING\" >&2; exit 3", "sh", "hello"]), "hello world\n3");
+        assert_eq!(output(&mut rt, "exec", &["sh", "-c", "echo $GREETING; cat"]), "only\nfrom stdin\n0");
+    }
+
+    #[test]
+    fn processes_can_be_destroyed() {
+        let mut rt = runtime(Runtime::builder());
+        // terminated by SIGTERM
+        assert_eq!(output(&mut rt, "destroy", &["sleep", "10"]), "true 143");
+    }
+
+    #[test]
+    fn spawning_needs_the_capability() {
+        let mut rt = runtime(Runtime::builder().capabilities(Capabilities::all().allow_process_spawn(false)));
+        match run(&mut rt, "run", &["true"]).map_err(|err| format!("{:?}", err.root())) {
+            Err(ref err) if err.starts_with("PermissionDenied") => (),
+            other => panic!("expected the spawn to be denied, got {:?}", other)
+        }
+
+        let mut rt = runtime(Runtime::builder());
+        match run(&mut rt, "run", &["/nonexistent/program"]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/io/IOException" => (),
+            other => panic!("expected an IOException, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use java::runtime::{Heap, ObjectRef};
+use std::collections::HashMap;
+use std::fmt;
+
+/// the bytecode instruction which allocated an object.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct AllocationSite {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    pub pc: usize,
+}
+
+impl fmt::Display for AllocationSite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}{} pc {}", self.class_name, self.method_name, self.descriptor, self.pc)
+    }
+}
+
+/// counts and sizes of the objects allocated by a class or site.
+/// retained objects are the ones which survived the collection taken with the profile.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct AllocationStats {
+    pub allocated_count: usize,
+    pub allocated_bytes: usize,
+    pub retained_count: usize,
+    pub retained_bytes: usize,
+}
+
+/// allocations grouped by class and by allocation site, largest first.
+/// allocations done by the vm itself outside of any java frame have no site.
+#[derive(Debug, Clone, Default)]
+pub struct AllocationProfile {
+    pub classes: Vec<(String, AllocationStats)>,
+    pub sites: Vec<(Option<AllocationSite>, AllocationStats)>,
+}
+
+impl AllocationProfile {
+    pub fn class(&self, class_name: &str) -> Option<&AllocationStats> {
+        self.classes.iter().find(|(name, _)| name == class_name).map(|(_, stats)| stats)
+    }
+}
+
+impl fmt::Display for AllocationProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>10} {:>12} {:>10} {:>12}  class", "objects", "bytes", "retained", "ret. bytes")?;
+        for (class_name, stats) in self.classes.iter() {
+            writeln!(f, "{:>10} {:>12} {:>10} {:>12}  {}", stats.allocated_count, stats.allocated_bytes, stats.retained_count, stats.retained_bytes, class_name)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:>10} {:>12} {:>10} {:>12}  site", "objects", "bytes", "retained", "ret. bytes")?;
+        for (site, stats) in self.sites.iter() {
+            let site = site.as_ref().map(|site| site.to_string()).unwrap_or_else(|| String::from("<vm>"));
+            writeln!(f, "{:>10} {:>12} {:>10} {:>12}  {}", stats.allocated_count, stats.allocated_bytes, stats.retained_count, stats.retained_bytes, site)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Allocation {
+    reference: ObjectRef,
+    class_name: String,
+    site: Option<AllocationSite>,
+    bytes: usize,
+}
+
+/// records every allocation of a `Runtime` while profiling is enabled.
+#[derive(Default)]
+pub struct AllocationProfiler {
+    enabled: bool,
+    allocations: Vec<Allocation>,
+}
+
+impl AllocationProfiler {
+    pub fn new(enabled: bool) -> AllocationProfiler {
+        AllocationProfiler { enabled, allocations: Vec::new() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn record(&mut self, reference: ObjectRef, class_name: &str, site: Option<AllocationSite>, bytes: usize) {
+        if self.enabled {
+            self.allocations.push(Allocation { reference, class_name: String::from(class_name), site, bytes });
+        }
+    }
+
+    /// aggregates the recorded allocations, checking against `heap` which objects are still alive.
+    pub fn profile(&self, heap: &Heap) -> AllocationProfile {
+        let mut classes: HashMap<&str, AllocationStats> = HashMap::new();
+        let mut sites: HashMap<&Option<AllocationSite>, AllocationStats> = HashMap::new();
+
+        for allocation in self.allocations.iter() {
+            let retained = heap.get(allocation.reference).map(|object| object.size());
+            for stats in [classes.entry(&allocation.class_name).or_default(), sites.entry(&allocation.site).or_default()] {
+                stats.allocated_count += 1;
+                stats.allocated_bytes += allocation.bytes;
+                if let Some(bytes) = retained {
+                    stats.retained_count += 1;
+                    stats.retained_bytes += bytes;
+                }
+            }
+        }
+
+        let mut classes = classes.into_iter().map(|(name, stats)| (String::from(name), stats)).collect::<Vec<_>>();
+        classes.sort_by(|a, b| b.1.allocated_bytes.cmp(&a.1.allocated_bytes).then_with(|| a.0.cmp(&b.0)));
+        let mut sites = sites.into_iter().map(|(site, stats)| (site.clone(), stats)).collect::<Vec<_>>();
+        sites.sort_by(|a, b| b.1.allocated_bytes.cmp(&a.1.allocated_bytes).then_with(|| a.0.cmp(&b.0)));
+
+        AllocationProfile { classes, sites }
+    }
+}
@@ -0,0 +1,207 @@
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+
+pub(super) const RANDOM: &str = "java/util/Random";
+pub(super) const SECURE_RANDOM: &str = "java/security/SecureRandom";
+
+const MULTIPLIER: i64 = 0x5_DEEC_E66D;
+const ADDEND: i64 = 0xB;
+const MASK: i64 = (1 << 48) - 1;
+
+impl<'a> Runtime<'a> {
+    /// implements `java.util.Random` and `java.security.SecureRandom`. `Random` is the linear
+    /// congruential generator of the class library, so seeded instances produce the same numbers
+    /// as on any other vm. the seed lives in the field `seed`, unseeded instances get theirs from
+    /// the environment. `SecureRandom` draws every number from `Environment::secure_random_bytes`.
+    /// returns `None` for methods the classes do not have.
+    pub(super) fn invoke_random(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let this = match arguments.first() {
+            Some(LocalVariable::Reference(this)) => *this,
+            _ => return None
+        };
+        let secure = self.heap.get(this).is_some_and(|object| self.is_subclass_of(&object.class_name, SECURE_RANDOM));
+
+        let value = match (class_name, method_name, descriptor, arguments.get(1)) {
+            (RANDOM, "<init>", "()V", _) => {
+                let seed = self.environment.random_seed();
+                self.set_random_seed(this, seed);
+                return Some(Ok(None));
+            }
+            (RANDOM, "<init>", "(J)V", Some(LocalVariable::Long(seed))) | (RANDOM, "setSeed", "(J)V", Some(LocalVariable::Long(seed))) => {
+                self.set_random_seed(this, *seed);
+                return Some(Ok(None));
+            }
+            // the seed of a secure generator only adds to its entropy, which needs none
+            (SECURE_RANDOM, "<init>", "()V", _) | (SECURE_RANDOM, "<init>", "([B)V", _) | (SECURE_RANDOM, "setSeed", "(J)V", _) | (SECURE_RANDOM, "setSeed", "([B)V", _) => return Some(Ok(None)),
+            (SECURE_RANDOM, "generateSeed", "(I)[B", Some(LocalVariable::Integer(count))) => {
+                if *count < 0 {
+                    return Some(Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("numBytes cannot be negative"))));
+                }
+                let bytes = self.environment.secure_random_bytes(*count as usize).into_iter()
+                    .map(|byte| StackValue::Integer(i64::from(byte as i8)))
+                    .collect();
+                StackValue::Reference(self.allocate("[B", ObjectData::Array(bytes)))
+            }
+            (_, "nextBytes", "([B)V", argument) => {
+                let array = match argument {
+                    Some(LocalVariable::Reference(array)) => *array,
+                    _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+                };
+                let length = match self.heap.get(array).map(|array| &array.data) {
+                    Some(ObjectData::Array(elements)) => elements.len(),
+                    _ => return Some(Err(RuntimeError::StackType { expected: String::from("[B") }))
+                };
+                let bytes = if secure {
+                    self.environment.secure_random_bytes(length)
+                } else {
+                    // little endian ints, like the class library
+                    let mut bytes = Vec::with_capacity(length + 4);
+                    while bytes.len() < length {
+                        let value = self.next_random_bits(this, false, 32);
+                        bytes.extend_from_slice(&value.to_le_bytes());
+                    }
+                    bytes
+                };
+                if let Some(ObjectData::Array(elements)) = self.heap.get_mut(array).map(|array| &mut array.data) {
+                    for (element, byte) in elements.iter_mut().zip(bytes) {
+                        *element = StackValue::Integer(i64::from(byte as i8));
+                    }
+                }
+                return Some(Ok(None));
+            }
+            (_, "nextInt", "()I", _) => StackValue::Integer(i64::from(self.next_random_bits(this, secure, 32))),
+            (_, "nextInt", "(I)I", Some(LocalVariable::Integer(bound))) => {
+                let bound = *bound as i32;
+                if bound <= 0 {
+                    return Some(Err(self.throw_in_caller("java/lang/IllegalArgumentException", Some("bound must be positive"))));
+                }
+                StackValue::Integer(i64::from(self.next_random_int(this, secure, bound)))
+            }
+            (_, "nextLong", "()J", _) => {
+                let high = i64::from(self.next_random_bits(this, secure, 32));
+                let low = i64::from(self.next_random_bits(this, secure, 32));
+                StackValue::Long((high << 32).wrapping_add(low))
+            }
+            (_, "nextBoolean", "()Z", _) => StackValue::Integer(i64::from(self.next_random_bits(this, secure, 1))),
+            _ => return None
+        };
+
+        Some(Ok(Some(value)))
+    }
+
+    fn set_random_seed(&mut self, random: ObjectRef, seed: i64) {
+        if let Some(object) = self.heap.get_mut(random) {
+            object.fields.insert(String::from("seed"), StackValue::Long((seed ^ MULTIPLIER) & MASK));
+        }
+    }
+
+    /// `Random.next`, the upper `bits` bits of the next value of the generator.
+    fn next_random_bits(&mut self, random: ObjectRef, secure: bool, bits: u32) -> i32 {
+        if secure {
+            let count = (bits as usize).div_ceil(8);
+            let value = self.environment.secure_random_bytes(count).into_iter()
+                .fold(0i64, |value, byte| (value << 8) + i64::from(byte));
+            return (value >> (count as u32 * 8 - bits)) as i32;
+        }
+
+        let object = match self.heap.get_mut(random) {
+            Some(object) => object,
+            None => return 0
+        };
+        let seed = match object.fields.get("seed") {
+            Some(StackValue::Long(seed)) => *seed,
+            _ => 0
+        };
+        let next = seed.wrapping_mul(MULTIPLIER).wrapping_add(ADDEND) & MASK;
+        object.fields.insert(String::from("seed"), StackValue::Long(next));
+        (next >> (48 - bits)) as i32
+    }
+
+    /// `Random.nextInt(bound)`, which rejects the values that would make some results more likely.
+    fn next_random_int(&mut self, random: ObjectRef, secure: bool, bound: i32) -> i32 {
+        let mut r = self.next_random_bits(random, secure, 31);
+        let m = bound - 1;
+        if bound & m == 0 {
+            return ((i64::from(bound) * i64::from(r)) >> 31) as i32;
+        }
+        let mut u = r;
+        loop {
+            r = u % bound;
+            if u.wrapping_sub(r).wrapping_add(m) >= 0 {
+                return r;
+            }
+            u = self.next_random_bits(random, secure, 31);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::RuntimeBuilder;
+    use super::*;
+
+    fn runtime(builder: RuntimeBuilder<'static>) -> Runtime<'static> {
+        builder.build(read_class_file(include_bytes!("../../../sample/Dice.class")).unwrap().1)
+    }
+
+    fn call(rt: &mut Runtime, method_name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> StackValue {
+        rt.invoke_static("Dice", method_name, descriptor, arguments).unwrap().unwrap()
+    }
+
+    fn elements(rt: &Runtime, array: StackValue) -> Vec<i64> {
+        match array {
+            StackValue::Reference(array) => match rt.heap.get(array).map(|array| &array.data) {
+                Some(ObjectData::Array(elements)) => elements.iter().map(|element| match element {
+                    StackValue::Integer(value) => *value,
+                    other => panic!("expected an int, got {:?}", other)
+                }).collect(),
+                other => panic!("expected an array, got {:?}", other)
+            },
+            other => panic!("expected an array, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn seeded_generators_match_the_class_library() {
+        // as printed by openjdk
+        let mut rt = runtime(Runtime::builder());
+        let ints = call(&mut rt, "ints", "(J)[I", vec![LocalVariable::Long(42)]);
+        assert_eq!(elements(&rt, ints), vec![-1_170_105_035, 3, 248, 0]);
+        assert_eq!(call(&mut rt, "nextLong", "(J)J", vec![LocalVariable::Long(42)]), StackValue::Long(-5_025_562_857_975_149_833));
+        let bytes = call(&mut rt, "bytes", "(JI)[B", vec![LocalVariable::Long(42), LocalVariable::Integer(6)]);
+        assert_eq!(elements(&rt, bytes), vec![53, -99, 65, -70, -9, -118]);
+    }
+
+    #[test]
+    fn unseeded_generators_take_the_seed_of_the_builder() {
+        let mut first = runtime(Runtime::builder().random_seed(7));
+        let mut second = runtime(Runtime::builder().random_seed(7));
+        for &(method_name, descriptor) in [("unseeded", "()J"), ("secure", "()J")].iter() {
+            assert_eq!(call(&mut first, method_name, descriptor, vec![]), call(&mut second, method_name, descriptor, vec![]));
+        }
+        let seed = call(&mut first, "secureSeed", "(I)[B", vec![LocalVariable::Integer(4)]);
+        let again = call(&mut second, "secureSeed", "(I)[B", vec![LocalVariable::Integer(4)]);
+        assert_eq!(elements(&first, seed), elements(&second, again));
+
+        let mut other = runtime(Runtime::builder().random_seed(8));
+        assert_ne!(call(&mut first, "unseeded", "()J", vec![]), call(&mut other, "unseeded", "()J", vec![]));
+
+        // the deterministic mode fixes the seeds, too
+        let mut deterministic = runtime(Runtime::builder().deterministic(7));
+        let mut again = runtime(Runtime::builder().deterministic(7));
+        assert_eq!(call(&mut deterministic, "secure", "()J", vec![]), call(&mut again, "secure", "()J", vec![]));
+    }
+
+    #[test]
+    fn bounds_must_be_positive() {
+        let mut rt = runtime(Runtime::builder());
+        match rt.invoke_static("Dice", "bounded", "(I)I", vec![LocalVariable::Integer(0)]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/IllegalArgumentException" => (),
+            other => panic!("expected an IllegalArgumentException, got {:?}", other)
+        }
+        match call(&mut rt, "bounded", "(I)I", vec![LocalVariable::Integer(10)]) {
+            StackValue::Integer(value) => assert!((0..10).contains(&value)),
+            other => panic!("expected an int, got {:?}", other)
+        }
+    }
+}
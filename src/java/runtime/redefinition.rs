@@ -0,0 +1,127 @@
+use java::class_file::ClassFile;
+use java::runtime::{Runtime, RuntimeError};
+
+/// the differences between two versions of a class which a redefinition may not make, like the
+/// `RedefineClasses` of jvmti: everything but the bodies of methods and the constant pool stays as it was.
+fn shape_change(old: &ClassFile, new: &ClassFile) -> Option<String> {
+    if old.access_flags != new.access_flags {
+        return Some(String::from("the class modifiers changed"));
+    }
+    if old.get_super_class_name() != new.get_super_class_name() {
+        return Some(String::from("the super class changed"));
+    }
+    let interfaces = |class: &ClassFile| class.interfaces.iter().map(|&index| class.get_class_name_at(index).map(String::from)).collect::<Vec<_>>();
+    if interfaces(old) != interfaces(new) {
+        return Some(String::from("the implemented interfaces changed"));
+    }
+    if old.field_signatures() != new.field_signatures() {
+        return Some(String::from("fields were added, removed or changed"));
+    }
+
+    // methods may be reordered, but not added, removed or change their modifiers
+    let methods = |class: &ClassFile| {
+        let mut methods = class.methods.iter().map(|method| (method.name.to_string(), method.descriptor.to_string(), method.access_flags)).collect::<Vec<_>>();
+        methods.sort();
+        methods
+    };
+    if methods(old) != methods(new) {
+        return Some(String::from("methods were added, removed or changed their modifiers"));
+    }
+
+    None
+}
+
+impl<'a> Runtime<'a> {
+    /// replaces the methods of the loaded class `class_name` with the ones of the class file `bytes`,
+    /// for edit and continue. the new version may only change the bodies of methods, statics and
+    /// objects keep their values and the class is not initialized again.
+    ///
+    /// methods which are running keep running their old code until they return, every call made
+    /// after the redefinition runs the new code. their stack trace elements show the lines of the
+    /// new version.
+    pub fn redefine_class(&mut self, class_name: &str, bytes: &'a [u8]) -> Result<(), RuntimeError> {
+        let invalid = |message: &str| RuntimeError::InvalidRedefinition { class_name: String::from(class_name), message: String::from(message) };
+        if self.classes.get(class_name).is_none() {
+            return Err(RuntimeError::ClassNotFound { class_name: String::from(class_name) });
+        }
+        let new = ClassFile::parse(bytes).map_err(|err| invalid(&err.to_string()))?;
+        self.replace_class(class_name, new)?;
+        // later retransformations start from the redefined version
+        self.class_bytes.insert(String::from(class_name), bytes);
+        Ok(())
+    }
+
+    /// replaces the loaded class `class_name` with `new`, if only the bodies of its methods differ.
+    pub(super) fn replace_class(&mut self, class_name: &str, new: ClassFile<'a>) -> Result<(), RuntimeError> {
+        let invalid = |message: &str| RuntimeError::InvalidRedefinition { class_name: String::from(class_name), message: String::from(message) };
+        let old = self.classes.get(class_name).ok_or_else(|| RuntimeError::ClassNotFound { class_name: String::from(class_name) })?;
+        if new.get_class_name() != class_name {
+            return Err(invalid(&format!("the class file defines {}", new.get_class_name())));
+        }
+        if let Some(change) = shape_change(&old, &new) {
+            return Err(invalid(&change));
+        }
+
+        debug!(class = class_name, methods = new.methods.len(), "redefined class");
+        for hook in self.hooks.iter_mut() {
+            hook.on_class_load(&new);
+        }
+        // the class keeps its id, the code and constant pool of the new version replace the old
+        // ones and calls resolved to the old methods are resolved again
+        self.classes.insert(new);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::StackValue;
+    use super::*;
+
+    const RELOAD: &[u8] = include_bytes!("../../../sample/Reload.class");
+
+    fn call(rt: &mut Runtime, name: &str) -> StackValue {
+        match rt.invoke_static("Reload", name, "()I", vec![]) {
+            Ok(Some(value)) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn redefined_methods_run_the_new_code() {
+        let mut rt = Runtime::builder().build(read_class_file(RELOAD).unwrap().1);
+        assert_eq!(call(&mut rt, "answer"), StackValue::Integer(1));
+        let old = rt.classes.decoded("Reload", "answer", "()I").unwrap();
+        let id = rt.classes.id("Reload");
+
+        rt.redefine_class("Reload", include_bytes!("../../../sample/redefined/Reload.class")).unwrap();
+        // the static field keeps its value, so this is the second call
+        assert_eq!(call(&mut rt, "answer"), StackValue::Integer(42));
+        assert_eq!(call(&mut rt, "calls"), StackValue::Integer(2));
+        assert_eq!(rt.classes.id("Reload"), id);
+
+        // a running activation holds on to the code it started with
+        let new = rt.classes.decoded("Reload", "answer", "()I").unwrap();
+        assert!(!std::sync::Arc::ptr_eq(&old, &new));
+        assert_ne!(old.instructions.len(), new.instructions.len());
+    }
+
+    #[test]
+    fn redefinitions_cannot_change_the_shape_of_a_class() {
+        let mut rt = Runtime::builder().build(read_class_file(RELOAD).unwrap().1);
+        match rt.redefine_class("Reload", include_bytes!("../../../sample/reshaped/Reload.class")) {
+            Err(RuntimeError::InvalidRedefinition { ref message, .. }) => assert!(message.starts_with("methods were added")),
+            other => panic!("redefine_class returned {:?}", other)
+        }
+        match rt.redefine_class("Reload", include_bytes!("../../../sample/Tiny.class")) {
+            Err(RuntimeError::InvalidRedefinition { ref message, .. }) => assert_eq!(message, "the class file defines Tiny"),
+            other => panic!("redefine_class returned {:?}", other)
+        }
+        match rt.redefine_class("Missing", RELOAD) {
+            Err(RuntimeError::ClassNotFound { .. }) => (),
+            other => panic!("redefine_class returned {:?}", other)
+        }
+        assert_eq!(call(&mut rt, "answer"), StackValue::Integer(1));
+    }
+}
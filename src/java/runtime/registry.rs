@@ -0,0 +1,447 @@
+use java::analysis::ClassHierarchy;
+use java::class_file::ClassFile;
+use java::runtime::{DecodedMethod, FastMethod, FieldLayout, RuntimeConstantPool, Signature, Signatures, Symbol, SymbolTable};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// the index of a loaded class in its `ClassRegistry`.
+///
+/// ids are handed out in load order and stay valid until the class is unloaded,
+/// reloading a class keeps its id. ids of unloaded classes are not reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClassId(u32);
+
+impl ClassId {
+    pub(super) fn from_index(index: usize) -> ClassId {
+        ClassId(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// everything the registry knows about one class.
+#[derive(Debug)]
+struct LoadedClass<'a> {
+    class: Arc<ClassFile<'a>>,
+    pool: Arc<RuntimeConstantPool>,
+    /// the index of each method, keyed by method name and descriptor.
+    methods: HashMap<(Symbol, Symbol), usize>,
+    /// the signature of each method, indexed like the methods. `None` for invalid descriptors.
+    signatures: Vec<Option<Arc<Signature>>>,
+    /// the decoded bytecode, indexed like the methods of the class. methods are decoded when they
+    /// are first run, `None` if they cannot be.
+    decoded: Vec<OnceLock<Option<Arc<DecodedMethod>>>>,
+    /// the verification results of a class from a `ClassArchive`, used instead of verifying again.
+    verified: Option<Vec<Option<FastMethod>>>,
+    /// the slots of the instance fields, computed when the first field is resolved or object created.
+    layout: OnceLock<Arc<FieldLayout>>,
+}
+
+/// the loaded classes at one point in time, indexed by name and id. a snapshot never changes, a
+/// change to the registry publishes a new one.
+#[derive(Debug, Default)]
+pub struct ClassSnapshot<'a> {
+    /// the version of the registry the snapshot was taken of.
+    version: u64,
+    ids: HashMap<String, ClassId>,
+    /// indexed by class id, `None` for unloaded classes.
+    classes: Vec<Option<Arc<ClassFile<'a>>>>,
+}
+
+impl<'a> ClassSnapshot<'a> {
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn id(&self, name: &str) -> Option<ClassId> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn class(&self, id: ClassId) -> Option<&Arc<ClassFile<'a>>> {
+        self.classes.get(id.index())?.as_ref()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<ClassFile<'a>>> {
+        self.class(self.id(name)?)
+    }
+
+    /// the number of loaded classes.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// the latest snapshot of a registry and its version, which readers check before taking it.
+#[derive(Debug, Default)]
+struct Published<'a> {
+    version: AtomicU64,
+    snapshot: RwLock<Arc<ClassSnapshot<'a>>>,
+}
+
+/// reads the classes of a registry from other threads, like a debugger or a thread dump does
+/// while the runtime loads classes.
+///
+/// the reader keeps the snapshot it last saw and only takes the lock of the registry to replace
+/// it once the registry published a newer one, so lookups between changes do not contend with
+/// each other or with the runtime. the registry only publishes while readers exist.
+#[derive(Debug, Clone)]
+pub struct ClassReader<'a> {
+    published: Arc<Published<'a>>,
+    current: Arc<ClassSnapshot<'a>>,
+}
+
+impl<'a> ClassReader<'a> {
+    /// the latest snapshot the registry published.
+    pub fn snapshot(&mut self) -> &Arc<ClassSnapshot<'a>> {
+        if self.published.version.load(Ordering::Acquire) != self.current.version {
+            self.current = self.published.snapshot.read().unwrap().clone();
+        }
+        &self.current
+    }
+
+    pub fn id(&mut self, name: &str) -> Option<ClassId> {
+        self.snapshot().id(name)
+    }
+
+    pub fn get(&mut self, name: &str) -> Option<Arc<ClassFile<'a>>> {
+        self.snapshot().get(name).cloned()
+    }
+}
+
+/// all classes loaded by a single `Runtime`.
+///
+/// the registry is owned by its runtime and there is no process wide state,
+/// so several runtimes can live side by side without seeing each others classes.
+#[derive(Debug, Default)]
+pub struct ClassRegistry<'a> {
+    /// the names of the classes and their methods and descriptors.
+    symbols: SymbolTable,
+    /// the parsed descriptors of the methods and of the method references resolved against them.
+    signatures: Signatures,
+    ids: HashMap<Symbol, ClassId>,
+    /// indexed by class id, `None` once a class was unloaded.
+    classes: Vec<Option<LoadedClass<'a>>>,
+    /// counts the changes which invalidate resolved references and inline caches.
+    generation: u64,
+    /// the subtypes of the loaded classes, for devirtualizing calls.
+    hierarchy: ClassHierarchy,
+    /// counts the changes which can give a call another target than the hierarchy promised: new
+    /// subtypes, replaced or unloaded classes, natives and lambdas.
+    hierarchy_generation: u64,
+    /// counts the classes loaded, replaced and unloaded, for the snapshots of the readers.
+    version: u64,
+    published: Arc<Published<'a>>,
+}
+
+impl<'a> ClassRegistry<'a> {
+    pub fn new() -> ClassRegistry<'a> {
+        ClassRegistry {
+            symbols: SymbolTable::new(),
+            signatures: Signatures::new(),
+            ids: HashMap::new(),
+            classes: Vec::new(),
+            generation: 0,
+            hierarchy: ClassHierarchy::new(),
+            hierarchy_generation: 0,
+            version: 0,
+            published: Arc::new(Published::default()),
+        }
+    }
+
+    /// the symbols of a method which is already known, without interning anything.
+    fn lookup_method_key(&self, method_name: &str, descriptor: &str) -> Option<(Symbol, Symbol)> {
+        Some((self.symbols.lookup(method_name)?, self.symbols.lookup(descriptor)?))
+    }
+
+    /// indexes the methods of a class. their code is left alone until they run.
+    fn link(&mut self, class: ClassFile<'a>) -> LoadedClass<'a> {
+        let mut methods = HashMap::new();
+        for (slot, method) in class.methods.iter().enumerate() {
+            methods.insert((self.symbols.intern(&method.name), self.symbols.intern(&method.descriptor)), slot);
+        }
+
+        LoadedClass {
+            signatures: class.methods.iter().map(|method| self.signatures.intern(&method.descriptor)).collect(),
+            pool: Arc::new(RuntimeConstantPool::new(&class)),
+            decoded: class.methods.iter().map(|_| OnceLock::new()).collect(),
+            verified: None,
+            layout: OnceLock::new(),
+            class: Arc::new(class),
+            methods,
+        }
+    }
+
+    pub fn insert(&mut self, class: ClassFile<'a>) -> ClassId {
+        let loaded = self.link(class);
+        self.insert_loaded(loaded)
+    }
+
+    /// inserts a class whose methods were already decoded, indexed like its methods.
+    pub fn insert_decoded(&mut self, class: ClassFile<'a>, decoded: Vec<Option<Arc<DecodedMethod>>>) -> ClassId {
+        let loaded = self.link(class);
+        for (cell, decoded) in loaded.decoded.iter().zip(decoded) {
+            let _ = cell.set(decoded);
+        }
+        self.insert_loaded(loaded)
+    }
+
+    /// inserts a class with the verification results of its methods, indexed like its methods.
+    pub fn insert_verified(&mut self, class: ClassFile<'a>, verified: Vec<Option<FastMethod>>) -> ClassId {
+        let mut loaded = self.link(class);
+        loaded.verified = Some(verified);
+        self.insert_loaded(loaded)
+    }
+
+    fn insert_loaded(&mut self, loaded: LoadedClass<'a>) -> ClassId {
+        let name = self.symbols.intern(loaded.class.get_class_name());
+        // references resolved before may now resolve to the new class
+        self.clear_resolutions();
+        let class = &loaded.class;
+        let subtypes_loaded_class = class.get_super_class_name().into_iter()
+            .chain(class.interfaces.iter().filter_map(|&index| class.get_class_name_at(index)))
+            .any(|supertype| self.hierarchy.get(supertype).is_some());
+        if subtypes_loaded_class || self.ids.contains_key(&name) {
+            self.invalidate_hierarchy();
+        }
+        self.hierarchy.add(class);
+        let id = match self.ids.get(&name).cloned() {
+            Some(id) => {
+                self.classes[id.index()] = Some(loaded);
+                id
+            }
+            None => {
+                let id = ClassId(self.classes.len() as u32);
+                self.classes.push(Some(loaded));
+                self.ids.insert(name, id);
+                id
+            }
+        };
+        self.changed();
+        id
+    }
+
+    /// publishes a snapshot of the classes for the readers, if there are any.
+    fn changed(&mut self) {
+        self.version += 1;
+        if Arc::strong_count(&self.published) > 1 {
+            self.publish();
+        }
+    }
+
+    fn publish(&self) {
+        if self.published.version.load(Ordering::Acquire) == self.version {
+            return;
+        }
+        let snapshot = ClassSnapshot {
+            version: self.version,
+            ids: self.ids.iter().map(|(&name, &id)| (String::from(self.symbols.name(name)), id)).collect(),
+            classes: self.classes.iter().map(|loaded| loaded.as_ref().map(|loaded| loaded.class.clone())).collect(),
+        };
+        *self.published.snapshot.write().unwrap() = Arc::new(snapshot);
+        self.published.version.store(self.version, Ordering::Release);
+    }
+
+    /// a reader of the classes which can be sent to other threads.
+    pub fn reader(&self) -> ClassReader<'a> {
+        self.publish();
+        ClassReader { published: self.published.clone(), current: self.published.snapshot.read().unwrap().clone() }
+    }
+
+    /// removes a class with its decoded methods and constant pool. returns its id, `None` if no
+    /// class of that name is loaded.
+    pub fn unload(&mut self, name: &str) -> Option<ClassId> {
+        let id = self.ids.remove(&self.symbols.lookup(name)?)?;
+        self.classes[id.index()] = None;
+        self.hierarchy.remove(name);
+        self.clear_resolutions();
+        self.invalidate_hierarchy();
+        self.changed();
+        Some(id)
+    }
+
+    /// the id of a loaded class.
+    pub fn id(&self, name: &str) -> Option<ClassId> {
+        self.ids.get(&self.symbols.lookup(name)?).cloned()
+    }
+
+    /// false once the class with the given id was unloaded.
+    pub fn is_loaded(&self, id: ClassId) -> bool {
+        self.classes.get(id.index()).is_some_and(Option::is_some)
+    }
+
+    fn loaded(&self, id: ClassId) -> &LoadedClass<'a> {
+        self.classes[id.index()].as_ref().expect("class was unloaded")
+    }
+
+    /// the class with the given id. panics on ids of another registry or of unloaded classes.
+    pub fn class(&self, id: ClassId) -> &Arc<ClassFile<'a>> {
+        &self.loaded(id).class
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<ClassFile<'a>>> {
+        self.id(name).map(|id| self.class(id).clone())
+    }
+
+    /// the index of a method declared by a loaded class, without looking at its super classes.
+    pub fn method(&self, class: ClassId, method_name: &str, descriptor: &str) -> Option<usize> {
+        self.loaded(class).methods.get(&self.lookup_method_key(method_name, descriptor)?).cloned()
+    }
+
+    /// the signature of the method at `slot` of a class, `None` once the class was unloaded.
+    pub fn signature_at(&self, class: ClassId, slot: usize) -> Option<&Arc<Signature>> {
+        self.classes.get(class.index())?.as_ref()?.signatures.get(slot)?.as_ref()
+    }
+
+    /// all loaded classes, ordered by name.
+    pub fn classes(&self) -> Vec<&Arc<ClassFile<'a>>> {
+        let mut classes = self.classes.iter().flatten().map(|loaded| &loaded.class).collect::<Vec<_>>();
+        classes.sort_by(|a, b| a.get_class_name().cmp(b.get_class_name()));
+        classes
+    }
+
+    /// the decoded bytecode of the method at `slot` of a class, decoding it on the first call.
+    /// methods which fail to decode report the error when they are invoked.
+    pub fn decoded_at(&self, class: ClassId, slot: usize) -> Option<&Arc<DecodedMethod>> {
+        let loaded = self.loaded(class);
+        loaded.decoded.get(slot)?.get_or_init(|| {
+            let method = &loaded.class.methods[slot];
+            let decoded = match loaded.verified {
+                Some(ref verified) => DecodedMethod::decode_unverified(&loaded.class, method).and_then(|mut decoded| {
+                    match verified.get(slot).cloned().flatten() {
+                        Some(ref fast) if !fast.fits(method, &decoded.instructions) => {
+                            warn!(class = loaded.class.get_class_name(), method = &*method.name, "archived verification does not fit the method, verifying it again");
+                            DecodedMethod::decode(&loaded.class, method)
+                        }
+                        fast => {
+                            decoded.fast = fast;
+                            Ok(decoded)
+                        }
+                    }
+                }),
+                None => DecodedMethod::decode(&loaded.class, method)
+            };
+            match decoded {
+                Ok(code) => Some(Arc::new(code)),
+                Err(err) => {
+                    warn!(class = loaded.class.get_class_name(), method = &*method.name, error = %err, "cannot decode method");
+                    None
+                }
+            }
+        }).as_ref()
+    }
+
+    /// the decoded bytecode of a method of a loaded class.
+    pub fn decoded(&self, class_name: &str, method_name: &str, descriptor: &str) -> Option<Arc<DecodedMethod>> {
+        let id = self.id(class_name)?;
+        let slot = self.method(id, method_name, descriptor)?;
+        self.decoded_at(id, slot).cloned()
+    }
+
+    /// the instance fields of a class below the ones of its loaded super classes.
+    pub fn layout(&self, class: ClassId) -> &Arc<FieldLayout> {
+        let loaded = self.loaded(class);
+        loaded.layout.get_or_init(|| {
+            let parent = loaded.class.get_super_class_name().and_then(|name| self.id(name)).map(|parent| self.layout(parent));
+            Arc::new(FieldLayout::extend(parent.cloned(), &loaded.class.field_signatures()))
+        })
+    }
+
+    /// the runtime constant pool of a class.
+    pub fn pool(&self, class: ClassId) -> &Arc<RuntimeConstantPool> {
+        &self.loaded(class).pool
+    }
+
+    /// the runtime constant pool of a loaded class.
+    pub fn constant_pool(&self, class_name: &str) -> Option<Arc<RuntimeConstantPool>> {
+        self.id(class_name).map(|id| self.pool(id).clone())
+    }
+
+    /// forgets all resolved references, inline cache entries and field layouts, so they are resolved
+    /// again on their next use.
+    pub fn clear_resolutions(&mut self) {
+        self.generation += 1;
+        for loaded in self.classes.iter_mut().flatten() {
+            loaded.pool.clear();
+            // objects keep the layout they were created with, fields resolved later look them up by name
+            loaded.layout = OnceLock::new();
+        }
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn signatures(&self) -> &Signatures {
+        &self.signatures
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// the subtype graph of the loaded classes.
+    pub fn hierarchy(&self) -> &ClassHierarchy {
+        &self.hierarchy
+    }
+
+    pub fn hierarchy_generation(&self) -> u64 {
+        self.hierarchy_generation
+    }
+
+    /// drops the call targets bound by class hierarchy analysis, for changes the registry does not
+    /// see itself, like new natives.
+    pub fn invalidate_hierarchy(&mut self) {
+        self.hierarchy_generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    #[test]
+    fn readers_see_published_snapshots() {
+        let mut registry = ClassRegistry::new();
+        registry.insert(read_class_file(include_bytes!("../../../sample/Box.class")).unwrap().1);
+        let mut reader = registry.reader();
+        let before = reader.snapshot().clone();
+        assert_eq!(before.len(), 1);
+
+        let id = registry.insert(read_class_file(include_bytes!("../../../sample/Cell.class")).unwrap().1);
+        assert_eq!(reader.id("Cell"), Some(id));
+        assert!(reader.get("Box").is_some());
+        // snapshots taken before stay as they were
+        assert_eq!(before.id("Cell"), None);
+
+        registry.unload("Box");
+        assert!(reader.get("Box").is_none());
+        assert_eq!(reader.snapshot().len(), 1);
+        assert_eq!(reader.snapshot().version(), 3);
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn classes_are_read_from_other_threads() {
+        use std::thread;
+
+        let mut registry = ClassRegistry::new();
+        let mut reader = registry.reader();
+        let looking = thread::spawn(move || {
+            while reader.id("Cell").is_none() {
+                thread::yield_now();
+            }
+            reader.get("Cell").map(|class| class.methods.len())
+        });
+        registry.insert(read_class_file(include_bytes!("../../../sample/Box.class")).unwrap().1);
+        registry.insert(read_class_file(include_bytes!("../../../sample/Cell.class")).unwrap().1);
+        assert_eq!(looking.join().unwrap(), registry.get("Cell").map(|class| class.methods.len()));
+    }
+}
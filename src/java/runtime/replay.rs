@@ -0,0 +1,178 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+const LOG_HEADER: &str = "rjvm input log 1";
+
+/// a nondeterministic input the program received, in the order it was received.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Input {
+    CurrentTimeMillis(i64),
+    /// the wall clock in nanoseconds since the epoch.
+    CurrentTimeNanos(i64),
+    NanoTime(i64),
+    /// a draw from the generator behind random seeds and identity hash codes.
+    Random(u64),
+    /// bytes of `SecureRandom`.
+    Entropy(Vec<u8>),
+    /// the index of the thread picked to run next.
+    Schedule(usize),
+    /// the bytes of a file or network read, or the message of the error it failed with.
+    Read(Result<Vec<u8>, String>),
+    EnvVar(Option<String>),
+}
+
+/// every nondeterministic input of one execution.
+///
+/// a log recorded with `RuntimeBuilder::record_inputs` replays the execution with `RuntimeBuilder::replay`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct InputLog {
+    pub inputs: Vec<Input>,
+}
+
+/// what the environment does with the inputs it hands out.
+#[derive(Debug, Clone)]
+pub enum Journal {
+    Off,
+    Record(InputLog),
+    /// hands out the inputs of the log instead of asking the host until the program asks for
+    /// something else than what was recorded. `diverged` is the position where that happened.
+    Replay { log: InputLog, position: usize, diverged: Option<usize> },
+}
+
+impl Journal {
+    /// the recorded input at the current replay position, if it has the expected kind.
+    pub fn replay<T>(&mut self, unwrap: fn(&Input) -> Option<T>) -> Option<T> {
+        if let Journal::Replay { ref log, ref mut position, ref mut diverged } = *self {
+            if diverged.is_some() {
+                return None;
+            }
+
+            match log.inputs.get(*position).and_then(unwrap) {
+                Some(value) => {
+                    *position += 1;
+                    return Some(value);
+                }
+                None => *diverged = Some(*position)
+            }
+        }
+
+        None
+    }
+
+    pub fn record(&mut self, input: Input) {
+        if let Journal::Record(ref mut log) = *self {
+            log.inputs.push(input);
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok()).collect()
+}
+
+fn invalid(line: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid input log entry in line {}", line))
+}
+
+impl InputLog {
+    pub fn new() -> InputLog {
+        InputLog::default()
+    }
+
+    /// writes the log as text, one input per line.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{}", LOG_HEADER)?;
+        for input in self.inputs.iter() {
+            match input {
+                Input::CurrentTimeMillis(value) => writeln!(out, "millis {}", value)?,
+                Input::CurrentTimeNanos(value) => writeln!(out, "epoch-nanos {}", value)?,
+                Input::NanoTime(value) => writeln!(out, "nanos {}", value)?,
+                Input::Random(value) => writeln!(out, "random {}", value)?,
+                Input::Entropy(bytes) => writeln!(out, "entropy {}", hex(bytes))?,
+                Input::Schedule(value) => writeln!(out, "schedule {}", value)?,
+                Input::Read(Ok(bytes)) => writeln!(out, "read {}", hex(bytes))?,
+                Input::Read(Err(message)) => writeln!(out, "read-error {}", hex(message.as_bytes()))?,
+                Input::EnvVar(Some(value)) => writeln!(out, "env {}", hex(value.as_bytes()))?,
+                Input::EnvVar(None) => writeln!(out, "env-unset")?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: BufRead>(input: R) -> io::Result<InputLog> {
+        let mut lines = input.lines();
+        match lines.next() {
+            Some(Ok(ref header)) if header == LOG_HEADER => (),
+            Some(Err(err)) => return Err(err),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "not an input log"))
+        }
+
+        let mut log = InputLog::new();
+        for (index, line) in lines.enumerate() {
+            let line = line?;
+            let number = index + 2;
+            let mut parts = line.splitn(2, ' ');
+            let kind = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("");
+
+            let input = match kind {
+                "millis" => Input::CurrentTimeMillis(value.parse().map_err(|_| invalid(number))?),
+                "epoch-nanos" => Input::CurrentTimeNanos(value.parse().map_err(|_| invalid(number))?),
+                "nanos" => Input::NanoTime(value.parse().map_err(|_| invalid(number))?),
+                "random" => Input::Random(value.parse().map_err(|_| invalid(number))?),
+                "entropy" => Input::Entropy(unhex(value).ok_or_else(|| invalid(number))?),
+                "schedule" => Input::Schedule(value.parse().map_err(|_| invalid(number))?),
+                "read" => Input::Read(Ok(unhex(value).ok_or_else(|| invalid(number))?)),
+                "read-error" => Input::Read(Err(unhex(value).and_then(|bytes| String::from_utf8(bytes).ok()).ok_or_else(|| invalid(number))?)),
+                "env" => Input::EnvVar(Some(unhex(value).and_then(|bytes| String::from_utf8(bytes).ok()).ok_or_else(|| invalid(number))?)),
+                "env-unset" => Input::EnvVar(None),
+                "" => continue,
+                _ => return Err(invalid(number))
+            };
+            log.inputs.push(input);
+        }
+
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn logs_survive_a_round_trip() {
+        let log = InputLog {
+            inputs: vec![
+                Input::CurrentTimeMillis(1_500_000_000_000),
+                Input::CurrentTimeNanos(1_500_000_000_000_000_001),
+                Input::NanoTime(-3),
+                Input::Random(u64::MAX),
+                Input::Entropy(vec![7, 0x80]),
+                Input::Schedule(2),
+                Input::Read(Ok(vec![0, 1, 0xff])),
+                Input::Read(Err(String::from("connection reset"))),
+                Input::EnvVar(Some(String::from("a b"))),
+                Input::EnvVar(None),
+            ]
+        };
+
+        let mut text = Vec::new();
+        log.write_to(&mut text).unwrap();
+        assert_eq!(InputLog::read_from(text.as_slice()).unwrap(), log);
+    }
+
+    #[test]
+    fn it_rejects_other_files() {
+        assert!(InputLog::read_from("millis 3\n".as_bytes()).is_err());
+        assert!(InputLog::read_from("rjvm input log 1\nmillis x\n".as_bytes()).is_err());
+    }
+}
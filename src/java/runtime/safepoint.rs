@@ -0,0 +1,164 @@
+//! safepoints are the places where the interpreters look at requests from outside of bytecode:
+//! the entry of a method, back edges and returns, so a running thread gets to one after a bounded
+//! number of instructions. both interpreters poll there, a single check while nothing is pending.
+//!
+//! at a safepoint the frames of the thread are complete, so a cancelled runtime unwinds, thread and
+//! heap dumps and snapshots are taken, the cleanups of unreachable objects run and a thread stops
+//! while a `SafepointHandle` pauses it. breakpoints and hooks still run at every instruction, and the
+//! garbage collector runs where the only thread allocates.
+
+use java::runtime::{Runtime, RuntimeError, StackFrame};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// where a thread reached a safepoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafepointKind {
+    MethodEntry,
+    BackEdge,
+    Return,
+}
+
+#[derive(Debug, Default)]
+struct PauseState {
+    requested: AtomicBool,
+    paused: Mutex<bool>,
+    changed: Condvar,
+}
+
+/// a cloneable handle to stop the java threads of a runtime at their next safepoint from another
+/// thread, e.g. to look at a hung program from a supervisor without killing it.
+#[derive(Debug, Clone, Default)]
+pub struct SafepointHandle {
+    state: Arc<PauseState>,
+}
+
+impl SafepointHandle {
+    pub fn new() -> SafepointHandle {
+        SafepointHandle::default()
+    }
+
+    /// asks the threads to stop at their next safepoint and waits up to `timeout` until they did.
+    /// false if they did not get there, like when no bytecode runs; the request stays until `resume`.
+    pub fn pause(&self, timeout: Duration) -> bool {
+        self.state.requested.store(true, Ordering::SeqCst);
+        let paused = self.state.paused.lock().unwrap();
+        let (paused, _) = self.state.changed.wait_timeout_while(paused, timeout, |paused| !*paused).unwrap();
+        *paused
+    }
+
+    /// lets paused threads continue. a runtime cancelled while paused stops once resumed.
+    pub fn resume(&self) {
+        self.state.requested.store(false, Ordering::SeqCst);
+        let _paused = self.state.paused.lock().unwrap();
+        self.state.changed.notify_all();
+    }
+
+    /// true while a thread waits at a safepoint.
+    pub fn is_paused(&self) -> bool {
+        *self.state.paused.lock().unwrap()
+    }
+
+    fn is_requested(&self) -> bool {
+        self.state.requested.load(Ordering::Relaxed)
+    }
+
+    /// blocks the calling java thread until the pause is over.
+    fn park(&self) {
+        let mut paused = self.state.paused.lock().unwrap();
+        *paused = true;
+        self.state.changed.notify_all();
+        while self.is_requested() {
+            paused = self.state.changed.wait(paused).unwrap();
+        }
+        *paused = false;
+        self.state.changed.notify_all();
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// a handle to pause the runtime at its next safepoint.
+    pub fn safepoint_handle(&self) -> SafepointHandle {
+        self.safepoints.clone()
+    }
+
+    /// the check of the poll, true if `safepoint` has something to do.
+    #[inline(always)]
+    pub(super) fn safepoint_pending(&self) -> bool {
+        self.cancellation.is_cancelled() || self.safepoints.is_requested()
+            || self.thread_dump_trigger.is_requested() || self.heap_dump_trigger.is_requested()
+            || self.checkpoint_trigger.is_requested() || self.cleaners.has_pending()
+    }
+
+    /// handles the pending requests at a safepoint of the innermost frame, whose values are
+    /// `current`. fails with `RuntimeError::Cancelled` if the runtime was cancelled.
+    pub(super) fn safepoint(&mut self, kind: SafepointKind, current: &mut StackFrame) -> Result<(), RuntimeError> {
+        trace!(?kind, "reached safepoint");
+        if self.safepoints.is_requested() {
+            self.safepoints.park();
+        }
+        if self.cancellation.is_cancelled() {
+            return Err(RuntimeError::Cancelled);
+        }
+        self.check_thread_dump(current);
+        if self.heap_dump_trigger.take() {
+            let path = self.heap_dump_path.clone();
+            match self.dump_heap(&path) {
+                Ok(()) => info!(path = %path.display(), "dumped heap"),
+                Err(err) => error!(path = %path.display(), error = %err, "cannot dump heap")
+            }
+        }
+        self.check_checkpoint(current);
+        if self.cleaners.has_pending() {
+            self.suspended(current, |rt| rt.run_cleanups())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{InterpreterMode, LocalVariable};
+    use std::sync::mpsc;
+    use std::thread;
+    use super::*;
+
+    fn pause_a_loop(mode: InterpreterMode) {
+        let (sender, receiver) = mpsc::channel();
+        let running = thread::spawn(move || {
+            let mut rt = Runtime::builder().interpreter(mode)
+                .build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+            sender.send((rt.safepoint_handle(), rt.cancellation_handle())).unwrap();
+            rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(i64::from(i32::MAX))])
+        });
+
+        let (safepoints, cancellation) = receiver.recv().unwrap();
+        assert!(safepoints.pause(Duration::from_secs(10)));
+        assert!(safepoints.is_paused());
+        // the cancellation is seen once the thread continues
+        cancellation.cancel();
+        safepoints.resume();
+        match running.join().unwrap() {
+            Err(RuntimeError::Cancelled) => (),
+            other => panic!("expected the loop to be cancelled, got {:?}", other)
+        }
+        assert!(!safepoints.is_paused());
+    }
+
+    #[test]
+    fn running_loops_can_be_paused_and_resumed() {
+        pause_a_loop(InterpreterMode::Checked);
+        pause_a_loop(InterpreterMode::Fast);
+    }
+
+    #[test]
+    fn pausing_an_idle_runtime_times_out() {
+        let rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        let safepoints = rt.safepoint_handle();
+        assert!(!safepoints.pause(Duration::from_millis(10)));
+        safepoints.resume();
+        assert!(!safepoints.is_paused());
+    }
+}
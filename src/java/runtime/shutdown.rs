@@ -0,0 +1,88 @@
+use java::runtime::Runtime;
+use std::io::Write;
+
+/// a callback which runs once when the runtime shuts down.
+pub type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+impl<'a> Runtime<'a> {
+    /// adds a callback for `shutdown`, like `Runtime.addShutdownHook` in java. hooks run in the
+    /// order they were added.
+    pub fn add_shutdown_hook<F>(&mut self, hook: F) where F: FnOnce() + Send + 'static {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// runs the shutdown hooks and flushes what the program printed. `run` shuts down when `main`
+    /// returns, throws or is cancelled; hooks added afterwards run on the next call.
+    pub fn shutdown(&mut self) {
+        for hook in self.shutdown_hooks.drain(..) {
+            hook();
+        }
+        // output which cannot be flushed any more is lost either way
+        let _ = self.stdout.flush();
+        let _ = self.stderr.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::Output;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    /// keeps what was written until it is flushed, like a `BufWriter`.
+    #[derive(Clone, Default)]
+    struct Buffered {
+        pending: Arc<Mutex<Vec<u8>>>,
+        flushed: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for Buffered {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.pending.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            let pending = self.pending.lock().unwrap().split_off(0);
+            self.flushed.lock().unwrap().extend(pending);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hooks_run_once_in_order() {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for name in ["first", "second"].iter() {
+            let calls = calls.clone();
+            rt.add_shutdown_hook(move || calls.lock().unwrap().push(*name));
+        }
+        rt.shutdown();
+        rt.shutdown();
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn cancelled_programs_still_shut_down() {
+        let out = Buffered::default();
+        let mut rt = Runtime::builder().stdout(Output::new(Box::new(out.clone())))
+            .build(read_class_file(include_bytes!("../../../sample/HelloWorld.class")).unwrap().1);
+        let ran = Arc::new(Mutex::new(false));
+        let hook_ran = ran.clone();
+        rt.add_shutdown_hook(move || *hook_ran.lock().unwrap() = true);
+
+        assert!(rt.run().unwrap().success());
+        assert_eq!(String::from_utf8(out.flushed.lock().unwrap().clone()).unwrap(), "Hello World!\n");
+        assert!(*ran.lock().unwrap());
+
+        // a program cancelled before its first instruction prints nothing, but the hooks run
+        *ran.lock().unwrap() = false;
+        let hook_ran = ran.clone();
+        rt.add_shutdown_hook(move || *hook_ran.lock().unwrap() = true);
+        rt.cancellation_handle().cancel();
+        assert!(rt.run().is_err());
+        assert!(*ran.lock().unwrap());
+        assert!(out.pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+use java::class_file::{MethodDescriptor, ValueType};
+use java::runtime::{LocalVariable, RuntimeError, StackFrame, StackValue};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+/// how a parameter or return value is kept by the interpreter. booleans, bytes, chars and shorts
+/// are ints, arrays are references.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    Int,
+    Long,
+    Reference,
+}
+
+impl ValueKind {
+    fn of(value_type: &ValueType) -> Option<ValueKind> {
+        match value_type {
+            ValueType::Void => None,
+            ValueType::Integer | ValueType::Boolean | ValueType::Byte | ValueType::Char | ValueType::Short => Some(ValueKind::Int),
+            ValueType::Long => Some(ValueKind::Long),
+            ValueType::Object(_) | ValueType::Array(_) => Some(ValueKind::Reference),
+        }
+    }
+
+    /// the number of local variable slots a value takes, 2 for longs.
+    pub fn slots(self) -> usize {
+        match self {
+            ValueKind::Long => 2,
+            _ => 1
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ValueKind::Int => "integer",
+            ValueKind::Long => "long",
+            ValueKind::Reference => "reference",
+        }
+    }
+
+    fn accepts(self, value: &StackValue) -> bool {
+        matches!((self, value),
+            (ValueKind::Int, StackValue::Integer(_)) | (ValueKind::Long, StackValue::Long(_)) |
+            (ValueKind::Reference, StackValue::Reference(_)) | (ValueKind::Reference, StackValue::Null))
+    }
+
+    fn accepts_local(self, value: &LocalVariable) -> bool {
+        matches!((self, value),
+            (ValueKind::Int, LocalVariable::Integer(_)) | (ValueKind::Long, LocalVariable::Long(_)) |
+            (ValueKind::Reference, LocalVariable::Reference(_)) | (ValueKind::Reference, LocalVariable::Null))
+    }
+}
+
+/// how an argument is named in errors, `None` and `Top` are no values at all.
+fn local_name(value: &LocalVariable) -> &'static str {
+    match value {
+        LocalVariable::Integer(_) => "integer",
+        LocalVariable::Long(_) => "long",
+        LocalVariable::Reference(_) => "reference",
+        LocalVariable::Null => "null",
+        LocalVariable::None | LocalVariable::Top => "no value",
+    }
+}
+
+/// a method descriptor parsed once for all calls: the kinds of the parameters, the slots they take
+/// and the kind of the return value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    parameters: Vec<ValueKind>,
+    slots: usize,
+    returns: Option<ValueKind>,
+}
+
+impl Signature {
+    /// `None` if `descriptor` is not a method descriptor.
+    pub fn parse(descriptor: &str) -> Option<Signature> {
+        let descriptor = MethodDescriptor::from_str(descriptor).ok()?;
+        let parameters = descriptor.arguments.iter().map(ValueKind::of).collect::<Option<Vec<_>>>()?;
+        Some(Signature {
+            slots: parameters.iter().map(|parameter| parameter.slots()).sum(),
+            returns: ValueKind::of(&descriptor.return_type),
+            parameters,
+        })
+    }
+
+    /// the kinds of the parameters, without the receiver.
+    pub fn parameters(&self) -> &[ValueKind] {
+        &self.parameters
+    }
+
+    /// the number of local variable slots the parameters take, without the receiver.
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// the kind of the return value, `None` for void methods.
+    pub fn returns(&self) -> Option<ValueKind> {
+        self.returns
+    }
+
+    /// pops the arguments of a call from the stack, where a long is a single value. the receiver
+    /// of instance methods becomes the first argument.
+    pub(super) fn pop_arguments(&self, stack_frame: &mut StackFrame, has_receiver: bool) -> Result<Vec<LocalVariable>, RuntimeError> {
+        let arguments = self.peek_arguments(stack_frame, has_receiver)?;
+        let depth = stack_frame.stack.len() - arguments.len();
+        stack_frame.stack.truncate(depth);
+        Ok(arguments)
+    }
+
+    /// the arguments of a call like `pop_arguments` returns them, but left on the stack.
+    pub(super) fn peek_arguments(&self, stack_frame: &StackFrame, has_receiver: bool) -> Result<Vec<LocalVariable>, RuntimeError> {
+        let receiver = if has_receiver { Some(ValueKind::Reference) } else { None };
+        let mut arguments = Vec::with_capacity(self.parameters.len() + receiver.map_or(0, |_| 1));
+        let mut values = stack_frame.stack.iter().rev();
+        for &kind in self.parameters.iter().rev().chain(receiver.iter()) {
+            match values.next() {
+                Some(value) if kind.accepts(value) => arguments.push(LocalVariable::from(value.clone())),
+                Some(_) => return Err(RuntimeError::StackType { expected: String::from(kind.name()) }),
+                None => return Err(RuntimeError::EmptyStack)
+            }
+        }
+        arguments.reverse();
+        Ok(arguments)
+    }
+
+    /// checks the arguments of a call before its frame is created, one per parameter and a long
+    /// as a single value. the receiver of instance methods is the first argument. `method` names
+    /// the callee in the error.
+    pub(super) fn check_arguments(&self, arguments: &[LocalVariable], has_receiver: bool, method: impl Fn() -> String) -> Result<(), RuntimeError> {
+        let receiver = if has_receiver { Some(ValueKind::Reference) } else { None };
+        let expected = self.parameters.len() + receiver.map_or(0, |_| 1);
+        if arguments.len() != expected {
+            return Err(RuntimeError::ArgumentCount { method: method(), expected, found: arguments.len() });
+        }
+        let kinds = receiver.iter().chain(self.parameters.iter());
+        for (index, (&kind, argument)) in kinds.zip(arguments).enumerate() {
+            if !kind.accepts_local(argument) {
+                let parameter = match receiver {
+                    Some(_) if index == 0 => String::from("this"),
+                    Some(_) => format!("parameter {}", index - 1),
+                    None => format!("parameter {}", index),
+                };
+                return Err(RuntimeError::ArgumentType { method: method(), parameter, expected: String::from(kind.name()), found: String::from(local_name(argument)) });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the signatures of a runtime, interned by descriptor so every method and call site with the same
+/// descriptor shares one.
+#[derive(Debug, Default)]
+pub struct Signatures {
+    interned: RwLock<HashMap<String, Arc<Signature>>>,
+}
+
+impl Signatures {
+    pub fn new() -> Signatures {
+        Signatures::default()
+    }
+
+    /// the signature of `descriptor`, parsing it on the first call. `None` if it is not a method
+    /// descriptor.
+    pub fn intern(&self, descriptor: &str) -> Option<Arc<Signature>> {
+        if let Some(signature) = self.interned.read().unwrap().get(descriptor) {
+            return Some(signature.clone());
+        }
+
+        let signature = Arc::new(Signature::parse(descriptor)?);
+        Some(self.interned.write().unwrap().entry(String::from(descriptor)).or_insert(signature).clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.interned.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interned.read().unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wide_parameters_take_two_slots() {
+        let signature = Signature::parse("(IJLjava/lang/String;[JZ)J").unwrap();
+        assert_eq!(signature.parameters(), &[ValueKind::Int, ValueKind::Long, ValueKind::Reference, ValueKind::Reference, ValueKind::Int]);
+        assert_eq!(signature.slots(), 6);
+        assert_eq!(signature.returns(), Some(ValueKind::Long));
+        assert_eq!(Signature::parse("()V").unwrap().returns(), None);
+        assert_eq!(Signature::parse("(I"), None);
+    }
+
+    #[test]
+    fn arguments_are_popped_by_kind() {
+        let signature = Signature::parse("(JI)V").unwrap();
+        let mut frame = StackFrame { local_variables: Vec::new(), stack: vec![StackValue::Null, StackValue::Long(1 << 40), StackValue::Integer(3)] };
+        assert_eq!(signature.pop_arguments(&mut frame, true).unwrap(), vec![LocalVariable::Null, LocalVariable::Long(1 << 40), LocalVariable::Integer(3)]);
+        assert!(frame.stack.is_empty());
+
+        // an int where the long belongs
+        let mut frame = StackFrame { local_variables: Vec::new(), stack: vec![StackValue::Integer(1), StackValue::Integer(3)] };
+        match signature.pop_arguments(&mut frame, false) {
+            Err(RuntimeError::StackType { expected }) => assert_eq!(expected, "long"),
+            other => panic!("popped {:?}", other)
+        }
+        let mut frame = StackFrame { local_variables: Vec::new(), stack: vec![StackValue::Integer(3)] };
+        assert!(signature.pop_arguments(&mut frame, false).is_err());
+    }
+
+    #[test]
+    fn arguments_are_checked_by_kind() {
+        let signature = Signature::parse("(JLjava/lang/String;)V").unwrap();
+        let name = || String::from("Tiny.f(JLjava/lang/String;)V");
+        assert!(signature.check_arguments(&[LocalVariable::Null, LocalVariable::Long(1), LocalVariable::Null], true, name).is_ok());
+
+        match signature.check_arguments(&[LocalVariable::Long(1)], false, name) {
+            Err(RuntimeError::ArgumentCount { expected: 2, found: 1, .. }) => (),
+            other => panic!("checked {:?}", other)
+        }
+        match signature.check_arguments(&[LocalVariable::Integer(1), LocalVariable::Long(1), LocalVariable::Null], true, name) {
+            Err(error @ RuntimeError::ArgumentType { .. }) => assert_eq!(error.to_string(), "this of Tiny.f(JLjava/lang/String;)V had the wrong type. expected: reference, found: integer"),
+            other => panic!("checked {:?}", other)
+        }
+        match signature.check_arguments(&[LocalVariable::Long(1), LocalVariable::Top], false, name) {
+            Err(RuntimeError::ArgumentType { parameter, found, .. }) => assert_eq!((&*parameter, &*found), ("parameter 1", "no value")),
+            other => panic!("checked {:?}", other)
+        }
+    }
+
+    #[test]
+    fn signatures_are_interned_once() {
+        let signatures = Signatures::new();
+        let first = signatures.intern("(II)I").unwrap();
+        assert!(Arc::ptr_eq(&first, &signatures.intern("(II)I").unwrap()));
+        assert!(signatures.intern("not a descriptor").is_none());
+        assert_eq!(signatures.len(), 1);
+    }
+}
@@ -0,0 +1,614 @@
+//! snapshots of the state of a runtime, for a fast start from initialized classes and warm heaps
+//! like CRaC, and to move a program to another process.
+//!
+//! a snapshot holds the names of the loaded classes, the heap with the interned strings, the
+//! statics, the initialization state of the classes, the class mirrors, identity hash codes, the
+//! spun lambda classes and the frames of the thread. the classes themselves are not in it, the
+//! runtime restoring it has to load the same ones. the interpreter keeps the frames of running
+//! methods on the native stack, so a snapshot taken at a safepoint records its frames but cannot
+//! be restored. files, native memory and processes cannot be snapshotted.
+
+use java::runtime::archive::{invalid_data, Reader};
+use java::runtime::{Cleaners, Fields, Heap, InitializationState, LambdaClass, LocalVariable, Object, ObjectData, ObjectHeader, ObjectRef, ReferenceKind,
+                    ResolvedMethod, Runtime, Signature, StackFrame, StackTraceElement, StackValue};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+const MAGIC: &[u8] = b"RJVMSNP1";
+
+/// a cloneable request to write a snapshot at the next safepoint, from another thread while the
+/// runtime executes bytecode.
+#[derive(Debug, Clone, Default)]
+pub struct CheckpointTrigger {
+    requested: Arc<AtomicBool>,
+    path: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl CheckpointTrigger {
+    pub fn new() -> CheckpointTrigger {
+        CheckpointTrigger::default()
+    }
+
+    /// writes the snapshot to `path`, a later request replaces the path of a pending one.
+    pub fn request(&self, path: PathBuf) {
+        *self.path.lock().unwrap() = Some(path);
+        self.requested.store(true, Ordering::SeqCst)
+    }
+
+    /// true while a request is pending, without taking it.
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// the path of the pending request, once for every request.
+    pub fn take(&self) -> Option<PathBuf> {
+        if !self.requested.swap(false, Ordering::SeqCst) {
+            return None;
+        }
+        self.path.lock().unwrap().take()
+    }
+}
+
+/// a method which was running when the snapshot was taken, with its values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotFrame {
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    pub pc: usize,
+    pub local_variables: Vec<LocalVariable>,
+    pub stack: Vec<StackValue>,
+}
+
+/// a lambda class as stored in a snapshot, its target is resolved again when it is restored.
+#[derive(Debug)]
+struct SnapshotLambda {
+    class_name: String,
+    interface: String,
+    method_name: String,
+    descriptor: String,
+    factory_descriptor: String,
+    kind: ReferenceKind,
+    target: ResolvedMethod,
+    resolved: bool,
+}
+
+/// the state of a runtime written by `Runtime::checkpoint`, see the module documentation.
+///
+/// the format starts with the magic `RJVMSNP1`, strings are preceded by their length and numbers
+/// are little endian.
+#[derive(Debug)]
+pub struct Snapshot {
+    classes: Vec<String>,
+    objects: Vec<Option<Object>>,
+    strings: HashMap<String, ObjectRef>,
+    statics: HashMap<(String, String), StackValue>,
+    initialization: HashMap<String, InitializationState>,
+    class_objects: HashMap<String, ObjectRef>,
+    lambdas: Vec<SnapshotLambda>,
+    frames: Vec<SnapshotFrame>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    invalid_data("snapshot", message)
+}
+
+fn write_u32<W: Write>(out: &mut W, value: usize) -> io::Result<()> {
+    out.write_all(&(value as u32).to_le_bytes())
+}
+
+fn write_string<W: Write>(out: &mut W, value: &str) -> io::Result<()> {
+    write_u32(out, value.len())?;
+    out.write_all(value.as_bytes())
+}
+
+fn write_value<W: Write>(out: &mut W, value: &LocalVariable) -> io::Result<()> {
+    let (tag, payload) = match *value {
+        LocalVariable::None => (0, 0),
+        LocalVariable::Null => (1, 0),
+        LocalVariable::Integer(value) => (2, value as u64),
+        LocalVariable::Reference(reference) => (3, reference.0 as u64),
+        LocalVariable::Long(value) => (4, value as u64),
+        LocalVariable::Top => (5, 0),
+    };
+    out.write_all(&[tag])?;
+    out.write_all(&payload.to_le_bytes())
+}
+
+fn write_stack_value<W: Write>(out: &mut W, value: &StackValue) -> io::Result<()> {
+    write_value(out, &LocalVariable::from(value.clone()))
+}
+
+fn write_object<W: Write>(out: &mut W, object: &Object) -> io::Result<()> {
+    write_string(out, &object.class_name)?;
+    write_u32(out, object.fields.len())?;
+    for (name, value) in object.fields.iter() {
+        write_string(out, name)?;
+        write_stack_value(out, value)?;
+    }
+    match object.data {
+        ObjectData::Instance => out.write_all(&[0]),
+        ObjectData::String(ref value) => {
+            out.write_all(&[1])?;
+            write_string(out, value)
+        }
+        ObjectData::Array(ref elements) => {
+            out.write_all(&[2])?;
+            write_u32(out, elements.len())?;
+            elements.iter().try_for_each(|element| write_stack_value(out, element))
+        }
+        ObjectData::Throwable(ref trace) => {
+            out.write_all(&[3])?;
+            write_u32(out, trace.len())?;
+            for element in trace.iter() {
+                write_string(out, &element.class_name)?;
+                write_string(out, &element.method_name)?;
+                write_string(out, element.file_name.as_ref().map_or("", String::as_str))?;
+                write_u32(out, element.line_number.map_or(0, |line| line + 1))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn kind_code(kind: ReferenceKind) -> u8 {
+    match kind {
+        ReferenceKind::InvokeVirtual => 5,
+        ReferenceKind::InvokeStatic => 6,
+        ReferenceKind::InvokeSpecial => 7,
+        ReferenceKind::NewInvokeSpecial => 8,
+        ReferenceKind::InvokeInterface => 9,
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn owned_string(&mut self) -> io::Result<String> {
+        self.string().map(String::from)
+    }
+
+    fn value(&mut self) -> io::Result<LocalVariable> {
+        let tag = self.u8()?;
+        let payload = self.u64()?;
+        match tag {
+            0 => Ok(LocalVariable::None),
+            1 => Ok(LocalVariable::Null),
+            2 => Ok(LocalVariable::Integer(payload as i64)),
+            // the restored heap starts every slot at generation 0
+            3 => Ok(LocalVariable::Reference(ObjectRef(payload as usize, 0))),
+            4 => Ok(LocalVariable::Long(payload as i64)),
+            5 => Ok(LocalVariable::Top),
+            _ => Err(invalid("unknown value type"))
+        }
+    }
+
+    fn stack_value(&mut self) -> io::Result<StackValue> {
+        self.value().map(StackValue::from)
+    }
+
+    fn object(&mut self) -> io::Result<Object> {
+        let class_name = self.owned_string()?;
+        let mut fields = Fields::new();
+        for _ in 0..self.u32()? {
+            let name = self.owned_string()?;
+            fields.insert(name, self.stack_value()?);
+        }
+        let data = match self.u8()? {
+            0 => ObjectData::Instance,
+            1 => ObjectData::String(self.owned_string()?.into()),
+            2 => ObjectData::Array((0..self.u32()?).map(|_| self.stack_value()).collect::<io::Result<_>>()?),
+            3 => ObjectData::Throwable((0..self.u32()?).map(|_| {
+                let class_name = self.owned_string()?;
+                let method_name = self.owned_string()?;
+                let file_name = Some(self.owned_string()?).filter(|name| !name.is_empty());
+                let line_number = (self.u32()? as usize).checked_sub(1);
+                Ok(StackTraceElement { class_name, method_name, file_name, line_number })
+            }).collect::<io::Result<_>>()?),
+            _ => return Err(invalid("unknown object data"))
+        };
+        Ok(Object { header: ObjectHeader::default(), class_name, fields, data })
+    }
+}
+
+impl Snapshot {
+    pub fn open(path: &Path) -> io::Result<Snapshot> {
+        Snapshot::read(&fs::read(path)?)
+    }
+
+    pub fn read(bytes: &[u8]) -> io::Result<Snapshot> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(invalid("bad magic"));
+        }
+        let mut reader = Reader { bytes: &bytes[MAGIC.len()..], what: "snapshot" };
+
+        let classes = (0..reader.u32()?).map(|_| reader.owned_string()).collect::<io::Result<Vec<_>>>()?;
+
+        let slots = reader.u32()? as usize;
+        let mut objects = Vec::with_capacity(slots);
+        for _ in 0..slots {
+            let mut object = match reader.u8()? {
+                0 => None,
+                1 => Some(reader.object()?),
+                _ => return Err(invalid("unknown heap slot"))
+            };
+            let tenured = reader.u8()? != 0;
+            if let Some(ref mut object) = object {
+                object.header.set_tenured(tenured);
+            }
+            objects.push(object);
+        }
+        let mut strings = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let value = reader.owned_string()?;
+            strings.insert(value, ObjectRef(reader.u32()? as usize, 0));
+        }
+
+        let mut statics = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let class_name = reader.owned_string()?;
+            let field_name = reader.owned_string()?;
+            statics.insert((class_name, field_name), reader.stack_value()?);
+        }
+        let mut initialization = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let class_name = reader.owned_string()?;
+            initialization.insert(class_name, match reader.u8()? {
+                0 => InitializationState::InProgress,
+                1 => InitializationState::Initialized,
+                2 => InitializationState::Erroneous,
+                _ => return Err(invalid("unknown initialization state"))
+            });
+        }
+        let mut class_objects = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let class_name = reader.owned_string()?;
+            class_objects.insert(class_name, ObjectRef(reader.u32()? as usize, 0));
+        }
+        for _ in 0..reader.u32()? {
+            let reference = reader.u32()? as usize;
+            let hash = reader.u32()? as i32;
+            match objects.get_mut(reference) {
+                Some(Some(object)) => object.header.set_identity_hash(hash),
+                _ => return Err(invalid("identity hash of a missing object"))
+            }
+        }
+
+        let mut lambdas = Vec::new();
+        for _ in 0..reader.u32()? {
+            let class_name = reader.owned_string()?;
+            let interface = reader.owned_string()?;
+            let method_name = reader.owned_string()?;
+            let descriptor = reader.owned_string()?;
+            let factory_descriptor = reader.owned_string()?;
+            let kind = ReferenceKind::from_u8(reader.u8()?).ok_or_else(|| invalid("unknown reference kind"))?;
+            let (target_class, target_method, target_descriptor) = (reader.owned_string()?, reader.owned_string()?, reader.owned_string()?);
+            let target = ResolvedMethod {
+                signature: Arc::new(Signature::parse(&target_descriptor).ok_or_else(|| invalid("method descriptor of a lambda"))?),
+                class_name: target_class,
+                method_name: target_method,
+                descriptor: target_descriptor,
+                intrinsic: None,
+                target: None,
+            };
+            let resolved = reader.u8()? != 0;
+            lambdas.push(SnapshotLambda { class_name, interface, method_name, descriptor, factory_descriptor, kind, target, resolved });
+        }
+
+        let mut frames = Vec::new();
+        for _ in 0..reader.u32()? {
+            let class_name = reader.owned_string()?;
+            let method_name = reader.owned_string()?;
+            let descriptor = reader.owned_string()?;
+            let pc = reader.u32()? as usize;
+            let local_variables = (0..reader.u32()?).map(|_| reader.value()).collect::<io::Result<_>>()?;
+            let stack = (0..reader.u32()?).map(|_| reader.stack_value()).collect::<io::Result<_>>()?;
+            frames.push(SnapshotFrame { class_name, method_name, descriptor, pc, local_variables, stack });
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+        Ok(Snapshot { classes, objects, strings, statics, initialization, class_objects, lambdas, frames })
+    }
+
+    /// the names of the classes which were loaded.
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    /// the number of live objects on the heap.
+    pub fn objects(&self) -> usize {
+        self.objects.iter().flatten().count()
+    }
+
+    /// the frames of the thread, outermost first. empty if the snapshot was not taken at a safepoint.
+    pub fn frames(&self) -> &[SnapshotFrame] {
+        &self.frames
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// a trigger to write a snapshot while the runtime is executing bytecode.
+    pub fn checkpoint_trigger(&self) -> CheckpointTrigger {
+        self.checkpoint_trigger.clone()
+    }
+
+    /// writes a snapshot of the runtime to `path`, to be restored with `Runtime::restore`.
+    pub fn checkpoint(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_snapshot(&mut out)?;
+        out.flush()
+    }
+
+    /// writes a snapshot of the runtime to `out`.
+    pub fn write_snapshot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_snapshot_at(out, None)
+    }
+
+    /// writes the snapshot a `CheckpointTrigger` asked for at a safepoint of the innermost frame,
+    /// whose values are `current`.
+    pub(super) fn check_checkpoint(&mut self, current: &StackFrame) {
+        if let Some(path) = self.checkpoint_trigger.take() {
+            let written = File::create(&path).and_then(|file| {
+                let mut out = BufWriter::new(file);
+                self.write_snapshot_at(&mut out, Some(current))?;
+                out.flush()
+            });
+            match written {
+                Ok(()) => info!(path = %path.display(), "wrote snapshot"),
+                Err(err) => error!(path = %path.display(), error = %err, "cannot write snapshot")
+            }
+        }
+    }
+
+    fn write_snapshot_at<W: Write>(&self, out: &mut W, current: Option<&StackFrame>) -> io::Result<()> {
+        if self.files.iter().any(Option::is_some) || !self.native_memory.is_empty() || !self.processes.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot snapshot open files, native memory or processes"));
+        }
+
+        out.write_all(MAGIC)?;
+        let classes = self.classes.classes();
+        write_u32(out, classes.len())?;
+        for class in classes {
+            write_string(out, class.get_class_name())?;
+        }
+
+        write_u32(out, self.heap.len())?;
+        for index in 0..self.heap.len() {
+            match self.heap.reference_at(index) {
+                Some(reference) => {
+                    out.write_all(&[1])?;
+                    write_object(out, self.heap.get(reference).unwrap())?;
+                    out.write_all(&[self.heap.is_tenured(reference) as u8])?;
+                }
+                None => out.write_all(&[0, 0])?
+            }
+        }
+        // maps are written sorted, so the same state always gives the same bytes
+        let mut strings = self.heap.interned_strings().collect::<Vec<_>>();
+        strings.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(out, strings.len())?;
+        for (value, reference) in strings {
+            write_string(out, value)?;
+            write_u32(out, reference.0)?;
+        }
+
+        let mut statics = self.statics.iter().collect::<Vec<_>>();
+        statics.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(out, statics.len())?;
+        for ((class_name, field_name), value) in statics {
+            write_string(out, class_name)?;
+            write_string(out, field_name)?;
+            write_stack_value(out, value)?;
+        }
+        let mut initialization = self.initialization.iter().collect::<Vec<_>>();
+        initialization.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(out, initialization.len())?;
+        for (class_name, state) in initialization {
+            write_string(out, class_name)?;
+            out.write_all(&[match state {
+                InitializationState::InProgress => 0,
+                InitializationState::Initialized => 1,
+                InitializationState::Erroneous => 2,
+            }])?;
+        }
+        let mut class_objects = self.class_objects.iter().collect::<Vec<_>>();
+        class_objects.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(out, class_objects.len())?;
+        for (class_name, reference) in class_objects {
+            write_string(out, class_name)?;
+            write_u32(out, reference.0)?;
+        }
+        let identity_hashes = self.heap.objects()
+            .filter_map(|(reference, object)| object.header.identity_hash().map(|hash| (reference, hash)))
+            .collect::<Vec<_>>();
+        write_u32(out, identity_hashes.len())?;
+        for (reference, hash) in identity_hashes {
+            write_u32(out, reference.0)?;
+            out.write_all(&hash.to_le_bytes())?;
+        }
+
+        // in the order they were spun, so that new lambda classes get the next number
+        let mut lambdas = self.lambda_classes.values().collect::<Vec<_>>();
+        lambdas.sort_by_key(|lambda| lambda.class_name.rsplit('$').next().and_then(|number| number.parse::<usize>().ok()));
+        write_u32(out, lambdas.len())?;
+        for lambda in lambdas {
+            write_string(out, &lambda.class_name)?;
+            write_string(out, &lambda.interface)?;
+            write_string(out, &lambda.method_name)?;
+            write_string(out, &lambda.descriptor)?;
+            write_string(out, &lambda.factory_descriptor)?;
+            out.write_all(&[kind_code(lambda.kind)])?;
+            write_string(out, &lambda.target.class_name)?;
+            write_string(out, &lambda.target.method_name)?;
+            write_string(out, &lambda.target.descriptor)?;
+            out.write_all(&[lambda.target.target.is_some() as u8])?;
+        }
+
+        write_u32(out, self.thread.frames.len())?;
+        let depth = self.thread.frames.len();
+        for (index, frame) in self.thread.frames.iter().enumerate() {
+            write_string(out, self.classes.class(frame.class).get_class_name())?;
+            write_string(out, &frame.method_name)?;
+            write_string(out, &frame.descriptor)?;
+            write_u32(out, frame.pc)?;
+            let values = if index + 1 == depth { current } else { frame.saved.as_ref() };
+            let (local_variables, stack) = values.map_or((&[][..], &[][..]), |values| (&values.local_variables[..], &values.stack[..]));
+            write_u32(out, local_variables.len())?;
+            local_variables.iter().try_for_each(|value| write_value(out, value))?;
+            write_u32(out, stack.len())?;
+            stack.iter().try_for_each(|value| write_stack_value(out, value))?;
+        }
+        Ok(())
+    }
+
+    /// replaces the heap, the statics and the state of the classes with the ones of `snapshot`.
+    /// the classes of the snapshot have to be loaded already. fails without changing anything if
+    /// one of them is missing or the snapshot was taken while methods were running.
+    pub fn restore(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        if !snapshot.frames.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot resume the running methods of a snapshot"));
+        }
+        if let Some(missing) = snapshot.classes.iter().find(|class_name| self.classes.id(class_name).is_none()) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("class {} of the snapshot is not loaded", missing)));
+        }
+
+        let mut lambda_classes = HashMap::new();
+        for lambda in snapshot.lambdas {
+            let mut target = lambda.target;
+            if lambda.resolved {
+                target.target = self.classes.id(&target.class_name)
+                    .and_then(|id| self.classes.method(id, &target.method_name, &target.descriptor).map(|slot| (id, slot)));
+            }
+            let class = LambdaClass {
+                class_name: lambda.class_name,
+                interface: lambda.interface,
+                method_name: lambda.method_name,
+                descriptor: lambda.descriptor,
+                factory_descriptor: lambda.factory_descriptor,
+                kind: lambda.kind,
+                target: Arc::new(target),
+            };
+            lambda_classes.insert(class.class_name.clone(), Arc::new(class));
+        }
+
+        let mut objects = snapshot.objects;
+        for object in objects.iter_mut().flatten() {
+            if let Some(id) = self.classes.id(&object.class_name) {
+                object.header.set_class(Some(id));
+                object.fields.set_layout(self.classes.layout(id).clone());
+            }
+        }
+        self.heap = Heap::restore(objects, snapshot.strings, self.heap.large_object_threshold());
+        self.statics = snapshot.statics;
+        self.initialization = snapshot.initialization;
+        self.class_objects = snapshot.class_objects;
+        self.lambda_classes = lambda_classes;
+        // the registered objects of before are not the ones of the snapshot
+        self.cleaners = Cleaners::new();
+        // linked call sites hold the lambda classes of before
+        self.classes.clear_resolutions();
+        self.classes.invalidate_hierarchy();
+        info!(objects = self.heap.objects().count(), "restored snapshot");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Checkpoint.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/CheckpointNode.class")).unwrap().1);
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> i64 {
+        match rt.invoke_static("Checkpoint", name, descriptor, arguments) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn a_restored_runtime_continues_with_the_state() {
+        let mut rt = runtime();
+        assert_eq!(int(&mut rt, "add", "(I)I", vec![LocalVariable::Integer(5)]), 5);
+        assert_eq!(int(&mut rt, "add", "(I)I", vec![LocalVariable::Integer(7)]), 12);
+        assert_eq!(int(&mut rt, "capture", "(I)I", vec![LocalVariable::Integer(3)]), 36);
+        let mut bytes = Vec::new();
+        rt.write_snapshot(&mut bytes).unwrap();
+
+        let snapshot = Snapshot::read(&bytes).unwrap();
+        assert!(snapshot.classes().iter().any(|class_name| class_name == "CheckpointNode"));
+        assert_eq!(snapshot.objects(), rt.heap().objects().count());
+        assert!(snapshot.frames().is_empty());
+
+        let mut restored = runtime();
+        restored.restore(snapshot).unwrap();
+        // the static initializer does not run again
+        assert_eq!(int(&mut restored, "initialized", "()I", vec![]), 1);
+        assert_eq!(int(&mut restored, "sum", "()I", vec![]), 12);
+        assert_eq!(int(&mut restored, "count", "(I)I", vec![LocalVariable::Integer(1)]), 1);
+        assert_eq!(int(&mut restored, "count", "(I)I", vec![LocalVariable::Integer(3)]), 1);
+        assert_eq!(int(&mut restored, "scaled", "(I)I", vec![LocalVariable::Integer(4)]), 12);
+        assert_eq!(int(&mut restored, "add", "(I)I", vec![LocalVariable::Integer(1)]), 13);
+        // the interned strings stay canonical
+        let name = restored.intern("checkpoint");
+        assert_eq!(restored.statics.get(&(String::from("Checkpoint"), String::from("name"))), Some(&StackValue::Reference(name)));
+    }
+
+    #[test]
+    fn the_same_state_gives_the_same_bytes() {
+        let snapshot = || {
+            let mut rt = runtime();
+            assert_eq!(int(&mut rt, "add", "(I)I", vec![LocalVariable::Integer(5)]), 5);
+            assert_eq!(int(&mut rt, "capture", "(I)I", vec![LocalVariable::Integer(3)]), 15);
+            let mut bytes = Vec::new();
+            rt.write_snapshot(&mut bytes).unwrap();
+            bytes
+        };
+        // every runtime hashes its maps with other keys
+        assert_eq!(snapshot(), snapshot());
+    }
+
+    #[test]
+    fn snapshots_at_safepoints_have_frames() {
+        let dir = std::env::temp_dir().join(format!("rjvm-snapshot-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("frames.snapshot");
+
+        let mut rt = runtime();
+        int(&mut rt, "add", "(I)I", vec![LocalVariable::Integer(2)]);
+        rt.checkpoint_trigger().request(path.clone());
+        assert_eq!(int(&mut rt, "count", "(I)I", vec![LocalVariable::Integer(2)]), 1);
+        assert!(!rt.checkpoint_trigger().is_requested());
+
+        let snapshot = Snapshot::open(&path).unwrap();
+        let frame = &snapshot.frames()[0];
+        assert_eq!((frame.class_name.as_str(), frame.method_name.as_str(), frame.descriptor.as_str()), ("Checkpoint", "count", "(I)I"));
+        assert_eq!(frame.local_variables, vec![LocalVariable::Integer(2)]);
+
+        let mut restored = runtime();
+        assert_eq!(restored.restore(snapshot).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restoring_needs_the_classes_of_the_snapshot() {
+        let mut rt = runtime();
+        int(&mut rt, "add", "(I)I", vec![LocalVariable::Integer(2)]);
+        let mut bytes = Vec::new();
+        rt.write_snapshot(&mut bytes).unwrap();
+
+        let mut other = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Checkpoint.class")).unwrap().1);
+        assert_eq!(other.restore(Snapshot::read(&bytes).unwrap()).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(Snapshot::read(&bytes[..bytes.len() - 1]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(Snapshot::read(b"RJVMCDS1").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
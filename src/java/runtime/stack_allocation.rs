@@ -0,0 +1,220 @@
+use java::analysis::EscapeAnalysis;
+use java::runtime::constant_pool::ACC_NATIVE;
+use java::runtime::{ClassId, Fields, ObjectData, ObjectRef, Runtime};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// constructors calling constructors deeper than this count as letting their receiver escape.
+const MAX_CONSTRUCTOR_DEPTH: usize = 8;
+
+#[derive(Debug)]
+struct AnalyzedSites {
+    /// by the pc of the `new`, true if the object it created before is dead when it runs again.
+    sites: HashMap<usize, bool>,
+    generation: u64,
+}
+
+/// the `new` instructions of a method whose objects never escape it, found by `EscapeAnalysis`.
+///
+/// their objects belong to the frame which created them and are freed when it returns instead of
+/// waiting for a collection. a site whose previous object is always dead when it runs again, like
+/// a temporary created in every iteration of a loop, reuses the object. the result is dropped when
+/// the `ClassRegistry` changes, since a constructor may be redefined.
+#[derive(Debug, Default)]
+pub struct FrameAllocations {
+    analyzed: Mutex<Option<AnalyzedSites>>,
+}
+
+impl FrameAllocations {
+    /// whether the `new` at `pc` allocates in the frame and can reuse the object it created before.
+    /// the outer `None` means the method was not analyzed for this generation.
+    fn lookup(&self, pc: usize, generation: u64) -> Option<Option<bool>> {
+        match *self.analyzed.lock().unwrap() {
+            Some(ref analyzed) if analyzed.generation == generation => Some(analyzed.sites.get(&pc).cloned()),
+            _ => None
+        }
+    }
+
+    fn update(&self, sites: HashMap<usize, bool>, generation: u64) {
+        *self.analyzed.lock().unwrap() = Some(AnalyzedSites { sites, generation });
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// the object for the `new` of `class_name` at `pc` of the running method, if it does not escape
+    /// the method: the object the instruction created before if that one is dead, or a new one the
+    /// frame frees when it returns. `None` if the object has to be allocated like any other.
+    pub(super) fn allocate_in_frame(&mut self, pc: usize, class_name: &str) -> Option<ObjectRef> {
+        // a frame would free objects to finalize without finalizing them
+        if !self.can_allocate_in_frame() || self.has_finalizer(class_name) {
+            return None;
+        }
+        let (id, slot) = match self.thread.frames.last() {
+            Some(frame) => (frame.class, frame.slot),
+            None => return None
+        };
+        // cold methods are not analyzed, like the ones inlining looks at
+        if !self.counters.is_hot(id, slot) {
+            return None;
+        }
+
+        let decoded = self.classes.decoded_at(id, slot)?.clone();
+        let generation = self.classes.generation();
+        let reusable = match decoded.frame_allocations.lookup(pc, generation) {
+            Some(site) => site?,
+            None => {
+                let sites = self.analyze_escapes(id, slot);
+                let site = sites.get(&pc).cloned();
+                decoded.frame_allocations.update(sites, generation);
+                site?
+            }
+        };
+
+        let previous = self.thread.frames.last().and_then(|frame| frame.local_objects.iter().rev().find(|&&(site, _)| site == pc).map(|&(_, object)| object));
+        if let (true, Some(object), Some(class)) = (reusable, previous, self.classes.id(class_name)) {
+            let layout = self.classes.layout(class).clone();
+            if let Some(previous) = self.heap.get_mut(object) {
+                previous.fields = Fields::with_layout(layout);
+                return Some(object);
+            }
+        }
+
+        let object = self.allocate(class_name, ObjectData::Instance);
+        if let Some(frame) = self.thread.frames.last_mut() {
+            frame.local_objects.push((pc, object));
+        }
+        Some(object)
+    }
+
+    /// objects in frames are neither reported to hooks nor to the allocation profiler, and what
+    /// a trace shows would not match the heap, so they are only used while nothing observes.
+    fn can_allocate_in_frame(&self) -> bool {
+        self.escape_analysis && self.hooks.is_empty() && !self.allocations.is_enabled() && self.debugger.is_none()
+            && self.trace.is_none() && self.explain.is_none()
+    }
+
+    /// frees the objects of a frame which returned.
+    pub(super) fn free_frame_objects(&mut self, objects: Vec<(usize, ObjectRef)>) {
+        for (_, object) in objects {
+            self.cleaners.forget(object);
+            self.heap.free(object);
+        }
+    }
+
+    /// the sites of a method whose objects can live in its frame, with whether they can be reused.
+    fn analyze_escapes(&self, id: ClassId, slot: usize) -> HashMap<usize, bool> {
+        let class = self.classes.class(id).clone();
+        let method = &class.methods[slot];
+        let analysis = match EscapeAnalysis::analyze(&class, method, |owner, name, descriptor| self.receiver_escapes(owner, name, descriptor, 0)) {
+            Ok(analysis) => analysis,
+            Err(err) => {
+                debug!(class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor, error = %err, "cannot analyze escapes");
+                return HashMap::new();
+            }
+        };
+
+        let sites = analysis.non_escaping()
+            // builtin classes keep their state outside of the fields
+            .filter(|allocation| self.classes.id(&allocation.class_name).is_some())
+            .map(|allocation| (allocation.pc, allocation.reusable))
+            .collect::<HashMap<_, _>>();
+        debug!(class = class.get_class_name(), method = &*method.name, descriptor = &*method.descriptor, sites = sites.len(), "analyzed escapes");
+        sites
+    }
+
+    /// true if the constructor of a class can let its receiver escape.
+    fn receiver_escapes(&self, class_name: &str, name: &str, descriptor: &str, depth: usize) -> bool {
+        if class_name == "java/lang/Object" {
+            return false;
+        }
+        let id = match self.classes.id(class_name) {
+            Some(id) if depth < MAX_CONSTRUCTOR_DEPTH => id,
+            _ => return true
+        };
+        let class = self.classes.class(id).clone();
+        let method = match self.classes.method(id, name, descriptor) {
+            Some(slot) => &class.methods[slot],
+            None => return true
+        };
+        if method.access_flags & ACC_NATIVE != 0 {
+            return true;
+        }
+
+        EscapeAnalysis::analyze(&class, method, |owner, name, descriptor| self.receiver_escapes(owner, name, descriptor, depth + 1))
+            .map(|analysis| analysis.receiver.is_some())
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, StackValue};
+    use super::*;
+
+    fn runtime<'a>(escape_analysis: bool) -> Runtime<'a> {
+        let mut rt = Runtime::builder().hot_method_threshold(2).escape_analysis(escape_analysis)
+            .build(read_class_file(include_bytes!("../../../sample/Escapes.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Vec2.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Tracked.class")).unwrap().1);
+        rt
+    }
+
+    fn int(rt: &mut Runtime, name: &str, n: i64) -> i64 {
+        match rt.invoke_static("Escapes", name, "(I)I", vec![LocalVariable::Integer(n)]) {
+            Ok(Some(StackValue::Integer(value))) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    fn vectors(rt: &Runtime) -> usize {
+        rt.heap.objects().filter(|(_, object)| object.class_name == "Vec2").count()
+    }
+
+    #[test]
+    fn temporaries_of_hot_methods_live_in_the_frame() {
+        let mut rt = runtime(true);
+        let mut heap = runtime(false);
+        for _ in 0..3 {
+            assert_eq!(int(&mut rt, "temporaries", 100), 333_300);
+            assert_eq!(int(&mut heap, "temporaries", 100), 333_300);
+            assert_eq!(int(&mut rt, "pairs", 10), 295);
+            assert_eq!(int(&mut heap, "pairs", 10), 295);
+        }
+
+        // each call of the hot methods allocates one object per site in its frame and frees it on
+        // return, so the next call reuses its slot. only the first iteration of the first calls ran
+        // while the methods were cold and left 3 objects behind
+        assert_eq!(heap.heap.len(), 3 * (100 + 2 * 10));
+        assert_eq!(rt.heap.len(), 3 + 2);
+        assert_eq!(vectors(&rt), 3);
+        rt.gc();
+        assert_eq!(vectors(&rt), 0);
+    }
+
+    #[test]
+    fn escaping_objects_stay_on_the_heap() {
+        let mut rt = runtime(true);
+        for n in 0..3 {
+            assert_eq!(int(&mut rt, "passed", n), n + 2);
+            assert_eq!(int(&mut rt, "stored", n), n);
+            assert_eq!(int(&mut rt, "tracked", n), n);
+        }
+        assert_eq!(vectors(&rt), 6);
+        assert_eq!(rt.heap.objects().filter(|(_, object)| object.class_name == "Tracked").count(), 3);
+    }
+
+    #[test]
+    fn collections_keep_the_objects_of_frames() {
+        let mut rt = runtime(true);
+        for n in 0..4 {
+            assert_eq!(int(&mut rt, "collected", n), n);
+            let kept = match rt.statics.get(&(String::from("Escapes"), String::from("kept"))) {
+                Some(&StackValue::Reference(kept)) => kept,
+                other => panic!("kept is {:?}", other)
+            };
+            // the object collected while its frame was running must not take the slot of `kept` with it
+            assert_eq!(rt.heap.get(kept).unwrap().fields.get("x"), Some(&StackValue::Integer(7)));
+        }
+    }
+}
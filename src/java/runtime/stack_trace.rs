@@ -0,0 +1,22 @@
+use std::fmt;
+
+/// one line of a java stack trace, mirrors `java.lang.StackTraceElement`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StackTraceElement {
+    /// the binary name of the class, with `.` as package separator.
+    pub class_name: String,
+    pub method_name: String,
+    pub file_name: Option<String>,
+    pub line_number: Option<usize>,
+}
+
+impl fmt::Display for StackTraceElement {
+    /// formats like `StackTraceElement.toString`: `pkg.Cls.m(Cls.java:12)`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.file_name, self.line_number) {
+            (Some(file), Some(line)) => write!(f, "{}.{}({}:{})", self.class_name, self.method_name, file, line),
+            (Some(file), None) => write!(f, "{}.{}({})", self.class_name, self.method_name, file),
+            (None, _) => write!(f, "{}.{}(Unknown Source)", self.class_name, self.method_name),
+        }
+    }
+}
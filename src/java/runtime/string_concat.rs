@@ -0,0 +1,183 @@
+use java::class_file::{ClassFile, ConstantType, Method, ValueType};
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::mem;
+use super::StackFrame;
+use super::call_site::Bootstrap;
+use super::enums::ENUM;
+use super::heap::HEADER_SIZE;
+use super::method_handles::parse_descriptor;
+
+/// marks an argument of the call site in a recipe.
+const TAG_ARGUMENT: char = '\u{1}';
+/// marks the next static argument of the bootstrap method in a recipe.
+const TAG_CONSTANT: char = '\u{2}';
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Part {
+    Literal(String),
+    /// the index of an argument of the call site.
+    Argument(usize),
+}
+
+/// a string concatenation which javac 9 and later compile to `invokedynamic`, with the recipe of
+/// `StringConcatFactory.makeConcatWithConstants` split into literal text and arguments.
+/// constants of the recipe are folded into the literal text when the call site is linked.
+#[derive(Debug)]
+pub struct ConcatRecipe {
+    parts: Vec<Part>,
+    /// the type of each argument of the call site.
+    arguments: Vec<ValueType>,
+    descriptor: String,
+}
+
+impl ConcatRecipe {
+    pub(super) fn link(class: &ClassFile, bootstrap: &Bootstrap) -> Result<ConcatRecipe, RuntimeError> {
+        let constant = |index: u16| match class.get_constant(index) {
+            Some(ConstantType::String { string_index }) => match class.get_constant(*string_index) {
+                Some(ConstantType::Utf8 { value }) => Ok(String::from(&**value)),
+                _ => Err(RuntimeError::InvalidConstant { index: *string_index, expected: String::from("Utf8") })
+            },
+            Some(ConstantType::Integer { value }) => Ok(value.to_string()),
+            Some(ConstantType::Long { value }) => Ok(value.to_string()),
+            _ => Err(RuntimeError::InvalidConstant { index, expected: String::from("String") })
+        };
+
+        let mut constants = bootstrap.arguments.iter();
+        let recipe = match constants.next() {
+            Some(&index) => constant(index)?,
+            None => return Err(RuntimeError::InvalidBytecode { message: String::from("makeConcatWithConstants without recipe") })
+        };
+
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut arguments = 0;
+        for c in recipe.chars() {
+            match c {
+                TAG_ARGUMENT => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Argument(arguments));
+                    arguments += 1;
+                }
+                TAG_CONSTANT => match constants.next() {
+                    Some(&index) => literal.push_str(&constant(index)?),
+                    None => return Err(RuntimeError::InvalidBytecode { message: format!("recipe {:?} uses more constants than given", recipe) })
+                },
+                c => literal.push(c)
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        let descriptor = parse_descriptor(bootstrap.descriptor)?;
+        if descriptor.arguments.len() != arguments {
+            return Err(RuntimeError::InvalidBytecode {
+                message: format!("recipe {:?} takes {} arguments, the call site {}", recipe, arguments, bootstrap.descriptor),
+            });
+        }
+
+        Ok(ConcatRecipe { parts, arguments: descriptor.arguments, descriptor: String::from(bootstrap.descriptor) })
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// concatenates the arguments on the stack into a new string following the recipe.
+    pub(super) fn exec_concat(&mut self, method: &Method, stack_frame: &mut StackFrame, recipe: &ConcatRecipe) -> Result<(), RuntimeError> {
+        // the arguments stay on the stack, visible to the garbage collector, while their toString methods run
+        let arguments = self.peek_arguments(stack_frame, &recipe.descriptor, false)?;
+        let value = self.suspended(stack_frame, |rt| -> Result<String, RuntimeError> {
+            let mut value = String::new();
+            for part in recipe.parts.iter() {
+                match part {
+                    Part::Literal(literal) => value.push_str(literal),
+                    Part::Argument(index) => {
+                        let argument = rt.string_value_of(&recipe.arguments[*index], &arguments[*index])?;
+                        value.push_str(&argument);
+                    }
+                }
+            }
+            Ok(value)
+        })?;
+        let depth = stack_frame.stack.len() - arguments.len();
+        stack_frame.stack.truncate(depth);
+
+        let data = ObjectData::String(value.into());
+        self.reserve(method, stack_frame, HEADER_SIZE + data.size())?;
+        let reference = self.allocate("java/lang/String", data);
+        stack_frame.push_stack(StackValue::Reference(reference));
+        Ok(())
+    }
+
+    /// `String.valueOf` for a value of the given type.
+    fn string_value_of(&mut self, value_type: &ValueType, value: &LocalVariable) -> Result<String, RuntimeError> {
+        Ok(match (value_type, value) {
+            (ValueType::Boolean, LocalVariable::Integer(value)) => String::from(if *value != 0 { "true" } else { "false" }),
+            (ValueType::Char, LocalVariable::Integer(value)) => std::char::from_u32(u32::from(*value as u16)).unwrap_or('\u{fffd}').to_string(),
+            (_, LocalVariable::Integer(value)) | (_, LocalVariable::Long(value)) => value.to_string(),
+            (_, LocalVariable::Null) => String::from("null"),
+            (_, LocalVariable::Reference(reference)) => self.object_to_string(*reference)?,
+            _ => return Err(RuntimeError::StackType { expected: String::from("value to concatenate") })
+        })
+    }
+
+    /// `toString` of an object. strings are themselves, enum constants their name, throwables show
+    /// their message, and classes without a `toString` method are formatted like `java.lang.Object` does.
+    pub(super) fn object_to_string(&mut self, object: ObjectRef) -> Result<String, RuntimeError> {
+        if let Some(value) = self.heap.string_value(object) {
+            return Ok(String::from(value));
+        }
+
+        let class_name = self.heap.get(object).map(|object| object.class_name.clone()).unwrap_or_default();
+        if let Some((class, slot)) = self.method_target(&class_name, "toString", "()Ljava/lang/String;") {
+            return Ok(match self.run_method(class, slot, vec![LocalVariable::Reference(object)])? {
+                Some(StackValue::Reference(value)) => self.heap.string_value(value).map(String::from).unwrap_or_default(),
+                _ => String::from("null")
+            });
+        }
+
+        if self.is_subclass_of(&class_name, ENUM) {
+            return Ok(match self.heap.get(object).and_then(|object| object.fields.get("name")) {
+                Some(StackValue::Reference(name)) => self.heap.string_value(*name).map(String::from).unwrap_or_default(),
+                _ => String::from("null")
+            });
+        }
+        if self.is_subclass_of(&class_name, "java/lang/Throwable") {
+            return Ok(String::from(self.format_stack_trace(object).lines().next().unwrap_or_default()));
+        }
+        Ok(format!("{}@{:x}", class_name.replace('/', "."), self.identity_hash_code(object)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn concat(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> String {
+        match rt.invoke_static("Concat", name, descriptor, arguments) {
+            Ok(Some(StackValue::Reference(value))) => String::from(rt.heap().string_value(value).unwrap()),
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn it_concatenates_following_the_recipe() {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Concat.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/ConcatPoint.class")).unwrap().1);
+
+        let name = LocalVariable::Reference(rt.heap.intern("World"));
+        assert_eq!(concat(&mut rt, "greet", "(Ljava/lang/String;)Ljava/lang/String;", vec![name]), "Hello, World!");
+        assert_eq!(concat(&mut rt, "greet", "(Ljava/lang/String;)Ljava/lang/String;", vec![LocalVariable::Null]), "Hello, null!");
+        assert_eq!(concat(&mut rt, "nothing", "()Ljava/lang/String;", vec![]), "value: null");
+        assert_eq!(concat(&mut rt, "primitives", "(IJCZBS)Ljava/lang/String;", vec![
+            LocalVariable::Integer(-3), LocalVariable::Long(1 << 40), LocalVariable::Integer(0x41), LocalVariable::Integer(1),
+            LocalVariable::Integer(-8), LocalVariable::Integer(300),
+        ]), "-3/1099511627776/A/true/-8/300");
+        // ConcatPoint.toString concatenates, too
+        assert_eq!(concat(&mut rt, "point", "(I)Ljava/lang/String;", vec![LocalVariable::Integer(2)]), "point (2, 3)");
+        // the tags in the literal text are passed as constants
+        assert_eq!(concat(&mut rt, "tagged", "(I)Ljava/lang/String;", vec![LocalVariable::Integer(7)]), "\u{1}7\u{2}");
+    }
+}
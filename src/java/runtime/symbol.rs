@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+/// an interned name or descriptor. symbols of the same table compare and hash as integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn id(self) -> u32 {
+        self.0
+    }
+}
+
+/// interns the class names, method names and descriptors of a runtime.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    ids: HashMap<String, Symbol>,
+    names: Vec<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// the symbol of `name`, adding it to the table if it is new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.ids.get(name) {
+            return symbol;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(String::from(name));
+        self.ids.insert(String::from(name), symbol);
+        symbol
+    }
+
+    /// the symbol of `name`, if it was interned before.
+    pub fn lookup(&self, name: &str) -> Option<Symbol> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_are_interned_once() {
+        let mut symbols = SymbolTable::new();
+        let main = symbols.intern("main");
+        let descriptor = symbols.intern("([Ljava/lang/String;)V");
+
+        assert_eq!(symbols.intern("main"), main);
+        assert_ne!(main, descriptor);
+        assert_eq!(symbols.lookup("([Ljava/lang/String;)V"), Some(descriptor));
+        assert_eq!(symbols.lookup("run"), None);
+        assert_eq!(symbols.name(main), "main");
+        assert_eq!(symbols.len(), 2);
+    }
+}
@@ -0,0 +1,169 @@
+use java::runtime::{ActiveFrame, FrameDump, FramePool, LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+
+pub(super) const THREAD: &str = "java/lang/Thread";
+
+/// the id and name the thread running `main` and the embedding calls has on the jvm.
+const MAIN_THREAD: (u64, &str) = (1, "main");
+
+/// the number of methods a thread runs at once before calls throw a `StackOverflowError`. the
+/// interpreters of an optimized build nest deep enough into the 8 MiB stack of the main thread.
+pub const DEFAULT_MAX_STACK_DEPTH: usize = 512;
+
+/// the state of a thread executing bytecode: its frames, the frames an exception unwound for the
+/// crash report, and the buffers its frames are allocated from.
+///
+/// the runtime runs its bytecode on a single thread, so it owns one of them. everything which
+/// belongs to the program as a whole, like classes, the heap and statics, stays in the runtime.
+pub struct JavaThread<'a> {
+    id: u64,
+    name: String,
+    /// the methods running on the thread, the innermost last.
+    pub(super) frames: Vec<ActiveFrame<'a>>,
+    /// the frames the last uncaught exception unwound, innermost first.
+    pub(super) unwound_frames: Vec<FrameDump>,
+    pub(super) frame_pool: FramePool,
+    /// the `java.lang.Thread` of the thread, created when the program first asks for it.
+    pub(super) object: Option<ObjectRef>,
+    pub(super) uncaught_exception_handler: Option<ObjectRef>,
+    /// the references among the arguments of the running builtins.
+    pub(super) arguments: Vec<ObjectRef>,
+}
+
+impl<'a> JavaThread<'a> {
+    pub fn new(id: u64, name: &str) -> JavaThread<'a> {
+        JavaThread { id, name: String::from(name), frames: Vec::new(), unwound_frames: Vec::new(), frame_pool: FramePool::new(), object: None, uncaught_exception_handler: None, arguments: Vec::new() }
+    }
+
+    /// the thread `main` runs on.
+    pub fn main() -> JavaThread<'a> {
+        JavaThread::new(MAIN_THREAD.0, MAIN_THREAD.1)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the number of methods running on the thread.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// a thread is alive while it runs a method.
+    pub fn is_alive(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    /// the handler `Thread.setUncaughtExceptionHandler` installed, which takes the exceptions
+    /// ending the thread before the default handler.
+    pub fn uncaught_exception_handler(&self) -> Option<ObjectRef> {
+        self.uncaught_exception_handler
+    }
+
+    /// the objects the thread keeps alive besides its frames.
+    pub(super) fn references(&self) -> impl Iterator<Item=ObjectRef> + '_ {
+        self.object.into_iter().chain(self.uncaught_exception_handler).chain(self.arguments.iter().cloned())
+    }
+
+    /// forgets the frames of the last uncaught exception, before a new invocation starts.
+    pub(super) fn start_invocation(&mut self) {
+        self.unwound_frames.clear();
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// the thread the runtime executes bytecode on.
+    pub fn current_thread(&self) -> &JavaThread<'a> {
+        &self.thread
+    }
+
+    /// the handler `Thread.setDefaultUncaughtExceptionHandler` installed for all threads.
+    pub fn default_uncaught_exception_handler(&self) -> Option<ObjectRef> {
+        self.default_uncaught_exception_handler
+    }
+
+    /// the `java.lang.Thread` of the current thread.
+    pub(super) fn thread_object(&mut self) -> ObjectRef {
+        match self.thread.object {
+            Some(object) => object,
+            None => {
+                let object = self.allocate(THREAD, ObjectData::Instance);
+                self.thread.object = Some(object);
+                object
+            }
+        }
+    }
+
+    /// implements the methods of `java.lang.Thread` the single thread of the runtime supports: its
+    /// name and id and the handlers of uncaught exceptions.
+    /// returns `None` for other methods.
+    pub(super) fn invoke_thread(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let handler = match arguments.last() {
+            Some(LocalVariable::Reference(handler)) => Some(*handler),
+            _ => None
+        };
+        match (method_name, descriptor) {
+            ("currentThread", "()Ljava/lang/Thread;") => Some(Ok(Some(StackValue::Reference(self.thread_object())))),
+            ("getName", "()Ljava/lang/String;") => {
+                let name = self.thread.name.clone();
+                Some(Ok(Some(StackValue::Reference(self.intern(&name)))))
+            }
+            ("getId", "()J") => Some(Ok(Some(StackValue::Long(self.thread.id as i64)))),
+            ("setUncaughtExceptionHandler", "(Ljava/lang/Thread$UncaughtExceptionHandler;)V") => {
+                self.thread.uncaught_exception_handler = handler;
+                Some(Ok(None))
+            }
+            ("getUncaughtExceptionHandler", "()Ljava/lang/Thread$UncaughtExceptionHandler;") => {
+                Some(Ok(Some(self.thread.uncaught_exception_handler.map_or(StackValue::Null, StackValue::Reference))))
+            }
+            ("setDefaultUncaughtExceptionHandler", "(Ljava/lang/Thread$UncaughtExceptionHandler;)V") => {
+                self.default_uncaught_exception_handler = handler;
+                Some(Ok(None))
+            }
+            ("getDefaultUncaughtExceptionHandler", "()Ljava/lang/Thread$UncaughtExceptionHandler;") => {
+                Some(Ok(Some(self.default_uncaught_exception_handler.map_or(StackValue::Null, StackValue::Reference))))
+            }
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::{LocalVariable, RuntimeError, StackValue};
+    use std::thread;
+    use super::*;
+
+    #[test]
+    fn the_main_thread_has_no_frames_between_invocations() {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/Loop.class")).unwrap().1);
+        assert_eq!((rt.current_thread().id(), rt.current_thread().name()), (1, "main"));
+        assert_eq!(rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(3)]).unwrap(), Some(StackValue::Integer(3)));
+        assert!(!rt.current_thread().is_alive());
+        assert_eq!(rt.current_thread().depth(), 0);
+    }
+
+    #[test]
+    fn calls_beyond_the_maximum_depth_throw_a_stack_overflow_error() {
+        let class = || read_class_file(include_bytes!("../../../sample/VmErrors.class")).unwrap().1;
+        let mut rt = Runtime::builder().max_stack_depth(16).build(class());
+        // the innermost call catches the error
+        assert_eq!(rt.invoke_static("VmErrors", "recurse", "(I)I", vec![LocalVariable::Integer(0)]).unwrap(), Some(StackValue::Integer(15)));
+        assert_eq!(rt.current_thread().depth(), 0);
+
+        // the frames of unoptimized builds need more than the 2 MiB of a test thread
+        let overflowed = thread::Builder::new().stack_size(256 << 20).spawn(move || {
+            let mut rt = Runtime::builder().build(class());
+            let result = match rt.invoke_static("VmErrors", "unbounded", "(I)I", vec![LocalVariable::Integer(0)]) {
+                Err(RuntimeError::Exception { class_name, .. }) => Some(class_name),
+                _ => None
+            };
+            (result, rt.current_thread().is_alive())
+        }).unwrap().join().unwrap();
+        assert_eq!(overflowed, (Some(String::from("java/lang/StackOverflowError")), false));
+    }
+}
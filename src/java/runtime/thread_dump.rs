@@ -0,0 +1,194 @@
+use java::runtime::{LocalVariable, Runtime, StackFrame, StackTraceElement};
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const ACC_STATIC: u16 = 0x0008;
+const ACC_SYNCHRONIZED: u16 = 0x0020;
+
+/// a cloneable flag to request a thread dump from another thread or a signal handler.
+///
+/// the runtime looks at the flag at the next safepoint and prints the dump to its stdout like
+/// `jstack` would show it.
+#[derive(Debug, Clone, Default)]
+pub struct ThreadDumpTrigger {
+    requested: Arc<AtomicBool>,
+}
+
+impl ThreadDumpTrigger {
+    pub fn new() -> ThreadDumpTrigger {
+        ThreadDumpTrigger { requested: Arc::new(AtomicBool::new(false)) }
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst)
+    }
+
+    /// true while a request is pending, without taking it.
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    /// true once for every request.
+    pub fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// the raw flag, for `signal_hook::flag::register`.
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.requested.clone()
+    }
+}
+
+/// a frame of a thread and the monitors it holds, like `<@3> (a Counter)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadFrame {
+    pub element: StackTraceElement,
+    pub locked: Vec<String>,
+}
+
+/// a live java thread with its stack, innermost frame first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    pub name: String,
+    pub id: u64,
+    pub frames: Vec<ThreadFrame>,
+}
+
+/// the java threads of a runtime. the vm runs bytecode on a single thread which is live while it
+/// has frames, and its monitors are the ones of the synchronized methods on the stack, since
+/// nothing can contend for them or wait.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadDump {
+    pub threads: Vec<ThreadInfo>,
+}
+
+/// like the dumps of the hotspot vm:
+///
+/// ```text
+/// Full thread dump rjvm:
+///
+/// "main" #1 prio=5 runnable
+///    java.lang.Thread.State: RUNNABLE
+///         at Monitors.add(Monitors.java:11)
+///         - locked <@0> (a Monitors)
+/// ```
+impl fmt::Display for ThreadDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Full thread dump rjvm:")?;
+        for thread in self.threads.iter() {
+            writeln!(f)?;
+            writeln!(f, "\"{}\" #{} prio=5 runnable", thread.name, thread.id)?;
+            writeln!(f, "   java.lang.Thread.State: RUNNABLE")?;
+            for frame in thread.frames.iter() {
+                writeln!(f, "\tat {}", frame.element)?;
+                for monitor in frame.locked.iter() {
+                    writeln!(f, "\t- locked {}", monitor)?;
+                }
+            }
+        }
+        writeln!(f)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// a trigger to request a thread dump while the runtime is executing bytecode.
+    pub fn thread_dump_trigger(&self) -> ThreadDumpTrigger {
+        self.thread_dump_trigger.clone()
+    }
+
+    /// the live java threads. outside of bytecode, like between two calls of `invoke_static`,
+    /// there are none; natives get to see the thread which called them.
+    pub fn thread_dump(&self) -> ThreadDump {
+        self.thread_dump_at(None)
+    }
+
+    /// the thread dump with the values of the innermost frame from `current`, which the runtime
+    /// keeps outside of `frames` while it runs.
+    fn thread_dump_at(&self, current: Option<&StackFrame>) -> ThreadDump {
+        if !self.thread.is_alive() {
+            return ThreadDump { threads: Vec::new() };
+        }
+        let depth = self.thread.frames.len();
+        let frames = self.thread.frames.iter().enumerate().rev().zip(self.stack_trace()).map(|((index, frame), element)| {
+            let class = self.classes.class(frame.class);
+            let access_flags = class.methods.iter()
+                .find(|method| method.name == frame.method_name && method.descriptor == frame.descriptor)
+                .map_or(0, |method| method.access_flags);
+            let values = if index + 1 == depth { current } else { frame.saved.as_ref() };
+
+            // a synchronized method holds the monitor of its class or of its receiver
+            let class_name = class.get_class_name().replace('/', ".");
+            let locked = match (access_flags & ACC_SYNCHRONIZED != 0, access_flags & ACC_STATIC != 0) {
+                (false, _) => None,
+                (true, true) => Some(format!("<{}.class> (a java.lang.Class)", class_name)),
+                (true, false) => Some(match values.and_then(|values| values.local_variables.first()) {
+                    Some(LocalVariable::Reference(reference)) => {
+                        let receiver = self.heap.get(*reference).map_or(class_name, |object| object.class_name.replace('/', "."));
+                        format!("<@{}> (a {})", reference.0, receiver)
+                    },
+                    _ => format!("(a {})", class_name)
+                })
+            };
+            ThreadFrame { element, locked: locked.into_iter().collect() }
+        }).collect();
+
+        ThreadDump { threads: vec![ThreadInfo { name: String::from(self.thread.name()), id: self.thread.id(), frames }] }
+    }
+
+    /// prints a thread dump to the stdout of the runtime if one was requested.
+    pub(super) fn check_thread_dump(&mut self, current: &StackFrame) {
+        if !self.thread_dump_trigger.take() {
+            return;
+        }
+        let dump = self.thread_dump_at(Some(current)).to_string();
+        // like other diagnostics, a dump which cannot be written does not stop the program
+        let _ = self.stdout.write_all(dump.as_bytes()).and_then(|_| self.stdout.flush());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use captured::Captured;
+    use java::runtime::{InterpreterMode, StackValue};
+    use super::*;
+
+    fn runtime(bytes: &'static [u8], mode: InterpreterMode, out: &Captured) -> Runtime<'static> {
+        Runtime::builder().interpreter(mode).stdout(out.output())
+            .build(read_class_file(bytes).unwrap().1)
+    }
+
+    #[test]
+    fn dumps_show_the_stack_and_the_monitors_held() {
+        let out = Captured::default();
+        let mut rt = runtime(include_bytes!("../../../sample/Monitors.class"), InterpreterMode::Checked, &out);
+        let trigger = rt.thread_dump_trigger();
+        rt.register_native("Monitors", "pause", "()V", move |_, _| {
+            trigger.request();
+            Ok(None)
+        });
+        assert_eq!(rt.invoke_static("Monitors", "run", "()I", vec![]).unwrap(), Some(StackValue::Integer(4)));
+
+        let dump = out.text();
+        assert_eq!(dump, "Full thread dump rjvm:\n\n\
+                          \"main\" #1 prio=5 runnable\n   java.lang.Thread.State: RUNNABLE\n\
+                          \tat Monitors.add(Monitors.java:11)\n\t- locked <@0> (a Monitors)\n\
+                          \tat Monitors.twice(Monitors.java:15)\n\t- locked <Monitors.class> (a java.lang.Class)\n\
+                          \tat Monitors.run(Monitors.java:19)\n\n");
+        assert!(rt.thread_dump().threads.is_empty());
+    }
+
+    #[test]
+    fn the_fast_interpreter_dumps_at_back_edges() {
+        let out = Captured::default();
+        let mut rt = runtime(include_bytes!("../../../sample/Loop.class"), InterpreterMode::Fast, &out);
+        rt.thread_dump_trigger().request();
+        rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(10)]).unwrap();
+
+        let dump = out.text();
+        assert!(dump.contains("\tat Loop.sum(Loop.java:"), "{}", dump);
+        assert!(!rt.thread_dump_trigger().take());
+    }
+}
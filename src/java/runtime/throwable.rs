@@ -0,0 +1,354 @@
+use java::class_file::Method;
+use java::runtime::builtin;
+use super::buffers::{BUFFER, BYTE_BUFFER, BYTE_ORDER, DIRECT_BYTE_BUFFER, FILE_CHANNEL, HEAP_BYTE_BUFFER, MAPPED_BYTE_BUFFER, RANDOM_ACCESS_FILE, UNSAFE};
+use super::charsets::CHARSET;
+use super::cleaner::{CLEANABLE, CLEANER, PHANTOM_CLEANABLE};
+use super::console::PRINT_STREAM;
+use super::enums::ENUM;
+use super::method_handles::{LOOKUP, METHOD_HANDLE, METHOD_HANDLES, METHOD_TYPE};
+use super::process::{INPUT_STREAM, JAVA_RUNTIME, OUTPUT_STREAM, PROCESS, PROCESS_BUILDER, PROCESS_ENVIRONMENT};
+use super::random::{RANDOM, SECURE_RANDOM};
+use super::thread::THREAD;
+use super::time::{JDK_VM, SYSTEM, TIME_ZONE};
+use super::var_handles::VAR_HANDLE;
+use java::runtime::{GcReason, LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackTraceElement, StackValue};
+
+const THROWABLE: &str = "java/lang/Throwable";
+
+impl<'a> Runtime<'a> {
+    /// the current java stack, innermost frame first, as `Throwable.fillInStackTrace` records it.
+    pub fn stack_trace(&self) -> Vec<StackTraceElement> {
+        self.thread.frames.iter().rev().map(|frame| {
+            let line = self.classes.class(frame.class).methods.iter()
+                .find(|method| method.name == frame.method_name && method.descriptor == frame.descriptor)
+                .and_then(|method| method.get_code())
+                .and_then(|code| code.line_number_at(frame.pc));
+
+            StackTraceElement {
+                class_name: self.classes.class(frame.class).get_class_name().replace('/', "."),
+                method_name: String::from(&*frame.method_name),
+                file_name: self.classes.class(frame.class).get_source_file().map(String::from),
+                line_number: line,
+            }
+        }).collect()
+    }
+
+    /// the super class of a loaded or builtin class.
+    pub fn superclass_of(&self, class_name: &str) -> Option<String> {
+        match self.classes.get(class_name) {
+            Some(class) => class.get_super_class_name().map(String::from),
+            None if self.lambda_classes.contains_key(class_name) => Some(String::from("java/lang/Object")),
+            // arrays only inherit the methods of Object
+            None if class_name.starts_with('[') => Some(String::from("java/lang/Object")),
+            None => builtin::superclass(class_name).map(String::from)
+        }
+    }
+
+    /// true if `class_name` is `parent` or one of its subclasses.
+    pub fn is_subclass_of(&self, class_name: &str, parent: &str) -> bool {
+        let mut current = Some(String::from(class_name));
+        while let Some(name) = current {
+            if name == parent {
+                return true;
+            }
+            current = self.superclass_of(&name);
+        }
+
+        false
+    }
+
+    /// true if `class_name` or one of its super classes implements `interface`, directly or through
+    /// a super interface.
+    pub fn implements(&self, class_name: &str, interface: &str) -> bool {
+        let mut current = Some(String::from(class_name));
+        while let Some(name) = current {
+            let class = match self.classes.get(&name) {
+                Some(class) => class,
+                None => return self.lambda_classes.get(&name)
+                    .is_some_and(|lambda| lambda.interface == interface || self.implements(&lambda.interface, interface))
+            };
+            let direct = class.interfaces.iter().filter_map(|&index| class.get_class_name_at(index));
+            for implemented in direct {
+                if implemented == interface || self.implements(implemented, interface) {
+                    return true;
+                }
+            }
+            current = self.superclass_of(&name);
+        }
+
+        false
+    }
+
+    /// true if a value of class `class_name` can be stored in a variable of type `target`,
+    /// both given as internal names like `java/lang/String` or `[I`.
+    pub fn is_assignable(&self, class_name: &str, target: &str) -> bool {
+        fn descriptor(name: &str) -> String {
+            if name.starts_with('[') { String::from(name) } else { format!("L{};", name) }
+        }
+
+        self.is_assignable_descriptor(&descriptor(class_name), &descriptor(target))
+    }
+
+    fn is_assignable_descriptor(&self, value: &str, target: &str) -> bool {
+        if value == target {
+            return true;
+        }
+
+        match (value.as_bytes()[0], target.as_bytes()[0]) {
+            (b'L', b'L') => {
+                let (value, target) = (&value[1..value.len() - 1], &target[1..target.len() - 1]);
+                target == "java/lang/Object" || self.is_subclass_of(value, target) || self.implements(value, target)
+            }
+            // arrays are objects which implement these two interfaces
+            (b'[', b'L') => target == "Ljava/lang/Object;" || target == "Ljava/lang/Cloneable;" || target == "Ljava/io/Serializable;",
+            (b'[', b'[') => self.is_assignable_descriptor(&value[1..], &target[1..]),
+            _ => false
+        }
+    }
+
+    /// formats an exception like `Throwable.printStackTrace` does. a cause seen before in the chain
+    /// is printed as `[CIRCULAR REFERENCE: ...]` and ends it.
+    pub fn format_stack_trace(&self, exception: ObjectRef) -> String {
+        let mut out = String::new();
+        let mut seen = vec![];
+        self.write_stack_trace(exception, &mut seen, &mut out);
+        out
+    }
+
+    fn write_stack_trace(&self, exception: ObjectRef, seen: &mut Vec<ObjectRef>, out: &mut String) {
+        let object = match self.heap.get(exception) {
+            Some(object) => object,
+            None => return
+        };
+        seen.push(exception);
+
+        out.push_str(&self.describe_exception(exception));
+        out.push('\n');
+
+        if let ObjectData::Throwable(ref trace) = object.data {
+            for element in trace {
+                out.push_str(&format!("\tat {}\n", element));
+            }
+        }
+        if let Some(StackValue::Reference(cause)) = object.fields.get("cause") {
+            // a throwable whose cause is itself has no cause
+            if *cause == exception {
+                return;
+            }
+            out.push_str("Caused by: ");
+            if seen.contains(cause) {
+                out.push_str(&format!("[CIRCULAR REFERENCE: {}]\n", self.describe_exception(*cause)));
+            } else {
+                self.write_stack_trace(*cause, seen, out);
+            }
+        }
+    }
+
+    /// the first line of a stack trace, the class name and the message if there is one.
+    fn describe_exception(&self, exception: ObjectRef) -> String {
+        let object = match self.heap.get(exception) {
+            Some(object) => object,
+            None => return String::new()
+        };
+        let mut out = object.class_name.replace('/', ".");
+        if let Some(StackValue::Reference(message)) = object.fields.get("detailMessage") {
+            if let Some(message) = self.heap.string_value(*message) {
+                out.push_str(": ");
+                out.push_str(message);
+            }
+        }
+        out
+    }
+
+    /// creates a new exception of `class_name` inside the vm and returns it as error,
+    /// ready to be unwound to the next matching handler.
+    pub(super) fn throw(&mut self, method: &Method, class_name: &str, message: Option<&str>) -> RuntimeError {
+        let exception = self.allocate(class_name, ObjectData::Instance);
+        let message = message.map(|message| self.intern(message));
+        self.throwable_init(exception, message);
+        self.exception_thrown(method, exception)
+    }
+
+    /// like `throw`, from a builtin method on behalf of the bytecode method which called it.
+    pub(super) fn throw_in_caller(&mut self, class_name: &str, message: Option<&str>) -> RuntimeError {
+        match self.caller() {
+            Ok((caller, slot)) => self.throw(&caller.methods[slot], class_name, message),
+            Err(err) => err
+        }
+    }
+
+    /// notifies the hooks about a thrown exception and wraps it into an error.
+    pub(super) fn exception_thrown(&mut self, method: &Method, exception: ObjectRef) -> RuntimeError {
+        let class_name = self.heap.get(exception).map(|object| object.class_name.clone()).unwrap_or_default();
+        let (current_class, pc) = match self.thread.frames.last() {
+            Some(frame) => (String::from(self.classes.class(frame.class).get_class_name()), frame.pc),
+            None => (String::new(), 0)
+        };
+
+        for hook in self.hooks.iter_mut() {
+            hook.on_exception_thrown(&class_name, &current_class, method, pc);
+        }
+
+        RuntimeError::Exception { exception, class_name }
+    }
+
+    /// implements the methods of the builtin classes.
+    /// returns `None` if the builtin class does not have the method. the arguments are no longer on
+    /// the stack of the caller, so they are roots of the collections while the builtin runs.
+    pub(super) fn invoke_builtin(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let rooted = self.thread.arguments.len();
+        self.thread.arguments.extend(arguments.iter().filter_map(|argument| match argument {
+            LocalVariable::Reference(reference) => Some(*reference),
+            _ => None
+        }));
+        let result = self.dispatch_builtin(class_name, method_name, descriptor, arguments);
+        self.thread.arguments.truncate(rooted);
+        result
+    }
+
+    fn dispatch_builtin(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let this = match arguments.first() {
+            Some(LocalVariable::Reference(this)) => Some(*this),
+            _ => None
+        };
+        if class_name == ENUM {
+            return self.invoke_enum(method_name, descriptor, arguments);
+        }
+        if [METHOD_HANDLES, LOOKUP, METHOD_HANDLE, METHOD_TYPE].contains(&class_name) {
+            return self.invoke_method_handles(class_name, method_name, descriptor, arguments);
+        }
+        if class_name == VAR_HANDLE {
+            return self.invoke_var_handle(method_name, descriptor, arguments);
+        }
+        if class_name == THREAD {
+            return self.invoke_thread(method_name, descriptor, arguments);
+        }
+        if class_name == PRINT_STREAM {
+            return self.invoke_print_stream(method_name, descriptor, arguments);
+        }
+        if class_name == CHARSET {
+            return self.invoke_charset(method_name, descriptor, arguments);
+        }
+        if class_name == "java/lang/String" {
+            if let Some(result) = self.invoke_string_bytes(method_name, descriptor, arguments) {
+                return Some(result);
+            }
+        }
+        if [PROCESS_BUILDER, PROCESS, PROCESS_ENVIRONMENT, JAVA_RUNTIME, INPUT_STREAM, OUTPUT_STREAM].contains(&class_name) {
+            return self.invoke_process(class_name, method_name, descriptor, arguments);
+        }
+        if class_name == RANDOM || class_name == SECURE_RANDOM {
+            return self.invoke_random(class_name, method_name, descriptor, arguments);
+        }
+        if [SYSTEM, JDK_VM, TIME_ZONE].contains(&class_name) {
+            if let Some(result) = self.invoke_time(class_name, method_name, descriptor, arguments) {
+                return Some(result);
+            }
+        }
+        if [CLEANER, CLEANABLE, PHANTOM_CLEANABLE].contains(&class_name) {
+            return self.invoke_cleaner(class_name, method_name, descriptor, arguments);
+        }
+        if [BUFFER, BYTE_BUFFER, MAPPED_BYTE_BUFFER, DIRECT_BYTE_BUFFER, HEAP_BYTE_BUFFER, BYTE_ORDER, FILE_CHANNEL, RANDOM_ACCESS_FILE, UNSAFE].contains(&class_name) {
+            return self.invoke_buffer(class_name, method_name, descriptor, arguments);
+        }
+        let throwable = self.is_subclass_of(class_name, THROWABLE);
+
+        match (method_name, descriptor, this) {
+            ("forName", "(Ljava/lang/String;)Ljava/lang/Class;", Some(name)) if class_name == "java/lang/Class" => Some(self.class_for_name(name)),
+            ("<init>", "()V", Some(this)) => {
+                if throwable {
+                    self.throwable_init(this, None);
+                }
+                Some(Ok(None))
+            }
+            ("<init>", "(Ljava/lang/String;)V", Some(this)) if throwable => {
+                let message = match arguments.get(1) {
+                    Some(LocalVariable::Reference(message)) => Some(*message),
+                    _ => None
+                };
+                self.throwable_init(this, message);
+                Some(Ok(None))
+            }
+            ("valueOf", "(Ljava/lang/Object;)Ljava/lang/String;", _) if class_name == "java/lang/String" => Some(match this {
+                Some(string) if self.heap.string_value(string).is_some() => Ok(Some(StackValue::Reference(string))),
+                Some(object) => self.object_to_string(object).map(|value| Some(StackValue::Reference(self.allocate("java/lang/String", ObjectData::String(value.into()))))),
+                None => Ok(Some(StackValue::Reference(self.intern("null"))))
+            }),
+            ("clone", "()Ljava/lang/Object;", Some(this)) if class_name == "java/lang/Object" => Some(self.clone_object(this)),
+            // the caller is suspended, so its frame is among the roots
+            ("gc", "()V", _) if class_name == SYSTEM => {
+                self.collect_garbage(GcReason::Explicit, None);
+                Some(Ok(None))
+            }
+            ("exit", "(I)V", _) if class_name == SYSTEM => match arguments.first() {
+                Some(LocalVariable::Integer(status)) => Some(Err(RuntimeError::Exit { status: *status as i32 })),
+                _ => None
+            },
+            ("runFinalization", "()V", _) if class_name == SYSTEM => Some(self.run_cleanups().map(|()| None)),
+            ("desiredAssertionStatus", "()Z", Some(this)) if class_name == "java/lang/Class" => Some(self.desired_assertion_status(this)),
+            // the detail message is the argument converted to a string, objects other than strings have none
+            ("<init>", _, Some(this)) if class_name == "java/lang/AssertionError" => {
+                let message = match (descriptor, arguments.get(1)) {
+                    ("(Ljava/lang/Object;)V", Some(LocalVariable::Reference(message))) if self.heap.string_value(*message).is_some() => Some(*message),
+                    ("(I)V", Some(LocalVariable::Integer(value))) => Some(self.intern(&value.to_string())),
+                    ("(Z)V", Some(LocalVariable::Integer(value))) => Some(self.intern(if *value != 0 { "true" } else { "false" })),
+                    ("(C)V", Some(LocalVariable::Integer(value))) => Some(self.intern(&std::char::from_u32(*value as u32).unwrap_or('\u{fffd}').to_string())),
+                    ("(Ljava/lang/Object;)V", _) | ("(I)V", _) | ("(Z)V", _) | ("(C)V", _) => None,
+                    _ => return None
+                };
+                self.throwable_init(this, message);
+                Some(Ok(None))
+            }
+            ("getMessage", "()Ljava/lang/String;", Some(this)) if throwable => {
+                let message = self.heap.get(this).and_then(|object| object.fields.get("detailMessage").cloned());
+                Some(Ok(Some(message.unwrap_or(StackValue::Null))))
+            }
+            ("fillInStackTrace", "()Ljava/lang/Throwable;", Some(this)) if throwable => {
+                let trace = self.stack_trace();
+                if let Some(object) = self.heap.get_mut(this) {
+                    object.data = ObjectData::Throwable(trace);
+                }
+                Some(Ok(Some(StackValue::Reference(this))))
+            }
+            ("getStackTrace", "()[Ljava/lang/StackTraceElement;", Some(this)) if throwable => {
+                let trace = match self.heap.get(this).map(|object| &object.data) {
+                    Some(ObjectData::Throwable(trace)) => trace.clone(),
+                    _ => Vec::new()
+                };
+                let elements = trace.iter().map(|element| StackValue::Reference(self.stack_trace_element(element))).collect();
+                Some(Ok(Some(StackValue::Reference(self.allocate("[Ljava/lang/StackTraceElement;", ObjectData::Array(elements))))))
+            }
+            _ => None
+        }
+    }
+
+    /// stores message and stack trace of a new throwable.
+    /// the frames of the constructors of the exception itself are not part of the stack trace.
+    pub(super) fn throwable_init(&mut self, this: ObjectRef, message: Option<ObjectRef>) {
+        let class_name = self.heap.get(this).map(|object| object.class_name.clone()).unwrap_or_default();
+        let constructors = self.thread.frames.iter().rev()
+            .take_while(|frame| frame.method_name == "<init>" && self.is_subclass_of(&class_name, self.classes.class(frame.class).get_class_name()))
+            .count();
+        let trace = self.stack_trace().split_off(constructors);
+
+        if let Some(object) = self.heap.get_mut(this) {
+            object.fields.insert(String::from("detailMessage"), message.map(StackValue::Reference).unwrap_or(StackValue::Null));
+            object.data = ObjectData::Throwable(trace);
+        }
+    }
+
+    fn stack_trace_element(&mut self, element: &StackTraceElement) -> ObjectRef {
+        let declaring_class = self.intern(&element.class_name);
+        let method_name = self.intern(&element.method_name);
+        let file_name = element.file_name.as_ref().map(|file| StackValue::Reference(self.intern(file))).unwrap_or(StackValue::Null);
+        let line_number = element.line_number.map(|line| line as i64).unwrap_or(-1);
+
+        let reference = self.allocate("java/lang/StackTraceElement", ObjectData::Instance);
+        let object = self.heap.get_mut(reference).unwrap();
+        object.fields.insert(String::from("declaringClass"), StackValue::Reference(declaring_class));
+        object.fields.insert(String::from("methodName"), StackValue::Reference(method_name));
+        object.fields.insert(String::from("fileName"), file_name);
+        object.fields.insert(String::from("lineNumber"), StackValue::Integer(line_number));
+        reference
+    }
+}
@@ -0,0 +1,130 @@
+use java::charset::Charset;
+use java::runtime::{LocalVariable, NativeContext, Runtime, RuntimeError, StackValue};
+use std::fs;
+use std::path::Path;
+
+pub(super) const SYSTEM: &str = "java/lang/System";
+pub(super) const JDK_VM: &str = "jdk/internal/misc/VM";
+pub(super) const TIME_ZONE: &str = "java/util/TimeZone";
+
+/// how far from its offset `VM.getNanoTimeAdjustment` may be asked for the time, like on hotspot.
+const MAX_ADJUSTMENT_SECONDS: i64 = 1 << 32;
+
+/// `VM.getNanoTimeAdjustment`: the nanoseconds from `offset` seconds after the epoch to `now`, or -1
+/// if `now` is too far away from it, which makes the class library ask again with another offset.
+fn nano_time_adjustment(now: i64, offset: i64) -> i64 {
+    let seconds = now.div_euclid(1_000_000_000);
+    match seconds.checked_sub(offset) {
+        Some(difference) if difference.abs() < MAX_ADJUSTMENT_SECONDS => difference * 1_000_000_000 + now.rem_euclid(1_000_000_000),
+        _ => -1
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// implements the natives of the class library which read the clocks and the time zone:
+    /// `System.currentTimeMillis` and `nanoTime`, `VM.getNanoTimeAdjustment` behind `Instant.now`
+    /// and `TimeZone.getSystemTimeZoneID` behind `ZoneId.systemDefault`. the clocks come from the
+    /// `Environment`, so they follow the deterministic mode, are recorded and replayed and can be
+    /// replaced with `RuntimeBuilder::clock`.
+    /// returns `None` for other methods.
+    pub(super) fn invoke_time(&mut self, class_name: &str, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let value = match (class_name, method_name, descriptor) {
+            (SYSTEM, "currentTimeMillis", "()J") => StackValue::Long(self.environment.current_time_millis()),
+            (SYSTEM, "nanoTime", "()J") => StackValue::Long(self.environment.nano_time()),
+            (JDK_VM, "getNanoTimeAdjustment", "(J)J") => {
+                let offset = match arguments.first() {
+                    Some(LocalVariable::Long(offset)) => *offset,
+                    _ => return Some(Err(RuntimeError::StackType { expected: String::from("long") }))
+                };
+                StackValue::Long(nano_time_adjustment(self.environment.current_time_nanos(), offset))
+            }
+            (TIME_ZONE, "getSystemTimeZoneID", "(Ljava/lang/String;)Ljava/lang/String;") => {
+                let id = self.system_time_zone();
+                StackValue::Reference(self.intern(&id))
+            }
+            _ => return None
+        };
+        Some(Ok(Some(value)))
+    }
+
+    /// the id of the time zone of the host, found like the class library does on linux: from `TZ`,
+    /// then `/etc/timezone`, then the zone `/etc/localtime` links to. `UTC` in the deterministic mode
+    /// and if none of them is there or the capabilities hide them.
+    fn system_time_zone(&mut self) -> String {
+        if self.environment.is_deterministic() {
+            return String::from("UTC");
+        }
+        let mut context = NativeContext::new(&mut self.environment, &self.capabilities);
+        let mut id = context.env_var("TZ").ok().and_then(|id| id)
+            .map(|id| String::from(id.trim_start_matches(':')));
+        if id.is_none() {
+            id = context.read_text(Path::new("/etc/timezone"), Charset::Utf8).ok().map(|id| String::from(id.trim()));
+        }
+        let localtime = Path::new("/etc/localtime");
+        if id.is_none() && context.capabilities().can_read(localtime) {
+            id = fs::read_link(localtime).ok()
+                .and_then(|target| target.to_str().and_then(|target| target.split("zoneinfo/").nth(1)).map(String::from));
+        }
+        id.filter(|id| !id.is_empty())
+            .unwrap_or_else(|| String::from("UTC"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::RuntimeBuilder;
+    use std::time::Duration;
+    use super::*;
+
+    fn runtime(builder: RuntimeBuilder<'static>) -> Runtime<'static> {
+        let mut rt = builder.build(read_class_file(include_bytes!("../../../sample/Clocks.class")).unwrap().1);
+        // stand-ins for the classes of the jdk, which declare the natives like the real ones
+        rt.load_class(read_class_file(include_bytes!("../../../sample/classlib/jdk/internal/misc/VM.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/classlib/java/util/TimeZone.class")).unwrap().1);
+        rt
+    }
+
+    fn long(result: Result<Option<StackValue>, RuntimeError>) -> i64 {
+        match result {
+            Ok(Some(StackValue::Long(value))) => value,
+            other => panic!("expected a long, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn clocks_come_from_the_injected_clock() {
+        let mut rt = runtime(Runtime::builder().clock(|| Duration::new(1_700_000_000, 123_456_789)));
+        assert_eq!(long(rt.invoke_static("Clocks", "millis", "()J", vec![])), 1_700_000_000_123);
+        let adjustment = rt.invoke_static(JDK_VM, "getNanoTimeAdjustment", "(J)J", vec![LocalVariable::Long(1_699_999_000)]);
+        assert_eq!(long(adjustment), 1_000_123_456_789);
+        let adjustment = rt.invoke_static(JDK_VM, "getNanoTimeAdjustment", "(J)J", vec![LocalVariable::Long(0)]);
+        assert_eq!(long(adjustment), 1_700_000_000_123_456_789);
+        // too far away from the offset
+        let adjustment = rt.invoke_static(JDK_VM, "getNanoTimeAdjustment", "(J)J", vec![LocalVariable::Long(-3_000_000_000)]);
+        assert_eq!(long(adjustment), -1);
+    }
+
+    #[test]
+    fn the_deterministic_clock_starts_in_2000_and_in_utc() {
+        let mut rt = runtime(Runtime::builder().deterministic(1));
+        assert_eq!(long(rt.invoke_static("Clocks", "millis", "()J", vec![])), 946_684_800_001);
+        let first = long(rt.invoke_static("Clocks", "nanos", "()J", vec![]));
+        assert!(long(rt.invoke_static("Clocks", "nanos", "()J", vec![])) > first);
+
+        let java_home = rt.intern("/usr/lib/jvm");
+        match rt.invoke_static(TIME_ZONE, "getSystemTimeZoneID", "(Ljava/lang/String;)Ljava/lang/String;", vec![LocalVariable::Reference(java_home)]) {
+            Ok(Some(StackValue::Reference(id))) => assert_eq!(rt.heap.string_value(id), Some("UTC")),
+            other => panic!("expected a time zone, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn unknown_natives_throw() {
+        let mut rt = runtime(Runtime::builder());
+        match rt.invoke_static(JDK_VM, "initialize", "()V", vec![]) {
+            Err(RuntimeError::Exception { ref class_name, .. }) if class_name == "java/lang/UnsatisfiedLinkError" => (),
+            other => panic!("expected an UnsatisfiedLinkError, got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use java::class_file::{ClassFile, ConstantType, MethodDescriptor};
+use java::instructions::Instruction;
+use java::runtime::{ObjectRef, ObjectState, Runtime, StackFrame, StackValue, StepState};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// an instruction the interpreter executed and what it did to the operand stack.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// the number of java frames below the one of the instruction.
+    pub depth: usize,
+    pub class_name: String,
+    pub method_name: String,
+    pub descriptor: String,
+    pub pc: usize,
+    pub instruction: Instruction,
+    /// the values taken from the top of the operand stack, deepest first.
+    pub popped: Vec<StackValue>,
+    /// the values left on top of the operand stack in their place, deepest first. `dup` pops one
+    /// value and pushes two.
+    pub pushed: Vec<StackValue>,
+    /// the class of the exception the instruction threw.
+    pub exception: Option<String>,
+    /// the frames and the changes to the heap after the instruction, if the trace records them.
+    pub state: Option<StepState>,
+}
+
+/// the instructions a `Runtime` executed since `Runtime::start_trace`, in the order they started,
+/// so an invoke comes before the instructions of the method it calls.
+///
+/// while a trace is recorded every instruction runs on its own in the checked interpreter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionTrace {
+    steps: Vec<TraceStep>,
+    /// the live objects when the trace started.
+    pub(super) initial_heap: BTreeMap<ObjectRef, ObjectState>,
+    /// the objects as of the last step, `None` unless the trace records the state of the vm.
+    pub(super) objects: Option<BTreeMap<ObjectRef, ObjectState>>,
+}
+
+impl ExecutionTrace {
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+}
+
+/// the number of arguments of the method a call site of `class` calls.
+fn argument_count(class: &ClassFile, index: u16) -> Option<usize> {
+    let name_and_type_index = match class.get_constant(index)? {
+        ConstantType::MethodRef { name_and_type_index, .. } | ConstantType::InterfaceMethodRef { name_and_type_index, .. } |
+        ConstantType::InvokeDynamic { name_and_type_index, .. } => *name_and_type_index,
+        _ => return None
+    };
+    let (_, descriptor) = class.get_name_and_type(name_and_type_index)?;
+    MethodDescriptor::from_str(descriptor).ok().map(|descriptor| descriptor.arguments.len())
+}
+
+/// how many values `instruction` takes from the operand stack, where a long is a single value.
+/// `None` for the instructions whose count depends on the category of the values, like `dup2`.
+fn operand_count(class: &ClassFile, instruction: &Instruction) -> Option<usize> {
+    Some(match instruction {
+        Instruction::InvokeVirtual(index) | Instruction::InvokeSpecial(index) | Instruction::InvokeInterface((index, _, _)) =>
+            argument_count(class, *index)? + 1,
+        Instruction::InvokeStatic(index) | Instruction::InvokeDynamic((index, _)) => argument_count(class, *index)?,
+        Instruction::MultianeWArray((_, dimensions)) => usize::from(*dimensions),
+        _ => match instruction.opcode() {
+            0x00..=0x2d | 0x84 | 0xa7..=0xa9 | 0xb1 | 0xb2 | 0xbb | 0xc8 | 0xc9 => 0,
+            0x36..=0x4e | 0x57 | 0x59 | 0x74..=0x77 | 0x85..=0x93 | 0x99..=0x9e | 0xaa..=0xb0 | 0xb3 | 0xb4 | 0xbc..=0xc3 | 0xc6 | 0xc7 => 1,
+            0x2e..=0x35 | 0x5f..=0x73 | 0x78..=0x83 | 0x94..=0x98 | 0x9f..=0xa6 | 0xb5 => 2,
+            0x4f..=0x56 => 3,
+            _ => return None
+        }
+    })
+}
+
+/// how a value is written in a trace: ints as they are, longs with `L`, references by their
+/// heap index.
+fn format_value(value: &StackValue) -> String {
+    match value {
+        StackValue::None => String::from("-"),
+        StackValue::Null => String::from("null"),
+        StackValue::Integer(value) => value.to_string(),
+        StackValue::Long(value) => format!("{}L", value),
+        StackValue::Reference(reference) => format!("@{}", reference.0),
+    }
+}
+
+fn format_values(values: &[StackValue]) -> String {
+    values.iter().map(format_value).collect::<Vec<_>>().join(", ")
+}
+
+/// a line for each method the trace enters or returns to, then a line per instruction with its
+/// pc and stack effect, indented by the depth of its frame:
+///
+/// ```text
+/// Loop.sum(I)I
+///      0 IConst0                [] -> [0]
+/// ```
+impl fmt::Display for ExecutionTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut current = None;
+        for step in self.steps.iter() {
+            let indent = "  ".repeat(step.depth);
+            let method = (step.depth, &step.class_name, &step.method_name, &step.descriptor);
+            if current != Some(method) {
+                writeln!(f, "{}{}.{}{}", indent, step.class_name, step.method_name, step.descriptor)?;
+                current = Some(method);
+            }
+            let instruction = format!("{:?}", step.instruction).replace("(())", "");
+            write!(f, "{}{:>6} {:<22} [{}] -> [{}]", indent, step.pc, instruction, format_values(&step.popped), format_values(&step.pushed))?;
+            match step.exception {
+                Some(ref exception) => writeln!(f, " throws {}", exception)?,
+                None => writeln!(f)?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// records the instructions executed from now on, until `take_trace`. an ongoing trace is discarded.
+    pub fn start_trace(&mut self) {
+        self.trace = Some(ExecutionTrace::default());
+    }
+
+    /// stops recording and returns what was recorded, `None` if no trace was started.
+    pub fn take_trace(&mut self) -> Option<ExecutionTrace> {
+        self.trace.take()
+    }
+
+    /// adds a step for `instruction` before it runs. returns its index and the number of values it
+    /// pops for `end_trace_step`.
+    pub(super) fn begin_trace_step(&mut self, class: &ClassFile, method_name: &str, descriptor: &str, pc: usize, instruction: &Instruction) -> Option<(usize, Option<usize>)> {
+        let depth = self.thread.frames.len().saturating_sub(1);
+        let trace = self.trace.as_mut()?;
+        trace.steps.push(TraceStep {
+            depth,
+            class_name: String::from(class.get_class_name()),
+            method_name: String::from(method_name),
+            descriptor: String::from(descriptor),
+            pc,
+            instruction: instruction.clone(),
+            popped: Vec::new(),
+            pushed: Vec::new(),
+            exception: None,
+            state: None,
+        });
+        Some((trace.steps.len() - 1, operand_count(class, instruction)))
+    }
+
+    /// fills in the stack effect of the step `index`, from the operand stack before and after it ran.
+    /// without the number of values it pops, everything above the unchanged bottom of the stack counts.
+    pub(super) fn end_trace_step(&mut self, (index, pops): (usize, Option<usize>), before: &[StackValue], current: &StackFrame, exception: Option<&str>) {
+        let state = match self.trace {
+            Some(ExecutionTrace { objects: Some(_), .. }) => Some(self.step_state(current)),
+            _ => None
+        };
+        let after = &current.stack;
+        let step = match self.trace.as_mut().and_then(|trace| trace.steps.get_mut(index)) {
+            Some(step) => step,
+            None => return
+        };
+        let unchanged = before.iter().zip(after.iter()).take_while(|(before, after)| before == after).count();
+        let kept = pops.and_then(|pops| before.len().checked_sub(pops)).filter(|&kept| kept <= after.len()).unwrap_or(unchanged);
+        step.popped = before[kept..].to_vec();
+        step.pushed = if exception.is_some() { Vec::new() } else { after[kept..].to_vec() };
+        step.exception = exception.map(String::from);
+        step.state = state;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::LocalVariable;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use super::*;
+
+    /// compares `trace` with `sample/golden/<name>.trace`. `RJVM_UPDATE_GOLDEN=1 cargo test` writes
+    /// the traces instead, review the changes to the files like code.
+    fn assert_golden(name: &str, trace: &ExecutionTrace) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("sample/golden").join(format!("{}.trace", name));
+        let actual = trace.to_string();
+        if env::var_os("RJVM_UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &actual).unwrap();
+            return;
+        }
+
+        let expected = fs::read_to_string(&path).unwrap_or_else(|err| panic!("cannot read {}: {}, create it with RJVM_UPDATE_GOLDEN=1", path.display(), err));
+        if let Some((line, (expected, actual))) = expected.lines().zip(actual.lines()).enumerate().find(|(_, (expected, actual))| expected != actual) {
+            panic!("the trace differs from {} in line {}:\n  expected: {}\n  actual:   {}\nrun with RJVM_UPDATE_GOLDEN=1 if the change is intended",
+                   path.display(), line + 1, expected, actual);
+        }
+        assert_eq!(expected.lines().count(), actual.lines().count(), "the trace differs from {} in length", path.display());
+    }
+
+    fn traced<F>(bytes: &'static [u8], others: &[&'static [u8]], run: F) -> ExecutionTrace where F: FnOnce(&mut Runtime) {
+        let mut rt = Runtime::builder().build(read_class_file(bytes).unwrap().1);
+        for other in others {
+            rt.load_class(read_class_file(other).unwrap().1);
+        }
+        rt.start_trace();
+        run(&mut rt);
+        rt.take_trace().unwrap()
+    }
+
+    #[test]
+    fn loops_match_their_golden_trace() {
+        let trace = traced(include_bytes!("../../../sample/Loop.class"), &[], |rt| {
+            rt.invoke_static("Loop", "sum", "(I)I", vec![LocalVariable::Integer(3)]).unwrap();
+        });
+        assert_eq!(trace.steps()[0].pushed, vec![StackValue::Integer(0)]);
+        assert_golden("loop_sum", &trace);
+    }
+
+    #[test]
+    fn calls_and_objects_match_their_golden_trace() {
+        let trace = traced(include_bytes!("../../../sample/Dispatch.class"),
+                           &[include_bytes!("../../../sample/Sub.class"), include_bytes!("../../../sample/Valued.class")], |rt| {
+            rt.invoke_static("Dispatch", "run", "()I", vec![]).unwrap();
+        });
+        assert!(trace.steps().iter().any(|step| step.depth == 1 && step.method_name == "twice"));
+        assert_golden("dispatch_run", &trace);
+    }
+
+    #[test]
+    fn exceptions_match_their_golden_trace() {
+        let trace = traced(include_bytes!("../../../sample/ExceptionExample.class"), &[], |rt| {
+            rt.invoke_static("ExceptionExample", "caught", "()I", vec![]).unwrap();
+        });
+        assert!(trace.steps().iter().any(|step| step.exception.as_deref() == Some("java/lang/RuntimeException")));
+        assert_golden("exception_caught", &trace);
+    }
+}
@@ -0,0 +1,108 @@
+use java::class_file::Method;
+use java::runtime::{LocalVariable, ObjectRef, Runtime, StackValue};
+use std::fmt;
+
+const UNCAUGHT_EXCEPTION: &str = "(Ljava/lang/Thread;Ljava/lang/Throwable;)V";
+
+/// an exception which ended a thread without a handler taking it. the jvm would print it to
+/// stderr, `run` leaves that to the embedder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UncaughtException {
+    pub thread_name: String,
+    pub exception: ObjectRef,
+    /// the binary name, like `java/lang/IllegalStateException`.
+    pub class_name: String,
+    pub message: Option<String>,
+    /// the trace as `Throwable.printStackTrace` prints it.
+    pub stack_trace: String,
+}
+
+/// formats the exception like the default handler of the jvm prints it.
+impl fmt::Display for UncaughtException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Exception in thread \"{}\" {}", self.thread_name, self.stack_trace)
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// hands an exception which unwound all frames of the thread to the handler of the thread or
+    /// else the default handler, like `Thread.dispatchUncaughtException`. `method` is the method
+    /// which threw it. what the handler throws is ignored, like the jvm does.
+    /// returns the exception if there is no handler.
+    pub(super) fn dispatch_uncaught_exception(&mut self, method: &Method, exception: ObjectRef) -> Option<UncaughtException> {
+        let handler = match self.thread.uncaught_exception_handler.or(self.default_uncaught_exception_handler) {
+            Some(handler) => handler,
+            None => return Some(self.uncaught_exception(exception))
+        };
+
+        let class_name = self.heap.get(handler).map(|object| object.class_name.clone()).unwrap_or_default();
+        let arguments = vec![LocalVariable::Reference(handler), LocalVariable::Reference(self.thread_object()), LocalVariable::Reference(exception)];
+        let result = match self.lambda_class(&class_name) {
+            Some(lambda) => self.invoke_lambda(method, &lambda, "uncaughtException", UNCAUGHT_EXCEPTION, arguments),
+            None => self.invoke(&class_name, "uncaughtException", UNCAUGHT_EXCEPTION, arguments)
+        };
+        if let Err(err) = result {
+            debug!(handler = %class_name, error = %err, "uncaught exception handler failed");
+        }
+        None
+    }
+
+    fn uncaught_exception(&self, exception: ObjectRef) -> UncaughtException {
+        let object = self.heap.get(exception);
+        let message = object.and_then(|object| match object.fields.get("detailMessage") {
+            Some(StackValue::Reference(message)) => self.heap.string_value(*message).map(String::from),
+            _ => None
+        });
+
+        UncaughtException {
+            thread_name: String::from(self.thread.name()),
+            exception,
+            class_name: object.map(|object| object.class_name.clone()).unwrap_or_default(),
+            message,
+            stack_trace: self.format_stack_trace(exception),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use captured::Captured;
+    use java::runtime::JvmError;
+    use super::*;
+
+    fn runtime<'a>(out: &Captured) -> Runtime<'a> {
+        let mut rt = Runtime::builder().stdout(out.output())
+            .build(read_class_file(include_bytes!("../../../sample/Uncaught.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Uncaught$Logger.class")).unwrap().1);
+        rt
+    }
+
+    #[test]
+    fn the_handler_of_the_thread_comes_before_the_default_handler() {
+        let out = Captured::default();
+        let mut rt = runtime(&out);
+        assert_eq!(rt.run().unwrap().code(), 1);
+        assert_eq!(out.take(), "default\n");
+
+        let mut rt = runtime(&out);
+        rt.invoke_static("Uncaught", "logOnThisThread", "()V", vec![]).unwrap();
+        assert_eq!(rt.run().unwrap().code(), 1);
+        assert_eq!(out.take(), "main\nboom\n");
+        assert!(rt.current_thread().uncaught_exception_handler().is_some());
+    }
+
+    #[test]
+    fn exceptions_without_a_handler_are_returned() {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/ExceptionExample.class")).unwrap().1);
+        let uncaught = match rt.run() {
+            Err(JvmError::UncaughtException(uncaught)) => uncaught,
+            other => panic!("expected an uncaught exception, got {:?}", other)
+        };
+        assert_eq!((uncaught.thread_name.as_str(), uncaught.class_name.as_str()), ("main", "java/lang/RuntimeException"));
+        assert_eq!(uncaught.message, Some(String::from("boom")));
+        assert_eq!(uncaught.to_string(), "Exception in thread \"main\" java.lang.RuntimeException: boom\n\
+                                          \tat ExceptionExample.thrower(ExceptionExample.java:3)\n\
+                                          \tat ExceptionExample.main(ExceptionExample.java:15)\n");
+    }
+}
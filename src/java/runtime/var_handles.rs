@@ -0,0 +1,237 @@
+use java::class_file::ValueType;
+use java::runtime::{LocalVariable, ObjectData, ObjectRef, Runtime, RuntimeError, StackValue};
+use std::sync::atomic::{self, Ordering};
+use super::method_handles::{convert, field_type, parse_descriptor};
+
+pub(super) const VAR_HANDLE: &str = "java/lang/invoke/VarHandle";
+
+/// an access mode of a `VarHandle`, see `VarHandle.AccessMode`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AccessMode {
+    Get,
+    Set,
+    CompareAndSet,
+    CompareAndExchange,
+    GetAndSet,
+    GetAndAdd,
+}
+
+impl AccessMode {
+    /// the mode of a `VarHandle` method and the memory ordering it asks for. `None` for the
+    /// ordering of plain accesses.
+    pub fn from_method_name(name: &str) -> Option<(AccessMode, Option<Ordering>)> {
+        Some(match name {
+            "get" => (AccessMode::Get, None),
+            "set" => (AccessMode::Set, None),
+            "getOpaque" | "getAcquire" => (AccessMode::Get, Some(Ordering::Acquire)),
+            "setOpaque" | "setRelease" => (AccessMode::Set, Some(Ordering::Release)),
+            "getVolatile" => (AccessMode::Get, Some(Ordering::SeqCst)),
+            "setVolatile" => (AccessMode::Set, Some(Ordering::SeqCst)),
+            "compareAndSet" | "weakCompareAndSet" | "weakCompareAndSetPlain" | "weakCompareAndSetAcquire" | "weakCompareAndSetRelease" =>
+                (AccessMode::CompareAndSet, Some(Ordering::SeqCst)),
+            "compareAndExchange" | "compareAndExchangeAcquire" | "compareAndExchangeRelease" => (AccessMode::CompareAndExchange, Some(Ordering::SeqCst)),
+            "getAndSet" | "getAndSetAcquire" | "getAndSetRelease" => (AccessMode::GetAndSet, Some(Ordering::SeqCst)),
+            "getAndAdd" | "getAndAddAcquire" | "getAndAddRelease" => (AccessMode::GetAndAdd, Some(Ordering::SeqCst)),
+            _ => return None
+        })
+    }
+
+    /// the number of values an access passes besides the receiver.
+    fn values(self) -> usize {
+        match self {
+            AccessMode::Get => 0,
+            AccessMode::Set | AccessMode::GetAndSet | AccessMode::GetAndAdd => 1,
+            AccessMode::CompareAndSet | AccessMode::CompareAndExchange => 2,
+        }
+    }
+}
+
+/// adds two values of a numeric field.
+fn add(descriptor: &str, value: &StackValue, delta: &StackValue) -> Option<StackValue> {
+    match (descriptor, value, delta) {
+        ("J", StackValue::Long(value), StackValue::Long(delta)) => Some(StackValue::Long(value.wrapping_add(*delta))),
+        ("I", StackValue::Integer(value), StackValue::Integer(delta)) => Some(StackValue::Integer(i64::from((*value as i32).wrapping_add(*delta as i32)))),
+        ("S", StackValue::Integer(value), StackValue::Integer(delta)) => Some(StackValue::Integer(i64::from((*value as i16).wrapping_add(*delta as i16)))),
+        ("C", StackValue::Integer(value), StackValue::Integer(delta)) => Some(StackValue::Integer(i64::from((*value as u16).wrapping_add(*delta as u16)))),
+        ("B", StackValue::Integer(value), StackValue::Integer(delta)) => Some(StackValue::Integer(i64::from((*value as i8).wrapping_add(*delta as i8)))),
+        _ => None
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// `findVarHandle` and `findStaticVarHandle` of `MethodHandles.Lookup`. throws a `NoSuchFieldException`
+    /// if a loaded class has no such field.
+    ///
+    /// like method handles, a var handle is a plain object which keeps the class, name and descriptor
+    /// of its field in fields.
+    pub(super) fn find_var_handle(&mut self, is_static: bool, class: Option<ObjectRef>, name: Option<ObjectRef>, field_class: Option<ObjectRef>) -> Result<StackValue, RuntimeError> {
+        let class_name = class.and_then(|class| self.class_object_name(class));
+        let field_name = name.and_then(|name| self.heap.string_value(name)).map(String::from);
+        let value_type = field_class.and_then(|field_class| self.class_type(field_class));
+        let (class_name, field_name, value_type) = match (class_name, field_name, value_type) {
+            (Some(class_name), Some(field_name), Some(value_type)) => (class_name, field_name, value_type),
+            _ => return Err(self.throw_in_caller("java/lang/NullPointerException", None))
+        };
+
+        let descriptor = value_type.to_string();
+        let owner = match self.field_owner(&class_name, &field_name, &descriptor) {
+            Some(owner) => owner,
+            None if self.classes.get(&class_name).is_none() => class_name.clone(),
+            None => {
+                let message = format!("no such field: {}.{}/{}", class_name.replace('/', "."), field_name, descriptor);
+                return Err(self.throw_in_caller("java/lang/NoSuchFieldException", Some(&message)));
+            }
+        };
+        if is_static {
+            let (caller, slot) = self.caller()?;
+            self.initialize_class(&caller.methods[slot], &owner)?;
+        }
+
+        let values = [
+            ("class", StackValue::Reference(self.intern(&owner))),
+            ("name", StackValue::Reference(self.intern(&field_name))),
+            ("descriptor", StackValue::Reference(self.intern(&descriptor))),
+            ("static", StackValue::Integer(if is_static { 1 } else { 0 })),
+        ];
+        let handle = self.allocate(VAR_HANDLE, ObjectData::Instance);
+        if let Some(object) = self.heap.get_mut(handle) {
+            for (name, value) in values.iter() {
+                object.fields.insert(String::from(*name), value.clone());
+            }
+        }
+        Ok(StackValue::Reference(handle))
+    }
+
+    /// implements the access mode methods of `VarHandle`, which are signature polymorphic like
+    /// `MethodHandle.invoke`: the descriptor is the one of the call site, whose arguments are
+    /// converted to the type of the field.
+    ///
+    /// the heap belongs to the thread running the vm, so there is nothing to synchronize with. the
+    /// accesses still issue the fence of their memory ordering, so the host sees them like java code
+    /// on a real vm would.
+    pub(super) fn invoke_var_handle(&mut self, method_name: &str, descriptor: &str, arguments: &[LocalVariable]) -> Option<Result<Option<StackValue>, RuntimeError>> {
+        let (mode, ordering) = AccessMode::from_method_name(method_name)?;
+        let handle = match arguments.first() {
+            Some(LocalVariable::Reference(handle)) => *handle,
+            _ => return Some(Err(self.throw_in_caller("java/lang/NullPointerException", None)))
+        };
+        Some(self.access_field(handle, mode, ordering, descriptor, &arguments[1..]))
+    }
+
+    fn access_field(&mut self, handle: ObjectRef, mode: AccessMode, ordering: Option<Ordering>, descriptor: &str, arguments: &[LocalVariable]) -> Result<Option<StackValue>, RuntimeError> {
+        let invalid = || RuntimeError::StackType { expected: String::from(VAR_HANDLE) };
+        let is_static = match self.heap.get(handle).and_then(|object| object.fields.get("static")) {
+            Some(StackValue::Integer(is_static)) => *is_static != 0,
+            _ => return Err(invalid())
+        };
+        let class_name = self.string_field(handle, "class").ok_or_else(invalid)?;
+        let name = self.string_field(handle, "name").ok_or_else(invalid)?;
+        let field_descriptor = self.string_field(handle, "descriptor").ok_or_else(invalid)?;
+
+        let call_type = parse_descriptor(descriptor)?;
+        let receivers = if is_static { 0 } else { 1 };
+        if call_type.arguments.len() != receivers + mode.values() {
+            let message = format!("{:?} of {}.{} cannot be invoked with {}", mode, class_name.replace('/', "."), name, call_type);
+            return Err(self.throw_in_caller("java/lang/invoke/WrongMethodTypeException", Some(&message)));
+        }
+        let receiver = match (is_static, arguments.first()) {
+            (true, _) => None,
+            (false, Some(LocalVariable::Reference(receiver))) => Some(*receiver),
+            (false, Some(LocalVariable::Null)) => return Err(self.throw_in_caller("java/lang/NullPointerException", None)),
+            (false, _) => return Err(RuntimeError::StackType { expected: String::from("reference") })
+        };
+        let value_type = field_type(&field_descriptor)?;
+        let values = arguments[receivers..].iter()
+            .map(|value| StackValue::from(convert(value.clone(), &value_type)))
+            .collect::<Vec<_>>();
+
+        let fence = |ordering: Option<Ordering>| if let Some(ordering) = ordering {
+            atomic::fence(ordering);
+        };
+        fence(ordering);
+        let current = match receiver {
+            Some(receiver) => self.heap.get(receiver).and_then(|object| object.fields.get(&name).cloned()),
+            None => self.statics.get(&(class_name.clone(), name.clone())).cloned()
+        }.unwrap_or_else(|| Runtime::default_value(&field_descriptor));
+
+        let (new_value, result) = match mode {
+            AccessMode::Get => (None, Some(current)),
+            AccessMode::Set => (Some(values[0].clone()), None),
+            AccessMode::GetAndSet => (Some(values[0].clone()), Some(current)),
+            AccessMode::GetAndAdd => match add(&field_descriptor, &current, &values[0]) {
+                Some(sum) => (Some(sum), Some(current)),
+                None => return Err(self.throw_in_caller("java/lang/UnsupportedOperationException", Some(&format!("getAndAdd of a {} field", field_descriptor))))
+            },
+            // references are compared by identity, primitives by value
+            AccessMode::CompareAndSet => {
+                let matches = current == values[0];
+                (if matches { Some(values[1].clone()) } else { None }, Some(StackValue::Integer(if matches { 1 } else { 0 })))
+            }
+            AccessMode::CompareAndExchange => {
+                let matches = current == values[0];
+                (if matches { Some(values[1].clone()) } else { None }, Some(current))
+            }
+        };
+
+        if let Some(new_value) = new_value {
+            match receiver {
+                Some(receiver) => if let Some(object) = self.heap.get_mut(receiver) {
+                    object.fields.insert(name, new_value);
+                },
+                None => {
+                    self.statics.insert((class_name, name), new_value);
+                }
+            }
+        }
+        fence(ordering);
+
+        Ok(match (&call_type.return_type, result) {
+            (ValueType::Void, _) => None,
+            (_, None) => return Err(RuntimeError::InvalidReturnValue { expected: call_type.return_type.to_string() }),
+            (return_type, Some(value)) => Some(StackValue::from(convert(LocalVariable::from(value), return_type)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use super::*;
+
+    fn runtime<'a>() -> Runtime<'a> {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/VarHandles.class")).unwrap().1);
+        rt.load_class(read_class_file(include_bytes!("../../../sample/Slot.class")).unwrap().1);
+        rt
+    }
+
+    fn call(rt: &mut Runtime, name: &str, descriptor: &str, arguments: Vec<LocalVariable>) -> StackValue {
+        match rt.invoke_static("VarHandles", name, descriptor, arguments) {
+            Ok(Some(value)) => value,
+            other => panic!("{} returned {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn var_handles_access_fields() {
+        let mut rt = runtime();
+        assert_eq!(call(&mut rt, "plain", "()I", vec![]), StackValue::Integer(42));
+        let (start, delta) = (LocalVariable::Long(5), LocalVariable::Long(10));
+        assert_eq!(call(&mut rt, "volatileLong", "(JJ)J", vec![start.clone(), delta.clone()]), StackValue::Long(15));
+        // getAndAdd returns the value before adding
+        assert_eq!(call(&mut rt, "previousLong", "(JJ)J", vec![start, delta]), StackValue::Long(5));
+        assert_eq!(call(&mut rt, "compareAndSet", "()I", vec![]), StackValue::Integer(100 + 7));
+        match call(&mut rt, "references", "()Ljava/lang/String;", vec![]) {
+            StackValue::Reference(value) => assert_eq!(rt.heap().string_value(value), Some("null:b")),
+            other => panic!("references returned {:?}", other)
+        }
+        assert_eq!(call(&mut rt, "statics", "()I", vec![]), StackValue::Integer(7 + 7));
+        assert_eq!(call(&mut rt, "missing", "()Z", vec![]), StackValue::Integer(1));
+    }
+
+    #[test]
+    fn access_modes_follow_the_method_name() {
+        assert_eq!(AccessMode::from_method_name("getAcquire"), Some((AccessMode::Get, Some(Ordering::Acquire))));
+        assert_eq!(AccessMode::from_method_name("weakCompareAndSetPlain").map(|(mode, _)| mode), Some(AccessMode::CompareAndSet));
+        assert_eq!(AccessMode::from_method_name("toMethodHandle"), None);
+    }
+}
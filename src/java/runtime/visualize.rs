@@ -0,0 +1,315 @@
+//! the state of the vm after every instruction of an `ExecutionTrace`, written as json for
+//! step-through visualizers of bytecode execution.
+//!
+//! ```text
+//! cargo run -- --visualize=out.json sample/Tiny.class
+//! ```
+//!
+//! the document, version 1:
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "heap": [object, ...],         the live objects when the recording started
+//!   "steps": [step, ...]           the executed instructions in the order they started
+//! }
+//!
+//! step: {
+//!   "index": 0,
+//!   "depth": 0,                    the number of java frames below the one of the instruction
+//!   "class": "Loop", "method": "sum", "descriptor": "(I)I", "pc": 0,
+//!   "opcode": 3, "instruction": "IConst0",
+//!   "exception": null,             or the class of the exception the instruction threw
+//!   "frames": [frame, ...],        the java stack after the instruction, innermost frame first
+//!   "heap": [change, ...]          the objects the instruction created, changed or freed
+//! }
+//!
+//! frame: {
+//!   "class": "Loop", "method": "sum", "descriptor": "(I)I", "pc": 0,
+//!   "line": 3,                     null without line numbers
+//!   "locals": [value, ...],        by slot, a long takes two
+//!   "stack": [value, ...]          the operand stack, bottom first
+//! }
+//!
+//! change: {"change": "allocated", "ref": 0, "object": object}
+//!       | {"change": "modified", "ref": 0, "object": object}
+//!       | {"change": "freed", "ref": 0}
+//!
+//! object: {
+//!   "ref": 0, "class": "[I",
+//!   "fields": {"name": value, ...},
+//!   "string": "text",              only for strings
+//!   "elements": [value, ...]       only for arrays
+//! }
+//!
+//! value: {"type": "int", "value": 42}
+//!      | {"type": "long", "value": "42"}  as a string, javascript numbers cannot hold every long
+//!      | {"type": "ref", "value": 0}      the ref of an object
+//!      | {"type": "null"}
+//!      | {"type": "none"}                 a local which was not written yet
+//!      | {"type": "top"}                  the second slot of a long
+//! ```
+//!
+//! every step compares the whole heap with the one before, so recording suits the small programs
+//! of a lecture rather than long running ones.
+
+use java::runtime::{ExecutionTrace, FrameView, LocalVariable, Object, ObjectData, ObjectRef, Runtime, StackFrame, StackValue};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// the version of the json document `ExecutionTrace::write_json` writes.
+pub const VISUALIZATION_VERSION: u32 = 1;
+
+/// an object as a visualizer shows it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectState {
+    pub class_name: String,
+    /// the fields by name, sorted.
+    pub fields: Vec<(String, StackValue)>,
+    /// the value of a string.
+    pub string: Option<String>,
+    /// the elements of an array.
+    pub elements: Option<Vec<StackValue>>,
+}
+
+impl ObjectState {
+    fn of(object: &Object) -> ObjectState {
+        let mut fields = object.fields.iter().map(|(name, value)| (name.clone(), value.clone())).collect::<Vec<_>>();
+        fields.sort_by(|(lh, _), (rh, _)| lh.cmp(rh));
+        let (string, elements) = match object.data {
+            ObjectData::String(ref value) => (Some(value.to_string()), None),
+            ObjectData::Array(ref elements) => (None, Some(elements.clone())),
+            ObjectData::Instance | ObjectData::Throwable(_) => (None, None)
+        };
+        ObjectState { class_name: object.class_name.clone(), fields, string, elements }
+    }
+}
+
+/// what an instruction did to an object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeapChange {
+    Allocated(ObjectRef, ObjectState),
+    Modified(ObjectRef, ObjectState),
+    Freed(ObjectRef),
+}
+
+/// the vm after an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepState {
+    /// the java stack, innermost frame first.
+    pub frames: Vec<FrameView>,
+    pub heap: Vec<HeapChange>,
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_option<T, F>(value: Option<T>, to_json: F) -> String where F: FnOnce(T) -> String {
+    value.map_or_else(|| String::from("null"), to_json)
+}
+
+fn json_value(value: &StackValue) -> String {
+    match value {
+        StackValue::None => String::from(r#"{"type": "none"}"#),
+        StackValue::Null => String::from(r#"{"type": "null"}"#),
+        StackValue::Integer(value) => format!(r#"{{"type": "int", "value": {}}}"#, value),
+        StackValue::Long(value) => format!(r#"{{"type": "long", "value": "{}"}}"#, value),
+        StackValue::Reference(reference) => format!(r#"{{"type": "ref", "value": {}}}"#, reference.0),
+    }
+}
+
+fn json_local(local: &LocalVariable) -> String {
+    match local {
+        LocalVariable::None => String::from(r#"{"type": "none"}"#),
+        LocalVariable::Top => String::from(r#"{"type": "top"}"#),
+        LocalVariable::Null => json_value(&StackValue::Null),
+        LocalVariable::Integer(value) => json_value(&StackValue::Integer(*value)),
+        LocalVariable::Long(value) => json_value(&StackValue::Long(*value)),
+        LocalVariable::Reference(reference) => json_value(&StackValue::Reference(*reference)),
+    }
+}
+
+fn json_list<T, F>(items: &[T], to_json: F) -> String where F: Fn(&T) -> String {
+    format!("[{}]", items.iter().map(to_json).collect::<Vec<_>>().join(", "))
+}
+
+fn json_object(reference: ObjectRef, object: &ObjectState) -> String {
+    let fields = object.fields.iter().map(|(name, value)| format!("{}: {}", json_string(name), json_value(value))).collect::<Vec<_>>();
+    let mut json = format!(r#"{{"ref": {}, "class": {}, "fields": {{{}}}"#, reference.0, json_string(&object.class_name), fields.join(", "));
+    if let Some(ref value) = object.string {
+        json.push_str(&format!(r#", "string": {}"#, json_string(value)));
+    }
+    if let Some(ref elements) = object.elements {
+        json.push_str(&format!(r#", "elements": {}"#, json_list(elements, json_value)));
+    }
+    json.push('}');
+    json
+}
+
+fn json_frame(frame: &FrameView) -> String {
+    format!(r#"{{"class": {}, "method": {}, "descriptor": {}, "pc": {}, "line": {}, "locals": {}, "stack": {}}}"#,
+            json_string(&frame.class_name), json_string(&frame.method_name), json_string(&frame.descriptor), frame.pc,
+            json_option(frame.line, |line| line.to_string()), json_list(&frame.locals, json_local), json_list(&frame.stack, json_value))
+}
+
+fn json_change(change: &HeapChange) -> String {
+    match change {
+        HeapChange::Allocated(reference, object) => format!(r#"{{"change": "allocated", "ref": {}, "object": {}}}"#, reference.0, json_object(*reference, object)),
+        HeapChange::Modified(reference, object) => format!(r#"{{"change": "modified", "ref": {}, "object": {}}}"#, reference.0, json_object(*reference, object)),
+        HeapChange::Freed(reference) => format!(r#"{{"change": "freed", "ref": {}}}"#, reference.0),
+    }
+}
+
+impl ExecutionTrace {
+    /// the live objects when the trace started, empty unless it records the state of the vm.
+    pub fn initial_heap(&self) -> Vec<(ObjectRef, &ObjectState)> {
+        self.initial_heap.iter().map(|(reference, object)| (*reference, object)).collect()
+    }
+
+    /// writes the trace as the json document described in this module, a line per step.
+    /// steps without state have empty `frames` and `heap`.
+    pub fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let heap = self.initial_heap.iter().map(|(reference, object)| json_object(*reference, object)).collect::<Vec<_>>();
+        writeln!(out, "{{")?;
+        writeln!(out, r#"  "version": {},"#, VISUALIZATION_VERSION)?;
+        writeln!(out, r#"  "heap": [{}],"#, heap.join(", "))?;
+        write!(out, r#"  "steps": ["#)?;
+        for (index, step) in self.steps().iter().enumerate() {
+            let (frames, changes) = match step.state {
+                Some(ref state) => (json_list(&state.frames, json_frame), json_list(&state.heap, json_change)),
+                None => (String::from("[]"), String::from("[]"))
+            };
+            write!(out, "{}\n    ", if index == 0 { "" } else { "," })?;
+            write!(out, r#"{{"index": {}, "depth": {}, "class": {}, "method": {}, "descriptor": {}, "pc": {}, "opcode": {}, "instruction": {}, "exception": {}, "frames": {}, "heap": {}}}"#,
+                   index, step.depth, json_string(&step.class_name), json_string(&step.method_name), json_string(&step.descriptor), step.pc,
+                   step.instruction.opcode(), json_string(&format!("{:?}", step.instruction).replace("(())", "")),
+                   json_option(step.exception.as_ref(), |exception| json_string(exception)), frames, changes)?;
+        }
+        writeln!(out, "\n  ]")?;
+        writeln!(out, "}}")
+    }
+}
+
+impl<'a> Runtime<'a> {
+    /// like `start_trace`, but every step also records the frames and what changed on the heap,
+    /// the input of `ExecutionTrace::write_json`.
+    pub fn start_visualization(&mut self) {
+        let objects = self.heap.objects().map(|(reference, object)| (reference, ObjectState::of(object))).collect::<BTreeMap<_, _>>();
+        self.start_trace();
+        if let Some(ref mut trace) = self.trace {
+            trace.initial_heap = objects.clone();
+            trace.objects = Some(objects);
+        }
+    }
+
+    /// the frames with `current` as the innermost one, and the objects which differ from the last step.
+    pub(super) fn step_state(&mut self, current: &StackFrame) -> StepState {
+        let frames = self.frame_views(current);
+        let previous = match self.trace.as_mut().and_then(|trace| trace.objects.as_mut()) {
+            Some(previous) => previous,
+            None => return StepState { frames, heap: Vec::new() }
+        };
+
+        let mut heap = Vec::new();
+        let mut live = BTreeMap::new();
+        for (reference, object) in self.heap.objects() {
+            let object = ObjectState::of(object);
+            match previous.get(&reference) {
+                None => heap.push(HeapChange::Allocated(reference, object.clone())),
+                Some(before) if *before != object => heap.push(HeapChange::Modified(reference, object.clone())),
+                Some(_) => ()
+            }
+            live.insert(reference, object);
+        }
+        heap.extend(previous.keys().filter(|reference| !live.contains_key(reference)).map(|reference| HeapChange::Freed(*reference)));
+        *previous = live;
+        StepState { frames, heap }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use java::class_file::read_class_file;
+    use java::runtime::FrameValue;
+    use super::*;
+
+    fn visualized(argument: i64) -> ExecutionTrace {
+        let mut rt = Runtime::builder().build(read_class_file(include_bytes!("../../../sample/SmallArrays.class")).unwrap().1);
+        rt.start_visualization();
+        rt.invoke_static("SmallArrays", "sum", "(I)I", vec![LocalVariable::Integer(argument)]).unwrap();
+        rt.take_trace().unwrap()
+    }
+
+    #[test]
+    fn steps_record_frames_and_heap_changes() {
+        let trace = visualized(2);
+        let states = trace.steps().iter().map(|step| step.state.as_ref().unwrap()).collect::<Vec<_>>();
+        for (step, state) in trace.steps().iter().zip(states.iter()) {
+            assert_eq!(state.frames.len(), step.depth + 1);
+            assert_eq!(state.frames[0].pc(), step.pc);
+        }
+
+        let allocation = trace.steps().iter().position(|step| step.instruction.name() == "NewArray").unwrap();
+        let array = match states[allocation].heap[..] {
+            [HeapChange::Allocated(reference, ref object)] => {
+                assert_eq!(object.class_name, "[I");
+                assert_eq!(object.elements, Some(vec![StackValue::Integer(0), StackValue::Integer(0)]));
+                reference
+            }
+            ref changes => panic!("unexpected changes {:?}", changes)
+        };
+        assert_eq!(states[allocation].frames[0].stack().collect::<Vec<_>>(), vec![FrameValue::Reference(array)]);
+
+        let stores = trace.steps().iter().zip(states.iter()).filter(|(step, _)| step.instruction.name() == "IAStore")
+            .map(|(_, state)| state.heap.clone()).collect::<Vec<_>>();
+        assert_eq!(stores.len(), 2);
+        match stores[1][..] {
+            [HeapChange::Modified(reference, ref object)] => {
+                assert_eq!(reference, array);
+                assert_eq!(object.elements, Some(vec![StackValue::Integer(1), StackValue::Integer(2)]));
+            }
+            ref changes => panic!("unexpected changes {:?}", changes)
+        }
+        // loads leave the heap alone
+        assert!(trace.steps().iter().zip(states.iter()).filter(|(step, _)| step.instruction.name() == "IALoad").all(|(_, state)| state.heap.is_empty()));
+
+        let last = states.last().unwrap();
+        assert!(last.frames[0].locals().any(|local| local == FrameValue::Reference(array)));
+    }
+
+    #[test]
+    fn traces_are_written_as_json() {
+        let trace = visualized(1);
+        let mut out = Vec::new();
+        trace.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+
+        assert!(json.starts_with("{\n  \"version\": 1,\n  \"heap\": [],\n  \"steps\": [\n    {\"index\": 0, \"depth\": 0, \"class\": \"SmallArrays\""));
+        assert_eq!(json.lines().filter(|line| line.starts_with("    {\"index\"")).count(), trace.steps().len());
+        assert!(json.contains(r#""heap": [{"change": "allocated", "ref": 0, "object": {"ref": 0, "class": "[I", "fields": {}, "elements": [{"type": "int", "value": 0}]}}]"#));
+        assert!(json.contains(r#""locals": [{"type": "int", "value": 1}, {"type": "ref", "value": 0}"#));
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        assert_eq!(json.matches('[').count() - json.matches("\"[I\"").count(), json.matches(']').count());
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}é"), r#""a\"b\\c\n\u0001é""#);
+        assert_eq!(json_value(&StackValue::Long(i64::MAX)), r#"{"type": "long", "value": "9223372036854775807"}"#);
+    }
+}
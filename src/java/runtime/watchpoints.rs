@@ -0,0 +1,73 @@
+use java::runtime::{FrameView, ObjectRef, Runtime, StackValue};
+use super::StackFrame;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FieldAccessKind {
+    GetField,
+    PutField,
+    GetStatic,
+    PutStatic,
+}
+
+/// an access to a watched field, passed to `RuntimeHook::on_field_watch`.
+#[derive(Debug, Clone)]
+pub struct FieldWatchEvent {
+    pub kind: FieldAccessKind,
+    /// the class the field reference names, which may be a subclass of the watched class.
+    pub class_name: String,
+    pub field_name: String,
+    /// the instance for `GetField` and `PutField`.
+    pub object: Option<ObjectRef>,
+    /// the value before the access.
+    pub old_value: StackValue,
+    /// the value written by `PutField` and `PutStatic`.
+    pub new_value: Option<StackValue>,
+    /// the frame executing the access.
+    pub frame: FrameView,
+}
+
+/// the field an instruction reads or writes, before the runtime checks whether it is watched.
+pub(super) struct FieldAccess<'f> {
+    pub kind: FieldAccessKind,
+    pub class_name: &'f str,
+    pub field_name: &'f str,
+    pub object: Option<ObjectRef>,
+    pub old_value: &'f StackValue,
+    pub new_value: Option<&'f StackValue>,
+}
+
+impl<'a> Runtime<'a> {
+    /// reports every read and write of `field_name` of `class_name` (or its subclasses) to the hooks.
+    pub fn watch_field(&mut self, class_name: &str, field_name: &str) {
+        self.watchpoints.push((String::from(class_name), String::from(field_name)));
+    }
+
+    pub fn unwatch_field(&mut self, class_name: &str, field_name: &str) {
+        self.watchpoints.retain(|(class, field)| class != class_name || field != field_name);
+    }
+
+    fn is_watched(&self, class_name: &str, field_name: &str) -> bool {
+        self.watchpoints.iter().any(|(class, field)| field == field_name && self.is_subclass_of(class_name, class))
+    }
+
+    /// notifies the hooks if the accessed field is watched.
+    pub(super) fn field_accessed(&mut self, access: FieldAccess, stack_frame: &StackFrame) {
+        if self.hooks.is_empty() || !self.is_watched(access.class_name, access.field_name) {
+            return;
+        }
+
+        let event = FieldWatchEvent {
+            kind: access.kind,
+            class_name: String::from(access.class_name),
+            field_name: String::from(access.field_name),
+            object: access.object,
+            old_value: access.old_value.clone(),
+            new_value: access.new_value.cloned(),
+            frame: self.frame_views(stack_frame).remove(0),
+        };
+
+        for hook in self.hooks.iter_mut() {
+            hook.on_field_watch(&event);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod java;
+#[cfg(feature = "std")]
+pub mod ffi;
+#[cfg(all(test, feature = "conformance"))]
+mod conformance;
+#[cfg(test)]
+mod differential;
+#[cfg(test)]
+mod captured;
+
+extern crate nom;
+#[macro_use]
+extern crate failure;
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate tracing;
+#[cfg(all(feature = "std", not(target_family = "wasm")))]
+extern crate signal_hook;
+#[cfg(feature = "std")]
+extern crate libc;
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+#[cfg(test)]
+extern crate proptest;
+
+/// stands in for `std` in builds without it, so `use std::...` in the modules which only need
+/// `core` and `alloc` resolves the same either way.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use core::*;
+    pub use alloc::{borrow, collections, fmt, rc, str, string, sync};
+}
+
+/// what the prelude of `std` adds to the one of `core`, for the same modules.
+#[cfg(not(feature = "std"))]
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+}
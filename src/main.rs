@@ -1,21 +1,85 @@
-mod java;
+extern crate rjvm;
+extern crate tracing_subscriber;
 
-#[macro_use]
-extern crate nom;
-#[macro_use]
-extern crate failure;
-
-use java::class_file::{read_class_file, ClassFile};
+use rjvm::java::class_file::{ClassFile, ClassFormatError};
 use std::fs::File;
 use std::env;
-use std::io::Read;
-use java::class_file::Attribute;
-use java::class_file::CodeBlock;
-use java::runtime::*;
+use std::io::{BufReader, Read};
+use rjvm::java::analysis::{ClassHierarchy, ControlFlowGraph, DeadCodeReport, EntryPoint};
+use rjvm::java::runtime::*;
+use tracing_subscriber::EnvFilter;
 
 fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    let filename = args.get(1);
+    // verbosity is controlled with RUST_LOG, e.g. `RUST_LOG=rjvm=trace` shows every instruction.
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.first().map(String::as_str) == Some("bench") {
+        return bench(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("cfg") {
+        return cfg(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("hierarchy") {
+        return hierarchy(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("analyze") {
+        return analyze(&args[1..]);
+    }
+    let alloc_profile = args.iter().any(|arg| arg == "--alloc-profile");
+    let method_profile = args.iter().any(|arg| arg == "--method-profile");
+    let eager_load = args.iter().any(|arg| arg == "--eager-load");
+    let classpath = args.iter().find(|arg| arg.starts_with("--classpath=")).map(|arg| env::split_paths(&arg[12..]).collect::<Vec<_>>());
+    let module_path = args.iter().find(|arg| arg.starts_with("--module-path=")).map(|arg| env::split_paths(&arg[14..]).collect::<Vec<_>>());
+    // --add-exports=module/package=target, as often as needed
+    let added_exports = args.iter().filter(|arg| arg.starts_with("--add-exports="))
+        .map(|arg| {
+            let (source, target) = arg[14..].split_at(arg[14..].find('=').expect("--add-exports needs a target module"));
+            let (module, package) = source.split_at(source.find('/').expect("--add-exports needs module/package"));
+            (String::from(module), String::from(&package[1..]), String::from(&target[1..]))
+        })
+        .collect::<Vec<_>>();
+    let archive_path = args.iter().find(|arg| arg.starts_with("--archive=")).map(|arg| std::path::PathBuf::from(&arg[10..]));
+    let dump_archive_path = args.iter().find(|arg| arg.starts_with("--dump-archive=")).map(|arg| String::from(&arg[15..]));
+    let fast_interpreter = args.iter().any(|arg| arg == "--fast-interpreter");
+    let verbose_gc = args.iter().any(|arg| arg == "-verbose:gc");
+    let verbose_jni = args.iter().any(|arg| arg == "-verbose:jni");
+    let string_deduplication = args.iter().any(|arg| arg == "-XX:+UseStringDeduplication");
+    let finalization = args.iter().any(|arg| arg == "--finalization=enabled");
+    let explain = args.iter().any(|arg| arg == "--explain");
+    #[cfg(all(feature = "threads", feature = "sockets"))]
+    let debug_port = args.iter().find(|arg| arg.starts_with("--debug=")).map(|arg| arg[8..].parse::<u16>().expect("invalid debug port"));
+    let record_path = args.iter().find(|arg| arg.starts_with("--record=")).map(|arg| String::from(&arg[9..]));
+    let replay_path = args.iter().find(|arg| arg.starts_with("--replay=")).map(|arg| String::from(&arg[9..]));
+    let cfg_method = args.iter().find(|arg| arg.starts_with("--cfg=")).map(|arg| String::from(&arg[6..]));
+    let coverage_path = args.iter().find(|arg| arg.starts_with("--coverage=")).map(|arg| String::from(&arg[11..]));
+    let visualize_path = args.iter().find(|arg| arg.starts_with("--visualize=")).map(|arg| String::from(&arg[12..]));
+    let max_heap = args.iter().find(|arg| arg.starts_with("-Xmx")).map(|arg| match parse_size(&arg[4..]) {
+        Some(size) => size,
+        None => {
+            // like the java launcher
+            eprintln!("Invalid maximum heap size: {}", arg);
+            eprintln!("Error: Could not create the Java Virtual Machine.");
+            std::process::exit(1);
+        }
+    });
+    let mut assertions = AssertionStatus::new();
+    for arg in args.iter() {
+        assertions.apply_flag(arg);
+    }
+    let filename = args.iter().find(|arg| !arg.starts_with('-'));
+
+    // writes the classes of the classpath with their verification results to an archive instead of running a class
+    if let Some(ref path) = dump_archive_path {
+        let classpath = classpath.unwrap_or_else(|| vec![std::path::PathBuf::from(".")]);
+        let count = ClassArchive::dump(&classpath, std::io::BufWriter::new(File::create(path).expect("cannot create class archive"))).expect("cannot write class archive");
+        println!("archived {} classes to {}", count, path);
+        return;
+    }
+
     let mut buffer = Vec::new();
     let content = if let Some(path) = filename {
         let mut f = File::open(path).expect("cannot open file");
@@ -25,19 +89,325 @@ fn main() {
         include_bytes!("../sample/Tiny.class")
     };
 
-
-    let report: ClassFile = read_class_file(content).unwrap().1;
+    // like the jvm, refuse malformed classes and classes of newer releases before running anything
+    let report = match ClassFile::parse(content) {
+        Ok(class) => class,
+        Err(err) => {
+            let error = match err {
+                ClassFormatError::Version(_) => "UnsupportedClassVersionError",
+                _ => "ClassFormatError"
+            };
+            eprintln!("Error: LinkageError occurred while loading main class {}", filename.map_or("Tiny", String::as_str));
+            eprintln!("\tjava.lang.{}: {}", error, err);
+            std::process::exit(1);
+        }
+    };
     //println!("{:#?}", report);
 
     println!("{:?}", report.get_class_name());
 
+    // prints the control flow graph of a method as Graphviz DOT instead of running the class
+    if let Some(ref name) = cfg_method {
+        for method in report.methods.iter().filter(|method| method.name == name.as_str()) {
+            match ControlFlowGraph::build(method) {
+                Ok(cfg) => print!("{}", cfg.to_dot_with_constants(&format!("{}.{}{}", report.get_class_name(), method.name, method.descriptor), &report)),
+                Err(err) => eprintln!("{}{}: {}", method.name, method.descriptor, err)
+            }
+        }
+        return;
+    }
+
 /*    report.methods.iter().for_each(|method| {
         println!("{:?} {:?}", method.get_access(), method.name);
         println!("{:?}", method.get_signature());
-        println!("{}", java::class_file::dissasm::disassemble(method))
+        println!("{}", rjvm::java::class_file::dissasm::disassemble(method))
     })*/
 
-    let mut rt = Runtime::create(report);
+    let store = ByteStore::new();
+    let mut builder = Runtime::builder()
+        .byte_store(&store)
+        .crash_dump_path(Some(std::path::PathBuf::from(format!("hs_err_pid{}.log", std::process::id()))))
+        .allocation_profiling(alloc_profile)
+        .verbose_gc(verbose_gc)
+        .verbose_jni(verbose_jni)
+        .string_deduplication(string_deduplication)
+        .finalization(finalization)
+        .explain(if explain { Some(Output::stdout()) } else { None })
+        .max_heap(max_heap)
+        .interpreter(if fast_interpreter { InterpreterMode::Fast } else { InterpreterMode::Checked })
+        .eager_loading(if eager_load { Some(0) } else { None })
+        .class_archive(archive_path)
+        .assertions(assertions);
+    if let Some(classpath) = classpath {
+        builder = builder.classpath(classpath);
+    }
+    if let Some(module_path) = module_path {
+        builder = builder.module_path(module_path);
+    }
+    for (module, package, target) in added_exports.iter() {
+        builder = builder.add_exports(module, package, target);
+    }
+    if record_path.is_some() {
+        builder = builder.record_inputs();
+    }
+    if let Some(ref path) = replay_path {
+        let log = InputLog::read_from(BufReader::new(File::open(path).expect("cannot open input log"))).expect("cannot read input log");
+        builder = builder.replay(log);
+    }
+    let mut rt = builder.build(report);
+
+    let coverage = CoverageCollector::new();
+    if coverage_path.is_some() {
+        rt.add_hook(Box::new(coverage.clone()));
+    }
+
+    let cancellation = rt.cancellation_handle();
+    #[cfg(not(target_family = "wasm"))]
+    register_signals(&rt);
+
+    #[cfg(all(feature = "threads", feature = "sockets"))]
+    if let Some(port) = debug_port {
+        println!("Listening for transport dt_socket at address: {}", port);
+        rt.listen_for_debugger(("127.0.0.1", port)).expect("cannot attach debugger");
+    }
+
+    if visualize_path.is_some() {
+        rt.start_visualization();
+    }
+
+    let status = rt.run_and_print();
+
+    // the state after every instruction, as json for step-through visualizers
+    if let Some(ref path) = visualize_path {
+        if let Some(trace) = rt.take_trace() {
+            let mut out = std::io::BufWriter::new(File::create(path).expect("cannot create visualization"));
+            trace.write_json(&mut out).expect("cannot write visualization");
+        }
+    }
+
+    if let Some(ref path) = record_path {
+        if let Some(log) = rt.environment().recording() {
+            let mut out = File::create(path).expect("cannot create input log");
+            log.write_to(&mut out).expect("cannot write input log");
+        }
+    }
+    if let Some(position) = rt.environment().replay_divergence() {
+        eprintln!("replay diverged from the recording at input {}", position);
+    }
+
+    // the report format follows the file name: JaCoCo XML for `.xml`, LCOV otherwise.
+    if let Some(ref path) = coverage_path {
+        let mut out = File::create(path).expect("cannot create coverage report");
+        if path.ends_with(".xml") {
+            coverage.coverage().write_jacoco_xml(&mut out).expect("cannot write coverage report");
+        } else {
+            coverage.coverage().write_lcov(&mut out).expect("cannot write coverage report");
+        }
+    }
+
+    if alloc_profile {
+        eprint!("{}", rt.allocation_profile());
+    }
+    if method_profile {
+        eprint!("{}", rt.method_profile());
+    }
+
+    // like the jvm, a program stopped by SIGINT exits with 128 + 2
+    if cancellation.is_cancelled() {
+        std::process::exit(130);
+    }
+    std::process::exit(status.code());
+}
+
+/// `bench Class::method [int arguments] --iterations N --warmup M` invokes a static method repeatedly
+/// and prints its timings. the class is looked up on the classpath.
+fn bench(args: &[String]) {
+    let mut iterations = 1000;
+    let mut warmup = 100;
+    let mut classpath = vec![std::path::PathBuf::from(".")];
+    let mut fast_interpreter = false;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" => iterations = args.next().and_then(|n| n.parse().ok()).expect("invalid iteration count"),
+            "--warmup" => warmup = args.next().and_then(|n| n.parse().ok()).expect("invalid warmup count"),
+            "--fast-interpreter" => fast_interpreter = true,
+            _ if arg.starts_with("--classpath=") => classpath = env::split_paths(&arg[12..]).collect(),
+            _ => positional.push(arg)
+        }
+    }
+
+    let target = positional.first().expect("usage: bench Class::method [arguments] --iterations N --warmup M");
+    let mut parts = target.splitn(2, "::");
+    let (class_name, method_name) = match (parts.next(), parts.next()) {
+        (Some(class_name), Some(method_name)) => (class_name.replace('.', "/"), method_name),
+        _ => panic!("expected Class::method, got {}", target)
+    };
+    let arguments = positional[1..].iter()
+        .map(|arg| LocalVariable::Integer(arg.parse().expect("arguments must be integers")))
+        .collect::<Vec<_>>();
+
+    let store = ByteStore::new();
+    let class = find_class(&store, &classpath, &class_name);
+    let descriptor = class.methods.iter()
+        .find(|method| method.name == method_name && method.get_access().contains(&rjvm::java::class_file::MethodAccess::Static)
+            && method.get_signature().is_ok_and(|signature| signature.arguments.len() == arguments.len()))
+        .map(|method| String::from(&*method.descriptor))
+        .unwrap_or_else(|| panic!("no static method {} with {} arguments in {}", method_name, arguments.len(), class_name));
+
+    let mut rt = Runtime::builder()
+        .classpath(classpath)
+        .interpreter(if fast_interpreter { InterpreterMode::Fast } else { InterpreterMode::Checked })
+        .build(class);
+    match rt.bench(&class_name, method_name, &descriptor, arguments, warmup, iterations) {
+        Ok(report) => print!("{}", report),
+        Err(err) => eprintln!("{}", err)
+    }
+}
+
+/// reads `class_name` from the first classpath entry which has it, keeping its bytes in `store`.
+fn find_class<'a>(store: &'a ByteStore, classpath: &[std::path::PathBuf], class_name: &str) -> ClassFile<'a> {
+    let path = classpath.iter().map(|entry| entry.join(format!("{}.class", class_name))).find(|path| path.is_file())
+        .unwrap_or_else(|| panic!("class {} not found on the classpath", class_name));
+    let bytes = store.keep(std::fs::read(&path).expect("cannot read class file"));
+    ClassFile::parse(bytes).unwrap_or_else(|err| panic!("cannot parse {}: {}", path.display(), err))
+}
+
+/// `cfg Class::method [-o out.dot]` writes the control flow graph of a method as Graphviz DOT, a
+/// box per basic block with its bytecode and branch and exception edges between them. all
+/// overloads are written unless the method has a descriptor, like `Class::method(I)I`.
+fn cfg(args: &[String]) {
+    let mut classpath = vec![std::path::PathBuf::from(".")];
+    let mut out_path = None;
+    let mut target = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => out_path = Some(args.next().expect("-o needs a file name")),
+            _ if arg.starts_with("--classpath=") => classpath = env::split_paths(&arg[12..]).collect(),
+            _ => target = Some(arg)
+        }
+    }
+
+    let target = target.expect("usage: cfg Class::method [-o out.dot] [--classpath=path]");
+    let mut parts = target.splitn(2, "::");
+    let (class_name, method) = match (parts.next(), parts.next()) {
+        (Some(class_name), Some(method)) => (class_name.replace('.', "/"), method),
+        _ => panic!("expected Class::method, got {}", target)
+    };
+    let (method_name, descriptor) = match method.find('(') {
+        Some(start) => (&method[..start], Some(&method[start..])),
+        None => (method, None)
+    };
+
+    let store = ByteStore::new();
+    let class = find_class(&store, &classpath, &class_name);
+    let mut dot = String::new();
+    for method in class.methods.iter().filter(|method| method.name == method_name && descriptor.is_none_or(|descriptor| method.descriptor == descriptor)) {
+        match ControlFlowGraph::build(method) {
+            Ok(cfg) => dot.push_str(&cfg.to_dot_with_constants(&format!("{}.{}{}", class_name, method.name, method.descriptor), &class)),
+            Err(err) => eprintln!("{}{}: {}", method.name, method.descriptor, err)
+        }
+    }
+    if dot.is_empty() {
+        eprintln!("no method {} with code in {}", method, class_name);
+        std::process::exit(1);
+    }
+    match out_path {
+        Some(path) => std::fs::write(path, dot).expect("cannot write DOT file"),
+        None => print!("{}", dot)
+    }
+}
+
+/// `hierarchy [--classpath=path] [--dot] [-o file]` reports the subtypes of every class on the
+/// classpath, the classes which are never extended and the interfaces with a single implementation,
+/// or writes the subtype graph as Graphviz DOT.
+fn hierarchy(args: &[String]) {
+    let mut classpath = vec![std::path::PathBuf::from(".")];
+    let mut out_path = None;
+    let mut dot = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => out_path = Some(args.next().expect("-o needs a file name")),
+            "--dot" => dot = true,
+            _ if arg.starts_with("--classpath=") => classpath = env::split_paths(&arg[12..]).collect(),
+            _ => panic!("usage: hierarchy [--classpath=path] [--dot] [-o file]")
+        }
+    }
+
+    let (hierarchy, failures) = ClassHierarchy::from_classpath(&classpath);
+    for (path, err) in failures.iter() {
+        eprintln!("skipped {}: {}", path.display(), err);
+    }
+    let report = if dot { hierarchy.to_dot() } else { hierarchy.to_string() };
+    match out_path {
+        Some(path) => std::fs::write(path, report).expect("cannot write hierarchy report"),
+        None => print!("{}", report)
+    }
+}
+
+/// `analyze [--classpath=path] [--entry=Class::method ...] [-o out.json]` reports the dead code of
+/// the classes on the classpath as json: unreachable basic blocks, exception handlers which can
+/// never catch anything and methods the entry points never call. the `main` methods are the entry
+/// points unless some are given.
+fn analyze(args: &[String]) {
+    let mut classpath = vec![std::path::PathBuf::from(".")];
+    let mut out_path = None;
+    let mut entry_points = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => out_path = Some(args.next().expect("-o needs a file name")),
+            _ if arg.starts_with("--classpath=") => classpath = env::split_paths(&arg[12..]).collect(),
+            _ if arg.starts_with("--entry=") => entry_points.push(arg[8..].parse::<EntryPoint>().unwrap_or_else(|err| panic!("{}", err))),
+            _ => panic!("usage: analyze [--classpath=path] [--entry=Class::method ...] [-o out.json]")
+        }
+    }
+
+    let store = ByteStore::new();
+    let mut classes = Vec::new();
+    for path in scan_classpath(&classpath) {
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => store.keep(bytes),
+            Err(err) => {
+                eprintln!("skipped {}: {}", path.display(), err);
+                continue;
+            }
+        };
+        match ClassFile::parse(bytes) {
+            Ok(class) => classes.push(class),
+            Err(err) => eprintln!("skipped {}: {}", path.display(), err)
+        }
+    }
+
+    let report = DeadCodeReport::analyze(&classes, &entry_points);
+    match out_path {
+        Some(path) => std::fs::write(path, report.to_json()).expect("cannot write analysis report"),
+        None => print!("{}", report.to_json())
+    }
+}
+
+/// parses a size like `-Xmx` does: bytes with an optional `k`, `m` or `g` suffix.
+fn parse_size(size: &str) -> Option<usize> {
+    let (number, factor) = match size.chars().last().map(|c| c.to_ascii_lowercase()) {
+        Some('k') => (&size[..size.len() - 1], 1 << 10),
+        Some('m') => (&size[..size.len() - 1], 1 << 20),
+        Some('g') => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1)
+    };
+
+    number.parse::<usize>().ok()?.checked_mul(factor)
+}
 
-    rt.run();
+/// like the hotspot vm, SIGQUIT (ctrl+\) prints a thread dump on stdout.
+/// SIGINT (ctrl+c) stops the program at the next instruction, so shutdown hooks run and output is
+/// flushed. a second one while shutting down kills the process.
+#[cfg(not(target_family = "wasm"))]
+fn register_signals(rt: &Runtime) {
+    signal_hook::flag::register(signal_hook::consts::SIGQUIT, rt.thread_dump_trigger().flag()).expect("cannot register SIGQUIT handler");
+    let cancellation = rt.cancellation_handle();
+    signal_hook::flag::register_conditional_default(signal_hook::consts::SIGINT, cancellation.flag()).expect("cannot register SIGINT handler");
+    signal_hook::flag::register(signal_hook::consts::SIGINT, cancellation.flag()).expect("cannot register SIGINT handler");
 }